@@ -0,0 +1,212 @@
+// Fan-out benchmark
+// Synthetic load: one publisher sending packets to N players of a channel,
+// through the same paths used by the sessions (send_packet and create_chunks_for_stream)
+//
+// Run with: cargo bench --features bench-internals
+
+use std::{
+    hint::black_box,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rtmp_server::{
+    rtmp::{
+        RtmpPacket, RTMP_CHANNEL_VIDEO, RTMP_CHUNK_SIZE_DEFAULT, RTMP_CHUNK_TYPE_0, RTMP_TYPE_VIDEO,
+    },
+    server::RtmpChannelStatus,
+    session::RtmpSessionPublishStreamStatus,
+};
+use tokio::sync::{broadcast::error::RecvError, Mutex};
+
+/// ID of the publisher session
+const PUBLISHER_ID: u64 = 1;
+
+/// Size of the buffer of packets shared by the players
+const PLAYER_PACKET_BUFFER_SIZE: usize = 256;
+
+/// Size limit of the GOP cache (bytes)
+const GOP_CACHE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Number of packets sent by the publisher on each fan-out scenario
+const FANOUT_PACKETS: usize = 10000;
+
+/// Size of the packets of the fan-out scenarios
+const FANOUT_PACKET_SIZE: usize = 4096;
+
+/// Numbers of players of the fan-out scenarios
+const FANOUT_PLAYERS: &[usize] = &[1, 10, 100, 1000];
+
+/// Payload sizes for the chunking benchmark
+const CHUNKS_PAYLOAD_SIZES: &[usize] = &[128, 4096, 65536, 1024 * 1024];
+
+/// Duration of each chunking benchmark
+const CHUNKS_DURATION: Duration = Duration::from_secs(1);
+
+/// Gets the CPU time used by the process (user + system)
+fn get_cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Duration::ZERO;
+    }
+
+    let to_duration =
+        |t: libc::timeval| Duration::new(t.tv_sec as u64, (t.tv_usec as u32).saturating_mul(1000));
+
+    to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+}
+
+/// Makes a video packet
+///
+/// # Arguments
+///
+/// * `index` - Index of the packet, used as timestamp
+/// * `size` - Size of the payload
+fn make_video_packet(index: usize, size: usize) -> RtmpPacket {
+    let mut packet = RtmpPacket::new_blank();
+
+    packet.header.format = RTMP_CHUNK_TYPE_0;
+    packet.header.channel_id = RTMP_CHANNEL_VIDEO;
+    packet.header.packet_type = RTMP_TYPE_VIDEO;
+    packet.header.timestamp = index as i64;
+    packet.payload = vec![0x27; size];
+    packet.header.length = size;
+
+    packet
+}
+
+/// Runs a fan-out scenario
+///
+/// # Arguments
+///
+/// * `players` - Number of players
+async fn run_fanout_scenario(players: usize) {
+    let mut channel_status = RtmpChannelStatus::new(PLAYER_PACKET_BUFFER_SIZE);
+
+    channel_status.publishing = true;
+    channel_status.publisher_id = Some(PUBLISHER_ID);
+    channel_status.publish_status =
+        Some(Arc::new(Mutex::new(RtmpSessionPublishStreamStatus::new())));
+
+    // Players: receive the packets and serialize them, as the player sessions do
+
+    let mut player_tasks = Vec::new();
+
+    for _ in 0..players {
+        let mut receiver = channel_status.subscribe_packets();
+
+        player_tasks.push(tokio::spawn(async move {
+            let mut received: u64 = 0;
+            let mut lost: u64 = 0;
+
+            loop {
+                match receiver.recv().await {
+                    Ok(packet) => {
+                        black_box(packet.create_chunks_for_stream(1, RTMP_CHUNK_SIZE_DEFAULT));
+                        received += 1;
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        lost += n;
+                    }
+                    Err(RecvError::Closed) => {
+                        return (received, lost);
+                    }
+                }
+            }
+        }));
+    }
+
+    // Publisher
+
+    let start = Instant::now();
+    let start_cpu = get_cpu_time();
+
+    for i in 0..FANOUT_PACKETS {
+        channel_status
+            .send_packet(
+                PUBLISHER_ID,
+                Arc::new(make_video_packet(i, FANOUT_PACKET_SIZE)),
+                false,
+                GOP_CACHE_SIZE,
+            )
+            .await;
+
+        // Wait for the players to catch up, so the measure is not dominated by lost packets
+        while channel_status.packet_sender.len() >= PLAYER_PACKET_BUFFER_SIZE / 2 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    let publish_elapsed = start.elapsed();
+
+    drop(channel_status);
+
+    let mut received: u64 = 0;
+    let mut lost: u64 = 0;
+
+    for task in player_tasks {
+        let (r, l) = task.await.unwrap_or((0, 0));
+
+        received += r;
+        lost += l;
+    }
+
+    let elapsed = start.elapsed();
+    let cpu = get_cpu_time().saturating_sub(start_cpu);
+
+    println!(
+        "fanout/{} players: publish {:.0} packets/s | delivered {:.0} packets/s | lost {} of {} | cpu {:.0} ms ({:.0}%)",
+        players,
+        FANOUT_PACKETS as f64 / publish_elapsed.as_secs_f64(),
+        received as f64 / elapsed.as_secs_f64(),
+        lost,
+        (FANOUT_PACKETS * players),
+        cpu.as_secs_f64() * 1000.0,
+        100.0 * cpu.as_secs_f64() / elapsed.as_secs_f64(),
+    );
+}
+
+/// Measures the serialization of packets into chunks
+///
+/// # Arguments
+///
+/// * `payload_size` - Size of the payload
+fn run_chunks_benchmark(payload_size: usize) {
+    let packet = make_video_packet(0, payload_size);
+
+    let start = Instant::now();
+    let mut iterations: u64 = 0;
+
+    while start.elapsed() < CHUNKS_DURATION {
+        for _ in 0..100 {
+            black_box(packet.create_chunks_for_stream(1, RTMP_CHUNK_SIZE_DEFAULT));
+        }
+
+        iterations += 100;
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!(
+        "create_chunks_for_stream/{} bytes: {:.0} packets/s | {:.1} MB/s",
+        payload_size,
+        iterations as f64 / elapsed,
+        (iterations as f64 * payload_size as f64) / elapsed / (1024.0 * 1024.0),
+    );
+}
+
+fn main() {
+    for payload_size in CHUNKS_PAYLOAD_SIZES {
+        run_chunks_benchmark(*payload_size);
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Could not create the runtime");
+
+    for players in FANOUT_PLAYERS {
+        runtime.block_on(run_fanout_scenario(*players));
+    }
+}
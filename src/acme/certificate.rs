@@ -0,0 +1,274 @@
+// Certificate provisioning through ACME
+
+use std::{collections::HashMap, sync::Arc};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+
+use crate::{log::Logger, log_debug, log_info};
+
+use super::{
+    encode_pem, generate_certificate_key, get_file_age_days, get_json_string,
+    make_certificate_signing_request, spawn_http_challenge_responder, write_storage_file,
+    AcmeAccountKey, AcmeChallengeTokens, AcmeClient, AcmeConfiguration,
+};
+
+/// Checks if the ACME certificate must be obtained or renewed
+///
+/// # Arguments
+///
+/// * `config` - The ACME configuration
+pub async fn acme_certificate_needs_renewal(config: &AcmeConfiguration) -> bool {
+    if get_file_age_days(&config.get_certificate_key_path())
+        .await
+        .is_none()
+    {
+        return true;
+    }
+
+    match get_file_age_days(&config.get_certificate_path()).await {
+        Some(age) => age >= config.renew_days as i64,
+        None => true,
+    }
+}
+
+/// Obtains a certificate from the ACME server,
+/// storing the certificate and its key in the storage folder
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The ACME configuration
+pub async fn obtain_acme_certificate(
+    logger: Arc<Logger>,
+    config: &AcmeConfiguration,
+) -> Result<(), String> {
+    log_info!(
+        logger,
+        format!(
+            "Requesting certificate for {} from {}",
+            config.domains.join(", "),
+            &config.directory_url
+        )
+    );
+
+    let account_key = AcmeAccountKey::load_or_create(&config.get_account_key_path()).await?;
+
+    let mut client = AcmeClient::new(&config.directory_url, account_key).await?;
+
+    client.register_account(&config.email).await?;
+
+    log_debug!(logger, "ACME account ready");
+
+    // The responder only runs while the challenges are being validated
+
+    let tokens: AcmeChallengeTokens = Arc::new(std::sync::Mutex::new(HashMap::new()));
+
+    let cancel_responder = spawn_http_challenge_responder(
+        logger.clone(),
+        &config.get_http_listen_addr(),
+        tokens.clone(),
+    )
+    .await?;
+
+    let res = run_acme_order(&logger, config, &mut client, &tokens).await;
+
+    _ = cancel_responder.send(()).await;
+
+    let (certificate_pem, key_pem) = res?;
+
+    // Store the key first, so the certificate never refers to an older key
+
+    write_storage_file(&config.get_certificate_key_path(), &key_pem, true).await?;
+    write_storage_file(&config.get_certificate_path(), &certificate_pem, false).await?;
+
+    log_info!(
+        logger,
+        format!("Certificate stored in {}", config.get_certificate_path())
+    );
+
+    Ok(())
+}
+
+/// Creates an order, completes the challenges and downloads the certificate
+///
+/// # Return value
+///
+/// Returns a tuple with the certificate chain (PEM) and the private key (PEM)
+async fn run_acme_order(
+    logger: &Logger,
+    config: &AcmeConfiguration,
+    client: &mut AcmeClient,
+    tokens: &AcmeChallengeTokens,
+) -> Result<(String, String), String> {
+    // Create order
+
+    let identifiers: Vec<Value> = config
+        .domains
+        .iter()
+        .map(|d| json!({ "type": "dns", "value": d }))
+        .collect();
+
+    let new_order_url = client.new_order_url();
+
+    let order_response = client
+        .post(&new_order_url, Some(&json!({ "identifiers": identifiers })))
+        .await?;
+
+    let order_url = match &order_response.location {
+        Some(l) => l.clone(),
+        None => {
+            return Err("Invalid response from the ACME server: Missing order URL".to_string());
+        }
+    };
+
+    let order = order_response.json()?;
+
+    // Complete authorizations
+
+    let authorizations: Vec<String> = match order.get("authorizations").and_then(|a| a.as_array()) {
+        Some(a) => a
+            .iter()
+            .filter_map(|u| u.as_str().map(|s| s.to_string()))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    for authorization_url in authorizations {
+        complete_authorization(logger, client, tokens, &authorization_url).await?;
+    }
+
+    // Finalize
+
+    let (key_pair, key_pkcs8) = match generate_certificate_key() {
+        Some(k) => k,
+        None => {
+            return Err("Could not generate certificate key".to_string());
+        }
+    };
+
+    let csr = match make_certificate_signing_request(&key_pair, &config.domains) {
+        Some(c) => c,
+        None => {
+            return Err("Could not generate certificate signing request".to_string());
+        }
+    };
+
+    let order = client.poll(&order_url, &["pending"]).await?;
+
+    if get_json_string(&order, "status")? != "ready" {
+        return Err(format!(
+            "ACME order is not ready: {}",
+            get_json_string(&order, "status")?
+        ));
+    }
+
+    let finalize_url = get_json_string(&order, "finalize")?;
+
+    client
+        .post(
+            &finalize_url,
+            Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(&csr) })),
+        )
+        .await?;
+
+    let order = client.poll(&order_url, &["ready", "processing"]).await?;
+
+    if get_json_string(&order, "status")? != "valid" {
+        return Err(format!(
+            "ACME order failed: {}",
+            get_json_string(&order, "status")?
+        ));
+    }
+
+    // Download certificate
+
+    let certificate_url = get_json_string(&order, "certificate")?;
+
+    let certificate_response = client.post(&certificate_url, None).await?;
+
+    let certificate_pem = String::from_utf8_lossy(&certificate_response.body).to_string();
+
+    if !certificate_pem.contains("-----BEGIN CERTIFICATE-----") {
+        return Err("Invalid certificate received from the ACME server".to_string());
+    }
+
+    Ok((certificate_pem, encode_pem("PRIVATE KEY", &key_pkcs8)))
+}
+
+/// Completes an authorization using the HTTP-01 challenge
+async fn complete_authorization(
+    logger: &Logger,
+    client: &mut AcmeClient,
+    tokens: &AcmeChallengeTokens,
+    authorization_url: &str,
+) -> Result<(), String> {
+    let authorization = client.post(authorization_url, None).await?.json()?;
+
+    let domain = authorization
+        .get("identifier")
+        .and_then(|i| i.get("value"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    if get_json_string(&authorization, "status")? == "valid" {
+        log_debug!(
+            logger,
+            format!("Authorization already valid for {}", domain)
+        );
+        return Ok(());
+    }
+
+    let challenge = match authorization
+        .get("challenges")
+        .and_then(|c| c.as_array())
+        .and_then(|c| {
+            c.iter()
+                .find(|ch| ch.get("type").and_then(|t| t.as_str()) == Some("http-01"))
+        }) {
+        Some(c) => c.clone(),
+        None => {
+            return Err(format!("No HTTP-01 challenge available for {}", domain));
+        }
+    };
+
+    let token = get_json_string(&challenge, "token")?;
+    let challenge_url = get_json_string(&challenge, "url")?;
+
+    tokens.lock().unwrap().insert(
+        token.clone(),
+        client.account_key().get_key_authorization(&token),
+    );
+
+    log_debug!(
+        logger,
+        format!("Validating HTTP-01 challenge for {}", domain)
+    );
+
+    client.post(&challenge_url, Some(&json!({}))).await?;
+
+    let authorization = client.poll(authorization_url, &["pending"]).await?;
+
+    tokens.lock().unwrap().remove(&token);
+
+    let status = get_json_string(&authorization, "status")?;
+
+    if status != "valid" {
+        return Err(format!(
+            "Authorization failed for {}: {}",
+            domain,
+            authorization
+                .get("challenges")
+                .and_then(|c| c.as_array())
+                .and_then(|c| c.iter().find_map(|ch| ch.get("error")))
+                .and_then(|e| e.get("detail"))
+                .and_then(|d| d.as_str())
+                .unwrap_or(&status)
+        ));
+    }
+
+    log_debug!(logger, format!("Authorization valid for {}", domain));
+
+    Ok(())
+}
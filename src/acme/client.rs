@@ -0,0 +1,277 @@
+// ACME protocol client (RFC 8555)
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::AcmeAccountKey;
+
+/// Max number of attempts to poll the status of an object
+const ACME_POLL_MAX_ATTEMPTS: u32 = 60;
+
+/// Interval to poll the status of an object (seconds)
+const ACME_POLL_INTERVAL_SECONDS: u64 = 2;
+
+/// Error type sent by the server when the nonce must be renewed
+const ACME_ERROR_BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+/// Response of the ACME server
+pub struct AcmeResponse {
+    /// Location header
+    pub location: Option<String>,
+
+    /// Response body
+    pub body: Vec<u8>,
+}
+
+impl AcmeResponse {
+    /// Parses the body as JSON
+    pub fn json(&self) -> Result<Value, String> {
+        serde_json::from_slice(&self.body)
+            .map_err(|e| format!("Invalid response from the ACME server: {}", e))
+    }
+}
+
+/// Client for the ACME server
+pub struct AcmeClient {
+    /// HTTP client
+    http: reqwest::Client,
+
+    /// Account key
+    account_key: AcmeAccountKey,
+
+    /// URL to get new nonces
+    new_nonce_url: String,
+
+    /// URL to create accounts
+    new_account_url: String,
+
+    /// URL to create orders
+    new_order_url: String,
+
+    /// Account URL (once registered)
+    account_url: Option<String>,
+
+    /// Nonce for the next request
+    nonce: Option<String>,
+}
+
+/// Gets a string field of a JSON object
+///
+/// # Arguments
+///
+/// * `value` - The JSON object
+/// * `field` - The field name
+pub fn get_json_string(value: &Value, field: &str) -> Result<String, String> {
+    match value.get(field).and_then(|v| v.as_str()) {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!(
+            "Invalid response from the ACME server: Missing field {}",
+            field
+        )),
+    }
+}
+
+impl AcmeClient {
+    /// Creates a new client, loading the directory of the ACME server
+    ///
+    /// # Arguments
+    ///
+    /// * `directory_url` - URL of the ACME directory
+    /// * `account_key` - The account key
+    pub async fn new(
+        directory_url: &str,
+        account_key: AcmeAccountKey,
+    ) -> Result<AcmeClient, String> {
+        let http = reqwest::Client::new();
+
+        let response = http
+            .get(directory_url)
+            .send()
+            .await
+            .map_err(|e| format!("Could not load ACME directory: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Could not load ACME directory: Status code {}",
+                response.status().as_u16()
+            ));
+        }
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Could not load ACME directory: {}", e))?;
+
+        let directory: Value =
+            serde_json::from_slice(&body).map_err(|e| format!("Invalid ACME directory: {}", e))?;
+
+        Ok(AcmeClient {
+            http,
+            account_key,
+            new_nonce_url: get_json_string(&directory, "newNonce")?,
+            new_account_url: get_json_string(&directory, "newAccount")?,
+            new_order_url: get_json_string(&directory, "newOrder")?,
+            account_url: None,
+            nonce: None,
+        })
+    }
+
+    /// Gets the account key
+    pub fn account_key(&self) -> &AcmeAccountKey {
+        &self.account_key
+    }
+
+    /// Gets the URL to create orders
+    pub fn new_order_url(&self) -> String {
+        self.new_order_url.clone()
+    }
+
+    /// Gets a nonce for the next request
+    async fn get_nonce(&mut self) -> Result<String, String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+
+        let response = self
+            .http
+            .head(&self.new_nonce_url)
+            .send()
+            .await
+            .map_err(|e| format!("Could not get ACME nonce: {}", e))?;
+
+        match response.headers().get("replay-nonce") {
+            Some(n) => Ok(n.to_str().unwrap_or("").to_string()),
+            None => Err("Could not get ACME nonce: Missing Replay-Nonce header".to_string()),
+        }
+    }
+
+    /// Sends a signed request to the ACME server
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL
+    /// * `payload` - The payload, or None for POST-as-GET requests
+    pub async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<AcmeResponse, String> {
+        let mut retried_nonce = false;
+
+        loop {
+            let nonce = self.get_nonce().await?;
+
+            let body =
+                self.account_key
+                    .sign_request(url, &nonce, self.account_url.as_deref(), payload)?;
+
+            let response = self
+                .http
+                .post(url)
+                .header("Content-Type", "application/jose+json")
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| format!("ACME request failed: {}", e))?;
+
+            if let Some(n) = response.headers().get("replay-nonce") {
+                self.nonce = n.to_str().ok().map(|n| n.to_string());
+            }
+
+            let status = response.status();
+
+            let location = response
+                .headers()
+                .get("location")
+                .and_then(|l| l.to_str().ok())
+                .map(|l| l.to_string());
+
+            let body = response
+                .bytes()
+                .await
+                .map_err(|e| format!("ACME request failed: {}", e))?
+                .to_vec();
+
+            let response = AcmeResponse { location, body };
+
+            if status.is_success() {
+                return Ok(response);
+            }
+
+            // Error (problem document)
+
+            let problem = response.json().unwrap_or(Value::Null);
+
+            let problem_type = problem.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+            if problem_type == ACME_ERROR_BAD_NONCE && !retried_nonce {
+                retried_nonce = true;
+                continue;
+            }
+
+            return Err(format!(
+                "ACME request failed: Status code {}: {}",
+                status.as_u16(),
+                problem
+                    .get("detail")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or(problem_type)
+            ));
+        }
+    }
+
+    /// Registers the account (or finds the existing one for the key)
+    ///
+    /// # Arguments
+    ///
+    /// * `email` - Contact email (can be empty)
+    pub async fn register_account(&mut self, email: &str) -> Result<(), String> {
+        let payload = if email.is_empty() {
+            json!({ "termsOfServiceAgreed": true })
+        } else {
+            json!({
+                "termsOfServiceAgreed": true,
+                "contact": [format!("mailto:{}", email)],
+            })
+        };
+
+        let url = self.new_account_url.clone();
+
+        let response = self.post(&url, Some(&payload)).await?;
+
+        match response.location {
+            Some(l) => {
+                self.account_url = Some(l);
+                Ok(())
+            }
+            None => Err("Could not register ACME account: Missing account URL".to_string()),
+        }
+    }
+
+    /// Polls an object until its status is not pending
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the object
+    /// * `pending_statuses` - Statuses considered pending
+    ///
+    /// # Return value
+    ///
+    /// Returns the object once it is not pending
+    pub async fn poll(&mut self, url: &str, pending_statuses: &[&str]) -> Result<Value, String> {
+        for _ in 0..ACME_POLL_MAX_ATTEMPTS {
+            let object = self.post(url, None).await?.json()?;
+
+            let status = get_json_string(&object, "status")?;
+
+            if !pending_statuses.contains(&status.as_str()) {
+                return Ok(object);
+            }
+
+            tokio::time::sleep(Duration::from_secs(ACME_POLL_INTERVAL_SECONDS)).await;
+        }
+
+        Err(format!("Timed out waiting for ACME object: {}", url))
+    }
+}
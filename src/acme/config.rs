@@ -0,0 +1,128 @@
+// ACME feature configuration
+
+use std::path::Path;
+
+use crate::{
+    log::Logger,
+    log_error,
+    utils::{get_env_string, get_env_string_list, get_env_u32},
+};
+
+/// Default ACME directory (Let's Encrypt)
+const ACME_DIRECTORY_URL_DEFAULT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// Default port for the HTTP-01 challenge responder
+const ACME_HTTP_PORT_DEFAULT: u32 = 80;
+
+/// Default number of days after issuance to renew the certificate
+const ACME_RENEW_DAYS_DEFAULT: u32 = 60;
+
+/// ACME configuration
+#[cfg_attr(not(feature = "tls"), allow(dead_code))]
+#[derive(Clone)]
+pub struct AcmeConfiguration {
+    /// Domains to request the certificate for (empty to disable ACME)
+    pub domains: Vec<String>,
+
+    /// Contact email for the ACME account
+    pub email: String,
+
+    /// URL of the ACME directory
+    pub directory_url: String,
+
+    /// Path of the folder to store the account key and the certificates
+    pub storage_path: String,
+
+    /// Port to listen for HTTP-01 challenges
+    pub http_port: u32,
+
+    /// Bind address to listen for HTTP-01 challenges
+    pub http_bind_address: String,
+
+    /// Number of days after issuance to renew the certificate
+    pub renew_days: u32,
+}
+
+impl AcmeConfiguration {
+    /// Loads ACME feature configuration
+    /// from environment variables
+    pub fn load_from_env(logger: &Logger) -> Result<AcmeConfiguration, ()> {
+        let domains: Vec<String> = get_env_string_list("ACME_DOMAINS", "")
+            .iter()
+            .map(|d| d.to_lowercase())
+            .collect();
+
+        let email = get_env_string("ACME_EMAIL", "");
+        let directory_url = get_env_string("ACME_DIRECTORY_URL", ACME_DIRECTORY_URL_DEFAULT);
+        let storage_path = get_env_string("ACME_STORAGE_PATH", "acme");
+
+        let http_port = get_env_u32("ACME_HTTP_PORT", ACME_HTTP_PORT_DEFAULT);
+
+        if http_port == 0 || http_port > 65535 {
+            log_error!(
+                logger,
+                format!("ACME_HTTP_PORT has an invalid value: {}", http_port)
+            );
+            return Err(());
+        }
+
+        let http_bind_address = get_env_string("ACME_HTTP_BIND_ADDRESS", "0.0.0.0");
+
+        let renew_days = get_env_u32("ACME_RENEW_DAYS", ACME_RENEW_DAYS_DEFAULT);
+
+        if renew_days == 0 {
+            log_error!(logger, "ACME_RENEW_DAYS must be greater than 0");
+            return Err(());
+        }
+
+        Ok(AcmeConfiguration {
+            domains,
+            email,
+            directory_url,
+            storage_path,
+            http_port,
+            http_bind_address,
+            renew_days,
+        })
+    }
+
+    /// Checks if ACME is enabled (at least a domain must be present)
+    pub fn is_enabled(&self) -> bool {
+        !self.domains.is_empty()
+    }
+
+    /// Gets the path of the account key
+    #[cfg(feature = "tls")]
+    pub fn get_account_key_path(&self) -> String {
+        Path::new(&self.storage_path)
+            .join("account.key")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Gets the path of the certificate
+    pub fn get_certificate_path(&self) -> String {
+        Path::new(&self.storage_path)
+            .join("certificate.pem")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Gets the path of the certificate private key
+    pub fn get_certificate_key_path(&self) -> String {
+        Path::new(&self.storage_path)
+            .join("certificate.key")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Gets the address to listen for HTTP-01 challenges
+    #[cfg(feature = "tls")]
+    pub fn get_http_listen_addr(&self) -> String {
+        if self.http_bind_address.contains(':') && !self.http_bind_address.starts_with('[') {
+            format!("[{}]:{}", self.http_bind_address, self.http_port)
+        } else {
+            format!("{}:{}", self.http_bind_address, self.http_port)
+        }
+    }
+}
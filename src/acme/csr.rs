@@ -0,0 +1,162 @@
+// Certificate signing request (CSR) generation
+
+use aws_lc_rs::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+
+/// OID 2.5.4.3 (Common name)
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// OID 1.2.840.10045.2.1 (EC public key)
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+
+/// OID 1.2.840.10045.3.1.7 (P-256 curve)
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+
+/// OID 1.2.840.113549.1.9.14 (Extension request)
+const OID_EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+
+/// OID 2.5.29.17 (Subject alternative name)
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// OID 1.2.840.10045.4.3.2 (ECDSA with SHA-256)
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+/// Encodes a DER element
+///
+/// # Arguments
+///
+/// * `tag` - The tag of the element
+/// * `content` - The content of the element
+fn der_element(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut res = vec![tag];
+    let len = content.len();
+
+    if len < 0x80 {
+        res.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .iter()
+            .skip_while(|b| **b == 0)
+            .copied()
+            .collect();
+
+        res.push(0x80 | (len_bytes.len() as u8));
+        res.extend_from_slice(&len_bytes);
+    }
+
+    res.extend_from_slice(content);
+    res
+}
+
+/// Encodes a DER sequence
+fn der_sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    der_element(0x30, &items.concat())
+}
+
+/// Encodes a DER set
+fn der_set(items: &[Vec<u8>]) -> Vec<u8> {
+    der_element(0x31, &items.concat())
+}
+
+/// Encodes a DER object identifier
+fn der_oid(oid: &[u8]) -> Vec<u8> {
+    der_element(0x06, oid)
+}
+
+/// Encodes a DER bit string (with no unused bits)
+fn der_bit_string(bits: &[u8]) -> Vec<u8> {
+    der_element(0x03, &[&[0x00], bits].concat())
+}
+
+/// Generates a certificate signing request, in DER format
+///
+/// # Arguments
+///
+/// * `key_pair` - The certificate key pair (ECDSA P-256 SHA-256 ASN.1)
+/// * `domains` - The domains. The first one is used as common name.
+///
+/// # Return value
+///
+/// Returns the CSR, or None if the request could not be signed
+pub fn make_certificate_signing_request(
+    key_pair: &EcdsaKeyPair,
+    domains: &[String],
+) -> Option<Vec<u8>> {
+    let common_name = domains.first()?;
+
+    let subject = der_sequence(&[der_set(&[der_sequence(&[
+        der_oid(OID_COMMON_NAME),
+        der_element(0x0c, common_name.as_bytes()),
+    ])])]);
+
+    let public_key_info = der_sequence(&[
+        der_sequence(&[der_oid(OID_EC_PUBLIC_KEY), der_oid(OID_PRIME256V1)]),
+        der_bit_string(key_pair.public_key().as_ref()),
+    ]);
+
+    let alt_names: Vec<Vec<u8>> = domains
+        .iter()
+        .map(|d| der_element(0x82, d.as_bytes()))
+        .collect();
+
+    let extensions = der_sequence(&[der_sequence(&[
+        der_oid(OID_SUBJECT_ALT_NAME),
+        der_element(0x04, &der_sequence(&alt_names)),
+    ])]);
+
+    let attributes = der_element(
+        0xa0,
+        &der_sequence(&[der_oid(OID_EXTENSION_REQUEST), der_set(&[extensions])]),
+    );
+
+    let request_info = der_sequence(&[
+        der_element(0x02, &[0x00]), // Version
+        subject,
+        public_key_info,
+        attributes,
+    ]);
+
+    let signature = key_pair.sign(&SystemRandom::new(), &request_info).ok()?;
+
+    Some(der_sequence(&[
+        request_info,
+        der_sequence(&[der_oid(OID_ECDSA_WITH_SHA256)]),
+        der_bit_string(signature.as_ref()),
+    ]))
+}
+
+/// Generates a new key pair for a certificate
+///
+/// # Return value
+///
+/// Returns a tuple with the key pair and the private key (PKCS#8 DER),
+/// or None if the key could not be generated
+pub fn generate_certificate_key() -> Option<(EcdsaKeyPair, Vec<u8>)> {
+    let pkcs8 =
+        EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new()).ok()?;
+
+    let key_pair =
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref()).ok()?;
+
+    Some((key_pair, pkcs8.as_ref().to_vec()))
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_der_element() {
+        assert_eq!(der_element(0x04, &[1, 2]), vec![0x04, 0x02, 1, 2]);
+
+        let long = der_element(0x04, &[0u8; 300]);
+
+        assert_eq!(&long[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        assert_eq!(long.len(), 304);
+    }
+}
@@ -0,0 +1,152 @@
+// Responder for ACME HTTP-01 challenges
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::Sender,
+};
+
+use crate::{log::Logger, log_debug, log_info, server::AcceptBackoff};
+
+/// Path prefix of the HTTP-01 challenges
+const ACME_CHALLENGE_PATH_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// Max size of a challenge request (bytes)
+const ACME_CHALLENGE_REQUEST_MAX_SIZE: usize = 4096;
+
+/// Timeout to read a challenge request (seconds)
+const ACME_CHALLENGE_REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+/// Pending challenges: token -> key authorization
+pub type AcmeChallengeTokens = Arc<Mutex<HashMap<String, String>>>;
+
+/// Starts the responder for HTTP-01 challenges
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `listen_addr` - The address to listen on
+/// * `tokens` - The pending challenges
+///
+/// # Return value
+///
+/// Returns a sender to stop the responder
+pub async fn spawn_http_challenge_responder(
+    logger: Arc<Logger>,
+    listen_addr: &str,
+    tokens: AcmeChallengeTokens,
+) -> Result<Sender<()>, String> {
+    let listener = TcpListener::bind(listen_addr)
+        .await
+        .map_err(|e| format!("Could not listen on {}: {}", listen_addr, e))?;
+
+    log_info!(
+        logger,
+        format!("Listening for HTTP-01 challenges on {}", listen_addr)
+    );
+
+    let (cancel_sender, mut cancel_receiver) = tokio::sync::mpsc::channel::<()>(1);
+
+    tokio::spawn(async move {
+        let mut backoff = AcceptBackoff::new();
+
+        loop {
+            tokio::select! {
+                accept_res = listener.accept() => {
+                    match accept_res {
+                        Ok((connection, _)) => {
+                            backoff.reset();
+
+                            let logger = logger.clone();
+                            let tokens = tokens.clone();
+
+                            tokio::spawn(async move {
+                                handle_challenge_request(&logger, connection, &tokens).await;
+                            });
+                        }
+                        Err(e) => {
+                            if !backoff.handle_error(&logger, &e).await {
+                                return;
+                            }
+                        }
+                    }
+                }
+                _ = cancel_receiver.recv() => {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(cancel_sender)
+}
+
+/// Handles a request for a HTTP-01 challenge
+async fn handle_challenge_request(
+    logger: &Logger,
+    mut connection: TcpStream,
+    tokens: &AcmeChallengeTokens,
+) {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    // Read the request head
+
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > ACME_CHALLENGE_REQUEST_MAX_SIZE {
+            return;
+        }
+
+        let read_res = tokio::time::timeout(
+            Duration::from_secs(ACME_CHALLENGE_REQUEST_TIMEOUT_SECONDS),
+            connection.read(&mut chunk),
+        )
+        .await;
+
+        match read_res {
+            Ok(Ok(n)) if n > 0 => {
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            _ => {
+                return;
+            }
+        }
+    }
+
+    // GET /.well-known/acme-challenge/TOKEN HTTP/1.1
+
+    let head = String::from_utf8_lossy(&buf);
+    let request_line: Vec<&str> = head.lines().next().unwrap_or("").split(' ').collect();
+
+    let key_authorization = match request_line.as_slice() {
+        ["GET", path, _] => match path.strip_prefix(ACME_CHALLENGE_PATH_PREFIX) {
+            Some(token) => tokens.lock().unwrap().get(token).cloned(),
+            None => None,
+        },
+        _ => None,
+    };
+
+    let response = match key_authorization {
+        Some(k) => {
+            log_debug!(logger, "Responded to HTTP-01 challenge");
+
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                k.len(),
+                k
+            )
+        }
+        None => {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        }
+    };
+
+    _ = connection.write_all(response.as_bytes()).await;
+    _ = connection.shutdown().await;
+}
@@ -0,0 +1,150 @@
+// ACME account key and request signing (JWS)
+
+use aws_lc_rs::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::{decode_pem, encode_pem, write_storage_file};
+
+/// Key of the ACME account
+pub struct AcmeAccountKey {
+    /// The key pair (ECDSA P-256)
+    key_pair: EcdsaKeyPair,
+
+    /// Public key as JWK
+    jwk: Value,
+
+    /// Thumbprint of the JWK (RFC 7638)
+    thumbprint: String,
+}
+
+impl AcmeAccountKey {
+    /// Loads the account key from a file, or creates it if it does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the key file
+    pub async fn load_or_create(path: &str) -> Result<AcmeAccountKey, String> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(pem) => {
+                let pkcs8 = decode_pem("PRIVATE KEY", &pem)
+                    .ok_or_else(|| format!("Invalid account key: {}", path))?;
+
+                AcmeAccountKey::from_pkcs8(&pkcs8)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    &SystemRandom::new(),
+                )
+                .map_err(|_| "Could not generate account key".to_string())?;
+
+                write_storage_file(path, &encode_pem("PRIVATE KEY", pkcs8.as_ref()), true).await?;
+
+                AcmeAccountKey::from_pkcs8(pkcs8.as_ref())
+            }
+            Err(e) => Err(format!("Could not read account key {}: {}", path, e)),
+        }
+    }
+
+    /// Creates the account key from a PKCS#8 private key
+    fn from_pkcs8(pkcs8: &[u8]) -> Result<AcmeAccountKey, String> {
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8)
+            .map_err(|e| format!("Invalid account key: {}", e))?;
+
+        // Uncompressed point: 0x04 | X (32 bytes) | Y (32 bytes)
+        let public_key = key_pair.public_key().as_ref();
+
+        if public_key.len() != 65 {
+            return Err("Invalid account key: Unexpected public key size".to_string());
+        }
+
+        let x = URL_SAFE_NO_PAD.encode(&public_key[1..33]);
+        let y = URL_SAFE_NO_PAD.encode(&public_key[33..65]);
+
+        // Members must be in lexicographic order, without spaces
+        let canonical_jwk = format!(
+            "{{\"crv\":\"P-256\",\"kty\":\"EC\",\"x\":\"{}\",\"y\":\"{}\"}}",
+            x, y
+        );
+
+        let thumbprint = URL_SAFE_NO_PAD.encode(Sha256::digest(canonical_jwk.as_bytes()));
+
+        Ok(AcmeAccountKey {
+            key_pair,
+            jwk: json!({
+                "crv": "P-256",
+                "kty": "EC",
+                "x": x,
+                "y": y,
+            }),
+            thumbprint,
+        })
+    }
+
+    /// Gets the key authorization for a challenge token
+    pub fn get_key_authorization(&self, token: &str) -> String {
+        format!("{}.{}", token, self.thumbprint)
+    }
+
+    /// Signs a request for the ACME server
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the request
+    /// * `nonce` - The replay nonce
+    /// * `account_url` - The account URL, or None to include the public key (new account)
+    /// * `payload` - The payload, or None for POST-as-GET requests
+    ///
+    /// # Return value
+    ///
+    /// Returns the request body (flattened JWS JSON)
+    pub fn sign_request(
+        &self,
+        url: &str,
+        nonce: &str,
+        account_url: Option<&str>,
+        payload: Option<&Value>,
+    ) -> Result<String, String> {
+        let protected = match account_url {
+            Some(kid) => json!({
+                "alg": "ES256",
+                "kid": kid,
+                "nonce": nonce,
+                "url": url,
+            }),
+            None => json!({
+                "alg": "ES256",
+                "jwk": self.jwk,
+                "nonce": nonce,
+                "url": url,
+            }),
+        };
+
+        let protected_b64 = URL_SAFE_NO_PAD.encode(protected.to_string());
+
+        let payload_b64 = match payload {
+            Some(p) => URL_SAFE_NO_PAD.encode(p.to_string()),
+            None => "".to_string(),
+        };
+
+        let signature = self
+            .key_pair
+            .sign(
+                &SystemRandom::new(),
+                format!("{}.{}", protected_b64, payload_b64).as_bytes(),
+            )
+            .map_err(|_| "Could not sign the request".to_string())?;
+
+        Ok(json!({
+            "protected": protected_b64,
+            "payload": payload_b64,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        })
+        .to_string())
+    }
+}
@@ -0,0 +1,29 @@
+// ACME feature (automatic certificates)
+
+#[cfg(feature = "tls")]
+mod certificate;
+#[cfg(feature = "tls")]
+mod client;
+mod config;
+#[cfg(feature = "tls")]
+mod csr;
+#[cfg(feature = "tls")]
+mod http_challenge;
+#[cfg(feature = "tls")]
+mod jws;
+#[cfg(feature = "tls")]
+mod storage;
+
+#[cfg(feature = "tls")]
+pub use certificate::*;
+#[cfg(feature = "tls")]
+pub use client::*;
+pub use config::*;
+#[cfg(feature = "tls")]
+pub use csr::*;
+#[cfg(feature = "tls")]
+pub use http_challenge::*;
+#[cfg(feature = "tls")]
+pub use jws::*;
+#[cfg(feature = "tls")]
+pub use storage::*;
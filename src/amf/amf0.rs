@@ -3,7 +3,7 @@
 use byteorder::{BigEndian, ByteOrder};
 use std::collections::HashMap;
 
-use super::AMFDecodingCursor;
+use super::{AMFDecodingCursor, AMFDecodingError, AMF_MAX_ELEMENTS, AMF_MAX_STRING_LENGTH};
 
 const AMF0_TYPE_NUMBER: u8 = 0x00;
 const AMF0_TYPE_BOOL: u8 = 0x01;
@@ -395,7 +395,10 @@ impl AMF0Value {
     // Deciding functions:
 
     /// Reads AMF0 value from buffer
-    pub fn read(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<AMF0Value, ()> {
+    pub fn read(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<AMF0Value, AMFDecodingError> {
         let amf0_type = cursor.read_byte(buffer)?;
 
         match amf0_type {
@@ -444,83 +447,107 @@ impl AMF0Value {
     }
 
     /// Reads number from buffer
-    pub fn read_number(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<f64, ()> {
+    pub fn read_number(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<f64, AMFDecodingError> {
         let buf = cursor.read(buffer, 8)?;
 
         if buf.len() < 8 {
-            return Err(());
+            return Err(AMFDecodingError::UnexpectedEnd);
         }
 
         Ok(BigEndian::read_f64(buf))
     }
 
     /// Reads number from buffer
-    pub fn read_date(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<f64, ()> {
+    pub fn read_date(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<f64, AMFDecodingError> {
         cursor.skip(2)?; // Skip prefix
         Self::read_number(cursor, buffer)
     }
 
     /// Reads boolean from buffer
-    pub fn read_bool(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<bool, ()> {
+    pub fn read_bool(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<bool, AMFDecodingError> {
         let b = cursor.read_byte(buffer)?;
         Ok(b != 0x00)
     }
 
     /// Reads u16 (big endian)
-    pub fn read_u16_be(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<u16, ()> {
+    pub fn read_u16_be(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<u16, AMFDecodingError> {
         let buf = cursor.read(buffer, 2)?;
 
         if buf.len() < 2 {
-            return Err(());
+            return Err(AMFDecodingError::UnexpectedEnd);
         }
 
         Ok(BigEndian::read_u16(buf))
     }
 
     /// Reads string from buffer
-    pub fn read_string(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<String, ()> {
+    pub fn read_string(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<String, AMFDecodingError> {
         let l = Self::read_u16_be(cursor, buffer)?;
 
-        let str_bytes = cursor.read(buffer, l as usize)?;
-
-        let str_res = String::from_utf8(str_bytes.to_vec());
+        Self::read_utf8(cursor, buffer, l as usize)
+    }
 
-        match str_res {
-            Ok(s) => Ok(s),
-            Err(_) => Err(()),
+    /// Reads UTF-8 string bytes
+    fn read_utf8(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+        len: usize,
+    ) -> Result<String, AMFDecodingError> {
+        if len > AMF_MAX_STRING_LENGTH {
+            return Err(AMFDecodingError::StringTooLong(len));
         }
+
+        let str_bytes = cursor.read(buffer, len)?;
+
+        String::from_utf8(str_bytes.to_vec()).map_err(|_| AMFDecodingError::InvalidString)
     }
 
     /// Reads u32 (big endian)
-    pub fn read_u32_be(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<u32, ()> {
+    pub fn read_u32_be(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<u32, AMFDecodingError> {
         let buf = cursor.read(buffer, 4)?;
 
         if buf.len() < 4 {
-            return Err(());
+            return Err(AMFDecodingError::UnexpectedEnd);
         }
 
         Ok(BigEndian::read_u32(buf))
     }
 
     /// Reads long string from buffer
-    pub fn read_long_string(cursor: &mut AMFDecodingCursor, buffer: &[u8]) -> Result<String, ()> {
+    pub fn read_long_string(
+        cursor: &mut AMFDecodingCursor,
+        buffer: &[u8],
+    ) -> Result<String, AMFDecodingError> {
         let l = Self::read_u32_be(cursor, buffer)?;
 
-        let str_bytes = cursor.read(buffer, l as usize)?;
-
-        let str_res = String::from_utf8(str_bytes.to_vec());
-
-        match str_res {
-            Ok(s) => Ok(s),
-            Err(_) => Err(()),
-        }
+        Self::read_utf8(cursor, buffer, l as usize)
     }
 
     /// Reads object from buffer
     pub fn read_object(
         cursor: &mut AMFDecodingCursor,
         buffer: &[u8],
-    ) -> Result<HashMap<String, AMF0Value>, ()> {
+    ) -> Result<HashMap<String, AMF0Value>, AMFDecodingError> {
+        cursor.enter_nested()?;
+
         let mut o: HashMap<String, AMF0Value> = HashMap::new();
 
         while !cursor.ended() {
@@ -532,11 +559,17 @@ impl AMF0Value {
                 break;
             }
 
+            if o.len() >= AMF_MAX_ELEMENTS {
+                return Err(AMFDecodingError::TooManyElements(o.len() + 1));
+            }
+
             let prop_value = Self::read(cursor, buffer)?;
 
             o.insert(prop_name, prop_value);
         }
 
+        cursor.exit_nested();
+
         Ok(o)
     }
 
@@ -544,7 +577,7 @@ impl AMF0Value {
     pub fn read_array(
         cursor: &mut AMFDecodingCursor,
         buffer: &[u8],
-    ) -> Result<HashMap<String, AMF0Value>, ()> {
+    ) -> Result<HashMap<String, AMF0Value>, AMFDecodingError> {
         cursor.skip(4)?;
         Self::read_object(cursor, buffer)
     }
@@ -553,19 +586,25 @@ impl AMF0Value {
     pub fn read_strict_array(
         cursor: &mut AMFDecodingCursor,
         buffer: &[u8],
-    ) -> Result<Vec<AMF0Value>, ()> {
-        let mut arr: Vec<AMF0Value> = Vec::new();
+    ) -> Result<Vec<AMF0Value>, AMFDecodingError> {
+        let l = Self::read_u32_be(cursor, buffer)? as usize;
+
+        if l > AMF_MAX_ELEMENTS {
+            return Err(AMFDecodingError::TooManyElements(l));
+        }
+
+        cursor.enter_nested()?;
 
-        let mut l = Self::read_u32_be(cursor, buffer)?;
+        let mut arr: Vec<AMF0Value> = Vec::with_capacity(l);
 
-        while l > 0 {
+        for _ in 0..l {
             let item = Self::read(cursor, buffer)?;
 
             arr.push(item);
-
-            l -= 1;
         }
 
+        cursor.exit_nested();
+
         Ok(arr)
     }
 
@@ -573,7 +612,7 @@ impl AMF0Value {
     pub fn read_typed_object(
         cursor: &mut AMFDecodingCursor,
         buffer: &[u8],
-    ) -> Result<(String, HashMap<String, AMF0Value>), ()> {
+    ) -> Result<(String, HashMap<String, AMF0Value>), AMFDecodingError> {
         let type_name = Self::read_string(cursor, buffer)?;
         let o = Self::read_object(cursor, buffer)?;
         Ok((type_name, o))
@@ -0,0 +1,241 @@
+// AMF3 encoding of AMF0 compatible values
+
+use std::collections::HashMap;
+
+use super::AMF0Value;
+
+/// AMF0 marker to switch to AMF3 for the next value
+const AMF0_TYPE_AVMPLUS: u8 = 0x11;
+
+const AMF3_TYPE_UNDEFINED: u8 = 0x00;
+const AMF3_TYPE_NULL: u8 = 0x01;
+const AMF3_TYPE_FALSE: u8 = 0x02;
+const AMF3_TYPE_TRUE: u8 = 0x03;
+const AMF3_TYPE_INTEGER: u8 = 0x04;
+const AMF3_TYPE_DOUBLE: u8 = 0x05;
+const AMF3_TYPE_STRING: u8 = 0x06;
+const AMF3_TYPE_XML_DOC: u8 = 0x07;
+const AMF3_TYPE_DATE: u8 = 0x08;
+const AMF3_TYPE_ARRAY: u8 = 0x09;
+const AMF3_TYPE_OBJECT: u8 = 0x0A;
+
+/// Min value of an AMF3 integer (29 bits, signed)
+const AMF3_INTEGER_MIN: f64 = -268435456.0;
+
+/// Max value of an AMF3 integer (29 bits, signed)
+const AMF3_INTEGER_MAX: f64 = 268435455.0;
+
+/// Max value of an U29 (29 bits, unsigned)
+const AMF3_U29_MAX: u32 = 0x1FFFFFFF;
+
+/// Object traits: inline, dynamic, no sealed members
+const AMF3_OBJECT_TRAITS_DYNAMIC: u32 = 0x0B;
+
+impl AMF0Value {
+    /// Encodes value into bytes, wrapped with the AVM+ marker,
+    /// so it can be placed in an AMF0 message as an AMF3 value
+    pub fn encode_avmplus(&self) -> Vec<u8> {
+        let mut buf = vec![AMF0_TYPE_AVMPLUS];
+        buf.extend(self.encode_amf3());
+        buf
+    }
+
+    /// Encodes value into bytes (AMF3)
+    /// No references are used, since every value is encoded once
+    pub fn encode_amf3(&self) -> Vec<u8> {
+        match self {
+            AMF0Value::Number { value } => {
+                if value.fract() == 0.0 && (AMF3_INTEGER_MIN..=AMF3_INTEGER_MAX).contains(value) {
+                    let mut buf = vec![AMF3_TYPE_INTEGER];
+                    buf.extend(Self::encode_amf3_u29((*value as i32 as u32) & AMF3_U29_MAX));
+                    buf
+                } else {
+                    let mut buf = vec![AMF3_TYPE_DOUBLE];
+                    buf.extend(Self::encode_number(*value));
+                    buf
+                }
+            }
+            AMF0Value::Bool { value } => {
+                if *value {
+                    vec![AMF3_TYPE_TRUE]
+                } else {
+                    vec![AMF3_TYPE_FALSE]
+                }
+            }
+            AMF0Value::String { value } | AMF0Value::LongString { value } => {
+                let mut buf = vec![AMF3_TYPE_STRING];
+                buf.extend(Self::encode_amf3_string(value));
+                buf
+            }
+            AMF0Value::Object { properties } => {
+                let mut buf = vec![AMF3_TYPE_OBJECT];
+                buf.extend(Self::encode_amf3_object("", properties));
+                buf
+            }
+            AMF0Value::TypedObject {
+                type_name,
+                properties,
+            } => {
+                let mut buf = vec![AMF3_TYPE_OBJECT];
+                buf.extend(Self::encode_amf3_object(type_name, properties));
+                buf
+            }
+            AMF0Value::Null | AMF0Value::Ref { .. } => vec![AMF3_TYPE_NULL],
+            AMF0Value::Undefined => vec![AMF3_TYPE_UNDEFINED],
+            AMF0Value::Array { items } => {
+                let mut buf = vec![AMF3_TYPE_ARRAY];
+                buf.extend(Self::encode_amf3_u29(1)); // No dense items
+                buf.extend(Self::encode_amf3_members(items));
+                buf
+            }
+            AMF0Value::StrictArray { items } => {
+                let mut buf = vec![AMF3_TYPE_ARRAY];
+                buf.extend(Self::encode_amf3_u29(((items.len() as u32) << 1) | 1));
+                buf.extend(Self::encode_amf3_string("")); // No associative items
+
+                for item in items {
+                    buf.extend(item.encode_amf3());
+                }
+
+                buf
+            }
+            AMF0Value::Date { timestamp } => {
+                let mut buf = vec![AMF3_TYPE_DATE];
+                buf.extend(Self::encode_amf3_u29(1));
+                buf.extend(Self::encode_number(*timestamp));
+                buf
+            }
+            AMF0Value::XmlDocument { content } => {
+                let mut buf = vec![AMF3_TYPE_XML_DOC];
+                buf.extend(Self::encode_amf3_string(content));
+                buf
+            }
+        }
+    }
+
+    /// Encodes variable length unsigned integer (29 bits)
+    pub fn encode_amf3_u29(n: u32) -> Vec<u8> {
+        let n = n & AMF3_U29_MAX;
+
+        if n < 0x80 {
+            vec![n as u8]
+        } else if n < 0x4000 {
+            vec![((n >> 7) | 0x80) as u8, (n & 0x7F) as u8]
+        } else if n < 0x200000 {
+            vec![
+                ((n >> 14) | 0x80) as u8,
+                ((n >> 7) | 0x80) as u8,
+                (n & 0x7F) as u8,
+            ]
+        } else {
+            vec![
+                ((n >> 22) | 0x80) as u8,
+                ((n >> 15) | 0x80) as u8,
+                ((n >> 8) | 0x80) as u8,
+                (n & 0xFF) as u8,
+            ]
+        }
+    }
+
+    /// Encodes string value (AMF3, inline)
+    pub fn encode_amf3_string(s: &str) -> Vec<u8> {
+        let len = s.len().min((AMF3_U29_MAX >> 1) as usize);
+        let mut buf = Self::encode_amf3_u29(((len as u32) << 1) | 1);
+        buf.extend(&s.as_bytes()[..len]);
+        buf
+    }
+
+    /// Encodes dynamic members, ending with an empty name
+    fn encode_amf3_members(o: &HashMap<String, AMF0Value>) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        let mut keys: Vec<&str> = o
+            .keys()
+            .map(|k| k.as_str())
+            .filter(|k| !k.is_empty())
+            .collect();
+
+        keys.sort();
+
+        for key in keys {
+            buf.extend(Self::encode_amf3_string(key));
+            buf.extend(o.get(key).unwrap().encode_amf3());
+        }
+
+        buf.extend(Self::encode_amf3_string(""));
+
+        buf
+    }
+
+    /// Encodes object value (AMF3, dynamic)
+    pub fn encode_amf3_object(type_name: &str, o: &HashMap<String, AMF0Value>) -> Vec<u8> {
+        let mut buf = Self::encode_amf3_u29(AMF3_OBJECT_TRAITS_DYNAMIC);
+        buf.extend(Self::encode_amf3_string(type_name));
+        buf.extend(Self::encode_amf3_members(o));
+        buf
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amf3_u29() {
+        assert_eq!(AMF0Value::encode_amf3_u29(0x7F), vec![0x7F]);
+        assert_eq!(AMF0Value::encode_amf3_u29(0x80), vec![0x81, 0x00]);
+        assert_eq!(AMF0Value::encode_amf3_u29(0x3FFF), vec![0xFF, 0x7F]);
+        assert_eq!(AMF0Value::encode_amf3_u29(0x4000), vec![0x81, 0x80, 0x00]);
+        assert_eq!(
+            AMF0Value::encode_amf3_u29(0x200000),
+            vec![0x80, 0xC0, 0x80, 0x00]
+        );
+        assert_eq!(
+            AMF0Value::encode_amf3_u29(AMF3_U29_MAX),
+            vec![0xFF, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_amf3_encode() {
+        assert_eq!(AMF0Value::Null.encode_avmplus(), vec![0x11, 0x01]);
+        assert_eq!(
+            AMF0Value::Number { value: 5.0 }.encode_amf3(),
+            vec![0x04, 0x05]
+        );
+        assert_eq!(
+            AMF0Value::Number { value: -1.0 }.encode_amf3(),
+            vec![0x04, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(
+            AMF0Value::Number { value: 1.5 }.encode_amf3(),
+            vec![0x05, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            AMF0Value::String {
+                value: "ab".to_string()
+            }
+            .encode_amf3(),
+            vec![0x06, 0x05, b'a', b'b']
+        );
+
+        let mut properties: HashMap<String, AMF0Value> = HashMap::new();
+        properties.insert("b".to_string(), AMF0Value::Bool { value: true });
+        properties.insert("a".to_string(), AMF0Value::Undefined);
+
+        assert_eq!(
+            AMF0Value::Object { properties }.encode_amf3(),
+            vec![0x0A, 0x0B, 0x01, 0x03, b'a', 0x00, 0x03, b'b', 0x03, 0x01]
+        );
+
+        assert_eq!(
+            AMF0Value::StrictArray {
+                items: vec![AMF0Value::Null, AMF0Value::Bool { value: false }]
+            }
+            .encode_amf3(),
+            vec![0x09, 0x05, 0x01, 0x01, 0x02]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 // AMF decoder utilities
 
+use super::{AMFDecodingError, AMF_MAX_DEPTH};
+
 // Cursor for AMF decoding
 pub struct AMFDecodingCursor {
     /// Current position
@@ -7,6 +9,9 @@ pub struct AMFDecodingCursor {
 
     // Length
     len: usize,
+
+    /// Current nesting depth of objects and arrays
+    depth: usize,
 }
 
 impl AMFDecodingCursor {
@@ -15,6 +20,7 @@ impl AMFDecodingCursor {
         AMFDecodingCursor {
             pos: 0,
             len: buffer.len(),
+            depth: 0,
         }
     }
 
@@ -31,9 +37,9 @@ impl AMFDecodingCursor {
 
     /// Reads bytes
     /// Errors on buffer overflow
-    pub fn read<'a>(&mut self, buffer: &'a [u8], n: usize) -> Result<&'a [u8], ()> {
+    pub fn read<'a>(&mut self, buffer: &'a [u8], n: usize) -> Result<&'a [u8], AMFDecodingError> {
         if !self.can_increment_pos(n) {
-            return Err(());
+            return Err(AMFDecodingError::UnexpectedEnd);
         }
 
         let pos = self.pos;
@@ -46,21 +52,21 @@ impl AMFDecodingCursor {
 
     /// Reads byte
     /// Errors on overflow
-    pub fn read_byte(&mut self, buffer: &[u8]) -> Result<u8, ()> {
+    pub fn read_byte(&mut self, buffer: &[u8]) -> Result<u8, AMFDecodingError> {
         let bytes = self.read(buffer, 1)?;
 
         if let Some(b) = bytes.first() {
             Ok(*b)
         } else {
-            Err(())
+            Err(AMFDecodingError::UnexpectedEnd)
         }
     }
 
     /// Reads bytes, without changing the cursor
     /// Errors on buffer overflow
-    pub fn look<'a>(&self, buffer: &'a [u8], n: usize) -> Result<&'a [u8], ()> {
+    pub fn look<'a>(&self, buffer: &'a [u8], n: usize) -> Result<&'a [u8], AMFDecodingError> {
         if !self.can_increment_pos(n) {
-            return Err(());
+            return Err(AMFDecodingError::UnexpectedEnd);
         }
 
         let r: &'a [u8] = &buffer[self.pos..(self.pos + n)];
@@ -70,20 +76,20 @@ impl AMFDecodingCursor {
 
     /// Looks byte
     /// Errors on overflow
-    pub fn look_byte(&self, buffer: &[u8]) -> Result<u8, ()> {
+    pub fn look_byte(&self, buffer: &[u8]) -> Result<u8, AMFDecodingError> {
         let bytes = self.look(buffer, 1)?;
 
         if let Some(b) = bytes.first() {
             Ok(*b)
         } else {
-            Err(())
+            Err(AMFDecodingError::UnexpectedEnd)
         }
     }
 
     /// Skips bytes
-    pub fn skip(&mut self, n: usize) -> Result<(), ()> {
+    pub fn skip(&mut self, n: usize) -> Result<(), AMFDecodingError> {
         if !self.can_increment_pos(n) {
-            return Err(());
+            return Err(AMFDecodingError::UnexpectedEnd);
         }
 
         self.pos += n;
@@ -91,6 +97,23 @@ impl AMFDecodingCursor {
         Ok(())
     }
 
+    /// Enters a nested object or array
+    /// Errors if the max nesting depth is exceeded
+    pub fn enter_nested(&mut self) -> Result<(), AMFDecodingError> {
+        if self.depth >= AMF_MAX_DEPTH {
+            return Err(AMFDecodingError::MaxDepthExceeded);
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    /// Exits a nested object or array
+    pub fn exit_nested(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
     /// Returns true if the cursor is at the end
     pub fn ended(&self) -> bool {
         self.pos >= self.len
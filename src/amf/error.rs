@@ -0,0 +1,53 @@
+// AMF decoding errors
+
+use std::fmt;
+
+/// Max nesting depth of objects and arrays
+pub const AMF_MAX_DEPTH: usize = 32;
+
+/// Max length of a string (bytes)
+pub const AMF_MAX_STRING_LENGTH: usize = 1024 * 1024;
+
+/// Max number of elements of an object or array
+pub const AMF_MAX_ELEMENTS: usize = 4096;
+
+/// Error decoding an AMF value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AMFDecodingError {
+    /// The buffer ended before the value
+    UnexpectedEnd,
+
+    /// A string is not valid UTF-8
+    InvalidString,
+
+    /// Objects or arrays nested too deep
+    MaxDepthExceeded,
+
+    /// A string is too long
+    StringTooLong(usize),
+
+    /// An object or array has too many elements
+    TooManyElements(usize),
+}
+
+impl fmt::Display for AMFDecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AMFDecodingError::UnexpectedEnd => write!(f, "Unexpected end of buffer"),
+            AMFDecodingError::InvalidString => write!(f, "Invalid UTF-8 string"),
+            AMFDecodingError::MaxDepthExceeded => {
+                write!(f, "Max nesting depth exceeded. Max: {}", AMF_MAX_DEPTH)
+            }
+            AMFDecodingError::StringTooLong(len) => write!(
+                f,
+                "String too long. Length: {}. Max: {}",
+                len, AMF_MAX_STRING_LENGTH
+            ),
+            AMFDecodingError::TooManyElements(count) => write!(
+                f,
+                "Too many elements. Count: {}. Max: {}",
+                count, AMF_MAX_ELEMENTS
+            ),
+        }
+    }
+}
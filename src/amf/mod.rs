@@ -1,10 +1,13 @@
 // AMF parsers and serializers
 
 mod amf0;
+mod amf3;
 mod cursor;
+mod error;
 
 pub use amf0::*;
 pub use cursor::*;
+pub use error::*;
 
 // Tests
 
@@ -264,6 +267,77 @@ mod tests {
             },
         ];
 
-        assert!(test_encode_decode(&AMF0Value::StrictArray { items: items }));
+        assert!(test_encode_decode(&AMF0Value::StrictArray { items }));
+    }
+
+    #[test]
+    fn test_amf_decode_limits() {
+        // Nesting depth
+
+        let mut value = AMF0Value::Null;
+
+        for _ in 0..AMF_MAX_DEPTH {
+            value = AMF0Value::StrictArray { items: vec![value] };
+        }
+
+        assert!(test_encode_decode(&value));
+
+        let encoded = AMF0Value::StrictArray { items: vec![value] }.encode();
+        let mut cursor = AMFDecodingCursor::new(&encoded);
+
+        assert_eq!(
+            AMF0Value::read(&mut cursor, &encoded).err(),
+            Some(AMFDecodingError::MaxDepthExceeded)
+        );
+
+        // Number of elements
+
+        let encoded = AMF0Value::StrictArray {
+            items: vec![AMF0Value::Null; AMF_MAX_ELEMENTS + 1],
+        }
+        .encode();
+        let mut cursor = AMFDecodingCursor::new(&encoded);
+
+        assert_eq!(
+            AMF0Value::read(&mut cursor, &encoded).err(),
+            Some(AMFDecodingError::TooManyElements(AMF_MAX_ELEMENTS + 1))
+        );
+
+        let mut properties: HashMap<String, AMF0Value> = HashMap::new();
+
+        for i in 0..=AMF_MAX_ELEMENTS {
+            properties.insert(format!("p{}", i), AMF0Value::Null);
+        }
+
+        let encoded = AMF0Value::Object { properties }.encode();
+        let mut cursor = AMFDecodingCursor::new(&encoded);
+
+        assert_eq!(
+            AMF0Value::read(&mut cursor, &encoded).err(),
+            Some(AMFDecodingError::TooManyElements(AMF_MAX_ELEMENTS + 1))
+        );
+
+        // String length
+
+        let encoded = AMF0Value::LongString {
+            value: "a".repeat(AMF_MAX_STRING_LENGTH + 1),
+        }
+        .encode();
+        let mut cursor = AMFDecodingCursor::new(&encoded);
+
+        assert_eq!(
+            AMF0Value::read(&mut cursor, &encoded).err(),
+            Some(AMFDecodingError::StringTooLong(AMF_MAX_STRING_LENGTH + 1))
+        );
+
+        // Truncated buffer
+
+        let encoded = AMF0Value::Number { value: 1.0 }.encode();
+        let mut cursor = AMFDecodingCursor::new(&encoded[..4]);
+
+        assert_eq!(
+            AMF0Value::read(&mut cursor, &encoded[..4]).err(),
+            Some(AMFDecodingError::UnexpectedEnd)
+        );
     }
 }
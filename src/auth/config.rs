@@ -0,0 +1,154 @@
+// Token authentication configuration
+
+use std::str::FromStr;
+
+use jsonwebtoken::{Algorithm, DecodingKey};
+
+use crate::{
+    log::Logger,
+    log_error,
+    utils::{get_env_bool, get_env_secret, get_env_string},
+};
+
+/// Token authentication configuration
+#[derive(Clone)]
+pub struct TokenAuthConfiguration {
+    /// Key to verify the tokens. None if token authentication is disabled.
+    pub decoding_key: Option<DecodingKey>,
+
+    /// Algorithm of the tokens
+    pub algorithm: Algorithm,
+
+    /// True to require tokens for playing
+    pub play_required: bool,
+}
+
+impl TokenAuthConfiguration {
+    /// Loads token authentication configuration
+    /// from environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `prefix` - Prefix of the environment variables (empty for the server-wide configuration)
+    pub fn load_from_env(logger: &Logger, prefix: &str) -> Result<TokenAuthConfiguration, ()> {
+        let secret_var = format!("{}AUTH_TOKEN_SECRET", prefix);
+        let public_key_var = format!("{}AUTH_TOKEN_PUBLIC_KEY", prefix);
+        let algorithm_var = format!("{}AUTH_TOKEN_ALGORITHM", prefix);
+
+        let secret = match get_env_secret(&secret_var, "") {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!(logger, e);
+                return Err(());
+            }
+        };
+        let public_key_path = get_env_string(&public_key_var, "");
+
+        if !secret.is_empty() && !public_key_path.is_empty() {
+            log_error!(
+                logger,
+                format!(
+                    "{} and {} cannot be set at the same time",
+                    secret_var, public_key_var
+                )
+            );
+            return Err(());
+        }
+
+        let default_algorithm = if public_key_path.is_empty() {
+            "HS256"
+        } else {
+            "RS256"
+        };
+
+        let algorithm_str = get_env_string(&algorithm_var, default_algorithm);
+
+        let algorithm = match Algorithm::from_str(&algorithm_str.to_uppercase()) {
+            Ok(a) => a,
+            Err(_) => {
+                log_error!(
+                    logger,
+                    format!("{} has an invalid value: {}", algorithm_var, algorithm_str)
+                );
+                return Err(());
+            }
+        };
+
+        let is_hmac = matches!(
+            algorithm,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        );
+
+        let decoding_key = if !secret.is_empty() {
+            if !is_hmac {
+                log_error!(
+                    logger,
+                    format!(
+                        "{} requires an HMAC algorithm (HS256, HS384 or HS512)",
+                        secret_var
+                    )
+                );
+                return Err(());
+            }
+
+            Some(DecodingKey::from_secret(secret.as_bytes()))
+        } else if !public_key_path.is_empty() {
+            if is_hmac {
+                log_error!(
+                    logger,
+                    format!(
+                        "{} requires a public key algorithm (RS*, PS*, ES* or EdDSA)",
+                        public_key_var
+                    )
+                );
+                return Err(());
+            }
+
+            let pem = match std::fs::read(&public_key_path) {
+                Ok(p) => p,
+                Err(e) => {
+                    log_error!(logger, format!("Could not read {}: {}", public_key_path, e));
+                    return Err(());
+                }
+            };
+
+            let key_res = match algorithm {
+                Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(&pem),
+                Algorithm::EdDSA => DecodingKey::from_ed_pem(&pem),
+                _ => DecodingKey::from_rsa_pem(&pem),
+            };
+
+            match key_res {
+                Ok(k) => Some(k),
+                Err(e) => {
+                    log_error!(
+                        logger,
+                        format!("Invalid public key {}: {}", public_key_path, e)
+                    );
+                    return Err(());
+                }
+            }
+        } else {
+            None
+        };
+
+        let play_required = get_env_bool(&format!("{}AUTH_TOKEN_PLAY", prefix), true);
+
+        Ok(TokenAuthConfiguration {
+            decoding_key,
+            algorithm,
+            play_required,
+        })
+    }
+
+    /// Checks if token authentication is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.decoding_key.is_some()
+    }
+
+    /// Checks if tokens are required for playing
+    pub fn is_play_token_required(&self) -> bool {
+        self.is_enabled() && self.play_required
+    }
+}
@@ -0,0 +1,154 @@
+// Cryptography provider for JSON Web Tokens
+
+use aws_lc_rs::{hmac, signature as aws_sig};
+use jsonwebtoken::{
+    crypto::{CryptoProvider, JwkUtils, JwtSigner, JwtVerifier},
+    errors::{new_error, ErrorKind},
+    signature::{Error, Signer, Verifier},
+    Algorithm, AlgorithmFamily, DecodingKey, DecodingKeyKind, EncodingKey,
+};
+
+/// Signer for the HMAC algorithms
+struct HmacJwtSigner {
+    algorithm: Algorithm,
+    key: hmac::Key,
+}
+
+impl Signer<Vec<u8>> for HmacJwtSigner {
+    fn try_sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(hmac::sign(&self.key, msg).as_ref().to_vec())
+    }
+}
+
+impl JwtSigner for HmacJwtSigner {
+    fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+}
+
+/// Verifier for the HMAC algorithms
+struct HmacJwtVerifier {
+    algorithm: Algorithm,
+    key: hmac::Key,
+}
+
+impl Verifier<Vec<u8>> for HmacJwtVerifier {
+    fn verify(&self, msg: &[u8], signature: &Vec<u8>) -> Result<(), Error> {
+        hmac::verify(&self.key, msg, signature).map_err(Error::from_source)
+    }
+}
+
+impl JwtVerifier for HmacJwtVerifier {
+    fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+}
+
+/// Verifier for the public key algorithms (RSA, ECDSA and EdDSA)
+struct PublicKeyJwtVerifier {
+    algorithm: Algorithm,
+    verification_algorithm: &'static dyn aws_sig::VerificationAlgorithm,
+    public_key: Vec<u8>,
+}
+
+impl Verifier<Vec<u8>> for PublicKeyJwtVerifier {
+    fn verify(&self, msg: &[u8], signature: &Vec<u8>) -> Result<(), Error> {
+        self.verification_algorithm
+            .verify_sig(&self.public_key, msg, signature)
+            .map_err(Error::from_source)
+    }
+}
+
+impl JwtVerifier for PublicKeyJwtVerifier {
+    fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+}
+
+/// Gets the HMAC algorithm for a JWT algorithm
+fn get_hmac_algorithm(algorithm: &Algorithm) -> Option<hmac::Algorithm> {
+    match algorithm {
+        Algorithm::HS256 => Some(hmac::HMAC_SHA256),
+        Algorithm::HS384 => Some(hmac::HMAC_SHA384),
+        Algorithm::HS512 => Some(hmac::HMAC_SHA512),
+        _ => None,
+    }
+}
+
+/// Gets the verification algorithm and the expected key family for a public key JWT algorithm
+fn get_verification_algorithm(
+    algorithm: &Algorithm,
+) -> Option<(&'static dyn aws_sig::VerificationAlgorithm, AlgorithmFamily)> {
+    match algorithm {
+        Algorithm::RS256 => Some((&aws_sig::RSA_PKCS1_2048_8192_SHA256, AlgorithmFamily::Rsa)),
+        Algorithm::RS384 => Some((&aws_sig::RSA_PKCS1_2048_8192_SHA384, AlgorithmFamily::Rsa)),
+        Algorithm::RS512 => Some((&aws_sig::RSA_PKCS1_2048_8192_SHA512, AlgorithmFamily::Rsa)),
+        Algorithm::PS256 => Some((&aws_sig::RSA_PSS_2048_8192_SHA256, AlgorithmFamily::Rsa)),
+        Algorithm::PS384 => Some((&aws_sig::RSA_PSS_2048_8192_SHA384, AlgorithmFamily::Rsa)),
+        Algorithm::PS512 => Some((&aws_sig::RSA_PSS_2048_8192_SHA512, AlgorithmFamily::Rsa)),
+        Algorithm::ES256 => Some((&aws_sig::ECDSA_P256_SHA256_FIXED, AlgorithmFamily::Ec)),
+        Algorithm::ES384 => Some((&aws_sig::ECDSA_P384_SHA384_FIXED, AlgorithmFamily::Ec)),
+        Algorithm::EdDSA => Some((&aws_sig::ED25519, AlgorithmFamily::Ed)),
+        _ => None,
+    }
+}
+
+/// Creates a signer. Only HMAC algorithms are used to sign tokens.
+fn new_signer(
+    algorithm: &Algorithm,
+    key: &EncodingKey,
+) -> jsonwebtoken::errors::Result<Box<dyn JwtSigner>> {
+    match get_hmac_algorithm(algorithm) {
+        Some(hmac_algorithm) => Ok(Box::new(HmacJwtSigner {
+            algorithm: *algorithm,
+            key: hmac::Key::new(hmac_algorithm, key.try_get_hmac_secret()?),
+        })),
+        None => Err(new_error(ErrorKind::InvalidAlgorithm)),
+    }
+}
+
+/// Creates a verifier
+fn new_verifier(
+    algorithm: &Algorithm,
+    key: &DecodingKey,
+) -> jsonwebtoken::errors::Result<Box<dyn JwtVerifier>> {
+    if let Some(hmac_algorithm) = get_hmac_algorithm(algorithm) {
+        return Ok(Box::new(HmacJwtVerifier {
+            algorithm: *algorithm,
+            key: hmac::Key::new(hmac_algorithm, key.try_get_hmac_secret()?),
+        }));
+    }
+
+    let (verification_algorithm, family) = match get_verification_algorithm(algorithm) {
+        Some(v) => v,
+        None => {
+            return Err(new_error(ErrorKind::InvalidAlgorithm));
+        }
+    };
+
+    if key.family() != family {
+        return Err(new_error(ErrorKind::InvalidKeyFormat));
+    }
+
+    match key.kind() {
+        DecodingKeyKind::SecretOrDer(public_key) => Ok(Box::new(PublicKeyJwtVerifier {
+            algorithm: *algorithm,
+            verification_algorithm,
+            public_key: public_key.clone(),
+        })),
+        DecodingKeyKind::RsaModulusExponent { .. } => Err(new_error(ErrorKind::InvalidKeyFormat)),
+    }
+}
+
+/// Cryptography provider, backed by aws-lc-rs
+static JWT_CRYPTO_PROVIDER: CryptoProvider = CryptoProvider {
+    signer_factory: new_signer,
+    verifier_factory: new_verifier,
+    jwk_utils: JwkUtils::new_unimplemented(),
+};
+
+/// Installs the cryptography provider used to sign and verify JSON Web Tokens.
+/// Must be called before using any token. Calling it more than once has no effect.
+pub fn install_jwt_crypto_provider() {
+    _ = JWT_CRYPTO_PROVIDER.install_default();
+}
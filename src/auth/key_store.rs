@@ -0,0 +1,393 @@
+// Built-in store of stream keys
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::utils::{get_env_bool, get_env_string};
+
+#[cfg(any(feature = "control", feature = "redis"))]
+use crate::utils::generate_uuid_v4;
+
+/// Number of random bytes of the generated keys
+#[cfg(any(feature = "control", feature = "redis"))]
+const GENERATED_KEY_BYTES: usize = 24;
+
+/// Configuration of the built-in key store
+#[derive(Clone)]
+pub struct StreamKeyStoreConfiguration {
+    /// True to validate the publishing keys with the store
+    pub enabled: bool,
+
+    /// Path of the file to persist the keys (empty to keep them only in memory)
+    pub file: String,
+}
+
+impl StreamKeyStoreConfiguration {
+    /// Loads the key store configuration
+    /// from environment variables
+    pub fn load_from_env() -> StreamKeyStoreConfiguration {
+        StreamKeyStoreConfiguration {
+            enabled: get_env_bool("KEY_STORE_USE", false),
+            file: get_env_string("KEY_STORE_FILE", ""),
+        }
+    }
+}
+
+/// Stream key of the store.
+/// The key itself is never stored, only its hash.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredStreamKey {
+    /// ID of the key (UUID)
+    pub id: String,
+
+    /// The channel the key is bound to
+    pub channel: String,
+
+    /// SHA-256 of the key (hex)
+    pub key_hash: String,
+
+    /// Creation time (Unix milliseconds)
+    pub created_at: i64,
+
+    /// Expiration time (Unix milliseconds), None if it never expires
+    pub expires_at: Option<i64>,
+}
+
+impl StoredStreamKey {
+    /// Checks if the key is expired
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time (Unix milliseconds)
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|e| now >= e)
+    }
+}
+
+/// Information of a stream key of the store, to list them
+#[cfg(any(feature = "control", feature = "redis"))]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StreamKeyInfo {
+    /// ID of the key
+    pub id: String,
+
+    /// The channel the key is bound to
+    pub channel: String,
+
+    /// Creation time (Unix milliseconds)
+    pub created_at: i64,
+
+    /// Expiration time (Unix milliseconds), None if it never expires
+    pub expires_at: Option<i64>,
+}
+
+#[cfg(any(feature = "control", feature = "redis"))]
+impl From<&StoredStreamKey> for StreamKeyInfo {
+    fn from(key: &StoredStreamKey) -> StreamKeyInfo {
+        StreamKeyInfo {
+            id: key.id.clone(),
+            channel: key.channel.clone(),
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+        }
+    }
+}
+
+/// Content of the key store file
+#[derive(Serialize, Deserialize, Default)]
+struct StreamKeyStoreFile {
+    /// The keys
+    keys: Vec<StoredStreamKey>,
+}
+
+/// Built-in store of stream keys, so the keys can be managed
+/// without a control server or a callback
+pub struct StreamKeyStore {
+    /// The configuration
+    config: StreamKeyStoreConfiguration,
+
+    /// The keys
+    keys: Mutex<Vec<StoredStreamKey>>,
+}
+
+impl StreamKeyStore {
+    /// Creates new StreamKeyStore
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The key store configuration
+    pub fn new(config: StreamKeyStoreConfiguration) -> StreamKeyStore {
+        StreamKeyStore {
+            config,
+            keys: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Checks if the store is used to validate the publishing keys
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Loads the keys from the file, if configured
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of loaded keys
+    pub async fn load(&self) -> Result<usize, String> {
+        if self.config.file.is_empty() {
+            return Ok(0);
+        }
+
+        let data = match tokio::fs::read(&self.config.file).await {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(0);
+            }
+            Err(e) => {
+                return Err(format!("Could not read key store file: {}", e));
+            }
+        };
+
+        let file: StreamKeyStoreFile =
+            serde_json::from_slice(&data).map_err(|e| format!("Invalid key store file: {}", e))?;
+
+        let mut keys = self.keys.lock().await;
+
+        *keys = file.keys;
+
+        Ok(keys.len())
+    }
+
+    /// Saves the keys to the file, if configured
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys
+    #[cfg(any(feature = "control", feature = "redis"))]
+    async fn save(&self, keys: &[StoredStreamKey]) -> Result<(), String> {
+        if self.config.file.is_empty() {
+            return Ok(());
+        }
+
+        let data = serde_json::to_vec(&StreamKeyStoreFile {
+            keys: keys.to_vec(),
+        })
+        .map_err(|e| e.to_string())?;
+
+        // Write to a temporary file first, so a crash never leaves a partial file
+
+        let tmp_path = format!("{}.tmp", &self.config.file);
+
+        if let Err(e) = tokio::fs::write(&tmp_path, &data).await {
+            return Err(format!("Could not write key store file: {}", e));
+        }
+
+        if let Err(e) = tokio::fs::rename(&tmp_path, &self.config.file).await {
+            _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(format!("Could not write key store file: {}", e));
+        }
+
+        Ok(())
+    }
+
+    /// Generates a new random key for a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `expiration_seconds` - Time until the key expires (seconds, 0 to never expire)
+    ///
+    /// # Return value
+    ///
+    /// Returns the stored key and the key itself, that cannot be retrieved later.
+    /// The error is set if the key could not be persisted (it is still usable).
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub async fn generate(
+        &self,
+        channel: &str,
+        expiration_seconds: u32,
+    ) -> (StoredStreamKey, String, Result<(), String>) {
+        let key = hex::encode(rand::random::<[u8; GENERATED_KEY_BYTES]>());
+        let now = Utc::now().timestamp_millis();
+
+        let stored_key = StoredStreamKey {
+            id: generate_uuid_v4(),
+            channel: channel.to_string(),
+            key_hash: hash_stream_key(&key),
+            created_at: now,
+            expires_at: if expiration_seconds > 0 {
+                Some(now + (expiration_seconds as i64) * 1000)
+            } else {
+                None
+            },
+        };
+
+        let mut keys = self.keys.lock().await;
+
+        keys.retain(|k| !k.is_expired(now));
+        keys.push(stored_key.clone());
+
+        let save_res = self.save(&keys).await;
+
+        (stored_key, key, save_res)
+    }
+
+    /// Lists the keys that are not expired
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel (None to list the keys of all the channels)
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub async fn list(&self, channel: Option<&str>) -> Vec<StreamKeyInfo> {
+        let now = Utc::now().timestamp_millis();
+        let keys = self.keys.lock().await;
+
+        keys.iter()
+            .filter(|k| !k.is_expired(now) && channel.is_none_or(|c| k.channel == c))
+            .map(StreamKeyInfo::from)
+            .collect()
+    }
+
+    /// Revokes a key
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `id` - ID of the key
+    ///
+    /// # Return value
+    ///
+    /// Returns true if the key was revoked, false if not found.
+    /// The error is set if the change could not be persisted.
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub async fn revoke(&self, channel: &str, id: &str) -> (bool, Result<(), String>) {
+        let mut keys = self.keys.lock().await;
+
+        let len = keys.len();
+
+        keys.retain(|k| k.channel != channel || k.id != id);
+
+        if keys.len() == len {
+            return (false, Ok(()));
+        }
+
+        let save_res = self.save(&keys).await;
+
+        (true, save_res)
+    }
+
+    /// Validates a key to publish on a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `key` - The key
+    ///
+    /// # Return value
+    ///
+    /// Returns the ID of the key if valid, None otherwise
+    pub async fn validate(&self, channel: &str, key: &str) -> Option<String> {
+        let key_hash = hash_stream_key(key);
+        let now = Utc::now().timestamp_millis();
+        let keys = self.keys.lock().await;
+
+        keys.iter()
+            .find(|k| {
+                k.channel == channel
+                    && !k.is_expired(now)
+                    && constant_time_eq(k.key_hash.as_bytes(), key_hash.as_bytes())
+            })
+            .map(|k| k.id.clone())
+    }
+}
+
+/// Computes the hash of a stream key, as stored in the key store
+///
+/// # Arguments
+///
+/// * `key` - The key
+fn hash_stream_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Compares two byte strings in constant time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0u8, |d, (x, y)| d | (x ^ y)) == 0
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "control", feature = "redis"))]
+    #[tokio::test]
+    async fn test_stream_key_store() {
+        let file = std::env::temp_dir().join(format!("rtmp-key-store-{}.json", generate_uuid_v4()));
+
+        let config = StreamKeyStoreConfiguration {
+            enabled: true,
+            file: file.to_string_lossy().to_string(),
+        };
+
+        let store = StreamKeyStore::new(config.clone());
+
+        let (stored_key, key, save_res) = store.generate("channel", 0).await;
+
+        assert!(save_res.is_ok());
+        assert_eq!(key.len(), GENERATED_KEY_BYTES * 2);
+        assert!(!stored_key.key_hash.contains(&key));
+
+        let (other_key, _, _) = store.generate("other", 60).await;
+
+        assert!(other_key.expires_at.is_some());
+
+        assert_eq!(
+            store.validate("channel", &key).await,
+            Some(stored_key.id.clone())
+        );
+        assert_eq!(store.validate("other", &key).await, None);
+        assert_eq!(store.validate("channel", "wrong").await, None);
+
+        assert_eq!(store.list(None).await.len(), 2);
+        assert_eq!(
+            store.list(Some("channel")).await,
+            vec![StreamKeyInfo::from(&stored_key)]
+        );
+
+        // Persisted
+
+        let loaded_store = StreamKeyStore::new(config);
+
+        assert_eq!(loaded_store.load().await, Ok(2));
+        assert_eq!(
+            loaded_store.validate("channel", &key).await,
+            Some(stored_key.id.clone())
+        );
+
+        // Revoked
+
+        assert!(!store.revoke("other", &stored_key.id).await.0);
+        assert!(store.revoke("channel", &stored_key.id).await.0);
+        assert_eq!(store.validate("channel", &key).await, None);
+
+        _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_stored_stream_key_expiration() {
+        let key = StoredStreamKey {
+            id: "id".to_string(),
+            channel: "channel".to_string(),
+            key_hash: hash_stream_key("key"),
+            created_at: 0,
+            expires_at: Some(1000),
+        };
+
+        assert!(!key.is_expired(999));
+        assert!(key.is_expired(1000));
+    }
+}
@@ -0,0 +1,11 @@
+// Built-in authentication
+
+mod config;
+mod jwt_crypto;
+mod key_store;
+mod token;
+
+pub use config::*;
+pub use jwt_crypto::*;
+pub use key_store::*;
+pub use token::*;
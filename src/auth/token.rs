@@ -0,0 +1,229 @@
+// Stream token validation
+
+use std::collections::HashMap;
+
+use jsonwebtoken::{decode, Validation};
+use serde::Deserialize;
+
+use crate::utils::string_compare_time_safe;
+
+use super::TokenAuthConfiguration;
+
+/// Name of the query parameter containing the token
+pub const AUTH_TOKEN_QUERY_PARAM: &str = "token";
+
+/// Value of the channel claim to allow any channel
+const AUTH_TOKEN_ANY_CHANNEL: &str = "*";
+
+/// Role granted by a token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamTokenRole {
+    /// Allowed to publish
+    Publish,
+
+    /// Allowed to play
+    Play,
+}
+
+impl StreamTokenRole {
+    /// Gets the value of the role claim
+    pub fn as_str(&self) -> &str {
+        match self {
+            StreamTokenRole::Publish => "publish",
+            StreamTokenRole::Play => "play",
+        }
+    }
+}
+
+/// Claims of a stream token
+#[derive(Debug, Deserialize)]
+struct StreamTokenClaims {
+    /// Channel the token grants access to, or `*` for any channel
+    channel: String,
+
+    /// Role granted by the token
+    role: String,
+
+    /// Stream key, if the token is restricted to a key
+    key: Option<String>,
+
+    /// Stream ID to assign to the published stream
+    stream_id: Option<String>,
+
+    /// Max number of concurrent players for the channel
+    max_players: Option<u32>,
+}
+
+/// Grant given by a valid stream token
+#[derive(Debug, PartialEq)]
+pub struct StreamTokenGrant {
+    /// Stream ID to assign to the published stream
+    pub stream_id: Option<String>,
+
+    /// Max number of concurrent players for the channel
+    pub max_players: Option<u32>,
+}
+
+/// Validates a stream token
+///
+/// # Arguments
+///
+/// * `config` - The token authentication configuration
+/// * `query_params` - The query parameters of the stream name, containing the token
+/// * `channel` - The channel
+/// * `key` - The stream key
+/// * `role` - The required role
+///
+/// # Return value
+///
+/// Returns the grant of the token, or the reason the token is invalid
+pub fn validate_stream_token(
+    config: &TokenAuthConfiguration,
+    query_params: &HashMap<String, String>,
+    channel: &str,
+    key: &str,
+    role: StreamTokenRole,
+) -> Result<StreamTokenGrant, String> {
+    let decoding_key = match &config.decoding_key {
+        Some(k) => k,
+        None => {
+            return Err("Token authentication is disabled".to_string());
+        }
+    };
+
+    let token = match query_params.get(AUTH_TOKEN_QUERY_PARAM) {
+        Some(t) => t,
+        None => {
+            return Err("No token provided".to_string());
+        }
+    };
+
+    let mut validation = Validation::new(config.algorithm);
+    validation.set_required_spec_claims(&["exp"]);
+    validation.validate_aud = false;
+
+    let claims = match decode::<StreamTokenClaims>(token, decoding_key, &validation) {
+        Ok(t) => t.claims,
+        Err(e) => {
+            return Err(e.to_string());
+        }
+    };
+
+    if claims.channel != channel && claims.channel != AUTH_TOKEN_ANY_CHANNEL {
+        return Err(format!("Token not valid for channel {}", channel));
+    }
+
+    if claims.role != role.as_str() {
+        return Err(format!("Token not valid to {}", role.as_str()));
+    }
+
+    if let Some(token_key) = &claims.key {
+        if !string_compare_time_safe(token_key, key) {
+            return Err("Token not valid for the provided key".to_string());
+        }
+    }
+
+    Ok(StreamTokenGrant {
+        stream_id: claims.stream_id,
+        max_players: claims.max_players,
+    })
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use jsonwebtoken::{encode, Algorithm, DecodingKey, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::super::install_jwt_crypto_provider;
+    use super::*;
+
+    #[test]
+    fn test_validate_stream_token() {
+        install_jwt_crypto_provider();
+
+        let config = TokenAuthConfiguration {
+            decoding_key: Some(DecodingKey::from_secret(b"secret")),
+            algorithm: Algorithm::HS256,
+            play_required: true,
+        };
+
+        let make_params = |claims: serde_json::Value, secret: &[u8]| {
+            let token = encode(
+                &Header::new(Algorithm::HS256),
+                &claims,
+                &EncodingKey::from_secret(secret),
+            )
+            .unwrap();
+
+            HashMap::from([(AUTH_TOKEN_QUERY_PARAM.to_string(), token)])
+        };
+
+        let exp = Utc::now().timestamp() + 60;
+
+        let params = make_params(
+            json!({ "exp": exp, "channel": "live", "role": "publish", "stream_id": "s1", "max_players": 10 }),
+            b"secret",
+        );
+
+        assert_eq!(
+            validate_stream_token(&config, &params, "live", "k", StreamTokenRole::Publish),
+            Ok(StreamTokenGrant {
+                stream_id: Some("s1".to_string()),
+                max_players: Some(10),
+            })
+        );
+        assert!(
+            validate_stream_token(&config, &params, "other", "k", StreamTokenRole::Publish)
+                .is_err()
+        );
+        assert!(
+            validate_stream_token(&config, &params, "live", "k", StreamTokenRole::Play).is_err()
+        );
+
+        let params = make_params(
+            json!({ "exp": exp, "channel": "*", "role": "play", "key": "k" }),
+            b"secret",
+        );
+
+        assert_eq!(
+            validate_stream_token(&config, &params, "any", "k", StreamTokenRole::Play),
+            Ok(StreamTokenGrant {
+                stream_id: None,
+                max_players: None,
+            })
+        );
+        assert!(
+            validate_stream_token(&config, &params, "any", "k2", StreamTokenRole::Play).is_err()
+        );
+
+        let params = make_params(
+            json!({ "exp": exp, "channel": "live", "role": "play" }),
+            b"wrong",
+        );
+
+        assert!(
+            validate_stream_token(&config, &params, "live", "k", StreamTokenRole::Play).is_err()
+        );
+
+        let params = make_params(
+            json!({ "exp": exp - 3600, "channel": "live", "role": "play" }),
+            b"secret",
+        );
+
+        assert!(
+            validate_stream_token(&config, &params, "live", "k", StreamTokenRole::Play).is_err()
+        );
+
+        assert!(validate_stream_token(
+            &config,
+            &HashMap::new(),
+            "live",
+            "k",
+            StreamTokenRole::Play
+        )
+        .is_err());
+    }
+}
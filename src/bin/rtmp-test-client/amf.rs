@@ -0,0 +1,234 @@
+// Minimal AMF0 encoding, for the messages exchanged with the server
+
+use byteorder::{BigEndian, ByteOrder};
+
+const AMF0_TYPE_NUMBER: u8 = 0x00;
+const AMF0_TYPE_BOOL: u8 = 0x01;
+const AMF0_TYPE_STRING: u8 = 0x02;
+const AMF0_TYPE_OBJECT: u8 = 0x03;
+const AMF0_TYPE_NULL: u8 = 0x05;
+const AMF0_TYPE_UNDEFINED: u8 = 0x06;
+const AMF0_TYPE_ECMA_ARRAY: u8 = 0x08;
+const AMF0_TYPE_OBJECT_END: u8 = 0x09;
+const AMF0_TYPE_STRICT_ARRAY: u8 = 0x0a;
+const AMF0_TYPE_DATE: u8 = 0x0b;
+const AMF0_TYPE_LONG_STRING: u8 = 0x0c;
+
+/// AMF0 value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Amf0Value {
+    Number(f64),
+    Bool(bool),
+    String(String),
+    Object(Vec<(String, Amf0Value)>),
+    Null,
+    Undefined,
+    EcmaArray(Vec<(String, Amf0Value)>),
+    StrictArray(Vec<Amf0Value>),
+}
+
+impl Amf0Value {
+    /// Gets a property of an object or ECMA array
+    pub fn get_property(&self, name: &str) -> Option<&Amf0Value> {
+        match self {
+            Amf0Value::Object(props) | Amf0Value::EcmaArray(props) => {
+                props.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Amf0Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a number
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Amf0Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Encodes the value
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Buffer to write the encoded value
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Amf0Value::Number(n) => {
+                out.push(AMF0_TYPE_NUMBER);
+                out.extend(n.to_be_bytes());
+            }
+            Amf0Value::Bool(b) => {
+                out.push(AMF0_TYPE_BOOL);
+                out.push(*b as u8);
+            }
+            Amf0Value::String(s) => {
+                if s.len() > 0xffff {
+                    out.push(AMF0_TYPE_LONG_STRING);
+                    out.extend((s.len() as u32).to_be_bytes());
+                } else {
+                    out.push(AMF0_TYPE_STRING);
+                    out.extend((s.len() as u16).to_be_bytes());
+                }
+                out.extend(s.as_bytes());
+            }
+            Amf0Value::Object(props) => {
+                out.push(AMF0_TYPE_OBJECT);
+                encode_properties(props, out);
+            }
+            Amf0Value::Null => {
+                out.push(AMF0_TYPE_NULL);
+            }
+            Amf0Value::Undefined => {
+                out.push(AMF0_TYPE_UNDEFINED);
+            }
+            Amf0Value::EcmaArray(props) => {
+                out.push(AMF0_TYPE_ECMA_ARRAY);
+                out.extend((props.len() as u32).to_be_bytes());
+                encode_properties(props, out);
+            }
+            Amf0Value::StrictArray(items) => {
+                out.push(AMF0_TYPE_STRICT_ARRAY);
+                out.extend((items.len() as u32).to_be_bytes());
+                for item in items {
+                    item.encode(out);
+                }
+            }
+        }
+    }
+}
+
+/// Encodes the properties of an object, including the end marker
+fn encode_properties(props: &[(String, Amf0Value)], out: &mut Vec<u8>) {
+    for (k, v) in props {
+        out.extend((k.len() as u16).to_be_bytes());
+        out.extend(k.as_bytes());
+        v.encode(out);
+    }
+
+    out.extend([0x00, 0x00, AMF0_TYPE_OBJECT_END]);
+}
+
+/// Encodes a list of values
+pub fn amf0_encode_all(values: &[Amf0Value]) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::new();
+
+    for v in values {
+        v.encode(&mut out);
+    }
+
+    out
+}
+
+/// Decodes all the values of a buffer
+///
+/// # Return value
+///
+/// Returns the list of values, or an error message
+pub fn amf0_decode_all(data: &[u8]) -> Result<Vec<Amf0Value>, String> {
+    let mut decoder = Amf0Decoder { data, pos: 0 };
+    let mut values: Vec<Amf0Value> = Vec::new();
+
+    while decoder.pos < data.len() {
+        values.push(decoder.read_value()?);
+    }
+
+    Ok(values)
+}
+
+/// AMF0 decoder
+struct Amf0Decoder<'a> {
+    /// Buffer
+    data: &'a [u8],
+
+    /// Current position
+    pos: usize,
+}
+
+impl Amf0Decoder<'_> {
+    /// Reads a number of bytes
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.data.len() - self.pos < n {
+            return Err("Unexpected end of AMF0 data".to_string());
+        }
+
+        let bytes = &self.data[self.pos..self.pos + n];
+
+        self.pos += n;
+
+        Ok(bytes)
+    }
+
+    /// Reads a string with a length of the given size (2 or 4 bytes)
+    fn read_string(&mut self, len_size: usize) -> Result<String, String> {
+        let len_bytes = self.read_bytes(len_size)?;
+
+        let len = if len_size == 2 {
+            BigEndian::read_u16(len_bytes) as usize
+        } else {
+            BigEndian::read_u32(len_bytes) as usize
+        };
+
+        Ok(String::from_utf8_lossy(self.read_bytes(len)?).to_string())
+    }
+
+    /// Reads properties until the end marker
+    fn read_properties(&mut self) -> Result<Vec<(String, Amf0Value)>, String> {
+        let mut props: Vec<(String, Amf0Value)> = Vec::new();
+
+        loop {
+            let key = self.read_string(2)?;
+
+            if key.is_empty() && self.data.get(self.pos) == Some(&AMF0_TYPE_OBJECT_END) {
+                self.pos += 1;
+                return Ok(props);
+            }
+
+            let value = self.read_value()?;
+
+            props.push((key, value));
+        }
+    }
+
+    /// Reads a value
+    fn read_value(&mut self) -> Result<Amf0Value, String> {
+        let value_type = self.read_bytes(1)?[0];
+
+        match value_type {
+            AMF0_TYPE_NUMBER => Ok(Amf0Value::Number(BigEndian::read_f64(self.read_bytes(8)?))),
+            AMF0_TYPE_BOOL => Ok(Amf0Value::Bool(self.read_bytes(1)?[0] != 0)),
+            AMF0_TYPE_STRING => Ok(Amf0Value::String(self.read_string(2)?)),
+            AMF0_TYPE_LONG_STRING => Ok(Amf0Value::String(self.read_string(4)?)),
+            AMF0_TYPE_OBJECT => Ok(Amf0Value::Object(self.read_properties()?)),
+            AMF0_TYPE_NULL => Ok(Amf0Value::Null),
+            AMF0_TYPE_UNDEFINED => Ok(Amf0Value::Undefined),
+            AMF0_TYPE_ECMA_ARRAY => {
+                self.read_bytes(4)?;
+                Ok(Amf0Value::EcmaArray(self.read_properties()?))
+            }
+            AMF0_TYPE_STRICT_ARRAY => {
+                let count = BigEndian::read_u32(self.read_bytes(4)?);
+                let mut items: Vec<Amf0Value> = Vec::new();
+
+                for _ in 0..count {
+                    items.push(self.read_value()?);
+                }
+
+                Ok(Amf0Value::StrictArray(items))
+            }
+            AMF0_TYPE_DATE => {
+                let time = BigEndian::read_f64(self.read_bytes(8)?);
+                self.read_bytes(2)?;
+                Ok(Amf0Value::Number(time))
+            }
+            _ => Err(format!("Unsupported AMF0 type: {}", value_type)),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+// RTMP chunk stream, client side
+
+use std::collections::HashMap;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Default chunk size, before any set chunk size message
+pub const DEFAULT_CHUNK_SIZE: usize = 128;
+
+/// RTMP message
+#[derive(Clone)]
+pub struct RtmpMessage {
+    /// Message type
+    pub message_type: u8,
+
+    /// Message stream ID
+    pub stream_id: u32,
+
+    /// Timestamp
+    pub timestamp: u32,
+
+    /// Payload
+    pub payload: Vec<u8>,
+}
+
+/// Serializes the basic header of a chunk
+fn write_basic_header(out: &mut Vec<u8>, format: u8, chunk_stream_id: u32) {
+    if chunk_stream_id >= 64 + 256 {
+        out.push((format << 6) | 1);
+        out.push(((chunk_stream_id - 64) & 0xff) as u8);
+        out.push(((chunk_stream_id - 64) >> 8) as u8);
+    } else if chunk_stream_id >= 64 {
+        out.push(format << 6);
+        out.push((chunk_stream_id - 64) as u8);
+    } else {
+        out.push((format << 6) | (chunk_stream_id as u8));
+    }
+}
+
+/// Serializes a message into chunks.
+/// The first chunk uses a type 0 header, and the rest type 3 headers,
+/// repeating the extended timestamp if needed.
+///
+/// # Arguments
+///
+/// * `out` - Buffer to write the chunks
+/// * `chunk_stream_id` - Chunk stream ID
+/// * `msg` - The message
+/// * `chunk_size` - Max size of the chunks payload
+pub fn write_chunks(out: &mut Vec<u8>, chunk_stream_id: u32, msg: &RtmpMessage, chunk_size: usize) {
+    let extended = msg.timestamp >= 0xffffff;
+
+    write_basic_header(out, 0, chunk_stream_id);
+
+    let mut b = [0u8; 4];
+
+    BigEndian::write_u32(&mut b, msg.timestamp.min(0xffffff));
+    out.extend(&b[1..]);
+
+    BigEndian::write_u32(&mut b, msg.payload.len() as u32);
+    out.extend(&b[1..]);
+
+    out.push(msg.message_type);
+
+    LittleEndian::write_u32(&mut b, msg.stream_id);
+    out.extend(b);
+
+    for (i, chunk) in msg.payload.chunks(chunk_size.max(1)).enumerate() {
+        if i > 0 {
+            write_basic_header(out, 3, chunk_stream_id);
+        }
+
+        if extended {
+            out.extend(msg.timestamp.to_be_bytes());
+        }
+
+        out.extend(chunk);
+    }
+
+    if msg.payload.is_empty() && extended {
+        out.extend(msg.timestamp.to_be_bytes());
+    }
+}
+
+/// Status of an input chunk stream
+#[derive(Default)]
+struct ChunkStreamStatus {
+    /// Timestamp of the message
+    timestamp: u32,
+
+    /// Last timestamp delta
+    delta: u32,
+
+    /// True if the last message header used an extended timestamp
+    extended: bool,
+
+    /// Message length
+    length: usize,
+
+    /// Message type
+    message_type: u8,
+
+    /// Message stream ID
+    stream_id: u32,
+
+    /// Received payload of the message
+    payload: Vec<u8>,
+}
+
+/// Reader of chunks, assembling the messages
+pub struct ChunkReader {
+    /// Size of the input chunks
+    pub chunk_size: usize,
+
+    /// Status of the chunk streams
+    streams: HashMap<u32, ChunkStreamStatus>,
+}
+
+impl ChunkReader {
+    /// Creates new ChunkReader
+    pub fn new() -> ChunkReader {
+        ChunkReader {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Reads chunks until a message is complete
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The stream to read from
+    pub async fn read_message<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> std::io::Result<RtmpMessage> {
+        loop {
+            if let Some(msg) = self.read_chunk(reader).await? {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Reads a chunk
+    ///
+    /// # Return value
+    ///
+    /// Returns the message if the chunk completed it
+    async fn read_chunk<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> std::io::Result<Option<RtmpMessage>> {
+        let start_byte = reader.read_u8().await?;
+
+        let format = start_byte >> 6;
+
+        let chunk_stream_id = match start_byte & 0x3f {
+            0 => 64 + reader.read_u8().await? as u32,
+            1 => {
+                let b1 = reader.read_u8().await? as u32;
+                let b2 = reader.read_u8().await? as u32;
+                64 + b1 + (b2 << 8)
+            }
+            id => id as u32,
+        };
+
+        let header_size = match format {
+            0 => 11,
+            1 => 7,
+            2 => 3,
+            _ => 0,
+        };
+
+        let mut header = vec![0u8; header_size];
+
+        reader.read_exact(&mut header).await?;
+
+        let status = self.streams.entry(chunk_stream_id).or_default();
+
+        let message_start = format < 3 || status.payload.is_empty();
+
+        let mut timestamp_field: u32 = 0;
+
+        if format < 3 {
+            timestamp_field = BigEndian::read_u24(&header[0..3]);
+            status.extended = timestamp_field == 0xffffff;
+        }
+
+        if format < 2 {
+            status.length = BigEndian::read_u24(&header[3..6]) as usize;
+            status.message_type = header[6];
+        }
+
+        if format == 0 {
+            status.stream_id = LittleEndian::read_u32(&header[7..11]);
+        }
+
+        if status.extended {
+            timestamp_field = reader.read_u32().await?;
+        }
+
+        if message_start {
+            match format {
+                0 => {
+                    status.timestamp = timestamp_field;
+                    status.delta = 0;
+                }
+                1 | 2 => {
+                    status.delta = timestamp_field;
+                    status.timestamp = status.timestamp.wrapping_add(status.delta);
+                }
+                _ => {
+                    status.timestamp = status.timestamp.wrapping_add(status.delta);
+                }
+            }
+
+            status.payload.clear();
+        }
+
+        let size = self.chunk_size.min(status.length - status.payload.len());
+
+        let offset = status.payload.len();
+
+        status.payload.resize(offset + size, 0);
+
+        reader.read_exact(&mut status.payload[offset..]).await?;
+
+        if status.payload.len() < status.length {
+            return Ok(None);
+        }
+
+        Ok(Some(RtmpMessage {
+            message_type: status.message_type,
+            stream_id: status.stream_id,
+            timestamp: status.timestamp,
+            payload: std::mem::take(&mut status.payload),
+        }))
+    }
+}
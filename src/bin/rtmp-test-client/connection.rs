@@ -0,0 +1,415 @@
+// RTMP client connection
+
+use std::time::Duration;
+
+use byteorder::{BigEndian, ByteOrder};
+use rand::RngCore;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    amf::{amf0_decode_all, amf0_encode_all, Amf0Value},
+    chunk::{write_chunks, ChunkReader, RtmpMessage, DEFAULT_CHUNK_SIZE},
+};
+
+/// Size of the handshake messages (C1, C2, S1, S2)
+const HANDSHAKE_SIZE: usize = 1536;
+
+/// Timeout to wait for a message of the server
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const MESSAGE_TYPE_SET_CHUNK_SIZE: u8 = 1;
+pub const MESSAGE_TYPE_EVENT: u8 = 4;
+pub const MESSAGE_TYPE_AUDIO: u8 = 8;
+pub const MESSAGE_TYPE_VIDEO: u8 = 9;
+pub const MESSAGE_TYPE_DATA: u8 = 18;
+pub const MESSAGE_TYPE_INVOKE: u8 = 20;
+
+const CHUNK_STREAM_PROTOCOL: u32 = 2;
+const CHUNK_STREAM_INVOKE: u32 = 3;
+const CHUNK_STREAM_AUDIO: u32 = 4;
+const CHUNK_STREAM_VIDEO: u32 = 5;
+const CHUNK_STREAM_DATA: u32 = 6;
+
+const EVENT_PING_REQUEST: u16 = 6;
+const EVENT_PING_RESPONSE: u16 = 7;
+
+/// RTMP client connection
+pub struct RtmpClientConnection {
+    /// TCP stream
+    stream: TcpStream,
+
+    /// Chunk reader
+    reader: ChunkReader,
+
+    /// Size of the output chunks
+    out_chunk_size: usize,
+
+    /// Transaction ID for the next command
+    next_transaction_id: f64,
+}
+
+impl RtmpClientConnection {
+    /// Connects to the server and performs the handshake
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Address of the server (host:port)
+    pub async fn connect(address: &str) -> Result<RtmpClientConnection, String> {
+        let mut stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+
+        _ = stream.set_nodelay(true);
+
+        // C0 + C1 (simple handshake: time, zero, random bytes)
+
+        let mut c0c1 = vec![0u8; HANDSHAKE_SIZE + 1];
+
+        c0c1[0] = 3;
+        rand::rng().fill_bytes(&mut c0c1[9..]);
+
+        stream
+            .write_all(&c0c1)
+            .await
+            .map_err(|e| format!("Could not send C0+C1: {}", e))?;
+
+        // S0 + S1 + S2
+
+        let mut s0s1s2 = vec![0u8; 2 * HANDSHAKE_SIZE + 1];
+
+        tokio::time::timeout(READ_TIMEOUT, stream.read_exact(&mut s0s1s2))
+            .await
+            .map_err(|_| "Timed out waiting for S0+S1+S2".to_string())?
+            .map_err(|e| format!("Could not read S0+S1+S2: {}", e))?;
+
+        if s0s1s2[0] != 3 {
+            return Err(format!("Unexpected RTMP version: {}", s0s1s2[0]));
+        }
+
+        // C2 (echo of S1)
+
+        stream
+            .write_all(&s0s1s2[1..HANDSHAKE_SIZE + 1])
+            .await
+            .map_err(|e| format!("Could not send C2: {}", e))?;
+
+        Ok(RtmpClientConnection {
+            stream,
+            reader: ChunkReader::new(),
+            out_chunk_size: DEFAULT_CHUNK_SIZE,
+            next_transaction_id: 1.0,
+        })
+    }
+
+    /// Sends a message
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_stream_id` - Chunk stream to use
+    /// * `msg` - The message
+    async fn send_message(
+        &mut self,
+        chunk_stream_id: u32,
+        msg: &RtmpMessage,
+    ) -> Result<(), String> {
+        let mut out: Vec<u8> = Vec::new();
+
+        write_chunks(&mut out, chunk_stream_id, msg, self.out_chunk_size);
+
+        self.stream
+            .write_all(&out)
+            .await
+            .map_err(|e| format!("Could not send message: {}", e))
+    }
+
+    /// Changes the size of the output chunks
+    pub async fn set_chunk_size(&mut self, chunk_size: usize) -> Result<(), String> {
+        self.send_message(
+            CHUNK_STREAM_PROTOCOL,
+            &RtmpMessage {
+                message_type: MESSAGE_TYPE_SET_CHUNK_SIZE,
+                stream_id: 0,
+                timestamp: 0,
+                payload: (chunk_size as u32).to_be_bytes().to_vec(),
+            },
+        )
+        .await?;
+
+        self.out_chunk_size = chunk_size;
+
+        Ok(())
+    }
+
+    /// Sends a command
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - Message stream ID
+    /// * `name` - Name of the command
+    /// * `args` - Arguments after the transaction ID
+    ///
+    /// # Return value
+    ///
+    /// Returns the transaction ID of the command
+    pub async fn send_command(
+        &mut self,
+        stream_id: u32,
+        name: &str,
+        args: Vec<Amf0Value>,
+    ) -> Result<f64, String> {
+        let transaction_id = self.next_transaction_id;
+
+        self.next_transaction_id += 1.0;
+
+        let mut values = vec![
+            Amf0Value::String(name.to_string()),
+            Amf0Value::Number(transaction_id),
+        ];
+
+        values.extend(args);
+
+        self.send_message(
+            CHUNK_STREAM_INVOKE,
+            &RtmpMessage {
+                message_type: MESSAGE_TYPE_INVOKE,
+                stream_id,
+                timestamp: 0,
+                payload: amf0_encode_all(&values),
+            },
+        )
+        .await?;
+
+        Ok(transaction_id)
+    }
+
+    /// Sends a media or data message
+    pub async fn send_media(&mut self, msg: &RtmpMessage) -> Result<(), String> {
+        let chunk_stream_id = match msg.message_type {
+            MESSAGE_TYPE_AUDIO => CHUNK_STREAM_AUDIO,
+            MESSAGE_TYPE_VIDEO => CHUNK_STREAM_VIDEO,
+            _ => CHUNK_STREAM_DATA,
+        };
+
+        self.send_message(chunk_stream_id, msg).await
+    }
+
+    /// Reads the next message from the server.
+    /// Protocol control messages and pings are handled before returning them.
+    pub async fn read_message(&mut self) -> Result<RtmpMessage, String> {
+        let msg = tokio::time::timeout(READ_TIMEOUT, self.reader.read_message(&mut self.stream))
+            .await
+            .map_err(|_| "Timed out waiting for a message".to_string())?
+            .map_err(|e| format!("Could not read message: {}", e))?;
+
+        match msg.message_type {
+            MESSAGE_TYPE_SET_CHUNK_SIZE if msg.payload.len() >= 4 => {
+                self.reader.chunk_size = BigEndian::read_u32(&msg.payload[0..4]) as usize;
+            }
+            MESSAGE_TYPE_EVENT
+                if msg.payload.len() >= 6
+                    && BigEndian::read_u16(&msg.payload[0..2]) == EVENT_PING_REQUEST =>
+            {
+                let mut payload = EVENT_PING_RESPONSE.to_be_bytes().to_vec();
+
+                payload.extend(&msg.payload[2..6]);
+
+                self.send_message(
+                    CHUNK_STREAM_PROTOCOL,
+                    &RtmpMessage {
+                        message_type: MESSAGE_TYPE_EVENT,
+                        stream_id: 0,
+                        timestamp: 0,
+                        payload,
+                    },
+                )
+                .await?;
+            }
+            _ => {}
+        }
+
+        Ok(msg)
+    }
+
+    /// Reads messages until a command matching the condition is received
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - Condition, receiving the command values
+    ///
+    /// # Return value
+    ///
+    /// Returns the values of the command
+    async fn wait_for_command<F: Fn(&[Amf0Value]) -> bool>(
+        &mut self,
+        condition: F,
+    ) -> Result<Vec<Amf0Value>, String> {
+        loop {
+            let msg = self.read_message().await?;
+
+            if msg.message_type != MESSAGE_TYPE_INVOKE {
+                continue;
+            }
+
+            let values = amf0_decode_all(&msg.payload)?;
+
+            if condition(&values) {
+                return Ok(values);
+            }
+        }
+    }
+
+    /// Waits for the result of a command
+    async fn wait_for_result(&mut self, transaction_id: f64) -> Result<Vec<Amf0Value>, String> {
+        let values = self
+            .wait_for_command(|v| {
+                matches!(
+                    v.first().and_then(|n| n.as_str()),
+                    Some("_result" | "_error")
+                ) && v.get(1).and_then(|t| t.as_number()) == Some(transaction_id)
+            })
+            .await?;
+
+        if values[0].as_str() == Some("_error") {
+            return Err(format!("Command failed: {:?}", values));
+        }
+
+        Ok(values)
+    }
+
+    /// Waits for a status message with a code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Expected code
+    pub async fn wait_for_status(&mut self, code: &str) -> Result<(), String> {
+        let values = self
+            .wait_for_command(|v| {
+                let info = match v.get(3) {
+                    Some(i) => i,
+                    None => return false,
+                };
+
+                v[0].as_str() == Some("onStatus")
+                    && (info.get_property("code").and_then(|c| c.as_str()) == Some(code)
+                        || info.get_property("level").and_then(|l| l.as_str()) == Some("error"))
+            })
+            .await?;
+
+        match values[3].get_property("code").and_then(|c| c.as_str()) {
+            Some(c) if c == code => Ok(()),
+            c => Err(format!("Expected status {}, but received {:?}", code, c)),
+        }
+    }
+
+    /// Sends the connect command and waits for the result
+    ///
+    /// # Arguments
+    ///
+    /// * `app` - The app (channel)
+    /// * `tc_url` - URL of the server
+    pub async fn connect_app(&mut self, app: &str, tc_url: &str) -> Result<(), String> {
+        let transaction_id = self
+            .send_command(
+                0,
+                "connect",
+                vec![Amf0Value::Object(vec![
+                    ("app".to_string(), Amf0Value::String(app.to_string())),
+                    (
+                        "type".to_string(),
+                        Amf0Value::String("nonprivate".to_string()),
+                    ),
+                    ("tcUrl".to_string(), Amf0Value::String(tc_url.to_string())),
+                ])],
+            )
+            .await?;
+
+        self.wait_for_result(transaction_id).await?;
+
+        Ok(())
+    }
+
+    /// Creates a stream
+    ///
+    /// # Return value
+    ///
+    /// Returns the ID of the stream
+    pub async fn create_stream(&mut self) -> Result<u32, String> {
+        let transaction_id = self
+            .send_command(0, "createStream", vec![Amf0Value::Null])
+            .await?;
+
+        let values = self.wait_for_result(transaction_id).await?;
+
+        match values.get(3).and_then(|v| v.as_number()) {
+            Some(id) => Ok(id as u32),
+            None => Err("createStream result is missing the stream ID".to_string()),
+        }
+    }
+
+    /// Starts publishing and waits for the server to accept it
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    /// * `key` - Stream key
+    pub async fn publish(&mut self, stream_id: u32, key: &str) -> Result<(), String> {
+        self.send_command(
+            stream_id,
+            "publish",
+            vec![
+                Amf0Value::Null,
+                Amf0Value::String(key.to_string()),
+                Amf0Value::String("live".to_string()),
+            ],
+        )
+        .await?;
+
+        self.wait_for_status("NetStream.Publish.Start").await
+    }
+
+    /// Starts playing and waits for the server to accept it
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    /// * `key` - Stream key
+    pub async fn play(&mut self, stream_id: u32, key: &str) -> Result<(), String> {
+        self.send_command(
+            stream_id,
+            "play",
+            vec![Amf0Value::Null, Amf0Value::String(key.to_string())],
+        )
+        .await?;
+
+        self.wait_for_status("NetStream.Play.Start").await
+    }
+
+    /// Pauses or resumes playing, and waits for the server to confirm it
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    /// * `pause` - True to pause, false to resume
+    pub async fn pause(&mut self, stream_id: u32, pause: bool) -> Result<(), String> {
+        self.send_command(
+            stream_id,
+            "pause",
+            vec![
+                Amf0Value::Null,
+                Amf0Value::Bool(pause),
+                Amf0Value::Number(0.0),
+            ],
+        )
+        .await?;
+
+        self.wait_for_status(if pause {
+            "NetStream.Pause.Notify"
+        } else {
+            "NetStream.Unpause.Notify"
+        })
+        .await
+    }
+}
@@ -0,0 +1,65 @@
+// RTMP test client
+// Runs a conformance test suite against a running server instance
+
+mod amf;
+mod chunk;
+mod connection;
+mod suite;
+
+use suite::{run_test_suite, TestSuiteOptions};
+
+/// Prints the usage of the client
+fn print_usage() {
+    println!("Usage: rtmp-test-client [--host HOST] [--port PORT] [--channel CHANNEL] [--key KEY]");
+}
+
+/// Main function
+#[tokio::main]
+async fn main() {
+    let mut options = TestSuiteOptions {
+        host: "127.0.0.1".to_string(),
+        port: 1935,
+        channel: "conformance".to_string(),
+        key: "test".to_string(),
+    };
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--help" | "-h" => {
+                print_usage();
+                return;
+            }
+            "--host" | "--port" | "--channel" | "--key" => match args.next() {
+                Some(v) => v,
+                None => {
+                    eprintln!("Missing value for {}", arg);
+                    std::process::exit(2);
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage();
+                std::process::exit(2);
+            }
+        };
+
+        match arg.as_str() {
+            "--host" => options.host = value,
+            "--port" => match value.parse::<u16>() {
+                Ok(p) => options.port = p,
+                Err(_) => {
+                    eprintln!("Invalid port: {}", value);
+                    std::process::exit(2);
+                }
+            },
+            "--channel" => options.channel = value,
+            _ => options.key = value,
+        }
+    }
+
+    if !run_test_suite(&options).await {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,345 @@
+// Conformance test suite
+
+use std::{future::Future, time::Duration};
+
+use crate::{
+    amf::{amf0_encode_all, Amf0Value},
+    chunk::RtmpMessage,
+    connection::{RtmpClientConnection, MESSAGE_TYPE_DATA, MESSAGE_TYPE_VIDEO},
+};
+
+/// Timestamp of the first frame, so the timestamps cross the extended timestamp threshold
+const FIRST_FRAME_TIMESTAMP: u32 = 0xffffff - 200;
+
+/// Duration of each frame (milliseconds)
+const FRAME_DURATION: u32 = 40;
+
+/// Options of the test suite
+pub struct TestSuiteOptions {
+    /// Host of the server
+    pub host: String,
+
+    /// Port of the server
+    pub port: u16,
+
+    /// Channel to use
+    pub channel: String,
+
+    /// Stream key to use
+    pub key: String,
+}
+
+/// Connections used by the tests
+struct TestConnections {
+    /// Publisher connection
+    publisher: RtmpClientConnection,
+
+    /// ID of the publisher stream
+    publisher_stream_id: u32,
+
+    /// Player connection
+    player: RtmpClientConnection,
+
+    /// ID of the player stream
+    player_stream_id: u32,
+
+    /// Index of the next frame to publish
+    next_frame: u32,
+}
+
+/// Makes the payload of a video key frame (AVC NALU).
+/// The payload contents depend on the frame index, so the player can check them.
+///
+/// # Arguments
+///
+/// * `index` - Index of the frame
+/// * `size` - Size of the payload
+fn make_frame_payload(index: u32, size: usize) -> Vec<u8> {
+    let mut payload = vec![0x17, 0x01, 0x00, 0x00, 0x00];
+
+    payload.extend(index.to_be_bytes());
+
+    while payload.len() < size {
+        payload.push((payload.len() as u32).wrapping_mul(31).wrapping_add(index) as u8);
+    }
+
+    payload
+}
+
+/// Gets the frame index from a video payload made with make_frame_payload
+fn get_frame_index(payload: &[u8]) -> Option<u32> {
+    if payload.len() < 9 || payload[0..2] != [0x17, 0x01] {
+        return None;
+    }
+
+    Some(u32::from_be_bytes([
+        payload[5], payload[6], payload[7], payload[8],
+    ]))
+}
+
+/// Gets the timestamp of a frame
+fn get_frame_timestamp(index: u32) -> u32 {
+    FIRST_FRAME_TIMESTAMP.wrapping_add(index.wrapping_mul(FRAME_DURATION))
+}
+
+impl TestConnections {
+    /// Publishes a number of frames
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Number of frames
+    /// * `size` - Size of the frames
+    ///
+    /// # Return value
+    ///
+    /// Returns the index of the first published frame
+    async fn publish_frames(&mut self, count: u32, size: usize) -> Result<u32, String> {
+        let first = self.next_frame;
+
+        for _ in 0..count {
+            let index = self.next_frame;
+
+            self.next_frame += 1;
+
+            self.publisher
+                .send_media(&RtmpMessage {
+                    message_type: MESSAGE_TYPE_VIDEO,
+                    stream_id: self.publisher_stream_id,
+                    timestamp: get_frame_timestamp(index),
+                    payload: make_frame_payload(index, size),
+                })
+                .await?;
+        }
+
+        Ok(first)
+    }
+
+    /// Waits for the player to receive a frame, checking it was not corrupted
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the frame
+    /// * `size` - Size of the frame
+    async fn wait_for_frame(&mut self, index: u32, size: usize) -> Result<(), String> {
+        loop {
+            let msg = self.player.read_message().await?;
+
+            if msg.message_type != MESSAGE_TYPE_VIDEO {
+                continue;
+            }
+
+            let received_index = match get_frame_index(&msg.payload) {
+                Some(i) => i,
+                None => continue,
+            };
+
+            if received_index > index {
+                return Err(format!(
+                    "Expected frame {}, but received frame {}",
+                    index, received_index
+                ));
+            }
+
+            if received_index < index {
+                continue;
+            }
+
+            if msg.payload != make_frame_payload(index, size) {
+                return Err(format!("Frame {} payload does not match", index));
+            }
+
+            if msg.timestamp != get_frame_timestamp(index) {
+                return Err(format!(
+                    "Frame {} timestamp does not match. Expected: {}. Received: {}",
+                    index,
+                    get_frame_timestamp(index),
+                    msg.timestamp
+                ));
+            }
+
+            return Ok(());
+        }
+    }
+}
+
+/// Prints the result of a test
+fn report<T>(name: &str, res: &Result<T, String>) {
+    match res {
+        Ok(_) => println!("[PASS] {}", name),
+        Err(e) => println!("[FAIL] {}: {}", name, e),
+    }
+}
+
+/// Runs a test, printing the result
+async fn run_test<T, F: Future<Output = Result<T, String>>>(
+    name: &str,
+    test: F,
+) -> Result<T, String> {
+    let res = test.await;
+
+    report(name, &res);
+
+    res
+}
+
+/// Connects the publisher and the player
+async fn setup_connections(options: &TestSuiteOptions) -> Result<TestConnections, String> {
+    let address = format!("{}:{}", options.host, options.port);
+    let tc_url = format!("rtmp://{}/{}", address, options.channel);
+
+    let mut publisher = run_test("Handshake", RtmpClientConnection::connect(&address)).await?;
+
+    let publisher_stream_id = run_test("Connect and publish", async {
+        publisher.set_chunk_size(1000).await?;
+        publisher.connect_app(&options.channel, &tc_url).await?;
+
+        let stream_id = publisher.create_stream().await?;
+
+        publisher.publish(stream_id, &options.key).await?;
+
+        // Metadata and AVC sequence header, sent to the player on play
+
+        let metadata = amf0_encode_all(&[
+            Amf0Value::String("@setDataFrame".to_string()),
+            Amf0Value::String("onMetaData".to_string()),
+            Amf0Value::EcmaArray(vec![
+                ("width".to_string(), Amf0Value::Number(1280.0)),
+                ("height".to_string(), Amf0Value::Number(720.0)),
+                ("videocodecid".to_string(), Amf0Value::Number(7.0)),
+            ]),
+        ]);
+
+        publisher
+            .send_media(&RtmpMessage {
+                message_type: MESSAGE_TYPE_DATA,
+                stream_id,
+                timestamp: 0,
+                payload: metadata,
+            })
+            .await?;
+
+        publisher
+            .send_media(&RtmpMessage {
+                message_type: MESSAGE_TYPE_VIDEO,
+                stream_id,
+                timestamp: 0,
+                payload: vec![
+                    0x17, 0x00, 0x00, 0x00, 0x00, 0x01, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0x00, 0x00,
+                    0x01, 0x00, 0x00,
+                ],
+            })
+            .await?;
+
+        Ok(stream_id)
+    })
+    .await?;
+
+    let (player, player_stream_id) = run_test("Connect and play", async {
+        let mut player = RtmpClientConnection::connect(&address).await?;
+
+        player.connect_app(&options.channel, &tc_url).await?;
+
+        let stream_id = player.create_stream().await?;
+
+        player.play(stream_id, &options.key).await?;
+
+        Ok((player, stream_id))
+    })
+    .await?;
+
+    Ok(TestConnections {
+        publisher,
+        publisher_stream_id,
+        player,
+        player_stream_id,
+        next_frame: 0,
+    })
+}
+
+/// Runs the test suite
+///
+/// # Arguments
+///
+/// * `options` - Options of the test suite
+///
+/// # Return value
+///
+/// Returns true if all the tests passed
+pub async fn run_test_suite(options: &TestSuiteOptions) -> bool {
+    let mut c = match setup_connections(options).await {
+        Ok(c) => c,
+        Err(_) => return false,
+    };
+
+    // Frames larger than the chunk size, with timestamps crossing 0xffffff
+
+    let res = run_test("Extended timestamps", async {
+        let first = c.publish_frames(10, 3000).await?;
+
+        for i in first..first + 10 {
+            c.wait_for_frame(i, 3000).await?;
+        }
+
+        if get_frame_timestamp(first + 9) < 0xffffff {
+            return Err("The frames did not reach the extended timestamp range".to_string());
+        }
+
+        Ok(())
+    })
+    .await;
+
+    if res.is_err() {
+        return false;
+    }
+
+    // Chunk size change in the middle of the stream
+
+    let res = run_test("Chunk size change", async {
+        c.publisher.set_chunk_size(200).await?;
+
+        let first = c.publish_frames(10, 1500).await?;
+
+        for i in first..first + 10 {
+            c.wait_for_frame(i, 1500).await?;
+        }
+
+        c.publisher.set_chunk_size(4096).await?;
+
+        let first = c.publish_frames(5, 10000).await?;
+
+        for i in first..first + 5 {
+            c.wait_for_frame(i, 10000).await?;
+        }
+
+        Ok(())
+    })
+    .await;
+
+    if res.is_err() {
+        return false;
+    }
+
+    // Pause and resume
+
+    let res = run_test("Pause and resume", async {
+        c.player.pause(c.player_stream_id, true).await?;
+
+        c.publish_frames(5, 1000).await?;
+
+        // Give the server time to discard the frames
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        c.player.pause(c.player_stream_id, false).await?;
+
+        let first = c.publish_frames(5, 1000).await?;
+
+        for i in first..first + 5 {
+            c.wait_for_frame(i, 1000).await?;
+        }
+
+        Ok(())
+    })
+    .await;
+
+    res.is_ok()
+}
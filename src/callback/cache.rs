@@ -0,0 +1,271 @@
+// Cache of the start callback responses
+
+use std::{collections::HashMap, net::IpAddr};
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::{server::PublishAuthorization, utils::normalize_ip_address};
+
+/// Max number of cached responses.
+/// When reached, the expired responses are removed.
+const CALLBACK_CACHE_MAX_SIZE: usize = 10000;
+
+/// Key of a cached response
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CallbackCacheKey {
+    /// The channel
+    channel: String,
+
+    /// Hash of the stream key (SHA-256), so the keys are not kept in memory
+    key_hash: [u8; 32],
+
+    /// The IP of the publisher
+    ip: IpAddr,
+}
+
+impl CallbackCacheKey {
+    /// Creates new CallbackCacheKey
+    fn new(channel: &str, key: &str, ip: &IpAddr) -> CallbackCacheKey {
+        CallbackCacheKey {
+            channel: channel.to_string(),
+            key_hash: Sha256::digest(key.as_bytes()).into(),
+            ip: normalize_ip_address(*ip),
+        }
+    }
+}
+
+/// Cached responses of the start callback
+pub struct CallbackCacheEntries {
+    /// Responses: key -> (authorization, expiration timestamp)
+    entries: HashMap<CallbackCacheKey, (Option<PublishAuthorization>, i64)>,
+}
+
+impl CallbackCacheEntries {
+    /// Creates new CallbackCacheEntries
+    pub fn new() -> CallbackCacheEntries {
+        CallbackCacheEntries {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Gets a cached response
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `key` - The stream key
+    /// * `ip` - The IP of the publisher
+    /// * `now` - The current timestamp (Unix milliseconds)
+    ///
+    /// # Return value
+    ///
+    /// Returns the cached authorization (None inside if the key was rejected),
+    /// or None if not cached or expired
+    pub fn get(
+        &self,
+        channel: &str,
+        key: &str,
+        ip: &IpAddr,
+        now: i64,
+    ) -> Option<Option<PublishAuthorization>> {
+        match self.entries.get(&CallbackCacheKey::new(channel, key, ip)) {
+            Some((authorization, expiration)) if *expiration > now => Some(authorization.clone()),
+            _ => None,
+        }
+    }
+
+    /// Caches a response
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `key` - The stream key
+    /// * `ip` - The IP of the publisher
+    /// * `authorization` - The authorization (None if the key was rejected)
+    /// * `ttl_seconds` - Time to cache the response (seconds). 0 to not cache it
+    /// * `now` - The current timestamp (Unix milliseconds)
+    pub fn set(
+        &mut self,
+        channel: &str,
+        key: &str,
+        ip: &IpAddr,
+        authorization: Option<PublishAuthorization>,
+        ttl_seconds: u32,
+        now: i64,
+    ) {
+        if ttl_seconds == 0 {
+            return;
+        }
+
+        if self.entries.len() >= CALLBACK_CACHE_MAX_SIZE {
+            self.entries.retain(|_, (_, expiration)| *expiration > now);
+
+            if self.entries.len() >= CALLBACK_CACHE_MAX_SIZE {
+                self.entries.clear();
+            }
+        }
+
+        self.entries.insert(
+            CallbackCacheKey::new(channel, key, ip),
+            (authorization, now + (ttl_seconds as i64) * 1000),
+        );
+    }
+
+    /// Removes the cached responses of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    #[cfg(feature = "control")]
+    pub fn invalidate_channel(&mut self, channel: &str) {
+        self.entries.retain(|k, _| k.channel != channel);
+    }
+}
+
+/// Cache of the start callback responses, so publishers
+/// retrying the same key do not repeat the callback request
+pub struct CallbackResponseCache {
+    /// Entries
+    entries: Mutex<CallbackCacheEntries>,
+}
+
+impl CallbackResponseCache {
+    /// Creates new CallbackResponseCache
+    pub fn new() -> CallbackResponseCache {
+        CallbackResponseCache {
+            entries: Mutex::new(CallbackCacheEntries::new()),
+        }
+    }
+
+    /// Gets a cached response
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `key` - The stream key
+    /// * `ip` - The IP of the publisher
+    ///
+    /// # Return value
+    ///
+    /// Returns the cached authorization (None inside if the key was rejected),
+    /// or None if not cached or expired
+    pub async fn get(
+        &self,
+        channel: &str,
+        key: &str,
+        ip: &IpAddr,
+    ) -> Option<Option<PublishAuthorization>> {
+        self.entries
+            .lock()
+            .await
+            .get(channel, key, ip, Utc::now().timestamp_millis())
+    }
+
+    /// Caches a response
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    /// * `key` - The stream key
+    /// * `ip` - The IP of the publisher
+    /// * `authorization` - The authorization (None if the key was rejected)
+    /// * `ttl_seconds` - Time to cache the response (seconds). 0 to not cache it
+    pub async fn set(
+        &self,
+        channel: &str,
+        key: &str,
+        ip: &IpAddr,
+        authorization: Option<PublishAuthorization>,
+        ttl_seconds: u32,
+    ) {
+        if ttl_seconds == 0 {
+            return;
+        }
+
+        self.entries.lock().await.set(
+            channel,
+            key,
+            ip,
+            authorization,
+            ttl_seconds,
+            Utc::now().timestamp_millis(),
+        );
+    }
+
+    /// Removes the cached responses of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    #[cfg(feature = "control")]
+    pub async fn invalidate_channel(&self, channel: &str) {
+        self.entries.lock().await.invalidate_channel(channel);
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_authorization(stream_id: &str) -> PublishAuthorization {
+        PublishAuthorization {
+            stream_id: stream_id.to_string(),
+            max_players: None,
+            flags: None,
+        }
+    }
+
+    #[test]
+    fn test_callback_cache() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let mut cache = CallbackCacheEntries::new();
+
+        assert!(cache.get("ch", "key", &ip, 0).is_none());
+
+        cache.set("ch", "bad", &ip, None, 5, 0);
+        cache.set("ch", "key", &ip, Some(make_authorization("s1")), 5, 0);
+
+        assert!(matches!(cache.get("ch", "bad", &ip, 1000), Some(None)));
+        assert_eq!(
+            cache
+                .get("ch", "key", &ip, 1000)
+                .unwrap()
+                .unwrap()
+                .stream_id,
+            "s1"
+        );
+
+        // Different IP or channel
+        assert!(cache.get("ch", "bad", &other_ip, 1000).is_none());
+        assert!(cache.get("ch2", "bad", &ip, 1000).is_none());
+
+        // Expired
+        assert!(cache.get("ch", "bad", &ip, 5000).is_none());
+
+        // Disabled
+        cache.set("ch", "other", &ip, None, 0, 0);
+        assert!(cache.get("ch", "other", &ip, 0).is_none());
+    }
+
+    #[cfg(feature = "control")]
+    #[test]
+    fn test_callback_cache_invalidate() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let mut cache = CallbackCacheEntries::new();
+
+        cache.set("ch", "key", &ip, Some(make_authorization("s1")), 60, 0);
+        cache.set("ch2", "key", &ip, Some(make_authorization("s2")), 60, 0);
+
+        cache.invalidate_channel("ch");
+
+        assert!(cache.get("ch", "key", &ip, 0).is_none());
+        assert!(cache.get("ch2", "key", &ip, 0).is_some());
+    }
+}
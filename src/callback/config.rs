@@ -1,12 +1,16 @@
 // Callback feature configuration
 
+use url::Url;
+
 use crate::{
     log::Logger,
-    log_warning,
-    utils::{get_env_string, get_env_u32},
+    log_error, log_warning,
+    server::ServerIdentity,
+    utils::{get_env_bool, get_env_secret, get_env_string, get_env_string_list, get_env_u32},
 };
 
 /// Callback configuration
+#[cfg_attr(not(feature = "callback"), allow(dead_code))]
 #[derive(Clone)]
 pub struct CallbackConfiguration {
     /// Callback URL
@@ -23,15 +27,54 @@ pub struct CallbackConfiguration {
 
     /// Port to add in the token clams
     pub port: u32,
+
+    /// Identity of the server node to add in the token claims
+    pub identity: ServerIdentity,
+
+    /// Time to cache the responses of the start callback (seconds). 0 to disable the cache
+    pub cache_seconds: u32,
+
+    /// True to send the play event, to validate the players
+    pub play_callback: bool,
 }
 
 impl CallbackConfiguration {
     /// Loads callback feature configuration
     /// from environment variables
-    pub fn load_from_env(logger: &Logger) -> Result<CallbackConfiguration, ()> {
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `identity` - Identity of the server node
+    pub fn load_from_env(
+        logger: &Logger,
+        identity: &ServerIdentity,
+    ) -> Result<CallbackConfiguration, ()> {
         let callback_url = get_env_string("CALLBACK_URL", "");
 
-        let jwt_secret = get_env_string("JWT_SECRET", "");
+        if !is_valid_callback_url(&callback_url) {
+            log_error!(
+                logger,
+                format!("CALLBACK_URL has an invalid value: {}", callback_url)
+            );
+            return Err(());
+        }
+
+        if !callback_url.is_empty() && !cfg!(feature = "callback") {
+            log_error!(
+                logger,
+                "CALLBACK_URL is set, but the server was built without the callback feature"
+            );
+            return Err(());
+        }
+
+        let jwt_secret = match get_env_secret("JWT_SECRET", "") {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!(logger, e);
+                return Err(());
+            }
+        };
 
         if jwt_secret.is_empty() {
             log_warning!(logger, "JWT_SECRET is empty. Make sure to set a secure JWT secret to prevent security issues.");
@@ -39,19 +82,31 @@ impl CallbackConfiguration {
 
         let jwt_custom_subject = get_env_string("CUSTOM_JWT_SUBJECT", "");
 
-        let port = get_env_u32("RTMP_PORT", 1935);
+        // If multiple ports are configured, the first one is the main one
+        let port = get_env_string_list("RTMP_PORT", "1935")
+            .first()
+            .and_then(|p| p.parse::<u32>().ok())
+            .unwrap_or(1935);
         let host = get_env_string("RTMP_HOST", "");
 
+        let cache_seconds = get_env_u32("CALLBACK_CACHE_SECONDS", 0);
+
+        let play_callback = get_env_bool("CALLBACK_PLAY", false);
+
         Ok(CallbackConfiguration {
             callback_url,
             jwt_secret,
             jwt_custom_subject,
             port,
             host,
+            identity: identity.clone(),
+            cache_seconds,
+            play_callback,
         })
     }
 
     /// Get JWT subject
+    #[cfg(feature = "callback")]
     pub fn get_jwt_subject(&self) -> &str {
         if self.jwt_custom_subject.is_empty() {
             "rtmp_event"
@@ -60,3 +115,35 @@ impl CallbackConfiguration {
         }
     }
 }
+
+/// Checks if a callback URL is valid
+///
+/// # Arguments
+///
+/// * `callback_url` - The callback URL (empty if not set)
+///
+/// # Return value
+///
+/// Returns true if the URL is empty, or an absolute HTTP or HTTPS URL
+pub fn is_valid_callback_url(callback_url: &str) -> bool {
+    callback_url.is_empty()
+        || Url::parse(callback_url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_callback_url() {
+        assert!(is_valid_callback_url(""));
+        assert!(is_valid_callback_url("http://localhost:8080/callback"));
+        assert!(is_valid_callback_url("https://example.com/callback"));
+
+        assert!(!is_valid_callback_url("localhost/callback"));
+        assert!(!is_valid_callback_url("ftp://example.com/callback"));
+        assert!(!is_valid_callback_url("http://"));
+    }
+}
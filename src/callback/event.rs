@@ -1,36 +1,257 @@
 // Callback events
 
-use std::net::IpAddr;
+#[cfg(feature = "callback")]
+use std::{collections::HashMap, net::IpAddr};
+
+#[cfg(feature = "callback")]
+use crate::{
+    geoip::GeoIpInfo,
+    rtmp::{RtmpPublishType, StreamMetadata},
+    server::PublishStatisticsSummary,
+    session::PlayerLagSnapshot,
+    utils::CertificateIdentity,
+};
+
+/// Result of the play event callback
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayAuthorization {
+    /// The player is allowed to play
+    Accepted,
+
+    /// The player is not allowed to play
+    Rejected,
+
+    /// The player must play from another URL
+    Redirect(String),
+}
+
+/// Identifiers to correlate the events of a published stream
+#[cfg_attr(not(feature = "callback"), allow(dead_code))]
+#[derive(Clone)]
+pub struct CallbackCorrelation {
+    /// ID of the publisher session
+    pub session_id: u64,
+
+    /// Unique ID of the publication (UUID)
+    pub publish_id: String,
+}
 
 /// Callback event
+#[cfg(feature = "callback")]
 pub enum CallbackEvent {
     /// Start event to check the key
-    Start { client_ip: IpAddr },
+    Start {
+        client_ip: IpAddr,
+        client_cert: Option<CertificateIdentity>,
+        client_geo: Option<GeoIpInfo>,
+        query_params: HashMap<String, String>,
+        publish_type: RtmpPublishType,
+    },
     /// Stop event
-    Stop { stream_id: String },
+    Stop {
+        stream_id: String,
+        stats: Option<PublishStatisticsSummary>,
+        reason: Option<String>,
+    },
+    /// Codecs event, sent when the codecs of the stream are identified.
+    /// The start event cannot include them, since the encoder
+    /// does not send media until the start event is responded.
+    Codecs {
+        stream_id: String,
+        audio_codec: Option<String>,
+        video_codec: Option<String>,
+        media_mode: String,
+    },
+    /// Metadata event, sent when the publisher sets the stream metadata
+    Metadata {
+        stream_id: String,
+        metadata: StreamMetadata,
+    },
+    /// Snapshot event, sent with the latest keyframe of the stream
+    Snapshot { stream_id: String },
+    /// Keyframe warning event, sent when the interval between key frames exceeds the threshold
+    KeyframeWarning {
+        stream_id: String,
+        keyframe_interval: i64,
+        threshold: i64,
+    },
+    /// Play event to check if a player is allowed to play
+    Play {
+        client_ip: IpAddr,
+        client_geo: Option<GeoIpInfo>,
+        query_params: HashMap<String, String>,
+    },
+    /// Player lagging event, sent when a player stays behind the stream
+    PlayerLagging {
+        client_ip: IpAddr,
+        lag: PlayerLagSnapshot,
+    },
 }
 
+#[cfg(feature = "callback")]
 impl CallbackEvent {
     /// Gets event
     pub fn get_event(&self) -> String {
         match self {
-            CallbackEvent::Start { client_ip: _ } => "start".to_string(),
-            CallbackEvent::Stop { stream_id: _ } => "stop".to_string(),
+            CallbackEvent::Start { .. } => "start".to_string(),
+            CallbackEvent::Stop { .. } => "stop".to_string(),
+            CallbackEvent::Codecs { .. } => "codecs".to_string(),
+            CallbackEvent::Metadata { .. } => "metadata".to_string(),
+            CallbackEvent::Snapshot { .. } => "snapshot".to_string(),
+            CallbackEvent::KeyframeWarning { .. } => "keyframe_warning".to_string(),
+            CallbackEvent::Play { .. } => "play".to_string(),
+            CallbackEvent::PlayerLagging { .. } => "player_lagging".to_string(),
         }
     }
 
     /// Gets stream ID
     pub fn get_stream_id(&self) -> Option<String> {
         match self {
-            CallbackEvent::Start { client_ip: _ } => None,
-            CallbackEvent::Stop { stream_id } => Some(stream_id.clone()),
+            CallbackEvent::Start { .. } => None,
+            CallbackEvent::Stop { stream_id, .. } => Some(stream_id.clone()),
+            CallbackEvent::Codecs { stream_id, .. } => Some(stream_id.clone()),
+            CallbackEvent::Metadata { stream_id, .. } => Some(stream_id.clone()),
+            CallbackEvent::Snapshot { stream_id } => Some(stream_id.clone()),
+            CallbackEvent::KeyframeWarning { stream_id, .. } => Some(stream_id.clone()),
+            CallbackEvent::Play { .. } => None,
+            CallbackEvent::PlayerLagging { .. } => None,
         }
     }
+
     /// Gets client IP
     pub fn get_client_ip(&self) -> Option<String> {
         match self {
-            CallbackEvent::Start { client_ip } => Some(client_ip.to_string()),
-            CallbackEvent::Stop { stream_id: _ } => None,
+            CallbackEvent::Start { client_ip, .. } => Some(client_ip.to_string()),
+            CallbackEvent::Play { client_ip, .. } => Some(client_ip.to_string()),
+            CallbackEvent::PlayerLagging { client_ip, .. } => Some(client_ip.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Gets the geolocation of the client IP
+    pub fn get_client_geo(&self) -> Option<&GeoIpInfo> {
+        match self {
+            CallbackEvent::Start { client_geo, .. } | CallbackEvent::Play { client_geo, .. } => {
+                client_geo.as_ref()
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the common name of the client certificate
+    pub fn get_client_cert_cn(&self) -> Option<String> {
+        match self {
+            CallbackEvent::Start {
+                client_cert: Some(cert),
+                ..
+            } => cert.common_name.clone(),
+            _ => None,
+        }
+    }
+
+    /// Gets the subject alternative names of the client certificate
+    pub fn get_client_cert_san(&self) -> Option<Vec<String>> {
+        match self {
+            CallbackEvent::Start {
+                client_cert: Some(cert),
+                ..
+            } if !cert.subject_alt_names.is_empty() => Some(cert.subject_alt_names.clone()),
+            _ => None,
+        }
+    }
+
+    /// Gets the query parameters of the stream name
+    pub fn get_query_params(&self) -> Option<HashMap<String, String>> {
+        match self {
+            CallbackEvent::Start { query_params, .. }
+            | CallbackEvent::Play { query_params, .. }
+                if !query_params.is_empty() =>
+            {
+                Some(query_params.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the publish type requested by the publisher
+    pub fn get_publish_type(&self) -> Option<String> {
+        match self {
+            CallbackEvent::Start { publish_type, .. } => Some(publish_type.as_str().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Gets audio codec
+    pub fn get_audio_codec(&self) -> Option<String> {
+        match self {
+            CallbackEvent::Codecs { audio_codec, .. } => audio_codec.clone(),
+            _ => None,
+        }
+    }
+
+    /// Gets video codec
+    pub fn get_video_codec(&self) -> Option<String> {
+        match self {
+            CallbackEvent::Codecs { video_codec, .. } => video_codec.clone(),
+            _ => None,
+        }
+    }
+
+    /// Gets the media accepted for the stream
+    pub fn get_media_mode(&self) -> Option<String> {
+        match self {
+            CallbackEvent::Codecs { media_mode, .. } => Some(media_mode.clone()),
+            _ => None,
+        }
+    }
+
+    /// Gets the statistics of the publication
+    pub fn get_publish_stats(&self) -> Option<PublishStatisticsSummary> {
+        match self {
+            CallbackEvent::Stop { stats, .. } => stats.clone(),
+            _ => None,
+        }
+    }
+
+    /// Gets the reason the publication was stopped by the server
+    pub fn get_stop_reason(&self) -> Option<String> {
+        match self {
+            CallbackEvent::Stop { reason, .. } => reason.clone(),
+            _ => None,
+        }
+    }
+
+    /// Gets the measured interval between key frames
+    pub fn get_keyframe_interval(&self) -> Option<i64> {
+        match self {
+            CallbackEvent::KeyframeWarning {
+                keyframe_interval, ..
+            } => Some(*keyframe_interval),
+            _ => None,
+        }
+    }
+
+    /// Gets the threshold of the interval between key frames
+    pub fn get_keyframe_interval_threshold(&self) -> Option<i64> {
+        match self {
+            CallbackEvent::KeyframeWarning { threshold, .. } => Some(*threshold),
+            _ => None,
+        }
+    }
+
+    /// Gets the diagnostics of the lagging player
+    pub fn get_player_lag(&self) -> Option<PlayerLagSnapshot> {
+        match self {
+            CallbackEvent::PlayerLagging { lag, .. } => Some(lag.clone()),
+            _ => None,
+        }
+    }
+
+    /// Gets stream metadata
+    pub fn get_metadata(&self) -> Option<StreamMetadata> {
+        match self {
+            CallbackEvent::Metadata { metadata, .. } => Some(metadata.clone()),
+            _ => None,
         }
     }
 }
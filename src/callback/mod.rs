@@ -1,11 +1,21 @@
 // Callback feature
 
+mod cache;
 mod config;
 mod event;
+#[cfg(feature = "callback")]
 mod request;
+#[cfg(not(feature = "callback"))]
+mod request_disabled;
+#[cfg(feature = "callback")]
 mod token;
 
+pub use cache::*;
 pub use config::*;
 pub use event::*;
+#[cfg(feature = "callback")]
 pub use request::*;
+#[cfg(not(feature = "callback"))]
+pub use request_disabled::*;
+#[cfg(feature = "callback")]
 pub use token::*;
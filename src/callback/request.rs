@@ -1,31 +1,56 @@
 // Callback requests
 
-use std::net::IpAddr;
+use std::{collections::HashMap, net::IpAddr};
 
 use reqwest::StatusCode;
 
-use crate::{log::Logger, log_debug};
+use crate::{
+    geoip::GeoIpInfo,
+    log::Logger,
+    log_debug,
+    rtmp::{RtmpPublishType, StreamMetadata},
+    server::{PublishAuthorization, PublishStatisticsSummary},
+    session::PlayerLagSnapshot,
+    utils::CertificateIdentity,
+};
 
-use super::{make_callback_jwt, CallbackConfiguration, CallbackEvent};
+use super::{
+    make_callback_jwt, CallbackConfiguration, CallbackCorrelation, CallbackEvent, PlayAuthorization,
+};
 
 /// Makes start event callback
 /// logger - The logger
 /// config - Callback config
 /// channel - The channel
 /// key - The streaming key
+/// correlation - Identifiers of the session and the publication
 /// client_ip - The IP of the publisher
-/// Returns the stream id, or None if invalid key / error
+/// client_cert - The identity of the client certificate of the publisher (if provided)
+/// client_geo - The geolocation of the publisher IP (if available)
+/// query_params - The query parameters of the stream name
+/// publish_type - The publish type requested by the publisher
+/// Returns the authorization (stream id, viewer limit and flags), or None if invalid key / error
+#[allow(clippy::too_many_arguments)]
 pub async fn make_start_callback(
     logger: &Logger,
     config: &CallbackConfiguration,
     channel: &str,
     key: &str,
+    correlation: &CallbackCorrelation,
     client_ip: &IpAddr,
-) -> Option<String> {
+    client_cert: Option<&CertificateIdentity>,
+    client_geo: Option<&GeoIpInfo>,
+    query_params: &HashMap<String, String>,
+    publish_type: RtmpPublishType,
+) -> Option<PublishAuthorization> {
     let callback_url = &config.callback_url;
 
     if callback_url.is_empty() {
-        return Some(key.to_string());
+        return Some(PublishAuthorization {
+            stream_id: key.to_string(),
+            max_players: None,
+            flags: None,
+        });
     }
 
     log_debug!(
@@ -43,8 +68,13 @@ pub async fn make_start_callback(
         config,
         channel,
         key,
+        correlation,
         &CallbackEvent::Start {
             client_ip: *client_ip,
+            client_cert: client_cert.cloned(),
+            client_geo: client_geo.cloned(),
+            query_params: query_params.clone(),
+            publish_type,
         },
     );
 
@@ -69,13 +99,31 @@ pub async fn make_start_callback(
                 return None;
             }
 
-            match r.headers().get("stream-id") {
+            let stream_id = match r.headers().get("stream-id") {
                 Some(s) => match s.to_str() {
-                    Ok(stream_id) => Some(stream_id.to_string()),
-                    Err(_) => Some("".to_string()),
+                    Ok(stream_id) => stream_id.to_string(),
+                    Err(_) => "".to_string(),
                 },
-                None => Some("".to_string()),
-            }
+                None => "".to_string(),
+            };
+
+            let max_players = r
+                .headers()
+                .get("max-players")
+                .and_then(|m| m.to_str().ok())
+                .and_then(|m| m.trim().parse::<u32>().ok());
+
+            let flags = r
+                .headers()
+                .get("stream-flags")
+                .and_then(|f| f.to_str().ok())
+                .map(|f| f.to_string());
+
+            Some(PublishAuthorization {
+                stream_id,
+                max_players,
+                flags,
+            })
         }
         Err(e) => {
             log_debug!(logger, format!("Callback resulted in error: {}", e));
@@ -85,19 +133,172 @@ pub async fn make_start_callback(
     }
 }
 
+/// Makes play event callback
+/// logger - The logger
+/// config - Callback config
+/// channel - The channel
+/// key - The streaming key
+/// session_id - ID of the player session
+/// client_ip - The IP of the player
+/// client_geo - The geolocation of the player IP (if available)
+/// query_params - The query parameters of the stream name
+/// Returns the authorization, accepting the player if the play event is disabled
+#[allow(clippy::too_many_arguments)]
+pub async fn make_play_callback(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    channel: &str,
+    key: &str,
+    session_id: u64,
+    client_ip: &IpAddr,
+    client_geo: Option<&GeoIpInfo>,
+    query_params: &HashMap<String, String>,
+) -> PlayAuthorization {
+    let callback_url = &config.callback_url;
+
+    if callback_url.is_empty() || !config.play_callback {
+        return PlayAuthorization::Accepted;
+    }
+
+    log_debug!(
+        logger,
+        format!(
+            "POST {} | | Event: PLAY | Channel: {}",
+            callback_url, channel
+        )
+    );
+
+    // Generate token
+
+    let token = make_callback_jwt(
+        logger,
+        config,
+        channel,
+        key,
+        &CallbackCorrelation {
+            session_id,
+            publish_id: "".to_string(),
+        },
+        &CallbackEvent::Play {
+            client_ip: *client_ip,
+            client_geo: client_geo.cloned(),
+            query_params: query_params.clone(),
+        },
+    );
+
+    // Make the request
+
+    let client = reqwest::Client::new();
+
+    let request_builder = client.post(callback_url).header("rtmp-event", token);
+
+    let response = request_builder.send().await;
+
+    // Check the response
+
+    match response {
+        Ok(r) => {
+            if r.status() != StatusCode::OK {
+                log_debug!(
+                    logger,
+                    format!("Callback resulted in status code: {}", r.status().as_u16())
+                );
+
+                return PlayAuthorization::Rejected;
+            }
+
+            match r
+                .headers()
+                .get("redirect")
+                .and_then(|u| u.to_str().ok())
+                .map(|u| u.trim())
+                .filter(|u| !u.is_empty())
+            {
+                Some(redirect_url) => PlayAuthorization::Redirect(redirect_url.to_string()),
+                None => PlayAuthorization::Accepted,
+            }
+        }
+        Err(e) => {
+            log_debug!(logger, format!("Callback resulted in error: {}", e));
+
+            PlayAuthorization::Rejected
+        }
+    }
+}
+
+/// Sends a callback event, expecting status code 200 as the response
+/// logger - The logger
+/// config - Callback config
+/// channel - The channel
+/// key - The streaming key
+/// correlation - Identifiers of the session and the publication
+/// event - The event to send
+/// Returns true on success, false on error
+async fn send_callback_event(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    channel: &str,
+    key: &str,
+    correlation: &CallbackCorrelation,
+    event: &CallbackEvent,
+) -> bool {
+    let callback_url = &config.callback_url;
+
+    // Generate token
+
+    let token = make_callback_jwt(logger, config, channel, key, correlation, event);
+
+    // Make the request
+
+    let client = reqwest::Client::new();
+
+    let request_builder = client.post(callback_url).header("rtmp-event", token);
+
+    let response = request_builder.send().await;
+
+    // Check the response
+
+    match response {
+        Ok(r) => {
+            if r.status() != StatusCode::OK {
+                log_debug!(
+                    logger,
+                    format!("Callback resulted in status code: {}", r.status().as_u16())
+                );
+
+                return false;
+            }
+
+            true
+        }
+        Err(e) => {
+            log_debug!(logger, format!("Callback resulted in error: {}", e));
+
+            false
+        }
+    }
+}
+
 /// Makes stop event callback
 /// logger - The logger
 /// config - Callback config
 /// channel - The channel
 /// key - The streaming key
+/// correlation - Identifiers of the session and the publication
 /// stream_id - The stream ID given when called the start callback
+/// stats - The statistics of the publication
+/// reason - The reason, if the publication was stopped by the server
 /// Returns true on success, false on error
+#[allow(clippy::too_many_arguments)]
 pub async fn make_stop_callback(
     logger: &Logger,
     config: &CallbackConfiguration,
     channel: &str,
     key: &str,
+    correlation: &CallbackCorrelation,
     stream_id: &str,
+    stats: Option<&PublishStatisticsSummary>,
+    reason: Option<&str>,
 ) -> bool {
     let callback_url = &config.callback_url;
 
@@ -113,6 +314,251 @@ pub async fn make_stop_callback(
         )
     );
 
+    send_callback_event(
+        logger,
+        config,
+        channel,
+        key,
+        correlation,
+        &CallbackEvent::Stop {
+            stream_id: stream_id.to_string(),
+            stats: stats.cloned(),
+            reason: reason.map(|r| r.to_string()),
+        },
+    )
+    .await
+}
+
+/// Makes codecs event callback
+/// logger - The logger
+/// config - Callback config
+/// channel - The channel
+/// key - The streaming key
+/// correlation - Identifiers of the session and the publication
+/// stream_id - The stream ID given when called the start callback
+/// audio_codec - The audio codec, if identified
+/// video_codec - The video codec, if identified
+/// media_mode - The media accepted for the stream (all, audio or video)
+/// Returns true on success, false on error
+#[allow(clippy::too_many_arguments)]
+pub async fn make_codecs_callback(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    channel: &str,
+    key: &str,
+    correlation: &CallbackCorrelation,
+    stream_id: &str,
+    audio_codec: Option<&str>,
+    video_codec: Option<&str>,
+    media_mode: &str,
+) -> bool {
+    let callback_url = &config.callback_url;
+
+    if callback_url.is_empty() {
+        return true;
+    }
+
+    log_debug!(
+        logger,
+        format!(
+            "POST {} | | Event: CODECS | Channel: {} | Stream ID: {}",
+            callback_url, channel, stream_id
+        )
+    );
+
+    send_callback_event(
+        logger,
+        config,
+        channel,
+        key,
+        correlation,
+        &CallbackEvent::Codecs {
+            stream_id: stream_id.to_string(),
+            audio_codec: audio_codec.map(|c| c.to_string()),
+            video_codec: video_codec.map(|c| c.to_string()),
+            media_mode: media_mode.to_string(),
+        },
+    )
+    .await
+}
+
+/// Makes metadata event callback
+/// logger - The logger
+/// config - Callback config
+/// channel - The channel
+/// key - The streaming key
+/// correlation - Identifiers of the session and the publication
+/// stream_id - The stream ID given when called the start callback
+/// metadata - The stream metadata
+/// Returns true on success, false on error
+pub async fn make_metadata_callback(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    channel: &str,
+    key: &str,
+    correlation: &CallbackCorrelation,
+    stream_id: &str,
+    metadata: StreamMetadata,
+) -> bool {
+    let callback_url = &config.callback_url;
+
+    if callback_url.is_empty() {
+        return true;
+    }
+
+    log_debug!(
+        logger,
+        format!(
+            "POST {} | | Event: METADATA | Channel: {} | Stream ID: {}",
+            callback_url, channel, stream_id
+        )
+    );
+
+    send_callback_event(
+        logger,
+        config,
+        channel,
+        key,
+        correlation,
+        &CallbackEvent::Metadata {
+            stream_id: stream_id.to_string(),
+            metadata,
+        },
+    )
+    .await
+}
+
+/// Makes keyframe warning event callback
+/// logger - The logger
+/// config - Callback config
+/// channel - The channel
+/// key - The streaming key
+/// correlation - Identifiers of the session and the publication
+/// stream_id - The stream ID given when called the start callback
+/// keyframe_interval - The measured interval between key frames (milliseconds)
+/// threshold - The warning threshold (milliseconds)
+/// Returns true on success, false on error
+#[allow(clippy::too_many_arguments)]
+pub async fn make_keyframe_warning_callback(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    channel: &str,
+    key: &str,
+    correlation: &CallbackCorrelation,
+    stream_id: &str,
+    keyframe_interval: i64,
+    threshold: i64,
+) -> bool {
+    let callback_url = &config.callback_url;
+
+    if callback_url.is_empty() {
+        return true;
+    }
+
+    log_debug!(
+        logger,
+        format!(
+            "POST {} | | Event: KEYFRAME_WARNING | Channel: {} | Stream ID: {}",
+            callback_url, channel, stream_id
+        )
+    );
+
+    send_callback_event(
+        logger,
+        config,
+        channel,
+        key,
+        correlation,
+        &CallbackEvent::KeyframeWarning {
+            stream_id: stream_id.to_string(),
+            keyframe_interval,
+            threshold,
+        },
+    )
+    .await
+}
+
+/// Makes player lagging event callback
+/// logger - The logger
+/// config - Callback config
+/// channel - The channel
+/// key - The streaming key provided by the player
+/// session_id - ID of the player session
+/// client_ip - The IP of the player
+/// lag - The diagnostics of the player
+/// Returns true on success, false on error
+pub async fn make_player_lagging_callback(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    channel: &str,
+    key: &str,
+    session_id: u64,
+    client_ip: &IpAddr,
+    lag: PlayerLagSnapshot,
+) -> bool {
+    let callback_url = &config.callback_url;
+
+    if callback_url.is_empty() {
+        return true;
+    }
+
+    log_debug!(
+        logger,
+        format!(
+            "POST {} | | Event: PLAYER_LAGGING | Channel: {}",
+            callback_url, channel
+        )
+    );
+
+    send_callback_event(
+        logger,
+        config,
+        channel,
+        key,
+        &CallbackCorrelation {
+            session_id,
+            publish_id: "".to_string(),
+        },
+        &CallbackEvent::PlayerLagging {
+            client_ip: *client_ip,
+            lag,
+        },
+    )
+    .await
+}
+
+/// Makes snapshot event callback, sending the snapshot as the request body
+/// logger - The logger
+/// config - Callback config (used to sign the event)
+/// snapshot_url - The URL to send the snapshot to
+/// channel - The channel
+/// key - The streaming key
+/// correlation - Identifiers of the session and the publication
+/// stream_id - The stream ID given when called the start callback
+/// snapshot - The snapshot (FLV)
+/// Returns true on success, false on error
+#[allow(clippy::too_many_arguments)]
+pub async fn make_snapshot_callback(
+    logger: &Logger,
+    config: &CallbackConfiguration,
+    snapshot_url: &str,
+    channel: &str,
+    key: &str,
+    correlation: &CallbackCorrelation,
+    stream_id: &str,
+    snapshot: Vec<u8>,
+) -> bool {
+    log_debug!(
+        logger,
+        format!(
+            "POST {} | | Event: SNAPSHOT | Channel: {} | Stream ID: {} | {} bytes",
+            snapshot_url,
+            channel,
+            stream_id,
+            snapshot.len()
+        )
+    );
+
     // Generate token
 
     let token = make_callback_jwt(
@@ -120,7 +566,8 @@ pub async fn make_stop_callback(
         config,
         channel,
         key,
-        &CallbackEvent::Stop {
+        correlation,
+        &CallbackEvent::Snapshot {
             stream_id: stream_id.to_string(),
         },
     );
@@ -129,7 +576,11 @@ pub async fn make_stop_callback(
 
     let client = reqwest::Client::new();
 
-    let request_builder = client.post(callback_url).header("rtmp-event", token);
+    let request_builder = client
+        .post(snapshot_url)
+        .header("rtmp-event", token)
+        .header("content-type", "video/x-flv")
+        .body(snapshot);
 
     let response = request_builder.send().await;
 
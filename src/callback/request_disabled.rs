@@ -0,0 +1,147 @@
+// Callback requests, when the server is built without the callback feature.
+// The configuration cannot set a callback URL, so every event behaves
+// as if the callback was not configured.
+
+use std::{collections::HashMap, net::IpAddr};
+
+use crate::{
+    geoip::GeoIpInfo,
+    log::Logger,
+    rtmp::{RtmpPublishType, StreamMetadata},
+    server::{PublishAuthorization, PublishStatisticsSummary},
+    session::PlayerLagSnapshot,
+    utils::CertificateIdentity,
+};
+
+use super::{CallbackConfiguration, CallbackCorrelation, PlayAuthorization};
+
+/// Makes start event callback
+/// Without the callback feature, the key is used as the stream ID
+#[allow(clippy::too_many_arguments)]
+pub async fn make_start_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    key: &str,
+    _correlation: &CallbackCorrelation,
+    _client_ip: &IpAddr,
+    _client_cert: Option<&CertificateIdentity>,
+    _client_geo: Option<&GeoIpInfo>,
+    _query_params: &HashMap<String, String>,
+    _publish_type: RtmpPublishType,
+) -> Option<PublishAuthorization> {
+    Some(PublishAuthorization {
+        stream_id: key.to_string(),
+        max_players: None,
+        flags: None,
+    })
+}
+
+/// Makes play event callback
+/// Without the callback feature, the player is always accepted
+#[allow(clippy::too_many_arguments)]
+pub async fn make_play_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    _key: &str,
+    _session_id: u64,
+    _client_ip: &IpAddr,
+    _client_geo: Option<&GeoIpInfo>,
+    _query_params: &HashMap<String, String>,
+) -> PlayAuthorization {
+    PlayAuthorization::Accepted
+}
+
+/// Makes stop event callback
+/// Without the callback feature, nothing is sent
+#[allow(clippy::too_many_arguments)]
+pub async fn make_stop_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    _key: &str,
+    _correlation: &CallbackCorrelation,
+    _stream_id: &str,
+    _stats: Option<&PublishStatisticsSummary>,
+    _reason: Option<&str>,
+) -> bool {
+    true
+}
+
+/// Makes codecs event callback
+/// Without the callback feature, nothing is sent
+#[allow(clippy::too_many_arguments)]
+pub async fn make_codecs_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    _key: &str,
+    _correlation: &CallbackCorrelation,
+    _stream_id: &str,
+    _audio_codec: Option<&str>,
+    _video_codec: Option<&str>,
+    _media_mode: &str,
+) -> bool {
+    true
+}
+
+/// Makes metadata event callback
+/// Without the callback feature, nothing is sent
+pub async fn make_metadata_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    _key: &str,
+    _correlation: &CallbackCorrelation,
+    _stream_id: &str,
+    _metadata: StreamMetadata,
+) -> bool {
+    true
+}
+
+/// Makes keyframe warning event callback
+/// Without the callback feature, nothing is sent
+#[allow(clippy::too_many_arguments)]
+pub async fn make_keyframe_warning_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    _key: &str,
+    _correlation: &CallbackCorrelation,
+    _stream_id: &str,
+    _keyframe_interval: i64,
+    _threshold: i64,
+) -> bool {
+    true
+}
+
+/// Makes player lagging event callback
+/// Without the callback feature, nothing is sent
+pub async fn make_player_lagging_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _channel: &str,
+    _key: &str,
+    _session_id: u64,
+    _client_ip: &IpAddr,
+    _lag: PlayerLagSnapshot,
+) -> bool {
+    true
+}
+
+/// Makes snapshot event callback
+/// Without the callback feature, nothing is sent
+#[allow(clippy::too_many_arguments)]
+pub async fn make_snapshot_callback(
+    _logger: &Logger,
+    _config: &CallbackConfiguration,
+    _snapshot_url: &str,
+    _channel: &str,
+    _key: &str,
+    _correlation: &CallbackCorrelation,
+    _stream_id: &str,
+    _snapshot: Vec<u8>,
+) -> bool {
+    true
+}
@@ -1,12 +1,17 @@
 // JWT generation logic
 
+use std::collections::HashMap;
+
 use chrono::Utc;
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 
-use crate::{log::Logger, log_error};
+use crate::{
+    log::Logger, log_error, rtmp::StreamMetadata, server::PublishStatisticsSummary,
+    session::PlayerLagSnapshot,
+};
 
-use super::{CallbackConfiguration, CallbackEvent};
+use super::{CallbackConfiguration, CallbackCorrelation, CallbackEvent};
 
 const JWT_EXPIRATION_TIME_SECONDS: i64 = 120;
 
@@ -30,12 +35,75 @@ struct CallbackJwtClaims {
     /// Key
     key: String,
 
+    /// ID of the publisher session
+    session_id: u64,
+
+    /// Unique ID of the publication
+    publish_id: String,
+
+    /// ID of the server instance
+    server_instance_id: String,
+
+    /// ID of the server node
+    server_id: Option<String>,
+
+    /// Region of the server node
+    server_region: Option<String>,
+
     /// Client IP
     client_ip: Option<String>,
 
+    /// Country of the client IP (ISO 3166-1 alpha-2 code)
+    client_country: Option<String>,
+
+    /// Autonomous system number of the client IP
+    client_asn: Option<u32>,
+
+    /// Organization of the autonomous system of the client IP
+    client_as_org: Option<String>,
+
+    /// Common name of the client certificate
+    client_cert_cn: Option<String>,
+
+    /// Subject alternative names of the client certificate
+    client_cert_san: Option<Vec<String>>,
+
+    /// Query parameters of the stream name
+    query_params: Option<HashMap<String, String>>,
+
+    /// Publish type requested by the publisher
+    publish_type: Option<String>,
+
     /// Stream ID
     stream_id: Option<String>,
 
+    /// Audio codec
+    audio_codec: Option<String>,
+
+    /// Video codec
+    video_codec: Option<String>,
+
+    /// Media accepted for the stream
+    media_mode: Option<String>,
+
+    /// Stream metadata
+    metadata: Option<StreamMetadata>,
+
+    /// Statistics of the publication
+    publish_stats: Option<PublishStatisticsSummary>,
+
+    /// Reason the publication was stopped by the server
+    stop_reason: Option<String>,
+
+    /// Measured interval between key frames (milliseconds)
+    keyframe_interval: Option<i64>,
+
+    /// Threshold of the interval between key frames (milliseconds)
+    keyframe_interval_threshold: Option<i64>,
+
+    /// Diagnostics of the lagging player
+    player_lag: Option<PlayerLagSnapshot>,
+
     /// RTMP port
     rtmp_port: u32,
 
@@ -48,12 +116,14 @@ struct CallbackJwtClaims {
 /// config - Callback configuration
 /// channel - The channel
 /// key - Streaming key
+/// correlation - Identifiers of the session and the publication
 /// event - Callback event
 pub fn make_callback_jwt(
     logger: &Logger,
     config: &CallbackConfiguration,
     channel: &str,
     key: &str,
+    correlation: &CallbackCorrelation,
     event: &CallbackEvent,
 ) -> String {
     let now = Utc::now().timestamp();
@@ -65,8 +135,29 @@ pub fn make_callback_jwt(
         event: event.get_event(),
         channel: channel.to_string(),
         key: key.to_string(),
+        session_id: correlation.session_id,
+        publish_id: correlation.publish_id.clone(),
+        server_instance_id: config.identity.instance_id.clone(),
+        server_id: config.identity.server_id(),
+        server_region: config.identity.region(),
         client_ip: event.get_client_ip(),
+        client_country: event.get_client_geo().and_then(|g| g.country.clone()),
+        client_asn: event.get_client_geo().and_then(|g| g.asn),
+        client_as_org: event.get_client_geo().and_then(|g| g.as_org.clone()),
+        client_cert_cn: event.get_client_cert_cn(),
+        client_cert_san: event.get_client_cert_san(),
+        query_params: event.get_query_params(),
+        publish_type: event.get_publish_type(),
         stream_id: event.get_stream_id(),
+        audio_codec: event.get_audio_codec(),
+        video_codec: event.get_video_codec(),
+        media_mode: event.get_media_mode(),
+        metadata: event.get_metadata(),
+        publish_stats: event.get_publish_stats(),
+        stop_reason: event.get_stop_reason(),
+        keyframe_interval: event.get_keyframe_interval(),
+        keyframe_interval_threshold: event.get_keyframe_interval_threshold(),
+        player_lag: event.get_player_lag(),
         rtmp_port: config.port,
         rtmp_host: config.host.clone(),
     };
@@ -0,0 +1,96 @@
+// Chaos testing configuration
+
+use crate::{log::Logger, log_error, utils::get_env_string};
+
+/// Chaos testing configuration
+#[derive(Clone)]
+pub struct ChaosConfiguration {
+    /// Probability to drop an outbound write (0 to 1)
+    pub drop_rate: f64,
+
+    /// Max random delay added to each outbound write (milliseconds)
+    pub latency_ms: u32,
+
+    /// Probability to disconnect the client on an outbound write (0 to 1)
+    pub disconnect_rate: f64,
+
+    /// Seed for the random decisions. None to use a random seed
+    pub seed: Option<u64>,
+}
+
+impl ChaosConfiguration {
+    /// Loads chaos testing configuration
+    /// from environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<ChaosConfiguration, ()> {
+        let drop_rate = load_rate(logger, "CHAOS_DROP_RATE")?;
+        let disconnect_rate = load_rate(logger, "CHAOS_DISCONNECT_RATE")?;
+
+        let latency_ms_str = get_env_string("CHAOS_LATENCY_MS", "0");
+        let latency_ms = match latency_ms_str.trim().parse::<u32>() {
+            Ok(l) => l,
+            Err(_) => {
+                log_error!(
+                    logger,
+                    format!("CHAOS_LATENCY_MS has an invalid value: {}", latency_ms_str)
+                );
+                return Err(());
+            }
+        };
+
+        let seed_str = get_env_string("CHAOS_SEED", "");
+        let seed = if seed_str.trim().is_empty() {
+            None
+        } else {
+            match seed_str.trim().parse::<u64>() {
+                Ok(s) => Some(s),
+                Err(_) => {
+                    log_error!(
+                        logger,
+                        format!("CHAOS_SEED has an invalid value: {}", seed_str)
+                    );
+                    return Err(());
+                }
+            }
+        };
+
+        Ok(ChaosConfiguration {
+            drop_rate,
+            latency_ms,
+            disconnect_rate,
+            seed,
+        })
+    }
+
+    /// Checks if the chaos testing mode is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.drop_rate > 0.0 || self.latency_ms > 0 || self.disconnect_rate > 0.0
+    }
+}
+
+/// Loads a probability from an environment variable
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `var_name` - Name of the environment variable
+fn load_rate(logger: &Logger, var_name: &str) -> Result<f64, ()> {
+    let rate_str = get_env_string(var_name, "0");
+
+    match rate_str.trim().parse::<f64>() {
+        Ok(r) if (0.0..=1.0).contains(&r) => Ok(r),
+        _ => {
+            log_error!(
+                logger,
+                format!(
+                    "{} has an invalid value: {}. It must be a number between 0 and 1",
+                    var_name, rate_str
+                )
+            );
+            Err(())
+        }
+    }
+}
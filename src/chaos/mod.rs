@@ -0,0 +1,7 @@
+// Chaos testing mode, to test the resilience of the clients
+
+mod config;
+mod writer;
+
+pub use config::*;
+pub use writer::*;
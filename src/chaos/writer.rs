@@ -0,0 +1,252 @@
+// Write stream wrapper injecting faults
+
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, IoSlice},
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::{io::AsyncWrite, time::Sleep};
+
+use super::ChaosConfiguration;
+
+/// Creates the fault-injecting write streams of the connections
+pub struct ChaosInjector {
+    /// Configuration
+    config: ChaosConfiguration,
+
+    /// Number of wrapped connections, to derive the seed of each one
+    connection_count: AtomicU64,
+}
+
+impl ChaosInjector {
+    /// Creates new ChaosInjector
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The chaos testing configuration
+    pub fn new(config: &ChaosConfiguration) -> ChaosInjector {
+        ChaosInjector {
+            config: config.clone(),
+            connection_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Wraps the write stream of a connection.
+    /// If the chaos testing mode is disabled, the bytes are written as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The write stream
+    pub fn wrap<TW: AsyncWrite + Unpin>(&self, inner: TW) -> ChaosWriter<TW> {
+        if !self.config.is_enabled() {
+            return ChaosWriter { inner, state: None };
+        }
+
+        let connection_index = self.connection_count.fetch_add(1, Ordering::Relaxed);
+
+        let rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(connection_index)),
+            None => StdRng::from_os_rng(),
+        };
+
+        ChaosWriter {
+            inner,
+            state: Some(ChaosWriterState {
+                config: self.config.clone(),
+                rng,
+                remaining: 0,
+                delay: None,
+                disconnected: false,
+            }),
+        }
+    }
+}
+
+/// Fault injection status of a write stream
+struct ChaosWriterState {
+    /// Configuration
+    config: ChaosConfiguration,
+
+    /// Random generator for the decisions
+    rng: StdRng,
+
+    /// Remaining bytes of the current write.
+    /// Decisions are only made at the start of each write, so writes are never cut.
+    remaining: usize,
+
+    /// Delay before the current write
+    delay: Option<Pin<Box<Sleep>>>,
+
+    /// True if a disconnection was injected
+    disconnected: bool,
+}
+
+/// Write stream wrapper injecting faults:
+/// randomly delaying or dropping the writes, and disconnecting the client
+pub struct ChaosWriter<TW: AsyncWrite + Unpin> {
+    /// The inner write stream
+    inner: TW,
+
+    /// Fault injection status (None if disabled)
+    state: Option<ChaosWriterState>,
+}
+
+impl<TW: AsyncWrite + Unpin> ChaosWriter<TW> {
+    /// Writes bytes, injecting faults
+    fn poll_write_chaos(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Error>> {
+        let state = match &mut self.state {
+            Some(s) => s,
+            None => {
+                return Pin::new(&mut self.inner).poll_write(cx, buf);
+            }
+        };
+
+        if state.disconnected {
+            _ = ready!(Pin::new(&mut self.inner).poll_shutdown(cx));
+            return Poll::Ready(Err(Error::from(ErrorKind::ConnectionReset)));
+        }
+
+        if state.remaining == 0 && state.delay.is_none() && !buf.is_empty() {
+            // Start of a new write
+
+            if state.config.disconnect_rate > 0.0
+                && state.rng.random_bool(state.config.disconnect_rate)
+            {
+                state.disconnected = true;
+
+                _ = ready!(Pin::new(&mut self.inner).poll_shutdown(cx));
+                return Poll::Ready(Err(Error::from(ErrorKind::ConnectionReset)));
+            }
+
+            if state.config.drop_rate > 0.0 && state.rng.random_bool(state.config.drop_rate) {
+                return Poll::Ready(Ok(buf.len()));
+            }
+
+            state.remaining = buf.len();
+
+            if state.config.latency_ms > 0 {
+                let latency = state.rng.random_range(0..=state.config.latency_ms);
+
+                if latency > 0 {
+                    state.delay = Some(Box::pin(tokio::time::sleep(Duration::from_millis(
+                        latency as u64,
+                    ))));
+                }
+            }
+        }
+
+        if let Some(delay) = &mut state.delay {
+            ready!(delay.as_mut().poll(cx));
+            state.delay = None;
+        }
+
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+
+        state.remaining = state.remaining.saturating_sub(n);
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<TW: AsyncWrite + Unpin> AsyncWrite for ChaosWriter<TW> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        self.get_mut().poll_write_chaos(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, Error>> {
+        let this = self.get_mut();
+
+        if this.state.is_none() {
+            return Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        }
+
+        // Each slice is a separate write, so they can be dropped or delayed separately
+
+        match bufs.iter().find(|b| !b.is_empty()) {
+            Some(buf) => this.poll_write_chaos(cx, buf),
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    fn make_config(drop_rate: f64, disconnect_rate: f64) -> ChaosConfiguration {
+        ChaosConfiguration {
+            drop_rate,
+            latency_ms: 0,
+            disconnect_rate,
+            seed: Some(42),
+        }
+    }
+
+    async fn write_messages(injector: &ChaosInjector) -> Vec<u8> {
+        let mut writer = injector.wrap(Vec::new());
+
+        for i in 0..64u8 {
+            writer.write_all(&[i; 4]).await.unwrap();
+        }
+
+        writer.inner
+    }
+
+    #[tokio::test]
+    async fn test_chaos_writer_drop() {
+        // Disabled
+        let output = write_messages(&ChaosInjector::new(&make_config(0.0, 0.0))).await;
+        assert_eq!(output.len(), 256);
+
+        // Drop everything
+        let output = write_messages(&ChaosInjector::new(&make_config(1.0, 0.0))).await;
+        assert!(output.is_empty());
+
+        // Deterministic with the seed. Writes are dropped as a whole.
+        let output_a = write_messages(&ChaosInjector::new(&make_config(0.5, 0.0))).await;
+        let output_b = write_messages(&ChaosInjector::new(&make_config(0.5, 0.0))).await;
+
+        assert_eq!(output_a, output_b);
+        assert!(!output_a.is_empty() && output_a.len() < 256);
+        assert!(output_a.chunks(4).all(|c| c.iter().all(|b| *b == c[0])));
+    }
+
+    #[tokio::test]
+    async fn test_chaos_writer_disconnect() {
+        let injector = ChaosInjector::new(&make_config(0.0, 1.0));
+        let mut writer = injector.wrap(Vec::new());
+
+        assert!(writer.write_all(&[1, 2, 3]).await.is_err());
+        assert!(writer.write_all(&[1, 2, 3]).await.is_err());
+        assert!(writer.inner.is_empty());
+    }
+}
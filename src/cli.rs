@@ -0,0 +1,200 @@
+// Command line arguments
+
+use crate::utils::ChannelSelector;
+
+/// Usage of the command line arguments
+pub const CLI_USAGE: &str = "Usage: rtmp-server [COMMAND] [OPTIONS]
+
+The server is configured with environment variables (or a .env file).
+The operational commands use the same variables to connect to Redis.
+
+Commands:
+    serve                Runs the server (default).
+    check-config         Loads and validates the configuration, printing a report, and exits.
+                         Exits with a non-zero code if the configuration has errors.
+    list-streams         Lists the streams published in the cluster,
+                         reading the publish registry stored in Redis.
+    kill-stream CHANNEL  Kills the stream published on a channel,
+                         sending the kill-session command through Redis.
+                         The channel can be a wildcard pattern (event-*) or a list
+                         split by commas, to kill a group of channels (requires --confirm).
+
+Options:
+    --check-config       Same as the check-config command.
+    --confirm            Confirms the kill of a group of channels (kill-stream).
+    -h, --help           Prints this help message and exits.
+    -V, --version        Prints the version and exits.";
+
+/// Action requested with the command line arguments
+#[derive(Debug, PartialEq)]
+pub enum CliAction {
+    /// Run the server
+    Serve,
+
+    /// Validate the configuration and exit
+    CheckConfig,
+
+    /// List the streams published in the cluster
+    ListStreams,
+
+    /// Kill the stream published on a channel
+    KillStream {
+        /// The channel ID (or a selector of a group of channels)
+        channel: String,
+
+        /// True to confirm the kill of a group of channels
+        confirm: bool,
+    },
+
+    /// Replay the messages received by a traced session, for debugging.
+    /// Not listed in the usage.
+    ReplayTrace {
+        /// Path of the trace file
+        path: String,
+    },
+
+    /// Print the usage and exit
+    Help,
+
+    /// Print the version and exit
+    Version,
+}
+
+impl CliAction {
+    /// Parses the command line arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments, not including the program name
+    ///
+    /// # Return value
+    ///
+    /// Returns the requested action, or an error message if an argument is invalid
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<CliAction, String> {
+        let mut action: Option<CliAction> = None;
+
+        for arg in args {
+            action = match (arg.as_str(), action) {
+                ("-h" | "--help", _) => {
+                    return Ok(CliAction::Help);
+                }
+                ("-V" | "--version", _) => {
+                    return Ok(CliAction::Version);
+                }
+                ("--check-config", None | Some(CliAction::CheckConfig)) => {
+                    Some(CliAction::CheckConfig)
+                }
+                ("serve", None) => Some(CliAction::Serve),
+                ("check-config", None) => Some(CliAction::CheckConfig),
+                ("list-streams", None) => Some(CliAction::ListStreams),
+                ("kill-stream", None) => Some(CliAction::KillStream {
+                    channel: String::new(),
+                    confirm: false,
+                }),
+                ("replay-trace", None) => Some(CliAction::ReplayTrace {
+                    path: String::new(),
+                }),
+                ("--confirm", Some(CliAction::KillStream { channel, .. })) => {
+                    Some(CliAction::KillStream {
+                        channel,
+                        confirm: true,
+                    })
+                }
+                (a, Some(CliAction::KillStream { channel, confirm }))
+                    if channel.is_empty() && !a.starts_with('-') =>
+                {
+                    Some(CliAction::KillStream {
+                        channel: a.to_string(),
+                        confirm,
+                    })
+                }
+                (a, Some(CliAction::ReplayTrace { path }))
+                    if path.is_empty() && !a.starts_with('-') =>
+                {
+                    Some(CliAction::ReplayTrace {
+                        path: a.to_string(),
+                    })
+                }
+                _ => {
+                    return Err(format!("Unexpected argument: {}", arg));
+                }
+            };
+        }
+
+        match action {
+            Some(CliAction::KillStream { channel, .. }) if channel.is_empty() => {
+                Err("The kill-stream command requires a channel".to_string())
+            }
+            Some(CliAction::KillStream { channel, confirm }) => {
+                match ChannelSelector::parse_kill_target(&channel, confirm) {
+                    Ok(_) => Ok(CliAction::KillStream { channel, confirm }),
+                    Err(e) if confirm => Err(e),
+                    Err(e) => Err(format!("{}. Use --confirm to kill a group of channels", e)),
+                }
+            }
+            Some(CliAction::ReplayTrace { path }) if path.is_empty() => {
+                Err("The replay-trace command requires a trace file".to_string())
+            }
+            Some(a) => Ok(a),
+            None => Ok(CliAction::Serve),
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliAction, String> {
+        CliAction::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_cli_action_parse() {
+        assert_eq!(parse(&[]), Ok(CliAction::Serve));
+        assert_eq!(parse(&["serve"]), Ok(CliAction::Serve));
+        assert_eq!(parse(&["--check-config"]), Ok(CliAction::CheckConfig));
+        assert_eq!(parse(&["check-config"]), Ok(CliAction::CheckConfig));
+        assert_eq!(parse(&["--check-config", "--help"]), Ok(CliAction::Help));
+        assert_eq!(parse(&["-V"]), Ok(CliAction::Version));
+        assert_eq!(parse(&["list-streams"]), Ok(CliAction::ListStreams));
+        assert_eq!(
+            parse(&["kill-stream", "live"]),
+            Ok(CliAction::KillStream {
+                channel: "live".to_string(),
+                confirm: false,
+            })
+        );
+        assert_eq!(
+            parse(&["kill-stream", "--confirm", "event-*"]),
+            Ok(CliAction::KillStream {
+                channel: "event-*".to_string(),
+                confirm: true,
+            })
+        );
+        assert_eq!(
+            parse(&["kill-stream", "a,b", "--confirm"]),
+            Ok(CliAction::KillStream {
+                channel: "a,b".to_string(),
+                confirm: true,
+            })
+        );
+
+        assert!(parse(&["--unknown"]).is_err());
+        assert!(parse(&["serve", "list-streams"]).is_err());
+        assert!(parse(&["kill-stream"]).is_err());
+        assert!(parse(&["kill-stream", "a", "b"]).is_err());
+        assert!(parse(&["kill-stream", "event-*"]).is_err());
+        assert!(parse(&["kill-stream", "a,,b", "--confirm"]).is_err());
+        assert!(parse(&["--confirm"]).is_err());
+        assert_eq!(
+            parse(&["replay-trace", "trace.jsonl"]),
+            Ok(CliAction::ReplayTrace {
+                path: "trace.jsonl".to_string()
+            })
+        );
+        assert!(parse(&["replay-trace"]).is_err());
+    }
+}
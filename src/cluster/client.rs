@@ -0,0 +1,209 @@
+// RTMP client, to pull streams from other nodes
+
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    amf::AMF0Value,
+    rtmp::{
+        rtmp_make_invoke_message, RtmpCommand, RTMP_HANDSHAKE_SIZE, RTMP_MIN_CHUNK_SIZE,
+        RTMP_OBJECT_ENCODING_AMF0, RTMP_PING_TIMEOUT, RTMP_PLAY_START_LIVE, RTMP_VERSION,
+    },
+};
+
+/// Transaction ID of the connect command
+pub const CLUSTER_CLIENT_CONNECT_TRANS_ID: i64 = 1;
+
+/// Transaction ID of the createStream command
+pub const CLUSTER_CLIENT_CREATE_STREAM_TRANS_ID: i64 = 2;
+
+/// Performs the client side of the RTMP handshake (basic format)
+///
+/// # Arguments
+///
+/// * `stream` - The stream connected to the server
+pub async fn rtmp_client_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+) -> Result<(), String> {
+    // C0 + C1: Time, zero and random bytes
+
+    let mut c0_c1: Vec<u8> = vec![0; 1 + RTMP_HANDSHAKE_SIZE];
+
+    c0_c1[0] = RTMP_VERSION;
+    c0_c1[1..5].copy_from_slice(&(Utc::now().timestamp() as u32).to_be_bytes());
+    StdRng::from_os_rng().fill_bytes(&mut c0_c1[9..]);
+
+    stream
+        .write_all(&c0_c1)
+        .await
+        .map_err(|e| format!("Could not send C0 and C1: {}", e))?;
+
+    // S0 + S1 + S2
+
+    let mut s0_s1_s2: Vec<u8> = vec![0; 1 + RTMP_HANDSHAKE_SIZE * 2];
+
+    match tokio::time::timeout(
+        Duration::from_secs(RTMP_PING_TIMEOUT),
+        stream.read_exact(&mut s0_s1_s2),
+    )
+    .await
+    {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => return Err(format!("Could not read S0, S1 and S2: {}", e)),
+        Err(_) => return Err("Timed out reading S0, S1 and S2".to_string()),
+    }
+
+    if s0_s1_s2[0] != RTMP_VERSION {
+        return Err(format!("Unsupported RTMP version: {}", s0_s1_s2[0]));
+    }
+
+    // C2: Echo of S1
+
+    stream
+        .write_all(&s0_s1_s2[1..1 + RTMP_HANDSHAKE_SIZE])
+        .await
+        .map_err(|e| format!("Could not send C2: {}", e))
+}
+
+/// Makes the connect command message
+///
+/// # Arguments
+///
+/// * `app` - The application (channel)
+/// * `tc_url` - The URL of the server
+pub fn rtmp_make_client_connect_message(app: &str, tc_url: &str) -> Vec<u8> {
+    let mut cmd = RtmpCommand::new("connect".to_string());
+
+    cmd.set_argument(
+        "transId".to_string(),
+        AMF0Value::Number {
+            value: CLUSTER_CLIENT_CONNECT_TRANS_ID as f64,
+        },
+    );
+
+    let mut cmd_obj: HashMap<String, AMF0Value> = HashMap::new();
+
+    cmd_obj.insert(
+        "app".to_string(),
+        AMF0Value::String {
+            value: app.to_string(),
+        },
+    );
+    cmd_obj.insert(
+        "tcUrl".to_string(),
+        AMF0Value::String {
+            value: tc_url.to_string(),
+        },
+    );
+    cmd_obj.insert(
+        "flashVer".to_string(),
+        AMF0Value::String {
+            value: format!("rtmp-server-rs/{}", env!("CARGO_PKG_VERSION")),
+        },
+    );
+
+    cmd.set_argument(
+        "cmdObj".to_string(),
+        AMF0Value::Object {
+            properties: cmd_obj,
+        },
+    );
+
+    rtmp_make_invoke_message(&cmd, 0, RTMP_OBJECT_ENCODING_AMF0, RTMP_MIN_CHUNK_SIZE)
+}
+
+/// Makes the createStream command message
+pub fn rtmp_make_client_create_stream_message() -> Vec<u8> {
+    let mut cmd = RtmpCommand::new("createStream".to_string());
+
+    cmd.set_argument(
+        "transId".to_string(),
+        AMF0Value::Number {
+            value: CLUSTER_CLIENT_CREATE_STREAM_TRANS_ID as f64,
+        },
+    );
+    cmd.set_argument("cmdObj".to_string(), AMF0Value::Null);
+
+    rtmp_make_invoke_message(&cmd, 0, RTMP_OBJECT_ENCODING_AMF0, RTMP_MIN_CHUNK_SIZE)
+}
+
+/// Makes the play command message, to play the live stream
+///
+/// # Arguments
+///
+/// * `stream_id` - The stream ID returned by createStream
+/// * `stream_name` - The stream name (key)
+pub fn rtmp_make_client_play_message(stream_id: u32, stream_name: &str) -> Vec<u8> {
+    let mut cmd = RtmpCommand::new("play".to_string());
+
+    cmd.set_argument("transId".to_string(), AMF0Value::Number { value: 0.0 });
+    cmd.set_argument("cmdObj".to_string(), AMF0Value::Null);
+    cmd.set_argument(
+        "streamName".to_string(),
+        AMF0Value::String {
+            value: stream_name.to_string(),
+        },
+    );
+    cmd.set_argument(
+        "start".to_string(),
+        AMF0Value::Number {
+            value: RTMP_PLAY_START_LIVE as f64,
+        },
+    );
+
+    rtmp_make_invoke_message(
+        &cmd,
+        stream_id,
+        RTMP_OBJECT_ENCODING_AMF0,
+        RTMP_MIN_CHUNK_SIZE,
+    )
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{log::Logger, rtmp::generate_s0_s1_s2};
+
+    #[tokio::test]
+    async fn test_rtmp_client_handshake() {
+        let (mut client, mut server) = tokio::io::duplex(8 * RTMP_HANDSHAKE_SIZE);
+
+        let server_task = tokio::spawn(async move {
+            let mut c0_c1: Vec<u8> = vec![0; 1 + RTMP_HANDSHAKE_SIZE];
+            server.read_exact(&mut c0_c1).await.unwrap();
+
+            assert_eq!(c0_c1[0], RTMP_VERSION);
+
+            let s0_s1_s2 = generate_s0_s1_s2(&c0_c1[1..], &Logger::new_disabled()).unwrap();
+            server.write_all(&s0_s1_s2).await.unwrap();
+
+            let mut c2: Vec<u8> = vec![0; RTMP_HANDSHAKE_SIZE];
+            server.read_exact(&mut c2).await.unwrap();
+
+            assert_eq!(c2, s0_s1_s2[1..1 + RTMP_HANDSHAKE_SIZE]);
+        });
+
+        rtmp_client_handshake(&mut client).await.unwrap();
+
+        server_task.await.unwrap();
+    }
+
+    #[test]
+    fn test_rtmp_client_commands() {
+        // Single chunk: 12 bytes of header + payload
+        let msg = rtmp_make_client_play_message(1, "key");
+        let cmd = RtmpCommand::decode(&msg[12..]).unwrap();
+
+        assert_eq!(cmd.cmd, "play");
+        assert_eq!(cmd.get_argument("streamName").unwrap().get_string(), "key");
+        assert_eq!(
+            cmd.get_argument("start").unwrap().get_integer(),
+            RTMP_PLAY_START_LIVE
+        );
+    }
+}
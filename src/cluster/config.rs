@@ -0,0 +1,80 @@
+// Clustering configuration
+
+use crate::{
+    log::Logger,
+    log_error,
+    utils::{get_env_string, get_env_u32},
+};
+
+/// Clustering configuration
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+#[derive(Clone)]
+pub struct ClusterConfiguration {
+    /// Address the other nodes use to pull the streams published on this node (host:port)
+    pub node_address: String,
+
+    /// Prefix of the keys of the publish registry
+    pub key_prefix: String,
+
+    /// Time to live of the entries of the publish registry (seconds)
+    pub registry_ttl_seconds: u32,
+
+    /// Max time to wait for a stream pulled from another node to start (seconds)
+    pub pull_timeout_seconds: u32,
+
+    /// Time to keep pulling a stream with no players (seconds)
+    pub pull_idle_seconds: u32,
+}
+
+impl ClusterConfiguration {
+    /// Loads clustering configuration
+    /// from environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<ClusterConfiguration, ()> {
+        let node_address = get_env_string("CLUSTER_NODE_ADDRESS", "");
+
+        if node_address.is_empty() {
+            log_error!(
+                logger,
+                "CLUSTER_NODE_ADDRESS is required to use the clustering mode"
+            );
+            return Err(());
+        }
+
+        let key_prefix = ClusterConfiguration::load_key_prefix_from_env();
+
+        let registry_ttl_seconds = get_env_u32("CLUSTER_REGISTRY_TTL", 30);
+
+        if registry_ttl_seconds < 3 {
+            log_error!(
+                logger,
+                format!(
+                    "CLUSTER_REGISTRY_TTL has an invalid value: {}. Min: 3",
+                    registry_ttl_seconds
+                )
+            );
+            return Err(());
+        }
+
+        let pull_timeout_seconds = get_env_u32("CLUSTER_PULL_TIMEOUT", 10).max(1);
+
+        let pull_idle_seconds = get_env_u32("CLUSTER_PULL_IDLE_TIMEOUT", 30);
+
+        Ok(ClusterConfiguration {
+            node_address,
+            key_prefix,
+            registry_ttl_seconds,
+            pull_timeout_seconds,
+            pull_idle_seconds,
+        })
+    }
+
+    /// Loads the prefix of the keys of the publish registry
+    /// from environment variables
+    pub fn load_key_prefix_from_env() -> String {
+        get_env_string("CLUSTER_KEY_PREFIX", "rtmp_cluster:")
+    }
+}
@@ -0,0 +1,19 @@
+// Clustering mode: streams published on other nodes are pulled on demand
+
+mod client;
+mod config;
+mod node;
+mod pull;
+#[cfg(feature = "redis")]
+mod registry;
+#[cfg(not(feature = "redis"))]
+mod registry_disabled;
+
+pub use client::*;
+pub use config::*;
+pub use node::*;
+pub use pull::*;
+#[cfg(feature = "redis")]
+pub use registry::*;
+#[cfg(not(feature = "redis"))]
+pub use registry_disabled::*;
@@ -0,0 +1,271 @@
+// Status of the node in the cluster
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::sync::{watch, Mutex};
+
+use crate::{
+    log::Logger,
+    log_debug, log_error,
+    server::{RtmpServerContext, SessionIdGenerator},
+};
+
+use super::{ClusterConfiguration, ClusterRegistry};
+
+/// First ID of the pull sessions.
+/// Pulls use a separate range, so their IDs never collide with the client sessions.
+const CLUSTER_PULL_FIRST_ID: u64 = 1 << 62;
+
+/// State of a stream pulled from another node
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClusterPullState {
+    /// Connecting to the node publishing the stream
+    Connecting,
+
+    /// The stream is being published locally
+    Publishing,
+
+    /// The pull ended
+    Ended,
+}
+
+/// Result of registering a pull for a channel
+pub enum ClusterPullRegistration {
+    /// No pull existed for the channel. The caller must start it,
+    /// updating the state with the sender.
+    New(watch::Sender<ClusterPullState>),
+
+    /// A pull already exists for the channel
+    Existing(watch::Receiver<ClusterPullState>),
+}
+
+/// Node of the cluster
+pub struct ClusterNode {
+    /// Clustering configuration
+    pub config: ClusterConfiguration,
+
+    /// Publish registry
+    pub registry: ClusterRegistry,
+
+    /// Streams being pulled from other nodes (channel -> state)
+    pulls: Mutex<HashMap<String, watch::Receiver<ClusterPullState>>>,
+
+    /// ID generator for the pull sessions
+    pull_id_generator: SessionIdGenerator,
+}
+
+impl ClusterNode {
+    /// Creates new ClusterNode
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The clustering configuration
+    /// * `registry` - The publish registry
+    pub fn new(config: ClusterConfiguration, registry: ClusterRegistry) -> ClusterNode {
+        ClusterNode {
+            config,
+            registry,
+            pulls: Mutex::new(HashMap::new()),
+            pull_id_generator: SessionIdGenerator::new_starting_at(CLUSTER_PULL_FIRST_ID),
+        }
+    }
+
+    /// Generates an ID for a pull session
+    pub fn generate_pull_id(&self) -> u64 {
+        self.pull_id_generator.generate_id()
+    }
+
+    /// Registers a pull for a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn add_pull(&self, channel: &str) -> ClusterPullRegistration {
+        let mut pulls = self.pulls.lock().await;
+
+        if let Some(receiver) = pulls.get(channel) {
+            return ClusterPullRegistration::Existing(receiver.clone());
+        }
+
+        let (sender, receiver) = watch::channel(ClusterPullState::Connecting);
+
+        pulls.insert(channel.to_string(), receiver);
+
+        ClusterPullRegistration::New(sender)
+    }
+
+    /// Removes the pull of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn remove_pull(&self, channel: &str) {
+        self.pulls.lock().await.remove(channel);
+    }
+
+    /// Checks if a channel is being pulled from another node
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn is_pulling(&self, channel: &str) -> bool {
+        self.pulls.lock().await.contains_key(channel)
+    }
+
+    /// Registers a channel as published on this node
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `channel` - The channel
+    pub async fn register_channel(&self, logger: &Logger, channel: &str) {
+        if let Err(e) = self.registry.register(channel).await {
+            log_error!(
+                logger,
+                format!(
+                    "Could not register channel {} in the cluster: {}",
+                    channel, e
+                )
+            );
+        }
+    }
+
+    /// Removes a channel from the publish registry
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `channel` - The channel
+    pub async fn unregister_channel(&self, logger: &Logger, channel: &str) {
+        if let Err(e) = self.registry.unregister(channel).await {
+            log_error!(
+                logger,
+                format!(
+                    "Could not unregister channel {} from the cluster: {}",
+                    channel, e
+                )
+            );
+        }
+    }
+}
+
+/// Spawns a task to periodically refresh the entries
+/// of the channels published on this node in the publish registry,
+/// so they do not expire while the streams are alive.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `cluster` - The cluster node
+pub fn spawn_task_cluster_registry_refresh(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    cluster: Arc<ClusterNode>,
+) {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs((cluster.config.registry_ttl_seconds / 3) as u64);
+
+        loop {
+            tokio::time::sleep(interval).await;
+
+            // Find the channels published on this node
+
+            let mut channels: Vec<String> = Vec::new();
+
+            for shard in server_context.status.shards() {
+                let shard_channels: Vec<_> = shard
+                    .lock()
+                    .await
+                    .channels
+                    .iter()
+                    .map(|(channel, channel_mu)| (channel.clone(), channel_mu.clone()))
+                    .collect();
+
+                for (channel, channel_mu) in shard_channels {
+                    let channel_status = channel_mu.lock().await;
+
+                    if channel_status.has_stream() && channel_status.relay_origin.is_none() {
+                        channels.push(channel);
+                    }
+                }
+            }
+
+            log_debug!(
+                logger,
+                format!("Refreshing {} channels in the registry", channels.len())
+            );
+
+            for channel in channels {
+                if cluster.is_pulling(&channel).await {
+                    continue;
+                }
+
+                cluster.register_channel(&logger, &channel).await;
+            }
+        }
+    });
+}
+
+// Tests
+
+// The registry of the tests needs the redis feature
+#[cfg(all(test, feature = "redis"))]
+mod tests {
+    use super::*;
+
+    fn make_node() -> ClusterNode {
+        let config = ClusterConfiguration {
+            node_address: "127.0.0.1:1935".to_string(),
+            key_prefix: "rtmp_cluster:".to_string(),
+            registry_ttl_seconds: 30,
+            pull_timeout_seconds: 10,
+            pull_idle_seconds: 30,
+        };
+
+        let registry = ClusterRegistry::new("redis://127.0.0.1:6379", &config).unwrap();
+
+        ClusterNode::new(config, registry)
+    }
+
+    #[tokio::test]
+    async fn test_cluster_pulls() {
+        let node = make_node();
+
+        assert!(node.generate_pull_id() >= CLUSTER_PULL_FIRST_ID);
+
+        let sender = match node.add_pull("channel").await {
+            ClusterPullRegistration::New(sender) => sender,
+            ClusterPullRegistration::Existing(_) => panic!("Pull should be new"),
+        };
+
+        assert!(node.is_pulling("channel").await);
+
+        // A second player waits for the same pull
+
+        let mut receiver = match node.add_pull("channel").await {
+            ClusterPullRegistration::New(_) => panic!("Pull should exist"),
+            ClusterPullRegistration::Existing(receiver) => receiver,
+        };
+
+        assert_eq!(*receiver.borrow(), ClusterPullState::Connecting);
+
+        sender.send(ClusterPullState::Publishing).unwrap();
+
+        assert_eq!(
+            *receiver
+                .wait_for(|s| *s != ClusterPullState::Connecting)
+                .await
+                .unwrap(),
+            ClusterPullState::Publishing
+        );
+
+        node.remove_pull("channel").await;
+
+        assert!(!node.is_pulling("channel").await);
+        assert!(matches!(
+            node.add_pull("channel").await,
+            ClusterPullRegistration::New(_)
+        ));
+    }
+}
@@ -0,0 +1,688 @@
+// Logic to pull streams published on other nodes
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::{mpsc::Receiver, watch, Mutex},
+};
+
+use crate::{
+    log::Logger,
+    log_debug, log_info, log_warning,
+    rtmp::{
+        rtmp_build_metadata, rtmp_make_ack, RtmpCommand, RtmpData, RtmpInputPacketBuffer,
+        RtmpPacket, StreamMetadata, RTMP_TYPE_DATA, RTMP_TYPE_FLEX_MESSAGE, RTMP_TYPE_INVOKE,
+    },
+    server::{
+        remove_publisher, set_channel_metadata, set_publisher, try_clear_channel,
+        PublishAuthorization, PublishStreamFlags, RtmpServerContext,
+    },
+    session::{
+        handle_rtmp_packet, read_rtmp_chunk_to_buffer, session_write_bytes,
+        PublishCongestionDetector, RtmpSessionMessage, RtmpSessionPublishStreamStatus,
+        RtmpSessionReadStatus, RtmpSessionStatus, SessionBandwidth, SessionObjectEncoding,
+        SessionOutChunkSize, SessionOutboundAck, SessionPanicTracker, SessionPlayerLag,
+        SessionReadThreadContext,
+    },
+    trace::SessionTraceTap,
+    utils::generate_uuid_v4,
+};
+
+use super::{
+    rtmp_client_handshake, rtmp_make_client_connect_message,
+    rtmp_make_client_create_stream_message, rtmp_make_client_play_message, ClusterNode,
+    ClusterPullRegistration, ClusterPullState, CLUSTER_CLIENT_CONNECT_TRANS_ID,
+    CLUSTER_CLIENT_CREATE_STREAM_TRANS_ID,
+};
+
+/// Interval to check if the pulled stream has players
+const PULL_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pulls a channel from the node of the cluster publishing it,
+/// so it can be played on this node.
+/// If the channel is already being pulled, waits for the pull to start.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `channel` - The channel
+/// * `key` - The channel key provided by the player
+///
+/// # Return value
+///
+/// Returns true if the stream is being pulled and published on this node
+pub async fn cluster_pull_channel(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    key: &str,
+) -> bool {
+    let cluster = match &server_context.cluster {
+        Some(c) => c.clone(),
+        None => return false,
+    };
+
+    let mut state_receiver = match cluster.add_pull(channel).await {
+        ClusterPullRegistration::Existing(receiver) => receiver,
+        ClusterPullRegistration::New(state_sender) => {
+            // Find the node publishing the channel
+
+            let origin = match cluster.registry.lookup(channel).await {
+                Ok(Some(o)) => o,
+                Ok(None) => {
+                    cluster.remove_pull(channel).await;
+                    return false;
+                }
+                Err(e) => {
+                    log_warning!(
+                        logger,
+                        format!(
+                            "Could not look up channel {} in the cluster: {}",
+                            channel, e
+                        )
+                    );
+
+                    cluster.remove_pull(channel).await;
+                    return false;
+                }
+            };
+
+            let receiver = state_sender.subscribe();
+
+            spawn_task_cluster_pull(
+                logger.make_child_logger(&format!("[CLUSTER:PULL] [{}] ", channel)),
+                server_context.clone(),
+                cluster.clone(),
+                channel.to_string(),
+                key.to_string(),
+                origin,
+                state_sender,
+            );
+
+            receiver
+        }
+    };
+
+    // Wait for the pull to start
+
+    let wait_res = tokio::time::timeout(
+        Duration::from_secs(cluster.config.pull_timeout_seconds as u64),
+        state_receiver.wait_for(|s| *s != ClusterPullState::Connecting),
+    )
+    .await;
+
+    match wait_res {
+        Ok(Ok(s)) => *s == ClusterPullState::Publishing,
+        _ => false,
+    }
+}
+
+/// Spawns a task to pull a channel from another node
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `cluster` - The cluster node
+/// * `channel` - The channel
+/// * `key` - The channel key
+/// * `origin` - Address of the node publishing the channel
+/// * `state_sender` - Sender to update the state of the pull
+fn spawn_task_cluster_pull(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    cluster: Arc<ClusterNode>,
+    channel: String,
+    key: String,
+    origin: String,
+    state_sender: watch::Sender<ClusterPullState>,
+) {
+    tokio::spawn(async move {
+        let pull_id = cluster.generate_pull_id();
+
+        log_info!(logger, format!("Pulling channel from {}", origin));
+
+        let mut pull = ClusterPull {
+            logger: &logger,
+            server_context: server_context.clone(),
+            cluster: &cluster,
+            channel: &channel,
+            key: &key,
+            origin: &origin,
+            state_sender: &state_sender,
+        };
+
+        match pull.run(pull_id).await {
+            Ok(()) => {
+                log_info!(logger, "Pull ended");
+            }
+            Err(e) => {
+                log_info!(logger, format!("Pull ended: {}", e));
+            }
+        }
+
+        // Cleanup
+
+        remove_publisher(
+            &logger,
+            &server_context,
+            &channel,
+            pull_id,
+            &key,
+            false,
+            None,
+        )
+        .await;
+        try_clear_channel(&server_context, &channel).await;
+
+        cluster.remove_pull(&channel).await;
+
+        _ = state_sender.send(ClusterPullState::Ended);
+    });
+}
+
+/// Stream being pulled from another node
+struct ClusterPull<'a> {
+    /// The logger
+    logger: &'a Logger,
+
+    /// The server context
+    server_context: RtmpServerContext,
+
+    /// The cluster node
+    cluster: &'a ClusterNode,
+
+    /// The channel
+    channel: &'a str,
+
+    /// The channel key
+    key: &'a str,
+
+    /// Address of the node publishing the channel
+    origin: &'a str,
+
+    /// Sender to update the state of the pull
+    state_sender: &'a watch::Sender<ClusterPullState>,
+}
+
+impl ClusterPull<'_> {
+    /// Runs the pull, until the stream ends
+    ///
+    /// # Arguments
+    ///
+    /// * `pull_id` - ID of the pull session
+    async fn run(&mut self, pull_id: u64) -> Result<(), String> {
+        // Connect
+
+        let connect_timeout = Duration::from_secs(self.cluster.config.pull_timeout_seconds as u64);
+
+        let mut stream =
+            match tokio::time::timeout(connect_timeout, TcpStream::connect(self.origin)).await {
+                Ok(Ok(s)) => s,
+                Ok(Err(e)) => return Err(format!("Could not connect: {}", e)),
+                Err(_) => return Err("Connection timed out".to_string()),
+            };
+
+        let ip = stream
+            .peer_addr()
+            .map(|a| a.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        rtmp_client_handshake(&mut stream).await?;
+
+        let (mut read_stream, write_stream) = stream.into_split();
+        let write_stream = Mutex::new(write_stream);
+
+        self.write(
+            &write_stream,
+            &rtmp_make_client_connect_message(
+                self.channel,
+                &format!("rtmp://{}/{}", self.origin, self.channel),
+            ),
+        )
+        .await?;
+
+        // Prepare the context of the publisher session
+
+        let (msg_sender, mut msg_receiver) = tokio::sync::mpsc::channel::<RtmpSessionMessage>(
+            self.server_context.config.msg_buffer_size,
+        );
+
+        let mut session_context = SessionReadThreadContext {
+            id: pull_id,
+            ip,
+            status: Arc::new(Mutex::new(RtmpSessionStatus::new())),
+            publish_status: Arc::new(Mutex::new(RtmpSessionPublishStreamStatus::new())),
+            bandwidth: Arc::new(SessionBandwidth::new()),
+            player_lag: Arc::new(SessionPlayerLag::new()),
+            trace: Arc::new(SessionTraceTap::new()),
+            out_chunk_size: Arc::new(SessionOutChunkSize::new()),
+            outbound_ack: Arc::new(SessionOutboundAck::new()),
+            object_encoding: Arc::new(SessionObjectEncoding::new()),
+            client_cert: None,
+            client_geo: None,
+            panic_tracker: Arc::new(SessionPanicTracker::new()),
+            session_msg_sender: msg_sender,
+            read_status: RtmpSessionReadStatus::new(),
+            publish_congestion: PublishCongestionDetector::new(0, 0, 0),
+        };
+
+        session_context.set_channel(self.channel).await;
+
+        let mut in_packets =
+            RtmpInputPacketBuffer::new(self.server_context.config.max_chunk_streams);
+
+        // Read packets
+
+        let start_deadline = Instant::now() + connect_timeout;
+        let mut publishing = false;
+        let mut last_idle_check = Instant::now();
+        let mut idle_since: Option<Instant> = None;
+
+        loop {
+            if publishing {
+                check_kill_message(&mut msg_receiver)?;
+
+                if last_idle_check.elapsed() >= PULL_IDLE_CHECK_INTERVAL {
+                    last_idle_check = Instant::now();
+
+                    if self.is_idle(&session_context, &mut idle_since).await {
+                        return Err("No players left".to_string());
+                    }
+                }
+            }
+
+            let read_res = if publishing {
+                self.read_packet(
+                    &mut session_context,
+                    &mut read_stream,
+                    &write_stream,
+                    &mut in_packets,
+                )
+                .await
+            } else {
+                match tokio::time::timeout_at(
+                    start_deadline.into(),
+                    self.read_packet(
+                        &mut session_context,
+                        &mut read_stream,
+                        &write_stream,
+                        &mut in_packets,
+                    ),
+                )
+                .await
+                {
+                    Ok(r) => r,
+                    Err(_) => return Err("Timed out waiting for the stream to start".to_string()),
+                }
+            };
+
+            let packet = match read_res? {
+                Some(p) => p,
+                None => continue,
+            };
+
+            match packet.header.packet_type {
+                RTMP_TYPE_INVOKE | RTMP_TYPE_FLEX_MESSAGE => {
+                    let cmd = decode_command(&packet)?;
+
+                    if self
+                        .handle_command(&mut session_context, &write_stream, &cmd)
+                        .await?
+                    {
+                        publishing = true;
+                    }
+                }
+                RTMP_TYPE_DATA => {
+                    self.handle_data(&mut session_context, &write_stream, &packet)
+                        .await?;
+                }
+                _ => {
+                    if !handle_rtmp_packet(
+                        self.logger,
+                        &mut self.server_context,
+                        &mut session_context,
+                        &write_stream,
+                        &packet,
+                    )
+                    .await
+                    {
+                        return Err("Packet handing failed".to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes bytes to the node publishing the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `write_stream` - The stream to write to the node
+    /// * `bytes` - The bytes to write
+    async fn write(
+        &self,
+        write_stream: &Mutex<OwnedWriteHalf>,
+        bytes: &[u8],
+    ) -> Result<(), String> {
+        session_write_bytes(write_stream, bytes)
+            .await
+            .map_err(|e| format!("Could not send message: {}", e))
+    }
+
+    /// Reads a chunk, sending acknowledgements as requested by the node
+    ///
+    /// # Arguments
+    ///
+    /// * `session_context` - The session context
+    /// * `read_stream` - The stream to read from the node
+    /// * `write_stream` - The stream to write to the node
+    /// * `in_packets` - Buffer of input packets
+    ///
+    /// # Return value
+    ///
+    /// Returns the packet, if the chunk completed it
+    async fn read_packet(
+        &self,
+        session_context: &mut SessionReadThreadContext,
+        read_stream: &mut OwnedReadHalf,
+        write_stream: &Mutex<OwnedWriteHalf>,
+        in_packets: &mut RtmpInputPacketBuffer,
+    ) -> Result<Option<RtmpPacket>, String> {
+        let chunk = read_rtmp_chunk_to_buffer(
+            self.logger,
+            read_stream,
+            in_packets,
+            session_context.read_status.in_chunk_size,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if chunk.message_start {
+            session_context.set_clock(chunk.clock).await;
+        }
+
+        // ACK
+
+        let read_status = &mut session_context.read_status;
+
+        read_status.in_ack_size = read_status.in_ack_size.wrapping_add(chunk.size);
+
+        if read_status.in_ack_size >= 0xf0000000 {
+            read_status.in_ack_size = 0;
+            read_status.in_last_ack = 0;
+        }
+
+        if read_status.ack_size > 0
+            && read_status.in_ack_size - read_status.in_last_ack >= read_status.ack_size
+        {
+            read_status.in_last_ack = read_status.in_ack_size;
+
+            self.write(write_stream, &rtmp_make_ack(read_status.in_ack_size))
+                .await?;
+        }
+
+        session_context.bandwidth.input.add(chunk.size);
+
+        Ok(chunk.packet)
+    }
+
+    /// Handles a command sent by the node publishing the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `session_context` - The session context
+    /// * `write_stream` - The stream to write to the node
+    /// * `cmd` - The command
+    ///
+    /// # Return value
+    ///
+    /// Returns true if the stream started, so it is published on this node
+    async fn handle_command(
+        &mut self,
+        session_context: &mut SessionReadThreadContext,
+        write_stream: &Mutex<OwnedWriteHalf>,
+        cmd: &RtmpCommand,
+    ) -> Result<bool, String> {
+        let trans_id = cmd
+            .get_argument("transId")
+            .map(|t| t.get_integer())
+            .unwrap_or(0);
+
+        match cmd.cmd.as_str() {
+            "_result" => {
+                if trans_id == CLUSTER_CLIENT_CONNECT_TRANS_ID {
+                    self.write(write_stream, &rtmp_make_client_create_stream_message())
+                        .await?;
+                } else if trans_id == CLUSTER_CLIENT_CREATE_STREAM_TRANS_ID {
+                    let stream_id = cmd
+                        .get_argument("info")
+                        .map(|i| i.get_integer())
+                        .unwrap_or(0) as u32;
+
+                    self.write(
+                        write_stream,
+                        &rtmp_make_client_play_message(stream_id, self.key),
+                    )
+                    .await?;
+                }
+
+                Ok(false)
+            }
+            "_error" => Err(format!("Command rejected: {}", get_status_description(cmd))),
+            "onStatus" => {
+                let code = cmd
+                    .get_argument("info")
+                    .and_then(|i| i.get_object_property("code"))
+                    .map(|c| c.get_string().to_string())
+                    .unwrap_or_default();
+
+                let logger = self.logger;
+                log_debug!(logger, format!("Status: {}", code));
+
+                match code.as_str() {
+                    "NetStream.Play.Start" => {
+                        self.start_publishing(session_context).await?;
+                        Ok(true)
+                    }
+                    "NetStream.Play.Stop"
+                    | "NetStream.Play.UnpublishNotify"
+                    | "NetStream.Play.StreamNotFound"
+                    | "NetStream.Play.BadName"
+                    | "NetStream.Play.BadConnection"
+                    | "NetStream.Play.Failed" => {
+                        Err(format!("{}: {}", code, get_status_description(cmd)))
+                    }
+                    _ => Ok(false),
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Sets the pull as the publisher of the channel on this node
+    ///
+    /// # Arguments
+    ///
+    /// * `session_context` - The session context
+    async fn start_publishing(
+        &mut self,
+        session_context: &mut SessionReadThreadContext,
+    ) -> Result<(), String> {
+        if session_context.is_publisher().await {
+            return Ok(());
+        }
+
+        session_context.set_publisher(0).await;
+
+        let authorization = PublishAuthorization {
+            stream_id: format!("cluster:{}", self.origin),
+            max_players: None,
+            flags: None,
+        };
+
+        if !set_publisher(
+            &self.server_context,
+            session_context,
+            self.channel,
+            self.key,
+            &generate_uuid_v4(),
+            &authorization,
+            &PublishStreamFlags::default(),
+        )
+        .await
+        {
+            return Err("The channel is already being published on this node".to_string());
+        }
+
+        if let Some(channel_status) = &session_context.read_status.channel_status {
+            channel_status.lock().await.relay_origin = Some(self.origin.to_string());
+        }
+
+        _ = self.state_sender.send(ClusterPullState::Publishing);
+
+        let logger = self.logger;
+        log_info!(logger, "Stream started");
+
+        Ok(())
+    }
+
+    /// Handles a data packet sent by the node publishing the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `session_context` - The session context
+    /// * `write_stream` - The stream to write to the node
+    /// * `packet` - The packet
+    async fn handle_data(
+        &mut self,
+        session_context: &mut SessionReadThreadContext,
+        write_stream: &Mutex<OwnedWriteHalf>,
+        packet: &RtmpPacket,
+    ) -> Result<(), String> {
+        let payload = &packet.payload[..packet.header.length.min(packet.payload.len())];
+
+        let data = match RtmpData::decode(payload) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Could not decode RTMP data: {}", e)),
+        };
+
+        if data.tag != "onMetaData" {
+            // Other data, handled as if it was sent by a publisher
+
+            if !handle_rtmp_packet(
+                self.logger,
+                &mut self.server_context,
+                session_context,
+                write_stream,
+                packet,
+            )
+            .await
+            {
+                return Err("Packet handing failed".to_string());
+            }
+
+            return Ok(());
+        }
+
+        // The players receive the metadata as onMetaData,
+        // instead of the @setDataFrame sent by the publisher
+
+        set_channel_metadata(
+            &self.server_context,
+            self.channel,
+            session_context.id,
+            Arc::new(rtmp_build_metadata(&data)),
+            StreamMetadata::from_data(&data),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Checks if the pulled stream has no players
+    ///
+    /// # Arguments
+    ///
+    /// * `session_context` - The session context
+    /// * `idle_since` - Time since the stream has no players
+    ///
+    /// # Return value
+    ///
+    /// Returns true if the stream had no players for longer than the configured idle time
+    async fn is_idle(
+        &self,
+        session_context: &SessionReadThreadContext,
+        idle_since: &mut Option<Instant>,
+    ) -> bool {
+        let has_players = match &session_context.read_status.channel_status {
+            Some(c) => !c.lock().await.players.is_empty(),
+            None => false,
+        };
+
+        if has_players {
+            *idle_since = None;
+            return false;
+        }
+
+        let since = *idle_since.get_or_insert_with(Instant::now);
+
+        since.elapsed() >= Duration::from_secs(self.cluster.config.pull_idle_seconds as u64)
+    }
+}
+
+/// Checks if the pull session was killed
+///
+/// # Arguments
+///
+/// * `msg_receiver` - Receiver of the session messages
+fn check_kill_message(msg_receiver: &mut Receiver<RtmpSessionMessage>) -> Result<(), String> {
+    while let Ok(msg) = msg_receiver.try_recv() {
+        if let RtmpSessionMessage::Kill { reason, .. } = msg {
+            return Err(format!("Killed: {}", reason));
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes a command packet
+///
+/// # Arguments
+///
+/// * `packet` - The packet
+fn decode_command(packet: &RtmpPacket) -> Result<RtmpCommand, String> {
+    let offset: usize = if packet.header.packet_type == RTMP_TYPE_FLEX_MESSAGE {
+        1
+    } else {
+        0
+    };
+
+    if packet.header.length <= offset || packet.header.length > packet.payload.len() {
+        return Err("Invalid command packet length".to_string());
+    }
+
+    RtmpCommand::decode(&packet.payload[offset..packet.header.length])
+        .map_err(|e| format!("Could not decode RTMP command: {}", e))
+}
+
+/// Gets the description of a status or error command
+///
+/// # Arguments
+///
+/// * `cmd` - The command
+fn get_status_description(cmd: &RtmpCommand) -> String {
+    cmd.get_argument("info")
+        .and_then(|i| i.get_object_property("description"))
+        .map(|d| d.get_string().to_string())
+        .unwrap_or_default()
+}
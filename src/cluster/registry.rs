@@ -0,0 +1,218 @@
+// Publish registry, shared by the nodes of the cluster
+
+use std::time::Duration;
+
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisError, Script};
+use tokio::sync::Mutex;
+
+use super::ClusterConfiguration;
+
+/// Max time to wait for a registry operation
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Script to remove an entry, only if it was set by the node
+const UNREGISTER_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+else
+    return 0
+end
+"#;
+
+/// Registry of the channels published on each node, stored in Redis.
+/// Each entry maps a channel to the address of the node publishing it.
+pub struct ClusterRegistry {
+    /// Redis client
+    client: redis::Client,
+
+    /// Connection, created on demand
+    connection: Mutex<Option<MultiplexedConnection>>,
+
+    /// Prefix of the keys
+    key_prefix: String,
+
+    /// Address of this node
+    node_address: String,
+
+    /// Time to live of the entries (seconds)
+    ttl_seconds: u64,
+}
+
+impl ClusterRegistry {
+    /// Creates new ClusterRegistry
+    ///
+    /// # Arguments
+    ///
+    /// * `redis_url` - URL of the Redis server
+    /// * `config` - The clustering configuration
+    pub fn new(
+        redis_url: &str,
+        config: &ClusterConfiguration,
+    ) -> Result<ClusterRegistry, RedisError> {
+        Ok(ClusterRegistry {
+            client: redis::Client::open(redis_url)?,
+            connection: Mutex::new(None),
+            key_prefix: config.key_prefix.clone(),
+            node_address: config.node_address.clone(),
+            ttl_seconds: config.registry_ttl_seconds as u64,
+        })
+    }
+
+    /// Gets the key of the entry of a channel
+    fn channel_key(&self, channel: &str) -> String {
+        format!("{}{}", self.key_prefix, channel)
+    }
+
+    /// Gets the connection to Redis, connecting if needed
+    async fn get_connection(&self) -> Result<MultiplexedConnection, RedisError> {
+        let mut connection = self.connection.lock().await;
+
+        if let Some(c) = connection.as_ref() {
+            return Ok(c.clone());
+        }
+
+        let c = self.client.get_multiplexed_async_connection().await?;
+
+        *connection = Some(c.clone());
+
+        Ok(c)
+    }
+
+    /// Drops the connection after an error, so the next operation reconnects
+    async fn reset_connection(&self) {
+        *self.connection.lock().await = None;
+    }
+
+    /// Runs an operation with the connection, with a timeout
+    async fn run<T, F, Fut>(&self, op: F) -> Result<T, String>
+    where
+        F: FnOnce(MultiplexedConnection) -> Fut,
+        Fut: std::future::Future<Output = Result<T, RedisError>>,
+    {
+        let res = tokio::time::timeout(REGISTRY_TIMEOUT, async {
+            let connection = self.get_connection().await?;
+            op(connection).await
+        })
+        .await;
+
+        match res {
+            Ok(Ok(v)) => Ok(v),
+            Ok(Err(e)) => {
+                self.reset_connection().await;
+                Err(e.to_string())
+            }
+            Err(_) => {
+                self.reset_connection().await;
+                Err("Timed out".to_string())
+            }
+        }
+    }
+
+    /// Registers a channel as published on this node,
+    /// or refreshes the entry if already registered
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn register(&self, channel: &str) -> Result<(), String> {
+        let key = self.channel_key(channel);
+
+        self.run(|mut c| async move {
+            c.set_ex::<_, _, ()>(&key, &self.node_address, self.ttl_seconds)
+                .await
+        })
+        .await
+    }
+
+    /// Removes the entry of a channel, if it was registered by this node
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn unregister(&self, channel: &str) -> Result<(), String> {
+        let key = self.channel_key(channel);
+
+        self.run(|mut c| async move {
+            Script::new(UNREGISTER_SCRIPT)
+                .key(&key)
+                .arg(&self.node_address)
+                .invoke_async::<()>(&mut c)
+                .await
+        })
+        .await
+    }
+
+    /// Finds the node publishing a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    ///
+    /// # Return value
+    ///
+    /// Returns the address of the node, or None if the channel is not published
+    /// or it is published on this node
+    pub async fn lookup(&self, channel: &str) -> Result<Option<String>, String> {
+        let key = self.channel_key(channel);
+
+        let node_address: Option<String> =
+            self.run(|mut c| async move { c.get(&key).await }).await?;
+
+        Ok(node_address.filter(|a| !a.is_empty() && *a != self.node_address))
+    }
+}
+
+/// Lists the channels published in the cluster, reading the publish registry
+///
+/// # Arguments
+///
+/// * `redis_url` - URL of the Redis server
+/// * `key_prefix` - Prefix of the keys of the registry
+///
+/// # Return value
+///
+/// Returns the channels, sorted, with the address of the node publishing each one
+pub async fn list_cluster_channels(
+    redis_url: &str,
+    key_prefix: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let client = redis::Client::open(redis_url).map_err(|e| e.to_string())?;
+
+    let res = tokio::time::timeout(REGISTRY_TIMEOUT, async {
+        let mut c = client.get_multiplexed_async_connection().await?;
+
+        let mut keys: Vec<String> = Vec::new();
+        let mut keys_iter = c
+            .scan_match::<_, String>(format!("{}*", key_prefix))
+            .await?;
+
+        while let Some(key) = keys_iter.next_item().await {
+            keys.push(key);
+        }
+
+        drop(keys_iter);
+
+        let mut channels: Vec<(String, String)> = Vec::new();
+
+        for key in keys {
+            let node_address: Option<String> = c.get(&key).await?;
+
+            if let (Some(channel), Some(node_address)) =
+                (key.strip_prefix(key_prefix), node_address)
+            {
+                channels.push((channel.to_string(), node_address));
+            }
+        }
+
+        channels.sort();
+
+        Ok::<_, RedisError>(channels)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(channels)) => Ok(channels),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("Timed out".to_string()),
+    }
+}
@@ -0,0 +1,52 @@
+// Publish registry, when the server is built without the redis feature
+
+use super::ClusterConfiguration;
+
+/// Error of the registry operations
+const REGISTRY_DISABLED_ERROR: &str = "The server was built without the redis feature";
+
+/// Registry of the channels published on each node.
+/// Not available without the redis feature.
+pub struct ClusterRegistry {}
+
+impl ClusterRegistry {
+    /// Creates new ClusterRegistry
+    ///
+    /// # Arguments
+    ///
+    /// * `redis_url` - URL of the Redis server
+    /// * `config` - The clustering configuration
+    pub fn new(
+        _redis_url: &str,
+        _config: &ClusterConfiguration,
+    ) -> Result<ClusterRegistry, String> {
+        Err(REGISTRY_DISABLED_ERROR.to_string())
+    }
+
+    /// Registers a channel as published on this node
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn register(&self, _channel: &str) -> Result<(), String> {
+        Err(REGISTRY_DISABLED_ERROR.to_string())
+    }
+
+    /// Removes the entry of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn unregister(&self, _channel: &str) -> Result<(), String> {
+        Err(REGISTRY_DISABLED_ERROR.to_string())
+    }
+
+    /// Finds the node publishing a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub async fn lookup(&self, _channel: &str) -> Result<Option<String>, String> {
+        Err(REGISTRY_DISABLED_ERROR.to_string())
+    }
+}
@@ -0,0 +1,218 @@
+// Validation of the configuration, without starting the server
+
+use crate::{
+    cluster::ClusterConfiguration,
+    control::ControlServerConnectionConfig,
+    geoip::GeoIpDatabase,
+    log::Logger,
+    log_error,
+    redis::RedisConfiguration,
+    server::{check_open_files_limit, RtmpServerConfiguration},
+    slate::SlateSource,
+    utils::get_env_bool,
+};
+
+#[cfg(feature = "tls")]
+use crate::server::check_tls_certificates;
+
+/// Result of a check of the configuration
+enum ConfigCheckResult {
+    /// Valid configuration
+    Ok,
+
+    /// Invalid configuration (the errors are logged)
+    Error,
+
+    /// Not checked, with the reason
+    Skipped(&'static str),
+}
+
+/// Report of the configuration check
+struct ConfigCheckReport {
+    /// Name and result of each check
+    checks: Vec<(&'static str, ConfigCheckResult)>,
+}
+
+impl ConfigCheckReport {
+    /// Adds the result of a check
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the check
+    /// * `result` - Result of the check
+    fn add(&mut self, name: &'static str, result: Result<(), ()>) {
+        self.checks.push((
+            name,
+            match result {
+                Ok(_) => ConfigCheckResult::Ok,
+                Err(_) => ConfigCheckResult::Error,
+            },
+        ));
+    }
+
+    /// Adds a skipped check
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the check
+    /// * `reason` - Reason to skip the check
+    fn skip(&mut self, name: &'static str, reason: &'static str) {
+        self.checks.push((name, ConfigCheckResult::Skipped(reason)));
+    }
+
+    /// Counts the failed checks
+    fn error_count(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|(_, r)| matches!(r, ConfigCheckResult::Error))
+            .count()
+    }
+
+    /// Prints the report to the standard output
+    fn print(&self) {
+        println!("Configuration check:");
+
+        for (name, result) in &self.checks {
+            match result {
+                ConfigCheckResult::Ok => println!("    [OK]      {}", name),
+                ConfigCheckResult::Error => println!("    [ERROR]   {}", name),
+                ConfigCheckResult::Skipped(reason) => {
+                    println!("    [SKIPPED] {} ({})", name, reason)
+                }
+            }
+        }
+
+        match self.error_count() {
+            0 => println!("The configuration is valid"),
+            n => println!("The configuration is not valid. Failed checks: {}", n),
+        }
+    }
+}
+
+/// Loads and validates all the configuration, printing a report.
+/// The errors found are logged, as they would be when starting the server.
+/// Nothing is started, and no connection is made.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+///
+/// # Return value
+///
+/// Returns true if the configuration is valid
+pub async fn check_configuration(logger: &Logger) -> bool {
+    let mut report = ConfigCheckReport { checks: Vec::new() };
+
+    // Server configuration (including the IP ranges, the callbacks and the tenants)
+
+    match RtmpServerConfiguration::load_from_env(logger) {
+        Ok(server_config) => {
+            report.add("Server configuration", Ok(()));
+
+            report.add(
+                "Open files limit",
+                check_open_files_limit(logger, &server_config),
+            );
+
+            #[cfg(feature = "tls")]
+            if server_config.tls.is_enabled() {
+                report.add(
+                    "TLS certificates",
+                    check_tls_certificates(logger, &server_config.tls).await,
+                );
+            } else {
+                report.skip("TLS certificates", "TLS disabled");
+            }
+
+            #[cfg(not(feature = "tls"))]
+            report.skip("TLS certificates", "built without the tls feature");
+
+            if server_config.slate_file.is_empty() {
+                report.skip("Slate file", "SLATE_FILE not set");
+            } else {
+                report.add(
+                    "Slate file",
+                    SlateSource::load_from_file(&server_config.slate_file)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| {
+                            log_error!(logger, format!("Could not load slate: {}", e));
+                        }),
+                );
+            }
+
+            if server_config.geoip.is_enabled() {
+                report.add(
+                    "GeoIP databases",
+                    GeoIpDatabase::load(&server_config.geoip)
+                        .map(|_| ())
+                        .map_err(|e| {
+                            log_error!(logger, e);
+                        }),
+                );
+            } else {
+                report.skip(
+                    "GeoIP databases",
+                    "GEOIP_COUNTRY_DATABASE and GEOIP_ASN_DATABASE not set",
+                );
+            }
+        }
+        Err(_) => {
+            report.add("Server configuration", Err(()));
+            report.skip("Open files limit", "invalid server configuration");
+            report.skip("TLS certificates", "invalid server configuration");
+            report.skip("Slate file", "invalid server configuration");
+            report.skip("GeoIP databases", "invalid server configuration");
+        }
+    }
+
+    // Control server
+
+    if get_env_bool("CONTROL_USE", false) {
+        report.add(
+            "Control server",
+            ControlServerConnectionConfig::load_from_env(logger).map(|_| ()),
+        );
+    } else {
+        report.skip("Control server", "CONTROL_USE not enabled");
+    }
+
+    // Redis (used by the Redis commands and the clustering mode)
+
+    let cluster_enabled = get_env_bool("CLUSTER_USE", false);
+
+    if get_env_bool("REDIS_USE", false) || cluster_enabled {
+        #[cfg(feature = "redis")]
+        let redis_res = RedisConfiguration::load_from_env(logger).and_then(|redis_config| {
+            match redis::Client::open(redis_config.get_redis_url()) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    log_error!(logger, format!("Invalid Redis configuration: {}", e));
+                    Err(())
+                }
+            }
+        });
+
+        #[cfg(not(feature = "redis"))]
+        let redis_res = RedisConfiguration::load_from_env(logger).map(|_| ());
+
+        report.add("Redis", redis_res);
+    } else {
+        report.skip("Redis", "REDIS_USE and CLUSTER_USE not enabled");
+    }
+
+    // Clustering mode
+
+    if cluster_enabled {
+        report.add(
+            "Clustering",
+            ClusterConfiguration::load_from_env(logger).map(|_| ()),
+        );
+    } else {
+        report.skip("Clustering", "CLUSTER_USE not enabled");
+    }
+
+    report.print();
+
+    report.error_count() == 0
+}
@@ -1,6 +1,6 @@
 // Control client connection logic
 
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::Duration};
 
 use futures_util::StreamExt;
 use tokio::sync::Mutex;
@@ -10,12 +10,22 @@ use tungstenite::{client::IntoClientRequest, http::HeaderValue};
 use crate::{
     log::Logger,
     log_debug, log_error, log_info, log_trace, log_warning,
-    server::{kill_publisher, remove_all_publishers, RtmpServerContext},
+    record::{start_recording, stop_recording, RecordingInfo},
+    server::{
+        clear_gop_cache, generate_stored_channel_key, get_channel_bandwidth, get_channel_players,
+        inject_cue_point, kick_player, kill_publisher, kill_publishers_matching,
+        parse_config_setting_changes, parse_cue_point_parameters, remove_all_publishers,
+        revoke_stored_channel_key, rotate_channel_key, RtmpServerContext,
+    },
+    trace::SessionTraceFilter,
+    utils::ChannelSelector,
 };
 
 use super::{
     make_control_auth_token, spawn_task_control_client_heartbeat, ControlClientStatus,
-    ControlKeyValidationResponse, ControlServerConnectionConfig, ControlServerMessage,
+    ControlKeyValidationResponse, ControlProtocolNegotiation, ControlServerConnectionConfig,
+    ControlServerMessage, CONTROL_PROTOCOL_FORMAT_HEADER, CONTROL_PROTOCOL_VERSION,
+    CONTROL_PROTOCOL_VERSION_HEADER,
 };
 
 /// Timeout for read operations
@@ -76,6 +86,48 @@ pub fn spawn_task_control_client(
             },
         };
 
+        let protocol_version_header: HeaderValue = HeaderValue::from(CONTROL_PROTOCOL_VERSION);
+
+        let protocol_format_header: HeaderValue =
+            match ControlProtocolNegotiation::offered_formats(config.binary_frames)
+                .parse::<HeaderValue>()
+            {
+                Ok(v) => v,
+                Err(e) => {
+                    log_error!(
+                        logger,
+                        format!("Error creating protocol format header: {}", e)
+                    );
+
+                    return;
+                }
+            };
+
+        // Identity of the server node
+
+        let identity = &server_context.config.identity;
+
+        let mut identity_headers: Vec<(&'static str, HeaderValue)> = Vec::new();
+
+        for (name, value) in [
+            ("x-server-instance-id", identity.instance_id.as_str()),
+            ("x-server-id", identity.server_id.as_str()),
+            ("x-server-region", identity.region.as_str()),
+        ] {
+            if value.is_empty() {
+                continue;
+            }
+
+            match value.parse::<HeaderValue>() {
+                Ok(v) => identity_headers.push((name, v)),
+                Err(e) => {
+                    log_error!(logger, format!("Error creating {} header: {}", name, e));
+
+                    return;
+                }
+            }
+        }
+
         loop {
             // Prepare request
 
@@ -124,7 +176,23 @@ pub fn spawn_task_control_client(
                     .insert("x-ssl-use", external_ssl_header.clone());
             }
 
-            let (stream, _) = match connect_async(request).await {
+            for (name, value) in &identity_headers {
+                request.headers_mut().insert(*name, value.clone());
+            }
+
+            // Protocol negotiation
+
+            request.headers_mut().insert(
+                CONTROL_PROTOCOL_VERSION_HEADER,
+                protocol_version_header.clone(),
+            );
+
+            request.headers_mut().insert(
+                CONTROL_PROTOCOL_FORMAT_HEADER,
+                protocol_format_header.clone(),
+            );
+
+            let (stream, response) = match connect_async(request).await {
                 Ok((s, r)) => (s, r),
                 Err(e) => {
                     log_error!(logger, format!("Could not connect to the server: {}", e));
@@ -137,9 +205,33 @@ pub fn spawn_task_control_client(
                 }
             };
 
+            let protocol = match ControlProtocolNegotiation::from_response_headers(
+                response.headers(),
+                config.binary_frames,
+            ) {
+                Ok(p) => p,
+                Err(e) => {
+                    log_error!(logger, format!("Protocol negotiation failed: {}", e));
+
+                    // Wait
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+
+                    // Reconnect
+                    continue;
+                }
+            };
+
             // Connected, split the stream so multiple tasks can use it
 
-            log_info!(logger, format!("Connected: {}", &config.connection_url));
+            log_info!(
+                logger,
+                format!(
+                    "Connected: {} (protocol version: {}, format: {})",
+                    &config.connection_url,
+                    protocol.version,
+                    protocol.format.as_str()
+                )
+            );
 
             let (write_stream, mut read_stream) = stream.split();
 
@@ -147,7 +239,7 @@ pub fn spawn_task_control_client(
 
             // Set status
 
-            ControlClientStatus::set_connected(&status, write_stream_mu).await;
+            ControlClientStatus::set_connected(&status, write_stream_mu, protocol.format).await;
 
             // Spawn task for heartbeat messages
 
@@ -194,95 +286,251 @@ pub fn spawn_task_control_client(
                     }
                 };
 
-                match msg {
+                let msg_parsed = match msg {
                     tungstenite::Message::Text(utf8_bytes) => {
-                        let msg_parsed = ControlServerMessage::parse(&utf8_bytes);
+                        ControlServerMessage::parse(&utf8_bytes)
+                    }
+                    tungstenite::Message::Binary(bytes) => {
+                        match ControlServerMessage::parse_binary(&bytes) {
+                            Ok(m) => m,
+                            Err(e) => {
+                                log_warning!(logger, format!("Invalid binary message: {}", e));
+                                continue;
+                            }
+                        }
+                    }
+                    _ => {
+                        log_debug!(logger, "Unknown message type received from websocket");
+                        continue;
+                    }
+                };
 
-                        log_trace!(logger, format!("RECEIVED: {}", msg_parsed.serialize()));
+                log_trace!(logger, format!("RECEIVED: {}", msg_parsed.serialize()));
 
-                        match msg_parsed.msg_type.as_str() {
-                            "ERROR" => {
-                                log_error!(
+                match msg_parsed.msg_type.as_str() {
+                    "ERROR" => {
+                        log_error!(
+                            logger,
+                            format!(
+                                "Remote error. Code={} / Details: {}",
+                                msg_parsed.get_parameter("Error-Code").unwrap_or(""),
+                                msg_parsed.get_parameter("Error-Message").unwrap_or("")
+                            )
+                        );
+                    }
+                    "PUBLISH-ACCEPT" => {
+                        let request_id = match msg_parsed.get_parameter("Request-Id") {
+                            Some(req_id_str) => match str::parse::<u64>(req_id_str) {
+                                Ok(id) => id,
+                                Err(_) => {
+                                    log_warning!(logger, "Received a PUBLISH-ACCEPT message with an invalid Request-Id parameter.");
+                                    read_loop_continue = false;
+                                    continue;
+                                }
+                            },
+                            None => {
+                                log_error!(logger, "Received a PUBLISH-ACCEPT message with no Request-Id parameter.");
+                                read_loop_continue = false;
+                                continue;
+                            }
+                        };
+
+                        let stream_id = msg_parsed.get_parameter("Stream-Id").unwrap_or("");
+
+                        let max_players = msg_parsed
+                            .get_parameter("Max-Players")
+                            .and_then(|m| m.trim().parse::<u32>().ok());
+
+                        let flags = msg_parsed
+                            .get_parameter("Stream-Flags")
+                            .map(|f| f.to_string());
+
+                        ControlClientStatus::complete_request(
+                            &status,
+                            request_id,
+                            ControlKeyValidationResponse::Accepted {
+                                stream_id: stream_id.to_string(),
+                                max_players,
+                                flags,
+                            },
+                        )
+                        .await;
+                    }
+                    "PUBLISH-DENY" => {
+                        let request_id = match msg_parsed.get_parameter("Request-Id") {
+                            Some(req_id_str) => match str::parse::<u64>(req_id_str) {
+                                Ok(id) => id,
+                                Err(_) => {
+                                    log_warning!(logger, "Received a PUBLISH-DENY message with an invalid Request-Id parameter.");
+                                    read_loop_continue = false;
+                                    continue;
+                                }
+                            },
+                            None => {
+                                log_warning!(
                                     logger,
-                                    format!(
-                                        "Remote error. Code={} / Details: {}",
-                                        msg_parsed.get_parameter("Error-Code").unwrap_or(""),
-                                        msg_parsed.get_parameter("Error-Message").unwrap_or("")
-                                    )
+                                    "Received a PUBLISH-DENY message with no Request-Id parameter."
                                 );
+                                read_loop_continue = false;
+                                continue;
                             }
-                            "PUBLISH-ACCEPT" => {
-                                let request_id = match msg_parsed.get_parameter("Request-Id") {
-                                    Some(req_id_str) => match str::parse::<u64>(req_id_str) {
-                                        Ok(id) => id,
-                                        Err(_) => {
-                                            log_warning!(logger, "Received a PUBLISH-ACCEPT message with an invalid Request-Id parameter.");
-                                            read_loop_continue = false;
-                                            continue;
-                                        }
-                                    },
-                                    None => {
-                                        log_error!(logger, "Received a PUBLISH-ACCEPT message with no Request-Id parameter.");
-                                        read_loop_continue = false;
-                                        continue;
-                                    }
-                                };
-
-                                let stream_id = msg_parsed.get_parameter("Stream-Id").unwrap_or("");
-
-                                ControlClientStatus::complete_request(
-                                    &status,
-                                    request_id,
-                                    ControlKeyValidationResponse::Accepted {
-                                        stream_id: stream_id.to_string(),
-                                    },
-                                )
-                                .await;
-                            }
-                            "PUBLISH-DENY" => {
-                                let request_id = match msg_parsed.get_parameter("Request-Id") {
-                                    Some(req_id_str) => match str::parse::<u64>(req_id_str) {
-                                        Ok(id) => id,
-                                        Err(_) => {
-                                            log_warning!(logger, "Received a PUBLISH-DENY message with an invalid Request-Id parameter.");
-                                            read_loop_continue = false;
-                                            continue;
-                                        }
-                                    },
-                                    None => {
-                                        log_warning!(logger, "Received a PUBLISH-DENY message with no Request-Id parameter.");
-                                        read_loop_continue = false;
-                                        continue;
-                                    }
-                                };
-
-                                ControlClientStatus::complete_request(
-                                    &status,
-                                    request_id,
-                                    ControlKeyValidationResponse::Rejected,
-                                )
-                                .await;
-                            }
-                            "STREAM-KILL" => {
-                                let channel =
-                                    msg_parsed.get_parameter("Stream-Channel").unwrap_or("");
-                                let stream_id = msg_parsed
-                                    .get_parameter("Stream-Id")
-                                    .filter(|&s| !s.is_empty());
-
-                                kill_publisher(&logger, &server_context, channel, stream_id).await;
+                        };
+
+                        ControlClientStatus::complete_request(
+                            &status,
+                            request_id,
+                            ControlKeyValidationResponse::Rejected,
+                        )
+                        .await;
+                    }
+                    "PLAY-ACCEPT" => {
+                        let request_id = match msg_parsed.get_parameter("Request-Id") {
+                            Some(req_id_str) => match str::parse::<u64>(req_id_str) {
+                                Ok(id) => id,
+                                Err(_) => {
+                                    log_warning!(logger, "Received a PLAY-ACCEPT message with an invalid Request-Id parameter.");
+                                    read_loop_continue = false;
+                                    continue;
+                                }
+                            },
+                            None => {
+                                log_error!(
+                                    logger,
+                                    "Received a PLAY-ACCEPT message with no Request-Id parameter."
+                                );
+                                read_loop_continue = false;
+                                continue;
                             }
-                            "HEARTBEAT" => {}
-                            _ => {
-                                log_debug!(
+                        };
+
+                        // The player can be redirected to another server
+
+                        let response = match msg_parsed
+                            .get_parameter("Redirect-Url")
+                            .map(|u| u.trim())
+                            .filter(|u| !u.is_empty())
+                        {
+                            Some(url) => ControlKeyValidationResponse::Redirect {
+                                url: url.to_string(),
+                            },
+                            None => ControlKeyValidationResponse::Accepted {
+                                stream_id: "".to_string(),
+                                max_players: None,
+                                flags: None,
+                            },
+                        };
+
+                        ControlClientStatus::complete_request(&status, request_id, response).await;
+                    }
+                    "PLAY-DENY" => {
+                        let request_id = match msg_parsed.get_parameter("Request-Id") {
+                            Some(req_id_str) => match str::parse::<u64>(req_id_str) {
+                                Ok(id) => id,
+                                Err(_) => {
+                                    log_warning!(logger, "Received a PLAY-DENY message with an invalid Request-Id parameter.");
+                                    read_loop_continue = false;
+                                    continue;
+                                }
+                            },
+                            None => {
+                                log_warning!(
                                     logger,
-                                    format!("Unrecognized message type: {}", &msg_parsed.msg_type)
+                                    "Received a PLAY-DENY message with no Request-Id parameter."
                                 );
+                                read_loop_continue = false;
+                                continue;
                             }
-                        }
+                        };
+
+                        ControlClientStatus::complete_request(
+                            &status,
+                            request_id,
+                            ControlKeyValidationResponse::Rejected,
+                        )
+                        .await;
+                    }
+                    "STREAM-KILL" => {
+                        handle_stream_kill_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "STREAM-KICK-PLAYER" => {
+                        let channel = msg_parsed.get_parameter("Stream-Channel").unwrap_or("");
+                        let session_id = msg_parsed
+                            .get_parameter("Session-Id")
+                            .and_then(|s| s.trim().parse::<u64>().ok());
+                        let ip = msg_parsed
+                            .get_parameter("Player-Ip")
+                            .and_then(|s| s.trim().parse::<IpAddr>().ok());
+
+                        kick_player(&logger, &server_context, channel, session_id, ip).await;
+                    }
+                    "RECORD-START" | "RECORD-STOP" => {
+                        handle_record_command(&logger, &status, &server_context, &msg_parsed).await;
+                    }
+                    "CHANNEL-EVENTS" => {
+                        handle_channel_events_command(
+                            &logger,
+                            &status,
+                            &server_context,
+                            &msg_parsed,
+                        )
+                        .await;
+                    }
+                    "CHANNEL-PLAYERS" => {
+                        handle_channel_players_command(
+                            &logger,
+                            &status,
+                            &server_context,
+                            &msg_parsed,
+                        )
+                        .await;
+                    }
+                    "CHANNEL-BANDWIDTH" => {
+                        handle_channel_bandwidth_command(
+                            &logger,
+                            &status,
+                            &server_context,
+                            &msg_parsed,
+                        )
+                        .await;
+                    }
+                    "CUE-POINT" => {
+                        handle_cue_point_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "GOP-CLEAR" => {
+                        handle_gop_clear_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "KEY-ROTATE" => {
+                        handle_key_rotate_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
                     }
+                    "KEY-GENERATE" => {
+                        handle_key_generate_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "KEY-LIST" => {
+                        handle_key_list_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "KEY-REVOKE" => {
+                        handle_key_revoke_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "TRACE-START" | "TRACE-STOP" => {
+                        handle_trace_command(&logger, &status, &server_context, &msg_parsed).await;
+                    }
+                    "CONFIG-SET" => {
+                        handle_config_set_command(&logger, &status, &server_context, &msg_parsed)
+                            .await;
+                    }
+                    "HEARTBEAT" => {}
                     _ => {
-                        log_debug!(logger, "Unknown message type received from websocket");
+                        log_debug!(
+                            logger,
+                            format!("Unrecognized message type: {}", &msg_parsed.msg_type)
+                        );
                     }
                 }
             }
@@ -301,7 +549,742 @@ pub fn spawn_task_control_client(
 
             // Kill all publishers
 
-            remove_all_publishers(&server_context).await;
+            remove_all_publishers(&server_context, "Control server disconnected").await;
         }
     });
 }
+
+/// Handles a command to kill the publisher of a channel, or a group of channels.
+/// Only the kills of groups of channels are responded.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message
+async fn handle_stream_kill_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let selector = msg.get_parameter("Stream-Channel").unwrap_or("");
+    let stream_id = msg.get_parameter("Stream-Id").filter(|&s| !s.is_empty());
+    let confirmed = msg
+        .get_parameter("Kill-Confirm")
+        .is_some_and(|c| c.trim().eq_ignore_ascii_case("yes"));
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), selector.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let result = ChannelSelector::parse_kill_target(selector, confirmed).and_then(|channels| {
+        if channels.is_group() && stream_id.is_some() {
+            Err("The stream ID can only be used to kill a single channel".to_string())
+        } else {
+            Ok(channels)
+        }
+    });
+
+    let channels = match result {
+        Ok(c) => c,
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!(
+                    "Could not process {} for channel {}: {}",
+                    &msg.msg_type, selector, e
+                )
+            );
+
+            parameters.insert("Error-Message".to_string(), e);
+
+            let response = ControlServerMessage::new_with_parameters(
+                "STREAM-KILL-ERROR".to_string(),
+                parameters,
+            );
+
+            _ = ControlClientStatus::send_message(status, response, logger).await;
+
+            return;
+        }
+    };
+
+    if let Some(channel) = channels.single_channel() {
+        // Do not reuse the cached validations of the killed stream
+        server_context
+            .status
+            .callback_cache
+            .invalidate_channel(channel)
+            .await;
+
+        kill_publisher(
+            logger,
+            server_context,
+            channel,
+            stream_id,
+            "STREAM-KILL from the control server",
+        )
+        .await;
+
+        return;
+    }
+
+    let killed = kill_publishers_matching(
+        logger,
+        server_context,
+        &channels,
+        "STREAM-KILL from the control server",
+    )
+    .await;
+
+    for channel in &killed {
+        server_context
+            .status
+            .callback_cache
+            .invalidate_channel(channel)
+            .await;
+    }
+
+    log_info!(
+        logger,
+        format!(
+            "Killed the publishers of {} channels matching {}",
+            killed.len(),
+            channels
+        )
+    );
+
+    parameters.insert("Killed-Channels".to_string(), killed.len().to_string());
+
+    let response =
+        ControlServerMessage::new_with_parameters("STREAM-KILLED".to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to start or stop recording a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (RECORD-START or RECORD-STOP)
+async fn handle_record_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+
+    let (res, response_type): (Result<RecordingInfo, String>, &str) =
+        if msg.msg_type == "RECORD-START" {
+            (
+                start_recording(logger, server_context, channel).await,
+                "RECORD-STARTED",
+            )
+        } else {
+            (
+                stop_recording(server_context, channel).await,
+                "RECORD-STOPPED",
+            )
+        };
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response_type = match res {
+        Ok(info) => {
+            parameters.insert("Stream-Id".to_string(), info.stream_id);
+            parameters.insert("Record-Path".to_string(), info.path);
+            response_type
+        }
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!(
+                    "Could not process {} for channel {}: {}",
+                    &msg.msg_type, channel, e
+                )
+            );
+            parameters.insert("Error-Message".to_string(), e);
+            "RECORD-ERROR"
+        }
+    };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to query the event history of a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (CHANNEL-EVENTS)
+async fn handle_channel_events_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+
+    let events = server_context.status.events.get_events(channel).await;
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+    parameters.insert(
+        "Events".to_string(),
+        serde_json::to_string(&events).unwrap_or_default(),
+    );
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response =
+        ControlServerMessage::new_with_parameters("CHANNEL-EVENTS-LIST".to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to query the diagnostics of the players of a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (CHANNEL-PLAYERS)
+async fn handle_channel_players_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+
+    let players = get_channel_players(server_context, channel).await;
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+    parameters.insert(
+        "Players".to_string(),
+        serde_json::to_string(&players).unwrap_or_default(),
+    );
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response =
+        ControlServerMessage::new_with_parameters("CHANNEL-PLAYERS-LIST".to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to query the bandwidth usage of a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (CHANNEL-BANDWIDTH)
+async fn handle_channel_bandwidth_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+
+    let stats = get_channel_bandwidth(server_context, channel).await;
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+    parameters.insert("Bit-Rate-In".to_string(), stats.bit_rate_in.to_string());
+    parameters.insert("Bit-Rate-Out".to_string(), stats.bit_rate_out.to_string());
+    parameters.insert("Bytes-In".to_string(), stats.bytes_in.to_string());
+    parameters.insert("Bytes-Out".to_string(), stats.bytes_out.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response = ControlServerMessage::new_with_parameters(
+        "CHANNEL-BANDWIDTH-STATS".to_string(),
+        parameters,
+    );
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to inject a cue point into the stream of a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (CUE-POINT)
+async fn handle_cue_point_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+    let name = msg.get_parameter("Cue-Name").unwrap_or("");
+    let record = msg
+        .get_parameter("Cue-Record")
+        .is_some_and(|r| matches!(r.to_uppercase().as_str(), "YES" | "TRUE"));
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+    parameters.insert("Cue-Name".to_string(), name.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let res = match parse_cue_point_parameters(msg.get_parameter("Cue-Parameters").unwrap_or("")) {
+        Ok(cue_parameters) => {
+            inject_cue_point(
+                logger,
+                server_context,
+                channel,
+                name,
+                &cue_parameters,
+                record,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    let response_type = match res {
+        Ok(()) => "CUE-POINT-INJECTED",
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!(
+                    "Could not process {} for channel {}: {}",
+                    &msg.msg_type, channel, e
+                )
+            );
+            parameters.insert("Error-Message".to_string(), e);
+            "CUE-POINT-ERROR"
+        }
+    };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to clear the GOP cache of a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message
+async fn handle_gop_clear_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response_type = match clear_gop_cache(logger, server_context, channel).await {
+        Ok(removed) => {
+            parameters.insert("Gop-Packets".to_string(), removed.to_string());
+            "GOP-CLEARED"
+        }
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!(
+                    "Could not process {} for channel {}: {}",
+                    &msg.msg_type, channel, e
+                )
+            );
+            parameters.insert("Error-Message".to_string(), e);
+            "GOP-CLEAR-ERROR"
+        }
+    };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to rotate the key the players must provide to play a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message
+async fn handle_key_rotate_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+    let key = msg.get_parameter("Stream-Key").unwrap_or("");
+    let grace_seconds = msg
+        .get_parameter("Grace-Seconds")
+        .and_then(|g| g.parse::<u32>().ok())
+        .unwrap_or(server_context.config.key_rotation_grace_seconds);
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response_type =
+        match rotate_channel_key(logger, server_context, channel, key, grace_seconds).await {
+            Ok(_) => {
+                parameters.insert("Grace-Seconds".to_string(), grace_seconds.to_string());
+                "KEY-ROTATED"
+            }
+            Err(e) => {
+                log_warning!(
+                    logger,
+                    format!(
+                        "Could not process {} for channel {}: {}",
+                        &msg.msg_type, channel, e
+                    )
+                );
+                parameters.insert("Error-Message".to_string(), e);
+                "KEY-ROTATE-ERROR"
+            }
+        };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to generate a key to publish on a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message
+async fn handle_key_generate_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+    let expiration_seconds = msg
+        .get_parameter("Key-Expiration")
+        .and_then(|e| e.parse::<u32>().ok())
+        .unwrap_or(0);
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let response_type = match generate_stored_channel_key(
+        logger,
+        server_context,
+        channel,
+        expiration_seconds,
+    )
+    .await
+    {
+        Ok((stored_key, key)) => {
+            parameters.insert("Key-Id".to_string(), stored_key.id);
+            parameters.insert("Stream-Key".to_string(), key);
+
+            if let Some(expires_at) = stored_key.expires_at {
+                parameters.insert("Key-Expires-At".to_string(), expires_at.to_string());
+            }
+
+            "KEY-GENERATED"
+        }
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!(
+                    "Could not process {} for channel {}: {}",
+                    &msg.msg_type, channel, e
+                )
+            );
+            parameters.insert("Error-Message".to_string(), e);
+            "KEY-GENERATE-ERROR"
+        }
+    };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to list the keys of the key store,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message
+async fn handle_key_list_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel");
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    if let Some(c) = channel {
+        parameters.insert("Stream-Channel".to_string(), c.to_string());
+    }
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let keys = server_context.status.key_store.list(channel).await;
+
+    parameters.insert(
+        "Keys".to_string(),
+        serde_json::to_string(&keys).unwrap_or_default(),
+    );
+
+    let response =
+        ControlServerMessage::new_with_parameters("KEY-LIST-RESULT".to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to revoke a key of the key store,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message
+async fn handle_key_revoke_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let channel = msg.get_parameter("Stream-Channel").unwrap_or("");
+    let key_id = msg.get_parameter("Key-Id").unwrap_or("");
+
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    parameters.insert("Stream-Channel".to_string(), channel.to_string());
+    parameters.insert("Key-Id".to_string(), key_id.to_string());
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let revoked = revoke_stored_channel_key(logger, server_context, channel, key_id)
+        .await
+        .is_ok();
+
+    parameters.insert(
+        "Revoked".to_string(),
+        if revoked { "YES" } else { "NO" }.to_string(),
+    );
+
+    let response = ControlServerMessage::new_with_parameters("KEY-REVOKED".to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to start or stop tracing the sessions
+/// from a client IP or connected to a channel,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (TRACE-START or TRACE-STOP)
+async fn handle_trace_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let filter = match (
+        msg.get_parameter("Client-Ip"),
+        msg.get_parameter("Stream-Channel"),
+    ) {
+        (Some(ip), _) => {
+            parameters.insert("Client-Ip".to_string(), ip.to_string());
+            SessionTraceFilter::parse("ip", ip)
+        }
+        (None, Some(channel)) => {
+            parameters.insert("Stream-Channel".to_string(), channel.to_string());
+            SessionTraceFilter::parse("channel", channel)
+        }
+        (None, None) => Err("Missing Client-Ip or Stream-Channel parameter".to_string()),
+    };
+
+    let traces = &server_context.status.traces;
+    let trace_config = &server_context.config.trace;
+
+    let (res, response_type): (Result<usize, String>, &str) = match filter {
+        Ok(f) => {
+            if msg.msg_type == "TRACE-START" {
+                (
+                    traces.start_trace(logger, trace_config, f).await,
+                    "TRACE-STARTED",
+                )
+            } else {
+                (
+                    traces.stop_trace(logger, trace_config, &f).await,
+                    "TRACE-STOPPED",
+                )
+            }
+        }
+        Err(e) => (Err(e), ""),
+    };
+
+    let response_type = match res {
+        Ok(count) => {
+            parameters.insert("Trace-Sessions".to_string(), count.to_string());
+            response_type
+        }
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!("Could not process {}: {}", &msg.msg_type, e)
+            );
+            parameters.insert("Error-Message".to_string(), e);
+            "TRACE-ERROR"
+        }
+    };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
+
+/// Handles a command to change settings of the server at runtime,
+/// sending the response to the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `status` - The control client status
+/// * `server_context` - The RTMP server context
+/// * `msg` - The received message (CONFIG-SET)
+async fn handle_config_set_command(
+    logger: &Logger,
+    status: &Mutex<ControlClientStatus>,
+    server_context: &RtmpServerContext,
+    msg: &ControlServerMessage,
+) {
+    let mut parameters: HashMap<String, String> = HashMap::new();
+
+    if let Some(request_id) = msg.get_parameter("Request-Id") {
+        parameters.insert("Request-Id".to_string(), request_id.to_string());
+    }
+
+    let res = match &msg.parameters {
+        Some(settings) => parse_config_setting_changes(settings, &["Request-Id"]),
+        None => Err("No settings to change".to_string()),
+    };
+
+    let response_type = match res {
+        Ok(changes) => {
+            server_context.status.config_overrides.apply(&changes);
+
+            for change in &changes {
+                log_info!(
+                    logger,
+                    format!("Setting changed: {} = {}", change.name(), change.value())
+                );
+
+                parameters.insert(change.name().to_string(), change.value());
+            }
+
+            "CONFIG-SET-ACK"
+        }
+        Err(e) => {
+            log_warning!(
+                logger,
+                format!("Could not process {}: {}", &msg.msg_type, e)
+            );
+            parameters.insert("Error-Message".to_string(), e);
+            "CONFIG-SET-ERROR"
+        }
+    };
+
+    let response = ControlServerMessage::new_with_parameters(response_type.to_string(), parameters);
+
+    _ = ControlClientStatus::send_message(status, response, logger).await;
+}
@@ -5,10 +5,39 @@ use url::Url;
 use crate::{
     log::Logger,
     log_error,
-    utils::{get_env_bool, get_env_string},
+    utils::{get_env_bool, get_env_secret, get_env_string, get_env_u32},
 };
 
+/// Default timeout for the key validation requests (milliseconds)
+const KEY_VALIDATION_TIMEOUT_MS_DEFAULT: u32 = 10000;
+
+/// Behavior when a key validation request times out
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ControlKeyValidationFallback {
+    /// Reject the publisher
+    Reject,
+
+    /// Validate the key with the event callback instead
+    Callback,
+}
+
+impl ControlKeyValidationFallback {
+    /// Parses the fallback behavior
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string (`reject` or `callback`)
+    pub fn parse(s: &str) -> Result<ControlKeyValidationFallback, String> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "reject" => Ok(ControlKeyValidationFallback::Reject),
+            "callback" => Ok(ControlKeyValidationFallback::Callback),
+            _ => Err(format!("Invalid key validation fallback: {}", s)),
+        }
+    }
+}
+
 /// Configuration of the connection to the control server
+#[cfg_attr(not(feature = "control"), allow(dead_code))]
 pub struct ControlServerConnectionConfig {
     /// Connection URL
     pub connection_url: String,
@@ -24,13 +53,39 @@ pub struct ControlServerConnectionConfig {
 
     /// True if external components must use TLS to connect
     pub external_ssl: bool,
+
+    /// True to offer binary frames to the control server
+    pub binary_frames: bool,
+
+    /// Timeout for the key validation requests (milliseconds, 0 for no timeout)
+    pub key_validation_timeout_ms: u32,
+
+    /// Behavior when a key validation request times out
+    pub key_validation_fallback: ControlKeyValidationFallback,
+
+    /// True to validate the players with the control server
+    pub play_validation: bool,
 }
 
 impl ControlServerConnectionConfig {
     /// Loads control server feature configuration
     /// from environment variables
     pub fn load_from_env(logger: &Logger) -> Result<ControlServerConnectionConfig, ()> {
-        let secret = get_env_string("CONTROL_SECRET", "");
+        if !cfg!(feature = "control") {
+            log_error!(
+                logger,
+                "CONTROL_USE is enabled, but the server was built without the control feature"
+            );
+            return Err(());
+        }
+
+        let secret = match get_env_secret("CONTROL_SECRET", "") {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!(logger, e);
+                return Err(());
+            }
+        };
         let base_url = get_env_string("CONTROL_BASE_URL", "");
 
         let connection_url = if !base_url.is_empty() {
@@ -60,6 +115,31 @@ impl ControlServerConnectionConfig {
         let external_ip = get_env_string("EXTERNAL_IP", "");
         let external_port = get_env_string("EXTERNAL_PORT", "");
         let external_ssl = get_env_bool("EXTERNAL_SSL", false);
+        let binary_frames = get_env_bool("CONTROL_BINARY_FRAMES", false);
+
+        let key_validation_timeout_ms = get_env_u32(
+            "CONTROL_KEY_VALIDATION_TIMEOUT_MS",
+            KEY_VALIDATION_TIMEOUT_MS_DEFAULT,
+        );
+
+        let key_validation_fallback = match ControlKeyValidationFallback::parse(&get_env_string(
+            "CONTROL_KEY_VALIDATION_FALLBACK",
+            "reject",
+        )) {
+            Ok(f) => f,
+            Err(e) => {
+                log_error!(
+                    logger,
+                    format!(
+                        "CONTROL_KEY_VALIDATION_FALLBACK has an invalid value: {}",
+                        e
+                    )
+                );
+                return Err(());
+            }
+        };
+
+        let play_validation = get_env_bool("CONTROL_PLAY_VALIDATION", false);
 
         Ok(ControlServerConnectionConfig {
             connection_url,
@@ -67,6 +147,10 @@ impl ControlServerConnectionConfig {
             external_ip,
             external_port,
             external_ssl,
+            binary_frames,
+            key_validation_timeout_ms,
+            key_validation_fallback,
+            play_validation,
         })
     }
 }
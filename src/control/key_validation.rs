@@ -1,26 +1,54 @@
-// Callback system to request key validation to the control server
+// Requests to validate the stream keys against the control server
 
-use std::{collections::HashMap, net::IpAddr, sync::Arc};
+use std::{collections::HashMap, net::IpAddr};
 
-use tokio::sync::{
-    mpsc::{Receiver, Sender},
-    Mutex,
-};
-
-use crate::{log::Logger, log_debug, log_error};
+use tokio::sync::mpsc::Sender;
 
-use super::{ControlClientStatus, ControlServerMessage};
+use crate::{
+    callback::PlayAuthorization,
+    rtmp::StreamMetadata,
+    server::{PublishAuthorization, PublishStatisticsSummary, RtmpPublisherInfo},
+    utils::CertificateIdentity,
+};
 
 /// Size for the buffer of the channel to communicate key validation requests
+#[cfg(feature = "control")]
 pub const KEY_VALIDATION_CHANNEL_BUFFER_SIZE: usize = 16;
 
 /// Response for key validation
+#[cfg_attr(not(feature = "control"), allow(dead_code))]
 pub enum ControlKeyValidationResponse {
-    Accepted { stream_id: String },
+    Accepted {
+        stream_id: String,
+        max_players: Option<u32>,
+        flags: Option<String>,
+    },
+    Rejected,
+
+    /// The player must play from another URL
+    Redirect {
+        url: String,
+    },
+
+    /// The control server did not answer in time,
+    /// so the key must be validated with the event callback
+    Fallback,
+}
+
+/// Result of the validation of a stream key against the control server
+pub enum ControlKeyValidationResult {
+    /// The key is valid
+    Accepted(PublishAuthorization),
+
+    /// The key is invalid, or the validation failed
     Rejected,
+
+    /// The key must be validated with the event callback
+    Fallback,
 }
 
 /// Request to validate stream keys against the control server
+#[cfg_attr(not(feature = "control"), allow(dead_code))]
 pub enum ControlKeyValidationRequest {
     PublishStart {
         /// The channel
@@ -29,8 +57,39 @@ pub enum ControlKeyValidationRequest {
         /// The provided key to publish
         key: String,
 
-        /// The IP of the publisher
-        client_ip: String,
+        /// ID of the publisher session
+        session_id: u64,
+
+        /// Unique ID of the publication
+        publish_id: String,
+
+        /// The publisher (IP address and client information)
+        publisher: RtmpPublisherInfo,
+
+        /// The identity of the client certificate of the publisher (if provided)
+        client_cert: Option<CertificateIdentity>,
+
+        /// The query parameters of the stream name
+        query_params: HashMap<String, String>,
+
+        /// Sender for the response
+        response_sender: Sender<ControlKeyValidationResponse>,
+    },
+    PlayStart {
+        /// The channel
+        channel: String,
+
+        /// The provided key to play
+        key: String,
+
+        /// ID of the player session
+        session_id: u64,
+
+        /// IP address of the player
+        ip: IpAddr,
+
+        /// The query parameters of the stream name
+        query_params: HashMap<String, String>,
 
         /// Sender for the response
         response_sender: Sender<ControlKeyValidationResponse>,
@@ -41,6 +100,60 @@ pub enum ControlKeyValidationRequest {
 
         /// The stream_id
         stream_id: String,
+
+        /// The statistics of the publication
+        stats: Option<PublishStatisticsSummary>,
+
+        /// The publisher (IP address and client information)
+        publisher: Option<RtmpPublisherInfo>,
+    },
+    StreamCodecs {
+        /// The channel
+        channel: String,
+
+        /// The stream_id
+        stream_id: String,
+
+        /// The audio codec, if identified
+        audio_codec: Option<String>,
+
+        /// The video codec, if identified
+        video_codec: Option<String>,
+
+        /// The media accepted for the stream
+        media_mode: String,
+
+        /// The publisher (IP address and client information)
+        publisher: Option<RtmpPublisherInfo>,
+    },
+    StreamMetadataChange {
+        /// The channel
+        channel: String,
+
+        /// The stream_id
+        stream_id: String,
+
+        /// The stream metadata
+        metadata: StreamMetadata,
+
+        /// The publisher (IP address and client information)
+        publisher: Option<RtmpPublisherInfo>,
+    },
+    KeyframeIntervalWarning {
+        /// The channel
+        channel: String,
+
+        /// The stream_id
+        stream_id: String,
+
+        /// The measured interval between key frames (milliseconds)
+        keyframe_interval: i64,
+
+        /// The warning threshold (milliseconds)
+        threshold: i64,
+
+        /// The publisher (IP address and client information)
+        publisher: Option<RtmpPublisherInfo>,
     },
 }
 
@@ -51,17 +164,26 @@ pub enum ControlKeyValidationRequest {
 /// * `control_key_validator_sender` - Sender to communicate with the control server
 /// * `channel` - Channel
 /// * `key` - Stream key
-/// * `client_ip` - IP of the publisher
+/// * `session_id` - ID of the publisher session
+/// * `publish_id` - Unique ID of the publication
+/// * `publisher` - The publisher (IP address and client information)
+/// * `client_cert` - Identity of the client certificate of the publisher (if provided)
+/// * `query_params` - Query parameters of the stream name
 ///
 /// # Return value
 ///
-/// Returns true if valid, false if invalid or error
+/// Returns the authorization (stream id, viewer limit and flags) if accepted
+#[allow(clippy::too_many_arguments)]
 pub async fn control_validate_key(
     control_key_validator_sender: &Sender<ControlKeyValidationRequest>,
     channel: &str,
     key: &str,
-    client_ip: &IpAddr,
-) -> Option<String> {
+    session_id: u64,
+    publish_id: &str,
+    publisher: &RtmpPublisherInfo,
+    client_cert: Option<&CertificateIdentity>,
+    query_params: &HashMap<String, String>,
+) -> ControlKeyValidationResult {
     // Create channel to communicate the response
     let (response_sender, mut response_receiver) =
         tokio::sync::mpsc::channel::<ControlKeyValidationResponse>(1);
@@ -72,116 +194,89 @@ pub async fn control_validate_key(
         .send(ControlKeyValidationRequest::PublishStart {
             channel: channel.to_string(),
             key: key.to_string(),
-            client_ip: client_ip.to_string(),
+            session_id,
+            publish_id: publish_id.to_string(),
+            publisher: publisher.clone(),
+            client_cert: client_cert.cloned(),
+            query_params: query_params.clone(),
             response_sender,
         })
         .await
         .is_err()
     {
-        return None;
+        return ControlKeyValidationResult::Rejected;
     }
 
     // Get the response
 
     match response_receiver.recv().await {
-        Some(r) => match r {
-            ControlKeyValidationResponse::Accepted { stream_id } => Some(stream_id),
-            ControlKeyValidationResponse::Rejected => None,
-        },
-        None => None,
+        Some(ControlKeyValidationResponse::Accepted {
+            stream_id,
+            max_players,
+            flags,
+        }) => ControlKeyValidationResult::Accepted(PublishAuthorization {
+            stream_id,
+            max_players,
+            flags,
+        }),
+        Some(ControlKeyValidationResponse::Fallback) => ControlKeyValidationResult::Fallback,
+        Some(ControlKeyValidationResponse::Rejected)
+        | Some(ControlKeyValidationResponse::Redirect { .. })
+        | None => ControlKeyValidationResult::Rejected,
     }
 }
 
-/// Spawns task to handle key validations against the control server
+/// Validates a player against the control server
 ///
 /// # Arguments
 ///
-/// * `logger` - The logger
-/// * `status` - The client status
-/// * `request_receiver` - Receiver for the requests
-pub fn spawn_task_handle_control_key_validations(
-    logger: Arc<Logger>,
-    status: Arc<Mutex<ControlClientStatus>>,
-    mut request_receiver: Receiver<ControlKeyValidationRequest>,
-) {
-    tokio::spawn(async move {
-        loop {
-            let req = match request_receiver.recv().await {
-                Some(m) => m,
-                None => {
-                    log_error!(logger, "Control key validation channel was closed");
-                    return;
-                }
-            };
-
-            match req {
-                ControlKeyValidationRequest::PublishStart {
-                    channel,
-                    key,
-                    client_ip,
-                    response_sender,
-                } => {
-                    log_debug!(
-                        logger,
-                        format!(
-                            "Handling validation request for channel: {} and key: {}",
-                            &channel, &key
-                        )
-                    );
-
-                    // Add request
-
-                    let req_id = match ControlClientStatus::add_request(&status, response_sender)
-                        .await
-                    {
-                        Some(id) => id,
-                        None => {
-                            log_debug!(logger, "Not connected to the control server, so the key validation request was rejected.");
-
-                            return;
-                        }
-                    };
-
-                    // Send message to the server
-
-                    let mut parameters: HashMap<String, String> = HashMap::new();
-
-                    parameters.insert("Request-ID".to_string(), req_id.to_string());
-                    parameters.insert("Stream-Channel".to_string(), channel);
-                    parameters.insert("Stream-Key".to_string(), key);
-                    parameters.insert("User-IP".to_string(), client_ip);
-
-                    let msg = ControlServerMessage::new_with_parameters(
-                        "PUBLISH-REQUEST".to_string(),
-                        parameters,
-                    );
-
-                    if !ControlClientStatus::send_message(&status, msg, &logger).await {
-                        // Failed to send message, reject the request
-                        ControlClientStatus::complete_request(
-                            &status,
-                            req_id,
-                            ControlKeyValidationResponse::Rejected,
-                        )
-                        .await;
-                    }
-                }
-                ControlKeyValidationRequest::PublishEnd { channel, stream_id } => {
-                    // Send message to the server
-
-                    let mut parameters: HashMap<String, String> = HashMap::new();
-
-                    parameters.insert("Stream-Channel".to_string(), channel);
-                    parameters.insert("Stream-ID".to_string(), stream_id);
-
-                    let msg = ControlServerMessage::new_with_parameters(
-                        "PUBLISH-END".to_string(),
-                        parameters,
-                    );
-
-                    _ = ControlClientStatus::send_message(&status, msg, &logger).await;
-                }
-            }
+/// * `control_key_validator_sender` - Sender to communicate with the control server
+/// * `channel` - Channel
+/// * `key` - Stream key
+/// * `session_id` - ID of the player session
+/// * `ip` - IP address of the player
+/// * `query_params` - Query parameters of the stream name
+///
+/// # Return value
+///
+/// Returns the authorization, or None if the player must be validated with the event callback
+pub async fn control_validate_play(
+    control_key_validator_sender: &Sender<ControlKeyValidationRequest>,
+    channel: &str,
+    key: &str,
+    session_id: u64,
+    ip: &IpAddr,
+    query_params: &HashMap<String, String>,
+) -> Option<PlayAuthorization> {
+    // Create channel to communicate the response
+    let (response_sender, mut response_receiver) =
+        tokio::sync::mpsc::channel::<ControlKeyValidationResponse>(1);
+
+    // Send the request
+
+    if control_key_validator_sender
+        .send(ControlKeyValidationRequest::PlayStart {
+            channel: channel.to_string(),
+            key: key.to_string(),
+            session_id,
+            ip: *ip,
+            query_params: query_params.clone(),
+            response_sender,
+        })
+        .await
+        .is_err()
+    {
+        return Some(PlayAuthorization::Rejected);
+    }
+
+    // Get the response
+
+    match response_receiver.recv().await {
+        Some(ControlKeyValidationResponse::Accepted { .. }) => Some(PlayAuthorization::Accepted),
+        Some(ControlKeyValidationResponse::Redirect { url }) => {
+            Some(PlayAuthorization::Redirect(url))
         }
-    });
+        Some(ControlKeyValidationResponse::Fallback) => None,
+        Some(ControlKeyValidationResponse::Rejected) | None => Some(PlayAuthorization::Rejected),
+    }
 }
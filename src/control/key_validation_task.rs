@@ -0,0 +1,508 @@
+// Task to send the key validation requests to the control server
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::sync::{mpsc::Receiver, Mutex};
+
+use crate::{
+    log::Logger,
+    log_debug, log_error, log_warning,
+    server::{PublishStatisticsSummary, RtmpPublisherInfo, ServerIdentity},
+    utils::REDACTED_STREAM_KEY,
+};
+
+use super::{
+    ControlClientStatus, ControlKeyValidationFallback, ControlKeyValidationRequest,
+    ControlKeyValidationResponse, ControlServerConnectionConfig, ControlServerMessage,
+};
+
+/// Adds the information of the publisher to the parameters of a message
+///
+/// # Arguments
+///
+/// * `parameters` - The parameters of the message
+/// * `publisher` - The publisher (IP address and client information)
+fn add_publisher_parameters(
+    parameters: &mut HashMap<String, String>,
+    publisher: &RtmpPublisherInfo,
+) {
+    parameters.insert("User-IP".to_string(), publisher.ip.to_string());
+
+    if let Some(flash_ver) = &publisher.connect_info.flash_ver {
+        parameters.insert("Encoder-Flash-Ver".to_string(), flash_ver.clone());
+    }
+
+    if let Some(swf_url) = &publisher.connect_info.swf_url {
+        parameters.insert("Encoder-Swf-Url".to_string(), swf_url.clone());
+    }
+
+    if let Some(tc_url) = &publisher.connect_info.tc_url {
+        parameters.insert("Encoder-Tc-Url".to_string(), tc_url.clone());
+    }
+}
+
+/// Adds the identity of the server node to the parameters of a message
+///
+/// # Arguments
+///
+/// * `parameters` - The parameters of the message
+/// * `identity` - The identity of the server node
+fn add_identity_parameters(parameters: &mut HashMap<String, String>, identity: &ServerIdentity) {
+    parameters.insert(
+        "Server-Instance-Id".to_string(),
+        identity.instance_id.clone(),
+    );
+
+    if let Some(server_id) = identity.server_id() {
+        parameters.insert("Server-Id".to_string(), server_id);
+    }
+
+    if let Some(region) = identity.region() {
+        parameters.insert("Server-Region".to_string(), region);
+    }
+}
+
+/// Adds the statistics of a publication to the parameters of a PUBLISH-END message
+///
+/// # Arguments
+///
+/// * `parameters` - The message parameters
+/// * `stats` - The statistics of the publication
+fn add_publish_stats_parameters(
+    parameters: &mut HashMap<String, String>,
+    stats: &PublishStatisticsSummary,
+) {
+    parameters.insert("Stats-Bytes".to_string(), stats.total_bytes.to_string());
+    parameters.insert("Stats-Duration".to_string(), stats.duration.to_string());
+    parameters.insert(
+        "Stats-Bitrate-Avg".to_string(),
+        stats.avg_bit_rate.to_string(),
+    );
+    parameters.insert(
+        "Stats-Bitrate-Max".to_string(),
+        stats.max_bit_rate.to_string(),
+    );
+    parameters.insert(
+        "Stats-Timestamp-Gaps".to_string(),
+        stats.timestamp_gaps.to_string(),
+    );
+    parameters.insert("Stats-Keyframes".to_string(), stats.keyframes.to_string());
+
+    if let Some(v) = stats.keyframe_interval_avg {
+        parameters.insert("Stats-Keyframe-Interval-Avg".to_string(), v.to_string());
+    }
+
+    if let Some(v) = stats.keyframe_interval_min {
+        parameters.insert("Stats-Keyframe-Interval-Min".to_string(), v.to_string());
+    }
+
+    if let Some(v) = stats.keyframe_interval_max {
+        parameters.insert("Stats-Keyframe-Interval-Max".to_string(), v.to_string());
+    }
+
+    parameters.insert(
+        "Stats-Keyframe-Interval-Warnings".to_string(),
+        stats.keyframe_interval_warnings.to_string(),
+    );
+}
+
+/// Spawns task to handle key validations against the control server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The control client configuration
+/// * `status` - The client status
+/// * `identity` - Identity of the server node
+/// * `request_receiver` - Receiver for the requests
+pub fn spawn_task_handle_control_key_validations(
+    logger: Arc<Logger>,
+    config: Arc<ControlServerConnectionConfig>,
+    status: Arc<Mutex<ControlClientStatus>>,
+    identity: ServerIdentity,
+    mut request_receiver: Receiver<ControlKeyValidationRequest>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let req = match request_receiver.recv().await {
+                Some(m) => m,
+                None => {
+                    log_error!(logger, "Control key validation channel was closed");
+                    return;
+                }
+            };
+
+            match req {
+                ControlKeyValidationRequest::PublishStart {
+                    channel,
+                    key,
+                    session_id,
+                    publish_id,
+                    publisher,
+                    client_cert,
+                    query_params,
+                    response_sender,
+                } => {
+                    log_debug!(
+                        logger,
+                        format!(
+                            "Handling validation request for channel: {} and key: {}",
+                            &channel, REDACTED_STREAM_KEY
+                        )
+                    );
+
+                    // Add request
+
+                    let req_id = match ControlClientStatus::add_request(&status, response_sender)
+                        .await
+                    {
+                        Some(id) => id,
+                        None => {
+                            log_debug!(logger, "Not connected to the control server, so the key validation request was rejected.");
+
+                            continue;
+                        }
+                    };
+
+                    // Reject or fall back if the control server does not answer in time
+
+                    if config.key_validation_timeout_ms > 0 {
+                        spawn_task_key_validation_timeout(
+                            logger.clone(),
+                            config.clone(),
+                            status.clone(),
+                            req_id,
+                        );
+                    }
+
+                    // Send message to the server
+
+                    let mut parameters: HashMap<String, String> = HashMap::new();
+
+                    parameters.insert("Request-ID".to_string(), req_id.to_string());
+                    parameters.insert("Stream-Channel".to_string(), channel);
+                    parameters.insert("Stream-Key".to_string(), key);
+                    add_publisher_parameters(&mut parameters, &publisher);
+                    parameters.insert("Session-Id".to_string(), session_id.to_string());
+                    parameters.insert("Publish-Id".to_string(), publish_id);
+                    add_identity_parameters(&mut parameters, &identity);
+
+                    if let Some(client_cert) = client_cert {
+                        if let Some(cn) = &client_cert.common_name {
+                            parameters.insert("Client-Cert-CN".to_string(), cn.clone());
+                        }
+
+                        if let Some(san) = client_cert.get_subject_alt_names_string() {
+                            parameters.insert("Client-Cert-SAN".to_string(), san);
+                        }
+                    }
+
+                    if !query_params.is_empty() {
+                        // Encoded as JSON, so values cannot break the message format
+                        parameters.insert(
+                            "Query-Params".to_string(),
+                            serde_json::to_string(&query_params).unwrap_or_default(),
+                        );
+                    }
+
+                    let msg = ControlServerMessage::new_with_parameters(
+                        "PUBLISH-REQUEST".to_string(),
+                        parameters,
+                    );
+
+                    if !ControlClientStatus::send_message(&status, msg, &logger).await {
+                        // Failed to send message, reject the request
+                        ControlClientStatus::complete_request(
+                            &status,
+                            req_id,
+                            ControlKeyValidationResponse::Rejected,
+                        )
+                        .await;
+                    }
+                }
+                ControlKeyValidationRequest::PlayStart {
+                    channel,
+                    key,
+                    session_id,
+                    ip,
+                    query_params,
+                    response_sender,
+                } => {
+                    // Without the play validation, players are accepted
+
+                    if !config.play_validation {
+                        _ = response_sender
+                            .send(ControlKeyValidationResponse::Accepted {
+                                stream_id: "".to_string(),
+                                max_players: None,
+                                flags: None,
+                            })
+                            .await;
+
+                        continue;
+                    }
+
+                    log_debug!(
+                        logger,
+                        format!(
+                            "Handling play validation request for channel: {} and key: {}",
+                            &channel, REDACTED_STREAM_KEY
+                        )
+                    );
+
+                    // Add request
+
+                    let req_id = match ControlClientStatus::add_request(&status, response_sender)
+                        .await
+                    {
+                        Some(id) => id,
+                        None => {
+                            log_debug!(logger, "Not connected to the control server, so the play validation request was rejected.");
+
+                            continue;
+                        }
+                    };
+
+                    // Reject or fall back if the control server does not answer in time
+
+                    if config.key_validation_timeout_ms > 0 {
+                        spawn_task_key_validation_timeout(
+                            logger.clone(),
+                            config.clone(),
+                            status.clone(),
+                            req_id,
+                        );
+                    }
+
+                    // Send message to the server
+
+                    let mut parameters: HashMap<String, String> = HashMap::new();
+
+                    parameters.insert("Request-ID".to_string(), req_id.to_string());
+                    parameters.insert("Stream-Channel".to_string(), channel);
+                    parameters.insert("Stream-Key".to_string(), key);
+                    parameters.insert("User-IP".to_string(), ip.to_string());
+                    parameters.insert("Session-Id".to_string(), session_id.to_string());
+                    add_identity_parameters(&mut parameters, &identity);
+
+                    if !query_params.is_empty() {
+                        // Encoded as JSON, so values cannot break the message format
+                        parameters.insert(
+                            "Query-Params".to_string(),
+                            serde_json::to_string(&query_params).unwrap_or_default(),
+                        );
+                    }
+
+                    let msg = ControlServerMessage::new_with_parameters(
+                        "PLAY-REQUEST".to_string(),
+                        parameters,
+                    );
+
+                    if !ControlClientStatus::send_message(&status, msg, &logger).await {
+                        // Failed to send message, reject the request
+                        ControlClientStatus::complete_request(
+                            &status,
+                            req_id,
+                            ControlKeyValidationResponse::Rejected,
+                        )
+                        .await;
+                    }
+                }
+                ControlKeyValidationRequest::PublishEnd {
+                    channel,
+                    stream_id,
+                    stats,
+                    publisher,
+                } => {
+                    // Send message to the server
+
+                    let mut parameters: HashMap<String, String> = HashMap::new();
+
+                    parameters.insert("Stream-Channel".to_string(), channel);
+                    parameters.insert("Stream-ID".to_string(), stream_id);
+
+                    if let Some(stats) = stats {
+                        add_publish_stats_parameters(&mut parameters, &stats);
+                    }
+
+                    if let Some(publisher) = publisher {
+                        add_publisher_parameters(&mut parameters, &publisher);
+                    }
+
+                    let msg = ControlServerMessage::new_with_parameters(
+                        "PUBLISH-END".to_string(),
+                        parameters,
+                    );
+
+                    _ = ControlClientStatus::send_message(&status, msg, &logger).await;
+                }
+                ControlKeyValidationRequest::StreamCodecs {
+                    channel,
+                    stream_id,
+                    audio_codec,
+                    video_codec,
+                    media_mode,
+                    publisher,
+                } => {
+                    // Send message to the server
+
+                    let mut parameters: HashMap<String, String> = HashMap::new();
+
+                    parameters.insert("Stream-Channel".to_string(), channel);
+                    parameters.insert("Stream-ID".to_string(), stream_id);
+
+                    if let Some(audio_codec) = audio_codec {
+                        parameters.insert("Audio-Codec".to_string(), audio_codec);
+                    }
+
+                    if let Some(video_codec) = video_codec {
+                        parameters.insert("Video-Codec".to_string(), video_codec);
+                    }
+
+                    parameters.insert("Media-Mode".to_string(), media_mode);
+
+                    if let Some(publisher) = publisher {
+                        add_publisher_parameters(&mut parameters, &publisher);
+                    }
+
+                    let msg = ControlServerMessage::new_with_parameters(
+                        "PUBLISH-CODECS".to_string(),
+                        parameters,
+                    );
+
+                    _ = ControlClientStatus::send_message(&status, msg, &logger).await;
+                }
+                ControlKeyValidationRequest::StreamMetadataChange {
+                    channel,
+                    stream_id,
+                    metadata,
+                    publisher,
+                } => {
+                    // Send message to the server
+
+                    let mut parameters: HashMap<String, String> = HashMap::new();
+
+                    parameters.insert("Stream-Channel".to_string(), channel);
+                    parameters.insert("Stream-ID".to_string(), stream_id);
+
+                    if let Some(width) = metadata.width {
+                        parameters.insert("Video-Width".to_string(), width.to_string());
+                    }
+
+                    if let Some(height) = metadata.height {
+                        parameters.insert("Video-Height".to_string(), height.to_string());
+                    }
+
+                    if let Some(framerate) = metadata.framerate {
+                        parameters.insert("Video-Framerate".to_string(), framerate.to_string());
+                    }
+
+                    if let Some(video_bitrate) = metadata.video_bitrate {
+                        parameters.insert("Video-Bitrate".to_string(), video_bitrate.to_string());
+                    }
+
+                    if let Some(audio_bitrate) = metadata.audio_bitrate {
+                        parameters.insert("Audio-Bitrate".to_string(), audio_bitrate.to_string());
+                    }
+
+                    if let Some(audio_sample_rate) = metadata.audio_sample_rate {
+                        parameters.insert(
+                            "Audio-Sample-Rate".to_string(),
+                            audio_sample_rate.to_string(),
+                        );
+                    }
+
+                    if let Some(audio_channels) = metadata.audio_channels {
+                        parameters.insert("Audio-Channels".to_string(), audio_channels.to_string());
+                    }
+
+                    if let Some(encoder) = metadata.encoder {
+                        parameters.insert("Encoder".to_string(), encoder);
+                    }
+
+                    if let Some(publisher) = publisher {
+                        add_publisher_parameters(&mut parameters, &publisher);
+                    }
+
+                    let msg = ControlServerMessage::new_with_parameters(
+                        "PUBLISH-METADATA".to_string(),
+                        parameters,
+                    );
+
+                    _ = ControlClientStatus::send_message(&status, msg, &logger).await;
+                }
+                ControlKeyValidationRequest::KeyframeIntervalWarning {
+                    channel,
+                    stream_id,
+                    keyframe_interval,
+                    threshold,
+                    publisher,
+                } => {
+                    // Send message to the server
+
+                    let mut parameters: HashMap<String, String> = HashMap::new();
+
+                    parameters.insert("Stream-Channel".to_string(), channel);
+                    parameters.insert("Stream-ID".to_string(), stream_id);
+                    parameters.insert(
+                        "Keyframe-Interval".to_string(),
+                        keyframe_interval.to_string(),
+                    );
+                    parameters.insert(
+                        "Keyframe-Interval-Threshold".to_string(),
+                        threshold.to_string(),
+                    );
+
+                    if let Some(publisher) = publisher {
+                        add_publisher_parameters(&mut parameters, &publisher);
+                    }
+
+                    let msg = ControlServerMessage::new_with_parameters(
+                        "PUBLISH-KEYFRAME-WARNING".to_string(),
+                        parameters,
+                    );
+
+                    _ = ControlClientStatus::send_message(&status, msg, &logger).await;
+                }
+            }
+        }
+    });
+}
+
+/// Spawns a task to complete a key validation request
+/// if the control server does not answer in time
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The control client configuration
+/// * `status` - The client status
+/// * `req_id` - The request ID
+fn spawn_task_key_validation_timeout(
+    logger: Arc<Logger>,
+    config: Arc<ControlServerConnectionConfig>,
+    status: Arc<Mutex<ControlClientStatus>>,
+    req_id: u64,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(
+            config.key_validation_timeout_ms as u64,
+        ))
+        .await;
+
+        let response = match config.key_validation_fallback {
+            ControlKeyValidationFallback::Reject => ControlKeyValidationResponse::Rejected,
+            ControlKeyValidationFallback::Callback => ControlKeyValidationResponse::Fallback,
+        };
+
+        if ControlClientStatus::complete_request(&status, req_id, response).await {
+            log_warning!(
+                logger,
+                format!(
+                    "Key validation request #{} timed out after {} ms",
+                    req_id, config.key_validation_timeout_ms
+                )
+            );
+        }
+    });
+}
@@ -2,6 +2,11 @@
 
 use std::collections::HashMap;
 
+use byteorder::{BigEndian, ByteOrder};
+
+/// Version of the binary frame layout
+const BINARY_FRAME_VERSION: u8 = 1;
+
 /// Control server message
 pub struct ControlServerMessage {
     /// Message type
@@ -75,6 +80,85 @@ impl ControlServerMessage {
         res
     }
 
+    /// Parses a message from a binary frame
+    ///
+    /// Layout (big endian): frame version (u8), type length (u16), type,
+    /// number of parameters (u16), and, for each parameter:
+    /// key length (u16), key, value length (u32), value
+    pub fn parse_binary(input: &[u8]) -> Result<ControlServerMessage, String> {
+        let mut cursor = BinaryFrameCursor { input, pos: 0 };
+
+        let frame_version = cursor.read(1)?[0];
+
+        if frame_version != BINARY_FRAME_VERSION {
+            return Err(format!(
+                "Unsupported binary frame version: {}",
+                frame_version
+            ));
+        }
+
+        let type_len = BigEndian::read_u16(cursor.read(2)?) as usize;
+        let msg_type = cursor.read_string(type_len)?.to_uppercase();
+
+        let param_count = BigEndian::read_u16(cursor.read(2)?) as usize;
+
+        let mut parameters: HashMap<String, String> = HashMap::new();
+
+        for _ in 0..param_count {
+            let key_len = BigEndian::read_u16(cursor.read(2)?) as usize;
+            let key = cursor.read_string(key_len)?.to_lowercase();
+
+            let val_len = BigEndian::read_u32(cursor.read(4)?) as usize;
+            let val = cursor.read_string(val_len)?;
+
+            parameters.insert(key, val);
+        }
+
+        if cursor.pos != input.len() {
+            return Err("Unexpected trailing bytes in binary frame".to_string());
+        }
+
+        if param_count == 0 {
+            return Ok(ControlServerMessage::new(msg_type));
+        }
+
+        Ok(ControlServerMessage::new_with_parameters(
+            msg_type, parameters,
+        ))
+    }
+
+    /// Serializes message to a binary frame,
+    /// in order to send it to the control server
+    pub fn serialize_binary(&self) -> Vec<u8> {
+        let msg_type = self.msg_type.to_uppercase();
+
+        let mut res: Vec<u8> = vec![BINARY_FRAME_VERSION];
+
+        push_u16_len(&mut res, msg_type.len());
+        res.extend_from_slice(msg_type.as_bytes());
+
+        match &self.parameters {
+            Some(parameters) => {
+                push_u16_len(&mut res, parameters.len());
+
+                for (key, val) in parameters {
+                    push_u16_len(&mut res, key.len());
+                    res.extend_from_slice(key.as_bytes());
+
+                    let mut val_len = [0u8; 4];
+                    BigEndian::write_u32(&mut val_len, val.len() as u32);
+                    res.extend_from_slice(&val_len);
+                    res.extend_from_slice(val.as_bytes());
+                }
+            }
+            None => {
+                push_u16_len(&mut res, 0);
+            }
+        }
+
+        res
+    }
+
     /// Gets the value of a parameter of the message
     pub fn get_parameter(&self, param_name: &str) -> Option<&str> {
         if let Some(parameters) = &self.parameters {
@@ -86,3 +170,82 @@ impl ControlServerMessage {
         }
     }
 }
+
+/// Appends a length as u16 (big endian)
+fn push_u16_len(buf: &mut Vec<u8>, len: usize) {
+    let mut len_bytes = [0u8; 2];
+    BigEndian::write_u16(&mut len_bytes, len.min(u16::MAX as usize) as u16);
+    buf.extend_from_slice(&len_bytes);
+}
+
+/// Cursor to read a binary frame
+struct BinaryFrameCursor<'a> {
+    /// Input frame
+    input: &'a [u8],
+
+    /// Current position
+    pos: usize,
+}
+
+impl<'a> BinaryFrameCursor<'a> {
+    /// Reads a number of bytes
+    fn read(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.input.len() - self.pos < len {
+            return Err("Unexpected end of binary frame".to_string());
+        }
+
+        let res = &self.input[self.pos..self.pos + len];
+        self.pos += len;
+
+        Ok(res)
+    }
+
+    /// Reads an UTF-8 string
+    fn read_string(&mut self, len: usize) -> Result<String, String> {
+        String::from_utf8(self.read(len)?.to_vec())
+            .map_err(|_| "Invalid UTF-8 string in binary frame".to_string())
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_frame_round_trip() {
+        let mut parameters: HashMap<String, String> = HashMap::new();
+        parameters.insert("request-id".to_string(), "12".to_string());
+        parameters.insert("stream-channel".to_string(), "ch:1\nx".to_string());
+
+        let msg =
+            ControlServerMessage::new_with_parameters("publish-accept".to_string(), parameters);
+
+        let parsed = ControlServerMessage::parse_binary(&msg.serialize_binary()).unwrap();
+
+        assert_eq!(parsed.msg_type, "PUBLISH-ACCEPT");
+        assert_eq!(parsed.get_parameter("Request-Id"), Some("12"));
+        assert_eq!(parsed.get_parameter("Stream-Channel"), Some("ch:1\nx"));
+
+        let msg = ControlServerMessage::new("HEARTBEAT".to_string());
+
+        let parsed = ControlServerMessage::parse_binary(&msg.serialize_binary()).unwrap();
+
+        assert_eq!(parsed.msg_type, "HEARTBEAT");
+        assert!(parsed.parameters.is_none());
+    }
+
+    #[test]
+    fn test_binary_frame_invalid() {
+        assert!(ControlServerMessage::parse_binary(&[]).is_err());
+        assert!(ControlServerMessage::parse_binary(&[2, 0, 0, 0, 0]).is_err());
+        assert!(ControlServerMessage::parse_binary(&[1, 0, 4, b'A']).is_err());
+        assert!(ControlServerMessage::parse_binary(&[1, 0, 1, b'A', 0, 0, 9]).is_err());
+        assert!(
+            ControlServerMessage::parse_binary(&[1, 0, 1, b'A', 0, 1, 0, 1, b'k', 0, 0, 0, 9])
+                .is_err()
+        );
+        assert!(ControlServerMessage::parse_binary(&[1, 0, 1, 0xff, 0, 0]).is_err());
+    }
+}
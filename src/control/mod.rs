@@ -1,17 +1,35 @@
 // Control server connection feature
 
+#[cfg(feature = "control")]
 mod auth;
+#[cfg(feature = "control")]
 mod client;
 mod config;
+#[cfg(feature = "control")]
 mod heartbeat;
 mod key_validation;
+#[cfg(feature = "control")]
+mod key_validation_task;
+#[cfg(feature = "control")]
 mod message;
+#[cfg(feature = "control")]
+mod protocol;
+#[cfg(feature = "control")]
 mod status;
 
+#[cfg(feature = "control")]
 pub use auth::*;
+#[cfg(feature = "control")]
 pub use client::*;
 pub use config::*;
+#[cfg(feature = "control")]
 pub use heartbeat::*;
 pub use key_validation::*;
+#[cfg(feature = "control")]
+pub use key_validation_task::*;
+#[cfg(feature = "control")]
 pub use message::*;
+#[cfg(feature = "control")]
+pub use protocol::*;
+#[cfg(feature = "control")]
 pub use status::*;
@@ -0,0 +1,204 @@
+// Control protocol version and frame format negotiation
+
+use tungstenite::http::HeaderMap;
+
+/// Version of the control protocol implemented by this server.
+/// Version 1 is the legacy protocol, with text frames only.
+pub const CONTROL_PROTOCOL_VERSION: u32 = 2;
+
+/// Request header with the protocol version of the client
+pub const CONTROL_PROTOCOL_VERSION_HEADER: &str = "x-control-protocol-version";
+
+/// Request header with the frame formats supported by the client (split by commas).
+/// The control server responds with the chosen format in the same header.
+pub const CONTROL_PROTOCOL_FORMAT_HEADER: &str = "x-control-protocol-format";
+
+/// Frame format of the control protocol
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ControlProtocolFormat {
+    /// Text frames (`TYPE\nKey: Value\n...`)
+    Text,
+
+    /// Binary frames (length-prefixed type and parameters)
+    Binary,
+}
+
+impl ControlProtocolFormat {
+    /// Parses the format
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string (`text` or `binary`)
+    pub fn parse(s: &str) -> Option<ControlProtocolFormat> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Some(ControlProtocolFormat::Text),
+            "binary" => Some(ControlProtocolFormat::Binary),
+            _ => None,
+        }
+    }
+
+    /// Gets the name of the format
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ControlProtocolFormat::Text => "text",
+            ControlProtocolFormat::Binary => "binary",
+        }
+    }
+}
+
+/// Result of the protocol negotiation with the control server
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ControlProtocolNegotiation {
+    /// Protocol version
+    pub version: u32,
+
+    /// Frame format
+    pub format: ControlProtocolFormat,
+}
+
+impl ControlProtocolNegotiation {
+    /// Gets the value of the format header for the connection request
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_frames` - True to offer binary frames
+    pub fn offered_formats(binary_frames: bool) -> String {
+        if binary_frames {
+            format!(
+                "{},{}",
+                ControlProtocolFormat::Binary.as_str(),
+                ControlProtocolFormat::Text.as_str()
+            )
+        } else {
+            ControlProtocolFormat::Text.as_str().to_string()
+        }
+    }
+
+    /// Reads the negotiated protocol from the headers
+    /// of the response of the control server.
+    /// Control servers not sending the headers use the legacy protocol (version 1, text).
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The response headers
+    /// * `binary_frames` - True if binary frames were offered
+    ///
+    /// # Return value
+    ///
+    /// Returns the negotiated protocol, or an error message if the response is not valid
+    pub fn from_response_headers(
+        headers: &HeaderMap,
+        binary_frames: bool,
+    ) -> Result<ControlProtocolNegotiation, String> {
+        let version = match headers.get(CONTROL_PROTOCOL_VERSION_HEADER) {
+            Some(v) => match v.to_str().ok().and_then(|v| v.trim().parse::<u32>().ok()) {
+                Some(v) if (1..=CONTROL_PROTOCOL_VERSION).contains(&v) => v,
+                _ => {
+                    return Err(format!(
+                        "Unsupported control protocol version: {}",
+                        String::from_utf8_lossy(v.as_bytes())
+                    ));
+                }
+            },
+            None => 1,
+        };
+
+        let format = match headers.get(CONTROL_PROTOCOL_FORMAT_HEADER) {
+            Some(f) if version > 1 => {
+                match f.to_str().ok().and_then(ControlProtocolFormat::parse) {
+                    Some(ControlProtocolFormat::Binary) if !binary_frames => {
+                        return Err(
+                            "The control server chose binary frames, which were not offered"
+                                .to_string(),
+                        );
+                    }
+                    Some(format) => format,
+                    None => {
+                        return Err(format!(
+                            "Unsupported control protocol format: {}",
+                            String::from_utf8_lossy(f.as_bytes())
+                        ));
+                    }
+                }
+            }
+            _ => ControlProtocolFormat::Text,
+        };
+
+        Ok(ControlProtocolNegotiation { version, format })
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_headers(version: Option<&str>, format: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(v) = version {
+            headers.insert(CONTROL_PROTOCOL_VERSION_HEADER, v.parse().unwrap());
+        }
+
+        if let Some(f) = format {
+            headers.insert(CONTROL_PROTOCOL_FORMAT_HEADER, f.parse().unwrap());
+        }
+
+        headers
+    }
+
+    #[test]
+    fn test_protocol_negotiation() {
+        // Legacy control server
+        assert_eq!(
+            ControlProtocolNegotiation::from_response_headers(&make_headers(None, None), true),
+            Ok(ControlProtocolNegotiation {
+                version: 1,
+                format: ControlProtocolFormat::Text,
+            })
+        );
+
+        assert_eq!(
+            ControlProtocolNegotiation::from_response_headers(
+                &make_headers(Some("2"), Some("binary")),
+                true
+            ),
+            Ok(ControlProtocolNegotiation {
+                version: 2,
+                format: ControlProtocolFormat::Binary,
+            })
+        );
+
+        assert_eq!(
+            ControlProtocolNegotiation::from_response_headers(
+                &make_headers(Some("2"), Some("text")),
+                true
+            ),
+            Ok(ControlProtocolNegotiation {
+                version: 2,
+                format: ControlProtocolFormat::Text,
+            })
+        );
+
+        // Binary frames not offered
+        assert!(ControlProtocolNegotiation::from_response_headers(
+            &make_headers(Some("2"), Some("binary")),
+            false
+        )
+        .is_err());
+
+        // Unsupported version or format
+        assert!(ControlProtocolNegotiation::from_response_headers(
+            &make_headers(Some("99"), None),
+            true
+        )
+        .is_err());
+
+        assert!(ControlProtocolNegotiation::from_response_headers(
+            &make_headers(Some("2"), Some("cbor")),
+            true
+        )
+        .is_err());
+    }
+}
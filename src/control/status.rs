@@ -12,7 +12,7 @@ use tungstenite::{Message, Utf8Bytes};
 
 use crate::{log::Logger, log_error, log_trace};
 
-use super::{ControlKeyValidationResponse, ControlServerMessage};
+use super::{ControlKeyValidationResponse, ControlProtocolFormat, ControlServerMessage};
 
 type ControlClientMessageSender =
     Arc<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>;
@@ -25,6 +25,9 @@ pub struct ControlClientStatus {
     /// The message sender
     pub msg_sender: Option<ControlClientMessageSender>,
 
+    /// Frame format negotiated with the control server
+    pub format: ControlProtocolFormat,
+
     /// Key validation request counter
     pub request_count: u64,
 
@@ -38,6 +41,7 @@ impl ControlClientStatus {
         ControlClientStatus {
             connected: false,
             msg_sender: None,
+            format: ControlProtocolFormat::Text,
             request_count: 0,
             pending_requests: HashMap::new(),
         }
@@ -47,11 +51,13 @@ impl ControlClientStatus {
     pub async fn set_connected(
         status: &Mutex<ControlClientStatus>,
         msg_sender: ControlClientMessageSender,
+        format: ControlProtocolFormat,
     ) {
         let mut status_v = status.lock().await;
 
         status_v.connected = true;
         status_v.msg_sender = Some(msg_sender);
+        status_v.format = format;
     }
 
     /// Sets the status to disconnected
@@ -80,6 +86,8 @@ impl ControlClientStatus {
             }
         };
 
+        let format = status_v.format;
+
         drop(status_v);
 
         let mut msg_sender_v = msg_sender.lock().await;
@@ -88,10 +96,16 @@ impl ControlClientStatus {
 
         log_trace!(logger, format!("SENT MESSAGE: {}", msg_serialized));
 
-        match msg_sender_v
-            .send(tungstenite::Message::Text(Utf8Bytes::from(msg_serialized)))
-            .await
-        {
+        let ws_message = match format {
+            ControlProtocolFormat::Text => {
+                tungstenite::Message::Text(Utf8Bytes::from(msg_serialized))
+            }
+            ControlProtocolFormat::Binary => {
+                tungstenite::Message::Binary(message.serialize_binary().into())
+            }
+        };
+
+        match msg_sender_v.send(ws_message).await {
             Ok(_) => true,
             Err(e) => {
                 log_error!(logger, &format!("Could not send a message: {}", e));
@@ -128,11 +142,12 @@ impl ControlClientStatus {
     }
 
     /// Completes pending key validation request
+    /// Returns true if the request was pending, false if it was already completed
     pub async fn complete_request(
         status: &Mutex<ControlClientStatus>,
         id: u64,
         response: ControlKeyValidationResponse,
-    ) {
+    ) -> bool {
         let mut status_v = status.lock().await;
 
         if let Some(rs) = status_v.pending_requests.get_mut(&id) {
@@ -141,6 +156,10 @@ impl ControlClientStatus {
             drop(status_v);
 
             _ = response_sender.send(response).await;
+
+            true
+        } else {
+            false
         }
     }
 
@@ -0,0 +1,29 @@
+// Geolocation configuration
+
+use crate::utils::get_env_string;
+
+/// Geolocation configuration
+#[derive(Clone, Default)]
+pub struct GeoIpConfiguration {
+    /// Path to the MaxMind country (or city) database (empty to disable)
+    pub country_database: String,
+
+    /// Path to the MaxMind ASN database (empty to disable)
+    pub asn_database: String,
+}
+
+impl GeoIpConfiguration {
+    /// Loads the geolocation configuration
+    /// from environment variables
+    pub fn load_from_env() -> GeoIpConfiguration {
+        GeoIpConfiguration {
+            country_database: get_env_string("GEOIP_COUNTRY_DATABASE", ""),
+            asn_database: get_env_string("GEOIP_ASN_DATABASE", ""),
+        }
+    }
+
+    /// Checks if any database is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.country_database.is_empty() || !self.asn_database.is_empty()
+    }
+}
@@ -0,0 +1,188 @@
+// Lookup of the client IP addresses in the MaxMind databases
+
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use super::GeoIpConfiguration;
+
+/// Geolocation of a client IP address
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GeoIpInfo {
+    /// Country (ISO 3166-1 alpha-2 code)
+    pub country: Option<String>,
+
+    /// Autonomous system number
+    pub asn: Option<u32>,
+
+    /// Organization of the autonomous system
+    pub as_org: Option<String>,
+}
+
+impl GeoIpInfo {
+    /// Gets a string to describe the geolocation in the logs
+    pub fn to_log_string(&self) -> String {
+        format!(
+            "Country: {} | ASN: {} | AS Organization: {}",
+            self.country.as_deref().unwrap_or("-"),
+            self.asn
+                .map(|a| format!("AS{}", a))
+                .unwrap_or_else(|| "-".to_string()),
+            self.as_org.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+/// Loaded MaxMind databases, to geolocate the client IP addresses
+pub struct GeoIpDatabase {
+    /// Country database
+    #[cfg(feature = "geoip")]
+    country: Option<maxminddb::Reader<Vec<u8>>>,
+
+    /// ASN database
+    #[cfg(feature = "geoip")]
+    asn: Option<maxminddb::Reader<Vec<u8>>>,
+}
+
+impl GeoIpDatabase {
+    /// Loads the configured databases
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The geolocation configuration
+    #[cfg(feature = "geoip")]
+    pub fn load(config: &GeoIpConfiguration) -> Result<GeoIpDatabase, String> {
+        let open = |path: &str| {
+            if path.is_empty() {
+                return Ok(None);
+            }
+
+            maxminddb::Reader::open_readfile(path)
+                .map(Some)
+                .map_err(|e| format!("Could not load GeoIP database {}: {}", path, e))
+        };
+
+        Ok(GeoIpDatabase {
+            country: open(&config.country_database)?,
+            asn: open(&config.asn_database)?,
+        })
+    }
+
+    /// Loads the configured databases
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The geolocation configuration
+    #[cfg(not(feature = "geoip"))]
+    pub fn load(config: &GeoIpConfiguration) -> Result<GeoIpDatabase, String> {
+        if config.is_enabled() {
+            return Err(
+                "A GeoIP database is configured, but the server was built without the geoip feature"
+                    .to_string(),
+            );
+        }
+
+        Ok(GeoIpDatabase {})
+    }
+
+    /// Checks if any database is loaded
+    pub fn is_enabled(&self) -> bool {
+        #[cfg(feature = "geoip")]
+        {
+            self.country.is_some() || self.asn.is_some()
+        }
+
+        #[cfg(not(feature = "geoip"))]
+        {
+            false
+        }
+    }
+
+    /// Geolocates an IP address
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address
+    ///
+    /// # Return value
+    ///
+    /// Returns the geolocation, or None if nothing was found
+    #[cfg(feature = "geoip")]
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoIpInfo> {
+        use maxminddb::geoip2;
+
+        let mut info = GeoIpInfo::default();
+
+        if let Some(Ok(Some(country))) = self
+            .country
+            .as_ref()
+            .map(|r| r.lookup::<geoip2::Country>(ip))
+        {
+            info.country = country
+                .country
+                .and_then(|c| c.iso_code)
+                .map(|c| c.to_string());
+        }
+
+        if let Some(Ok(Some(asn))) = self.asn.as_ref().map(|r| r.lookup::<geoip2::Asn>(ip)) {
+            info.asn = asn.autonomous_system_number;
+            info.as_org = asn.autonomous_system_organization.map(|o| o.to_string());
+        }
+
+        if info == GeoIpInfo::default() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    /// Geolocates an IP address
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address
+    ///
+    /// # Return value
+    ///
+    /// Returns the geolocation, or None if nothing was found
+    #[cfg(not(feature = "geoip"))]
+    pub fn lookup(&self, _ip: IpAddr) -> Option<GeoIpInfo> {
+        None
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geoip_info_log_string() {
+        let info = GeoIpInfo {
+            country: Some("ES".to_string()),
+            asn: Some(3352),
+            as_org: None,
+        };
+
+        assert_eq!(
+            info.to_log_string(),
+            "Country: ES | ASN: AS3352 | AS Organization: -"
+        );
+    }
+
+    #[test]
+    fn test_geoip_database_disabled() {
+        let database = GeoIpDatabase::load(&GeoIpConfiguration::default()).unwrap();
+
+        assert!(!database.is_enabled());
+        assert_eq!(database.lookup("1.1.1.1".parse().unwrap()), None);
+
+        let missing = GeoIpDatabase::load(&GeoIpConfiguration {
+            country_database: "/nonexistent/GeoLite2-Country.mmdb".to_string(),
+            asn_database: "".to_string(),
+        });
+
+        assert!(missing.is_err());
+    }
+}
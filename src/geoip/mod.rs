@@ -0,0 +1,7 @@
+// Geolocation of the client IP addresses
+
+mod config;
+mod database;
+
+pub use config::*;
+pub use database::*;
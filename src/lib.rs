@@ -0,0 +1,24 @@
+// Library target, exposing the server internals for the benchmarks
+// Only built with the bench-internals feature
+
+#![cfg(feature = "bench-internals")]
+// The modules are shared with the binary, and were not designed as a public API
+#![allow(clippy::new_without_default, clippy::result_unit_err)]
+
+pub mod acme;
+pub mod amf;
+pub mod auth;
+pub mod callback;
+pub mod chaos;
+pub mod cluster;
+pub mod control;
+pub mod geoip;
+pub mod log;
+pub mod record;
+pub mod redis;
+pub mod rtmp;
+pub mod server;
+pub mod session;
+pub mod slate;
+pub mod trace;
+pub mod utils;
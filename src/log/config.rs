@@ -1,5 +1,42 @@
 // Log config
 
+use crate::utils::{get_env_bool, get_env_string};
+
+use super::LogLevel;
+
+/// Format of the timestamps of the log lines
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogTimestampFormat {
+    /// Local date and time (eg: `[2024-01-01 10:00:00]`)
+    Local,
+
+    /// RFC 3339, with milliseconds (eg: `[2024-01-01T10:00:00.000+01:00]`)
+    Rfc3339,
+
+    /// Unix timestamp, in milliseconds (eg: `[1704099600000]`)
+    Epoch,
+
+    /// No timestamp
+    None,
+}
+
+impl LogTimestampFormat {
+    /// Parses the timestamp format from a string
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string (`LOCAL`, `RFC3339`, `EPOCH` or `NONE`)
+    pub fn parse(s: &str) -> Result<LogTimestampFormat, String> {
+        match s.trim().to_uppercase().as_str() {
+            "" | "LOCAL" => Ok(LogTimestampFormat::Local),
+            "RFC3339" => Ok(LogTimestampFormat::Rfc3339),
+            "EPOCH" => Ok(LogTimestampFormat::Epoch),
+            "NONE" => Ok(LogTimestampFormat::None),
+            _ => Err(format!("Invalid log timestamp format: {}", s)),
+        }
+    }
+}
+
 /// Logger configuration
 pub struct LogConfig {
     // Prefix for all the logs
@@ -19,9 +56,56 @@ pub struct LogConfig {
 
     // Trace messages enabled?
     pub trace_enabled: bool,
+
+    // Format of the timestamps
+    pub timestamp_format: LogTimestampFormat,
+
+    // Colors enabled? (only applied if the output is a terminal)
+    pub colors_enabled: bool,
 }
 
 impl LogConfig {
+    /// Loads the logger configuration
+    /// from environment variables
+    ///
+    /// If the timestamp format is not valid, the default one is used,
+    /// returning an error message to log it
+    pub fn load_from_env() -> (LogConfig, Option<String>) {
+        let (timestamp_format, error) =
+            match LogTimestampFormat::parse(&get_env_string("LOG_TIMESTAMP_FORMAT", "LOCAL")) {
+                Ok(f) => (f, None),
+                Err(e) => (LogTimestampFormat::Local, Some(e)),
+            };
+
+        let config = LogConfig {
+            prefix: "".to_string(),
+            error_enabled: get_env_bool("LOG_ERROR", true),
+            warning_enabled: get_env_bool("LOG_WARNING", true),
+            info_enabled: get_env_bool("LOG_INFO", true),
+            debug_enabled: get_env_bool("LOG_DEBUG", false),
+            trace_enabled: get_env_bool("LOG_TRACE", get_env_bool("LOG_DEBUG", false)),
+            timestamp_format,
+            colors_enabled: get_env_bool("LOG_COLORS", false),
+        };
+
+        (config, error)
+    }
+
+    /// Checks if the messages of a level are enabled
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The level
+    pub fn is_level_enabled(&self, level: LogLevel) -> bool {
+        match level {
+            LogLevel::Error => self.error_enabled,
+            LogLevel::Warning => self.warning_enabled,
+            LogLevel::Info => self.info_enabled,
+            LogLevel::Debug => self.debug_enabled,
+            LogLevel::Trace => self.trace_enabled,
+        }
+    }
+
     /// Creates a child configuration for a child logger
     ///
     /// The prefix parameter will be added to the parent's prefix,
@@ -36,6 +120,8 @@ impl LogConfig {
             info_enabled: self.info_enabled,
             debug_enabled: self.debug_enabled,
             trace_enabled: self.trace_enabled,
+            timestamp_format: self.timestamp_format,
+            colors_enabled: self.colors_enabled,
         }
     }
 }
@@ -1,18 +1,179 @@
 // Logger
 
-use super::config::LogConfig;
-use chrono::{DateTime, Local};
+use std::{
+    io::IsTerminal,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use super::{
+    config::{LogConfig, LogTimestampFormat},
+    get_log_packet_sample_rate, LogSessionScope,
+};
+use chrono::{DateTime, Local, SecondsFormat};
+
+/// Level of a log message, from the most to the least severe
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// Parses the level from a string
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string (`ERROR`, `WARNING`, `INFO`, `DEBUG` or `TRACE`)
+    #[cfg(feature = "control")]
+    pub fn parse(s: &str) -> Result<LogLevel, String> {
+        match s.trim().to_uppercase().as_str() {
+            "ERROR" => Ok(LogLevel::Error),
+            "WARNING" => Ok(LogLevel::Warning),
+            "INFO" => Ok(LogLevel::Info),
+            "DEBUG" => Ok(LogLevel::Debug),
+            "TRACE" => Ok(LogLevel::Trace),
+            _ => Err(format!("Invalid log level: {}", s.trim())),
+        }
+    }
+
+    /// Gets the name of the level
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warning => "WARNING",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+
+    /// Gets the tag of the level, added to the log lines
+    pub fn tag(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "[ERROR]",
+            LogLevel::Warning => "[WARNING]",
+            LogLevel::Info => "[INFO]",
+            LogLevel::Debug => "[DEBUG]",
+            LogLevel::Trace => "[TRACE]",
+        }
+    }
+
+    /// Gets the ANSI color code of the level
+    pub fn color(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "\x1b[31m",
+            LogLevel::Warning => "\x1b[33m",
+            LogLevel::Info => "\x1b[32m",
+            LogLevel::Debug => "\x1b[36m",
+            LogLevel::Trace => "\x1b[90m",
+        }
+    }
+}
+
+/// ANSI code to reset the color
+const COLOR_RESET: &str = "\x1b[0m";
+
+/// Max level of the messages to log, set at runtime for all the loggers.
+/// 0 if not set (the configuration of each logger is used), otherwise the level + 1.
+static LOG_LEVEL_OVERRIDE: AtomicU8 = AtomicU8::new(0);
+
+/// Levels enabled by reloading the configuration at runtime, for all the loggers.
+/// 0 if not reloaded, otherwise LOG_LEVELS_RELOADED_FLAG with a bit set for each enabled level.
+static LOG_LEVELS_RELOADED: AtomicU8 = AtomicU8::new(0);
+
+/// Flag of LOG_LEVELS_RELOADED, set when the configuration was reloaded
+const LOG_LEVELS_RELOADED_FLAG: u8 = 0x80;
+
+/// All the log levels
+pub const LOG_LEVELS: [LogLevel; 5] = [
+    LogLevel::Error,
+    LogLevel::Warning,
+    LogLevel::Info,
+    LogLevel::Debug,
+    LogLevel::Trace,
+];
+
+/// Sets the levels to log, for all the loggers,
+/// replacing the ones they were created with.
+/// The max level set with set_log_level_override takes precedence.
+///
+/// # Arguments
+///
+/// * `config` - The reloaded configuration
+pub fn set_reloaded_log_levels(config: &LogConfig) {
+    let mask = LOG_LEVELS
+        .iter()
+        .filter(|l| config.is_level_enabled(**l))
+        .fold(LOG_LEVELS_RELOADED_FLAG, |mask, l| mask | (1 << (*l as u8)));
+
+    LOG_LEVELS_RELOADED.store(mask, Ordering::Relaxed);
+}
+
+/// Sets the max level of the messages to log, for all the loggers,
+/// overriding their configuration
+///
+/// # Arguments
+///
+/// * `level` - The max level, or None to use the configuration again
+#[cfg(feature = "control")]
+pub fn set_log_level_override(level: Option<LogLevel>) {
+    LOG_LEVEL_OVERRIDE.store(level.map(|l| l as u8 + 1).unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Gets the max level of the messages to log, if set at runtime
+fn get_log_level_override() -> Option<LogLevel> {
+    match LOG_LEVEL_OVERRIDE.load(Ordering::Relaxed) {
+        1 => Some(LogLevel::Error),
+        2 => Some(LogLevel::Warning),
+        3 => Some(LogLevel::Info),
+        4 => Some(LogLevel::Debug),
+        5 => Some(LogLevel::Trace),
+        _ => None,
+    }
+}
 
 /// Logger
 pub struct Logger {
     /// Configuration
     pub config: LogConfig,
+
+    /// True to use colors (enabled and the output is a terminal)
+    use_colors: bool,
+
+    /// True if fully disabled, ignoring the level override
+    disabled: bool,
+
+    /// Session of the logger, to apply the session filter
+    session: Option<Arc<LogSessionScope>>,
+
+    /// Counter of the packet messages, for sampling
+    packet_counter: AtomicU64,
 }
 
 impl Logger {
     // Creates new logger
     pub fn new(config: LogConfig) -> Logger {
-        Logger { config }
+        let use_colors = config.colors_enabled
+            && if config.trace_enabled {
+                std::io::stderr().is_terminal()
+            } else {
+                std::io::stdout().is_terminal()
+            };
+
+        Logger {
+            config,
+            use_colors,
+            disabled: false,
+            session: None,
+            packet_counter: AtomicU64::new(0),
+        }
     }
 
     // Creates new fully disabled logger
@@ -25,7 +186,13 @@ impl Logger {
                 info_enabled: false,
                 debug_enabled: false,
                 trace_enabled: false,
+                timestamp_format: LogTimestampFormat::None,
+                colors_enabled: false,
             },
+            use_colors: false,
+            disabled: true,
+            session: None,
+            packet_counter: AtomicU64::new(0),
         }
     }
 
@@ -33,18 +200,287 @@ impl Logger {
     pub fn make_child_logger(&self, prefix: &str) -> Logger {
         Logger {
             config: self.config.child_config(prefix),
+            use_colors: self.use_colors,
+            disabled: self.disabled,
+            session: self.session.clone(),
+            packet_counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Makes child logger for a session.
+    /// The info, debug and trace messages are only logged
+    /// if the session passes the session filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Prefix of the child logger
+    /// * `ip` - Client IP address of the session
+    pub fn make_session_logger(&self, prefix: &str, ip: IpAddr) -> Logger {
+        let mut logger = self.make_child_logger(prefix);
+
+        logger.session = Some(Arc::new(LogSessionScope::new(ip)));
+
+        logger
+    }
+
+    /// Sets the channel of the session of the logger,
+    /// to apply the session filter
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub fn set_session_channel(&self, channel: &str) {
+        if let Some(session) = &self.session {
+            session.set_channel(channel);
+        }
+    }
+
+    /// Counts a packet message, checking if it must be logged
+    /// according to the packet sample rate
+    pub fn sample_packet(&self) -> bool {
+        let rate = get_log_packet_sample_rate();
+
+        rate <= 1
+            || self
+                .packet_counter
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(rate)
+    }
+
+    /// Checks if the messages of a level are logged
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The level
+    pub fn is_enabled(&self, level: LogLevel) -> bool {
+        if !self.is_level_enabled(level) {
+            return false;
+        }
+
+        // Session filter
+
+        match &self.session {
+            Some(session) if level >= LogLevel::Info => session.is_selected(),
+            _ => true,
+        }
+    }
+
+    /// Checks if a level is enabled, without applying the session filter
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The level
+    fn is_level_enabled(&self, level: LogLevel) -> bool {
+        if !self.disabled {
+            if let Some(max_level) = get_log_level_override() {
+                return level <= max_level;
+            }
+
+            let reloaded = LOG_LEVELS_RELOADED.load(Ordering::Relaxed);
+
+            if reloaded & LOG_LEVELS_RELOADED_FLAG != 0 {
+                return reloaded & (1 << (level as u8)) != 0;
+            }
+        }
+
+        self.config.is_level_enabled(level)
+    }
+
+    /// Formats a log line
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - Time of the message
+    /// * `level` - Level of the message
+    /// * `msg` - The message
+    fn format_line(&self, time: DateTime<Local>, level: LogLevel, msg: &str) -> String {
+        let time_str = match self.config.timestamp_format {
+            LogTimestampFormat::Local => time.format("[%Y-%m-%d %H:%M:%S] ").to_string(),
+            LogTimestampFormat::Rfc3339 => {
+                format!("[{}] ", time.to_rfc3339_opts(SecondsFormat::Millis, false))
+            }
+            LogTimestampFormat::Epoch => format!("[{}] ", time.timestamp_millis()),
+            LogTimestampFormat::None => "".to_string(),
+        };
+
+        if self.use_colors {
+            format!(
+                "{}{}{}{}{} {}",
+                time_str,
+                self.config.prefix,
+                level.color(),
+                level.tag(),
+                COLOR_RESET,
+                msg
+            )
+        } else {
+            format!("{}{}{} {}", time_str, self.config.prefix, level.tag(), msg)
         }
     }
 
     /// Logs a message
-    pub fn log(&self, line: &str) {
-        let time_local: DateTime<Local> = Local::now();
-        let time_format = time_local.format("[%Y-%m-%d %H:%M:%S] ");
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - Level of the message
+    /// * `msg` - The message
+    pub fn log(&self, level: LogLevel, msg: &str) {
+        let line = self.format_line(Local::now(), level, msg);
 
         if self.config.trace_enabled {
-            eprintln!("{}{}{}", time_format, self.config.prefix, line);
+            eprintln!("{}", line);
         } else {
-            println!("{}{}{}", time_format, self.config.prefix, line);
+            println!("{}", line);
         }
     }
 }
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    use crate::{
+        log::{set_log_packet_sample_rate, set_log_session_filter},
+        trace::SessionTraceFilter,
+    };
+
+    /// Lock for the tests changing the global state of the loggers
+    static GLOBAL_STATE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_format_line() {
+        let mut logger = Logger::new_disabled();
+
+        logger.config.prefix = "[TEST] ".to_string();
+
+        let time = Local.timestamp_millis_opt(1704099600123).unwrap();
+
+        assert_eq!(
+            logger.format_line(time, LogLevel::Info, "Message"),
+            "[TEST] [INFO] Message"
+        );
+
+        logger.config.timestamp_format = LogTimestampFormat::Epoch;
+
+        assert_eq!(
+            logger.format_line(time, LogLevel::Error, "Message"),
+            "[1704099600123] [TEST] [ERROR] Message"
+        );
+
+        logger.config.timestamp_format = LogTimestampFormat::Rfc3339;
+
+        let line = logger.format_line(time, LogLevel::Debug, "Message");
+
+        assert!(line.starts_with("[2024-01-0"));
+        assert!(line.contains(".123"));
+        assert!(line.ends_with("] [TEST] [DEBUG] Message"));
+
+        logger.use_colors = true;
+        logger.config.timestamp_format = LogTimestampFormat::None;
+
+        assert_eq!(
+            logger.format_line(time, LogLevel::Warning, "Message"),
+            "[TEST] \x1b[33m[WARNING]\x1b[0m Message"
+        );
+
+        assert_eq!(
+            LogTimestampFormat::parse("rfc3339"),
+            Ok(LogTimestampFormat::Rfc3339)
+        );
+        assert!(LogTimestampFormat::parse("iso").is_err());
+    }
+
+    #[cfg(feature = "control")]
+    #[test]
+    fn test_log_level_override() {
+        let _lock = GLOBAL_STATE_LOCK.lock().unwrap();
+
+        assert_eq!(LogLevel::parse(" debug"), Ok(LogLevel::Debug));
+        assert!(LogLevel::parse("verbose").is_err());
+
+        let mut logger = Logger::new_disabled();
+
+        logger.disabled = false;
+        logger.config.info_enabled = true;
+
+        assert!(logger.is_enabled(LogLevel::Info));
+        assert!(!logger.is_enabled(LogLevel::Debug));
+
+        set_log_level_override(Some(LogLevel::Debug));
+
+        assert!(logger.is_enabled(LogLevel::Debug));
+        assert!(!logger.is_enabled(LogLevel::Trace));
+        assert!(!Logger::new_disabled().is_enabled(LogLevel::Error));
+
+        set_log_level_override(None);
+
+        assert!(!logger.is_enabled(LogLevel::Debug));
+
+        // Reloaded configuration
+
+        let mut reloaded = Logger::new_disabled().config;
+
+        reloaded.error_enabled = true;
+        reloaded.trace_enabled = true;
+
+        set_reloaded_log_levels(&reloaded);
+
+        assert!(logger.is_enabled(LogLevel::Error));
+        assert!(!logger.is_enabled(LogLevel::Info));
+        assert!(logger.is_enabled(LogLevel::Trace));
+
+        // The override takes precedence
+
+        set_log_level_override(Some(LogLevel::Info));
+
+        assert!(logger.is_enabled(LogLevel::Info));
+        assert!(!logger.is_enabled(LogLevel::Trace));
+
+        set_log_level_override(None);
+        LOG_LEVELS_RELOADED.store(0, Ordering::Relaxed);
+
+        assert!(logger.is_enabled(LogLevel::Info));
+    }
+
+    #[test]
+    fn test_session_logger_sampling() {
+        let _lock = GLOBAL_STATE_LOCK.lock().unwrap();
+
+        let mut root = Logger::new_disabled();
+
+        root.disabled = false;
+        root.config.error_enabled = true;
+        root.config.info_enabled = true;
+
+        let logger = root.make_session_logger("[#1] ", "10.0.0.1".parse().unwrap());
+
+        // Session filter
+
+        set_log_session_filter(Some(SessionTraceFilter::Channel("live".to_string())));
+
+        assert!(logger.is_enabled(LogLevel::Error));
+        assert!(!logger.is_enabled(LogLevel::Info));
+        assert!(root.is_enabled(LogLevel::Info));
+
+        logger.make_child_logger("").set_session_channel("live");
+
+        assert!(logger.is_enabled(LogLevel::Info));
+
+        set_log_session_filter(None);
+
+        // Packet sampling
+
+        set_log_packet_sample_rate(3);
+
+        let sampled = (0..9).filter(|_| logger.sample_packet()).count();
+
+        set_log_packet_sample_rate(1);
+
+        assert_eq!(sampled, 3);
+        assert!(logger.sample_packet());
+    }
+}
@@ -2,9 +2,15 @@
 
 mod config;
 mod logger;
+mod panic_hook;
+mod reload;
+mod sampling;
 
 pub use config::*;
 pub use logger::*;
+pub use panic_hook::*;
+pub use reload::*;
+pub use sampling::*;
 
 #[macro_export]
 macro_rules! log_error {
@@ -12,8 +18,8 @@ macro_rules! log_error {
     // The first argument must be the logger
     // The second argument must be the message to log, as a string
     ($logger:ident, $msg:expr) => {
-        if $logger.config.error_enabled {
-            $logger.log(&format!("[ERROR] {}", $msg));
+        if $logger.is_enabled($crate::log::LogLevel::Error) {
+            $logger.log($crate::log::LogLevel::Error, &format!("{}", $msg));
         }
     };
 }
@@ -24,8 +30,8 @@ macro_rules! log_warning {
     // The first argument must be the logger
     // The second argument must be the message to log, as a string
     ($logger:ident, $msg:expr) => {
-        if $logger.config.warning_enabled {
-            $logger.log(&format!("[WARNING] {}", $msg));
+        if $logger.is_enabled($crate::log::LogLevel::Warning) {
+            $logger.log($crate::log::LogLevel::Warning, &format!("{}", $msg));
         }
     };
 }
@@ -36,8 +42,8 @@ macro_rules! log_info {
     // The first argument must be the logger
     // The second argument must be the message to log, as a string
     ($logger:ident, $msg:expr) => {
-        if $logger.config.info_enabled {
-            $logger.log(&format!("[INFO] {}", $msg));
+        if $logger.is_enabled($crate::log::LogLevel::Info) {
+            $logger.log($crate::log::LogLevel::Info, &format!("{}", $msg));
         }
     };
 }
@@ -48,8 +54,8 @@ macro_rules! log_debug {
     // The first argument must be the logger
     // The second argument must be the message to log, as a string
     ($logger:ident, $msg:expr) => {
-        if $logger.config.debug_enabled {
-            $logger.log(&format!("[DEBUG] {}", $msg));
+        if $logger.is_enabled($crate::log::LogLevel::Debug) {
+            $logger.log($crate::log::LogLevel::Debug, &format!("{}", $msg));
         }
     };
 }
@@ -60,8 +66,21 @@ macro_rules! log_trace {
     // The first argument must be the logger
     // The second argument must be the message to log, as a string
     ($logger:ident, $msg:expr) => {
-        if $logger.config.trace_enabled {
-            $logger.log(&format!("[TRACE] {}", $msg));
+        if $logger.is_enabled($crate::log::LogLevel::Trace) {
+            $logger.log($crate::log::LogLevel::Trace, &format!("{}", $msg));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_trace_sampled {
+    // This marco logs a TRACE message for a packet, only if the TRACE level is enabled,
+    // and only 1 of every N messages, according to the packet sample rate
+    // The first argument must be the logger
+    // The second argument must be the message to log, as a string
+    ($logger:ident, $msg:expr) => {
+        if $logger.is_enabled($crate::log::LogLevel::Trace) && $logger.sample_packet() {
+            $logger.log($crate::log::LogLevel::Trace, &format!("{}", $msg));
         }
     };
 }
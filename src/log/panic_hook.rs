@@ -0,0 +1,68 @@
+// Logging of the panics
+
+use std::any::Any;
+
+use crate::log_error;
+
+use super::Logger;
+
+/// Gets the message of a panic, from its payload
+///
+/// # Arguments
+///
+/// * `payload` - The payload of the panic
+pub fn get_panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+/// Installs a hook to log the panics with the logger,
+/// instead of printing them to the standard error
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+pub fn install_panic_hook(logger: Logger) {
+    std::panic::set_hook(Box::new(move |info| {
+        let location = match info.location() {
+            Some(l) => format!("{}:{}:{}", l.file(), l.line(), l.column()),
+            None => "unknown location".to_string(),
+        };
+
+        log_error!(
+            logger,
+            format!(
+                "Panic at {}: {}",
+                location,
+                get_panic_message(info.payload())
+            )
+        );
+    }));
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_panic_message() {
+        let payload: Box<dyn Any + Send> = Box::new("static message");
+
+        assert_eq!(get_panic_message(payload.as_ref()), "static message");
+
+        let payload: Box<dyn Any + Send> = Box::new(format!("message {}", 1));
+
+        assert_eq!(get_panic_message(payload.as_ref()), "message 1");
+
+        let payload: Box<dyn Any + Send> = Box::new(1);
+
+        assert_eq!(get_panic_message(payload.as_ref()), "Unknown panic payload");
+    }
+}
@@ -0,0 +1,90 @@
+// Reload of the log levels at runtime
+
+use crate::{log_info, log_warning};
+
+use super::{
+    set_log_sampling, set_reloaded_log_levels, LogConfig, LogSamplingConfig, Logger, LOG_LEVELS,
+};
+
+/// Prefix of the variables reloaded from the .env file
+const LOG_ENV_PREFIX: &str = "LOG_";
+
+/// Reloads the log levels and the log sampling configuration
+/// from the environment variables, updating the ones set in the .env file
+///
+/// # Return value
+///
+/// Returns the reloaded configuration,
+/// and an error message if the sampling configuration is not valid (not reloaded)
+pub fn reload_log_levels() -> (LogConfig, Option<String>) {
+    if let Ok(vars) = dotenvy::dotenv_iter() {
+        for (key, value) in vars.flatten() {
+            if key.starts_with(LOG_ENV_PREFIX) {
+                std::env::set_var(key, value);
+            }
+        }
+    }
+
+    let (config, _) = LogConfig::load_from_env();
+
+    set_reloaded_log_levels(&config);
+
+    let error = match LogSamplingConfig::load_from_env() {
+        Ok(sampling) => {
+            set_log_sampling(&sampling);
+            None
+        }
+        Err(e) => Some(e),
+    };
+
+    (config, error)
+}
+
+/// Spawns a task to reload the log levels
+/// every time the SIGHUP signal is received
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+#[cfg(unix)]
+pub fn spawn_task_reload_log_levels_on_signal(logger: Logger) {
+    tokio::spawn(async move {
+        let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(s) => s,
+            Err(e) => {
+                log_warning!(
+                    logger,
+                    format!("Could not listen for the reload signal: {}", e)
+                );
+                return;
+            }
+        };
+
+        loop {
+            sighup.recv().await;
+
+            let (config, error) = reload_log_levels();
+
+            if let Some(e) = error {
+                log_warning!(logger, e);
+            }
+
+            let enabled: Vec<&str> = LOG_LEVELS
+                .into_iter()
+                .filter(|l| config.is_level_enabled(*l))
+                .map(|l| l.as_str())
+                .collect();
+
+            log_info!(
+                logger,
+                format!("Log levels reloaded. Enabled: {}", enabled.join(", "))
+            );
+        }
+    });
+}
+
+/// Spawns a task to reload the log levels
+/// (not supported on this platform)
+#[cfg(not(unix))]
+pub fn spawn_task_reload_log_levels_on_signal(_logger: Logger) {}
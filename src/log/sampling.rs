@@ -0,0 +1,207 @@
+// Sampling of the log messages, to reduce the log volume
+
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use crate::{trace::SessionTraceFilter, utils::get_env_string};
+
+/// Log only 1 of every N messages of each packet (1 to log all of them)
+static LOG_PACKET_SAMPLE_RATE: AtomicU64 = AtomicU64::new(1);
+
+/// True if the session filter is set
+static LOG_SESSION_FILTER_SET: AtomicBool = AtomicBool::new(false);
+
+/// Filter to select the sessions to log
+static LOG_SESSION_FILTER: RwLock<Option<SessionTraceFilter>> = RwLock::new(None);
+
+/// Log sampling configuration
+#[derive(Clone)]
+pub struct LogSamplingConfig {
+    /// Log only 1 of every N messages of each packet (1 to log all of them)
+    pub packet_sample_rate: u64,
+
+    /// Filter to select the sessions to log
+    pub session_filter: Option<SessionTraceFilter>,
+}
+
+impl Default for LogSamplingConfig {
+    fn default() -> Self {
+        LogSamplingConfig {
+            packet_sample_rate: 1,
+            session_filter: None,
+        }
+    }
+}
+
+impl LogSamplingConfig {
+    /// Loads the log sampling configuration
+    /// from environment variables
+    pub fn load_from_env() -> Result<LogSamplingConfig, String> {
+        let packet_sample_rate_str = get_env_string("LOG_PACKET_SAMPLE_RATE", "1");
+
+        let packet_sample_rate = parse_log_packet_sample_rate(&packet_sample_rate_str)?;
+
+        let session_filter = parse_log_session_filter(&get_env_string("LOG_SESSION_FILTER", ""))?;
+
+        Ok(LogSamplingConfig {
+            packet_sample_rate,
+            session_filter,
+        })
+    }
+}
+
+/// Parses the sample rate of the packet messages
+///
+/// # Arguments
+///
+/// * `s` - The string (positive number)
+pub fn parse_log_packet_sample_rate(s: &str) -> Result<u64, String> {
+    match s.trim().parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Invalid log packet sample rate: {}", s.trim())),
+    }
+}
+
+/// Parses the filter of the sessions to log
+///
+/// # Arguments
+///
+/// * `s` - The string: `ip:IP`, `channel:CHANNEL`, or empty to log all the sessions
+pub fn parse_log_session_filter(s: &str) -> Result<Option<SessionTraceFilter>, String> {
+    let s = s.trim();
+
+    if s.is_empty() {
+        return Ok(None);
+    }
+
+    match s.split_once(':') {
+        Some((kind, value)) => SessionTraceFilter::parse(kind, value).map(Some),
+        None => Err(format!("Invalid log session filter: {}", s)),
+    }
+}
+
+/// Sets the log sampling configuration, for all the loggers
+///
+/// # Arguments
+///
+/// * `config` - The configuration
+pub fn set_log_sampling(config: &LogSamplingConfig) {
+    set_log_packet_sample_rate(config.packet_sample_rate);
+    set_log_session_filter(config.session_filter.clone());
+}
+
+/// Sets the sample rate of the packet messages
+///
+/// # Arguments
+///
+/// * `rate` - Log only 1 of every N messages (1 to log all of them)
+pub fn set_log_packet_sample_rate(rate: u64) {
+    LOG_PACKET_SAMPLE_RATE.store(rate.max(1), Ordering::Relaxed);
+}
+
+/// Gets the sample rate of the packet messages
+pub fn get_log_packet_sample_rate() -> u64 {
+    LOG_PACKET_SAMPLE_RATE.load(Ordering::Relaxed)
+}
+
+/// Sets the filter of the sessions to log
+///
+/// # Arguments
+///
+/// * `filter` - The filter, or None to log all the sessions
+pub fn set_log_session_filter(filter: Option<SessionTraceFilter>) {
+    let is_set = filter.is_some();
+
+    if let Ok(mut f) = LOG_SESSION_FILTER.write() {
+        *f = filter;
+    }
+
+    LOG_SESSION_FILTER_SET.store(is_set, Ordering::Relaxed);
+}
+
+/// Session of a logger, to apply the session filter
+pub struct LogSessionScope {
+    /// Client IP address of the session
+    ip: IpAddr,
+
+    /// Channel of the session, if connected
+    channel: RwLock<Option<String>>,
+}
+
+impl LogSessionScope {
+    /// Creates new LogSessionScope
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - Client IP address of the session
+    pub fn new(ip: IpAddr) -> LogSessionScope {
+        LogSessionScope {
+            ip,
+            channel: RwLock::new(None),
+        }
+    }
+
+    /// Sets the channel of the session
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    pub fn set_channel(&self, channel: &str) {
+        if let Ok(mut c) = self.channel.write() {
+            *c = Some(channel.to_string());
+        }
+    }
+
+    /// Checks if the session passes the session filter
+    pub fn is_selected(&self) -> bool {
+        if !LOG_SESSION_FILTER_SET.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        let filter = match LOG_SESSION_FILTER.read() {
+            Ok(f) => f,
+            Err(_) => {
+                return true;
+            }
+        };
+
+        match filter.as_ref() {
+            Some(f) => {
+                let channel = self.channel.read().ok().and_then(|c| c.clone());
+                f.matches(&self.ip, channel.as_deref())
+            }
+            None => true,
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_sampling() {
+        assert_eq!(parse_log_packet_sample_rate(" 10"), Ok(10));
+        assert!(parse_log_packet_sample_rate("0").is_err());
+        assert!(parse_log_packet_sample_rate("many").is_err());
+
+        assert!(parse_log_session_filter("").unwrap().is_none());
+        assert!(
+            parse_log_session_filter("channel:live").unwrap()
+                == Some(SessionTraceFilter::Channel("live".to_string()))
+        );
+        assert!(
+            parse_log_session_filter("ip:10.0.0.1").unwrap()
+                == Some(SessionTraceFilter::Ip("10.0.0.1".parse().unwrap()))
+        );
+        assert!(parse_log_session_filter("live").is_err());
+        assert!(parse_log_session_filter("ip:invalid").is_err());
+    }
+}
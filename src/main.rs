@@ -1,57 +1,147 @@
 // Main
 
+mod acme;
 mod amf;
+mod auth;
 mod callback;
+mod chaos;
+mod cli;
+mod cluster;
+mod config_check;
 mod control;
+mod geoip;
 mod log;
+#[cfg(feature = "redis")]
+mod ops;
+mod record;
 mod redis;
 mod rtmp;
 mod server;
 mod session;
+mod slate;
+mod trace;
 mod utils;
 
 use std::sync::Arc;
 
+use auth::{install_jwt_crypto_provider, StreamKeyStore};
+use cli::{CliAction, CLI_USAGE};
+use cluster::{
+    spawn_task_cluster_registry_refresh, ClusterConfiguration, ClusterNode, ClusterRegistry,
+};
+use config_check::check_configuration;
+#[cfg(feature = "control")]
 use control::{
     spawn_task_control_client, spawn_task_handle_control_key_validations, ControlClientStatus,
-    ControlKeyValidationRequest, ControlServerConnectionConfig, KEY_VALIDATION_CHANNEL_BUFFER_SIZE,
+    KEY_VALIDATION_CHANNEL_BUFFER_SIZE,
+};
+use control::{ControlKeyValidationRequest, ControlServerConnectionConfig};
+use geoip::GeoIpDatabase;
+use log::{
+    install_panic_hook, set_log_sampling, spawn_task_reload_log_levels_on_signal, LogConfig,
+    LogSamplingConfig, Logger,
+};
+#[cfg(feature = "redis")]
+use ops::{run_kill_stream, run_list_streams};
+#[cfg(feature = "redis")]
+use redis::spawn_task_redis_client;
+use redis::RedisConfiguration;
+use server::{
+    bind_server_listeners, check_open_files_limit, load_server_state, run_server,
+    spawn_task_persist_server_state, spawn_task_report_orphaned_publishes, wait_for_binary_upgrade,
+    ActiveConnections, InheritedListeners, RtmpServerConfiguration, RtmpServerContext,
+    RtmpServerStatus, UpgradeHandoff, UPGRADE_LISTEN_FDS_ENV,
 };
-use log::{LogConfig, Logger};
-use redis::{spawn_task_redis_client, RedisConfiguration};
-use server::{run_server, RtmpServerConfiguration, RtmpServerContext, RtmpServerStatus};
-use tokio::sync::{mpsc::Sender, Mutex};
+use session::GopReplayLimiter;
+use slate::{spawn_task_play_slate, SlateSource};
+use tokio::sync::mpsc::Sender;
+#[cfg(feature = "control")]
+use tokio::sync::Mutex;
+use trace::run_replay_trace;
 use utils::get_env_bool;
 
 /// Main function
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+    // Parse command line arguments
+
+    let cli_action = match CliAction::parse(std::env::args().skip(1)) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, CLI_USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match cli_action {
+        CliAction::Help => {
+            println!("{}", CLI_USAGE);
+            return Ok(());
+        }
+        CliAction::Version => {
+            println!("{}", VERSION);
+            return Ok(());
+        }
+        _ => {}
+    }
+
     // Load .env
     let _ = dotenvy::dotenv();
 
+    // Install the cryptography provider for tokens
+    install_jwt_crypto_provider();
+
     // Initialize logger
 
-    let logger = Logger::new(LogConfig {
-        prefix: "".to_string(),
-        error_enabled: get_env_bool("LOG_ERROR", true),
-        warning_enabled: get_env_bool("LOG_WARNING", true),
-        info_enabled: get_env_bool("LOG_INFO", true),
-        debug_enabled: get_env_bool("LOG_DEBUG", false),
-        trace_enabled: get_env_bool("LOG_TRACE", get_env_bool("LOG_DEBUG", false)),
-    });
+    let (log_config, log_config_error) = LogConfig::load_from_env();
 
-    // Initialize server status
+    let logger = Logger::new(log_config);
 
-    let server_status = Arc::new(Mutex::new(RtmpServerStatus::new()));
+    if let Some(e) = log_config_error {
+        log_warning!(logger, e);
+    }
 
-    // Print version
+    install_panic_hook(logger.make_child_logger("[PANIC] "));
 
-    const VERSION: &str = env!("CARGO_PKG_VERSION");
+    match LogSamplingConfig::load_from_env() {
+        Ok(c) => set_log_sampling(&c),
+        Err(e) => {
+            log_warning!(logger, e);
+        }
+    }
+
+    // Print version
 
     log_info!(
         logger,
         format!("RTMP Server (Rust Implementation) ({VERSION})")
     );
 
+    // Run the requested command, instead of the server
+
+    let command_result = match &cli_action {
+        CliAction::CheckConfig => Some(check_configuration(&logger).await),
+        CliAction::ReplayTrace { path } => Some(run_replay_trace(&logger, path).await.is_ok()),
+        #[cfg(feature = "redis")]
+        CliAction::ListStreams => Some(run_list_streams(&logger).await.is_ok()),
+        #[cfg(feature = "redis")]
+        CliAction::KillStream { channel, confirm } => {
+            Some(run_kill_stream(&logger, channel, *confirm).await.is_ok())
+        }
+        #[cfg(not(feature = "redis"))]
+        CliAction::ListStreams | CliAction::KillStream { .. } => {
+            log_error!(logger, "The server was built without the redis feature");
+            Some(false)
+        }
+        _ => None,
+    };
+
+    if let Some(success) = command_result {
+        std::process::exit(if success { 0 } else { 1 });
+    }
+
     // Load configuration
 
     let server_config = match RtmpServerConfiguration::load_from_env(&logger) {
@@ -61,24 +151,182 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
     };
 
-    // Load and run control client
+    if check_open_files_limit(&logger, &server_config).is_err() {
+        std::process::exit(1);
+    }
 
-    let control_client_enabled = get_env_bool("CONTROL_USE", false);
-    let control_key_validator_sender: Option<Sender<ControlKeyValidationRequest>>;
+    // Load the configuration of the optional features,
+    // so the server does not start anything with an invalid configuration
+
+    let cluster_config = if get_env_bool("CLUSTER_USE", false) {
+        match ClusterConfiguration::load_from_env(&logger) {
+            Ok(c) => Some(c),
+            Err(_) => {
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let control_config = if get_env_bool("CONTROL_USE", false) {
+        match ControlServerConnectionConfig::load_from_env(&logger) {
+            Ok(c) => Some(Arc::new(c)),
+            Err(_) => {
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
 
-    if control_client_enabled {
-        // Load config
+    let use_redis = get_env_bool("REDIS_USE", false);
 
-        let control_config = match ControlServerConnectionConfig::load_from_env(&logger) {
-            Ok(c) => Arc::new(c),
+    // The Redis server is also used for the publish registry of the cluster
+
+    let redis_config = if use_redis || cluster_config.is_some() {
+        match RedisConfiguration::load_from_env(&logger) {
+            Ok(c) => Some(c),
             Err(_) => {
                 std::process::exit(1);
             }
+        }
+    } else {
+        None
+    };
+
+    // Attribute the log messages to the server node
+
+    let logger = logger.make_child_logger(&server_config.identity.log_prefix());
+
+    // Reload the log levels on SIGHUP
+
+    spawn_task_reload_log_levels_on_signal(logger.make_child_logger("[LOG] "));
+
+    // Bind the listeners, reusing the ones inherited from the parent process
+
+    let listeners = match bind_server_listeners(
+        &logger,
+        &server_config,
+        InheritedListeners::load_from_env(&logger),
+    )
+    .await
+    {
+        Ok(l) => l,
+        Err(_) => {
+            std::process::exit(1);
+        }
+    };
+
+    // Initialize server status
+
+    let server_status = Arc::new(RtmpServerStatus::new(
+        server_config.channel_event_history_size,
+        GopReplayLimiter::new(
+            server_config.gop_replay_max_concurrent as usize,
+            server_config.gop_replay_queue_timeout_ms as u64,
+        ),
+        StreamKeyStore::new(server_config.key_store.clone()),
+    ));
+
+    // Load the keys of the built-in key store
+
+    match server_status.key_store.load().await {
+        Ok(0) => {}
+        Ok(count) => {
+            log_info!(logger, format!("Loaded {} keys into the key store", count));
+        }
+        Err(e) => {
+            log_error!(logger, e);
+            std::process::exit(1);
+        }
+    }
+
+    // Load the GeoIP databases
+
+    let geoip = match GeoIpDatabase::load(&server_config.geoip) {
+        Ok(g) => {
+            if g.is_enabled() {
+                log_info!(logger, "Loaded the GeoIP databases");
+            }
+
+            Arc::new(g)
+        }
+        Err(e) => {
+            log_error!(logger, e);
+            std::process::exit(1);
+        }
+    };
+
+    // Load slate
+
+    let slate = if server_config.slate_file.is_empty() {
+        None
+    } else {
+        match SlateSource::load_from_file(&server_config.slate_file).await {
+            Ok(s) => {
+                let slate = Arc::new(s);
+
+                log_info!(
+                    logger,
+                    format!(
+                        "Loaded slate from {} ({} ms)",
+                        &server_config.slate_file, slate.duration
+                    )
+                );
+
+                spawn_task_play_slate(slate.clone());
+
+                Some(slate)
+            }
+            Err(e) => {
+                log_error!(logger, format!("Could not load slate: {}", e));
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // Clustering mode
+
+    let cluster = if let (Some(cluster_config), Some(redis_config)) =
+        (cluster_config, &redis_config)
+    {
+        // The publish registry is stored in the Redis server
+
+        let registry = match ClusterRegistry::new(&redis_config.get_redis_url(), &cluster_config) {
+            Ok(r) => r,
+            Err(e) => {
+                log_error!(logger, format!("Could not create cluster registry: {}", e));
+                std::process::exit(1);
+            }
         };
 
+        log_info!(
+            logger,
+            format!(
+                "Clustering mode enabled. Node address: {}",
+                &cluster_config.node_address
+            )
+        );
+
+        Some(Arc::new(ClusterNode::new(cluster_config, registry)))
+    } else {
+        None
+    };
+
+    // Load and run control client
+
+    let control_key_validator_sender: Option<Sender<ControlKeyValidationRequest>>;
+    #[cfg(feature = "control")]
+    let control_client_status: Option<Arc<Mutex<ControlClientStatus>>>;
+
+    #[cfg(feature = "control")]
+    if let Some(control_config) = control_config {
         // Initialize status
 
-        let control_client_status = Arc::new(Mutex::new(ControlClientStatus::new()));
+        let client_status = Arc::new(Mutex::new(ControlClientStatus::new()));
+
+        control_client_status = Some(client_status.clone());
 
         // Create key validation channel
 
@@ -93,11 +341,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         spawn_task_control_client(
             Arc::new(logger.make_child_logger("[CONTROL/CLIENT] ")),
             control_config.clone(),
-            control_client_status.clone(),
+            client_status.clone(),
             RtmpServerContext {
                 config: server_config.clone(),
                 status: server_status.clone(),
                 control_key_validator_sender: control_key_validator_sender.clone(),
+                slate: slate.clone(),
+                cluster: cluster.clone(),
             },
         );
 
@@ -105,27 +355,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
         spawn_task_handle_control_key_validations(
             Arc::new(logger.make_child_logger("[CONTROL/KEY_VALIDATION] ")),
-            control_client_status,
+            control_config,
+            client_status,
+            server_config.identity.clone(),
             kv_receiver,
         );
     } else {
         control_key_validator_sender = None;
+        control_client_status = None;
     }
 
-    // Redis feature
-
-    let use_redis = get_env_bool("REDIS_USE", false);
+    // Without the control feature, the configuration is never loaded
 
-    if use_redis {
-        // Load config
+    #[cfg(not(feature = "control"))]
+    {
+        drop(control_config);
+        control_key_validator_sender = None;
+    }
 
-        let redis_config = match RedisConfiguration::load_from_env(&logger) {
-            Ok(c) => c,
-            Err(_) => {
-                std::process::exit(1);
-            }
-        };
+    // Redis feature
 
+    #[cfg(feature = "redis")]
+    if let (true, Some(redis_config)) = (use_redis, redis_config) {
         // Spawn task
 
         spawn_task_redis_client(
@@ -135,6 +386,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 config: server_config.clone(),
                 status: server_status.clone(),
                 control_key_validator_sender: control_key_validator_sender.clone(),
+                slate: slate.clone(),
+                cluster: cluster.clone(),
             },
         );
     }
@@ -145,11 +398,109 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         config: server_config.clone(),
         status: server_status.clone(),
         control_key_validator_sender,
+        slate,
+        cluster,
     };
 
-    run_server(logger, server_context).await;
+    // Keep the entries of the published channels in the cluster registry
+
+    if let Some(cluster) = &server_context.cluster {
+        spawn_task_cluster_registry_refresh(
+            logger.make_child_logger("[CLUSTER] "),
+            server_context.clone(),
+            cluster.clone(),
+        );
+    }
+
+    let events_logger = logger.make_child_logger("");
+    let upgrade_logger = logger.make_child_logger("[UPGRADE] ");
+
+    let upgrade_handoff = UpgradeHandoff::new(&listeners)?;
+
+    let connections = Arc::new(ActiveConnections::new());
+    let (stop_sender, stop_receiver) = tokio::sync::watch::channel(false);
+
+    // Persist the state, reporting the publications orphaned by the previous instance
+
+    if server_config.state_persistence.is_enabled() {
+        let state_logger = logger.make_child_logger("[STATE] ");
+
+        // After a binary upgrade, the previous process is still running its publications
+
+        if std::env::var(UPGRADE_LISTEN_FDS_ENV).is_err() {
+            match load_server_state(&server_config.state_persistence.file).await {
+                Ok(Some(state)) => {
+                    spawn_task_report_orphaned_publishes(
+                        logger.make_child_logger("[STATE] "),
+                        server_context.clone(),
+                        #[cfg(feature = "control")]
+                        control_client_status,
+                        state,
+                    );
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log_warning!(state_logger, e);
+                }
+            }
+        }
+
+        spawn_task_persist_server_state(
+            state_logger,
+            server_context.clone(),
+            stop_receiver.clone(),
+        );
+    }
+
+    tokio::select! {
+        _ = run_server(logger, server_context, listeners, connections.clone(), geoip, stop_receiver) => {}
+        _ = wait_for_shutdown_signal() => {
+            log_info!(events_logger, "Shutting down...");
+
+            // Dump the event history, for debugging
+            server_status.events.dump(&events_logger).await;
+        }
+        _ = wait_for_binary_upgrade(&upgrade_logger, &upgrade_handoff) => {
+            // The new process accepts the connections from now on
+            _ = stop_sender.send(true);
+
+            if !connections.wait_drained(server_config.upgrade_drain_seconds).await {
+                log_info!(
+                    upgrade_logger,
+                    format!("Drain timeout reached with {} connections left", connections.count())
+                );
+            }
+
+            log_info!(upgrade_logger, "Upgrade completed. Exiting...");
+        }
+    }
 
     // End of main
 
     Ok(())
 }
+
+/// Waits for a signal to shut down the server (SIGINT or SIGTERM)
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(s) => s,
+                Err(_) => {
+                    _ = tokio::signal::ctrl_c().await;
+                    return;
+                }
+            };
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        _ = tokio::signal::ctrl_c().await;
+    }
+}
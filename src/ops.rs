@@ -0,0 +1,115 @@
+// Operational commands of the command line, talking to the servers through Redis
+
+use std::time::Duration;
+
+use redis::AsyncCommands;
+
+use crate::{
+    cluster::{list_cluster_channels, ClusterConfiguration},
+    log::Logger,
+    log_error,
+    redis::RedisConfiguration,
+};
+
+/// Max time to wait for a Redis operation
+const OPS_REDIS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lists the streams published in the cluster,
+/// reading the publish registry stored in Redis
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+pub async fn run_list_streams(logger: &Logger) -> Result<(), ()> {
+    let redis_config = RedisConfiguration::load_from_env(logger)?;
+    let key_prefix = ClusterConfiguration::load_key_prefix_from_env();
+
+    let channels = match list_cluster_channels(&redis_config.get_redis_url(), &key_prefix).await {
+        Ok(c) => c,
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not read the publish registry: {}", e)
+            );
+            return Err(());
+        }
+    };
+
+    if channels.is_empty() {
+        println!("No streams published");
+        return Ok(());
+    }
+
+    let channel_width = channels.iter().map(|(c, _)| c.len()).max().unwrap_or(0);
+
+    println!("{:channel_width$}  NODE", "CHANNEL");
+
+    for (channel, node_address) in channels {
+        println!("{:channel_width$}  {}", channel, node_address);
+    }
+
+    Ok(())
+}
+
+/// Kills the stream published on a channel (or a group of channels),
+/// sending the kill-session command to the servers through Redis
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `channel` - The channel ID, or the selector of the group of channels
+/// * `confirm` - True to confirm the kill of a group of channels
+pub async fn run_kill_stream(logger: &Logger, channel: &str, confirm: bool) -> Result<(), ()> {
+    let redis_config = RedisConfiguration::load_from_env(logger)?;
+
+    let client = match redis::Client::open(redis_config.get_redis_url()) {
+        Ok(c) => c,
+        Err(e) => {
+            log_error!(logger, format!("Could not create a Redis client: {}", e));
+            return Err(());
+        }
+    };
+
+    let command = if confirm {
+        format!("kill-session>{}|confirm", channel)
+    } else {
+        format!("kill-session>{}", channel)
+    };
+
+    let res = tokio::time::timeout(OPS_REDIS_TIMEOUT, async {
+        let mut connection = client.get_multiplexed_async_connection().await?;
+
+        connection
+            .publish::<_, _, usize>(&redis_config.channel, command)
+            .await
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => {
+            log_error!(
+                logger,
+                format!(
+                    "No server is subscribed to the Redis channel {}",
+                    &redis_config.channel
+                )
+            );
+            Err(())
+        }
+        Ok(Ok(receivers)) => {
+            println!(
+                "Sent the command to kill the stream of channel {} ({} servers received it)",
+                channel, receivers
+            );
+            Ok(())
+        }
+        Ok(Err(e)) => {
+            log_error!(logger, format!("Could not send the command: {}", e));
+            Err(())
+        }
+        Err(_) => {
+            log_error!(logger, "Could not send the command: Timed out");
+            Err(())
+        }
+    }
+}
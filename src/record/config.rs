@@ -0,0 +1,202 @@
+// Recording configuration
+
+use crate::{
+    log::Logger,
+    log_error,
+    utils::{get_env_string, get_env_u32},
+};
+
+/// Policy for the publishers requesting to record the stream
+/// (`record` or `append` type in the publish command)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PublishTypeRecordingPolicy {
+    /// Ignore the request, only reporting it in the callbacks
+    Ignore,
+
+    /// Record the stream
+    Record,
+
+    /// Reject the publisher
+    Reject,
+}
+
+impl PublishTypeRecordingPolicy {
+    /// Parses the policy
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string (`ignore`, `record` or `reject`)
+    pub fn parse(s: &str) -> Result<PublishTypeRecordingPolicy, String> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "ignore" => Ok(PublishTypeRecordingPolicy::Ignore),
+            "record" => Ok(PublishTypeRecordingPolicy::Record),
+            "reject" => Ok(PublishTypeRecordingPolicy::Reject),
+            _ => Err(format!("Invalid publish type recording policy: {}", s)),
+        }
+    }
+}
+
+/// Recording configuration
+#[derive(Clone)]
+pub struct RecordingConfiguration {
+    /// Folder to store the recordings
+    pub path: String,
+
+    /// Policy for the publishers requesting to record the stream
+    pub publish_type_policy: PublishTypeRecordingPolicy,
+}
+
+impl RecordingConfiguration {
+    /// Loads recording configuration
+    /// from environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<RecordingConfiguration, ()> {
+        let path = get_env_string("RECORD_PATH", "recordings");
+
+        let publish_type_policy =
+            match PublishTypeRecordingPolicy::parse(&get_env_string("RECORD_PUBLISH_TYPE", "")) {
+                Ok(p) => p,
+                Err(e) => {
+                    log_error!(logger, format!("RECORD_PUBLISH_TYPE: {}", e));
+                    return Err(());
+                }
+            };
+
+        Ok(RecordingConfiguration {
+            path,
+            publish_type_policy,
+        })
+    }
+
+    /// Gets the path of a new recording file
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    /// * `stream_id` - The stream ID
+    /// * `timestamp` - Start timestamp of the recording (Unix milliseconds)
+    pub fn get_recording_path(&self, channel: &str, stream_id: &str, timestamp: i64) -> String {
+        format!(
+            "{}/{}/{}-{}.flv",
+            self.path.trim_end_matches('/'),
+            sanitize_file_name(channel),
+            sanitize_file_name(stream_id),
+            timestamp
+        )
+    }
+}
+
+/// Snapshots configuration
+#[derive(Clone)]
+pub struct SnapshotConfiguration {
+    /// Min interval between snapshots of a channel (seconds). 0 to disable snapshots.
+    pub interval_seconds: u32,
+
+    /// Folder to store the snapshots (empty to not store them)
+    pub path: String,
+
+    /// URL to send the snapshots to (empty to not send them)
+    pub callback_url: String,
+}
+
+impl SnapshotConfiguration {
+    /// Loads snapshots configuration
+    /// from environment variables
+    pub fn load_from_env() -> SnapshotConfiguration {
+        let interval_seconds = get_env_u32("SNAPSHOT_INTERVAL_SECONDS", 0);
+        let path = get_env_string("SNAPSHOT_PATH", "");
+        let callback_url = get_env_string("SNAPSHOT_CALLBACK_URL", "");
+
+        SnapshotConfiguration {
+            interval_seconds,
+            path,
+            callback_url,
+        }
+    }
+
+    /// Checks if snapshots are enabled
+    pub fn is_enabled(&self) -> bool {
+        self.interval_seconds > 0 && (!self.path.is_empty() || !self.callback_url.is_empty())
+    }
+
+    /// Gets the path of the snapshot file of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    pub fn get_snapshot_path(&self, channel: &str) -> String {
+        format!(
+            "{}/{}.flv",
+            self.path.trim_end_matches('/'),
+            sanitize_file_name(channel)
+        )
+    }
+}
+
+/// Replaces the characters not allowed in file names
+///
+/// # Arguments
+///
+/// * `name` - The name to sanitize
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if sanitized.is_empty() {
+        "_".to_string()
+    } else {
+        sanitized
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_recording_path() {
+        let config = RecordingConfiguration {
+            path: "/var/recordings/".to_string(),
+            publish_type_policy: PublishTypeRecordingPolicy::Ignore,
+        };
+
+        assert_eq!(
+            config.get_recording_path("acme/live", "../stream", 1000),
+            "/var/recordings/acme_live/___stream-1000.flv"
+        );
+        assert_eq!(
+            config.get_recording_path("live", "", 5),
+            "/var/recordings/live/_-5.flv"
+        );
+    }
+
+    #[test]
+    fn test_publish_type_recording_policy_parse() {
+        assert_eq!(
+            PublishTypeRecordingPolicy::parse(""),
+            Ok(PublishTypeRecordingPolicy::Ignore)
+        );
+        assert_eq!(
+            PublishTypeRecordingPolicy::parse("Record"),
+            Ok(PublishTypeRecordingPolicy::Record)
+        );
+        assert_eq!(
+            PublishTypeRecordingPolicy::parse("reject"),
+            Ok(PublishTypeRecordingPolicy::Reject)
+        );
+        assert!(PublishTypeRecordingPolicy::parse("append").is_err());
+    }
+}
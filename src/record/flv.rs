@@ -0,0 +1,173 @@
+// FLV format encoding
+
+/// FLV tag type: Audio
+pub const FLV_TAG_AUDIO: u8 = 8;
+
+/// FLV tag type: Video
+pub const FLV_TAG_VIDEO: u8 = 9;
+
+/// FLV tag type: Script data (metadata)
+pub const FLV_TAG_SCRIPT_DATA: u8 = 18;
+
+/// Size of the header of FLV tags
+const FLV_TAG_HEADER_SIZE: usize = 11;
+
+/// Tag of a FLV file
+pub struct FlvTag {
+    /// Tag type
+    pub tag_type: u8,
+
+    /// Timestamp (milliseconds)
+    pub timestamp: i64,
+
+    /// Tag data
+    pub payload: Vec<u8>,
+}
+
+/// Makes the header of a FLV file, including the first previous tag size
+pub fn flv_make_header() -> Vec<u8> {
+    vec![
+        b'F', b'L', b'V', // Signature
+        0x01, // Version
+        0x05, // Flags (audio and video)
+        0x00, 0x00, 0x00, 0x09, // Header size
+        0x00, 0x00, 0x00, 0x00, // Previous tag size
+    ]
+}
+
+/// Makes a FLV tag, including the previous tag size after it
+///
+/// # Arguments
+///
+/// * `tag_type` - The tag type
+/// * `timestamp` - The timestamp (milliseconds)
+/// * `payload` - The tag data
+pub fn flv_make_tag(tag_type: u8, timestamp: i64, payload: &[u8]) -> Vec<u8> {
+    let mut tag = Vec::with_capacity(FLV_TAG_HEADER_SIZE + payload.len() + 4);
+
+    let data_size = (payload.len() as u32).to_be_bytes();
+    let timestamp = (timestamp.max(0) as u32).to_be_bytes();
+
+    tag.push(tag_type);
+    tag.extend_from_slice(&data_size[1..4]);
+    tag.extend_from_slice(&timestamp[1..4]);
+    tag.push(timestamp[0]); // Timestamp extended
+    tag.extend_from_slice(&[0x00, 0x00, 0x00]); // Stream ID
+    tag.extend_from_slice(payload);
+
+    let tag_size = (FLV_TAG_HEADER_SIZE + payload.len()) as u32;
+
+    tag.extend_from_slice(&tag_size.to_be_bytes());
+
+    tag
+}
+
+/// Makes a FLV file with a single video frame
+///
+/// # Arguments
+///
+/// * `sequence_header` - The video sequence header (decoder configuration)
+/// * `keyframe` - The video keyframe
+pub fn flv_make_snapshot(sequence_header: &[u8], keyframe: &[u8]) -> Vec<u8> {
+    let mut snapshot = flv_make_header();
+
+    // Only video
+    snapshot[4] = 0x01;
+
+    snapshot.extend(flv_make_tag(FLV_TAG_VIDEO, 0, sequence_header));
+    snapshot.extend(flv_make_tag(FLV_TAG_VIDEO, 0, keyframe));
+
+    snapshot
+}
+
+/// Reads the tags of a FLV file
+///
+/// # Arguments
+///
+/// * `data` - The contents of the FLV file
+///
+/// # Return value
+///
+/// Returns the list of tags, or an error message if the file is not valid
+pub fn flv_read_tags(data: &[u8]) -> Result<Vec<FlvTag>, String> {
+    if data.len() < 9 || &data[0..3] != b"FLV" {
+        return Err("Invalid FLV signature".to_string());
+    }
+
+    let header_size = u32::from_be_bytes([data[5], data[6], data[7], data[8]]) as usize;
+
+    let mut tags = Vec::new();
+
+    // Skip the header and the first previous tag size
+    let mut offset = header_size.saturating_add(4);
+
+    while offset < data.len() {
+        let header = match data.get(offset..offset + FLV_TAG_HEADER_SIZE) {
+            Some(h) => h,
+            None => {
+                return Err(format!("Truncated FLV tag header at offset {}", offset));
+            }
+        };
+
+        let data_size = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let timestamp = u32::from_be_bytes([header[7], header[4], header[5], header[6]]) as i64;
+
+        let payload_start = offset + FLV_TAG_HEADER_SIZE;
+
+        let payload = match data.get(payload_start..payload_start + data_size) {
+            Some(p) => p,
+            None => {
+                return Err(format!("Truncated FLV tag at offset {}", offset));
+            }
+        };
+
+        tags.push(FlvTag {
+            tag_type: header[0] & 0x1f,
+            timestamp,
+            payload: payload.to_vec(),
+        });
+
+        offset = payload_start + data_size + 4;
+    }
+
+    Ok(tags)
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flv_make_tag() {
+        assert_eq!(flv_make_header().len(), 13);
+
+        let tag = flv_make_tag(FLV_TAG_VIDEO, 0x01020304, &[0xaa, 0xbb]);
+
+        assert_eq!(
+            tag,
+            vec![
+                0x09, 0x00, 0x00, 0x02, 0x02, 0x03, 0x04, 0x01, 0x00, 0x00, 0x00, 0xaa, 0xbb, 0x00,
+                0x00, 0x00, 0x0d
+            ]
+        );
+
+        let mut file = flv_make_header();
+
+        file.extend(tag);
+        file.extend(flv_make_tag(FLV_TAG_AUDIO, 5, &[0xcc]));
+
+        let tags = flv_read_tags(&file).unwrap();
+
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].tag_type, FLV_TAG_VIDEO);
+        assert_eq!(tags[0].timestamp, 0x01020304);
+        assert_eq!(tags[0].payload, vec![0xaa, 0xbb]);
+        assert_eq!(tags[1].tag_type, FLV_TAG_AUDIO);
+        assert_eq!(tags[1].timestamp, 5);
+
+        assert!(flv_read_tags(&file[..file.len() - 6]).is_err());
+        assert!(flv_read_tags(b"NOT A FLV").is_err());
+    }
+}
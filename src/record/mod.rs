@@ -0,0 +1,11 @@
+// Recording of live streams to FLV files
+
+mod config;
+mod flv;
+mod recorder;
+mod snapshot;
+
+pub use config::*;
+pub use flv::*;
+pub use recorder::*;
+pub use snapshot::*;
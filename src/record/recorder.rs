@@ -0,0 +1,374 @@
+// Recording of channels to FLV files
+
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    sync::{broadcast::error::RecvError, mpsc::Receiver},
+};
+
+use crate::{
+    log::Logger,
+    log_debug, log_error, log_info,
+    rtmp::{
+        RtmpPacket, RTMP_CHANNEL_INJECTED_DATA, RTMP_TYPE_AUDIO, RTMP_TYPE_DATA, RTMP_TYPE_VIDEO,
+    },
+    server::{RtmpChannelRecording, RtmpServerContext},
+    session::RtmpPacketReceiver,
+};
+
+use super::{flv_make_header, flv_make_tag, FLV_TAG_AUDIO, FLV_TAG_SCRIPT_DATA, FLV_TAG_VIDEO};
+
+/// Info of a recording
+#[cfg_attr(not(feature = "control"), allow(dead_code))]
+pub struct RecordingInfo {
+    /// ID of the recorded stream
+    pub stream_id: String,
+
+    /// Path of the recording file
+    pub path: String,
+}
+
+/// Initial data of the stream, written before the live packets
+struct RecordingStartData {
+    /// Metadata
+    metadata: Arc<Vec<u8>>,
+
+    /// AAC sequence header
+    aac_sequence_header: Arc<Vec<u8>>,
+
+    /// AVC sequence header
+    avc_sequence_header: Arc<Vec<u8>>,
+
+    /// Copy of the GOP cache
+    gop_cache: Vec<Arc<RtmpPacket>>,
+}
+
+/// Gets the current Unix timestamp (milliseconds)
+fn get_unix_timestamp_ms() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(_) => 0,
+    }
+}
+
+/// Starts recording a channel
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+///
+/// # Return value
+///
+/// Returns the info of the recording, or an error message
+pub async fn start_recording(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+) -> Result<RecordingInfo, String> {
+    // Find the stream
+
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return Err("Channel not found".to_string());
+        }
+    };
+
+    drop(status);
+
+    let channel_status = channel_mu.lock().await;
+
+    if !channel_status.publishing {
+        return Err("Channel is not publishing".to_string());
+    }
+
+    if let Some(r) = &channel_status.recording {
+        return Err(format!("Channel is already being recorded to {}", r.path));
+    }
+
+    let stream_id = channel_status.stream_id.clone().unwrap_or_default();
+
+    drop(channel_status);
+
+    // Create the file
+
+    let path = server_context.config.recording.get_recording_path(
+        channel,
+        &stream_id,
+        get_unix_timestamp_ms(),
+    );
+
+    if let Some(parent) = Path::new(&path).parent() {
+        if let Err(e) = tokio::fs::create_dir_all(parent).await {
+            return Err(format!("Could not create recording folder: {}", e));
+        }
+    }
+
+    let file = match File::create(&path).await {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(format!("Could not create recording file: {}", e));
+        }
+    };
+
+    // The stream may have changed while creating the file
+
+    let mut channel_status = channel_mu.lock().await;
+
+    if !channel_status.publishing || channel_status.stream_id.as_deref() != Some(&stream_id) {
+        drop(channel_status);
+        _ = tokio::fs::remove_file(&path).await;
+        return Err("Channel is not publishing".to_string());
+    }
+
+    if let Some(r) = &channel_status.recording {
+        let err = format!("Channel is already being recorded to {}", r.path);
+        drop(channel_status);
+        _ = tokio::fs::remove_file(&path).await;
+        return Err(err);
+    }
+
+    let start_data = match &channel_status.publish_status {
+        Some(s) => {
+            let publish_status = s.lock().await;
+
+            RecordingStartData {
+                metadata: publish_status.metadata.clone(),
+                aac_sequence_header: publish_status.aac_sequence_header.clone(),
+                avc_sequence_header: publish_status.avc_sequence_header.clone(),
+                gop_cache: publish_status.gop_cache.iter().cloned().collect(),
+            }
+        }
+        None => RecordingStartData {
+            metadata: Arc::new(Vec::new()),
+            aac_sequence_header: Arc::new(Vec::new()),
+            avc_sequence_header: Arc::new(Vec::new()),
+            gop_cache: Vec::new(),
+        },
+    };
+
+    let packet_receiver = channel_status.subscribe_packets();
+
+    let (stop_sender, stop_receiver) = tokio::sync::mpsc::channel::<()>(1);
+
+    channel_status.recording = Some(RtmpChannelRecording {
+        path: path.clone(),
+        stop_sender,
+    });
+
+    drop(channel_status);
+
+    log_info!(
+        logger,
+        format!("Channel {}: Recording started: {}", channel, path)
+    );
+
+    spawn_task_write_recording(
+        logger.make_child_logger(&format!("[RECORD:{}] ", channel)),
+        file,
+        path.clone(),
+        start_data,
+        packet_receiver,
+        stop_receiver,
+    );
+
+    Ok(RecordingInfo { stream_id, path })
+}
+
+/// Stops recording a channel
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+///
+/// # Return value
+///
+/// Returns the info of the stopped recording, or an error message
+#[cfg(any(feature = "control", feature = "redis"))]
+pub async fn stop_recording(
+    server_context: &RtmpServerContext,
+    channel: &str,
+) -> Result<RecordingInfo, String> {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return Err("Channel not found".to_string());
+        }
+    };
+
+    drop(status);
+
+    let mut channel_status = channel_mu.lock().await;
+
+    match channel_status.recording.take() {
+        Some(r) => {
+            _ = r.stop_sender.try_send(());
+
+            Ok(RecordingInfo {
+                stream_id: channel_status.stream_id.clone().unwrap_or_default(),
+                path: r.path,
+            })
+        }
+        None => Err("Channel is not being recorded".to_string()),
+    }
+}
+
+/// Spawns a task to write the packets of a channel to the recording file
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `file` - The recording file
+/// * `path` - Path of the recording file
+/// * `start_data` - Initial data of the stream
+/// * `packet_receiver` - Receiver for the packets of the channel
+/// * `stop_receiver` - Receiver to stop the recording (closed to stop)
+fn spawn_task_write_recording(
+    logger: Logger,
+    file: File,
+    path: String,
+    start_data: RecordingStartData,
+    packet_receiver: RtmpPacketReceiver,
+    stop_receiver: Receiver<()>,
+) {
+    tokio::spawn(async move {
+        let mut writer = BufWriter::new(file);
+
+        let res = write_recording(
+            &logger,
+            &mut writer,
+            start_data,
+            packet_receiver,
+            stop_receiver,
+        )
+        .await;
+
+        let res = match res {
+            Ok(_) => writer.flush().await,
+            Err(e) => Err(e),
+        };
+
+        match res {
+            Ok(_) => {
+                log_info!(logger, format!("Recording finished: {}", path));
+            }
+            Err(e) => {
+                log_error!(logger, format!("Could not write recording {}: {}", path, e));
+            }
+        }
+    });
+}
+
+/// Writes the packets of a channel to the recording file,
+/// until the recording is stopped
+async fn write_recording(
+    logger: &Logger,
+    writer: &mut BufWriter<File>,
+    start_data: RecordingStartData,
+    mut packet_receiver: RtmpPacketReceiver,
+    mut stop_receiver: Receiver<()>,
+) -> std::io::Result<()> {
+    writer.write_all(&flv_make_header()).await?;
+
+    if !start_data.metadata.is_empty() {
+        writer
+            .write_all(&flv_make_tag(FLV_TAG_SCRIPT_DATA, 0, &start_data.metadata))
+            .await?;
+    }
+
+    if !start_data.aac_sequence_header.is_empty() {
+        writer
+            .write_all(&flv_make_tag(
+                FLV_TAG_AUDIO,
+                0,
+                &start_data.aac_sequence_header,
+            ))
+            .await?;
+    }
+
+    if !start_data.avc_sequence_header.is_empty() {
+        writer
+            .write_all(&flv_make_tag(
+                FLV_TAG_VIDEO,
+                0,
+                &start_data.avc_sequence_header,
+            ))
+            .await?;
+    }
+
+    // The timestamps of the recording start at the first packet
+
+    let mut base_timestamp: Option<i64> = None;
+
+    for packet in start_data.gop_cache {
+        write_recording_packet(writer, &mut base_timestamp, &packet).await?;
+    }
+
+    loop {
+        tokio::select! {
+            _ = stop_receiver.recv() => {
+                return Ok(());
+            }
+            res = packet_receiver.recv() => {
+                match res {
+                    Ok(packet) => {
+                        write_recording_packet(writer, &mut base_timestamp, &packet).await?;
+                    }
+                    Err(RecvError::Lagged(lost)) => {
+                        log_debug!(logger, format!("Recording lost {} packets", lost));
+                    }
+                    Err(RecvError::Closed) => {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes a packet to the recording file
+///
+/// # Arguments
+///
+/// * `writer` - The recording file writer
+/// * `base_timestamp` - Timestamp of the first packet of the recording
+/// * `packet` - The packet
+async fn write_recording_packet(
+    writer: &mut BufWriter<File>,
+    base_timestamp: &mut Option<i64>,
+    packet: &RtmpPacket,
+) -> std::io::Result<()> {
+    let tag_type = match packet.header.packet_type {
+        RTMP_TYPE_AUDIO => FLV_TAG_AUDIO,
+        RTMP_TYPE_VIDEO => FLV_TAG_VIDEO,
+        RTMP_TYPE_DATA if packet.header.channel_id != RTMP_CHANNEL_INJECTED_DATA => {
+            FLV_TAG_SCRIPT_DATA
+        }
+        _ => {
+            return Ok(());
+        }
+    };
+
+    let base = *base_timestamp.get_or_insert(packet.header.timestamp);
+
+    writer
+        .write_all(&flv_make_tag(
+            tag_type,
+            packet.header.timestamp.wrapping_sub(base),
+            &packet.payload,
+        ))
+        .await
+}
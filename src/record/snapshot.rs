@@ -0,0 +1,41 @@
+// Snapshots of the latest keyframe of the channels
+
+use super::SnapshotConfiguration;
+
+/// Stores the snapshot of a channel, replacing the previous one
+///
+/// # Arguments
+///
+/// * `config` - The snapshots configuration
+/// * `channel` - The channel ID
+/// * `snapshot` - The snapshot (FLV)
+///
+/// # Return value
+///
+/// Returns the path of the snapshot file, or an error message
+pub async fn save_snapshot(
+    config: &SnapshotConfiguration,
+    channel: &str,
+    snapshot: &[u8],
+) -> Result<String, String> {
+    if let Err(e) = tokio::fs::create_dir_all(&config.path).await {
+        return Err(format!("Could not create snapshots folder: {}", e));
+    }
+
+    let path = config.get_snapshot_path(channel);
+
+    // Write to a temporary file first, so readers never see a partial snapshot
+
+    let tmp_path = format!("{}.tmp", path);
+
+    if let Err(e) = tokio::fs::write(&tmp_path, snapshot).await {
+        return Err(format!("Could not write snapshot file: {}", e));
+    }
+
+    if let Err(e) = tokio::fs::rename(&tmp_path, &path).await {
+        _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(format!("Could not write snapshot file: {}", e));
+    }
+
+    Ok(path)
+}
@@ -1,13 +1,20 @@
 // Redis client
 
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
-use redis::{PushKind, Value};
+use redis::{aio::MultiplexedConnection, AsyncCommands, PushKind, Value};
 
 use crate::{
     log::Logger,
-    log_debug, log_error, log_info, log_trace,
-    server::{kill_publisher, RtmpServerContext},
+    log_debug, log_error, log_info, log_trace, log_warning,
+    record::{start_recording, stop_recording},
+    server::{
+        clear_gop_cache, generate_stored_channel_key, get_channel_bandwidth, get_channel_players,
+        inject_cue_point, kick_player, kill_publisher, kill_publishers_matching,
+        parse_cue_point_parameters, revoke_stored_channel_key, rotate_channel_key,
+        RtmpServerContext, ServerIdentity,
+    },
+    trace::SessionTraceFilter,
 };
 
 use super::{RedisConfiguration, RedisRtmpCommand};
@@ -86,9 +93,68 @@ pub fn spawn_task_redis_client(
                                 let cmd = RedisRtmpCommand::parse(&msg_str);
 
                                 match cmd {
-                                    RedisRtmpCommand::KillSession { channel } => {
-                                        kill_publisher(&logger, &server_context, &channel, None)
-                                            .await;
+                                    RedisRtmpCommand::KillSession { channels } => {
+                                        match channels.single_channel() {
+                                            Some(channel) => {
+                                                kill_publisher(
+                                                    &logger,
+                                                    &server_context,
+                                                    channel,
+                                                    None,
+                                                    "kill-session from Redis",
+                                                )
+                                                .await;
+                                            }
+                                            None => {
+                                                let killed = kill_publishers_matching(
+                                                    &logger,
+                                                    &server_context,
+                                                    &channels,
+                                                    "kill-session from Redis",
+                                                )
+                                                .await;
+
+                                                log_info!(
+                                                    logger,
+                                                    format!(
+                                                        "Killed the publishers of {} channels matching {}",
+                                                        killed.len(),
+                                                        channels
+                                                    )
+                                                );
+
+                                                send_response(
+                                                    &logger,
+                                                    &config,
+                                                    &server_context.config.identity,
+                                                    &mut connection,
+                                                    format!(
+                                                        "sessions-killed>{}|{}",
+                                                        channels,
+                                                        killed.len()
+                                                    ),
+                                                )
+                                                .await;
+                                            }
+                                        }
+                                    }
+                                    RedisRtmpCommand::KillSessionRejected { selector, error } => {
+                                        log_warning!(
+                                            logger,
+                                            format!(
+                                                "Rejected kill-session for {}: {}",
+                                                selector, error
+                                            )
+                                        );
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            format!("kill-session-error>{}|{}", selector, error),
+                                        )
+                                        .await;
                                     }
                                     RedisRtmpCommand::CloseStream { channel, stream_id } => {
                                         kill_publisher(
@@ -96,6 +162,338 @@ pub fn spawn_task_redis_client(
                                             &server_context,
                                             &channel,
                                             Some(&stream_id),
+                                            "close-stream from Redis",
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::KickPlayer { channel, player } => {
+                                        // The player is identified by session ID or IP address
+                                        let (session_id, ip) = match player.parse::<u64>() {
+                                            Ok(id) => (Some(id), None),
+                                            Err(_) => (None, player.parse::<IpAddr>().ok()),
+                                        };
+
+                                        kick_player(
+                                            &logger,
+                                            &server_context,
+                                            &channel,
+                                            session_id,
+                                            ip,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::RecordStart { channel } => {
+                                        let response = match start_recording(
+                                            &logger,
+                                            &server_context,
+                                            &channel,
+                                        )
+                                        .await
+                                        {
+                                            Ok(info) => {
+                                                format!("record-started>{}|{}", channel, info.path)
+                                            }
+                                            Err(e) => format!("record-error>{}|{}", channel, e),
+                                        };
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::RecordStop { channel } => {
+                                        let response =
+                                            match stop_recording(&server_context, &channel).await {
+                                                Ok(info) => format!(
+                                                    "record-stopped>{}|{}",
+                                                    channel, info.path
+                                                ),
+                                                Err(e) => {
+                                                    format!("record-error>{}|{}", channel, e)
+                                                }
+                                            };
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::TraceStart { kind, value } => {
+                                        let response = handle_trace_command(
+                                            &logger,
+                                            &server_context,
+                                            true,
+                                            &kind,
+                                            &value,
+                                        )
+                                        .await;
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::TraceStop { kind, value } => {
+                                        let response = handle_trace_command(
+                                            &logger,
+                                            &server_context,
+                                            false,
+                                            &kind,
+                                            &value,
+                                        )
+                                        .await;
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::ChannelEvents { channel } => {
+                                        let events =
+                                            server_context.status.events.get_events(&channel).await;
+
+                                        let response = format!(
+                                            "channel-events-list>{}|{}",
+                                            channel,
+                                            serde_json::to_string(&events).unwrap_or_default()
+                                        );
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::ChannelPlayers { channel } => {
+                                        let players =
+                                            get_channel_players(&server_context, &channel).await;
+
+                                        let response = format!(
+                                            "channel-players-list>{}|{}",
+                                            channel,
+                                            serde_json::to_string(&players).unwrap_or_default()
+                                        );
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::ChannelBandwidth { channel } => {
+                                        let stats =
+                                            get_channel_bandwidth(&server_context, &channel).await;
+
+                                        let response = format!(
+                                            "channel-bandwidth-stats>{}|{}|{}|{}|{}",
+                                            channel,
+                                            stats.bit_rate_in,
+                                            stats.bit_rate_out,
+                                            stats.bytes_in,
+                                            stats.bytes_out
+                                        );
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::CuePoint {
+                                        channel,
+                                        name,
+                                        record,
+                                        parameters,
+                                    } => {
+                                        let res = match parse_cue_point_parameters(&parameters) {
+                                            Ok(p) => {
+                                                inject_cue_point(
+                                                    &logger,
+                                                    &server_context,
+                                                    &channel,
+                                                    &name,
+                                                    &p,
+                                                    record,
+                                                )
+                                                .await
+                                            }
+                                            Err(e) => Err(e),
+                                        };
+
+                                        let response = match res {
+                                            Ok(()) => {
+                                                format!("cue-point-injected>{}|{}", channel, name)
+                                            }
+                                            Err(e) => format!("cue-point-error>{}|{}", channel, e),
+                                        };
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::GopClear { channel } => {
+                                        let response = match clear_gop_cache(
+                                            &logger,
+                                            &server_context,
+                                            &channel,
+                                        )
+                                        .await
+                                        {
+                                            Ok(removed) => {
+                                                format!("gop-cleared>{}|{}", channel, removed)
+                                            }
+                                            Err(e) => format!("gop-clear-error>{}|{}", channel, e),
+                                        };
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::KeyRotate {
+                                        channel,
+                                        key,
+                                        grace_seconds,
+                                    } => {
+                                        let response = match rotate_channel_key(
+                                            &logger,
+                                            &server_context,
+                                            &channel,
+                                            &key,
+                                            grace_seconds.unwrap_or(
+                                                server_context.config.key_rotation_grace_seconds,
+                                            ),
+                                        )
+                                        .await
+                                        {
+                                            Ok(_) => format!("key-rotated>{}", channel),
+                                            Err(e) => format!("key-rotate-error>{}|{}", channel, e),
+                                        };
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::KeyGenerate {
+                                        channel,
+                                        expiration_seconds,
+                                    } => {
+                                        let response = match generate_stored_channel_key(
+                                            &logger,
+                                            &server_context,
+                                            &channel,
+                                            expiration_seconds,
+                                        )
+                                        .await
+                                        {
+                                            Ok((stored_key, key)) => format!(
+                                                "key-generated>{}|{}|{}|{}",
+                                                channel,
+                                                stored_key.id,
+                                                key,
+                                                stored_key
+                                                    .expires_at
+                                                    .map(|e| e.to_string())
+                                                    .unwrap_or_default()
+                                            ),
+                                            Err(e) => {
+                                                format!("key-generate-error>{}|{}", channel, e)
+                                            }
+                                        };
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::KeyList { channel } => {
+                                        let keys = server_context
+                                            .status
+                                            .key_store
+                                            .list(Some(&channel))
+                                            .await;
+
+                                        let response = format!(
+                                            "key-list-result>{}|{}",
+                                            channel,
+                                            serde_json::to_string(&keys).unwrap_or_default()
+                                        );
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
+                                        )
+                                        .await;
+                                    }
+                                    RedisRtmpCommand::KeyRevoke { channel, key_id } => {
+                                        let revoked = revoke_stored_channel_key(
+                                            &logger,
+                                            &server_context,
+                                            &channel,
+                                            &key_id,
+                                        )
+                                        .await
+                                        .is_ok();
+
+                                        let response = format!(
+                                            "key-revoked>{}|{}|{}",
+                                            channel,
+                                            key_id,
+                                            if revoked { "yes" } else { "no" }
+                                        );
+
+                                        send_response(
+                                            &logger,
+                                            &config,
+                                            &server_context.config.identity,
+                                            &mut connection,
+                                            response,
                                         )
                                         .await;
                                     }
@@ -124,6 +522,101 @@ pub fn spawn_task_redis_client(
     });
 }
 
+/// Publishes a response to a command
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The Redis client configuration
+/// * `identity` - The identity of the server node
+/// * `connection` - The Redis connection
+/// * `response` - The response message
+async fn send_response(
+    logger: &Logger,
+    config: &RedisConfiguration,
+    identity: &ServerIdentity,
+    connection: &mut MultiplexedConnection,
+    response: String,
+) {
+    if config.response_channel.is_empty() {
+        return;
+    }
+
+    // If the server ID is set, add the identity as the last arguments,
+    // so the responses of the nodes listening to the same channel can be attributed
+
+    let response = match identity.server_id() {
+        Some(server_id) => format!("{}|{}|{}", response, server_id, identity.region),
+        None => response,
+    };
+
+    log_trace!(logger, format!("Sending response: {}", &response));
+
+    let res: redis::RedisResult<i64> = connection
+        .publish(&config.response_channel, &response)
+        .await;
+
+    if let Err(e) = res {
+        log_warning!(
+            logger,
+            format!(
+                "Could not publish response to {}: {}",
+                &config.response_channel, e
+            )
+        );
+    }
+}
+
+/// Handles a command to start or stop tracing sessions
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The RTMP server context
+/// * `start` - True to start tracing, false to stop
+/// * `kind` - Kind of filter (`ip` or `channel`)
+/// * `value` - Value of the filter
+///
+/// # Return value
+///
+/// Returns the response message
+async fn handle_trace_command(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    start: bool,
+    kind: &str,
+    value: &str,
+) -> String {
+    let traces = &server_context.status.traces;
+    let trace_config = &server_context.config.trace;
+
+    let res = match SessionTraceFilter::parse(kind, value) {
+        Ok(filter) => {
+            if start {
+                traces.start_trace(logger, trace_config, filter).await
+            } else {
+                traces.stop_trace(logger, trace_config, &filter).await
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok(count) => format!(
+            "{}>{}|{}|{}",
+            if start {
+                "trace-started"
+            } else {
+                "trace-stopped"
+            },
+            kind,
+            value,
+            count
+        ),
+        Err(e) => format!("trace-error>{}|{}|{}", kind, value, e),
+    }
+}
+
 fn value_to_string(v: &Value) -> String {
     match v {
         Value::BulkString(items) => match String::from_utf8(items.clone()) {
@@ -1,9 +1,75 @@
 // Redis command
 
+use crate::utils::ChannelSelector;
+
+/// Argument to confirm the kill of a group of channels
+const REDIS_KILL_CONFIRMATION: &str = "confirm";
+
 /// RTMP command received via REdis
 pub enum RedisRtmpCommand {
-    KillSession { channel: String },
-    CloseStream { channel: String, stream_id: String },
+    KillSession {
+        channels: ChannelSelector,
+    },
+    KillSessionRejected {
+        selector: String,
+        error: String,
+    },
+    CloseStream {
+        channel: String,
+        stream_id: String,
+    },
+    KickPlayer {
+        channel: String,
+        player: String,
+    },
+    RecordStart {
+        channel: String,
+    },
+    RecordStop {
+        channel: String,
+    },
+    TraceStart {
+        kind: String,
+        value: String,
+    },
+    TraceStop {
+        kind: String,
+        value: String,
+    },
+    ChannelEvents {
+        channel: String,
+    },
+    ChannelPlayers {
+        channel: String,
+    },
+    ChannelBandwidth {
+        channel: String,
+    },
+    CuePoint {
+        channel: String,
+        name: String,
+        record: bool,
+        parameters: String,
+    },
+    GopClear {
+        channel: String,
+    },
+    KeyRotate {
+        channel: String,
+        key: String,
+        grace_seconds: Option<u32>,
+    },
+    KeyGenerate {
+        channel: String,
+        expiration_seconds: u32,
+    },
+    KeyList {
+        channel: String,
+    },
+    KeyRevoke {
+        channel: String,
+        key_id: String,
+    },
     Unknown,
 }
 
@@ -26,8 +92,16 @@ impl RedisRtmpCommand {
                     return RedisRtmpCommand::Unknown;
                 }
 
-                RedisRtmpCommand::KillSession {
-                    channel: args[0].to_string(),
+                let confirmed = args
+                    .get(1)
+                    .is_some_and(|c| c.eq_ignore_ascii_case(REDIS_KILL_CONFIRMATION));
+
+                match ChannelSelector::parse_kill_target(args[0], confirmed) {
+                    Ok(channels) => RedisRtmpCommand::KillSession { channels },
+                    Err(error) => RedisRtmpCommand::KillSessionRejected {
+                        selector: args[0].to_string(),
+                        error,
+                    },
                 }
             }
             "close-stream" => {
@@ -40,6 +114,148 @@ impl RedisRtmpCommand {
                     stream_id: args[1].to_string(),
                 }
             }
+            "kick-player" => {
+                if args.len() < 2 {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::KickPlayer {
+                    channel: args[0].to_string(),
+                    player: args[1].to_string(),
+                }
+            }
+            "record-start" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::RecordStart {
+                    channel: args[0].to_string(),
+                }
+            }
+            "record-stop" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::RecordStop {
+                    channel: args[0].to_string(),
+                }
+            }
+            "trace-start" => {
+                if args.len() < 2 {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::TraceStart {
+                    kind: args[0].to_string(),
+                    value: args[1].to_string(),
+                }
+            }
+            "trace-stop" => {
+                if args.len() < 2 {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::TraceStop {
+                    kind: args[0].to_string(),
+                    value: args[1].to_string(),
+                }
+            }
+            "channel-events" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::ChannelEvents {
+                    channel: args[0].to_string(),
+                }
+            }
+            "channel-players" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::ChannelPlayers {
+                    channel: args[0].to_string(),
+                }
+            }
+            "channel-bandwidth" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::ChannelBandwidth {
+                    channel: args[0].to_string(),
+                }
+            }
+            "cue-point" => {
+                if args.len() < 2 {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::CuePoint {
+                    channel: args[0].to_string(),
+                    name: args[1].to_string(),
+                    record: args
+                        .get(2)
+                        .is_some_and(|r| matches!(r.to_lowercase().as_str(), "yes" | "true")),
+                    // The parameters are a JSON object, that may contain the separator
+                    parameters: args.get(3..).map(|p| p.join("|")).unwrap_or_default(),
+                }
+            }
+            "gop-clear" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::GopClear {
+                    channel: args[0].to_string(),
+                }
+            }
+            "key-rotate" => {
+                if args.len() < 2 {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::KeyRotate {
+                    channel: args[0].to_string(),
+                    key: args[1].to_string(),
+                    grace_seconds: args.get(2).and_then(|g| g.parse::<u32>().ok()),
+                }
+            }
+            "key-generate" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::KeyGenerate {
+                    channel: args[0].to_string(),
+                    expiration_seconds: args
+                        .get(1)
+                        .and_then(|e| e.parse::<u32>().ok())
+                        .unwrap_or(0),
+                }
+            }
+            "key-list" => {
+                if args.is_empty() {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::KeyList {
+                    channel: args[0].to_string(),
+                }
+            }
+            "key-revoke" => {
+                if args.len() < 2 {
+                    return RedisRtmpCommand::Unknown;
+                }
+
+                RedisRtmpCommand::KeyRevoke {
+                    channel: args[0].to_string(),
+                    key_id: args[1].to_string(),
+                }
+            }
             _ => RedisRtmpCommand::Unknown,
         }
     }
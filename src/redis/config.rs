@@ -3,10 +3,11 @@
 use crate::{
     log::Logger,
     log_error,
-    utils::{get_env_bool, get_env_string, get_env_u32},
+    utils::{get_env_bool, get_env_secret, get_env_string, get_env_u32},
 };
 
 /// Redis configuration
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
 pub struct RedisConfiguration {
     /// Redis host
     pub host: String,
@@ -20,6 +21,9 @@ pub struct RedisConfiguration {
     /// Channel to subscribe to
     pub channel: String,
 
+    /// Channel to publish the responses to (empty to not send them)
+    pub response_channel: String,
+
     /// Use TLS?
     pub tls: bool,
 }
@@ -28,6 +32,14 @@ impl RedisConfiguration {
     /// Loads redis feature configuration
     /// from environment variables
     pub fn load_from_env(logger: &Logger) -> Result<RedisConfiguration, ()> {
+        if !cfg!(feature = "redis") {
+            log_error!(
+                logger,
+                "Redis is enabled, but the server was built without the redis feature"
+            );
+            return Err(());
+        }
+
         let host = get_env_string("REDIS_HOST", "127.0.0.1");
 
         let port = get_env_u32("REDIS_PORT", 6379);
@@ -37,8 +49,15 @@ impl RedisConfiguration {
             return Err(());
         }
 
-        let password = get_env_string("REDIS_PASSWORD", "");
+        let password = match get_env_secret("REDIS_PASSWORD", "") {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!(logger, e);
+                return Err(());
+            }
+        };
         let channel = get_env_string("REDIS_CHANNEL", "rtmp_commands");
+        let response_channel = get_env_string("REDIS_RESPONSE_CHANNEL", "");
 
         let tls = get_env_bool("REDIS_TLS", false);
 
@@ -47,6 +66,7 @@ impl RedisConfiguration {
             port,
             password,
             channel,
+            response_channel,
             tls,
         })
     }
@@ -1,9 +1,13 @@
 // Redis feature
 
+#[cfg(feature = "redis")]
 mod client;
+#[cfg(feature = "redis")]
 mod command;
 mod config;
 
+#[cfg(feature = "redis")]
 pub use client::*;
+#[cfg(feature = "redis")]
 pub use command::*;
 pub use config::*;
@@ -0,0 +1,630 @@
+// Chunk decoding logic
+
+use std::{cmp, fmt};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::{log::Logger, log_debug};
+
+use super::{
+    get_rtmp_header_size, RtmpInputPacketBuffer, RtmpPacket, RTMP_CHUNK_TYPE_0, RTMP_CHUNK_TYPE_1,
+    RTMP_CHUNK_TYPE_2, RTMP_TYPE_METADATA,
+};
+
+/// Error decoding a RTMP chunk
+#[derive(Debug)]
+pub enum RtmpChunkDecodeError {
+    /// The chunk stream has no previous message header to continue
+    MissingPreviousHeader { channel_id: u32 },
+
+    /// The chunk header could not be parsed
+    InvalidHeader(&'static str),
+
+    /// The client sent a stop packet (invalid packet type)
+    StopPacket { packet_type: u32 },
+}
+
+impl fmt::Display for RtmpChunkDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtmpChunkDecodeError::MissingPreviousHeader { channel_id } => write!(
+                f,
+                "Chunk stream {} has no previous message header",
+                channel_id
+            ),
+            RtmpChunkDecodeError::InvalidHeader(field) => {
+                write!(f, "Could not parse {}", field)
+            }
+            RtmpChunkDecodeError::StopPacket { packet_type } => {
+                write!(f, "Received stop packet: {}", packet_type)
+            }
+        }
+    }
+}
+
+/// Decoded RTMP chunk
+pub struct RtmpDecodedChunk {
+    /// Size of the chunk (bytes)
+    pub size: usize,
+
+    /// True if the chunk started a new message
+    pub message_start: bool,
+
+    /// Clock value of the message
+    pub clock: i64,
+
+    /// The message, if the chunk completed it
+    pub packet: Option<RtmpPacket>,
+}
+
+/// Status of the decoding of a RTMP chunk
+pub enum RtmpChunkDecodeStatus {
+    /// More bytes are required to decode the chunk
+    Incomplete {
+        /// Total number of bytes required to continue decoding
+        needed: usize,
+
+        /// The field of the chunk that is incomplete
+        field: &'static str,
+    },
+
+    /// The chunk was decoded
+    Complete(RtmpDecodedChunk),
+}
+
+/// Decodes a RTMP chunk, storing its payload into the buffer of input packets
+///
+/// The buffer is only changed when the chunk is complete,
+/// so the call can be repeated with more bytes until it is.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `data` - The received bytes, starting at the chunk
+/// * `in_packets` - Buffer of input packets
+/// * `in_chunk_size` - Size of the input chunks
+///
+/// # Return value
+///
+/// Returns the decoding status, or an error if the session must be closed
+pub fn rtmp_decode_chunk(
+    logger: &Logger,
+    data: &[u8],
+    in_packets: &mut RtmpInputPacketBuffer,
+    in_chunk_size: usize,
+) -> Result<RtmpChunkDecodeStatus, RtmpChunkDecodeError> {
+    // Start byte
+
+    let start_byte = match data.first() {
+        Some(b) => *b,
+        None => {
+            return Ok(RtmpChunkDecodeStatus::Incomplete {
+                needed: 1,
+                field: "start byte",
+            });
+        }
+    };
+
+    // Header
+
+    let basic_bytes: usize = if start_byte & 0x3f == 0 {
+        2
+    } else if start_byte & 0x3f == 1 {
+        3
+    } else {
+        1
+    };
+
+    if data.len() < basic_bytes {
+        return Ok(RtmpChunkDecodeStatus::Incomplete {
+            needed: basic_bytes,
+            field: "basic header",
+        });
+    }
+
+    let header_size = basic_bytes + get_rtmp_header_size(start_byte >> 6);
+
+    if data.len() < header_size {
+        return Ok(RtmpChunkDecodeStatus::Incomplete {
+            needed: header_size,
+            field: "header",
+        });
+    }
+
+    let header = &data[..header_size];
+
+    // Parse packet metadata
+
+    let format = (header[0] >> 6) as u32;
+
+    let channel_id = match basic_bytes {
+        2 => 64 + (header[1] as u32),
+        3 => 64 + (header[1] as u32) + ((header[2] as u32) << 8),
+        _ => (header[0] & 0x3f) as u32,
+    };
+
+    // Status of the chunk stream, as it will be when the chunk is stored.
+    // Handled packets are reset, and chunks of type 0, 1 and 2 always start a new message.
+
+    let (mut packet_type, mut length, bytes, previous_extended_timestamp) =
+        match in_packets.peek_packet(channel_id) {
+            Some(p) => (
+                p.packet.header.packet_type,
+                p.packet.header.length,
+                if p.handled || format <= RTMP_CHUNK_TYPE_2 {
+                    0
+                } else {
+                    p.bytes
+                },
+                p.extended_timestamp,
+            ),
+            None => (0, 0, 0, None),
+        };
+
+    if format >= RTMP_CHUNK_TYPE_2 && packet_type == 0 {
+        return Err(RtmpChunkDecodeError::MissingPreviousHeader { channel_id });
+    }
+
+    let mut offset: usize = basic_bytes;
+
+    // Timestamp / delta
+    let mut timestamp: Option<i64> = None;
+
+    if format <= RTMP_CHUNK_TYPE_2 {
+        if header.len() < offset + 3 {
+            return Err(RtmpChunkDecodeError::InvalidHeader("timestamp/delta"));
+        }
+
+        let ts_bytes = &header[offset..offset + 3];
+
+        timestamp = Some(
+            ((ts_bytes[2] as u32) | ((ts_bytes[1] as u32) << 8) | ((ts_bytes[0] as u32) << 16))
+                as i64,
+        );
+
+        offset += 3;
+    }
+
+    // Message length + type
+    if format <= RTMP_CHUNK_TYPE_1 {
+        if header.len() < offset + 4 {
+            return Err(RtmpChunkDecodeError::InvalidHeader("message length + type"));
+        }
+
+        let ts_bytes = &header[offset..offset + 3];
+
+        length = ((ts_bytes[2] as u32) | ((ts_bytes[1] as u32) << 8) | ((ts_bytes[0] as u32) << 16))
+            as usize;
+        packet_type = header[offset + 3] as u32;
+
+        offset += 4;
+    }
+
+    // Stream id
+    let mut stream_id: Option<u32> = None;
+
+    if format == RTMP_CHUNK_TYPE_0 {
+        if header.len() < offset + 4 {
+            return Err(RtmpChunkDecodeError::InvalidHeader("stream id"));
+        }
+
+        stream_id = Some(LittleEndian::read_u32(&header[offset..offset + 4]));
+    }
+
+    // Stop packet
+    if packet_type == 0 || packet_type > RTMP_TYPE_METADATA {
+        return Err(RtmpChunkDecodeError::StopPacket { packet_type });
+    }
+
+    // Extended timestamp
+    // Type 3 chunks carry it when the last message header of the chunk stream did
+    let has_extended_timestamp = match timestamp {
+        Some(t) => t == 0xffffff,
+        None => previous_extended_timestamp.is_some(),
+    };
+
+    let payload_offset = if has_extended_timestamp {
+        header_size + 4
+    } else {
+        header_size
+    };
+
+    if data.len() < payload_offset {
+        return Ok(RtmpChunkDecodeStatus::Incomplete {
+            needed: payload_offset,
+            field: "extended timestamp",
+        });
+    }
+
+    // Packet payload
+
+    let size_to_read: usize = cmp::min(
+        in_chunk_size - (bytes % in_chunk_size),
+        length.saturating_sub(bytes),
+    );
+
+    let chunk_size = payload_offset + size_to_read;
+
+    if data.len() < chunk_size {
+        return Ok(RtmpChunkDecodeStatus::Incomplete {
+            needed: chunk_size,
+            field: "payload bytes",
+        });
+    }
+
+    // The chunk is complete, store it
+
+    let (packet_wrapper, dropped_channel_id) = in_packets.get_packet(channel_id);
+
+    if let Some(dropped_channel_id) = dropped_channel_id {
+        log_debug!(
+            logger,
+            format!(
+                "Input packets buffer full. Dropped chunk stream {} to make room for {}",
+                dropped_channel_id, channel_id
+            )
+        );
+    }
+
+    if format <= RTMP_CHUNK_TYPE_2 && packet_wrapper.bytes > 0 {
+        log_debug!(
+            logger,
+            format!(
+                "Chunk stream {}: Discarded incomplete message: {} of {} bytes received",
+                channel_id, packet_wrapper.bytes, packet_wrapper.packet.header.length
+            )
+        );
+
+        packet_wrapper.reset();
+    }
+
+    packet_wrapper.packet.header.channel_id = channel_id;
+    packet_wrapper.packet.header.format = format;
+    packet_wrapper.packet.header.length = length;
+    packet_wrapper.packet.header.packet_type = packet_type;
+
+    if let Some(timestamp) = timestamp {
+        packet_wrapper.packet.header.timestamp = timestamp;
+    }
+
+    if let Some(stream_id) = stream_id {
+        packet_wrapper.packet.header.stream_id = stream_id;
+    }
+
+    let extended_timestamp: i64 = if has_extended_timestamp {
+        let extended_timestamp = BigEndian::read_u32(&data[header_size..payload_offset]);
+
+        if format <= RTMP_CHUNK_TYPE_2 {
+            packet_wrapper.extended_timestamp = Some(extended_timestamp);
+            packet_wrapper.packet.header.timestamp = extended_timestamp as i64;
+        } else if packet_wrapper.bytes > 0
+            && packet_wrapper.extended_timestamp != Some(extended_timestamp)
+        {
+            log_debug!(
+                logger,
+                format!(
+                    "Chunk stream {}: Extended timestamp mismatch in continuation chunk. Expected: {:?}. Received: {}",
+                    channel_id, packet_wrapper.extended_timestamp, extended_timestamp
+                )
+            );
+        }
+
+        extended_timestamp as i64
+    } else {
+        if format <= RTMP_CHUNK_TYPE_2 {
+            packet_wrapper.extended_timestamp = None;
+        }
+
+        packet_wrapper.packet.header.timestamp
+    };
+
+    let mut message_start = false;
+
+    if packet_wrapper.bytes == 0 {
+        if format == RTMP_CHUNK_TYPE_0 {
+            packet_wrapper.clock = extended_timestamp;
+        } else {
+            packet_wrapper.clock = packet_wrapper.clock.wrapping_add(extended_timestamp);
+        }
+
+        message_start = true;
+    }
+
+    if size_to_read > 0 {
+        packet_wrapper
+            .packet
+            .payload
+            .extend_from_slice(&data[payload_offset..chunk_size]);
+        packet_wrapper.bytes += size_to_read;
+    }
+
+    let packet = if packet_wrapper.bytes >= packet_wrapper.packet.header.length {
+        packet_wrapper.handled = true;
+
+        Some(RtmpPacket {
+            header: packet_wrapper.packet.header.clone(),
+            payload: std::mem::take(&mut packet_wrapper.packet.payload),
+        })
+    } else {
+        None
+    };
+
+    Ok(RtmpChunkDecodeStatus::Complete(RtmpDecodedChunk {
+        size: chunk_size,
+        message_start,
+        clock: packet_wrapper.clock,
+        packet,
+    }))
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::rtmp::{
+        RTMP_CHUNK_TYPE_3, RTMP_MIN_CHUNK_SIZE, RTMP_TYPE_AUDIO, RTMP_TYPE_DATA, RTMP_TYPE_INVOKE,
+        RTMP_TYPE_VIDEO,
+    };
+
+    use super::*;
+
+    /// Received message: (stream id, packet type, clock, payload)
+    type TestMessage = (u32, u32, i64, Vec<u8>);
+
+    /// Serializes a packet, splitting the result in chunks
+    fn make_split_chunks(packet: &RtmpPacket, out_chunk_size: usize) -> Vec<Vec<u8>> {
+        let data = packet.create_chunks(out_chunk_size);
+
+        let extended_timestamp_size = if packet.header.timestamp >= 0xffffff {
+            4
+        } else {
+            0
+        };
+
+        let mut header_size =
+            RtmpPacket::serialize_basic_header(packet.header.format, packet.header.channel_id)
+                .len()
+                + packet
+                    .serialize_chunk_message_header(packet.header.stream_id)
+                    .len()
+                + extended_timestamp_size;
+
+        let mut chunks: Vec<Vec<u8>> = Vec::new();
+        let mut offset: usize = 0;
+        let mut remaining = packet.payload.len();
+
+        loop {
+            let payload_size = remaining.min(out_chunk_size);
+
+            chunks.push(data[offset..offset + header_size + payload_size].to_vec());
+
+            offset += header_size + payload_size;
+            remaining -= payload_size;
+
+            header_size =
+                RtmpPacket::serialize_basic_header(RTMP_CHUNK_TYPE_3, packet.header.channel_id)
+                    .len()
+                    + extended_timestamp_size;
+
+            if remaining == 0 {
+                break;
+            }
+        }
+
+        assert_eq!(offset, data.len());
+
+        chunks
+    }
+
+    /// Generates a random stream of interleaved chunks
+    ///
+    /// # Return value
+    ///
+    /// Returns the chunk size, the stream and the expected messages, by chunk stream
+    fn make_random_chunk_stream(
+        rng: &mut StdRng,
+    ) -> (usize, Vec<u8>, HashMap<u32, Vec<TestMessage>>) {
+        let chunk_size = rng.random_range(RTMP_MIN_CHUNK_SIZE..=1024);
+
+        let mut expected: HashMap<u32, Vec<TestMessage>> = HashMap::new();
+        let mut pending: Vec<VecDeque<Vec<u8>>> = Vec::new();
+
+        for channel_id in [2, 3, 4, 5, 6, 8, 63, 64, 200, 319, 320, 65599] {
+            if rng.random_bool(0.3) {
+                continue;
+            }
+
+            let stream_id: u32 = rng.random_range(0..4);
+            let mut clock: i64 = 0;
+            let mut messages: Vec<TestMessage> = Vec::new();
+            let mut chunks: VecDeque<Vec<u8>> = VecDeque::new();
+
+            for i in 0..rng.random_range(1..6) {
+                let mut packet = RtmpPacket::new_blank();
+
+                packet.header.channel_id = channel_id;
+                packet.header.stream_id = stream_id;
+                packet.header.packet_type = [
+                    RTMP_TYPE_AUDIO,
+                    RTMP_TYPE_VIDEO,
+                    RTMP_TYPE_DATA,
+                    RTMP_TYPE_INVOKE,
+                ][rng.random_range(0..4)];
+
+                if i == 0 {
+                    packet.header.format = RTMP_CHUNK_TYPE_0;
+                    packet.header.timestamp = if rng.random_bool(0.3) {
+                        rng.random_range(0xffffff..0xffffffff)
+                    } else {
+                        rng.random_range(0..100000)
+                    };
+                    clock = packet.header.timestamp;
+                } else {
+                    packet.header.format = RTMP_CHUNK_TYPE_1;
+                    packet.header.timestamp = if rng.random_bool(0.1) {
+                        rng.random_range(0xffffff..0x1ffffff)
+                    } else {
+                        rng.random_range(0..1000)
+                    };
+                    clock = clock.wrapping_add(packet.header.timestamp);
+                }
+
+                packet.payload = vec![0; rng.random_range(0..3000)];
+                rng.fill(&mut packet.payload[..]);
+                packet.header.length = packet.payload.len();
+
+                chunks.extend(make_split_chunks(&packet, chunk_size));
+                messages.push((stream_id, packet.header.packet_type, clock, packet.payload));
+            }
+
+            expected.insert(channel_id, messages);
+            pending.push(chunks);
+        }
+
+        // Interleave the chunks of the chunk streams
+
+        let mut stream: Vec<u8> = Vec::new();
+
+        loop {
+            pending.retain(|p| !p.is_empty());
+
+            if pending.is_empty() {
+                break;
+            }
+
+            let i = rng.random_range(0..pending.len());
+
+            stream.extend(pending[i].pop_front().unwrap());
+        }
+
+        (chunk_size, stream, expected)
+    }
+
+    /// Decodes a stream, offering the bytes in random pieces
+    ///
+    /// # Return value
+    ///
+    /// Returns the received messages, by chunk stream, or None if the decoding failed
+    fn decode_stream(
+        rng: &mut StdRng,
+        stream: &[u8],
+        in_packets: &mut RtmpInputPacketBuffer,
+        chunk_size: usize,
+    ) -> Option<HashMap<u32, Vec<TestMessage>>> {
+        let logger = Logger::new_disabled();
+
+        let mut received: HashMap<u32, Vec<TestMessage>> = HashMap::new();
+        let mut offset: usize = 0;
+        let mut available: usize = 0;
+
+        while offset < stream.len() {
+            available = (available + rng.random_range(0..64)).min(stream.len() - offset);
+
+            match rtmp_decode_chunk(
+                &logger,
+                &stream[offset..offset + available],
+                in_packets,
+                chunk_size,
+            )
+            .ok()?
+            {
+                RtmpChunkDecodeStatus::Incomplete { needed, .. } => {
+                    assert!(needed > available);
+
+                    if available == stream.len() - offset {
+                        return None;
+                    }
+                }
+                RtmpChunkDecodeStatus::Complete(chunk) => {
+                    if let Some(packet) = chunk.packet {
+                        received.entry(packet.header.channel_id).or_default().push((
+                            packet.header.stream_id,
+                            packet.header.packet_type,
+                            chunk.clock,
+                            packet.payload,
+                        ));
+                    }
+
+                    offset += chunk.size;
+                    available -= chunk.size;
+                }
+            }
+        }
+
+        Some(received)
+    }
+
+    #[test]
+    fn test_decode_interleaved_chunks() {
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            let (chunk_size, stream, expected) = make_random_chunk_stream(&mut rng);
+
+            let mut in_packets = RtmpInputPacketBuffer::new(expected.len());
+
+            let received = decode_stream(&mut rng, &stream, &mut in_packets, chunk_size)
+                .unwrap_or_else(|| panic!("Could not decode stream. Seed: {}", seed));
+
+            assert!(received == expected, "Seed: {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed_chunks() {
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            let (chunk_size, mut stream, _) = make_random_chunk_stream(&mut rng);
+
+            // Corrupt some bytes of the stream
+
+            for _ in 0..rng.random_range(1..8) {
+                let i = rng.random_range(0..stream.len());
+                stream[i] = rng.random();
+            }
+
+            // Decoding must end with an error or at the end of the stream, with no panics
+
+            let mut in_packets = RtmpInputPacketBuffer::new(4);
+
+            decode_stream(&mut rng, &stream, &mut in_packets, chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_decode_chunk_errors() {
+        let logger = Logger::new_disabled();
+
+        let mut in_packets = RtmpInputPacketBuffer::new(4);
+
+        assert!(matches!(
+            rtmp_decode_chunk(&logger, &[], &mut in_packets, 128),
+            Ok(RtmpChunkDecodeStatus::Incomplete {
+                needed: 1,
+                field: "start byte"
+            })
+        ));
+
+        // Type 3 chunk with no previous message
+        assert!(matches!(
+            rtmp_decode_chunk(&logger, &[0xC3, 0x00], &mut in_packets, 128),
+            Err(RtmpChunkDecodeError::MissingPreviousHeader { channel_id: 3 })
+        ));
+
+        // Type 0 chunk with invalid packet type
+        assert!(matches!(
+            rtmp_decode_chunk(
+                &logger,
+                &[0x03, 0, 0, 0, 0, 0, 1, 0x7F, 0, 0, 0, 0, 0x00],
+                &mut in_packets,
+                128
+            ),
+            Err(RtmpChunkDecodeError::StopPacket { packet_type: 0x7F })
+        ));
+    }
+}
@@ -0,0 +1,313 @@
+// Codec identification utilities
+
+/// Legacy FLV sound formats
+pub const AUDIO_CODEC_PCM: u32 = 0;
+pub const AUDIO_CODEC_ADPCM: u32 = 1;
+pub const AUDIO_CODEC_MP3: u32 = 2;
+pub const AUDIO_CODEC_PCM_LE: u32 = 3;
+pub const AUDIO_CODEC_NELLYMOSER_16K: u32 = 4;
+pub const AUDIO_CODEC_NELLYMOSER_8K: u32 = 5;
+pub const AUDIO_CODEC_NELLYMOSER: u32 = 6;
+pub const AUDIO_CODEC_G711A: u32 = 7;
+pub const AUDIO_CODEC_G711U: u32 = 8;
+pub const AUDIO_CODEC_EX_HEADER: u32 = 9;
+pub const AUDIO_CODEC_AAC: u32 = 10;
+pub const AUDIO_CODEC_SPEEX: u32 = 11;
+pub const AUDIO_CODEC_OPUS: u32 = 13;
+pub const AUDIO_CODEC_MP3_8K: u32 = 14;
+
+/// Legacy FLV video codec IDs
+pub const VIDEO_CODEC_SORENSON: u32 = 2;
+pub const VIDEO_CODEC_SCREEN: u32 = 3;
+pub const VIDEO_CODEC_VP6: u32 = 4;
+pub const VIDEO_CODEC_VP6_ALPHA: u32 = 5;
+pub const VIDEO_CODEC_SCREEN_V2: u32 = 6;
+pub const VIDEO_CODEC_H264: u32 = 7;
+pub const VIDEO_CODEC_HEVC: u32 = 12;
+pub const VIDEO_CODEC_AV1: u32 = 13;
+
+/// Flag of the first video byte indicating an Enhanced RTMP header
+pub const VIDEO_EX_HEADER_FLAG: u8 = 0x80;
+
+/// Enhanced RTMP audio packet type of the sequence header (lower 4 bits of the first byte)
+pub const AUDIO_PACKET_TYPE_SEQUENCE_START: u8 = 0;
+
+/// Enhanced RTMP video packet type of the coded frames, with composition time (lower 4 bits of the first byte)
+pub const VIDEO_PACKET_TYPE_CODED_FRAMES: u8 = 1;
+
+/// Enhanced RTMP video packet type of the coded frames, with zero composition time
+pub const VIDEO_PACKET_TYPE_CODED_FRAMES_X: u8 = 3;
+
+/// AVC packet type of the NAL units (legacy H.264 and HEVC)
+pub const AVC_PACKET_TYPE_NALU: u8 = 1;
+
+/// Names of the audio codecs that can be identified
+pub const KNOWN_AUDIO_CODEC_NAMES: &[&str] = &[
+    "pcm",
+    "adpcm",
+    "mp3",
+    "nellymoser",
+    "g711a",
+    "g711u",
+    "aac",
+    "speex",
+    "opus",
+    "flac",
+    "ac3",
+    "eac3",
+];
+
+/// Names of the video codecs that can be identified
+pub const KNOWN_VIDEO_CODEC_NAMES: &[&str] =
+    &["sorenson", "screen", "vp6", "h264", "hevc", "av1", "vp9"];
+
+/// Gets the name of an audio codec from the first byte of an audio packet
+/// Enhanced RTMP packets are identified by their FourCC
+///
+/// # Arguments
+///
+/// * `payload` - Payload of the audio packet
+///
+/// # Return value
+///
+/// Returns the codec name, or None if it could not be identified
+pub fn rtmp_get_audio_codec_name(payload: &[u8]) -> Option<&'static str> {
+    let first_byte = *payload.first()?;
+
+    let sound_format = ((first_byte >> 4) & 0x0f) as u32;
+
+    if sound_format == AUDIO_CODEC_EX_HEADER {
+        if payload.len() < 5 {
+            return None;
+        }
+
+        return match &payload[1..5] {
+            b"Opus" => Some("opus"),
+            b"fLaC" => Some("flac"),
+            b"ac-3" => Some("ac3"),
+            b"ec-3" => Some("eac3"),
+            b"mp4a" => Some("aac"),
+            b".mp3" => Some("mp3"),
+            _ => None,
+        };
+    }
+
+    match sound_format {
+        AUDIO_CODEC_PCM | AUDIO_CODEC_PCM_LE => Some("pcm"),
+        AUDIO_CODEC_ADPCM => Some("adpcm"),
+        AUDIO_CODEC_MP3 | AUDIO_CODEC_MP3_8K => Some("mp3"),
+        AUDIO_CODEC_NELLYMOSER_16K | AUDIO_CODEC_NELLYMOSER_8K | AUDIO_CODEC_NELLYMOSER => {
+            Some("nellymoser")
+        }
+        AUDIO_CODEC_G711A => Some("g711a"),
+        AUDIO_CODEC_G711U => Some("g711u"),
+        AUDIO_CODEC_AAC => Some("aac"),
+        AUDIO_CODEC_SPEEX => Some("speex"),
+        AUDIO_CODEC_OPUS => Some("opus"),
+        _ => None,
+    }
+}
+
+/// Checks if the sound format of a stream uses a sequence header,
+/// that must be sent to the players before the audio frames
+///
+/// # Arguments
+///
+/// * `sound_format` - The sound format (upper 4 bits of the first byte of the audio packets)
+pub fn rtmp_audio_codec_has_sequence_header(sound_format: u32) -> bool {
+    matches!(
+        sound_format,
+        AUDIO_CODEC_AAC | AUDIO_CODEC_OPUS | AUDIO_CODEC_EX_HEADER
+    )
+}
+
+/// Checks if an audio packet is a sequence header (AAC, Opus or Enhanced RTMP)
+///
+/// # Arguments
+///
+/// * `payload` - Payload of the audio packet
+pub fn rtmp_is_audio_sequence_header(payload: &[u8]) -> bool {
+    if payload.len() < 2 {
+        return false;
+    }
+
+    match ((payload[0] >> 4) & 0x0f) as u32 {
+        AUDIO_CODEC_AAC | AUDIO_CODEC_OPUS => payload[1] == 0,
+        AUDIO_CODEC_EX_HEADER => payload[0] & 0x0f == AUDIO_PACKET_TYPE_SEQUENCE_START,
+        _ => false,
+    }
+}
+
+/// Gets the name of a video codec from the first bytes of a video packet
+/// Enhanced RTMP packets are identified by their FourCC
+///
+/// # Arguments
+///
+/// * `payload` - Payload of the video packet
+///
+/// # Return value
+///
+/// Returns the codec name, or None if it could not be identified
+pub fn rtmp_get_video_codec_name(payload: &[u8]) -> Option<&'static str> {
+    let first_byte = *payload.first()?;
+
+    if first_byte & VIDEO_EX_HEADER_FLAG != 0 {
+        if payload.len() < 5 {
+            return None;
+        }
+
+        return match &payload[1..5] {
+            b"avc1" => Some("h264"),
+            b"hvc1" => Some("hevc"),
+            b"av01" => Some("av1"),
+            b"vp09" => Some("vp9"),
+            _ => None,
+        };
+    }
+
+    match (first_byte & 0x0f) as u32 {
+        VIDEO_CODEC_SORENSON => Some("sorenson"),
+        VIDEO_CODEC_SCREEN | VIDEO_CODEC_SCREEN_V2 => Some("screen"),
+        VIDEO_CODEC_VP6 | VIDEO_CODEC_VP6_ALPHA => Some("vp6"),
+        VIDEO_CODEC_H264 => Some("h264"),
+        VIDEO_CODEC_HEVC => Some("hevc"),
+        VIDEO_CODEC_AV1 => Some("av1"),
+        _ => None,
+    }
+}
+
+/// Reads the composition time offset (CTS) of a video packet,
+/// the difference between its presentation and decoding timestamps,
+/// not zero for the frames reordered by B-frames
+///
+/// # Arguments
+///
+/// * `payload` - The payload of the video packet
+///
+/// # Return value
+///
+/// Returns the composition time offset (milliseconds),
+/// or None if the packet is not a coded frame of a codec with composition time
+pub fn rtmp_get_video_composition_time(payload: &[u8]) -> Option<i32> {
+    let first_byte = *payload.first()?;
+
+    if first_byte & VIDEO_EX_HEADER_FLAG != 0 {
+        if payload.len() < 5 {
+            return None;
+        }
+
+        let has_composition_time = matches!(&payload[1..5], b"avc1" | b"hvc1");
+
+        return match first_byte & 0x0f {
+            VIDEO_PACKET_TYPE_CODED_FRAMES if has_composition_time => read_si24(payload.get(5..8)?),
+            VIDEO_PACKET_TYPE_CODED_FRAMES | VIDEO_PACKET_TYPE_CODED_FRAMES_X => Some(0),
+            _ => None,
+        };
+    }
+
+    match (first_byte & 0x0f) as u32 {
+        VIDEO_CODEC_H264 | VIDEO_CODEC_HEVC if *payload.get(1)? == AVC_PACKET_TYPE_NALU => {
+            read_si24(payload.get(2..5)?)
+        }
+        _ => None,
+    }
+}
+
+/// Reads a signed 24 bit big endian integer
+///
+/// # Arguments
+///
+/// * `bytes` - The 3 bytes
+fn read_si24(bytes: &[u8]) -> Option<i32> {
+    match bytes {
+        [b0, b1, b2] => Some(i32::from_be_bytes([*b0, *b1, *b2, 0]) >> 8),
+        _ => None,
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_names() {
+        assert_eq!(rtmp_get_audio_codec_name(&[]), None);
+        assert_eq!(rtmp_get_audio_codec_name(&[0xaf, 0x00]), Some("aac"));
+        assert_eq!(rtmp_get_audio_codec_name(&[0x2f]), Some("mp3"));
+        assert_eq!(rtmp_get_audio_codec_name(&[0xd0]), Some("opus"));
+        assert_eq!(rtmp_get_audio_codec_name(&[0xf0]), None);
+        assert_eq!(
+            rtmp_get_audio_codec_name(&[0x90, b'O', b'p', b'u', b's']),
+            Some("opus")
+        );
+        assert_eq!(
+            rtmp_get_audio_codec_name(&[0x91, b'f', b'L', b'a', b'C']),
+            Some("flac")
+        );
+        assert_eq!(
+            rtmp_get_audio_codec_name(&[0x91, b'a', b'c', b'-', b'3']),
+            Some("ac3")
+        );
+        assert_eq!(rtmp_get_audio_codec_name(&[0x90, b'O', b'p']), None);
+
+        assert!(rtmp_is_audio_sequence_header(&[0xaf, 0x00]));
+        assert!(!rtmp_is_audio_sequence_header(&[0xaf, 0x01]));
+        assert!(rtmp_is_audio_sequence_header(&[
+            0x90, b'O', b'p', b'u', b's'
+        ]));
+        assert!(!rtmp_is_audio_sequence_header(&[
+            0x91, b'O', b'p', b'u', b's'
+        ]));
+        assert!(!rtmp_is_audio_sequence_header(&[0x20, 0x00]));
+
+        assert_eq!(rtmp_get_video_codec_name(&[]), None);
+        assert_eq!(rtmp_get_video_codec_name(&[0x17, 0x00]), Some("h264"));
+        assert_eq!(rtmp_get_video_codec_name(&[0x1c, 0x00]), Some("hevc"));
+        assert_eq!(rtmp_get_video_codec_name(&[0x90, b'a', b'v']), None);
+        assert_eq!(
+            rtmp_get_video_codec_name(&[0x90, b'h', b'v', b'c', b'1']),
+            Some("hevc")
+        );
+        assert_eq!(
+            rtmp_get_video_codec_name(&[0x91, b'a', b'v', b'0', b'1']),
+            Some("av1")
+        );
+    }
+
+    #[test]
+    fn test_video_composition_time() {
+        assert_eq!(rtmp_get_video_composition_time(&[]), None);
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x27, 0x01, 0x00, 0x00, 0x50]),
+            Some(80)
+        );
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x27, 0x01, 0xff, 0xff, 0xd8]),
+            Some(-40)
+        );
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x17, 0x00, 0x00, 0x00, 0x00]),
+            None
+        );
+        assert_eq!(rtmp_get_video_composition_time(&[0x27, 0x01, 0x00]), None);
+        assert_eq!(rtmp_get_video_composition_time(&[0x22, 0x01]), None);
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x91, b'h', b'v', b'c', b'1', 0x00, 0x00, 0x28]),
+            Some(40)
+        );
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x93, b'h', b'v', b'c', b'1']),
+            Some(0)
+        );
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x91, b'a', b'v', b'0', b'1']),
+            Some(0)
+        );
+        assert_eq!(
+            rtmp_get_video_composition_time(&[0x90, b'h', b'v', b'c', b'1']),
+            None
+        );
+    }
+}
@@ -1,8 +1,44 @@
 // RTMP command
 
-use std::{collections::HashMap, sync::LazyLock};
+use std::{collections::HashMap, fmt, sync::LazyLock};
+
+use crate::{
+    amf::{AMF0Value, AMFDecodingCursor, AMFDecodingError},
+    utils::REDACTED_STREAM_KEY,
+};
+
+/// Error decoding a RTMP command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtmpCommandError {
+    /// Could not decode the command name
+    Name(AMFDecodingError),
+
+    /// Could not decode an argument of the command
+    Argument {
+        command: String,
+        argument: String,
+        error: AMFDecodingError,
+    },
+}
 
-use crate::amf::{AMF0Value, AMFDecodingCursor};
+impl fmt::Display for RtmpCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtmpCommandError::Name(error) => {
+                write!(f, "Could not decode command name: {}", error)
+            }
+            RtmpCommandError::Argument {
+                command,
+                argument,
+                error,
+            } => write!(
+                f,
+                "Could not decode argument {} of command {}: {}",
+                argument, command, error
+            ),
+        }
+    }
+}
 
 /// RTMP command
 pub struct RtmpCommand {
@@ -239,7 +275,8 @@ impl RtmpCommand {
         self.arguments.get(arg_name)
     }
 
-    /// Gets string representation of the command for debug logging
+    /// Gets string representation of the command for debug logging.
+    /// The stream name is redacted, since it contains the stream key.
     pub fn to_debug_string(&self) -> String {
         let mut s = format!("{} {}\n", self.cmd, "{");
 
@@ -247,7 +284,11 @@ impl RtmpCommand {
             s.push_str(&format!(
                 "    '{}' = {}\n",
                 arg_name,
-                arg_val.to_debug_string("    ")
+                if arg_name == "streamName" {
+                    REDACTED_STREAM_KEY.to_string()
+                } else {
+                    arg_val.to_debug_string("    ")
+                }
             ));
         }
 
@@ -284,11 +325,43 @@ impl RtmpCommand {
         buf
     }
 
+    /// Encodes command for a client using object encoding AMF3 (FLEX message).
+    /// The command name and the transaction ID are encoded as AMF0,
+    /// and the rest of the arguments as AMF3, wrapped with the AVM+ marker.
+    pub fn encode_amf3(&self) -> Vec<u8> {
+        let x = AMF0Value::String {
+            value: self.cmd.clone(),
+        };
+
+        let mut buf = vec![0x00];
+
+        buf.extend(x.encode());
+
+        let arg_list_res = RTMP_COMMAND_CODES.get(&self.cmd);
+
+        if let Some(arg_list) = arg_list_res {
+            for arg_name in arg_list {
+                let val = self
+                    .arguments
+                    .get(arg_name)
+                    .unwrap_or(&AMF0Value::Undefined);
+
+                if arg_name == "transId" {
+                    buf.extend(val.encode());
+                } else {
+                    buf.extend(val.encode_avmplus());
+                }
+            }
+        }
+
+        buf
+    }
+
     /// Decodes command from bytes
-    pub fn decode(data: &[u8]) -> Result<RtmpCommand, ()> {
+    pub fn decode(data: &[u8]) -> Result<RtmpCommand, RtmpCommandError> {
         let mut cursor = AMFDecodingCursor::new(data);
 
-        let cmd_amf = AMF0Value::read(&mut cursor, data)?;
+        let cmd_amf = AMF0Value::read(&mut cursor, data).map_err(RtmpCommandError::Name)?;
         let cmd = cmd_amf.get_string();
 
         let mut c = RtmpCommand::new(cmd.to_string());
@@ -299,7 +372,13 @@ impl RtmpCommand {
             let mut i: usize = 0;
 
             while i < arg_list.len() && !cursor.ended() {
-                let val = AMF0Value::read(&mut cursor, data)?;
+                let val = AMF0Value::read(&mut cursor, data).map_err(|error| {
+                    RtmpCommandError::Argument {
+                        command: cmd.to_string(),
+                        argument: arg_list[i].clone(),
+                        error,
+                    }
+                })?;
 
                 c.set_argument(arg_list[i].clone(), val);
 
@@ -42,6 +42,8 @@ pub const RTMP_CHANNEL_INVOKE: u32 = 3;
 pub const RTMP_CHANNEL_AUDIO: u32 = 4;
 pub const RTMP_CHANNEL_VIDEO: u32 = 5;
 pub const RTMP_CHANNEL_DATA: u32 = 6;
+pub const RTMP_CHANNEL_INJECTED_DATA: u32 = 7; // Data injected by the server, not recorded
+pub const RTMP_CHANNEL_METADATA_UPDATE: u32 = 8; // Metadata updates, only recorded (players receive them as session messages)
 
 /// Gets RTMP header size from the first byte
 pub fn get_rtmp_header_size(header_byte: u8) -> usize {
@@ -57,6 +59,8 @@ pub fn get_rtmp_header_size(header_byte: u8) -> usize {
 
 /* Protocol Control Messages */
 pub const RTMP_TYPE_SET_CHUNK_SIZE: u32 = 1;
+pub const RTMP_TYPE_ABORT: u32 = 2;
+pub const RTMP_TYPE_ACKNOWLEDGEMENT: u32 = 3;
 pub const RTMP_TYPE_WINDOW_ACKNOWLEDGEMENT_SIZE: u32 = 5; // server bandwidth
 
 /* User Control Messages Event (4) */
@@ -80,6 +84,30 @@ pub const RTMP_TYPE_METADATA: u32 = 22;
 
 pub const STREAM_BEGIN: u16 = 0x00;
 pub const STREAM_EOF: u16 = 0x01;
+pub const STREAM_DRY: u16 = 0x02;
+
+// User control events
+
+pub const RTMP_EVENT_STREAM_BEGIN: u16 = STREAM_BEGIN;
+pub const RTMP_EVENT_STREAM_EOF: u16 = STREAM_EOF;
+pub const RTMP_EVENT_STREAM_DRY: u16 = STREAM_DRY;
+pub const RTMP_EVENT_SET_BUFFER_LENGTH: u16 = 0x03;
+pub const RTMP_EVENT_STREAM_IS_RECORDED: u16 = 0x04;
+pub const RTMP_EVENT_PING_REQUEST: u16 = 0x06;
+pub const RTMP_EVENT_PING_RESPONSE: u16 = 0x07;
+
+// Play start positions
+
+/// Play the live stream, or the recorded one if not live (default)
+pub const RTMP_PLAY_START_ANY: i64 = -2;
+
+/// Play only the live stream
+pub const RTMP_PLAY_START_LIVE: i64 = -1;
+
+// Object encodings
+
+pub const RTMP_OBJECT_ENCODING_AMF0: u32 = 0;
+pub const RTMP_OBJECT_ENCODING_AMF3: u32 = 3;
 
 /// Min chunk size
 pub const RTMP_MIN_CHUNK_SIZE: usize = 128;
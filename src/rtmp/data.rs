@@ -2,7 +2,7 @@
 
 use std::{collections::HashMap, sync::LazyLock};
 
-use crate::amf::{AMF0Value, AMFDecodingCursor};
+use crate::amf::{AMF0Value, AMFDecodingCursor, AMFDecodingError};
 
 /// RTMP data
 pub struct RtmpData {
@@ -21,10 +21,16 @@ static RTMP_DATA_CODES: LazyLock<HashMap<String, Vec<String>>> = LazyLock::new(|
         vec!["method".to_string(), "dataObj".to_string()],
     );
 
+    m.insert("onCuePoint".to_string(), vec!["cuePoint".to_string()]);
+
     m.insert("onFI".to_string(), vec!["info".to_string()]);
 
     m.insert("onMetaData".to_string(), vec!["dataObj".to_string()]);
 
+    m.insert("onPlayStatus".to_string(), vec!["info".to_string()]);
+
+    m.insert("onStatistics".to_string(), vec!["info".to_string()]);
+
     m.insert(
         "|RtmpSampleAccess".to_string(),
         vec!["bool1".to_string(), "bool2".to_string()],
@@ -93,7 +99,7 @@ impl RtmpData {
     }
 
     /// Decodes data from bytes
-    pub fn decode(data: &[u8]) -> Result<RtmpData, ()> {
+    pub fn decode(data: &[u8]) -> Result<RtmpData, AMFDecodingError> {
         let mut cursor = AMFDecodingCursor::new(data);
 
         let tag_amf = AMF0Value::read(&mut cursor, data)?;
@@ -5,7 +5,7 @@ use sha2::Sha256;
 
 use rand::{rngs::StdRng, RngCore, SeedableRng};
 
-use std::sync::LazyLock;
+use std::{fmt, sync::LazyLock};
 
 use crate::{log::Logger, log_debug};
 
@@ -14,6 +14,41 @@ use super::{
     RTMP_SIG_SIZE, RTMP_VERSION, SHA256DL, SHA256K,
 };
 
+/// Error generating the handshake response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtmpHandshakeError {
+    /// The client signature is too small
+    SignatureTooSmall { expected: usize, found: usize },
+
+    /// The generated response is too small
+    ResponseTooSmall { expected: usize, found: usize },
+
+    /// The HMAC has an invalid size
+    InvalidHmacSize { expected: usize, found: usize },
+}
+
+impl fmt::Display for RtmpHandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtmpHandshakeError::SignatureTooSmall { expected, found } => write!(
+                f,
+                "Client signature is too small. Expected at least {}, but found {}",
+                expected, found
+            ),
+            RtmpHandshakeError::ResponseTooSmall { expected, found } => write!(
+                f,
+                "Handshake bytes too small. Expected at least {}, but found {}",
+                expected, found
+            ),
+            RtmpHandshakeError::InvalidHmacSize { expected, found } => write!(
+                f,
+                "HMAC size invalid. Expected {}, but found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
 // Consts for handshake
 
 static GENUINE_FMS_PLUS_CRUD: LazyLock<Vec<u8>> = LazyLock::new(|| {
@@ -30,8 +65,11 @@ static GENUINE_FMS_PLUS_CRUD: LazyLock<Vec<u8>> = LazyLock::new(|| {
 /// Generates full RTMP handshake response
 /// client_signature - Client signature
 /// logger - Logger of the RTMP session
-pub fn generate_s0_s1_s2(client_signature: &[u8], logger: &Logger) -> Result<Vec<u8>, ()> {
-    let msg_format = detect_client_message_format(client_signature, logger)?;
+pub fn generate_s0_s1_s2(
+    client_signature: &[u8],
+    logger: &Logger,
+) -> Result<Vec<u8>, RtmpHandshakeError> {
+    let msg_format = detect_client_message_format(client_signature)?;
 
     let mut all_bytes: Vec<u8> = Vec::new();
 
@@ -44,8 +82,8 @@ pub fn generate_s0_s1_s2(client_signature: &[u8], logger: &Logger) -> Result<Vec
     } else {
         log_debug!(logger, "Using S1S2 handshake");
 
-        let s1 = generate_s1(msg_format, logger)?;
-        let s2 = generate_s2(msg_format, client_signature, logger)?;
+        let s1 = generate_s1(msg_format)?;
+        let s2 = generate_s2(msg_format, client_signature)?;
 
         all_bytes.push(RTMP_VERSION);
         all_bytes.extend(s1);
@@ -57,8 +95,7 @@ pub fn generate_s0_s1_s2(client_signature: &[u8], logger: &Logger) -> Result<Vec
 
 /// Generates RTMP handshake response (S1)
 /// msg_format - Message format
-/// logger - Logger of the RTMP session
-pub fn generate_s1(msg_format: u32, logger: &Logger) -> Result<Vec<u8>, ()> {
+pub fn generate_s1(msg_format: u32) -> Result<Vec<u8>, RtmpHandshakeError> {
     let mut random_bytes = vec![0; RTMP_SIG_SIZE - 8];
 
     let mut rng = StdRng::from_os_rng();
@@ -84,16 +121,10 @@ pub fn generate_s1(msg_format: u32, logger: &Logger) -> Result<Vec<u8>, ()> {
     let mut msg: Vec<u8> = vec![0; server_digest_offset];
 
     if handshake_bytes.len() < server_digest_offset + SHA256DL {
-        log_debug!(
-            logger,
-            format!(
-                "Handshake bytes too small. Expected at least {}, but found {}",
-                server_digest_offset + SHA256DL,
-                handshake_bytes.len()
-            )
-        );
-
-        return Err(());
+        return Err(RtmpHandshakeError::ResponseTooSmall {
+            expected: server_digest_offset + SHA256DL,
+            found: handshake_bytes.len(),
+        });
     }
 
     msg.copy_from_slice(&handshake_bytes[0..server_digest_offset]);
@@ -113,16 +144,10 @@ pub fn generate_s1(msg_format: u32, logger: &Logger) -> Result<Vec<u8>, ()> {
     let h = calc_hmac(&msg, GENUINE_FMS.as_bytes());
 
     if h.len() != SHA256DL {
-        log_debug!(
-            logger,
-            format!(
-                "HMAC size invalid. Expected {}, but found {}",
-                SHA256DL,
-                h.len()
-            )
-        );
-
-        return Err(());
+        return Err(RtmpHandshakeError::InvalidHmacSize {
+            expected: SHA256DL,
+            found: h.len(),
+        });
     }
 
     handshake_bytes[server_digest_offset..server_digest_offset + SHA256DL].copy_from_slice(&h);
@@ -133,21 +158,15 @@ pub fn generate_s1(msg_format: u32, logger: &Logger) -> Result<Vec<u8>, ()> {
 /// Generates RTMP handshake response (S2)
 /// msg_format - Message format
 /// client_signature - Client signature
-/// logger - Logger of the RTMP session
 pub fn generate_s2(
     msg_format: u32,
     client_signature: &[u8],
-    logger: &Logger,
-) -> Result<Vec<u8>, ()> {
+) -> Result<Vec<u8>, RtmpHandshakeError> {
     if client_signature.len() < 776 {
-        log_debug!(
-            logger,
-            format!(
-                "Client signature is too small. Expected at least 776, but found {}",
-                client_signature.len()
-            )
-        );
-        return Err(());
+        return Err(RtmpHandshakeError::SignatureTooSmall {
+            expected: 776,
+            found: client_signature.len(),
+        });
     }
 
     let mut random_bytes = vec![0; RTMP_SIG_SIZE - 32];
@@ -163,15 +182,10 @@ pub fn generate_s2(
     };
 
     if client_signature.len() < challenge_key_offset + SHA256K {
-        log_debug!(
-            logger,
-            format!(
-                "Client signature is too small. Expected at least {}, but found {}",
-                challenge_key_offset + SHA256K,
-                client_signature.len()
-            )
-        );
-        return Err(());
+        return Err(RtmpHandshakeError::SignatureTooSmall {
+            expected: challenge_key_offset + SHA256K,
+            found: client_signature.len(),
+        });
     }
 
     let challenge_key = &client_signature[challenge_key_offset..challenge_key_offset + SHA256K];
@@ -221,16 +235,12 @@ fn compare_signatures(sig1: &[u8], sig2: &[u8]) -> bool {
 }
 
 /// Detects message format from client signature
-fn detect_client_message_format(client_signature: &[u8], logger: &Logger) -> Result<u32, ()> {
+fn detect_client_message_format(client_signature: &[u8]) -> Result<u32, RtmpHandshakeError> {
     if client_signature.len() < 776 {
-        log_debug!(
-            logger,
-            format!(
-                "Client signature is too small. Expected at least 776, but found {}",
-                client_signature.len()
-            )
-        );
-        return Err(());
+        return Err(RtmpHandshakeError::SignatureTooSmall {
+            expected: 776,
+            found: client_signature.len(),
+        });
     }
 
     {
@@ -239,16 +249,10 @@ fn detect_client_message_format(client_signature: &[u8], logger: &Logger) -> Res
         let mut msg = vec![0; sdl];
 
         if client_signature.len() < sdl + SHA256DL {
-            log_debug!(
-                logger,
-                format!(
-                    "Client signature is too small. Expected at least {}, but found {}",
-                    sdl + SHA256DL,
-                    client_signature.len()
-                )
-            );
-
-            return Err(());
+            return Err(RtmpHandshakeError::SignatureTooSmall {
+                expected: sdl + SHA256DL,
+                found: client_signature.len(),
+            });
         }
 
         msg.copy_from_slice(&client_signature[0..sdl]);
@@ -276,16 +280,10 @@ fn detect_client_message_format(client_signature: &[u8], logger: &Logger) -> Res
         let mut msg2 = vec![0; sdl_2];
 
         if client_signature.len() < sdl_2 + SHA256DL {
-            log_debug!(
-                logger,
-                format!(
-                    "Client signature is too small. Expected at least {}, but found {}",
-                    sdl_2 + SHA256DL,
-                    client_signature.len()
-                )
-            );
-
-            return Err(());
+            return Err(RtmpHandshakeError::SignatureTooSmall {
+                expected: sdl_2 + SHA256DL,
+                found: client_signature.len(),
+            });
         }
 
         msg2.copy_from_slice(&client_signature[0..sdl_2]);
@@ -0,0 +1,157 @@
+// Buffer of input packets
+
+use std::collections::HashMap;
+
+use super::RtmpPacketWrapper;
+
+/// Buffer to store the input packets, by chunk stream
+pub struct RtmpInputPacketBuffer {
+    /// Packets, mapped by chunk stream ID
+    packets: HashMap<u32, RtmpPacketWrapper>,
+
+    /// Max number of chunk streams to keep
+    max_size: usize,
+
+    /// Counter increased on each use, to find the least recently used packet
+    use_counter: u64,
+}
+
+impl RtmpInputPacketBuffer {
+    /// Creates new RtmpInputPacketBuffer
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - Max number of chunk streams to keep
+    pub fn new(max_size: usize) -> RtmpInputPacketBuffer {
+        RtmpInputPacketBuffer {
+            packets: HashMap::new(),
+            max_size: max_size.max(1),
+            use_counter: 0,
+        }
+    }
+
+    /// Gets the input packet for a chunk stream
+    ///
+    /// If the buffer is full, the least recently used packet is dropped,
+    /// preferring the packets that were already handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Chunk stream ID
+    ///
+    /// # Return value
+    ///
+    /// Returns the packet to use, and the ID of the chunk stream that was dropped to make room for it
+    pub fn get_packet(&mut self, channel_id: u32) -> (&mut RtmpPacketWrapper, Option<u32>) {
+        self.use_counter = self.use_counter.wrapping_add(1);
+
+        let mut dropped: Option<u32> = None;
+        let mut reused: Option<RtmpPacketWrapper> = None;
+
+        if !self.packets.contains_key(&channel_id) && self.packets.len() >= self.max_size {
+            dropped = self
+                .packets
+                .iter()
+                .min_by_key(|(_, p)| (!p.handled, p.last_use))
+                .map(|(id, _)| *id);
+
+            if let Some(id) = dropped {
+                reused = self.packets.remove(&id);
+            }
+        }
+
+        let packet_wrapper = self
+            .packets
+            .entry(channel_id)
+            .or_insert_with(|| match reused {
+                Some(mut p) => {
+                    p.reset_full();
+                    p
+                }
+                None => RtmpPacketWrapper::new(),
+            });
+
+        if packet_wrapper.handled {
+            packet_wrapper.reset();
+        }
+
+        packet_wrapper.last_use = self.use_counter;
+
+        (packet_wrapper, dropped)
+    }
+
+    /// Gets the input packet for a chunk stream, without using it
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Chunk stream ID
+    pub fn peek_packet(&self, channel_id: u32) -> Option<&RtmpPacketWrapper> {
+        self.packets.get(&channel_id)
+    }
+
+    /// Discards the partially read packet of a chunk stream
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Chunk stream ID
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of discarded bytes, or None if the chunk stream was not in the buffer
+    pub fn abort_packet(&mut self, channel_id: u32) -> Option<usize> {
+        let packet_wrapper = self.packets.get_mut(&channel_id)?;
+
+        let discarded = if packet_wrapper.handled {
+            0
+        } else {
+            packet_wrapper.bytes
+        };
+
+        // Keep the header and the clock, since the next chunks may be relative to them
+        packet_wrapper.reset();
+
+        Some(discarded)
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_packet_buffer_eviction() {
+        let mut buffer = RtmpInputPacketBuffer::new(3);
+
+        for channel_id in 2..7 {
+            let (packet_wrapper, dropped) = buffer.get_packet(channel_id);
+
+            packet_wrapper.bytes = 1;
+
+            assert_eq!(
+                dropped,
+                if channel_id < 5 {
+                    None
+                } else {
+                    Some(channel_id - 3)
+                }
+            );
+        }
+
+        // Handled packets are dropped first
+
+        buffer.get_packet(4);
+        buffer.get_packet(5).0.handled = true;
+
+        assert_eq!(buffer.get_packet(7).1, Some(5));
+
+        // Otherwise, the least recently used one
+
+        assert_eq!(buffer.get_packet(8).1, Some(6));
+
+        assert_eq!(buffer.abort_packet(4), Some(1));
+        assert_eq!(buffer.abort_packet(4), Some(0));
+        assert_eq!(buffer.abort_packet(9), None);
+    }
+}
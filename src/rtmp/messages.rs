@@ -9,8 +9,10 @@ use crate::amf::AMF0Value;
 
 use super::{
     RtmpCommand, RtmpData, RtmpPacket, RTMP_CHANNEL_AUDIO, RTMP_CHANNEL_DATA, RTMP_CHANNEL_INVOKE,
-    RTMP_CHANNEL_PROTOCOL, RTMP_CHANNEL_VIDEO, RTMP_CHUNK_TYPE_0, RTMP_TYPE_AUDIO, RTMP_TYPE_DATA,
-    RTMP_TYPE_EVENT, RTMP_TYPE_INVOKE, RTMP_TYPE_VIDEO,
+    RTMP_CHANNEL_PROTOCOL, RTMP_CHANNEL_VIDEO, RTMP_CHUNK_TYPE_0, RTMP_EVENT_PING_REQUEST,
+    RTMP_EVENT_PING_RESPONSE, RTMP_OBJECT_ENCODING_AMF0, RTMP_OBJECT_ENCODING_AMF3,
+    RTMP_TYPE_AUDIO, RTMP_TYPE_DATA, RTMP_TYPE_EVENT, RTMP_TYPE_FLEX_MESSAGE, RTMP_TYPE_INVOKE,
+    RTMP_TYPE_VIDEO,
 };
 
 /// Makes RTMP ACK message
@@ -90,7 +92,7 @@ pub fn rtmp_make_ping_request(connect_time: i64, out_chunk_size: usize) -> Vec<u
 
     packet.payload = vec![
         0,
-        6,
+        RTMP_EVENT_PING_REQUEST as u8,
         ((current_timestamp >> 24) as u8),
         ((current_timestamp >> 16) as u8),
         ((current_timestamp >> 8) as u8),
@@ -102,19 +104,50 @@ pub fn rtmp_make_ping_request(connect_time: i64, out_chunk_size: usize) -> Vec<u
     packet.create_chunks(out_chunk_size)
 }
 
+/// Makes RTMP ping response message
+///
+/// # Arguments
+///
+/// * `timestamp` - The timestamp received in the ping request
+/// * `out_chunk_size` - The chunk size
+pub fn rtmp_make_ping_response(timestamp: u32, out_chunk_size: usize) -> Vec<u8> {
+    let mut packet = RtmpPacket::new_blank();
+
+    packet.header.format = RTMP_CHUNK_TYPE_0;
+    packet.header.channel_id = RTMP_CHANNEL_PROTOCOL;
+    packet.header.packet_type = RTMP_TYPE_EVENT;
+
+    packet.payload = vec![0, RTMP_EVENT_PING_RESPONSE as u8, 0, 0, 0, 0];
+
+    BigEndian::write_u32(&mut packet.payload[2..6], timestamp);
+
+    packet.header.length = packet.payload.len();
+
+    packet.create_chunks(out_chunk_size)
+}
+
 /// Makes RTMP invoke command message
+/// If the client negotiated object encoding AMF3, the command is sent as a FLEX message
 pub fn rtmp_make_invoke_message(
     cmd: &RtmpCommand,
     stream_id: u32,
+    object_encoding: u32,
     out_chunk_size: usize,
 ) -> Vec<u8> {
     let mut packet = RtmpPacket::new_blank();
 
     packet.header.format = RTMP_CHUNK_TYPE_0;
     packet.header.channel_id = RTMP_CHANNEL_INVOKE;
-    packet.header.packet_type = RTMP_TYPE_INVOKE;
     packet.header.stream_id = stream_id;
-    packet.payload = cmd.encode();
+
+    if object_encoding == RTMP_OBJECT_ENCODING_AMF3 {
+        packet.header.packet_type = RTMP_TYPE_FLEX_MESSAGE;
+        packet.payload = cmd.encode_amf3();
+    } else {
+        packet.header.packet_type = RTMP_TYPE_INVOKE;
+        packet.payload = cmd.encode();
+    }
+
     packet.header.length = packet.payload.len();
 
     packet.create_chunks(out_chunk_size)
@@ -140,6 +173,7 @@ pub fn rtmp_make_status_message(
     level: &str,
     code: &str,
     description: Option<&str>,
+    object_encoding: u32,
     out_chunk_size: usize,
 ) -> Vec<u8> {
     let mut cmd = RtmpCommand::new("onStatus".to_string());
@@ -173,7 +207,126 @@ pub fn rtmp_make_status_message(
 
     cmd.set_argument("info".to_string(), AMF0Value::Object { properties: info });
 
-    rtmp_make_invoke_message(&cmd, stream_id, out_chunk_size)
+    rtmp_make_invoke_message(&cmd, stream_id, object_encoding, out_chunk_size)
+}
+
+/// Makes RTMP status message (onStatus) redirecting the client to another URL
+/// The info object follows the connection rejection format (`ex.code` = 302, `ex.redirect`)
+pub fn rtmp_make_redirect_status_message(
+    stream_id: u32,
+    redirect_url: &str,
+    object_encoding: u32,
+    out_chunk_size: usize,
+) -> Vec<u8> {
+    let mut cmd = RtmpCommand::new("onStatus".to_string());
+
+    cmd.set_argument("transId".to_string(), AMF0Value::Number { value: 0.0 });
+    cmd.set_argument("cmdObj".to_string(), AMF0Value::Null);
+
+    let mut ex: HashMap<String, AMF0Value> = HashMap::new();
+
+    ex.insert("code".to_string(), AMF0Value::Number { value: 302.0 });
+    ex.insert(
+        "redirect".to_string(),
+        AMF0Value::String {
+            value: redirect_url.to_string(),
+        },
+    );
+
+    let mut info: HashMap<String, AMF0Value> = HashMap::new();
+
+    info.insert(
+        "level".to_string(),
+        AMF0Value::String {
+            value: "error".to_string(),
+        },
+    );
+    info.insert(
+        "code".to_string(),
+        AMF0Value::String {
+            value: "NetConnection.Connect.Rejected".to_string(),
+        },
+    );
+    info.insert(
+        "description".to_string(),
+        AMF0Value::String {
+            value: format!("Redirected to {}", redirect_url),
+        },
+    );
+    info.insert("ex".to_string(), AMF0Value::Object { properties: ex });
+
+    cmd.set_argument("info".to_string(), AMF0Value::Object { properties: info });
+
+    rtmp_make_invoke_message(&cmd, stream_id, object_encoding, out_chunk_size)
+}
+
+/// Makes RTMP play status message (onPlayStatus)
+pub fn rtmp_make_play_status_message(
+    stream_id: u32,
+    code: &str,
+    description: Option<&str>,
+    out_chunk_size: usize,
+) -> Vec<u8> {
+    let mut data = RtmpData::new("onPlayStatus".to_string());
+
+    let mut info: HashMap<String, AMF0Value> = HashMap::new();
+
+    info.insert(
+        "level".to_string(),
+        AMF0Value::String {
+            value: "status".to_string(),
+        },
+    );
+    info.insert(
+        "code".to_string(),
+        AMF0Value::String {
+            value: code.to_string(),
+        },
+    );
+
+    if let Some(d) = description {
+        info.insert(
+            "description".to_string(),
+            AMF0Value::String {
+                value: d.to_string(),
+            },
+        );
+    }
+
+    data.set_argument("info".to_string(), AMF0Value::Object { properties: info });
+
+    rtmp_make_data_message(&data, stream_id, out_chunk_size)
+}
+
+/// Makes RTMP statistics message (onStatistics), sent to the publisher.
+/// The bit rate is in bits per second.
+pub fn rtmp_make_statistics_message(
+    stream_id: u32,
+    viewers: usize,
+    bit_rate: u64,
+    out_chunk_size: usize,
+) -> Vec<u8> {
+    let mut data = RtmpData::new("onStatistics".to_string());
+
+    let mut info: HashMap<String, AMF0Value> = HashMap::new();
+
+    info.insert(
+        "viewers".to_string(),
+        AMF0Value::Number {
+            value: viewers as f64,
+        },
+    );
+
+    info.insert(
+        "bitrate".to_string(),
+        AMF0Value::Number {
+            value: bit_rate as f64,
+        },
+    );
+
+    data.set_argument("info".to_string(), AMF0Value::Object { properties: info });
+
+    rtmp_make_data_message(&data, stream_id, out_chunk_size)
 }
 
 /// Makes RTMP sample access message
@@ -256,13 +409,16 @@ pub fn rtmp_make_connect_response(
 
     cmd.set_argument("info".to_string(), AMF0Value::Object { properties: info });
 
-    rtmp_make_invoke_message(&cmd, 0, out_chunk_size)
+    // The response to connect is always AMF0, since the client does not know yet
+    // if the server supports the requested object encoding
+    rtmp_make_invoke_message(&cmd, 0, RTMP_OBJECT_ENCODING_AMF0, out_chunk_size)
 }
 
 /// Makes message to respond to a connect message
 pub fn rtmp_make_create_stream_response(
     trans_id: i64,
     stream_index: u32,
+    object_encoding: u32,
     out_chunk_size: usize,
 ) -> Vec<u8> {
     let mut cmd = RtmpCommand::new("_result".to_string());
@@ -283,7 +439,7 @@ pub fn rtmp_make_create_stream_response(
         },
     );
 
-    rtmp_make_invoke_message(&cmd, 0, out_chunk_size)
+    rtmp_make_invoke_message(&cmd, 0, object_encoding, out_chunk_size)
 }
 
 /// Creates metadata message (used to send stream metadata to clients)
@@ -361,6 +517,64 @@ pub fn rtmp_make_video_codec_header_message(
     packet.create_chunks(out_chunk_size)
 }
 
+/// Builds a cue point data message (onCuePoint) to send to players
+///
+/// # Arguments
+///
+/// * `name` - Name of the cue point
+/// * `time` - Time of the cue point in the stream (milliseconds)
+/// * `parameters` - Parameters of the cue point (eg: ad metadata)
+#[cfg(any(feature = "control", feature = "redis"))]
+pub fn rtmp_build_cue_point(
+    name: &str,
+    time: i64,
+    parameters: &HashMap<String, String>,
+) -> Vec<u8> {
+    let mut cue_point_props: HashMap<String, AMF0Value> = HashMap::new();
+
+    cue_point_props.insert(
+        "name".to_string(),
+        AMF0Value::String {
+            value: name.to_string(),
+        },
+    );
+
+    cue_point_props.insert(
+        "type".to_string(),
+        AMF0Value::String {
+            value: "event".to_string(),
+        },
+    );
+
+    cue_point_props.insert(
+        "time".to_string(),
+        AMF0Value::Number {
+            value: (time as f64) / 1000.0,
+        },
+    );
+
+    cue_point_props.insert(
+        "parameters".to_string(),
+        AMF0Value::Object {
+            properties: parameters
+                .iter()
+                .map(|(k, v)| (k.clone(), AMF0Value::String { value: v.clone() }))
+                .collect(),
+        },
+    );
+
+    let mut res = RtmpData::new("onCuePoint".to_string());
+
+    res.set_argument(
+        "cuePoint".to_string(),
+        AMF0Value::Object {
+            properties: cue_point_props,
+        },
+    );
+
+    res.encode()
+}
+
 /// Build RTMP metadata to be stored in order to send to players
 pub fn rtmp_build_metadata(data: &RtmpData) -> Vec<u8> {
     let mut res = RtmpData::new("onMetaData".to_string());
@@ -0,0 +1,174 @@
+// Stream metadata extraction
+
+use serde::{Deserialize, Serialize};
+
+use crate::amf::AMF0Value;
+
+use super::RtmpData;
+
+/// Metadata of a published stream, extracted from onMetaData
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamMetadata {
+    /// Video width (pixels)
+    pub width: Option<u32>,
+
+    /// Video height (pixels)
+    pub height: Option<u32>,
+
+    /// Video frame rate (frames per second)
+    pub framerate: Option<f64>,
+
+    /// Video bitrate (kbps)
+    pub video_bitrate: Option<f64>,
+
+    /// Audio bitrate (kbps)
+    pub audio_bitrate: Option<f64>,
+
+    /// Audio sample rate (Hz)
+    pub audio_sample_rate: Option<u32>,
+
+    /// Number of audio channels
+    pub audio_channels: Option<u32>,
+
+    /// Encoder name
+    pub encoder: Option<String>,
+}
+
+/// Reads a positive number property from a metadata object
+fn get_metadata_number(data_obj: &AMF0Value, property_name: &str) -> Option<f64> {
+    match data_obj.get_object_property(property_name) {
+        Some(AMF0Value::Number { value }) => {
+            if value.is_finite() && *value > 0.0 {
+                Some(*value)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Reads a non-empty string property from a metadata object
+/// Control characters are removed, since the value is provided by the publisher
+fn get_metadata_string(data_obj: &AMF0Value, property_name: &str) -> Option<String> {
+    match data_obj.get_object_property(property_name) {
+        Some(v) => {
+            let s: String = v.get_string().chars().filter(|c| !c.is_control()).collect();
+
+            if s.is_empty() {
+                None
+            } else {
+                Some(s)
+            }
+        }
+        None => None,
+    }
+}
+
+impl StreamMetadata {
+    /// Extracts the stream metadata from a data message
+    /// (@setDataFrame or onMetaData)
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The RTMP data message
+    ///
+    /// # Return value
+    ///
+    /// Returns the extracted metadata. Missing or invalid fields are set to None.
+    pub fn from_data(data: &RtmpData) -> StreamMetadata {
+        let data_obj = match data.get_argument("dataObj") {
+            Some(o) => o,
+            None => {
+                return StreamMetadata::default();
+            }
+        };
+
+        let audio_channels = match get_metadata_number(data_obj, "audiochannels") {
+            Some(c) => Some(c as u32),
+            None => data_obj
+                .get_object_property("stereo")
+                .map(|s| if s.get_bool() { 2 } else { 1 }),
+        };
+
+        StreamMetadata {
+            width: get_metadata_number(data_obj, "width").map(|v| v as u32),
+            height: get_metadata_number(data_obj, "height").map(|v| v as u32),
+            framerate: get_metadata_number(data_obj, "framerate")
+                .or_else(|| get_metadata_number(data_obj, "fps")),
+            video_bitrate: get_metadata_number(data_obj, "videodatarate"),
+            audio_bitrate: get_metadata_number(data_obj, "audiodatarate"),
+            audio_sample_rate: get_metadata_number(data_obj, "audiosamplerate").map(|v| v as u32),
+            audio_channels,
+            encoder: get_metadata_string(data_obj, "encoder"),
+        }
+    }
+
+    /// Gets a string representation of the metadata for logging
+    pub fn to_debug_string(&self) -> String {
+        format!(
+            "Resolution: {}x{} | Framerate: {} | Video bitrate: {} | Audio bitrate: {} | Audio sample rate: {} | Encoder: {}",
+            self.width.map(|v| v.to_string()).unwrap_or_default(),
+            self.height.map(|v| v.to_string()).unwrap_or_default(),
+            self.framerate.map(|v| v.to_string()).unwrap_or_default(),
+            self.video_bitrate.map(|v| v.to_string()).unwrap_or_default(),
+            self.audio_bitrate.map(|v| v.to_string()).unwrap_or_default(),
+            self.audio_sample_rate
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            self.encoder.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_stream_metadata_from_data() {
+        let mut items: HashMap<String, AMF0Value> = HashMap::new();
+
+        items.insert("width".to_string(), AMF0Value::Number { value: 1920.0 });
+        items.insert("height".to_string(), AMF0Value::Number { value: 1080.0 });
+        items.insert("framerate".to_string(), AMF0Value::Number { value: 30.0 });
+        items.insert(
+            "videodatarate".to_string(),
+            AMF0Value::Number { value: 2500.0 },
+        );
+        items.insert(
+            "audiosamplerate".to_string(),
+            AMF0Value::Number { value: 44100.0 },
+        );
+        items.insert("stereo".to_string(), AMF0Value::Bool { value: true });
+        items.insert(
+            "encoder".to_string(),
+            AMF0Value::String {
+                value: "obs-output module".to_string(),
+            },
+        );
+        items.insert("audiodatarate".to_string(), AMF0Value::Null);
+
+        let mut data = RtmpData::new("@setDataFrame".to_string());
+        data.set_argument("dataObj".to_string(), AMF0Value::Array { items });
+
+        let metadata = StreamMetadata::from_data(&data);
+
+        assert_eq!(metadata.width, Some(1920));
+        assert_eq!(metadata.height, Some(1080));
+        assert_eq!(metadata.framerate, Some(30.0));
+        assert_eq!(metadata.video_bitrate, Some(2500.0));
+        assert_eq!(metadata.audio_bitrate, None);
+        assert_eq!(metadata.audio_sample_rate, Some(44100));
+        assert_eq!(metadata.audio_channels, Some(2));
+        assert_eq!(metadata.encoder, Some("obs-output module".to_string()));
+
+        let empty = StreamMetadata::from_data(&RtmpData::new("@setDataFrame".to_string()));
+
+        assert_eq!(empty, StreamMetadata::default());
+    }
+}
@@ -1,15 +1,29 @@
 // RTMP protocol utilities
+// This module does not perform any IO, so it can be reused with any runtime.
+// The sessions adapt it to the tokio streams.
 
+mod chunk_decode;
+mod codec;
 mod command;
 mod constants;
 mod data;
 mod handshake;
+mod in_packets;
 mod messages;
+mod metadata;
 mod packet;
+mod packet_wrapper;
+mod publish_type;
 
+pub use chunk_decode::*;
+pub use codec::*;
 pub use command::*;
 pub use constants::*;
 pub use data::*;
 pub use handshake::*;
+pub use in_packets::*;
 pub use messages::*;
+pub use metadata::*;
 pub use packet::*;
+pub use packet_wrapper::*;
+pub use publish_type::*;
@@ -174,14 +174,16 @@ impl RtmpPacket {
             header_size += 4;
         }
 
-        let mut n = header_size + payload_size + (payload_size / out_chunk_size);
+        let mut n = header_size
+            + payload_size
+            + (payload_size / out_chunk_size) * chunk_basic_header_3.len();
 
         if use_extended_timestamp {
             n += (payload_size / out_chunk_size) * 4
         }
 
-        if payload_size > 0 && payload_size % out_chunk_size == 0 {
-            n -= 1;
+        if payload_size > 0 && payload_size.is_multiple_of(out_chunk_size) {
+            n -= chunk_basic_header_3.len();
 
             if use_extended_timestamp {
                 n -= 4;
@@ -1,4 +1,6 @@
-use crate::rtmp::RtmpPacket;
+// Wrapper of the input packets
+
+use super::RtmpPacket;
 
 /// Wrapper for a packet
 /// Contains a packet alongside some metadata related to its status
@@ -10,14 +12,18 @@ pub struct RtmpPacketWrapper {
     /// Clock value (Used for extended timestamp)
     pub clock: i64,
 
+    /// Extended timestamp of the last message header, if it used one.
+    /// The type 3 chunks of the chunk stream carry it too.
+    pub extended_timestamp: Option<u32>,
+
     /// Current packet size
     pub bytes: usize,
 
     /// True if the packet was handled
     pub handled: bool,
 
-    // True if used
-    pub used: bool,
+    /// Value of the use counter of the buffer when last used
+    pub last_use: u64,
 }
 
 impl RtmpPacketWrapper {
@@ -26,9 +32,10 @@ impl RtmpPacketWrapper {
         RtmpPacketWrapper {
             packet: RtmpPacket::new_blank(),
             clock: 0,
+            extended_timestamp: None,
             bytes: 0,
             handled: false,
-            used: false,
+            last_use: 0,
         }
     }
 
@@ -42,9 +49,10 @@ impl RtmpPacketWrapper {
     /// Fully resets the packet wrapper
     pub fn reset_full(&mut self) {
         self.clock = 0;
+        self.extended_timestamp = None;
         self.bytes = 0;
         self.handled = false;
-        self.used = false;
+        self.last_use = 0;
 
         self.packet.reset();
     }
@@ -0,0 +1,68 @@
+// Publish type (argument of the publish command)
+
+/// Type of publication requested by the client
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RtmpPublishType {
+    /// Live stream, not recorded
+    Live,
+
+    /// Live stream, recorded
+    Record,
+
+    /// Live stream, recorded, appending to an existing recording
+    Append,
+}
+
+impl RtmpPublishType {
+    /// Parses the publish type.
+    /// Unknown or missing types are considered live.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The type argument of the publish command
+    pub fn parse(s: &str) -> RtmpPublishType {
+        match s.to_lowercase().as_str() {
+            "record" => RtmpPublishType::Record,
+            "append" | "appendwithgap" => RtmpPublishType::Append,
+            _ => RtmpPublishType::Live,
+        }
+    }
+
+    /// Checks if the client requested the stream to be recorded
+    pub fn is_recording(&self) -> bool {
+        matches!(self, RtmpPublishType::Record | RtmpPublishType::Append)
+    }
+
+    /// Gets the publish type as string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RtmpPublishType::Live => "live",
+            RtmpPublishType::Record => "record",
+            RtmpPublishType::Append => "append",
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_type_parse() {
+        assert_eq!(RtmpPublishType::parse("live"), RtmpPublishType::Live);
+        assert_eq!(RtmpPublishType::parse(""), RtmpPublishType::Live);
+        assert_eq!(RtmpPublishType::parse("other"), RtmpPublishType::Live);
+        assert_eq!(RtmpPublishType::parse("Record"), RtmpPublishType::Record);
+        assert_eq!(RtmpPublishType::parse("append"), RtmpPublishType::Append);
+        assert_eq!(
+            RtmpPublishType::parse("appendWithGap"),
+            RtmpPublishType::Append
+        );
+
+        assert!(!RtmpPublishType::Live.is_recording());
+        assert!(RtmpPublishType::Record.is_recording());
+        assert!(RtmpPublishType::Append.is_recording());
+    }
+}
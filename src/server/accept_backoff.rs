@@ -0,0 +1,247 @@
+// Backoff of the accept loops on errors
+
+use std::{
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{log::Logger, log_debug, log_error};
+
+/// Initial delay to accept again after a resource exhaustion error
+const ACCEPT_BACKOFF_INITIAL: Duration = Duration::from_millis(5);
+
+/// Max delay to accept again after a resource exhaustion error
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+/// OS error codes for the exhaustion of file descriptors (EMFILE, ENFILE)
+#[cfg(unix)]
+const FD_EXHAUSTION_ERROR_CODES: &[i32] = &[24, 23];
+
+/// OS error codes for the exhaustion of file descriptors (WSAEMFILE)
+#[cfg(windows)]
+const FD_EXHAUSTION_ERROR_CODES: &[i32] = &[10024];
+
+/// OS error codes for the exhaustion of memory or buffers (ENOMEM, ENOBUFS)
+#[cfg(target_os = "linux")]
+const MEMORY_EXHAUSTION_ERROR_CODES: &[i32] = &[12, 105];
+
+/// OS error codes for the exhaustion of memory or buffers (ENOMEM, ENOBUFS)
+#[cfg(all(unix, not(target_os = "linux")))]
+const MEMORY_EXHAUSTION_ERROR_CODES: &[i32] = &[12, 55];
+
+/// OS error codes for the exhaustion of memory or buffers (WSAENOBUFS)
+#[cfg(windows)]
+const MEMORY_EXHAUSTION_ERROR_CODES: &[i32] = &[10055];
+
+/// Number of times the file descriptors were exhausted, by all the accept loops
+static FD_EXHAUSTION_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Kind of error returned by accept()
+#[derive(Debug, PartialEq)]
+pub enum AcceptErrorKind {
+    /// Error of the connection being accepted (eg: aborted by the client).
+    /// The loop can accept again right away.
+    Connection,
+
+    /// The process ran out of file descriptors
+    FileDescriptorsExhausted,
+
+    /// The system ran out of memory or buffers
+    MemoryExhausted,
+
+    /// Error of the listener. The loop cannot continue.
+    Fatal,
+}
+
+impl AcceptErrorKind {
+    /// Classifies an error returned by accept()
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error
+    pub fn classify(error: &io::Error) -> AcceptErrorKind {
+        if let Some(code) = error.raw_os_error() {
+            if FD_EXHAUSTION_ERROR_CODES.contains(&code) {
+                return AcceptErrorKind::FileDescriptorsExhausted;
+            }
+
+            if MEMORY_EXHAUSTION_ERROR_CODES.contains(&code) {
+                return AcceptErrorKind::MemoryExhausted;
+            }
+        }
+
+        match error.kind() {
+            io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut => AcceptErrorKind::Connection,
+            io::ErrorKind::OutOfMemory => AcceptErrorKind::MemoryExhausted,
+            _ => AcceptErrorKind::Fatal,
+        }
+    }
+}
+
+/// Backoff of an accept loop, so transient errors
+/// do not end the loop, nor make it spin while the resources are exhausted
+pub struct AcceptBackoff {
+    /// Delay to wait after the next resource exhaustion error
+    delay: Duration,
+}
+
+impl AcceptBackoff {
+    /// Creates new AcceptBackoff
+    pub fn new() -> AcceptBackoff {
+        AcceptBackoff {
+            delay: ACCEPT_BACKOFF_INITIAL,
+        }
+    }
+
+    /// Resets the backoff, after a connection is accepted
+    pub fn reset(&mut self) {
+        self.delay = ACCEPT_BACKOFF_INITIAL;
+    }
+
+    /// Gets the delay to wait for an error, increasing the backoff
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of error
+    ///
+    /// # Return value
+    ///
+    /// Returns the delay, or None if the loop cannot continue
+    fn next_delay(&mut self, kind: &AcceptErrorKind) -> Option<Duration> {
+        match kind {
+            AcceptErrorKind::Connection => Some(Duration::ZERO),
+            AcceptErrorKind::FileDescriptorsExhausted | AcceptErrorKind::MemoryExhausted => {
+                let delay = self.delay;
+                self.delay = (self.delay * 2).min(ACCEPT_BACKOFF_MAX);
+                Some(delay)
+            }
+            AcceptErrorKind::Fatal => None,
+        }
+    }
+
+    /// Handles an error returned by accept(),
+    /// logging it and waiting before accepting again
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `error` - The error
+    ///
+    /// # Return value
+    ///
+    /// Returns true if the loop can continue, false if the error is fatal
+    pub async fn handle_error(&mut self, logger: &Logger, error: &io::Error) -> bool {
+        let kind = AcceptErrorKind::classify(error);
+
+        let delay = match self.next_delay(&kind) {
+            Some(d) => d,
+            None => {
+                log_error!(logger, format!("Could not accept connection: {}", error));
+                return false;
+            }
+        };
+
+        match kind {
+            AcceptErrorKind::FileDescriptorsExhausted => {
+                let fd_exhaustion_count = FD_EXHAUSTION_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+
+                log_error!(
+                    logger,
+                    format!(
+                        "Could not accept connection: File descriptors exhausted ({}). Increase the limit of open files (ulimit -n) or reduce the connection limits. Retrying in {} ms. Times exhausted: {}",
+                        error,
+                        delay.as_millis(),
+                        fd_exhaustion_count
+                    )
+                );
+            }
+            AcceptErrorKind::MemoryExhausted => {
+                log_error!(
+                    logger,
+                    format!(
+                        "Could not accept connection: {}. Retrying in {} ms",
+                        error,
+                        delay.as_millis()
+                    )
+                );
+            }
+            _ => {
+                log_debug!(logger, format!("Could not accept connection: {}", error));
+            }
+        }
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        true
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_error_classify() {
+        assert_eq!(
+            AcceptErrorKind::classify(&io::Error::from_raw_os_error(FD_EXHAUSTION_ERROR_CODES[0])),
+            AcceptErrorKind::FileDescriptorsExhausted
+        );
+        assert_eq!(
+            AcceptErrorKind::classify(&io::Error::from_raw_os_error(
+                MEMORY_EXHAUSTION_ERROR_CODES[0]
+            )),
+            AcceptErrorKind::MemoryExhausted
+        );
+        assert_eq!(
+            AcceptErrorKind::classify(&io::Error::from(io::ErrorKind::ConnectionAborted)),
+            AcceptErrorKind::Connection
+        );
+        assert_eq!(
+            AcceptErrorKind::classify(&io::Error::from(io::ErrorKind::InvalidInput)),
+            AcceptErrorKind::Fatal
+        );
+    }
+
+    #[test]
+    fn test_accept_backoff() {
+        let mut backoff = AcceptBackoff::new();
+
+        assert_eq!(
+            backoff.next_delay(&AcceptErrorKind::Connection),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(backoff.next_delay(&AcceptErrorKind::Fatal), None);
+
+        let mut last_delay = Duration::ZERO;
+
+        for _ in 0..20 {
+            let delay = backoff
+                .next_delay(&AcceptErrorKind::FileDescriptorsExhausted)
+                .unwrap();
+
+            assert!(delay >= last_delay);
+            assert!(delay <= ACCEPT_BACKOFF_MAX);
+
+            last_delay = delay;
+        }
+
+        assert_eq!(last_delay, ACCEPT_BACKOFF_MAX);
+
+        backoff.reset();
+
+        assert_eq!(
+            backoff.next_delay(&AcceptErrorKind::MemoryExhausted),
+            Some(ACCEPT_BACKOFF_INITIAL)
+        );
+    }
+}
@@ -1,12 +1,28 @@
 /// RTMP server configuration
+use std::collections::HashMap;
+
 use crate::{
-    callback::CallbackConfiguration,
+    acme::AcmeConfiguration,
+    auth::{StreamKeyStoreConfiguration, TokenAuthConfiguration},
+    callback::{is_valid_callback_url, CallbackConfiguration},
+    chaos::ChaosConfiguration,
+    geoip::GeoIpConfiguration,
     log::Logger,
     log_error,
-    rtmp::{RTMP_CHUNK_SIZE_DEFAULT, RTMP_MAX_CHUNK_SIZE, RTMP_MIN_CHUNK_SIZE},
-    utils::{get_env_bool, get_env_string, get_env_u32, IdValidationConfig, IpRangeConfig},
+    record::{RecordingConfiguration, SnapshotConfiguration},
+    rtmp::{
+        KNOWN_AUDIO_CODEC_NAMES, KNOWN_VIDEO_CODEC_NAMES, RTMP_CHUNK_SIZE_DEFAULT,
+        RTMP_MAX_CHUNK_SIZE, RTMP_MIN_CHUNK_SIZE,
+    },
+    trace::TraceConfiguration,
+    utils::{
+        get_env_bool, get_env_secret, get_env_string, get_env_string_list, get_env_u32,
+        validate_id_string, ChannelAliasConfig, IdValidationConfig, IpRangeConfig,
+    },
 };
 
+use super::{ServerIdentity, StatePersistenceConfiguration};
+
 const RTMP_PORT_DEFAULT: u32 = 1935;
 const TLS_PORT_DEFAULT: u32 = 443;
 
@@ -14,26 +30,245 @@ const MAX_PORT: u32 = 65535;
 
 const GOP_CACHE_SIZE_MB_DEFAULT: u32 = 256;
 const MSG_BUFFER_SIZE_DEFAULT: u32 = 8;
+const RTMP_ACK_TIMEOUT_SECONDS_DEFAULT: u32 = 10;
+const RTMP_WRITE_TIMEOUT_SECONDS_DEFAULT: u32 = 30;
+const PLAYER_PACKET_BUFFER_SIZE_DEFAULT: u32 = 256;
+const MAX_CHUNK_STREAMS_DEFAULT: u32 = 16;
+const DATA_PASSTHROUGH_DEFAULT: &str = "onTextData,onCuePoint,onFI";
+const CHANNEL_EVENT_HISTORY_SIZE_DEFAULT: u32 = 32;
+const UPGRADE_DRAIN_SECONDS_DEFAULT: u32 = 600;
+const FD_RESERVED_DEFAULT: u32 = 128;
+const PLAYER_MAX_BITRATE_PERCENT_MIN: u32 = 100;
+const PLAYER_MAX_BITRATE_PERCENT_MAX: u32 = 1000;
 
 const SSL_CHECK_RELOAD_SECONDS_DEFAULT: u32 = 60;
 
-/// RTMP server configuration
+const TCP_KEEPALIVE_TIME_DEFAULT: u32 = 60;
+const TCP_KEEPALIVE_INTERVAL_DEFAULT: u32 = 10;
+const TCP_KEEPALIVE_RETRIES_DEFAULT: u32 = 5;
+
+/// Name of the tenant to accept any tenant name
+const TENANT_WILDCARD: &str = "*";
+
+/// Configuration overrides for a tenant
 #[derive(Clone)]
-pub struct TlsServerConfiguration {
-    /// Port
-    pub port: u32,
+pub struct TenantConfiguration {
+    /// Callback configuration
+    pub callback: CallbackConfiguration,
+
+    /// Token authentication configuration
+    pub auth_token: TokenAuthConfiguration,
+
+    /// Max number of concurrent players per channel (0 for unlimited)
+    pub max_players_per_channel: u32,
+}
+
+impl TenantConfiguration {
+    /// Loads the configuration of a tenant from environment variables,
+    /// using the server-wide configuration for the options not overridden
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `name` - Name of the tenant
+    /// * `callback` - Server-wide callback configuration
+    /// * `auth_token` - Server-wide token authentication configuration
+    /// * `max_players_per_channel` - Server-wide max number of players per channel
+    pub fn load_from_env(
+        logger: &Logger,
+        name: &str,
+        callback: &CallbackConfiguration,
+        auth_token: &TokenAuthConfiguration,
+        max_players_per_channel: u32,
+    ) -> Result<TenantConfiguration, ()> {
+        let prefix = format!("TENANT_{}_", name.to_uppercase().replace('-', "_"));
+
+        let mut tenant_callback = callback.clone();
+
+        let callback_url = get_env_string(&format!("{}CALLBACK_URL", prefix), "");
+
+        if !is_valid_callback_url(&callback_url) {
+            log_error!(
+                logger,
+                format!(
+                    "{}CALLBACK_URL has an invalid value: {}",
+                    prefix, callback_url
+                )
+            );
+            return Err(());
+        }
+
+        if !callback_url.is_empty() && !cfg!(feature = "callback") {
+            log_error!(
+                logger,
+                format!(
+                    "{}CALLBACK_URL is set, but the server was built without the callback feature",
+                    prefix
+                )
+            );
+            return Err(());
+        }
+
+        if !callback_url.is_empty() {
+            tenant_callback.callback_url = callback_url;
+        }
+
+        let jwt_secret = match get_env_secret(&format!("{}JWT_SECRET", prefix), "") {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!(logger, e);
+                return Err(());
+            }
+        };
+
+        if !jwt_secret.is_empty() {
+            tenant_callback.jwt_secret = jwt_secret;
+        }
+
+        let tenant_auth_token = TokenAuthConfiguration::load_from_env(logger, &prefix)?;
+
+        let tenant_max_players = get_env_u32(
+            &format!("{}MAX_PLAYERS_PER_CHANNEL", prefix),
+            max_players_per_channel,
+        );
+
+        Ok(TenantConfiguration {
+            callback: tenant_callback,
+            auth_token: if tenant_auth_token.is_enabled() {
+                tenant_auth_token
+            } else {
+                auth_token.clone()
+            },
+            max_players_per_channel: tenant_max_players,
+        })
+    }
+}
+
+/// Registry of tenants
+#[derive(Clone)]
+pub struct TenantRegistry {
+    /// Configuration of the tenants
+    tenants: HashMap<String, TenantConfiguration>,
+
+    /// True to accept any tenant name (with the server-wide configuration if not registered)
+    allow_any: bool,
+}
+
+impl TenantRegistry {
+    /// Loads the tenant registry from environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `id_validation` - ID validation configuration
+    /// * `callback` - Server-wide callback configuration
+    /// * `auth_token` - Server-wide token authentication configuration
+    /// * `max_players_per_channel` - Server-wide max number of players per channel
+    pub fn load_from_env(
+        logger: &Logger,
+        id_validation: &IdValidationConfig,
+        callback: &CallbackConfiguration,
+        auth_token: &TokenAuthConfiguration,
+        max_players_per_channel: u32,
+    ) -> Result<TenantRegistry, ()> {
+        let mut tenants: HashMap<String, TenantConfiguration> = HashMap::new();
+        let mut allow_any = false;
+
+        for name in get_env_string_list("TENANTS", "") {
+            if name == TENANT_WILDCARD {
+                allow_any = true;
+                continue;
+            }
+
+            if validate_id_string(&name, id_validation).is_err() || name.contains('/') {
+                log_error!(logger, format!("TENANTS has an invalid tenant: {}", name));
+                return Err(());
+            }
+
+            let tenant = TenantConfiguration::load_from_env(
+                logger,
+                &name,
+                callback,
+                auth_token,
+                max_players_per_channel,
+            )?;
+
+            tenants.insert(name, tenant);
+        }
+
+        Ok(TenantRegistry { tenants, allow_any })
+    }
 
-    /// Bind address
-    pub bind_address: String,
+    /// Checks if tenant namespaces are enabled
+    pub fn is_enabled(&self) -> bool {
+        self.allow_any || !self.tenants.is_empty()
+    }
+
+    /// Checks if a tenant is accepted
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - Name of the tenant
+    pub fn is_allowed(&self, tenant: &str) -> bool {
+        self.allow_any || self.tenants.contains_key(tenant)
+    }
+
+    /// Gets the configuration of the tenant of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID, with the format `tenant/app`
+    ///
+    /// # Return value
+    ///
+    /// Returns the configuration, or None if the tenant has no configuration overrides
+    pub fn get_channel_tenant(&self, channel: &str) -> Option<&TenantConfiguration> {
+        let (tenant, _) = channel.split_once('/')?;
+        self.tenants.get(tenant)
+    }
+}
+
+/// TLS certificate configuration
+#[cfg_attr(not(feature = "tls"), allow(dead_code))]
+#[derive(Clone)]
+pub struct TlsCertificateConfiguration {
+    /// Server name (SNI) to use the certificate for.
+    /// Can be a wildcard (*.example.com). Empty for the default certificate.
+    pub server_name: String,
 
     /// Certificate path
     pub certificate: String,
 
     /// Key path
     pub key: String,
+}
+
+/// TLS server configuration
+#[cfg_attr(not(feature = "tls"), allow(dead_code))]
+#[derive(Clone)]
+pub struct TlsServerConfiguration {
+    /// Ports
+    pub ports: Vec<u32>,
+
+    /// Bind addresses
+    pub bind_addresses: Vec<String>,
+
+    /// Certificates
+    pub certificates: Vec<TlsCertificateConfiguration>,
 
     /// Seconds to check for auto-renewal
     pub check_reload_seconds: u32,
+
+    /// Path to the CA certificates to verify client certificates.
+    /// Empty to disable client certificate authentication
+    pub client_ca: String,
+
+    /// True to reject clients without a valid certificate.
+    /// False to make the client certificate optional
+    pub client_cert_required: bool,
+
+    /// ACME configuration (automatic certificates)
+    pub acme: AcmeConfiguration,
 }
 
 impl TlsServerConfiguration {
@@ -43,82 +278,472 @@ impl TlsServerConfiguration {
     ///
     /// * `logger` - The logger
     pub fn load_from_env(logger: &Logger) -> Result<TlsServerConfiguration, ()> {
-        let port = get_env_u32("SSL_PORT", TLS_PORT_DEFAULT);
-
-        if port == 0 || port > MAX_PORT {
-            log_error!(logger, format!("SSL_PORT has an invalid value: {}", port));
-            return Err(());
-        }
+        let ports = load_port_list_from_env(logger, "SSL_PORT", TLS_PORT_DEFAULT)?;
 
-        let bind_address = get_env_string(
+        let bind_addresses = get_env_string_list(
             "SSL_BIND_ADDRESS",
-            &get_env_string("BIND_ADDRESS", "0.0.0.0"),
+            &get_env_string_list("BIND_ADDRESS", "0.0.0.0").join(","),
         );
 
+        let mut certificates: Vec<TlsCertificateConfiguration> = Vec::new();
+
         let certificate = get_env_string("SSL_CERT", "");
         let key = get_env_string("SSL_KEY", "");
 
+        if !certificate.is_empty() && !key.is_empty() {
+            certificates.push(TlsCertificateConfiguration {
+                server_name: "".to_string(),
+                certificate,
+                key,
+            });
+        }
+
+        // Certificates by server name (SERVER_NAME:CERT:KEY;...)
+
+        for entry in get_env_string("SSL_CERTS", "").split(';') {
+            let entry = entry.trim();
+
+            if entry.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = entry.splitn(3, ':').map(|p| p.trim()).collect();
+
+            if parts.len() != 3 || parts.iter().any(|p| p.is_empty()) {
+                log_error!(
+                    logger,
+                    format!(
+                        "SSL_CERTS has an invalid value: {}. Expected format: SERVER_NAME:CERT:KEY",
+                        entry
+                    )
+                );
+                return Err(());
+            }
+
+            certificates.push(TlsCertificateConfiguration {
+                server_name: parts[0].to_string(),
+                certificate: parts[1].to_string(),
+                key: parts[2].to_string(),
+            });
+        }
+
+        // Certificate obtained through ACME
+
+        let acme = AcmeConfiguration::load_from_env(logger)?;
+
+        if acme.is_enabled() {
+            if certificates.iter().any(|c| c.server_name.is_empty()) {
+                for domain in &acme.domains {
+                    certificates.push(TlsCertificateConfiguration {
+                        server_name: domain.clone(),
+                        certificate: acme.get_certificate_path(),
+                        key: acme.get_certificate_key_path(),
+                    });
+                }
+            } else {
+                certificates.insert(
+                    0,
+                    TlsCertificateConfiguration {
+                        server_name: "".to_string(),
+                        certificate: acme.get_certificate_path(),
+                        key: acme.get_certificate_key_path(),
+                    },
+                );
+            }
+        }
+
+        if !certificates.is_empty() && !cfg!(feature = "tls") {
+            log_error!(
+                logger,
+                "A TLS certificate is configured, but the server was built without the tls feature"
+            );
+            return Err(());
+        }
+
         let check_reload_seconds =
             get_env_u32("SSL_CHECK_RELOAD_SECONDS", SSL_CHECK_RELOAD_SECONDS_DEFAULT);
 
+        let client_ca = get_env_string("SSL_CLIENT_CA", "");
+        let client_cert_required = get_env_bool("SSL_CLIENT_CERT_REQUIRED", true);
+
         Ok(TlsServerConfiguration {
-            port,
-            bind_address,
-            certificate,
-            key,
+            ports,
+            bind_addresses,
+            certificates,
             check_reload_seconds,
+            client_ca,
+            client_cert_required,
+            acme,
+        })
+    }
+
+    /// Checks if the TLS config is enabled (at least a cert and key must be present)
+    pub fn is_enabled(&self) -> bool {
+        !self.certificates.is_empty()
+    }
+
+    /// Checks if client certificate authentication is enabled
+    #[cfg(feature = "tls")]
+    pub fn is_client_auth_enabled(&self) -> bool {
+        !self.client_ca.is_empty()
+    }
+
+    /// Gets TLS addresses for listening
+    pub fn get_tcp_listen_addrs(&self) -> Vec<String> {
+        make_listen_addrs(&self.bind_addresses, &self.ports)
+    }
+}
+
+/// TCP socket options for accepted connections
+#[derive(Clone)]
+pub struct TcpSocketConfiguration {
+    /// True to disable Nagle's algorithm (TCP_NODELAY)
+    pub nodelay: bool,
+
+    /// True to enable TCP keepalive (SO_KEEPALIVE)
+    pub keepalive: bool,
+
+    /// Idle seconds before sending keepalive probes
+    pub keepalive_time: u32,
+
+    /// Seconds between keepalive probes
+    pub keepalive_interval: u32,
+
+    /// Number of unanswered probes before closing the connection
+    pub keepalive_retries: u32,
+
+    /// Send buffer size in bytes (0 means the OS default)
+    pub send_buffer_size: usize,
+
+    /// Receive buffer size in bytes (0 means the OS default)
+    pub recv_buffer_size: usize,
+}
+
+impl TcpSocketConfiguration {
+    /// Loads configuration for environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<TcpSocketConfiguration, ()> {
+        let nodelay = get_env_bool("TCP_NODELAY", true);
+        let keepalive = get_env_bool("TCP_KEEPALIVE", false);
+
+        let keepalive_time = get_env_u32("TCP_KEEPALIVE_TIME", TCP_KEEPALIVE_TIME_DEFAULT);
+        let keepalive_interval =
+            get_env_u32("TCP_KEEPALIVE_INTERVAL", TCP_KEEPALIVE_INTERVAL_DEFAULT);
+        let keepalive_retries = get_env_u32("TCP_KEEPALIVE_RETRIES", TCP_KEEPALIVE_RETRIES_DEFAULT);
+
+        if keepalive && (keepalive_time == 0 || keepalive_interval == 0 || keepalive_retries == 0) {
+            log_error!(
+                logger,
+                "TCP_KEEPALIVE_TIME, TCP_KEEPALIVE_INTERVAL and TCP_KEEPALIVE_RETRIES must be greater than 0"
+            );
+            return Err(());
+        }
+
+        let send_buffer_size = get_env_u32("TCP_SEND_BUFFER_SIZE", 0) as usize;
+        let recv_buffer_size = get_env_u32("TCP_RECV_BUFFER_SIZE", 0) as usize;
+
+        Ok(TcpSocketConfiguration {
+            nodelay,
+            keepalive,
+            keepalive_time,
+            keepalive_interval,
+            keepalive_retries,
+            send_buffer_size,
+            recv_buffer_size,
+        })
+    }
+}
+
+/// Default time to cache the verdicts of the IP screening service (seconds)
+const IP_SCREENING_CACHE_SECONDS_DEFAULT: u32 = 300;
+
+/// Default timeout for the requests to the IP screening service (milliseconds)
+const IP_SCREENING_TIMEOUT_MS_DEFAULT: u32 = 1000;
+
+/// Configuration of the IP screening of the incoming connections
+#[cfg_attr(not(feature = "ip-screening"), allow(dead_code))]
+#[derive(Clone)]
+pub struct IpScreeningConfiguration {
+    /// URL of the IP screening service (empty to disable it)
+    pub url: String,
+
+    /// Time to cache the verdicts (seconds)
+    pub cache_seconds: u32,
+
+    /// Timeout for the requests (milliseconds)
+    pub timeout_ms: u32,
+
+    /// True to accept the connections if the service fails
+    pub fail_open: bool,
+}
+
+impl IpScreeningConfiguration {
+    /// Loads configuration for environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<IpScreeningConfiguration, ()> {
+        let url = get_env_string("IP_SCREENING_URL", "");
+        let cache_seconds = get_env_u32(
+            "IP_SCREENING_CACHE_SECONDS",
+            IP_SCREENING_CACHE_SECONDS_DEFAULT,
+        );
+        let timeout_ms = get_env_u32("IP_SCREENING_TIMEOUT_MS", IP_SCREENING_TIMEOUT_MS_DEFAULT);
+        let fail_open = get_env_bool("IP_SCREENING_FAIL_OPEN", true);
+
+        if !url.is_empty() && timeout_ms == 0 {
+            log_error!(logger, "IP_SCREENING_TIMEOUT_MS must be greater than 0");
+            return Err(());
+        }
+
+        if !url.is_empty() && !cfg!(feature = "ip-screening") {
+            log_error!(
+                logger,
+                "IP_SCREENING_URL is set, but the server was built without the ip-screening feature"
+            );
+            return Err(());
+        }
+
+        Ok(IpScreeningConfiguration {
+            url,
+            cache_seconds,
+            timeout_ms,
+            fail_open,
         })
     }
 
-    /// Checks if the TLS config is enabled (cert and key must be present)
+    /// Checks if the IP screening is enabled
     pub fn is_enabled(&self) -> bool {
-        !self.certificate.is_empty() && !self.key.is_empty()
+        !self.url.is_empty()
     }
+}
+
+/// Policy when a session tries to publish to a channel already being published
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PublishTakeoverPolicy {
+    /// Reject the new publisher
+    Reject,
 
-    /// Gets TLS address for listening
-    pub fn get_tcp_listen_addr(&self) -> String {
-        format!("{}:{}", self.bind_address, self.port)
+    /// Kick the existing publisher, if the new one provides the same key
+    KickExisting,
+}
+
+impl PublishTakeoverPolicy {
+    /// Parses the policy
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string (`reject` or `kick_existing`)
+    pub fn parse(s: &str) -> Result<PublishTakeoverPolicy, String> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "reject" => Ok(PublishTakeoverPolicy::Reject),
+            "kick_existing" => Ok(PublishTakeoverPolicy::KickExisting),
+            _ => Err(format!("Invalid publish takeover policy: {}", s)),
+        }
     }
 }
 
 /// RTMP server configuration
 #[derive(Clone)]
 pub struct RtmpServerConfiguration {
-    /// Port
-    pub port: u32,
+    /// Ports
+    pub ports: Vec<u32>,
 
-    /// Bind address
-    pub bind_address: String,
+    /// Bind addresses
+    pub bind_addresses: Vec<String>,
+
+    /// True to accept only IPv6 connections on IPv6 bind addresses.
+    /// False to also accept IPv4 connections (dual-stack)
+    pub ipv6_only: bool,
 
     /// TLS config
     pub tls: TlsServerConfiguration,
 
+    /// TCP socket options
+    pub tcp_socket: TcpSocketConfiguration,
+
+    /// IP screening of the incoming connections
+    pub ip_screening: IpScreeningConfiguration,
+
     /// ID validation configuration
     pub id_validation: IdValidationConfig,
 
+    /// True to take the channel from the stream name if the app is empty on connect
+    pub empty_app_compat: bool,
+
+    /// Channel aliasing rules
+    pub channel_aliases: ChannelAliasConfig,
+
+    /// Recording configuration
+    pub recording: RecordingConfiguration,
+
+    /// Snapshots configuration
+    pub snapshots: SnapshotConfiguration,
+
+    /// Trace configuration
+    pub trace: TraceConfiguration,
+
+    /// Chaos testing configuration
+    pub chaos: ChaosConfiguration,
+
     /// Whitelist of IPs to play
     pub play_whitelist: IpRangeConfig,
 
     /// RTMP chunk size
     pub chunk_size: usize,
 
+    /// Prefixes of flashVer for the clients only supporting the default chunk size
+    pub chunk_size_legacy_clients: Vec<String>,
+
+    /// True to use the chunk size announced by the client, if bigger than chunk_size
+    pub chunk_size_match_client: bool,
+
     /// Size limit in megabytes of packet cache (bytes).
     pub gop_cache_size: usize,
 
+    /// Max number of players receiving the GOP cache at the same time (0 for unlimited)
+    pub gop_replay_max_concurrent: u32,
+
+    /// Max time a player waits to receive the GOP cache (milliseconds)
+    pub gop_replay_queue_timeout_ms: u32,
+
+    /// Max interval between key frames of the published streams
+    /// before sending a warning (milliseconds, 0 to disable)
+    pub keyframe_interval_warning_ms: u32,
+
+    /// True to advise the publishers of the max interval between key frames
+    pub keyframe_interval_advice: bool,
+
+    /// Interval to send the statistics of the channel to the publishers (seconds, 0 to disable)
+    pub publish_statistics_interval_seconds: u32,
+
+    /// Input bit rate cap of the publishers, to notify them the congestion (kbps, 0 to disable)
+    pub publish_bitrate_cap_kbps: u32,
+
+    /// Max delay of the media received from the publishers, to notify them the congestion (milliseconds, 0 to disable)
+    pub publish_max_lag_ms: u32,
+
+    /// Time the congestion must be sustained, and min interval between congestion notifications (seconds)
+    pub publish_congestion_seconds: u32,
+
+    /// True to measure the duration of the playback (duration play argument)
+    /// with the presentation timestamps of the video packets
+    pub play_duration_composition_time: bool,
+
     /// Size of the message buffer for sessions
     pub msg_buffer_size: usize,
 
+    /// Max number of bytes sent to a client without acknowledgement,
+    /// in windows of acknowledgement (0 for unlimited)
+    pub ack_window_limit: u32,
+
+    /// Max time to wait for an acknowledgement of the client (seconds)
+    pub ack_timeout_seconds: u32,
+
+    /// Max time a write to the client can make no progress (seconds, 0 to disable)
+    pub write_timeout_seconds: u32,
+
+    /// Size of the buffer of packets shared by the players of a channel
+    pub player_packet_buffer_size: usize,
+
+    /// Max number of chunk streams to keep partially read packets for, per session
+    pub max_chunk_streams: usize,
+
     /// Max number of concurrent connections per IP address
     pub max_concurrent_connections_per_ip: u32,
 
+    /// Number of file descriptors reserved for other than the client connections,
+    /// rejecting new connections when the open files limit is closer (0 to disable)
+    pub fd_reserved: u32,
+
+    /// Max number of connection attempts per minute per IP address (0 for unlimited)
+    pub max_connections_per_minute_per_ip: u32,
+
     /// List of IP ranges not affected by the max number of concurrent connections limit.
     pub max_concurrent_connections_whitelist: IpRangeConfig,
 
+    /// Max number of concurrent players per channel (0 for unlimited)
+    pub max_players_per_channel: u32,
+
+    /// Min number of packets waiting to be sent to a player
+    /// to consider it lagging (0 to disable the detection)
+    pub player_lag_packets: u32,
+
+    /// Time a player must stay lagging before reporting it (seconds)
+    pub player_lag_seconds: u32,
+
+    /// Min number of packets waiting to be sent to a player
+    /// to send the audio ahead of the queued video (0 to disable)
+    pub player_audio_priority_packets: u32,
+
+    /// Interval to notify the players waiting for the stream to be published (seconds, 0 to disable)
+    pub player_idle_notify_seconds: u32,
+
+    /// Max time a player waits for the stream to be published (seconds, 0 for unlimited)
+    pub player_idle_max_seconds: u32,
+
+    /// Max bit rate of the bytes sent to each player (kilobits per second, 0 for unlimited)
+    pub player_max_kbps: u32,
+
+    /// Max bit rate of the bytes sent to each player,
+    /// relative to the bit rate of the stream (percent, 0 for unlimited)
+    pub player_max_stream_bit_rate_percent: u32,
+
+    /// Seconds to wait for a dropped publisher to resume the stream (0 to disable)
+    pub publish_resume_seconds: u32,
+
+    /// Seconds the previous player key of a channel is still accepted after rotating it
+    #[cfg_attr(not(any(feature = "control", feature = "redis")), allow(dead_code))]
+    pub key_rotation_grace_seconds: u32,
+
+    /// Policy when a session tries to publish to a channel already being published
+    pub publish_takeover: PublishTakeoverPolicy,
+
+    /// Path to the FLV file to play in the channels with no publisher (empty to disable)
+    pub slate_file: String,
+
+    /// List of allowed audio codecs (empty means all codecs are allowed)
+    pub allowed_audio_codecs: Vec<String>,
+
+    /// List of allowed video codecs (empty means all codecs are allowed)
+    pub allowed_video_codecs: Vec<String>,
+
+    /// List of data messages forwarded from the publisher to the players (eg: onTextData)
+    pub data_passthrough: Vec<String>,
+
+    /// Max number of lifecycle events to keep in the history of each channel (0 to disable)
+    pub channel_event_history_size: usize,
+
+    /// Max time to wait for the connections to end after a binary upgrade (seconds, 0 for no limit)
+    pub upgrade_drain_seconds: u32,
+
     /// Callback configuration
     pub callback: CallbackConfiguration,
 
+    /// Token authentication configuration
+    pub auth_token: TokenAuthConfiguration,
+
+    /// Built-in key store configuration
+    pub key_store: StreamKeyStoreConfiguration,
+
+    /// Geolocation configuration
+    pub geoip: GeoIpConfiguration,
+
+    /// Tenant registry
+    pub tenants: TenantRegistry,
+
     /// True to log requests
     pub log_requests: bool,
+
+    /// Identity of the server node, to correlate events with other components
+    pub identity: ServerIdentity,
+
+    /// Persistence of the state, to recover from crashes
+    pub state_persistence: StatePersistenceConfiguration,
 }
 
 impl RtmpServerConfiguration {
@@ -128,16 +753,17 @@ impl RtmpServerConfiguration {
     ///
     /// * `logger` - The logger
     pub fn load_from_env(logger: &Logger) -> Result<RtmpServerConfiguration, ()> {
-        let port = get_env_u32("RTMP_PORT", RTMP_PORT_DEFAULT);
+        let ports = load_port_list_from_env(logger, "RTMP_PORT", RTMP_PORT_DEFAULT)?;
 
-        if port == 0 || port > MAX_PORT {
-            log_error!(logger, format!("RTMP_PORT has an invalid value: {}", port));
-            return Err(());
-        }
+        let bind_addresses = get_env_string_list("BIND_ADDRESS", "0.0.0.0");
+
+        let ipv6_only = get_env_bool("BIND_IPV6_ONLY", false);
+
+        let id_validation = IdValidationConfig::load_from_env(logger)?;
 
-        let bind_address = get_env_string("BIND_ADDRESS", "0.0.0.0");
+        let empty_app_compat = get_env_bool("EMPTY_APP_COMPAT", false);
 
-        let id_validation = IdValidationConfig::load_from_env();
+        let channel_aliases = ChannelAliasConfig::load_from_env(logger)?;
 
         let play_whitelist =
             match IpRangeConfig::new_from_string(&get_env_string("RTMP_PLAY_WHITELIST", "")) {
@@ -164,10 +790,77 @@ impl RtmpServerConfiguration {
             return Err(());
         }
 
+        let chunk_size_legacy_clients = get_env_string_list("RTMP_CHUNK_SIZE_LEGACY_CLIENTS", "");
+        let chunk_size_match_client = get_env_bool("RTMP_CHUNK_SIZE_MATCH_CLIENT", false);
+
         let gop_cache_size =
             (get_env_u32("GOP_CACHE_SIZE_MB", GOP_CACHE_SIZE_MB_DEFAULT) as usize) * 1024 * 1024;
+        let gop_replay_max_concurrent = get_env_u32("GOP_REPLAY_MAX_CONCURRENT", 0);
+        let gop_replay_queue_timeout_ms = get_env_u32("GOP_REPLAY_QUEUE_TIMEOUT_MS", 2000);
+        let keyframe_interval_warning_ms = get_env_u32("KEYFRAME_INTERVAL_WARNING_MS", 0);
+        let keyframe_interval_advice = get_env_bool("KEYFRAME_INTERVAL_ADVICE", false);
+        let publish_statistics_interval_seconds =
+            get_env_u32("PUBLISH_STATISTICS_INTERVAL_SECONDS", 0);
+        let publish_bitrate_cap_kbps = get_env_u32("PUBLISH_BITRATE_CAP_KBPS", 0);
+        let publish_max_lag_ms = get_env_u32("PUBLISH_MAX_LAG_MS", 0);
+        let publish_congestion_seconds = get_env_u32("PUBLISH_CONGESTION_SECONDS", 5);
+        let play_duration_composition_time = get_env_bool("PLAY_DURATION_COMPOSITION_TIME", false);
         let max_concurrent_connections_per_ip = get_env_u32("MAX_IP_CONCURRENT_CONNECTIONS", 4);
+        let fd_reserved = get_env_u32("FD_RESERVED", FD_RESERVED_DEFAULT);
+        let max_connections_per_minute_per_ip = get_env_u32("MAX_IP_CONNECTIONS_PER_MINUTE", 0);
+        let max_chunk_streams =
+            get_env_u32("MAX_CHUNK_STREAMS", MAX_CHUNK_STREAMS_DEFAULT).max(1) as usize;
+        let max_players_per_channel = get_env_u32("MAX_PLAYERS_PER_CHANNEL", 0);
+        let player_lag_packets = get_env_u32("PLAYER_LAG_PACKETS", 0);
+        let player_lag_seconds = get_env_u32("PLAYER_LAG_SECONDS", 5);
+        let player_audio_priority_packets = get_env_u32("PLAYER_AUDIO_PRIORITY_PACKETS", 0);
+        let player_idle_notify_seconds = get_env_u32("PLAYER_IDLE_NOTIFY_SECONDS", 0);
+        let player_idle_max_seconds = get_env_u32("PLAYER_IDLE_MAX_SECONDS", 0);
+        let player_max_kbps = get_env_u32("PLAYER_MAX_KBPS", 0);
+        let player_max_stream_bit_rate_percent = get_env_u32("PLAYER_MAX_BITRATE_PERCENT", 0);
+
+        // Under 100%, the players could never keep up with the stream
+
+        if player_max_stream_bit_rate_percent != 0
+            && !(PLAYER_MAX_BITRATE_PERCENT_MIN..=PLAYER_MAX_BITRATE_PERCENT_MAX)
+                .contains(&player_max_stream_bit_rate_percent)
+        {
+            log_error!(
+                logger,
+                format!(
+                    "PLAYER_MAX_BITRATE_PERCENT must be 0 (unlimited) or between {} and {}",
+                    PLAYER_MAX_BITRATE_PERCENT_MIN, PLAYER_MAX_BITRATE_PERCENT_MAX
+                )
+            );
+            return Err(());
+        }
+        let publish_resume_seconds = get_env_u32("PUBLISH_RESUME_SECONDS", 0);
+        let key_rotation_grace_seconds = get_env_u32("KEY_ROTATION_GRACE_SECONDS", 30);
+        let publish_takeover =
+            match PublishTakeoverPolicy::parse(&get_env_string("PUBLISH_TAKEOVER", "reject")) {
+                Ok(p) => p,
+                Err(e) => {
+                    log_error!(
+                        logger,
+                        format!("PUBLISH_TAKEOVER has an invalid value: {}", e)
+                    );
+                    return Err(());
+                }
+            };
+        let slate_file = get_env_string("SLATE_FILE", "");
         let msg_buffer_size = get_env_u32("MSG_BUFFER_SIZE", MSG_BUFFER_SIZE_DEFAULT) as usize;
+        let ack_window_limit = get_env_u32("RTMP_ACK_WINDOW_LIMIT", 0);
+        let ack_timeout_seconds =
+            get_env_u32("RTMP_ACK_TIMEOUT_SECONDS", RTMP_ACK_TIMEOUT_SECONDS_DEFAULT).max(1);
+        let write_timeout_seconds = get_env_u32(
+            "RTMP_WRITE_TIMEOUT_SECONDS",
+            RTMP_WRITE_TIMEOUT_SECONDS_DEFAULT,
+        );
+        let player_packet_buffer_size = get_env_u32(
+            "PLAYER_PACKET_BUFFER_SIZE",
+            PLAYER_PACKET_BUFFER_SIZE_DEFAULT,
+        )
+        .max(1) as usize;
 
         let max_concurrent_connections_whitelist =
             match IpRangeConfig::new_from_string(&get_env_string("CONCURRENT_LIMIT_WHITELIST", ""))
@@ -182,6 +875,27 @@ impl RtmpServerConfiguration {
                 }
             };
 
+        let allowed_audio_codecs =
+            load_codec_list_from_env(logger, "ALLOWED_AUDIO_CODECS", KNOWN_AUDIO_CODEC_NAMES)?;
+
+        let allowed_video_codecs =
+            load_codec_list_from_env(logger, "ALLOWED_VIDEO_CODECS", KNOWN_VIDEO_CODEC_NAMES)?;
+
+        let data_passthrough: Vec<String> =
+            get_env_string("DATA_PASSTHROUGH", DATA_PASSTHROUGH_DEFAULT)
+                .split(',')
+                .map(|d| d.trim().to_string())
+                .filter(|d| !d.is_empty())
+                .collect();
+
+        let channel_event_history_size = get_env_u32(
+            "CHANNEL_EVENT_HISTORY_SIZE",
+            CHANNEL_EVENT_HISTORY_SIZE_DEFAULT,
+        ) as usize;
+
+        let upgrade_drain_seconds =
+            get_env_u32("UPGRADE_DRAIN_SECONDS", UPGRADE_DRAIN_SECONDS_DEFAULT);
+
         let tls = match TlsServerConfiguration::load_from_env(logger) {
             Ok(c) => c,
             Err(()) => {
@@ -189,33 +903,294 @@ impl RtmpServerConfiguration {
             }
         };
 
-        let callback = match CallbackConfiguration::load_from_env(logger) {
+        let tcp_socket = match TcpSocketConfiguration::load_from_env(logger) {
             Ok(c) => c,
             Err(()) => {
                 return Err(());
             }
         };
 
+        let ip_screening = match IpScreeningConfiguration::load_from_env(logger) {
+            Ok(c) => c,
+            Err(()) => {
+                return Err(());
+            }
+        };
+
+        let identity = ServerIdentity::load_from_env();
+
+        let callback = match CallbackConfiguration::load_from_env(logger, &identity) {
+            Ok(c) => c,
+            Err(()) => {
+                return Err(());
+            }
+        };
+
+        let auth_token = match TokenAuthConfiguration::load_from_env(logger, "") {
+            Ok(c) => c,
+            Err(()) => {
+                return Err(());
+            }
+        };
+
+        let key_store = StreamKeyStoreConfiguration::load_from_env();
+
+        let geoip = GeoIpConfiguration::load_from_env();
+
+        let tenants = TenantRegistry::load_from_env(
+            logger,
+            &id_validation,
+            &callback,
+            &auth_token,
+            max_players_per_channel,
+        )?;
+
+        let recording = RecordingConfiguration::load_from_env(logger)?;
+        let snapshots = SnapshotConfiguration::load_from_env();
+
+        if !snapshots.callback_url.is_empty() && !cfg!(feature = "callback") {
+            log_error!(
+                logger,
+                "SNAPSHOT_CALLBACK_URL is set, but the server was built without the callback feature"
+            );
+            return Err(());
+        }
+
+        let trace = TraceConfiguration::load_from_env();
+        let chaos = ChaosConfiguration::load_from_env(logger)?;
+
+        let state_persistence = StatePersistenceConfiguration::load_from_env();
+
         let log_requests = get_env_bool("LOG_REQUESTS", true);
 
         Ok(RtmpServerConfiguration {
-            port,
-            bind_address,
+            ports,
+            bind_addresses,
+            ipv6_only,
             tls,
+            tcp_socket,
+            ip_screening,
             id_validation,
+            empty_app_compat,
+            channel_aliases,
+            recording,
+            snapshots,
+            trace,
+            chaos,
             play_whitelist,
             chunk_size,
+            chunk_size_legacy_clients,
+            chunk_size_match_client,
             gop_cache_size,
+            gop_replay_max_concurrent,
+            gop_replay_queue_timeout_ms,
+            keyframe_interval_warning_ms,
+            keyframe_interval_advice,
+            publish_statistics_interval_seconds,
+            publish_bitrate_cap_kbps,
+            publish_max_lag_ms,
+            publish_congestion_seconds,
+            play_duration_composition_time,
             msg_buffer_size,
+            ack_window_limit,
+            ack_timeout_seconds,
+            write_timeout_seconds,
+            player_packet_buffer_size,
+            max_chunk_streams,
             max_concurrent_connections_per_ip,
+            fd_reserved,
+            max_connections_per_minute_per_ip,
             max_concurrent_connections_whitelist,
+            max_players_per_channel,
+            player_lag_packets,
+            player_lag_seconds,
+            player_audio_priority_packets,
+            player_idle_notify_seconds,
+            player_idle_max_seconds,
+            player_max_kbps,
+            player_max_stream_bit_rate_percent,
+            publish_resume_seconds,
+            key_rotation_grace_seconds,
+            publish_takeover,
+            slate_file,
+            allowed_audio_codecs,
+            allowed_video_codecs,
+            data_passthrough,
+            channel_event_history_size,
+            upgrade_drain_seconds,
             callback,
+            auth_token,
+            key_store,
+            geoip,
+            tenants,
             log_requests,
+            identity,
+            state_persistence,
         })
     }
 
-    /// Gets TLS address for listening
-    pub fn get_tcp_listen_addr(&self) -> String {
-        format!("{}:{}", self.bind_address, self.port)
+    /// Gets TCP addresses for listening
+    pub fn get_tcp_listen_addrs(&self) -> Vec<String> {
+        make_listen_addrs(&self.bind_addresses, &self.ports)
+    }
+
+    /// Gets the callback configuration for a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    pub fn get_callback_config(&self, channel: &str) -> &CallbackConfiguration {
+        match self.tenants.get_channel_tenant(channel) {
+            Some(t) => &t.callback,
+            None => &self.callback,
+        }
+    }
+
+    /// Gets the token authentication configuration for a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    pub fn get_auth_token_config(&self, channel: &str) -> &TokenAuthConfiguration {
+        match self.tenants.get_channel_tenant(channel) {
+            Some(t) => &t.auth_token,
+            None => &self.auth_token,
+        }
+    }
+
+    /// Gets the max number of concurrent players per channel (0 for unlimited)
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    pub fn get_max_players_per_channel(&self, channel: &str) -> u32 {
+        match self.tenants.get_channel_tenant(channel) {
+            Some(t) => t.max_players_per_channel,
+            None => self.max_players_per_channel,
+        }
     }
+
+    /// Checks if an audio codec is allowed to be published
+    pub fn is_audio_codec_allowed(&self, codec: &str) -> bool {
+        self.allowed_audio_codecs.is_empty() || self.allowed_audio_codecs.iter().any(|c| c == codec)
+    }
+
+    /// Checks if a video codec is allowed to be published
+    pub fn is_video_codec_allowed(&self, codec: &str) -> bool {
+        self.allowed_video_codecs.is_empty() || self.allowed_video_codecs.iter().any(|c| c == codec)
+    }
+
+    /// Checks if a data message must be forwarded to the players
+    pub fn is_data_passthrough_allowed(&self, tag: &str) -> bool {
+        self.data_passthrough.iter().any(|d| d == tag)
+    }
+}
+
+/// Loads a list of ports from an environment variable
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `var_name` - The name of the environment variable
+/// * `default_port` - The default port
+///
+/// # Return value
+///
+/// Returns the list of ports, or an error if any of the ports is invalid
+fn load_port_list_from_env(
+    logger: &Logger,
+    var_name: &str,
+    default_port: u32,
+) -> Result<Vec<u32>, ()> {
+    let mut ports: Vec<u32> = Vec::new();
+
+    for port_str in get_env_string_list(var_name, &default_port.to_string()) {
+        let port: u32 = port_str.parse().unwrap_or(0);
+
+        if port == 0 || port > MAX_PORT {
+            log_error!(
+                logger,
+                format!("{} has an invalid value: {}", var_name, port_str)
+            );
+            return Err(());
+        }
+
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+
+    Ok(ports)
+}
+
+/// Makes the list of addresses for listening,
+/// combining every bind address with every port.
+/// IPv6 addresses are enclosed in brackets, if they are not already.
+///
+/// # Arguments
+///
+/// * `bind_addresses` - The bind addresses
+/// * `ports` - The ports
+fn make_listen_addrs(bind_addresses: &[String], ports: &[u32]) -> Vec<String> {
+    let mut addrs: Vec<String> = Vec::new();
+
+    for bind_address in bind_addresses {
+        for port in ports {
+            let addr = if bind_address.contains(':') && !bind_address.starts_with('[') {
+                format!("[{}]:{}", bind_address, port)
+            } else {
+                format!("{}:{}", bind_address, port)
+            };
+
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+    }
+
+    addrs
+}
+
+/// Loads a list of codec names from an environment variable
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `var_name` - The name of the environment variable
+/// * `known_codecs` - The list of known codec names
+///
+/// # Return value
+///
+/// Returns the list of codec names (lower case), or an error if any of the names is unknown
+fn load_codec_list_from_env(
+    logger: &Logger,
+    var_name: &str,
+    known_codecs: &[&str],
+) -> Result<Vec<String>, ()> {
+    let mut codecs: Vec<String> = Vec::new();
+
+    for codec in get_env_string(var_name, "").split(",") {
+        let codec = codec.trim().to_lowercase();
+
+        if codec.is_empty() {
+            continue;
+        }
+
+        if !known_codecs.contains(&codec.as_str()) {
+            log_error!(
+                logger,
+                format!(
+                    "{} has an invalid value: {}. Known codecs: {}",
+                    var_name,
+                    codec,
+                    known_codecs.join(", ")
+                )
+            );
+            return Err(());
+        }
+
+        codecs.push(codec);
+    }
+
+    Ok(codecs)
 }
@@ -0,0 +1,398 @@
+// Configuration overrides, set at runtime by the control server
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "control")]
+use std::collections::HashMap;
+
+#[cfg(feature = "control")]
+use crate::{
+    log::{
+        parse_log_packet_sample_rate, parse_log_session_filter, set_log_level_override,
+        set_log_packet_sample_rate, set_log_session_filter, LogLevel, LogSamplingConfig,
+    },
+    trace::SessionTraceFilter,
+};
+
+use super::RtmpServerConfiguration;
+
+/// Value of an override not set
+const CONFIG_OVERRIDE_UNSET: u64 = u64::MAX;
+
+/// Max size of the GOP cache that can be set at runtime (megabytes)
+#[cfg(feature = "control")]
+const GOP_CACHE_SIZE_MB_MAX: u32 = 4096;
+
+/// Value to restore the configured value of a setting
+#[cfg(feature = "control")]
+const CONFIG_OVERRIDE_DEFAULT_VALUE: &str = "DEFAULT";
+
+/// Value of the log session filter to log all the sessions
+#[cfg(feature = "control")]
+const LOG_SESSION_FILTER_ALL: &str = "ALL";
+
+/// Parameter to set the max number of concurrent players per channel
+#[cfg(feature = "control")]
+pub const CONFIG_SETTING_MAX_PLAYERS_PER_CHANNEL: &str = "Max-Players-Per-Channel";
+
+/// Parameter to set the size limit of the GOP cache (megabytes)
+#[cfg(feature = "control")]
+pub const CONFIG_SETTING_GOP_CACHE_SIZE: &str = "Gop-Cache-Size";
+
+/// Parameter to set the max level of the log messages
+#[cfg(feature = "control")]
+pub const CONFIG_SETTING_LOG_LEVEL: &str = "Log-Level";
+
+/// Parameter to set the sample rate of the packet log messages
+#[cfg(feature = "control")]
+pub const CONFIG_SETTING_LOG_PACKET_SAMPLE_RATE: &str = "Log-Packet-Sample-Rate";
+
+/// Parameter to set the filter of the sessions to log
+#[cfg(feature = "control")]
+pub const CONFIG_SETTING_LOG_SESSION_FILTER: &str = "Log-Session-Filter";
+
+/// Change of a setting. None restores the configured value.
+#[cfg(feature = "control")]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSettingChange {
+    /// Max number of concurrent players per channel (0 for unlimited)
+    MaxPlayersPerChannel(Option<u32>),
+
+    /// Size limit of the GOP cache (megabytes)
+    GopCacheSize(Option<u32>),
+
+    /// Max level of the log messages
+    LogLevel(Option<LogLevel>),
+
+    /// Log only 1 of every N packet messages
+    LogPacketSampleRate(Option<u64>),
+
+    /// Filter of the sessions to log (None inside to log all the sessions)
+    LogSessionFilter(Option<Option<SessionTraceFilter>>),
+}
+
+#[cfg(feature = "control")]
+impl ConfigSettingChange {
+    /// Gets the name of the setting
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigSettingChange::MaxPlayersPerChannel(_) => CONFIG_SETTING_MAX_PLAYERS_PER_CHANNEL,
+            ConfigSettingChange::GopCacheSize(_) => CONFIG_SETTING_GOP_CACHE_SIZE,
+            ConfigSettingChange::LogLevel(_) => CONFIG_SETTING_LOG_LEVEL,
+            ConfigSettingChange::LogPacketSampleRate(_) => CONFIG_SETTING_LOG_PACKET_SAMPLE_RATE,
+            ConfigSettingChange::LogSessionFilter(_) => CONFIG_SETTING_LOG_SESSION_FILTER,
+        }
+    }
+
+    /// Gets the new value, as a string
+    pub fn value(&self) -> String {
+        match self {
+            ConfigSettingChange::MaxPlayersPerChannel(Some(v))
+            | ConfigSettingChange::GopCacheSize(Some(v)) => v.to_string(),
+            ConfigSettingChange::LogLevel(Some(l)) => l.as_str().to_string(),
+            ConfigSettingChange::LogPacketSampleRate(Some(r)) => r.to_string(),
+            ConfigSettingChange::LogSessionFilter(Some(Some(f))) => {
+                format!("{}:{}", f.kind(), f.value())
+            }
+            ConfigSettingChange::LogSessionFilter(Some(None)) => LOG_SESSION_FILTER_ALL.to_string(),
+            _ => CONFIG_OVERRIDE_DEFAULT_VALUE.to_string(),
+        }
+    }
+}
+
+/// Parses the value of a numeric setting
+///
+/// # Arguments
+///
+/// * `name` - Name of the setting
+/// * `value` - The value
+/// * `max` - Max value allowed
+#[cfg(feature = "control")]
+fn parse_u32_setting(name: &str, value: &str, max: u32) -> Result<Option<u32>, String> {
+    match value.parse::<u32>() {
+        Ok(v) if v <= max => Ok(Some(v)),
+        Ok(v) => Err(format!("Invalid value for {}: {}. Max: {}", name, v, max)),
+        Err(_) => Err(format!("Invalid value for {}: {}", name, value)),
+    }
+}
+
+/// Parses the settings to change, validating all of them
+///
+/// # Arguments
+///
+/// * `parameters` - The settings (name -> value). Names are case insensitive.
+/// * `ignored` - Names of the parameters to ignore (eg: the request ID)
+///
+/// # Return value
+///
+/// Returns the list of changes, or an error message if any setting is not valid
+#[cfg(feature = "control")]
+pub fn parse_config_setting_changes(
+    parameters: &HashMap<String, String>,
+    ignored: &[&str],
+) -> Result<Vec<ConfigSettingChange>, String> {
+    let mut changes: Vec<ConfigSettingChange> = Vec::new();
+
+    for (name, value) in parameters {
+        if ignored.iter().any(|i| i.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+
+        let value = value.trim();
+        let restore = value.eq_ignore_ascii_case(CONFIG_OVERRIDE_DEFAULT_VALUE);
+
+        let change = if name.eq_ignore_ascii_case(CONFIG_SETTING_MAX_PLAYERS_PER_CHANNEL) {
+            ConfigSettingChange::MaxPlayersPerChannel(if restore {
+                None
+            } else {
+                parse_u32_setting(CONFIG_SETTING_MAX_PLAYERS_PER_CHANNEL, value, u32::MAX)?
+            })
+        } else if name.eq_ignore_ascii_case(CONFIG_SETTING_GOP_CACHE_SIZE) {
+            ConfigSettingChange::GopCacheSize(if restore {
+                None
+            } else {
+                parse_u32_setting(CONFIG_SETTING_GOP_CACHE_SIZE, value, GOP_CACHE_SIZE_MB_MAX)?
+            })
+        } else if name.eq_ignore_ascii_case(CONFIG_SETTING_LOG_LEVEL) {
+            ConfigSettingChange::LogLevel(if restore {
+                None
+            } else {
+                Some(LogLevel::parse(value)?)
+            })
+        } else if name.eq_ignore_ascii_case(CONFIG_SETTING_LOG_PACKET_SAMPLE_RATE) {
+            ConfigSettingChange::LogPacketSampleRate(if restore {
+                None
+            } else {
+                Some(parse_log_packet_sample_rate(value)?)
+            })
+        } else if name.eq_ignore_ascii_case(CONFIG_SETTING_LOG_SESSION_FILTER) {
+            ConfigSettingChange::LogSessionFilter(if restore {
+                None
+            } else if value.eq_ignore_ascii_case(LOG_SESSION_FILTER_ALL) {
+                Some(None)
+            } else {
+                match parse_log_session_filter(value)? {
+                    Some(f) => Some(Some(f)),
+                    None => {
+                        return Err(format!(
+                            "Invalid value for {}: {}",
+                            CONFIG_SETTING_LOG_SESSION_FILTER, value
+                        ));
+                    }
+                }
+            })
+        } else {
+            return Err(format!("Unknown setting: {}", name));
+        };
+
+        changes.push(change);
+    }
+
+    if changes.is_empty() {
+        return Err("No settings to change".to_string());
+    }
+
+    changes.sort_by_key(|c| c.name());
+
+    Ok(changes)
+}
+
+/// Overrides of the server configuration, set at runtime
+pub struct ConfigOverrides {
+    /// Max number of concurrent players per channel
+    max_players_per_channel: AtomicU64,
+
+    /// Size limit of the GOP cache (bytes)
+    gop_cache_size: AtomicU64,
+}
+
+impl ConfigOverrides {
+    /// Creates new ConfigOverrides, with no overrides set
+    pub fn new() -> ConfigOverrides {
+        ConfigOverrides {
+            max_players_per_channel: AtomicU64::new(CONFIG_OVERRIDE_UNSET),
+            gop_cache_size: AtomicU64::new(CONFIG_OVERRIDE_UNSET),
+        }
+    }
+
+    /// Applies changes of the settings
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The changes, already validated
+    #[cfg(feature = "control")]
+    pub fn apply(&self, changes: &[ConfigSettingChange]) {
+        for change in changes {
+            match change {
+                ConfigSettingChange::MaxPlayersPerChannel(v) => {
+                    self.max_players_per_channel.store(
+                        v.map(|v| v as u64).unwrap_or(CONFIG_OVERRIDE_UNSET),
+                        Ordering::Relaxed,
+                    );
+                }
+                ConfigSettingChange::GopCacheSize(v) => {
+                    self.gop_cache_size.store(
+                        v.map(|v| (v as u64) * 1024 * 1024)
+                            .unwrap_or(CONFIG_OVERRIDE_UNSET),
+                        Ordering::Relaxed,
+                    );
+                }
+                ConfigSettingChange::LogLevel(l) => {
+                    set_log_level_override(*l);
+                }
+                ConfigSettingChange::LogPacketSampleRate(r) => {
+                    set_log_packet_sample_rate(r.unwrap_or_else(|| {
+                        LogSamplingConfig::load_from_env()
+                            .unwrap_or_default()
+                            .packet_sample_rate
+                    }));
+                }
+                ConfigSettingChange::LogSessionFilter(f) => {
+                    set_log_session_filter(f.clone().unwrap_or_else(|| {
+                        LogSamplingConfig::load_from_env()
+                            .unwrap_or_default()
+                            .session_filter
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Gets the max number of concurrent players for a channel (0 for unlimited).
+    /// If overridden, the override applies to every channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The server configuration
+    /// * `channel` - The channel
+    pub fn get_max_players_per_channel(
+        &self,
+        config: &RtmpServerConfiguration,
+        channel: &str,
+    ) -> u32 {
+        match self.max_players_per_channel.load(Ordering::Relaxed) {
+            CONFIG_OVERRIDE_UNSET => config.get_max_players_per_channel(channel),
+            v => v as u32,
+        }
+    }
+
+    /// Gets the size limit of the GOP cache (bytes)
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The server configuration
+    pub fn get_gop_cache_size(&self, config: &RtmpServerConfiguration) -> usize {
+        match self.gop_cache_size.load(Ordering::Relaxed) {
+            CONFIG_OVERRIDE_UNSET => config.gop_cache_size,
+            v => v as usize,
+        }
+    }
+}
+
+// Tests
+
+#[cfg(all(test, feature = "control"))]
+mod tests {
+    use super::*;
+
+    fn make_parameters(parameters: &[(&str, &str)]) -> HashMap<String, String> {
+        parameters
+            .iter()
+            .map(|(k, v)| (k.to_lowercase(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_config_setting_changes() {
+        let changes = parse_config_setting_changes(
+            &make_parameters(&[
+                ("Request-Id", "1"),
+                ("Max-Players-Per-Channel", " 10"),
+                ("Gop-Cache-Size", "default"),
+            ]),
+            &["Request-Id"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            changes,
+            vec![
+                ConfigSettingChange::GopCacheSize(None),
+                ConfigSettingChange::MaxPlayersPerChannel(Some(10)),
+            ]
+        );
+        assert_eq!(changes[0].value(), "DEFAULT");
+        assert_eq!(changes[1].value(), "10");
+
+        assert!(parse_config_setting_changes(&make_parameters(&[]), &[]).is_err());
+        assert!(parse_config_setting_changes(&make_parameters(&[("Unknown", "1")]), &[]).is_err());
+        assert!(
+            parse_config_setting_changes(&make_parameters(&[("Gop-Cache-Size", "-1")]), &[])
+                .is_err()
+        );
+        assert!(
+            parse_config_setting_changes(&make_parameters(&[("Gop-Cache-Size", "5000")]), &[])
+                .is_err()
+        );
+        assert!(
+            parse_config_setting_changes(&make_parameters(&[("Log-Level", "verbose")]), &[])
+                .is_err()
+        );
+
+        let changes = parse_config_setting_changes(
+            &make_parameters(&[
+                ("Log-Packet-Sample-Rate", "100"),
+                ("Log-Session-Filter", "channel:live"),
+            ]),
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(changes[0].value(), "100");
+        assert_eq!(changes[1].value(), "channel:live");
+
+        let changes =
+            parse_config_setting_changes(&make_parameters(&[("Log-Session-Filter", "all")]), &[])
+                .unwrap();
+
+        assert_eq!(
+            changes,
+            vec![ConfigSettingChange::LogSessionFilter(Some(None))]
+        );
+        assert!(parse_config_setting_changes(
+            &make_parameters(&[("Log-Packet-Sample-Rate", "0")]),
+            &[]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let overrides = ConfigOverrides::new();
+
+        overrides.apply(&[
+            ConfigSettingChange::MaxPlayersPerChannel(Some(5)),
+            ConfigSettingChange::GopCacheSize(Some(2)),
+        ]);
+
+        assert_eq!(overrides.max_players_per_channel.load(Ordering::Relaxed), 5);
+        assert_eq!(
+            overrides.gop_cache_size.load(Ordering::Relaxed),
+            2 * 1024 * 1024
+        );
+
+        overrides.apply(&[
+            ConfigSettingChange::MaxPlayersPerChannel(None),
+            ConfigSettingChange::GopCacheSize(None),
+        ]);
+
+        assert_eq!(
+            overrides.max_players_per_channel.load(Ordering::Relaxed),
+            CONFIG_OVERRIDE_UNSET
+        );
+        assert_eq!(
+            overrides.gop_cache_size.load(Ordering::Relaxed),
+            CONFIG_OVERRIDE_UNSET
+        );
+    }
+}
@@ -9,10 +9,14 @@ use tokio::{
 
 use crate::{
     log::Logger,
-    log_info,
+    log_debug, log_info, log_warning,
     session::{
-        handle_rtmp_session, RtmpSessionPublishStreamStatus, RtmpSessionStatus, SessionContext,
+        handle_rtmp_session, RtmpSessionPublishStreamStatus, RtmpSessionStatus, SessionBandwidth,
+        SessionContext, SessionObjectEncoding, SessionOutChunkSize, SessionOutboundAck,
+        SessionPanicTracker, SessionPlayerLag,
     },
+    trace::SessionTraceTap,
+    utils::CertificateIdentity,
 };
 
 use super::{RtmpServerContext, RtmpServerContextExtended};
@@ -26,6 +30,10 @@ use super::{RtmpServerContext, RtmpServerContextExtended};
 /// * `read_stream` - The stream to read from the client
 /// * `write_stream` - The stream to write to the client
 /// * `ip` - The client IP address
+/// * `bandwidth` - The bandwidth usage of the session
+/// * `trace` - The trace tap of the session
+/// * `client_cert` - The identity of the client certificate (if provided)
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_connection<
     TR: AsyncRead + AsyncReadExt + Send + Sync + Unpin,
     TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
@@ -35,17 +43,27 @@ pub async fn handle_connection<
     read_stream: TR,
     write_stream: Arc<Mutex<TW>>,
     ip: IpAddr,
+    bandwidth: Arc<SessionBandwidth>,
+    trace: Arc<SessionTraceTap>,
+    client_cert: Option<CertificateIdentity>,
 ) {
+    // Screen the client IP before the handshake
+    if !server_context.ip_screener.check_ip(&logger, &ip).await {
+        log_info!(
+            logger,
+            format!("Rejected request from {} due to IP screening", ip)
+        );
+        return;
+    }
+
     // Generate an unique ID for the session
-    let mut session_id_generator_v = server_context.session_id_generator.as_ref().lock().await;
-    let session_id = (*session_id_generator_v).generate_id();
-    drop(session_id_generator_v);
+    let session_id = generate_session_id(&logger, &server_context).await;
 
     // Create a logger for the session
     let session_logger = Arc::new(if server_context.config.log_requests {
         logger
             .as_ref()
-            .make_child_logger(&format!("[#{}] ", session_id))
+            .make_session_logger(&format!("[#{}] ", session_id), ip)
     } else {
         Logger::new_disabled()
     });
@@ -54,8 +72,32 @@ pub async fn handle_connection<
     let session_status = Arc::new(Mutex::new(RtmpSessionStatus::new()));
     let publish_status = Arc::new(Mutex::new(RtmpSessionPublishStreamStatus::new()));
 
+    // Geolocate the client
+    let client_geo = server_context.geoip.lookup(ip);
+
     // Log request
-    log_info!(session_logger, format!("Connection accepted from {}", ip));
+    match &client_geo {
+        Some(geo) => {
+            log_info!(
+                session_logger,
+                format!("Connection accepted from {} | {}", ip, geo.to_log_string())
+            );
+        }
+        None => {
+            log_info!(session_logger, format!("Connection accepted from {}", ip));
+        }
+    }
+
+    if let Some(cert) = &client_cert {
+        log_debug!(
+            session_logger,
+            format!(
+                "Client certificate: CN={} | SAN={}",
+                cert.common_name.as_deref().unwrap_or_default(),
+                cert.get_subject_alt_names_string().unwrap_or_default()
+            )
+        );
+    }
 
     // Create session context
     let session_context = SessionContext {
@@ -63,19 +105,72 @@ pub async fn handle_connection<
         ip,
         status: session_status,
         publish_status,
+        bandwidth,
+        player_lag: Arc::new(SessionPlayerLag::new()),
+        trace,
+        out_chunk_size: Arc::new(SessionOutChunkSize::new()),
+        outbound_ack: Arc::new(SessionOutboundAck::new()),
+        object_encoding: Arc::new(SessionObjectEncoding::new()),
+        client_cert: client_cert.map(Arc::new),
+        client_geo: client_geo.map(Arc::new),
+        panic_tracker: Arc::new(SessionPanicTracker::new()),
     };
 
     // Handle session
+    server_context.connections.add();
+
     handle_rtmp_session(
         session_logger,
         RtmpServerContext {
             config: server_context.config,
             status: server_context.status,
             control_key_validator_sender: server_context.control_key_validator_sender,
+            slate: server_context.slate,
+            cluster: server_context.cluster,
         },
         session_context,
         read_stream,
         write_stream,
     )
     .await;
+
+    server_context.connections.remove();
+}
+
+/// Max number of attempts to find an unused session ID, after the counter wrapped around
+const SESSION_ID_MAX_ATTEMPTS: usize = 16;
+
+/// Generates an unique ID for a session.
+/// If the counter wrapped around, the IDs still in use are skipped.
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `server_context` - The server context
+async fn generate_session_id(logger: &Logger, server_context: &RtmpServerContextExtended) -> u64 {
+    let mut session_id = server_context.session_id_generator.generate_id();
+
+    if !server_context.session_id_generator.has_wrapped() {
+        return session_id;
+    }
+
+    for _ in 0..SESSION_ID_MAX_ATTEMPTS {
+        if !server_context
+            .status
+            .traces
+            .is_session_registered(session_id)
+            .await
+        {
+            return session_id;
+        }
+
+        log_warning!(
+            logger,
+            format!("Session ID #{} is still in use. Skipping it.", session_id)
+        );
+
+        session_id = server_context.session_id_generator.generate_id();
+    }
+
+    session_id
 }
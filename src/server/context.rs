@@ -4,9 +4,15 @@ use std::sync::Arc;
 
 use tokio::sync::{mpsc::Sender, Mutex};
 
-use crate::control::ControlKeyValidationRequest;
+use crate::{
+    chaos::ChaosInjector, cluster::ClusterNode, control::ControlKeyValidationRequest,
+    geoip::GeoIpDatabase, slate::SlateSource,
+};
 
-use super::{IpConnectionCounter, RtmpServerConfiguration, RtmpServerStatus, SessionIdGenerator};
+use super::{
+    ActiveConnections, IpConnectionCounter, IpConnectionRateLimiter, IpScreener,
+    OpenSocketsTracker, RtmpServerConfiguration, RtmpServerStatus, SessionIdGenerator,
+};
 
 /// RTMP server context
 #[derive(Clone)]
@@ -15,10 +21,16 @@ pub struct RtmpServerContext {
     pub config: Arc<RtmpServerConfiguration>,
 
     /// Server status
-    pub status: Arc<Mutex<RtmpServerStatus>>,
+    pub status: Arc<RtmpServerStatus>,
 
     /// Sender for key validation against the control server
     pub control_key_validator_sender: Option<Sender<ControlKeyValidationRequest>>,
+
+    /// Slate to play in the channels with no publisher
+    pub slate: Option<Arc<SlateSource>>,
+
+    /// Node of the cluster (if the clustering mode is enabled)
+    pub cluster: Option<Arc<ClusterNode>>,
 }
 
 /// RTMP server context
@@ -28,14 +40,38 @@ pub struct RtmpServerContextExtended {
     pub config: Arc<RtmpServerConfiguration>,
 
     /// Server status
-    pub status: Arc<Mutex<RtmpServerStatus>>,
+    pub status: Arc<RtmpServerStatus>,
 
     /// Sender for key validation against the control server
     pub control_key_validator_sender: Option<Sender<ControlKeyValidationRequest>>,
 
+    /// Slate to play in the channels with no publisher
+    pub slate: Option<Arc<SlateSource>>,
+
+    /// Node of the cluster (if the clustering mode is enabled)
+    pub cluster: Option<Arc<ClusterNode>>,
+
     /// IP counter
     pub ip_counter: Arc<Mutex<IpConnectionCounter>>,
 
+    /// IP connection rate limiter
+    pub ip_rate_limiter: Arc<IpConnectionRateLimiter>,
+
     /// Session ID generator
-    pub session_id_generator: Arc<Mutex<SessionIdGenerator>>,
+    pub session_id_generator: Arc<SessionIdGenerator>,
+
+    /// IP screening of the incoming connections
+    pub ip_screener: Arc<IpScreener>,
+
+    /// Geolocation of the client IP addresses
+    pub geoip: Arc<GeoIpDatabase>,
+
+    /// Counter of the active connections
+    pub connections: Arc<ActiveConnections>,
+
+    /// Fault injection for chaos testing
+    pub chaos: Arc<ChaosInjector>,
+
+    /// Tracker of the open sockets, to reject connections close to the open files limit
+    pub open_sockets: Arc<OpenSocketsTracker>,
 }
@@ -0,0 +1,291 @@
+// Limit of open file descriptors
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::{log::Logger, log_error, log_info, log_warning};
+
+use super::RtmpServerConfiguration;
+
+/// Min number of file descriptors available for the connections,
+/// below which a warning is logged at startup
+const FD_AVAILABLE_WARNING_THRESHOLD: u64 = 1024;
+
+/// Limit of open files of the process
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenFilesLimit {
+    /// Soft limit (the one applied)
+    pub soft: u64,
+
+    /// Hard limit (max value the soft limit can be raised to)
+    pub hard: Option<u64>,
+}
+
+/// Gets the limit of open files of the process (RLIMIT_NOFILE)
+///
+/// # Return value
+///
+/// Returns the limit, or None if unlimited or unknown
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // rlim_t is not u64 in all the platforms
+pub fn get_open_files_limit() -> Option<OpenFilesLimit> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    // Safety: getrlimit only writes to the provided struct
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+
+    if limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+
+    Some(OpenFilesLimit {
+        soft: limit.rlim_cur as u64,
+        hard: if limit.rlim_max == libc::RLIM_INFINITY {
+            None
+        } else {
+            Some(limit.rlim_max as u64)
+        },
+    })
+}
+
+/// Gets the limit of open files of the process
+///
+/// # Return value
+///
+/// Returns the limit, or None if unlimited or unknown
+#[cfg(not(unix))]
+pub fn get_open_files_limit() -> Option<OpenFilesLimit> {
+    None
+}
+
+/// Checks the limit of open files against the configuration, logging it
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The server configuration
+///
+/// # Return value
+///
+/// Returns an error if the limit leaves no file descriptors for the connections
+pub fn check_open_files_limit(logger: &Logger, config: &RtmpServerConfiguration) -> Result<(), ()> {
+    let limit = match get_open_files_limit() {
+        Some(l) => l,
+        None => {
+            log_info!(logger, "Open files limit: unlimited or unknown");
+            return Ok(());
+        }
+    };
+
+    let reserved = config.fd_reserved as u64;
+
+    log_info!(
+        logger,
+        format!(
+            "Open files limit: {} (hard: {}). Reserved for other than connections: {}",
+            limit.soft,
+            limit
+                .hard
+                .map(|h| h.to_string())
+                .unwrap_or("unlimited".to_string()),
+            reserved
+        )
+    );
+
+    if reserved == 0 {
+        return Ok(());
+    }
+
+    if limit.soft <= reserved {
+        log_error!(
+            logger,
+            format!(
+                "The open files limit ({}) is not greater than FD_RESERVED ({}). No connection could be accepted. Increase the limit (ulimit -n) or reduce FD_RESERVED.",
+                limit.soft, reserved
+            )
+        );
+        return Err(());
+    }
+
+    if limit.soft - reserved < FD_AVAILABLE_WARNING_THRESHOLD {
+        log_warning!(
+            logger,
+            format!(
+                "Only {} connections can be open at the same time, due to the open files limit ({}). Consider increasing it (ulimit -n).",
+                limit.soft - reserved,
+                limit.soft
+            )
+        );
+    }
+
+    Ok(())
+}
+
+/// Tracker of the open sockets of the accepted connections,
+/// to reject new connections before the open files limit is reached
+pub struct OpenSocketsTracker {
+    /// Max number of open sockets (None for unlimited)
+    max: Option<u64>,
+
+    /// Number of open sockets
+    open: AtomicU64,
+
+    /// Number of connections rejected due to the limit
+    rejected: AtomicU64,
+
+    /// True if the limit is currently reached
+    saturated: AtomicBool,
+}
+
+impl OpenSocketsTracker {
+    /// Creates new OpenSocketsTracker
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The open files limit (None for unlimited)
+    /// * `reserved` - File descriptors reserved for other than connections (0 to disable the limit)
+    pub fn new(limit: Option<OpenFilesLimit>, reserved: u32) -> OpenSocketsTracker {
+        let max = match limit {
+            Some(l) if reserved > 0 => Some(l.soft.saturating_sub(reserved as u64)),
+            _ => None,
+        };
+
+        OpenSocketsTracker {
+            max,
+            open: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            saturated: AtomicBool::new(false),
+        }
+    }
+
+    /// Registers an accepted socket
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    ///
+    /// # Return value
+    ///
+    /// Returns a guard to keep while the socket is open,
+    /// or None if the connection must be rejected
+    pub fn try_open(self: &Arc<Self>, logger: &Logger) -> Option<OpenSocketGuard> {
+        let max = match self.max {
+            Some(m) => m,
+            None => {
+                self.open.fetch_add(1, Ordering::AcqRel);
+                return Some(OpenSocketGuard {
+                    tracker: self.clone(),
+                });
+            }
+        };
+
+        let res = self
+            .open
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                if open < max {
+                    Some(open + 1)
+                } else {
+                    None
+                }
+            });
+
+        match res {
+            Ok(_) => {
+                if self.saturated.swap(false, Ordering::AcqRel) {
+                    log_info!(
+                        logger,
+                        format!(
+                            "Accepting connections again. Open sockets are below the limit ({})",
+                            max
+                        )
+                    );
+                }
+
+                Some(OpenSocketGuard {
+                    tracker: self.clone(),
+                })
+            }
+            Err(_) => {
+                let rejected = self.rejected.fetch_add(1, Ordering::Relaxed) + 1;
+
+                if !self.saturated.swap(true, Ordering::AcqRel) {
+                    log_warning!(
+                        logger,
+                        format!(
+                            "Rejecting new connections: {} open sockets, close to the open files limit. Rejected connections so far: {}",
+                            max, rejected
+                        )
+                    );
+                }
+
+                None
+            }
+        }
+    }
+}
+
+/// Guard of an open socket, releasing it from the tracker when dropped
+pub struct OpenSocketGuard {
+    /// The tracker
+    tracker: Arc<OpenSocketsTracker>,
+}
+
+impl Drop for OpenSocketGuard {
+    fn drop(&mut self) {
+        self.tracker.open.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_sockets_tracker() {
+        let logger = Logger::new_disabled();
+
+        let tracker = Arc::new(OpenSocketsTracker::new(
+            Some(OpenFilesLimit {
+                soft: 12,
+                hard: None,
+            }),
+            10,
+        ));
+
+        let first = tracker.try_open(&logger);
+        let second = tracker.try_open(&logger);
+
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(tracker.try_open(&logger).is_none());
+        assert_eq!(tracker.open.load(Ordering::Acquire), 2);
+
+        drop(first);
+
+        assert_eq!(tracker.open.load(Ordering::Acquire), 1);
+        assert!(tracker.try_open(&logger).is_some());
+
+        // Disabled
+
+        let tracker = Arc::new(OpenSocketsTracker::new(
+            Some(OpenFilesLimit {
+                soft: 12,
+                hard: None,
+            }),
+            0,
+        ));
+
+        let guards: Vec<_> = (0..20).map(|_| tracker.try_open(&logger)).collect();
+
+        assert!(guards.iter().all(|g| g.is_some()));
+    }
+}
@@ -0,0 +1,95 @@
+// Identity of the server node
+
+use crate::utils::{generate_uuid_v4, get_env_string};
+
+/// Identity of the server node, included in the external integrations
+/// (callbacks, control server, Redis responses and logs),
+/// so the events of multi-node deployments can be attributed
+#[derive(Clone)]
+pub struct ServerIdentity {
+    /// ID of this server instance, unique for each run of the server
+    pub instance_id: String,
+
+    /// ID of the server node, stable across restarts (empty if not set)
+    pub server_id: String,
+
+    /// Region of the server node (empty if not set)
+    pub region: String,
+}
+
+impl ServerIdentity {
+    /// Loads the server identity
+    /// from environment variables
+    pub fn load_from_env() -> ServerIdentity {
+        let mut instance_id = get_env_string("SERVER_INSTANCE_ID", "");
+
+        if instance_id.is_empty() {
+            instance_id = generate_uuid_v4();
+        }
+
+        ServerIdentity {
+            instance_id,
+            server_id: get_env_string("SERVER_ID", ""),
+            region: get_env_string("SERVER_REGION", ""),
+        }
+    }
+
+    /// Gets the server ID, or None if not set
+    #[cfg(any(feature = "callback", feature = "control", feature = "redis"))]
+    pub fn server_id(&self) -> Option<String> {
+        if self.server_id.is_empty() {
+            None
+        } else {
+            Some(self.server_id.clone())
+        }
+    }
+
+    /// Gets the region, or None if not set
+    #[cfg(any(feature = "callback", feature = "control"))]
+    pub fn region(&self) -> Option<String> {
+        if self.region.is_empty() {
+            None
+        } else {
+            Some(self.region.clone())
+        }
+    }
+
+    /// Gets the prefix for the log messages (eg: `[node-1@eu-west] `).
+    /// Empty if the server ID is not set.
+    pub fn log_prefix(&self) -> String {
+        match (self.server_id.is_empty(), self.region.is_empty()) {
+            (true, _) => "".to_string(),
+            (false, true) => format!("[{}] ", self.server_id),
+            (false, false) => format!("[{}@{}] ", self.server_id, self.region),
+        }
+    }
+}
+
+// Tests
+
+#[cfg(all(test, any(feature = "callback", feature = "control")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_identity_log_prefix() {
+        let mut identity = ServerIdentity {
+            instance_id: "instance".to_string(),
+            server_id: "".to_string(),
+            region: "eu-west".to_string(),
+        };
+
+        assert_eq!(identity.log_prefix(), "");
+        assert_eq!(identity.server_id(), None);
+        assert_eq!(identity.region(), Some("eu-west".to_string()));
+
+        identity.server_id = "node-1".to_string();
+
+        assert_eq!(identity.log_prefix(), "[node-1@eu-west] ");
+
+        identity.region = "".to_string();
+
+        assert_eq!(identity.log_prefix(), "[node-1] ");
+        assert_eq!(identity.region(), None);
+    }
+}
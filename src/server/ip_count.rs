@@ -1,6 +1,10 @@
 // IP address connection counter
 
-use std::{collections::HashMap, net::IpAddr};
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+
+use tokio::sync::Mutex;
+
+use crate::utils::normalize_ip_address;
 
 use super::RtmpServerConfiguration;
 
@@ -25,6 +29,8 @@ impl IpConnectionCounter {
     /// Adds IP address, trying to fit it into the limit
     /// Returns true if accepted, false if rejected
     pub fn add(&mut self, ip: &IpAddr) -> bool {
+        let ip = &normalize_ip_address(*ip);
+
         match self.counters.get(ip) {
             Some(old_count) => {
                 if *old_count < self.limit {
@@ -49,12 +55,140 @@ impl IpConnectionCounter {
 
     /// Removes IP address
     pub fn remove(&mut self, ip: &IpAddr) {
+        let ip = &normalize_ip_address(*ip);
+
         if let Some(old_count) = self.counters.get(ip) {
-            if *old_count > 0 {
+            if *old_count > 1 {
                 self.counters.insert(*ip, *old_count - 1);
             } else {
+                // Last connection, release the entry
                 self.counters.remove(ip);
             }
         }
     }
 }
+
+/// Connection added to the IP connection counter,
+/// removed from it when dropped, even if the connection task panics
+pub struct IpConnectionGuard {
+    /// The counter
+    counter: Arc<Mutex<IpConnectionCounter>>,
+
+    /// The IP address
+    ip: IpAddr,
+}
+
+impl IpConnectionGuard {
+    /// Adds a connection to the counter, trying to fit it into the limit
+    ///
+    /// # Arguments
+    ///
+    /// * `counter` - The counter
+    /// * `ip` - The IP address of the connection
+    ///
+    /// # Return value
+    ///
+    /// Returns the guard if accepted, None if rejected
+    pub async fn try_add(
+        counter: &Arc<Mutex<IpConnectionCounter>>,
+        ip: &IpAddr,
+    ) -> Option<IpConnectionGuard> {
+        let mut counter_v = counter.lock().await;
+
+        if !counter_v.add(ip) {
+            return None;
+        }
+
+        drop(counter_v);
+
+        Some(IpConnectionGuard {
+            counter: counter.clone(),
+            ip: *ip,
+        })
+    }
+}
+
+impl Drop for IpConnectionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut counter_v) = self.counter.try_lock() {
+            counter_v.remove(&self.ip);
+            return;
+        }
+
+        // The counter is locked, remove in a new task
+
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(r) => r,
+            Err(_) => {
+                return;
+            }
+        };
+
+        let counter = self.counter.clone();
+        let ip = self.ip;
+
+        runtime.spawn(async move {
+            counter.lock().await.remove(&ip);
+        });
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn make_counter(limit: usize) -> Arc<Mutex<IpConnectionCounter>> {
+        Arc::new(Mutex::new(IpConnectionCounter {
+            limit,
+            counters: HashMap::new(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_ip_connection_guard() {
+        let counter = make_counter(2);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        let guard_1 = IpConnectionGuard::try_add(&counter, &ip).await;
+        let guard_2 = IpConnectionGuard::try_add(&counter, &ip).await;
+
+        assert!(guard_1.is_some());
+        assert!(guard_2.is_some());
+        assert!(IpConnectionGuard::try_add(&counter, &ip).await.is_none());
+
+        drop(guard_1);
+
+        assert_eq!(counter.lock().await.counters.get(&ip), Some(&1));
+
+        // Dropped while the counter is locked
+
+        let counter_v = counter.lock().await;
+        drop(guard_2);
+        drop(counter_v);
+
+        tokio::task::yield_now().await;
+
+        assert!(counter.lock().await.counters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ip_connection_guard_panic() {
+        let counter = make_counter(1);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        let task_counter = counter.clone();
+
+        let res = tokio::spawn(async move {
+            let _guard = IpConnectionGuard::try_add(&task_counter, &ip).await;
+            panic!("Connection task panicked");
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert!(IpConnectionGuard::try_add(&counter, &ip).await.is_some());
+    }
+}
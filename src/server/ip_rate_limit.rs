@@ -0,0 +1,235 @@
+// IP address connection rate limiter
+
+use std::{collections::HashMap, net::IpAddr};
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::{log::Logger, log_info, utils::normalize_ip_address};
+
+/// Max number of tracked IP addresses.
+/// When reached, the idle buckets are removed.
+const IP_RATE_LIMIT_MAX_SIZE: usize = 10000;
+
+/// Milliseconds per minute
+const MS_PER_MINUTE: f64 = 60000.0;
+
+/// Token bucket of an IP address
+struct IpRateLimitBucket {
+    /// Available tokens (connection attempts)
+    tokens: f64,
+
+    /// Timestamp of the last refill (Unix milliseconds)
+    last_refill: i64,
+
+    /// Number of rejected attempts since the bucket was created
+    rejected: u64,
+}
+
+/// Token buckets of the IP addresses
+pub struct IpRateLimitBuckets {
+    /// Max number of connection attempts per minute (0 for unlimited)
+    attempts_per_minute: u32,
+
+    /// Buckets
+    buckets: HashMap<IpAddr, IpRateLimitBucket>,
+
+    /// Total number of rejected attempts
+    rejected_total: u64,
+}
+
+impl IpRateLimitBuckets {
+    /// Creates new IpRateLimitBuckets
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts_per_minute` - Max number of connection attempts per minute (0 for unlimited)
+    pub fn new(attempts_per_minute: u32) -> IpRateLimitBuckets {
+        IpRateLimitBuckets {
+            attempts_per_minute,
+            buckets: HashMap::new(),
+            rejected_total: 0,
+        }
+    }
+
+    /// Checks if the rate limiter is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.attempts_per_minute > 0
+    }
+
+    /// Registers a connection attempt, consuming a token
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address
+    /// * `now` - The current timestamp (Unix milliseconds)
+    ///
+    /// # Return value
+    ///
+    /// Returns Ok if accepted, or Err with the number of
+    /// rejected attempts from the IP address if rejected
+    pub fn try_acquire(&mut self, ip: &IpAddr, now: i64) -> Result<(), u64> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let ip = normalize_ip_address(*ip);
+        let capacity = self.attempts_per_minute as f64;
+
+        if self.buckets.len() >= IP_RATE_LIMIT_MAX_SIZE && !self.buckets.contains_key(&ip) {
+            self.remove_idle_buckets(now);
+        }
+
+        let bucket = self.buckets.entry(ip).or_insert(IpRateLimitBucket {
+            tokens: capacity,
+            last_refill: now,
+            rejected: 0,
+        });
+
+        let elapsed = (now - bucket.last_refill).max(0) as f64;
+
+        bucket.tokens = (bucket.tokens + elapsed * capacity / MS_PER_MINUTE).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            bucket.rejected = bucket.rejected.saturating_add(1);
+            self.rejected_total = self.rejected_total.saturating_add(1);
+            Err(bucket.rejected)
+        }
+    }
+
+    /// Gets the total number of rejected attempts
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected_total
+    }
+
+    /// Removes the buckets that would be full by now,
+    /// since they are equivalent to a new bucket
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp (Unix milliseconds)
+    fn remove_idle_buckets(&mut self, now: i64) {
+        let capacity = self.attempts_per_minute as f64;
+
+        self.buckets.retain(|_, bucket| {
+            let elapsed = (now - bucket.last_refill).max(0) as f64;
+            bucket.tokens + elapsed * capacity / MS_PER_MINUTE < capacity
+        });
+
+        if self.buckets.len() >= IP_RATE_LIMIT_MAX_SIZE {
+            self.buckets.clear();
+        }
+    }
+}
+
+/// IP connection rate limiter (token bucket per IP address),
+/// checked before the handshake
+pub struct IpConnectionRateLimiter {
+    /// Buckets
+    buckets: Mutex<IpRateLimitBuckets>,
+}
+
+impl IpConnectionRateLimiter {
+    /// Creates new IpConnectionRateLimiter
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts_per_minute` - Max number of connection attempts per minute (0 for unlimited)
+    pub fn new(attempts_per_minute: u32) -> IpConnectionRateLimiter {
+        IpConnectionRateLimiter {
+            buckets: Mutex::new(IpRateLimitBuckets::new(attempts_per_minute)),
+        }
+    }
+
+    /// Checks if a client IP address is allowed to connect,
+    /// consuming a connection attempt
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `ip` - The client IP address
+    ///
+    /// # Return value
+    ///
+    /// Returns true to accept the connection, false to reject it
+    pub async fn check_ip(&self, logger: &Logger, ip: &IpAddr) -> bool {
+        let mut buckets = self.buckets.lock().await;
+
+        if !buckets.is_enabled() {
+            return true;
+        }
+
+        let res = buckets.try_acquire(ip, Utc::now().timestamp_millis());
+        let rejected_total = buckets.rejected_total();
+
+        drop(buckets);
+
+        match res {
+            Ok(()) => true,
+            Err(rejected) => {
+                log_info!(
+                    logger,
+                    format!(
+                        "Rejected request from {} due to connection rate limit. Rejected attempts: {} (total: {})",
+                        ip, rejected, rejected_total
+                    )
+                );
+
+                false
+            }
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_rate_limit() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let mut limiter = IpRateLimitBuckets::new(3);
+
+        assert_eq!(limiter.try_acquire(&ip, 0), Ok(()));
+        assert_eq!(limiter.try_acquire(&ip, 0), Ok(()));
+        assert_eq!(limiter.try_acquire(&ip, 0), Ok(()));
+        assert_eq!(limiter.try_acquire(&ip, 0), Err(1));
+        assert_eq!(limiter.try_acquire(&ip, 1000), Err(2));
+
+        // Other IP addresses have their own bucket
+        assert_eq!(limiter.try_acquire(&other_ip, 1000), Ok(()));
+
+        // One token every 20 seconds
+        assert_eq!(limiter.try_acquire(&ip, 21000), Ok(()));
+        assert_eq!(limiter.try_acquire(&ip, 21000), Err(3));
+
+        assert_eq!(limiter.rejected_total(), 3);
+
+        // Bucket capacity is limited
+        assert_eq!(limiter.try_acquire(&other_ip, 3600000), Ok(()));
+        assert_eq!(limiter.try_acquire(&other_ip, 3600000), Ok(()));
+        assert_eq!(limiter.try_acquire(&other_ip, 3600000), Ok(()));
+        assert_eq!(limiter.try_acquire(&other_ip, 3600000), Err(1));
+    }
+
+    #[test]
+    fn test_ip_rate_limit_disabled() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let mut limiter = IpRateLimitBuckets::new(0);
+
+        for _ in 0..100 {
+            assert_eq!(limiter.try_acquire(&ip, 0), Ok(()));
+        }
+
+        assert_eq!(limiter.rejected_total(), 0);
+    }
+}
@@ -0,0 +1,234 @@
+// IP screening of the incoming connections
+
+use std::{collections::HashMap, net::IpAddr};
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::log::Logger;
+
+use super::IpScreeningConfiguration;
+
+/// Max number of cached verdicts.
+/// When reached, the expired verdicts are removed.
+const IP_SCREENING_CACHE_MAX_SIZE: usize = 10000;
+
+/// Cache of the verdicts of the IP screening service
+pub struct IpScreeningCache {
+    /// Time to cache the verdicts (milliseconds)
+    ttl: i64,
+
+    /// Verdicts: IP -> (accepted, expiration timestamp)
+    verdicts: HashMap<IpAddr, (bool, i64)>,
+}
+
+impl IpScreeningCache {
+    /// Creates new IpScreeningCache
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl_seconds` - Time to cache the verdicts (seconds)
+    pub fn new(ttl_seconds: u32) -> IpScreeningCache {
+        IpScreeningCache {
+            ttl: (ttl_seconds as i64) * 1000,
+            verdicts: HashMap::new(),
+        }
+    }
+
+    /// Gets the cached verdict for an IP address
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address
+    /// * `now` - The current timestamp (Unix milliseconds)
+    ///
+    /// # Return value
+    ///
+    /// Returns the verdict (true to accept), or None if not cached or expired
+    pub fn get(&self, ip: &IpAddr, now: i64) -> Option<bool> {
+        match self.verdicts.get(ip) {
+            Some((accepted, expiration)) if *expiration > now => Some(*accepted),
+            _ => None,
+        }
+    }
+
+    /// Caches the verdict for an IP address
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address
+    /// * `accepted` - The verdict
+    /// * `now` - The current timestamp (Unix milliseconds)
+    pub fn set(&mut self, ip: IpAddr, accepted: bool, now: i64) {
+        if self.ttl <= 0 {
+            return;
+        }
+
+        if self.verdicts.len() >= IP_SCREENING_CACHE_MAX_SIZE {
+            self.verdicts.retain(|_, (_, expiration)| *expiration > now);
+
+            if self.verdicts.len() >= IP_SCREENING_CACHE_MAX_SIZE {
+                self.verdicts.clear();
+            }
+        }
+
+        self.verdicts.insert(ip, (accepted, now + self.ttl));
+    }
+}
+
+/// IP screening of the incoming connections,
+/// asking an external service before the handshake
+pub struct IpScreener {
+    /// Configuration
+    config: IpScreeningConfiguration,
+
+    /// HTTP client
+    #[cfg(feature = "ip-screening")]
+    client: reqwest::Client,
+
+    /// Cache of verdicts
+    cache: Mutex<IpScreeningCache>,
+}
+
+impl IpScreener {
+    /// Creates new IpScreener
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The IP screening configuration
+    pub fn new(config: &IpScreeningConfiguration) -> IpScreener {
+        IpScreener {
+            config: config.clone(),
+            #[cfg(feature = "ip-screening")]
+            client: reqwest::Client::new(),
+            cache: Mutex::new(IpScreeningCache::new(config.cache_seconds)),
+        }
+    }
+
+    /// Checks if a client IP address is allowed to connect
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `ip` - The client IP address
+    ///
+    /// # Return value
+    ///
+    /// Returns true to accept the connection, false to reject it
+    pub async fn check_ip(&self, logger: &Logger, ip: &IpAddr) -> bool {
+        if !self.config.is_enabled() {
+            return true;
+        }
+
+        let cache = self.cache.lock().await;
+        let cached = cache.get(ip, Utc::now().timestamp_millis());
+        drop(cache);
+
+        if let Some(accepted) = cached {
+            return accepted;
+        }
+
+        let accepted = match self.request_verdict(logger, ip).await {
+            Some(accepted) => accepted,
+            None => {
+                // Not cached, so the service is asked again for the next connection
+                return self.config.fail_open;
+            }
+        };
+
+        let mut cache = self.cache.lock().await;
+        cache.set(*ip, accepted, Utc::now().timestamp_millis());
+        drop(cache);
+
+        accepted
+    }
+
+    /// Requests the verdict for an IP address to the IP screening service
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `ip` - The client IP address
+    ///
+    /// # Return value
+    ///
+    /// Returns the verdict (true to accept), or None if the service failed
+    #[cfg(feature = "ip-screening")]
+    async fn request_verdict(&self, logger: &Logger, ip: &IpAddr) -> Option<bool> {
+        use crate::log_debug;
+        use reqwest::StatusCode;
+        use std::time::Duration;
+
+        log_debug!(
+            logger,
+            format!("POST {} | IP screening: {}", &self.config.url, ip)
+        );
+
+        let response = self
+            .client
+            .post(&self.config.url)
+            .header("rtmp-client-ip", ip.to_string())
+            .timeout(Duration::from_millis(self.config.timeout_ms as u64))
+            .send()
+            .await;
+
+        match response {
+            Ok(r) => match r.status() {
+                StatusCode::OK | StatusCode::NO_CONTENT => Some(true),
+                StatusCode::FORBIDDEN => Some(false),
+                status => {
+                    log_debug!(
+                        logger,
+                        format!("IP screening resulted in status code: {}", status.as_u16())
+                    );
+
+                    None
+                }
+            },
+            Err(e) => {
+                log_debug!(logger, format!("IP screening resulted in error: {}", e));
+
+                None
+            }
+        }
+    }
+
+    /// Requests the verdict for an IP address to the IP screening service.
+    /// Without the ip-screening feature, the service cannot be configured.
+    #[cfg(not(feature = "ip-screening"))]
+    async fn request_verdict(&self, _logger: &Logger, _ip: &IpAddr) -> Option<bool> {
+        None
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_screening_cache() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let mut cache = IpScreeningCache::new(10);
+
+        assert_eq!(cache.get(&ip, 0), None);
+
+        cache.set(ip, false, 1000);
+        cache.set(other_ip, true, 1000);
+
+        assert_eq!(cache.get(&ip, 5000), Some(false));
+        assert_eq!(cache.get(&other_ip, 5000), Some(true));
+        assert_eq!(cache.get(&ip, 11000), None);
+
+        // Caching disabled
+
+        let mut cache = IpScreeningCache::new(0);
+
+        cache.set(ip, false, 1000);
+
+        assert_eq!(cache.get(&ip, 1000), None);
+    }
+}
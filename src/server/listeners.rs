@@ -0,0 +1,245 @@
+// Listeners of the server, bound on startup or inherited from the parent process
+
+use std::net::SocketAddr;
+
+use tokio::{net::TcpListener, sync::watch};
+
+use crate::{log::Logger, log_error, log_info, log_warning};
+
+use super::RtmpServerConfiguration;
+
+/// Environment variable with the file descriptors of the listeners
+/// passed by the previous process on a binary upgrade (split by commas)
+pub const UPGRADE_LISTEN_FDS_ENV: &str = "RTMP_UPGRADE_LISTEN_FDS";
+
+/// First file descriptor passed by systemd socket activation
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Listening sockets inherited from the parent process,
+/// through systemd socket activation or a binary upgrade
+pub struct InheritedListeners {
+    /// Listeners, not yet assigned to an address
+    listeners: Vec<std::net::TcpListener>,
+}
+
+impl InheritedListeners {
+    /// Loads the inherited listeners from the environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    #[cfg(unix)]
+    pub fn load_from_env(logger: &Logger) -> InheritedListeners {
+        use std::os::fd::FromRawFd;
+
+        let mut fds: Vec<i32> = Vec::new();
+
+        // systemd socket activation (LISTEN_PID must match this process)
+
+        let listen_pid: u32 = std::env::var("LISTEN_PID")
+            .ok()
+            .and_then(|p| p.trim().parse().ok())
+            .unwrap_or(0);
+
+        if listen_pid == std::process::id() {
+            let listen_fds: i32 = std::env::var("LISTEN_FDS")
+                .ok()
+                .and_then(|n| n.trim().parse().ok())
+                .unwrap_or(0);
+
+            fds.extend(SD_LISTEN_FDS_START..SD_LISTEN_FDS_START.saturating_add(listen_fds.max(0)));
+        }
+
+        // Binary upgrade
+
+        if let Ok(upgrade_fds) = std::env::var(UPGRADE_LISTEN_FDS_ENV) {
+            for fd_str in upgrade_fds.split(',') {
+                let fd_str = fd_str.trim();
+
+                if fd_str.is_empty() {
+                    continue;
+                }
+
+                match fd_str.parse::<i32>() {
+                    Ok(fd) if fd >= 0 => {
+                        if !fds.contains(&fd) {
+                            fds.push(fd);
+                        }
+                    }
+                    _ => {
+                        log_warning!(
+                            logger,
+                            format!(
+                                "{} has an invalid file descriptor: {}",
+                                UPGRADE_LISTEN_FDS_ENV, fd_str
+                            )
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut listeners: Vec<std::net::TcpListener> = Vec::new();
+
+        for fd in fds {
+            // SAFETY: The file descriptor was passed to this process in order
+            // to be used as a listener, and nothing else in the process owns it
+            let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+
+            let socket = socket2::SockRef::from(&listener);
+
+            // Do not leak the listener to other child processes
+            if let Err(e) = socket.set_cloexec(true) {
+                log_warning!(
+                    logger,
+                    format!(
+                        "Could not set FD_CLOEXEC on inherited listener {}: {}",
+                        fd, e
+                    )
+                );
+            }
+
+            match (listener.local_addr(), listener.set_nonblocking(true)) {
+                (Ok(addr), Ok(())) => {
+                    log_info!(logger, format!("Inherited listener on {}", addr));
+                    listeners.push(listener);
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    log_warning!(
+                        logger,
+                        format!("Ignored inherited file descriptor {}: {}", fd, e)
+                    );
+                }
+            }
+        }
+
+        InheritedListeners { listeners }
+    }
+
+    /// Loads the inherited listeners from the environment variables
+    /// (not supported on this platform)
+    #[cfg(not(unix))]
+    pub fn load_from_env(_logger: &Logger) -> InheritedListeners {
+        InheritedListeners {
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Takes the inherited listener bound to any of the addresses
+    ///
+    /// # Arguments
+    ///
+    /// * `addrs` - The resolved addresses to listen on
+    ///
+    /// # Return value
+    ///
+    /// Returns the listener, or None if no listener was inherited for the addresses
+    pub fn take(&mut self, addrs: &[SocketAddr]) -> Option<std::net::TcpListener> {
+        let index = self.listeners.iter().position(|l| match l.local_addr() {
+            Ok(local_addr) => addrs.contains(&local_addr),
+            Err(_) => false,
+        })?;
+
+        Some(self.listeners.swap_remove(index))
+    }
+
+    /// Gets the number of listeners not yet assigned to an address
+    pub fn len(&self) -> usize {
+        self.listeners.len()
+    }
+
+    /// Checks if all the listeners were assigned to an address
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+}
+
+/// Listener bound to an address
+pub struct BoundListener {
+    /// The address (host:port)
+    pub addr: String,
+
+    /// The listener
+    pub listener: TcpListener,
+}
+
+/// Listeners of the server
+pub struct ServerListeners {
+    /// Listeners for RTMP
+    pub tcp: Vec<BoundListener>,
+
+    /// Listeners for RTMPS
+    pub tls: Vec<BoundListener>,
+}
+
+/// Binds the listeners of the server, reusing the inherited ones
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The server configuration
+/// * `inherited` - The listeners inherited from the parent process
+///
+/// # Return value
+///
+/// Returns the listeners, or an error if any of the addresses could not be bound
+pub async fn bind_server_listeners(
+    logger: &Logger,
+    config: &RtmpServerConfiguration,
+    mut inherited: InheritedListeners,
+) -> Result<ServerListeners, ()> {
+    let mut tcp: Vec<BoundListener> = Vec::new();
+
+    for addr in config.get_tcp_listen_addrs() {
+        tcp.push(bind_server_listener(logger, config, &mut inherited, addr).await?);
+    }
+
+    let mut tls: Vec<BoundListener> = Vec::new();
+
+    if config.tls.is_enabled() {
+        for addr in config.tls.get_tcp_listen_addrs() {
+            tls.push(bind_server_listener(logger, config, &mut inherited, addr).await?);
+        }
+    }
+
+    if !inherited.is_empty() {
+        log_warning!(
+            logger,
+            format!(
+                "{} inherited listeners do not match any configured address and were closed",
+                inherited.len()
+            )
+        );
+    }
+
+    Ok(ServerListeners { tcp, tls })
+}
+
+/// Binds a listener of the server
+async fn bind_server_listener(
+    logger: &Logger,
+    config: &RtmpServerConfiguration,
+    inherited: &mut InheritedListeners,
+    addr: String,
+) -> Result<BoundListener, ()> {
+    match super::bind_tcp_listener(&addr, config.ipv6_only, inherited).await {
+        Ok(listener) => Ok(BoundListener { addr, listener }),
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not create TCP listener on {}: {}", addr, e)
+            );
+            Err(())
+        }
+    }
+}
+
+/// Waits for the signal to stop accepting connections
+///
+/// # Arguments
+///
+/// * `stop_receiver` - Receiver to stop accepting connections
+pub async fn wait_for_stop(stop_receiver: &mut watch::Receiver<bool>) {
+    _ = stop_receiver.wait_for(|stop| *stop).await;
+}
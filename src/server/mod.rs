@@ -1,70 +1,142 @@
 // RTMP server
 
+mod accept_backoff;
 mod config;
+mod config_overrides;
 mod connection_handle;
 mod context;
+mod fd_limit;
+mod identity;
 mod ip_count;
+mod ip_rate_limit;
+mod ip_screening;
+mod listeners;
+mod persistence;
 mod session_id_generator;
+mod socket_options;
 mod status;
 mod tcp;
+#[cfg(feature = "tls")]
 mod tls;
+mod upgrade;
 mod utils;
 
 use std::sync::Arc;
 
+pub use accept_backoff::*;
 pub use config::*;
+pub use config_overrides::*;
 pub use connection_handle::*;
 pub use context::*;
+pub use fd_limit::*;
+pub use identity::*;
 pub use ip_count::*;
+pub use ip_rate_limit::*;
+pub use ip_screening::*;
+pub use listeners::*;
+pub use persistence::*;
 pub use session_id_generator::*;
+pub use socket_options::*;
 pub use status::*;
 pub use tcp::*;
+#[cfg(feature = "tls")]
 pub use tls::*;
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
+pub use upgrade::*;
 pub use utils::*;
 
-use crate::log::Logger;
+use crate::{chaos::ChaosInjector, geoip::GeoIpDatabase, log::Logger, log_warning};
 
 /// Runs the RTMP server
-pub async fn run_server(logger: Logger, server_context: RtmpServerContext) {
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `server_context` - The server context
+/// * `listeners` - The listeners, already bound
+/// * `connections` - Counter of the active connections
+/// * `geoip` - Geolocation of the client IP addresses
+/// * `stop_receiver` - Receiver to stop accepting connections
+pub async fn run_server(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    listeners: ServerListeners,
+    connections: Arc<ActiveConnections>,
+    geoip: Arc<GeoIpDatabase>,
+    stop_receiver: watch::Receiver<bool>,
+) {
     let ip_counter = Arc::new(Mutex::new(IpConnectionCounter::new(
         server_context.config.as_ref(),
     )));
-    let session_id_generator = Arc::new(Mutex::new(SessionIdGenerator::new()));
+    let ip_rate_limiter = Arc::new(IpConnectionRateLimiter::new(
+        server_context.config.max_connections_per_minute_per_ip,
+    ));
+    let session_id_generator = Arc::new(SessionIdGenerator::new());
+    let ip_screener = Arc::new(IpScreener::new(&server_context.config.ip_screening));
+    let chaos = Arc::new(ChaosInjector::new(&server_context.config.chaos));
+    let open_sockets = Arc::new(OpenSocketsTracker::new(
+        get_open_files_limit(),
+        server_context.config.fd_reserved,
+    ));
+
+    if server_context.config.chaos.is_enabled() {
+        log_warning!(
+            logger,
+            "Chaos testing mode is enabled: outbound writes will be delayed, dropped or disconnected. Do not use it in production."
+        );
+    }
 
     let extended_context = RtmpServerContextExtended {
         config: server_context.config.clone(),
         status: server_context.status,
         control_key_validator_sender: server_context.control_key_validator_sender,
+        slate: server_context.slate,
+        cluster: server_context.cluster,
         ip_counter,
+        ip_rate_limiter,
         session_id_generator,
+        ip_screener,
+        geoip,
+        connections,
+        chaos,
+        open_sockets,
     };
 
-    let (end_notifier_tcp, mut end_receiver_tcp) = tokio::sync::mpsc::channel::<()>(1);
+    // One accept loop per endpoint
 
-    tcp_server(
-        Arc::new(logger.make_child_logger("[SERVER:TCP] ")),
-        extended_context.clone(),
-        end_notifier_tcp,
-    );
+    let servers_count = listeners.tcp.len() + usize::from(!listeners.tls.is_empty());
 
-    if server_context.config.tls.is_enabled() {
-        let (end_notifier_tls, mut end_receiver_tls) = tokio::sync::mpsc::channel::<()>(1);
+    let (end_notifier, mut end_receiver) = tokio::sync::mpsc::channel::<()>(servers_count + 1);
 
+    for listener in listeners.tcp {
+        tcp_server(
+            Arc::new(logger.make_child_logger("[SERVER:TCP] ")),
+            extended_context.clone(),
+            listener,
+            stop_receiver.clone(),
+            end_notifier.clone(),
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    if !listeners.tls.is_empty() {
         tls_server(
             Arc::new(logger.make_child_logger("[SERVER:TLS] ")),
             extended_context.clone(),
-            end_notifier_tls,
+            listeners.tls,
+            stop_receiver,
+            end_notifier.clone(),
         );
+    }
+
+    drop(end_notifier);
 
-        end_receiver_tls
+    // Wait for all the servers to end
+
+    for _ in 0..servers_count {
+        end_receiver
             .recv()
             .await
-            .expect("could not receive signal from TLS server thread");
+            .expect("could not receive signal from server thread");
     }
-
-    end_receiver_tcp
-        .recv()
-        .await
-        .expect("could not receive signal from TCP server thread");
 }
@@ -0,0 +1,354 @@
+// Persistent state of the server, to recover from crashes
+
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::{
+    callback::{make_stop_callback, CallbackCorrelation},
+    control::ControlKeyValidationRequest,
+    log::Logger,
+    log_error, log_info, log_warning,
+    utils::{get_env_string, get_env_u32},
+};
+
+#[cfg(feature = "control")]
+use std::sync::Arc;
+#[cfg(feature = "control")]
+use tokio::sync::Mutex;
+
+#[cfg(feature = "control")]
+use crate::control::ControlClientStatus;
+
+use super::{PublishStatisticsSummary, RtmpServerContext};
+
+/// Reason sent to the control server and callbacks for the orphaned publications
+const ORPHANED_PUBLISH_REASON: &str = "Server restarted";
+
+/// Interval to check if the control client is connected (seconds)
+#[cfg(feature = "control")]
+const CONTROL_CONNECTED_CHECK_INTERVAL_SECONDS: u64 = 1;
+
+/// State persistence configuration
+#[derive(Clone)]
+pub struct StatePersistenceConfiguration {
+    /// Path of the state file (empty to disable the persistence)
+    pub file: String,
+
+    /// Interval to save the state (seconds)
+    pub save_interval_seconds: u32,
+}
+
+impl StatePersistenceConfiguration {
+    /// Loads state persistence configuration
+    /// from environment variables
+    pub fn load_from_env() -> StatePersistenceConfiguration {
+        let file = get_env_string("STATE_FILE", "");
+        let save_interval_seconds = get_env_u32("STATE_SAVE_INTERVAL", 5).max(1);
+
+        StatePersistenceConfiguration {
+            file,
+            save_interval_seconds,
+        }
+    }
+
+    /// Checks if the state persistence is enabled
+    pub fn is_enabled(&self) -> bool {
+        !self.file.is_empty()
+    }
+}
+
+/// Persisted state of a published channel.
+/// The streaming key is not stored, not even hashed,
+/// since the hashes are salted for each server instance.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PersistedChannelState {
+    /// The channel
+    pub channel: String,
+
+    /// The stream ID
+    pub stream_id: String,
+
+    /// ID of the publisher session
+    pub session_id: u64,
+
+    /// Unique ID of the publication (UUID)
+    pub publish_id: String,
+
+    /// Start time of the publication (Unix milliseconds)
+    pub started_at: i64,
+
+    /// Statistics of the publication, when the state was saved
+    pub stats: Option<PublishStatisticsSummary>,
+}
+
+/// Persisted state of the server
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersistedServerState {
+    /// ID of the server instance that saved the state
+    pub instance_id: String,
+
+    /// Time the state was saved (Unix milliseconds)
+    pub saved_at: i64,
+
+    /// Published channels
+    pub channels: Vec<PersistedChannelState>,
+}
+
+/// Collects the state of the channels published on this server.
+/// Streams pulled from other nodes are not included.
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+pub async fn collect_server_state(server_context: &RtmpServerContext) -> PersistedServerState {
+    let now = Utc::now().timestamp_millis();
+    let mut channels: Vec<PersistedChannelState> = Vec::new();
+
+    for shard in server_context.status.shards() {
+        let shard_channels: Vec<_> = shard
+            .lock()
+            .await
+            .channels
+            .iter()
+            .map(|(channel, channel_mu)| (channel.clone(), channel_mu.clone()))
+            .collect();
+
+        for (channel, channel_mu) in shard_channels {
+            let channel_status = channel_mu.lock().await;
+
+            if !channel_status.has_stream() || channel_status.relay_origin.is_some() {
+                continue;
+            }
+
+            channels.push(PersistedChannelState {
+                channel,
+                stream_id: channel_status.stream_id.clone().unwrap_or_default(),
+                session_id: channel_status.publisher_id.unwrap_or(0),
+                publish_id: channel_status.publish_id.clone().unwrap_or_default(),
+                started_at: channel_status
+                    .publish_stats
+                    .as_ref()
+                    .map(|s| s.start_time())
+                    .unwrap_or(now),
+                stats: channel_status
+                    .publish_stats
+                    .as_ref()
+                    .map(|s| s.summary(now)),
+            });
+        }
+    }
+
+    PersistedServerState {
+        instance_id: server_context.config.identity.instance_id.clone(),
+        saved_at: now,
+        channels,
+    }
+}
+
+/// Saves the state of the server, replacing the previous one
+///
+/// # Arguments
+///
+/// * `path` - Path of the state file
+/// * `state` - The state
+pub async fn save_server_state(path: &str, state: &PersistedServerState) -> Result<(), String> {
+    let data = serde_json::to_vec(state).map_err(|e| e.to_string())?;
+
+    // Write to a temporary file first, so a crash never leaves a partial state
+
+    let tmp_path = format!("{}.tmp", path);
+
+    if let Err(e) = tokio::fs::write(&tmp_path, &data).await {
+        return Err(format!("Could not write state file: {}", e));
+    }
+
+    if let Err(e) = tokio::fs::rename(&tmp_path, path).await {
+        _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(format!("Could not write state file: {}", e));
+    }
+
+    Ok(())
+}
+
+/// Loads the state of the server
+///
+/// # Arguments
+///
+/// * `path` - Path of the state file
+///
+/// # Return value
+///
+/// Returns the state, or None if the file does not exist
+pub async fn load_server_state(path: &str) -> Result<Option<PersistedServerState>, String> {
+    let data = match tokio::fs::read(path).await {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(format!("Could not read state file: {}", e));
+        }
+    };
+
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|e| format!("Invalid state file: {}", e))
+}
+
+/// Spawns a task to periodically save the state of the server
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `stop_receiver` - Receiver to stop saving the state (binary upgrade)
+pub fn spawn_task_persist_server_state(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    mut stop_receiver: watch::Receiver<bool>,
+) {
+    tokio::spawn(async move {
+        let config = &server_context.config.state_persistence;
+        let interval = Duration::from_secs(config.save_interval_seconds as u64);
+
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = stop_receiver.wait_for(|stop| *stop) => {
+                    // The new process owns the state file from now on
+                    return;
+                }
+            }
+
+            let state = collect_server_state(&server_context).await;
+
+            if let Err(e) = save_server_state(&config.file, &state).await {
+                log_error!(logger, e);
+            }
+        }
+    });
+}
+
+/// Spawns a task to report the publications orphaned by a crash
+/// or restart of the server, as if they had ended.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `control_client_status` - Status of the control client, if the control server is used
+/// * `state` - The state saved by the previous instance
+pub fn spawn_task_report_orphaned_publishes(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    #[cfg(feature = "control")] control_client_status: Option<Arc<Mutex<ControlClientStatus>>>,
+    state: PersistedServerState,
+) {
+    tokio::spawn(async move {
+        if state.channels.is_empty() {
+            return;
+        }
+
+        log_info!(
+            logger,
+            format!(
+                "Reporting {} publications orphaned by instance {}",
+                state.channels.len(),
+                state.instance_id
+            )
+        );
+
+        // Wait for the control client to connect, so the messages are not lost
+
+        #[cfg(feature = "control")]
+        if let Some(status) = &control_client_status {
+            while !status.lock().await.connected {
+                tokio::time::sleep(Duration::from_secs(
+                    CONTROL_CONNECTED_CHECK_INTERVAL_SECONDS,
+                ))
+                .await;
+            }
+        }
+
+        for orphan in state.channels {
+            log_warning!(
+                logger,
+                format!(
+                    "Orphaned publication | Channel: {} | Stream ID: {} | Publish ID: {}",
+                    orphan.channel, orphan.stream_id, orphan.publish_id
+                )
+            );
+
+            match &server_context.control_key_validator_sender {
+                Some(sender) => {
+                    _ = sender
+                        .send(ControlKeyValidationRequest::PublishEnd {
+                            channel: orphan.channel,
+                            stream_id: orphan.stream_id,
+                            stats: orphan.stats,
+                            publisher: None,
+                        })
+                        .await;
+                }
+                None => {
+                    // The key is never stored, so the stop event of an orphaned publication carries no key.
+                    // The receivers can identify it by the channel, stream ID and publish ID.
+                    make_stop_callback(
+                        &logger,
+                        server_context.config.get_callback_config(&orphan.channel),
+                        &orphan.channel,
+                        "",
+                        &CallbackCorrelation {
+                            session_id: orphan.session_id,
+                            publish_id: orphan.publish_id.clone(),
+                        },
+                        &orphan.stream_id,
+                        orphan.stats.as_ref(),
+                        Some(ORPHANED_PUBLISH_REASON),
+                    )
+                    .await;
+                }
+            }
+        }
+    });
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_load_server_state() {
+        let path = std::env::temp_dir().join(format!(
+            "rtmp-server-state-test-{}.json",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        assert_eq!(load_server_state(path).await.unwrap(), None);
+
+        let state = PersistedServerState {
+            instance_id: "instance".to_string(),
+            saved_at: 1000,
+            channels: vec![PersistedChannelState {
+                channel: "channel".to_string(),
+                stream_id: "stream".to_string(),
+                session_id: 1,
+                publish_id: "publish".to_string(),
+                started_at: 500,
+                stats: Some(PublishStatisticsSummary::default()),
+            }],
+        };
+
+        save_server_state(path, &state).await.unwrap();
+
+        assert_eq!(load_server_state(path).await.unwrap(), Some(state));
+
+        _ = tokio::fs::remove_file(path).await;
+    }
+}
@@ -1,21 +1,106 @@
 // Logic to generate unique session IDs
 
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
 /// Session ID generator
+/// IDs are generated with an atomic counter, so the accept path does not need a lock
 pub struct SessionIdGenerator {
-    // Next ID
-    pub next_id: u64,
+    /// Next ID
+    next_id: AtomicU64,
+
+    /// True if the counter wrapped around,
+    /// meaning the generated IDs may be still in use by long-lived sessions
+    wrapped: AtomicBool,
 }
 
 impl SessionIdGenerator {
     /// Creates new SessionIdGenerator
     pub fn new() -> SessionIdGenerator {
-        SessionIdGenerator { next_id: 1 }
+        SessionIdGenerator::new_starting_at(1)
+    }
+
+    /// Creates new SessionIdGenerator, starting at an ID
+    ///
+    /// # Arguments
+    ///
+    /// * `first_id` - The first ID to generate
+    pub fn new_starting_at(first_id: u64) -> SessionIdGenerator {
+        SessionIdGenerator {
+            next_id: AtomicU64::new(first_id),
+            wrapped: AtomicBool::new(false),
+        }
+    }
+
+    /// Generates a new ID.
+    /// The ID 0 is never generated.
+    pub fn generate_id(&self) -> u64 {
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+            if id != 0 {
+                return id;
+            }
+
+            self.wrapped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Checks if the counter wrapped around.
+    /// If so, the generated IDs must be checked against the IDs in use.
+    pub fn has_wrapped(&self) -> bool {
+        self.wrapped.load(Ordering::Relaxed)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_id_sequence() {
+        let generator = SessionIdGenerator::new();
+
+        assert_eq!(generator.generate_id(), 1);
+        assert_eq!(generator.generate_id(), 2);
+        assert_eq!(generator.generate_id(), 3);
+        assert!(!generator.has_wrapped());
+    }
+
+    #[test]
+    fn test_generate_id_wrap_around() {
+        let generator = SessionIdGenerator::new_starting_at(u64::MAX);
+
+        assert_eq!(generator.generate_id(), u64::MAX);
+        assert!(!generator.has_wrapped());
+
+        // The ID 0 is skipped
+        assert_eq!(generator.generate_id(), 1);
+        assert!(generator.has_wrapped());
+    }
+
+    #[test]
+    fn test_generate_id_concurrent() {
+        let generator = std::sync::Arc::new(SessionIdGenerator::new());
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let generator = generator.clone();
+                std::thread::spawn(move || {
+                    (0..1000)
+                        .map(|_| generator.generate_id())
+                        .collect::<Vec<u64>>()
+                })
+            })
+            .collect();
+
+        let mut ids: Vec<u64> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+
+        ids.sort();
+        ids.dedup();
 
-    /// Generates a new unique ID
-    pub fn generate_id(&mut self) -> u64 {
-        let id = self.next_id;
-        self.next_id = self.next_id.wrapping_add(1);
-        id
+        assert_eq!(ids.len(), 4000);
     }
 }
@@ -0,0 +1,116 @@
+// TCP socket options
+
+use std::{net::SocketAddr, time::Duration};
+
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+use tokio::net::{lookup_host, TcpListener, TcpStream};
+
+use crate::{log::Logger, log_warning};
+
+use super::{InheritedListeners, TcpSocketConfiguration};
+
+/// Max length of the queue of pending connections
+const TCP_LISTEN_BACKLOG: i32 = 1024;
+
+/// Creates a TCP listener bound to an address.
+/// If a listener for the address was inherited from the parent process, it is reused.
+///
+/// # Arguments
+///
+/// * `listen_addr` - The address to listen on (host:port)
+/// * `ipv6_only` - True to only accept IPv6 connections on IPv6 addresses
+/// * `inherited` - The listeners inherited from the parent process
+///
+/// # Return value
+///
+/// Returns the listener, or the error of the last address that could not be bound
+pub async fn bind_tcp_listener(
+    listen_addr: &str,
+    ipv6_only: bool,
+    inherited: &mut InheritedListeners,
+) -> std::io::Result<TcpListener> {
+    let addrs: Vec<SocketAddr> = lookup_host(listen_addr).await?.collect();
+
+    if let Some(listener) = inherited.take(&addrs) {
+        return TcpListener::from_std(listener);
+    }
+
+    let mut last_err: Option<std::io::Error> = None;
+
+    for addr in addrs {
+        match bind_tcp_listener_addr(addr, ipv6_only) {
+            Ok(l) => {
+                return Ok(l);
+            }
+            Err(e) => {
+                last_err = Some(e);
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+/// Creates a TCP listener bound to a resolved socket address
+fn bind_tcp_listener_addr(addr: SocketAddr, ipv6_only: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(TCP_LISTEN_BACKLOG)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// Applies the configured socket options to an accepted TCP connection
+/// Failures are logged as warnings, since the connection can still be used
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `connection` - The accepted TCP connection
+/// * `config` - The TCP socket configuration
+pub fn apply_tcp_socket_options(
+    logger: &Logger,
+    connection: &TcpStream,
+    config: &TcpSocketConfiguration,
+) {
+    if let Err(e) = connection.set_nodelay(config.nodelay) {
+        log_warning!(logger, format!("Could not set TCP_NODELAY: {}", e));
+    }
+
+    let socket = SockRef::from(connection);
+
+    if config.keepalive {
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(config.keepalive_time as u64))
+            .with_interval(Duration::from_secs(config.keepalive_interval as u64))
+            .with_retries(config.keepalive_retries);
+
+        if let Err(e) = socket.set_tcp_keepalive(&keepalive) {
+            log_warning!(logger, format!("Could not set SO_KEEPALIVE: {}", e));
+        }
+    }
+
+    if config.send_buffer_size > 0 {
+        if let Err(e) = socket.set_send_buffer_size(config.send_buffer_size) {
+            log_warning!(logger, format!("Could not set SO_SNDBUF: {}", e));
+        }
+    }
+
+    if config.recv_buffer_size > 0 {
+        if let Err(e) = socket.set_recv_buffer_size(config.recv_buffer_size) {
+            log_warning!(logger, format!("Could not set SO_RCVBUF: {}", e));
+        }
+    }
+}
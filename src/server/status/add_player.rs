@@ -3,9 +3,9 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::{
-    server::{RtmpChannelStatus, RtmpPlayerStatus, RtmpServerContext},
-    session::SessionReadThreadContext,
-    utils::string_compare_time_safe,
+    server::{play_slate, RtmpChannelStatus, RtmpPlayerStatus, RtmpServerContext},
+    session::{RtmpSessionMessage, SessionReadThreadContext},
+    utils::StreamKeyHash,
 };
 
 /// Options to add a player to a channel
@@ -18,6 +18,28 @@ pub struct AddPlayerOptions {
 
     /// Receive video
     pub receive_video: bool,
+
+    /// Only live streams. If the channel is not publishing, the player is not added.
+    pub live_only: bool,
+
+    /// Low latency mode: skip the GOP cache and start at the next key frame
+    pub low_latency: bool,
+}
+
+/// Result of adding a player to a channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddPlayerResult {
+    /// The player was added
+    Added,
+
+    /// The provided key is invalid
+    InvalidKey,
+
+    /// The channel reached the max number of concurrent players
+    PlayerLimitReached,
+
+    /// Live stream required, but the channel is not publishing
+    NotPublishing,
 }
 
 /// Adds a player to a channel
@@ -32,15 +54,17 @@ pub struct AddPlayerOptions {
 ///
 /// # Return value
 ///
-/// Returns true if success, false if cannot add the player (invalid key)
+/// Returns the result, indicating if the player was added
 pub async fn add_player(
     server_context: &RtmpServerContext,
     session_context: &mut SessionReadThreadContext,
     channel: &str,
     key: &str,
     player_options: AddPlayerOptions,
-) -> bool {
-    let mut status = server_context.status.lock().await;
+) -> AddPlayerResult {
+    let provided_key = StreamKeyHash::new(key);
+
+    let mut status = server_context.status.lock_shard(channel).await;
 
     match status.channels.get_mut(channel) {
         Some(c) => {
@@ -49,14 +73,31 @@ pub async fn add_player(
 
             let mut channel_status = channel_mu.lock().await;
 
+            let max_players = server_context
+                .status
+                .config_overrides
+                .get_max_players_per_channel(&server_context.config, channel);
+
+            if channel_status.is_player_limit_reached(max_players) {
+                return AddPlayerResult::PlayerLimitReached;
+            }
+
+            if player_options.live_only && !channel_status.publishing {
+                return AddPlayerResult::NotPublishing;
+            }
+
             let player_status = RtmpPlayerStatus {
-                provided_key: key.to_string(),
+                provided_key: provided_key.clone(),
+                ip: session_context.ip,
                 message_sender: session_context.session_msg_sender.clone(),
                 gop_clear: player_options.gop_clear,
                 paused: false,
                 idle: !channel_status.publishing,
                 receive_audio: player_options.receive_audio,
                 receive_video: player_options.receive_video,
+                low_latency: player_options.low_latency,
+                bandwidth: session_context.bandwidth.clone(),
+                lag: session_context.player_lag.clone(),
             };
 
             channel_status
@@ -65,21 +106,24 @@ pub async fn add_player(
 
             if !channel_status.publishing {
                 // Not publishing yet, stay idle until a publisher appears
-                return true;
+                _ = session_context
+                    .session_msg_sender
+                    .send(RtmpSessionMessage::PlayIdle)
+                    .await;
+                play_slate(server_context, &session_context.session_msg_sender).await;
+                return AddPlayerResult::Added;
             }
 
-            if let Some(channel_key) = &channel_status.key {
-                if !string_compare_time_safe(channel_key, key) {
-                    // If the key is invalid, remove the player
-                    channel_status.players.remove(&session_context.id);
-                    return false;
-                }
+            if !channel_status.is_player_key_valid(&provided_key) {
+                // If the key is invalid, remove the player
+                channel_status.players.remove(&session_context.id);
+                return AddPlayerResult::InvalidKey;
             }
 
             let publish_status_mu = match &channel_status.publish_status {
                 Some(s) => s,
                 None => {
-                    return true;
+                    return AddPlayerResult::Added;
                 }
             };
 
@@ -87,7 +131,11 @@ pub async fn add_player(
 
             let mut publish_status = publish_status_mu.lock().await;
 
-            let player_start_msg = publish_status.get_play_start_message();
+            let player_start_msg = publish_status.get_play_start_message(
+                channel_status.subscribe_packets(),
+                player_options.low_latency,
+                channel_status.publisher_bandwidth.clone(),
+            );
 
             if player_options.gop_clear {
                 publish_status.clear_gop();
@@ -100,19 +148,28 @@ pub async fn add_player(
                 .send(player_start_msg)
                 .await;
 
-            true
+            AddPlayerResult::Added
         }
         None => {
-            let mut new_channel_status = RtmpChannelStatus::new();
+            if player_options.live_only {
+                return AddPlayerResult::NotPublishing;
+            }
+
+            let mut new_channel_status =
+                RtmpChannelStatus::new(server_context.config.player_packet_buffer_size);
 
             let player_status = RtmpPlayerStatus {
-                provided_key: key.to_string(),
+                provided_key: provided_key.clone(),
+                ip: session_context.ip,
                 message_sender: session_context.session_msg_sender.clone(),
                 gop_clear: player_options.gop_clear,
                 paused: false,
                 idle: true,
                 receive_audio: player_options.receive_audio,
                 receive_video: player_options.receive_video,
+                low_latency: player_options.low_latency,
+                bandwidth: session_context.bandwidth.clone(),
+                lag: session_context.player_lag.clone(),
             };
 
             new_channel_status
@@ -125,7 +182,15 @@ pub async fn add_player(
 
             // Since this channel is brand new, no publishing, so the player remains idle
 
-            true
+            drop(status);
+
+            _ = session_context
+                .session_msg_sender
+                .send(RtmpSessionMessage::PlayIdle)
+                .await;
+            play_slate(server_context, &session_context.session_msg_sender).await;
+
+            AddPlayerResult::Added
         }
     }
 }
@@ -0,0 +1,172 @@
+// History of the lifecycle events of the channels, for debugging
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::{log::Logger, log_info};
+
+/// Max number of channels to keep the history for.
+/// When reached, the history of the channel with the oldest event is removed.
+const CHANNEL_EVENTS_MAX_CHANNELS: usize = 10000;
+
+/// Lifecycle event of a channel
+#[derive(Clone, Serialize, Debug)]
+pub struct ChannelEvent {
+    /// Timestamp (Unix milliseconds)
+    pub timestamp: i64,
+
+    /// Event name (eg: `publish-start`, `player-join` or `publish-killed`)
+    pub event: &'static str,
+
+    /// ID of the session (publisher or player)
+    pub session_id: u64,
+
+    /// Details of the event (eg: the reason of the kill)
+    pub details: String,
+}
+
+/// History of the last lifecycle events of each channel
+pub struct ChannelEventHistory {
+    /// Max number of events to keep per channel (0 to disable)
+    size: usize,
+
+    /// Events of each channel
+    channels: Mutex<HashMap<String, VecDeque<ChannelEvent>>>,
+}
+
+impl ChannelEventHistory {
+    /// Creates new ChannelEventHistory
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Max number of events to keep per channel (0 to disable)
+    pub fn new(size: usize) -> ChannelEventHistory {
+        ChannelEventHistory {
+            size,
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks if the history is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    /// Records an event of a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    /// * `event` - Event name
+    /// * `session_id` - ID of the session (publisher or player)
+    /// * `details` - Details of the event
+    pub async fn record(&self, channel: &str, event: &'static str, session_id: u64, details: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut channels = self.channels.lock().await;
+
+        if !channels.contains_key(channel) && channels.len() >= CHANNEL_EVENTS_MAX_CHANNELS {
+            let oldest = channels
+                .iter()
+                .min_by_key(|(_, events)| events.back().map(|e| e.timestamp).unwrap_or(0))
+                .map(|(c, _)| c.clone());
+
+            if let Some(oldest) = oldest {
+                channels.remove(&oldest);
+            }
+        }
+
+        let events = channels.entry(channel.to_string()).or_default();
+
+        while events.len() >= self.size {
+            events.pop_front();
+        }
+
+        events.push_back(ChannelEvent {
+            timestamp: Utc::now().timestamp_millis(),
+            event,
+            session_id,
+            details: details.to_string(),
+        });
+    }
+
+    /// Gets the recorded events of a channel, from oldest to newest
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub async fn get_events(&self, channel: &str) -> Vec<ChannelEvent> {
+        let channels = self.channels.lock().await;
+
+        match channels.get(channel) {
+            Some(events) => events.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Logs the recorded events of all the channels
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub async fn dump(&self, logger: &Logger) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let channels = self.channels.lock().await;
+
+        for (channel, events) in channels.iter() {
+            for e in events {
+                log_info!(
+                    logger,
+                    format!(
+                        "[EVENT HISTORY] Channel {} | {} | {} | Session #{} | {}",
+                        channel, e.timestamp, e.event, e.session_id, &e.details
+                    )
+                );
+            }
+        }
+    }
+}
+
+// Tests
+
+#[cfg(all(test, any(feature = "control", feature = "redis")))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_channel_event_history() {
+        let history = ChannelEventHistory::new(2);
+
+        history.record("a", "publish-start", 1, "").await;
+        history.record("a", "player-join", 2, "").await;
+        history.record("a", "publish-killed", 1, "reason").await;
+        history.record("b", "player-join", 3, "").await;
+
+        let events = history.get_events("a").await;
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "player-join");
+        assert_eq!(events[1].event, "publish-killed");
+        assert_eq!(events[1].details, "reason");
+
+        assert_eq!(history.get_events("b").await.len(), 1);
+        assert!(history.get_events("c").await.is_empty());
+
+        // Disabled
+
+        let history = ChannelEventHistory::new(0);
+
+        history.record("a", "publish-start", 1, "").await;
+
+        assert!(history.get_events("a").await.is_empty());
+    }
+}
@@ -1,16 +1,39 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::Instant};
 
-use tokio::sync::{mpsc::Sender, Mutex};
+#[cfg(any(feature = "control", feature = "redis"))]
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::{broadcast, mpsc::Sender, oneshot, Mutex};
 
 use crate::{
-    rtmp::{RtmpPacket, RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO},
-    session::{RtmpSessionMessage, RtmpSessionPublishStreamStatus},
+    rtmp::{RtmpPacket, StreamMetadata, RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO},
+    server::{PublishStatistics, PublishStreamFlags, PublisherResumeEnd},
+    session::{
+        RtmpConnectInfo, RtmpPacketReceiver, RtmpSessionMessage, RtmpSessionPublishStreamStatus,
+        SessionBandwidth, SessionPlayerLag,
+    },
+    utils::StreamKeyHash,
 };
 
+/// Information of the publisher of a channel
+#[cfg_attr(not(feature = "control"), allow(dead_code))]
+#[derive(Clone)]
+pub struct RtmpPublisherInfo {
+    /// IP address of the publisher
+    pub ip: IpAddr,
+
+    /// Client information sent by the publisher on connect
+    pub connect_info: RtmpConnectInfo,
+}
+
 /// Status of an RTMP player
 pub struct RtmpPlayerStatus {
-    /// Provided stream key
-    pub provided_key: String,
+    /// Hash of the provided stream key
+    pub provided_key: StreamKeyHash,
+
+    /// IP address of the player
+    pub ip: IpAddr,
 
     /// Message sender to communicate with the player session
     pub message_sender: Sender<RtmpSessionMessage>,
@@ -29,12 +52,67 @@ pub struct RtmpPlayerStatus {
 
     /// True to receive video
     pub receive_video: bool,
+
+    /// True if the player requested the low latency mode
+    pub low_latency: bool,
+
+    /// Bandwidth usage of the player session
+    pub bandwidth: Arc<SessionBandwidth>,
+
+    /// Diagnostics of the delivery of the stream to the player
+    #[cfg_attr(not(any(feature = "control", feature = "redis")), allow(dead_code))]
+    pub lag: Arc<SessionPlayerLag>,
+}
+
+/// Bandwidth usage of a channel
+#[derive(Default)]
+pub struct RtmpChannelBandwidthStats {
+    /// Bytes received from the publisher
+    pub bytes_in: u64,
+
+    /// Bytes sent to the players (including players no longer connected)
+    pub bytes_out: u64,
+
+    /// Input bit rate (bits per second)
+    pub bit_rate_in: u64,
+
+    /// Output bit rate (bits per second)
+    pub bit_rate_out: u64,
+
+    /// Round trip time of the publisher (milliseconds), if measured
+    pub publisher_rtt: Option<u32>,
+}
+
+/// Recording of a channel in progress
+pub struct RtmpChannelRecording {
+    /// Path of the recording file
+    pub path: String,
+
+    /// Sender to stop the recording (dropping it also stops the recording)
+    #[cfg_attr(not(any(feature = "control", feature = "redis")), allow(dead_code))]
+    pub stop_sender: Sender<()>,
+}
+
+/// Rotation of the key the players must provide to play a channel.
+/// The publisher keeps using the original channel key.
+pub struct RtmpChannelKeyRotation {
+    /// Hash of the key the players must provide
+    pub key: StreamKeyHash,
+
+    /// Hash of the previous key, still accepted during the grace period
+    pub previous_key: Option<StreamKeyHash>,
+
+    /// Time when the previous key stops being accepted
+    pub previous_key_expiration: Instant,
 }
 
 /// RTMP channel status
 pub struct RtmpChannelStatus {
-    /// Channel key
-    pub key: Option<String>,
+    /// Hash of the channel key (the key itself is not kept in memory)
+    pub key: Option<StreamKeyHash>,
+
+    /// Rotation of the key for the players (None if the key was not rotated)
+    pub key_rotation: Option<RtmpChannelKeyRotation>,
 
     /// Current stream ID
     pub stream_id: Option<String>,
@@ -45,40 +123,197 @@ pub struct RtmpChannelStatus {
     /// ID of the publisher session
     pub publisher_id: Option<u64>,
 
+    /// Unique ID of the current publication (UUID)
+    pub publish_id: Option<String>,
+
     /// Message sender for the publisher session
     pub publisher_message_sender: Option<Sender<RtmpSessionMessage>>,
 
     /// Status of the published stream
     pub publish_status: Option<Arc<Mutex<RtmpSessionPublishStreamStatus>>>,
 
+    /// Metadata of the published stream
+    pub stream_metadata: Option<StreamMetadata>,
+
+    /// Version of the metadata, increased every time the publisher sets it.
+    /// Never reset while the channel exists, so the players can discard outdated updates.
+    pub metadata_version: u64,
+
+    /// Bandwidth usage of the publisher session
+    pub publisher_bandwidth: Option<Arc<SessionBandwidth>>,
+
+    /// Information of the publisher (IP address and client information)
+    pub publisher_info: Option<RtmpPublisherInfo>,
+
+    /// Bytes sent to players already removed from the channel
+    pub removed_players_bytes_out: u64,
+
+    /// Max number of concurrent players, set when the publisher was authorized.
+    /// None to use the server configuration.
+    pub max_players: Option<u32>,
+
+    /// Flags of the stream, set when the publisher was authorized
+    pub flags: PublishStreamFlags,
+
+    /// Statistics of the current publication
+    pub publish_stats: Option<PublishStatistics>,
+
+    /// If the publisher dropped and the stream is waiting for it to resume,
+    /// timestamp of the last packet sent to the players
+    pub resume_timestamp: Option<i64>,
+
+    /// Sender of the requests to end the stream waiting for the publisher to resume it
+    /// (set only while waiting)
+    pub resume_end_sender: Option<oneshot::Sender<PublisherResumeEnd>>,
+
+    /// Recording of the stream in progress
+    pub recording: Option<RtmpChannelRecording>,
+
+    /// If the stream is pulled from another node of the cluster, address of the node
+    pub relay_origin: Option<String>,
+
     /// Players
     pub players: HashMap<u64, RtmpPlayerStatus>,
+
+    /// Sender to broadcast the packets to the players
+    pub packet_sender: broadcast::Sender<Arc<RtmpPacket>>,
 }
 
 impl RtmpChannelStatus {
     /// Creates a new instance of RtmpChannelStatus
-    pub fn new() -> RtmpChannelStatus {
+    ///
+    /// # Arguments
+    ///
+    /// * `packet_buffer_size` - Size of the buffer of packets shared by the players (server config)
+    pub fn new(packet_buffer_size: usize) -> RtmpChannelStatus {
+        let (packet_sender, _) = broadcast::channel(packet_buffer_size);
+
         RtmpChannelStatus {
             publishing: false,
             key: None,
+            key_rotation: None,
             stream_id: None,
             publisher_id: None,
+            publish_id: None,
             publisher_message_sender: None,
             publish_status: None,
+            stream_metadata: None,
+            metadata_version: 0,
+            publisher_bandwidth: None,
+            publisher_info: None,
+            removed_players_bytes_out: 0,
+            max_players: None,
+            flags: PublishStreamFlags::default(),
+            publish_stats: None,
+            resume_timestamp: None,
+            resume_end_sender: None,
+            recording: None,
+            relay_origin: None,
             players: HashMap::new(),
+            packet_sender,
+        }
+    }
+
+    /// Checks if the channel has a stream, being published
+    /// or waiting for the publisher to resume it
+    pub fn has_stream(&self) -> bool {
+        self.publishing || self.resume_timestamp.is_some()
+    }
+
+    /// Subscribes to the packets sent to the players of the channel
+    pub fn subscribe_packets(&self) -> RtmpPacketReceiver {
+        self.packet_sender.subscribe()
+    }
+
+    /// Checks if the key provided by a player is valid to play the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `provided_key` - Hash of the key provided by the player
+    pub fn is_player_key_valid(&self, provided_key: &StreamKeyHash) -> bool {
+        match &self.key_rotation {
+            Some(rotation) => {
+                rotation.key.matches_hash(provided_key)
+                    || (Instant::now() < rotation.previous_key_expiration
+                        && rotation
+                            .previous_key
+                            .as_ref()
+                            .is_some_and(|k| k.matches_hash(provided_key)))
+            }
+            None => match &self.key {
+                Some(k) => k.matches_hash(provided_key),
+                None => true,
+            },
+        }
+    }
+
+    /// Rotates the key the players must provide to play the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `new_key` - The new key for the players
+    /// * `grace_period` - Time the previous key is still accepted
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub fn rotate_player_key(&mut self, new_key: &str, grace_period: Duration) {
+        let previous_key = match &self.key_rotation {
+            Some(rotation) => Some(rotation.key.clone()),
+            None => self.key.clone(),
+        };
+
+        self.key_rotation = Some(RtmpChannelKeyRotation {
+            key: StreamKeyHash::new(new_key),
+            previous_key,
+            previous_key_expiration: Instant::now() + grace_period,
+        });
+    }
+
+    /// Checks if the channel reached the max number of concurrent players
+    ///
+    /// # Arguments
+    ///
+    /// * `default_max_players` - The max number of players if not set for the channel (server config, 0 for unlimited)
+    pub fn is_player_limit_reached(&self, default_max_players: u32) -> bool {
+        let max_players = self.max_players.unwrap_or(default_max_players);
+
+        max_players > 0 && self.players.len() >= max_players as usize
+    }
+
+    /// Gets the bandwidth usage of the channel
+    pub fn get_bandwidth_stats(&self) -> RtmpChannelBandwidthStats {
+        let (bytes_in, bit_rate_in, publisher_rtt) = match &self.publisher_bandwidth {
+            Some(b) => (b.input.total_bytes(), b.input.bit_rate(), b.rtt()),
+            None => (0, 0, None),
+        };
+
+        let mut bytes_out = self.removed_players_bytes_out;
+        let mut bit_rate_out: u64 = 0;
+
+        for player in self.players.values() {
+            bytes_out = bytes_out.wrapping_add(player.bandwidth.output.total_bytes());
+            bit_rate_out = bit_rate_out.wrapping_add(player.bandwidth.output.bit_rate());
+        }
+
+        RtmpChannelBandwidthStats {
+            bytes_in,
+            bytes_out,
+            bit_rate_in,
+            bit_rate_out,
+            publisher_rtt,
         }
     }
 
     /// Sends a packet to players and stored it in the GOP cache if applicable
+    /// Each player filters the packets depending on its own options
+    /// The packet is also counted in the statistics of the publication
     ///
     /// # Arguments
     ///
     /// * `publisher_id` - ID of the publisher sending the packet
     /// * `packet` - Packet to send
-    /// * `skip_cache` - True if the packet should not be added to the GOP cache
+    /// * `skip_cache` - True if the packet should not be added to the GOP cache (also skipped if disabled by the stream flags)
     /// * `gop_cache_size` - The max size of the GOP cache (server config)
     pub async fn send_packet(
-        &self,
+        &mut self,
         publisher_id: u64,
         packet: Arc<RtmpPacket>,
         skip_cache: bool,
@@ -101,7 +336,25 @@ impl RtmpChannelStatus {
             }
         };
 
-        if !skip_cache {
+        if let Some(stats) = &mut self.publish_stats {
+            let now = Utc::now().timestamp_millis();
+
+            if packet.header.packet_type == RTMP_TYPE_VIDEO {
+                let is_keyframe =
+                    !skip_cache && packet.payload.first().is_some_and(|b| (b >> 4) & 0x07 == 1);
+
+                stats.record_video(
+                    now,
+                    packet.payload.len(),
+                    packet.header.timestamp,
+                    is_keyframe,
+                );
+            } else if packet.header.packet_type == RTMP_TYPE_AUDIO {
+                stats.record_audio(now, packet.payload.len(), packet.header.timestamp);
+            }
+        }
+
+        if !skip_cache && self.flags.is_gop_cache_enabled() {
             let mut publish_status = publish_status_mu.lock().await;
 
             // Push packet to the GOP cache
@@ -116,6 +369,7 @@ impl RtmpChannelStatus {
                 if let Some(removed) = publish_status.gop_cache.pop_front() {
                     publish_status.gop_cache_size =
                         publish_status.gop_cache_size.wrapping_sub(removed.size());
+                    publish_status.gop_blob_cache.invalidate();
                 }
             }
 
@@ -124,25 +378,40 @@ impl RtmpChannelStatus {
 
         // Send packet to players
 
-        for player in self.players.values() {
-            if player.paused {
-                continue;
-            }
+        _ = self.packet_sender.send(packet);
+    }
+}
 
-            if packet.header.packet_type == RTMP_TYPE_AUDIO && !player.receive_audio {
-                continue;
-            }
+// Tests
 
-            if packet.header.packet_type == RTMP_TYPE_VIDEO && !player.receive_video {
-                continue;
-            }
+#[cfg(all(test, any(feature = "control", feature = "redis")))]
+mod tests {
+    use super::*;
 
-            _ = player
-                .message_sender
-                .send(RtmpSessionMessage::PlayPacket {
-                    packet: packet.clone(),
-                })
-                .await;
-        }
+    #[test]
+    fn test_player_key_rotation() {
+        let mut channel_status = RtmpChannelStatus::new(16);
+
+        let is_valid =
+            |c: &RtmpChannelStatus, key: &str| c.is_player_key_valid(&StreamKeyHash::new(key));
+
+        channel_status.key = Some(StreamKeyHash::new("old"));
+
+        assert!(is_valid(&channel_status, "old"));
+        assert!(!is_valid(&channel_status, "new"));
+
+        channel_status.rotate_player_key("new", Duration::from_secs(60));
+
+        assert!(is_valid(&channel_status, "old"));
+        assert!(is_valid(&channel_status, "new"));
+        assert!(!is_valid(&channel_status, "other"));
+
+        // Grace period expired
+
+        channel_status.rotate_player_key("newer", Duration::ZERO);
+
+        assert!(!is_valid(&channel_status, "old"));
+        assert!(!is_valid(&channel_status, "new"));
+        assert!(is_valid(&channel_status, "newer"));
     }
 }
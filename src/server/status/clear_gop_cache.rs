@@ -0,0 +1,56 @@
+// Logic to clear the GOP cache of a channel
+
+use crate::{log::Logger, log_info, server::RtmpServerContext};
+
+/// Clears the GOP cache of a channel being published,
+/// so the new players do not receive the cached packets
+/// (eg: if corrupted packets are breaking the playback)
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+///
+/// # Return value
+///
+/// Returns the number of removed packets, or an error message if the channel is not publishing
+pub async fn clear_gop_cache(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+) -> Result<usize, String> {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return Err("The channel is not publishing".to_string());
+        }
+    };
+
+    drop(status);
+
+    let channel_status = channel_mu.lock().await;
+
+    let publish_status_mu = match (&channel_status.publish_status, channel_status.publishing) {
+        (Some(s), true) => s.clone(),
+        _ => {
+            return Err("The channel is not publishing".to_string());
+        }
+    };
+
+    drop(channel_status);
+
+    let removed = publish_status_mu.lock().await.force_clear_gop();
+
+    log_info!(
+        logger,
+        format!(
+            "Cleared the GOP cache of channel {}: {} packets removed",
+            channel, removed
+        )
+    );
+
+    Ok(removed)
+}
@@ -0,0 +1,32 @@
+use crate::server::RtmpServerContext;
+
+use super::RtmpChannelBandwidthStats;
+
+/// Gets the bandwidth usage of a channel:
+/// the input of the publisher and the output to all the players
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+///
+/// # Return value
+///
+/// Returns the bandwidth usage (all zero if the channel does not exist)
+pub async fn get_channel_bandwidth(
+    server_context: &RtmpServerContext,
+    channel: &str,
+) -> RtmpChannelBandwidthStats {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => return RtmpChannelBandwidthStats::default(),
+    };
+
+    drop(status);
+
+    let channel_status = channel_mu.lock().await;
+
+    channel_status.get_bandwidth_stats()
+}
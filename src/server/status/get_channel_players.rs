@@ -0,0 +1,80 @@
+use serde::Serialize;
+
+use crate::{server::RtmpServerContext, session::PlayerLagSnapshot};
+
+/// Diagnostics of a player of a channel
+#[derive(Serialize)]
+pub struct RtmpChannelPlayerInfo {
+    /// ID of the player session
+    pub session_id: u64,
+
+    /// IP address of the player
+    pub ip: String,
+
+    /// True if waiting for the channel to be published
+    pub idle: bool,
+
+    /// True if paused
+    pub paused: bool,
+
+    /// True if the player requested the low latency mode
+    pub low_latency: bool,
+
+    /// Output bit rate (bits per second)
+    pub bit_rate: u64,
+
+    /// Last measured round trip time (milliseconds)
+    pub rtt: Option<u32>,
+
+    /// Diagnostics of the delivery of the stream
+    #[serde(flatten)]
+    pub lag: PlayerLagSnapshot,
+}
+
+/// Gets the diagnostics of the players of a channel,
+/// to find the players receiving the stream slower than it is published
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+///
+/// # Return value
+///
+/// Returns the players, sorted by session ID
+pub async fn get_channel_players(
+    server_context: &RtmpServerContext,
+    channel: &str,
+) -> Vec<RtmpChannelPlayerInfo> {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => return Vec::new(),
+    };
+
+    drop(status);
+
+    let channel_status = channel_mu.lock().await;
+
+    let mut players: Vec<RtmpChannelPlayerInfo> = channel_status
+        .players
+        .iter()
+        .map(|(session_id, player)| RtmpChannelPlayerInfo {
+            session_id: *session_id,
+            ip: player.ip.to_string(),
+            idle: player.idle,
+            paused: player.paused,
+            low_latency: player.low_latency,
+            bit_rate: player.bandwidth.output.bit_rate(),
+            rtt: player.bandwidth.rtt(),
+            lag: player.lag.snapshot(),
+        })
+        .collect();
+
+    drop(channel_status);
+
+    players.sort_by_key(|p| p.session_id);
+
+    players
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    log::Logger,
+    log_debug,
+    rtmp::{
+        rtmp_build_cue_point, RtmpPacket, RTMP_CHANNEL_DATA, RTMP_CHANNEL_INJECTED_DATA,
+        RTMP_CHUNK_TYPE_0, RTMP_TYPE_DATA,
+    },
+    server::RtmpServerContext,
+};
+
+/// Parses the parameters of a cue point
+///
+/// # Arguments
+///
+/// * `s` - The parameters, encoded as a JSON object with string values (empty for no parameters)
+pub fn parse_cue_point_parameters(s: &str) -> Result<HashMap<String, String>, String> {
+    if s.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    serde_json::from_str::<HashMap<String, String>>(s)
+        .map_err(|e| format!("Invalid cue point parameters: {}", e))
+}
+
+/// Injects a cue point (onCuePoint) into the live stream of a channel,
+/// sending it to all the players (eg: to mark an ad break)
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `name` - Name of the cue point
+/// * `parameters` - Parameters of the cue point (eg: ad metadata)
+/// * `record` - True to also write the cue point into the recording of the channel, if any
+///
+/// # Return value
+///
+/// Returns an error message if the channel is not publishing
+pub async fn inject_cue_point(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    name: &str,
+    parameters: &HashMap<String, String>,
+    record: bool,
+) -> Result<(), String> {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return Err("The channel is not publishing".to_string());
+        }
+    };
+
+    drop(status);
+
+    let channel_status = channel_mu.lock().await;
+
+    let publish_status_mu = match (&channel_status.publish_status, channel_status.publishing) {
+        (Some(s), true) => s.clone(),
+        _ => {
+            return Err("The channel is not publishing".to_string());
+        }
+    };
+
+    // The cue point is placed at the current time of the stream
+
+    let publish_status = publish_status_mu.lock().await;
+    let clock = publish_status
+        .clock
+        .wrapping_add(publish_status.timestamp_offset);
+    drop(publish_status);
+
+    let mut packet = RtmpPacket::new_blank();
+
+    packet.header.format = RTMP_CHUNK_TYPE_0;
+    packet.header.channel_id = if record {
+        RTMP_CHANNEL_DATA
+    } else {
+        RTMP_CHANNEL_INJECTED_DATA
+    };
+    packet.header.packet_type = RTMP_TYPE_DATA;
+    packet.payload = rtmp_build_cue_point(name, clock, parameters);
+    packet.header.length = packet.payload.len();
+    packet.header.timestamp = clock;
+
+    _ = channel_status.packet_sender.send(Arc::new(packet));
+
+    drop(channel_status);
+
+    log_debug!(
+        logger,
+        format!("Injected cue point into channel {}: {}", channel, name)
+    );
+
+    Ok(())
+}
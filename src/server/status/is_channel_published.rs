@@ -0,0 +1,23 @@
+use crate::server::RtmpServerContext;
+
+/// Checks if a channel has a stream on this node,
+/// being published or waiting for the publisher to resume it
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+pub async fn is_channel_published(server_context: &RtmpServerContext, channel: &str) -> bool {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => return false,
+    };
+
+    drop(status);
+
+    let channel_status = channel_mu.lock().await;
+
+    channel_status.has_stream()
+}
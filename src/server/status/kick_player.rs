@@ -0,0 +1,115 @@
+use std::net::IpAddr;
+
+use crate::{
+    log::Logger,
+    log_debug,
+    server::{RtmpChannelStatus, RtmpPlayerStatus, RtmpServerContext},
+    session::RtmpSessionMessage,
+    utils::normalize_ip_address,
+};
+
+/// Kicks players from a channel
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `session_id` - Optionally, the session ID of the player to kick
+/// * `ip` - Optionally, the IP address of the players to kick
+///
+/// # Return value
+///
+/// Returns the number of kicked players.
+/// If neither the session ID nor the IP are provided, no players are kicked.
+pub async fn kick_player(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    session_id: Option<u64>,
+    ip: Option<IpAddr>,
+) -> usize {
+    if session_id.is_none() && ip.is_none() {
+        return 0;
+    }
+
+    let ip = ip.map(normalize_ip_address);
+
+    kick_players_matching(logger, server_context, channel, |_, player_id, player| {
+        session_id.is_none_or(|sid| sid == player_id) && ip.is_none_or(|ip| ip == player.ip)
+    })
+    .await
+}
+
+/// Kicks the players of a channel matching a filter
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `filter` - Function receiving the channel status, the player ID and the player status, returning true to kick the player
+///
+/// # Return value
+///
+/// Returns the number of kicked players
+pub async fn kick_players_matching<F>(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    filter: F,
+) -> usize
+where
+    F: Fn(&RtmpChannelStatus, u64, &RtmpPlayerStatus) -> bool,
+{
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return 0;
+        }
+    };
+
+    drop(status);
+
+    let mut channel_status = channel_mu.lock().await;
+
+    let players_to_kick: Vec<u64> = channel_status
+        .players
+        .iter()
+        .filter(|(player_id, player)| filter(&channel_status, **player_id, player))
+        .map(|(player_id, _)| *player_id)
+        .collect();
+
+    for player_id in &players_to_kick {
+        if let Some(player) = channel_status.players.remove(player_id) {
+            channel_status.removed_players_bytes_out = channel_status
+                .removed_players_bytes_out
+                .wrapping_add(player.bandwidth.output.total_bytes());
+
+            _ = player
+                .message_sender
+                .send(RtmpSessionMessage::KickPlayer)
+                .await;
+
+            log_debug!(
+                logger,
+                format!("Kicked player #{} from channel {}", player_id, channel)
+            );
+
+            server_context
+                .status
+                .events
+                .record(
+                    channel,
+                    "player-kicked",
+                    *player_id,
+                    &format!("IP: {}", player.ip),
+                )
+                .await;
+        }
+    }
+
+    players_to_kick.len()
+}
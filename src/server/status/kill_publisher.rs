@@ -1,6 +1,19 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+
 use crate::{
-    callback::make_stop_callback, control::ControlKeyValidationRequest, log::Logger,
-    server::RtmpServerContext, session::RtmpSessionMessage,
+    callback::CallbackCorrelation,
+    control::ControlKeyValidationRequest,
+    log::Logger,
+    log_debug,
+    server::{
+        play_slate, request_publisher_resume_end, KilledPublishStop, PublishStreamFlags,
+        RtmpChannelStatus, RtmpServerContext,
+    },
+    session::RtmpSessionMessage,
+    utils::ChannelSelector,
 };
 
 /// Kills publisher
@@ -11,13 +24,15 @@ use crate::{
 /// * `server_context` - The server context
 /// * `channel` - The channel ID
 /// * `stream_id` - Optionally, the stream ID
+/// * `reason` - Reason of the kill, recorded in the event history of the channel
 pub async fn kill_publisher(
     logger: &Logger,
     server_context: &RtmpServerContext,
     channel: &str,
     stream_id: Option<&str>,
+    reason: &str,
 ) {
-    let status = server_context.status.lock().await;
+    let status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get(channel) {
         let channel_mu = c.clone();
@@ -25,7 +40,7 @@ pub async fn kill_publisher(
 
         let mut channel_status = channel_mu.lock().await;
 
-        if !channel_status.publishing {
+        if !channel_status.has_stream() {
             return;
         }
 
@@ -42,30 +57,109 @@ pub async fn kill_publisher(
             }
         }
 
-        // Kill the publisher
+        // Waiting for the publisher to resume the stream.
+        // The task waiting for it keeps the key, so it ends the stream.
 
-        if let Some(pub_sender) = &channel_status.publisher_message_sender {
-            _ = pub_sender.send(RtmpSessionMessage::Kill).await;
-        }
+        if !channel_status.publishing {
+            let end_sender = channel_status.resume_end_sender.take();
 
-        // Unpublish
+            drop(channel_status);
 
-        let unpublished_stream_key = match &channel_status.key {
-            Some(k) => k.clone(),
-            None => "".to_string(),
-        };
+            if let Some(end_sender) = end_sender {
+                request_publisher_resume_end(end_sender, Some(reason.to_string())).await;
+            }
+
+            return;
+        }
 
         let unpublished_stream_id = match &channel_status.stream_id {
             Some(i) => i.clone(),
             None => "".to_string(),
         };
 
+        let killed_publisher_id = channel_status.publisher_id.unwrap_or(0);
+
+        // Only the publisher session keeps the key,
+        // so the session sends the stop callback
+
+        let stop = if server_context.control_key_validator_sender.is_none()
+            && channel_status.relay_origin.is_none()
+        {
+            Some(Box::new(KilledPublishStop {
+                channel: channel.to_string(),
+                stream_id: unpublished_stream_id.clone(),
+                correlation: CallbackCorrelation {
+                    session_id: killed_publisher_id,
+                    publish_id: channel_status.publish_id.clone().unwrap_or_default(),
+                },
+                stats: channel_status
+                    .publish_stats
+                    .as_ref()
+                    .map(|s| s.summary(Utc::now().timestamp_millis())),
+                reason: reason.to_string(),
+            }))
+        } else {
+            None
+        };
+
+        // Kill the publisher
+
+        if let Some(pub_sender) = &channel_status.publisher_message_sender {
+            if pub_sender
+                .send(RtmpSessionMessage::Kill {
+                    reason: reason.to_string(),
+                    stop,
+                })
+                .await
+                .is_err()
+            {
+                // The session already ended, its cleanup removes it from the channel
+                return;
+            }
+        }
+
+        // Unpublish
+
+        let bandwidth_stats = channel_status.get_bandwidth_stats();
+
+        log_debug!(
+            logger,
+            format!(
+                "Channel {} bandwidth: Bytes in: {} | Bytes out: {} | Bit rate in: {} bps | Bit rate out: {} bps | Publisher RTT: {}",
+                channel,
+                bandwidth_stats.bytes_in,
+                bandwidth_stats.bytes_out,
+                bandwidth_stats.bit_rate_in,
+                bandwidth_stats.bit_rate_out,
+                match bandwidth_stats.publisher_rtt {
+                    Some(rtt) => format!("{} ms", rtt),
+                    None => "unknown".to_string(),
+                }
+            )
+        );
+
+        let publish_stats = channel_status
+            .publish_stats
+            .take()
+            .map(|s| s.summary(Utc::now().timestamp_millis()));
+
         channel_status.publishing = false;
         channel_status.publisher_id = None;
+        channel_status.publish_id = None;
         channel_status.publish_status = None;
+        channel_status.stream_metadata = None;
+        channel_status.publisher_bandwidth = None;
+        let publisher_info = channel_status.publisher_info.take();
         channel_status.publisher_message_sender = None;
         channel_status.key = None;
+        channel_status.key_rotation = None;
         channel_status.stream_id = None;
+        channel_status.max_players = None;
+        channel_status.flags = PublishStreamFlags::default();
+        channel_status.resume_timestamp = None;
+        channel_status.resume_end_sender = None;
+        channel_status.recording = None;
+        let relay_origin = channel_status.relay_origin.take();
 
         // Notify players
 
@@ -75,33 +169,95 @@ pub async fn kill_publisher(
                 .message_sender
                 .send(RtmpSessionMessage::PlayStop)
                 .await;
+
+            if !player.paused {
+                play_slate(server_context, &player.message_sender).await;
+            }
         }
 
         drop(channel_status);
 
-        // Send callback
-
-        match &server_context.control_key_validator_sender {
-            Some(sender) => {
-                // Notify control server
-                _ = sender
-                    .send(ControlKeyValidationRequest::PublishEnd {
-                        channel: channel.to_string(),
-                        stream_id: unpublished_stream_id,
-                    })
-                    .await;
-            }
-            None => {
-                // Callback
-                make_stop_callback(
-                    logger,
-                    &server_context.config.callback,
-                    channel,
-                    &unpublished_stream_key,
-                    &unpublished_stream_id,
-                )
+        server_context
+            .status
+            .events
+            .record(
+                channel,
+                "publish-killed",
+                killed_publisher_id,
+                &format!("Stream ID: {} | Reason: {}", &unpublished_stream_id, reason),
+            )
+            .await;
+
+        // Streams pulled from other nodes are reported by the node publishing them
+
+        if relay_origin.is_some() {
+            return;
+        }
+
+        if let Some(cluster) = &server_context.cluster {
+            cluster.unregister_channel(logger, channel).await;
+        }
+
+        // Notify control server.
+        // Otherwise, the stop callback is sent by the killed publisher session.
+
+        if let Some(sender) = &server_context.control_key_validator_sender {
+            _ = sender
+                .send(ControlKeyValidationRequest::PublishEnd {
+                    channel: channel.to_string(),
+                    stream_id: unpublished_stream_id,
+                    stats: publish_stats,
+                    publisher: publisher_info,
+                })
                 .await;
+        }
+    }
+}
+
+/// Kills the publishers of a group of channels
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channels` - Selector of the channels
+/// * `reason` - Reason of the kill, recorded in the event history of the channels
+///
+/// # Return value
+///
+/// Returns the IDs of the channels with killed publishers
+pub async fn kill_publishers_matching(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channels: &ChannelSelector,
+    reason: &str,
+) -> Vec<String> {
+    let mut matching_channels: Vec<(String, Arc<Mutex<RtmpChannelStatus>>)> = Vec::new();
+
+    for shard in server_context.status.shards() {
+        let status = shard.lock().await;
+
+        for (channel, c) in &status.channels {
+            if channels.matches(channel) {
+                matching_channels.push((channel.clone(), c.clone()));
             }
         }
     }
+
+    // The channels are checked after releasing the shard locks,
+    // so the other channels of the shards are not blocked
+
+    let mut published_channels: Vec<String> = Vec::new();
+
+    for (channel, channel_mu) in matching_channels {
+        if channel_mu.lock().await.has_stream() {
+            published_channels.push(channel);
+        }
+    }
+
+    for channel in &published_channels {
+        kill_publisher(logger, server_context, channel, None, reason).await;
+    }
+
+    published_channels
 }
@@ -0,0 +1,20 @@
+use crate::{callback::CallbackCorrelation, server::PublishStatisticsSummary};
+
+/// Stop callback of a killed publication,
+/// sent by the publisher session, since only the session keeps the key
+pub struct KilledPublishStop {
+    /// Channel ID
+    pub channel: String,
+
+    /// Stream ID
+    pub stream_id: String,
+
+    /// IDs to correlate the callback with the start callback
+    pub correlation: CallbackCorrelation,
+
+    /// Statistics of the publication
+    pub stats: Option<PublishStatisticsSummary>,
+
+    /// Reason of the kill
+    pub reason: String,
+}
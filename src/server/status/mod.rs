@@ -1,45 +1,178 @@
 // Server status
 
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::Mutex;
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    sync::Arc,
+};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::{
+    auth::StreamKeyStore, callback::CallbackResponseCache, session::GopReplayLimiter,
+    trace::SessionTraceRegistry,
+};
+
+use super::ConfigOverrides;
 
 mod add_player;
+mod channel_events;
 mod channel_status;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod clear_gop_cache;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod get_channel_bandwidth;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod get_channel_players;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod inject_cue_point;
+mod is_channel_published;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod kick_player;
+#[cfg(any(feature = "control", feature = "redis"))]
 mod kill_publisher;
+mod killed_publish_stop;
+mod play_slate;
 mod player_pause;
 mod player_resume;
 mod player_set_receive;
+mod publish_flags;
+mod publish_stats;
+#[cfg(feature = "control")]
 mod remove_all_publishers;
 mod remove_player;
 mod remove_publisher;
+mod resume_publisher;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod rotate_channel_key;
 mod set_channel_metadata;
 mod set_publisher;
+#[cfg(any(feature = "control", feature = "redis"))]
+mod stored_channel_keys;
+mod takeover_publisher;
 mod try_clear_channel;
 
 pub use add_player::*;
+pub use channel_events::*;
 pub use channel_status::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use clear_gop_cache::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use get_channel_bandwidth::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use get_channel_players::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use inject_cue_point::*;
+pub use is_channel_published::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use kick_player::*;
+#[cfg(any(feature = "control", feature = "redis"))]
 pub use kill_publisher::*;
+pub use killed_publish_stop::*;
+pub use play_slate::*;
 pub use player_pause::*;
 pub use player_resume::*;
 pub use player_set_receive::*;
+pub use publish_flags::*;
+pub use publish_stats::*;
+#[cfg(feature = "control")]
 pub use remove_all_publishers::*;
 pub use remove_player::*;
 pub use remove_publisher::*;
+pub use resume_publisher::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use rotate_channel_key::*;
 pub use set_channel_metadata::*;
 pub use set_publisher::*;
+#[cfg(any(feature = "control", feature = "redis"))]
+pub use stored_channel_keys::*;
+pub use takeover_publisher::*;
 pub use try_clear_channel::*;
 
-/// Server status
-pub struct RtmpServerStatus {
+/// Number of shards of the server status
+/// Channels are distributed among the shards, so unrelated channels do not contend for the same lock
+pub const RTMP_SERVER_STATUS_SHARDS: usize = 16;
+
+/// Shard of the server status
+pub struct RtmpServerStatusShard {
     /// Channels
     pub channels: HashMap<String, Arc<Mutex<RtmpChannelStatus>>>,
 }
 
+/// Server status
+pub struct RtmpServerStatus {
+    /// Shards
+    shards: Vec<Mutex<RtmpServerStatusShard>>,
+
+    /// Hasher to assign the channels to the shards
+    hasher: RandomState,
+
+    /// Sessions that can be traced
+    pub traces: SessionTraceRegistry,
+
+    /// History of the lifecycle events of the channels
+    pub events: ChannelEventHistory,
+
+    /// Cache of the start callback responses
+    pub callback_cache: CallbackResponseCache,
+
+    /// Configuration overrides, set at runtime by the control server
+    pub config_overrides: ConfigOverrides,
+
+    /// Limiter of the concurrent GOP cache replays
+    pub gop_replay_limiter: GopReplayLimiter,
+
+    /// Built-in store of stream keys
+    pub key_store: StreamKeyStore,
+}
+
 impl RtmpServerStatus {
     /// Creates new instance of RtmpServerStatus
-    pub fn new() -> RtmpServerStatus {
+    ///
+    /// # Arguments
+    ///
+    /// * `event_history_size` - Max number of events to keep per channel (server config, 0 to disable)
+    /// * `gop_replay_limiter` - Limiter of the concurrent GOP cache replays
+    /// * `key_store` - Built-in store of stream keys
+    pub fn new(
+        event_history_size: usize,
+        gop_replay_limiter: GopReplayLimiter,
+        key_store: StreamKeyStore,
+    ) -> RtmpServerStatus {
         RtmpServerStatus {
-            channels: HashMap::new(),
+            shards: (0..RTMP_SERVER_STATUS_SHARDS)
+                .map(|_| {
+                    Mutex::new(RtmpServerStatusShard {
+                        channels: HashMap::new(),
+                    })
+                })
+                .collect(),
+            hasher: RandomState::new(),
+            traces: SessionTraceRegistry::new(),
+            events: ChannelEventHistory::new(event_history_size),
+            callback_cache: CallbackResponseCache::new(),
+            config_overrides: ConfigOverrides::new(),
+            gop_replay_limiter,
+            key_store,
         }
     }
+
+    /// Locks the shard containing a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    ///
+    /// # Return value
+    ///
+    /// Returns the guard of the locked shard
+    pub async fn lock_shard(&self, channel: &str) -> MutexGuard<'_, RtmpServerStatusShard> {
+        let shard_index = (self.hasher.hash_one(channel) as usize) % self.shards.len();
+
+        self.shards[shard_index].lock().await
+    }
+
+    /// Gets all the shards, in order to iterate over all the channels
+    pub fn shards(&self) -> &[Mutex<RtmpServerStatusShard>] {
+        &self.shards
+    }
 }
@@ -0,0 +1,19 @@
+use tokio::sync::mpsc::Sender;
+
+use crate::{server::RtmpServerContext, session::RtmpSessionMessage};
+
+/// Starts playing the slate for a player waiting for the channel to be published.
+/// Does nothing if no slate is configured.
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `message_sender` - Message sender to communicate with the player session
+pub async fn play_slate(
+    server_context: &RtmpServerContext,
+    message_sender: &Sender<RtmpSessionMessage>,
+) {
+    if let Some(slate) = &server_context.slate {
+        _ = message_sender.send(slate.get_play_start_message()).await;
+    }
+}
@@ -8,7 +8,7 @@ use crate::{server::RtmpServerContext, session::RtmpSessionMessage};
 /// * `channel` - The channel ID
 /// * `player_id` - ID of the player
 pub async fn player_pause(server_context: &RtmpServerContext, channel: &str, player_id: u64) {
-    let mut status = server_context.status.lock().await;
+    let mut status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get_mut(channel) {
         let channel_mu = c.clone();
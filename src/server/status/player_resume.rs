@@ -8,7 +8,7 @@ use crate::{server::RtmpServerContext, session::RtmpSessionMessage};
 /// * `channel` - The channel ID
 /// * `player_id` - ID of the player
 pub async fn player_resume(server_context: &RtmpServerContext, channel: &str, player_id: u64) {
-    let mut status = server_context.status.lock().await;
+    let mut status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get_mut(channel) {
         let channel_mu = c.clone();
@@ -18,6 +18,7 @@ pub async fn player_resume(server_context: &RtmpServerContext, channel: &str, pl
 
         let publishing = channel_status.publishing;
         let publish_status = channel_status.publish_status.clone();
+        let packet_sender = channel_status.packet_sender.clone();
 
         if let Some(player_status) = channel_status.players.get_mut(&player_id) {
             if !player_status.paused {
@@ -30,17 +31,26 @@ pub async fn player_resume(server_context: &RtmpServerContext, channel: &str, pl
                 if let Some(publish_status_mu) = &publish_status {
                     let publish_status = publish_status_mu.lock().await;
 
-                    let player_resume_message = publish_status.get_player_resume_message();
+                    let player_resume_message =
+                        publish_status.get_player_resume_message(packet_sender.subscribe());
 
                     drop(publish_status);
 
-                    _ = player_status.message_sender.send(player_resume_message);
+                    _ = player_status
+                        .message_sender
+                        .send(player_resume_message)
+                        .await;
                 } else {
                     _ = player_status
                         .message_sender
                         .send(RtmpSessionMessage::ResumeIdle)
                         .await;
                 }
+            } else if let Some(slate) = &server_context.slate {
+                _ = player_status
+                    .message_sender
+                    .send(slate.get_player_resume_message())
+                    .await;
             } else {
                 _ = player_status
                     .message_sender
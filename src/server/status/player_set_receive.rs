@@ -15,7 +15,7 @@ pub async fn player_set_receive_audio(
     player_id: u64,
     receive_audio: bool,
 ) {
-    let mut status = server_context.status.lock().await;
+    let mut status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get_mut(channel) {
         let channel_mu = c.clone();
@@ -44,7 +44,7 @@ pub async fn player_set_receive_video(
     player_id: u64,
     receive_video: bool,
 ) {
-    let mut status_v = server_context.status.lock().await;
+    let mut status_v = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status_v.channels.get_mut(channel) {
         let channel_mu = c.clone();
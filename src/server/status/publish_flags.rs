@@ -0,0 +1,164 @@
+// Flags to override the server defaults for a published stream
+
+/// Media accepted for a published stream
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PublishMediaMode {
+    /// Audio and video
+    #[default]
+    All,
+
+    /// Only audio (eg: radio). Video packets are ignored.
+    AudioOnly,
+
+    /// Only video. Audio packets are ignored.
+    VideoOnly,
+}
+
+impl PublishMediaMode {
+    /// Parses the media mode
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The mode (`all`, `audio` or `video`)
+    pub fn parse(s: &str) -> Result<PublishMediaMode, ()> {
+        match s.to_lowercase().as_str() {
+            "all" | "av" => Ok(PublishMediaMode::All),
+            "audio" => Ok(PublishMediaMode::AudioOnly),
+            "video" => Ok(PublishMediaMode::VideoOnly),
+            _ => Err(()),
+        }
+    }
+
+    /// Gets the name of the media mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PublishMediaMode::All => "all",
+            PublishMediaMode::AudioOnly => "audio",
+            PublishMediaMode::VideoOnly => "video",
+        }
+    }
+
+    /// Checks if audio packets are accepted
+    pub fn accepts_audio(&self) -> bool {
+        *self != PublishMediaMode::VideoOnly
+    }
+
+    /// Checks if video packets are accepted
+    pub fn accepts_video(&self) -> bool {
+        *self != PublishMediaMode::AudioOnly
+    }
+}
+
+/// Flags of a published stream, set by the event callback or the control server
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct PublishStreamFlags {
+    /// Use the GOP cache for the stream (None to use the server configuration)
+    pub gop_cache: Option<bool>,
+
+    /// Record the stream since the start (None to use the server configuration)
+    pub record: Option<bool>,
+
+    /// Media accepted for the stream
+    pub media: PublishMediaMode,
+}
+
+impl PublishStreamFlags {
+    /// Parses the flags of a stream
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The flags, split by commas (eg: `gop_cache=off,record=on,media=audio`)
+    pub fn parse(s: &str) -> Result<PublishStreamFlags, String> {
+        let mut flags = PublishStreamFlags::default();
+
+        for flag in s.split(',') {
+            let flag = flag.trim();
+
+            if flag.is_empty() {
+                continue;
+            }
+
+            let (name, value) = match flag.split_once('=') {
+                Some((n, v)) => (n.trim(), v.trim()),
+                None => {
+                    return Err(format!("Invalid stream flag: {}", flag));
+                }
+            };
+
+            let invalid_value_err =
+                format!("Invalid value for the stream flag {}: {}", name, value);
+
+            let bool_value = match value.to_lowercase().as_str() {
+                "on" | "yes" | "true" => Some(true),
+                "off" | "no" | "false" => Some(false),
+                _ => None,
+            };
+
+            match name.to_lowercase().as_str() {
+                "gop_cache" => {
+                    flags.gop_cache = Some(bool_value.ok_or(invalid_value_err)?);
+                }
+                "record" => {
+                    flags.record = Some(bool_value.ok_or(invalid_value_err)?);
+                }
+                "media" => {
+                    flags.media = PublishMediaMode::parse(value).map_err(|_| invalid_value_err)?;
+                }
+                _ => {
+                    return Err(format!("Unknown stream flag: {}", name));
+                }
+            }
+        }
+
+        Ok(flags)
+    }
+
+    /// Checks if the GOP cache is enabled for the stream.
+    /// Audio-only streams do not use it, since there are no key frames
+    /// to wait for: new players start with the live audio.
+    pub fn is_gop_cache_enabled(&self) -> bool {
+        self.media != PublishMediaMode::AudioOnly && self.gop_cache.unwrap_or(true)
+    }
+
+    /// Checks if the stream must be recorded since the start
+    pub fn is_record_enabled(&self) -> bool {
+        self.record.unwrap_or(false)
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_stream_flags() {
+        let flags = PublishStreamFlags::parse("gop_cache=off, record=ON").unwrap();
+
+        assert_eq!(flags.gop_cache, Some(false));
+        assert_eq!(flags.record, Some(true));
+        assert!(!flags.is_gop_cache_enabled());
+        assert!(flags.is_record_enabled());
+
+        let flags = PublishStreamFlags::parse("").unwrap();
+
+        assert_eq!(flags, PublishStreamFlags::default());
+        assert!(flags.is_gop_cache_enabled());
+        assert!(!flags.is_record_enabled());
+
+        assert!(PublishStreamFlags::parse("gop_cache").is_err());
+        assert!(PublishStreamFlags::parse("gop_cache=maybe").is_err());
+        assert!(PublishStreamFlags::parse("transcode=on").is_err());
+
+        let flags = PublishStreamFlags::parse("media=audio").unwrap();
+
+        assert_eq!(flags.media, PublishMediaMode::AudioOnly);
+        assert!(!flags.is_gop_cache_enabled());
+        assert!(!flags.media.accepts_video());
+        assert!(flags.media.accepts_audio());
+
+        assert!(PublishStreamFlags::parse("media=on").is_err());
+        assert!(PublishStreamFlags::parse("record=audio").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+// Statistics of the published streams
+
+use serde::{Deserialize, Serialize};
+
+/// Min difference between the timestamps of consecutive packets
+/// of the same track to count it as a gap (milliseconds)
+pub const PUBLISH_STATS_TIMESTAMP_GAP_MS: i64 = 1000;
+
+/// Duration of the windows to measure the max bit rate (milliseconds)
+const PUBLISH_STATS_BIT_RATE_WINDOW_MS: i64 = 1000;
+
+/// Summary of the statistics of a published stream,
+/// sent when the stream ends
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishStatisticsSummary {
+    /// Total bytes of audio and video received
+    pub total_bytes: u64,
+
+    /// Duration of the publication (milliseconds)
+    pub duration: i64,
+
+    /// Average bit rate (bits per second)
+    pub avg_bit_rate: u64,
+
+    /// Max bit rate, measured in windows of 1 second (bits per second).
+    /// If the stream is shorter than a window, the average bit rate.
+    pub max_bit_rate: u64,
+
+    /// Number of timestamp gaps (jumps forward longer than 1 second, or backwards)
+    pub timestamp_gaps: u64,
+
+    /// Number of video key frames
+    pub keyframes: u64,
+
+    /// Average interval between key frames (milliseconds)
+    pub keyframe_interval_avg: Option<i64>,
+
+    /// Min interval between key frames (milliseconds)
+    pub keyframe_interval_min: Option<i64>,
+
+    /// Max interval between key frames (milliseconds)
+    pub keyframe_interval_max: Option<i64>,
+
+    /// Number of times the interval between key frames exceeded the warning threshold
+    #[serde(default)]
+    pub keyframe_interval_warnings: u64,
+}
+
+/// Statistics of a published stream, accumulated while receiving its packets
+#[derive(Clone)]
+pub struct PublishStatistics {
+    /// Start time (Unix milliseconds)
+    start_time: i64,
+
+    /// Total bytes received
+    total_bytes: u64,
+
+    /// Start time of the current bit rate window (Unix milliseconds)
+    window_start: i64,
+
+    /// Bytes received in the current bit rate window
+    window_bytes: u64,
+
+    /// Max bit rate of the finished windows
+    max_bit_rate: u64,
+
+    /// Bit rate of the last finished window
+    last_bit_rate: u64,
+
+    /// Timestamp of the last audio packet
+    last_audio_timestamp: Option<i64>,
+
+    /// Timestamp of the last video packet
+    last_video_timestamp: Option<i64>,
+
+    /// Number of timestamp gaps
+    timestamp_gaps: u64,
+
+    /// Number of key frames
+    keyframes: u64,
+
+    /// Timestamp of the last key frame
+    last_keyframe_timestamp: Option<i64>,
+
+    /// Number of intervals between key frames
+    keyframe_interval_count: i64,
+
+    /// Sum of the intervals between key frames
+    keyframe_interval_sum: i64,
+
+    /// Min interval between key frames
+    keyframe_interval_min: Option<i64>,
+
+    /// Max interval between key frames
+    keyframe_interval_max: Option<i64>,
+
+    /// Number of key frame interval warnings
+    keyframe_interval_warnings: u64,
+}
+
+impl PublishStatistics {
+    /// Creates new PublishStatistics
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp (Unix milliseconds)
+    pub fn new(now: i64) -> PublishStatistics {
+        PublishStatistics {
+            start_time: now,
+            total_bytes: 0,
+            window_start: now,
+            window_bytes: 0,
+            max_bit_rate: 0,
+            last_bit_rate: 0,
+            last_audio_timestamp: None,
+            last_video_timestamp: None,
+            timestamp_gaps: 0,
+            keyframes: 0,
+            last_keyframe_timestamp: None,
+            keyframe_interval_count: 0,
+            keyframe_interval_sum: 0,
+            keyframe_interval_min: None,
+            keyframe_interval_max: None,
+            keyframe_interval_warnings: 0,
+        }
+    }
+
+    /// Adds the bytes to the current bit rate window, finishing it if its duration has passed
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp (Unix milliseconds)
+    /// * `size` - Number of bytes
+    fn add_bytes(&mut self, now: i64, size: usize) {
+        let elapsed = now - self.window_start;
+
+        if elapsed >= PUBLISH_STATS_BIT_RATE_WINDOW_MS {
+            let bit_rate = self.window_bytes * 8 * 1000 / (elapsed as u64);
+
+            self.max_bit_rate = self.max_bit_rate.max(bit_rate);
+            self.last_bit_rate = bit_rate;
+            self.window_start = now;
+            self.window_bytes = 0;
+        }
+
+        self.total_bytes = self.total_bytes.wrapping_add(size as u64);
+        self.window_bytes = self.window_bytes.wrapping_add(size as u64);
+    }
+
+    /// Checks the timestamp of a packet, counting the gaps
+    ///
+    /// # Arguments
+    ///
+    /// * `last_timestamp` - Timestamp of the last packet of the same track
+    /// * `timestamp` - Timestamp of the packet
+    fn check_timestamp(last_timestamp: &mut Option<i64>, timestamp: i64) -> bool {
+        let is_gap = match *last_timestamp {
+            Some(last) => timestamp < last || timestamp - last > PUBLISH_STATS_TIMESTAMP_GAP_MS,
+            None => false,
+        };
+
+        *last_timestamp = Some(timestamp);
+
+        is_gap
+    }
+
+    /// Records an audio packet
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp (Unix milliseconds)
+    /// * `size` - Size of the payload
+    /// * `timestamp` - Timestamp of the packet
+    pub fn record_audio(&mut self, now: i64, size: usize, timestamp: i64) {
+        self.add_bytes(now, size);
+
+        if Self::check_timestamp(&mut self.last_audio_timestamp, timestamp) {
+            self.timestamp_gaps += 1;
+        }
+    }
+
+    /// Records a video packet
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp (Unix milliseconds)
+    /// * `size` - Size of the payload
+    /// * `timestamp` - Timestamp of the packet
+    /// * `is_keyframe` - True if the packet is a key frame
+    pub fn record_video(&mut self, now: i64, size: usize, timestamp: i64, is_keyframe: bool) {
+        self.add_bytes(now, size);
+
+        if Self::check_timestamp(&mut self.last_video_timestamp, timestamp) {
+            self.timestamp_gaps += 1;
+        }
+
+        if !is_keyframe {
+            return;
+        }
+
+        self.keyframes += 1;
+
+        if let Some(last) = self.last_keyframe_timestamp {
+            if timestamp >= last {
+                let interval = timestamp - last;
+
+                self.keyframe_interval_count += 1;
+                self.keyframe_interval_sum += interval;
+                self.keyframe_interval_min = Some(
+                    self.keyframe_interval_min
+                        .map_or(interval, |m| m.min(interval)),
+                );
+                self.keyframe_interval_max = Some(
+                    self.keyframe_interval_max
+                        .map_or(interval, |m| m.max(interval)),
+                );
+            }
+        }
+
+        self.last_keyframe_timestamp = Some(timestamp);
+    }
+
+    /// Records a key frame interval exceeding the warning threshold
+    pub fn record_keyframe_interval_warning(&mut self) {
+        self.keyframe_interval_warnings += 1;
+    }
+
+    /// Gets the current bit rate, measured in the last window of 1 second (bits per second)
+    pub fn bit_rate(&self) -> u64 {
+        self.last_bit_rate
+    }
+
+    /// Gets the start time (Unix milliseconds)
+    pub fn start_time(&self) -> i64 {
+        self.start_time
+    }
+
+    /// Gets the summary of the statistics
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp (Unix milliseconds)
+    pub fn summary(&self, now: i64) -> PublishStatisticsSummary {
+        let duration = (now - self.start_time).max(0);
+
+        let avg_bit_rate = if duration > 0 {
+            self.total_bytes * 8 * 1000 / (duration as u64)
+        } else {
+            0
+        };
+
+        PublishStatisticsSummary {
+            total_bytes: self.total_bytes,
+            duration,
+            avg_bit_rate,
+            max_bit_rate: if self.max_bit_rate > 0 {
+                self.max_bit_rate
+            } else {
+                // Shorter than a window
+                avg_bit_rate
+            },
+            timestamp_gaps: self.timestamp_gaps,
+            keyframes: self.keyframes,
+            keyframe_interval_avg: if self.keyframe_interval_count > 0 {
+                Some(self.keyframe_interval_sum / self.keyframe_interval_count)
+            } else {
+                None
+            },
+            keyframe_interval_min: self.keyframe_interval_min,
+            keyframe_interval_max: self.keyframe_interval_max,
+            keyframe_interval_warnings: self.keyframe_interval_warnings,
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_statistics() {
+        let mut stats = PublishStatistics::new(0);
+
+        // 4 seconds of stream: 1000 bytes of video every 100 ms, key frames every 2 seconds
+
+        for i in 0..40 {
+            stats.record_video(i * 100, 1000, i * 100, i % 20 == 0);
+            stats.record_audio(i * 100, 100, i * 100);
+        }
+
+        // Gap in the audio timestamps
+
+        stats.record_audio(4000, 100, 6000);
+
+        stats.record_keyframe_interval_warning();
+
+        let summary = stats.summary(4000);
+
+        assert_eq!(summary.total_bytes, 44100);
+        assert_eq!(summary.duration, 4000);
+        assert_eq!(summary.avg_bit_rate, 88200);
+        assert_eq!(summary.max_bit_rate, 88000);
+        assert_eq!(summary.timestamp_gaps, 1);
+        assert_eq!(summary.keyframes, 2);
+        assert_eq!(summary.keyframe_interval_avg, Some(2000));
+        assert_eq!(summary.keyframe_interval_min, Some(2000));
+        assert_eq!(summary.keyframe_interval_max, Some(2000));
+        assert_eq!(summary.keyframe_interval_warnings, 1);
+        assert_eq!(stats.bit_rate(), 88000);
+    }
+}
@@ -1,36 +1,89 @@
-use crate::{server::RtmpServerContext, session::RtmpSessionMessage};
+use crate::{
+    server::{play_slate, PublishStreamFlags, RtmpServerContext, RtmpServerStatusShard},
+    session::RtmpSessionMessage,
+};
 
 /// Removes all the publishers and kills them
 ///
 /// # Arguments
 ///
 /// * `server_context` - Server context
-pub async fn remove_all_publishers(server_context: &RtmpServerContext) {
-    let mut status = server_context.status.lock().await;
+/// * `reason` - Reason of the kill, recorded in the event history of the channels
+pub async fn remove_all_publishers(server_context: &RtmpServerContext, reason: &str) {
+    for shard in server_context.status.shards() {
+        let mut status = shard.lock().await;
 
+        remove_all_publishers_from_shard(server_context, &mut status, reason).await;
+    }
+}
+
+/// Removes all the publishers of a shard of the server status and kills them
+///
+/// # Arguments
+///
+/// * `server_context` - Server context
+/// * `status` - The locked shard
+/// * `reason` - Reason of the kill
+async fn remove_all_publishers_from_shard(
+    server_context: &RtmpServerContext,
+    status: &mut RtmpServerStatusShard,
+    reason: &str,
+) {
     let mut channels_to_delete: Vec<String> = Vec::new();
 
     for (channel, c) in &mut status.channels {
         let mut channel_status = c.lock().await;
 
-        if !channel_status.publishing {
+        if !channel_status.has_stream() {
             continue;
         }
 
         // Kill the publisher
 
         if let Some(pub_sender) = &channel_status.publisher_message_sender {
-            _ = pub_sender.send(RtmpSessionMessage::Kill).await;
+            _ = pub_sender
+                .send(RtmpSessionMessage::Kill {
+                    reason: reason.to_string(),
+                    stop: None,
+                })
+                .await;
         }
 
+        server_context
+            .status
+            .events
+            .record(
+                channel,
+                "publish-killed",
+                channel_status.publisher_id.unwrap_or(0),
+                &format!(
+                    "Stream ID: {} | Reason: {}",
+                    channel_status.stream_id.as_deref().unwrap_or(""),
+                    reason
+                ),
+            )
+            .await;
+
         // Unpublish
 
         channel_status.publishing = false;
         channel_status.publisher_id = None;
+        channel_status.publish_id = None;
         channel_status.publish_status = None;
+        channel_status.stream_metadata = None;
+        channel_status.publisher_bandwidth = None;
+        channel_status.publisher_info = None;
         channel_status.publisher_message_sender = None;
         channel_status.key = None;
+        channel_status.key_rotation = None;
         channel_status.stream_id = None;
+        channel_status.max_players = None;
+        channel_status.flags = PublishStreamFlags::default();
+        channel_status.publish_stats = None;
+        channel_status.resume_timestamp = None;
+        channel_status.resume_end_sender = None;
+        channel_status.recording = None;
+        channel_status.relay_origin = None;
 
         // Notify players
 
@@ -40,6 +93,10 @@ pub async fn remove_all_publishers(server_context: &RtmpServerContext) {
                 .message_sender
                 .send(RtmpSessionMessage::PlayStop)
                 .await;
+
+            if !player.paused {
+                play_slate(server_context, &player.message_sender).await;
+            }
         }
 
         // Check if it can be deleted
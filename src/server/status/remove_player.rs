@@ -8,7 +8,7 @@ use crate::server::RtmpServerContext;
 /// * `channel` - Channel ID
 /// * `player_id` - The ID of the player to remove
 pub async fn remove_player(server_context: &RtmpServerContext, channel: &str, player_id: u64) {
-    let mut status = server_context.status.lock().await;
+    let mut status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get_mut(channel) {
         let channel_mu = c.clone();
@@ -16,6 +16,23 @@ pub async fn remove_player(server_context: &RtmpServerContext, channel: &str, pl
 
         let mut channel_status = channel_mu.lock().await;
 
-        channel_status.players.remove(&player_id);
+        if let Some(player) = channel_status.players.remove(&player_id) {
+            channel_status.removed_players_bytes_out = channel_status
+                .removed_players_bytes_out
+                .wrapping_add(player.bandwidth.output.total_bytes());
+
+            drop(channel_status);
+
+            server_context
+                .status
+                .events
+                .record(
+                    channel,
+                    "player-leave",
+                    player_id,
+                    &format!("IP: {}", player.ip),
+                )
+                .await;
+        }
     }
 }
@@ -1,6 +1,15 @@
+use chrono::Utc;
+use tokio::sync::oneshot;
+
 use crate::{
-    callback::make_stop_callback, control::ControlKeyValidationRequest, log::Logger,
-    server::RtmpServerContext, session::RtmpSessionMessage,
+    callback::{make_stop_callback, CallbackCorrelation},
+    control::ControlKeyValidationRequest,
+    log::Logger,
+    log_debug,
+    server::{
+        play_slate, spawn_task_expire_publisher_resume, PublishStreamFlags, RtmpServerContext,
+    },
+    session::RtmpSessionMessage,
 };
 
 /// Removes a publisher from a channel
@@ -11,13 +20,19 @@ use crate::{
 /// * `server_context` - The server context
 /// * `channel` - The channel ID
 /// * `publisher_id` - ID of the publisher to remove
+/// * `key` - Key provided by the publisher, to send it in the stop callback (empty if unknown)
+/// * `allow_resume` - True if the publisher dropped unexpectedly, so it can resume the stream
+/// * `kill_reason` - Reason, if the stream was killed
 pub async fn remove_publisher(
     logger: &Logger,
     server_context: &RtmpServerContext,
     channel: &str,
     publisher_id: u64,
+    key: &str,
+    allow_resume: bool,
+    kill_reason: Option<&str>,
 ) {
-    let status = server_context.status.lock().await;
+    let status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get(channel) {
         let channel_mu = c.clone();
@@ -25,7 +40,7 @@ pub async fn remove_publisher(
 
         let mut channel_status = channel_mu.lock().await;
 
-        if !channel_status.publishing {
+        if !channel_status.has_stream() {
             return;
         }
 
@@ -35,24 +50,118 @@ pub async fn remove_publisher(
             }
         }
 
-        // Unpublish
+        // Keep the stream during the grace period, so the publisher can resume it
 
-        let unpublished_stream_key = match &channel_status.key {
-            Some(k) => k.clone(),
-            None => "".to_string(),
-        };
+        let resume_seconds = server_context.config.publish_resume_seconds;
+
+        if allow_resume && resume_seconds > 0 && channel_status.publishing {
+            let last_timestamp = match &channel_status.publish_status {
+                Some(s) => {
+                    let publish_status = s.lock().await;
+                    publish_status
+                        .clock
+                        .wrapping_add(publish_status.timestamp_offset)
+                }
+                None => 0,
+            };
+
+            channel_status.publishing = false;
+            channel_status.publish_status = None;
+            channel_status.publisher_message_sender = None;
+            channel_status.resume_timestamp = Some(last_timestamp);
+
+            // The task waiting for the publisher keeps the key,
+            // so the stream can only be ended early through it
+
+            let (end_sender, end_receiver) = oneshot::channel();
+            channel_status.resume_end_sender = Some(end_sender);
+
+            drop(channel_status);
+
+            server_context
+                .status
+                .events
+                .record(
+                    channel,
+                    "publish-dropped",
+                    publisher_id,
+                    &format!("Waiting {} seconds to resume", resume_seconds),
+                )
+                .await;
+
+            log_debug!(
+                logger,
+                format!(
+                    "Channel {}: Waiting {} seconds for the publisher to resume the stream",
+                    channel, resume_seconds
+                )
+            );
+
+            spawn_task_expire_publisher_resume(
+                logger.make_child_logger(""),
+                server_context.clone(),
+                channel.to_string(),
+                publisher_id,
+                key.to_string(),
+                resume_seconds,
+                end_receiver,
+            );
+
+            return;
+        }
+
+        // Unpublish
 
         let unpublished_stream_id = match &channel_status.stream_id {
             Some(i) => i.clone(),
             None => "".to_string(),
         };
 
+        let correlation = CallbackCorrelation {
+            session_id: channel_status.publisher_id.unwrap_or(0),
+            publish_id: channel_status.publish_id.clone().unwrap_or_default(),
+        };
+
+        let bandwidth_stats = channel_status.get_bandwidth_stats();
+
+        log_debug!(
+            logger,
+            format!(
+                "Channel {} bandwidth: Bytes in: {} | Bytes out: {} | Bit rate in: {} bps | Bit rate out: {} bps | Publisher RTT: {}",
+                channel,
+                bandwidth_stats.bytes_in,
+                bandwidth_stats.bytes_out,
+                bandwidth_stats.bit_rate_in,
+                bandwidth_stats.bit_rate_out,
+                match bandwidth_stats.publisher_rtt {
+                    Some(rtt) => format!("{} ms", rtt),
+                    None => "unknown".to_string(),
+                }
+            )
+        );
+
+        let publish_stats = channel_status
+            .publish_stats
+            .take()
+            .map(|s| s.summary(Utc::now().timestamp_millis()));
+
         channel_status.publishing = false;
         channel_status.publisher_id = None;
+        channel_status.publish_id = None;
         channel_status.publish_status = None;
+        channel_status.stream_metadata = None;
+        channel_status.publisher_bandwidth = None;
+        let publisher_info = channel_status.publisher_info.take();
         channel_status.publisher_message_sender = None;
         channel_status.key = None;
+        channel_status.key_rotation = None;
         channel_status.stream_id = None;
+        channel_status.max_players = None;
+        channel_status.flags = PublishStreamFlags::default();
+        channel_status.resume_timestamp = None;
+        channel_status.resume_end_sender = None;
+        channel_status.recording = None;
+        let relay_origin = channel_status.relay_origin.take();
 
         // Notify players
 
@@ -62,10 +171,44 @@ pub async fn remove_publisher(
                 .message_sender
                 .send(RtmpSessionMessage::PlayStop)
                 .await;
+
+            if !player.paused {
+                play_slate(server_context, &player.message_sender).await;
+            }
         }
 
         drop(channel_status);
 
+        server_context
+            .status
+            .events
+            .record(
+                channel,
+                if kill_reason.is_some() {
+                    "publish-killed"
+                } else {
+                    "publish-end"
+                },
+                publisher_id,
+                &match kill_reason {
+                    Some(reason) => {
+                        format!("Stream ID: {} | Reason: {}", &unpublished_stream_id, reason)
+                    }
+                    None => format!("Stream ID: {}", &unpublished_stream_id),
+                },
+            )
+            .await;
+
+        // Streams pulled from other nodes are reported by the node publishing them
+
+        if relay_origin.is_some() {
+            return;
+        }
+
+        if let Some(cluster) = &server_context.cluster {
+            cluster.unregister_channel(logger, channel).await;
+        }
+
         // Send callback
 
         match &server_context.control_key_validator_sender {
@@ -75,6 +218,8 @@ pub async fn remove_publisher(
                     .send(ControlKeyValidationRequest::PublishEnd {
                         channel: channel.to_string(),
                         stream_id: unpublished_stream_id,
+                        stats: publish_stats,
+                        publisher: publisher_info,
                     })
                     .await;
             }
@@ -82,10 +227,13 @@ pub async fn remove_publisher(
                 // Callback
                 make_stop_callback(
                     logger,
-                    &server_context.config.callback,
+                    server_context.config.get_callback_config(channel),
                     channel,
-                    &unpublished_stream_key,
+                    key,
+                    &correlation,
                     &unpublished_stream_id,
+                    publish_stats.as_ref(),
+                    kill_reason,
                 )
                 .await;
             }
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::{
+    log::Logger,
+    server::{remove_publisher, start_idle_players, try_clear_channel, RtmpServerContext},
+    session::SessionReadThreadContext,
+};
+
+/// Request to end a stream waiting for its publisher to resume it,
+/// sent to the task waiting for the publisher, since only the task keeps the key
+pub struct PublisherResumeEnd {
+    /// Reason, if the stream was killed
+    pub reason: Option<String>,
+
+    /// Sender to notify once the stream is ended
+    pub done_sender: oneshot::Sender<()>,
+}
+
+/// Resumes a stream waiting for its publisher to reconnect,
+/// if the provided key is the key of the stream
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `session_context` - The context of the reconnecting publisher session
+/// * `channel` - Channel ID
+/// * `key` - Channel key
+///
+/// # Return value
+///
+/// Returns true if the stream was resumed, false if there was no stream to resume
+pub async fn resume_publisher(
+    server_context: &RtmpServerContext,
+    session_context: &mut SessionReadThreadContext,
+    channel: &str,
+    key: &str,
+) -> bool {
+    let publisher_info = session_context.publisher_info().await;
+
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return false;
+        }
+    };
+
+    drop(status);
+
+    let mut c = channel_mu.lock().await;
+
+    if c.publishing {
+        return false;
+    }
+
+    let last_timestamp = match c.resume_timestamp {
+        Some(t) => t,
+        None => {
+            return false;
+        }
+    };
+
+    match &c.key {
+        Some(k) => {
+            if !k.matches(key) {
+                return false;
+            }
+        }
+        None => {
+            return false;
+        }
+    }
+
+    // Continue the timestamps of the packets sent to the players
+
+    let mut publish_status = session_context.publish_status.lock().await;
+    publish_status.timestamp_offset = last_timestamp;
+    publish_status.media_mode = c.flags.media;
+    drop(publish_status);
+
+    // Update
+    c.resume_timestamp = None;
+    c.resume_end_sender = None;
+    c.publishing = true;
+    c.publisher_id = Some(session_context.id);
+    c.publish_status = Some(session_context.publish_status.clone());
+    c.publisher_message_sender = Some(session_context.session_msg_sender.clone());
+    c.publisher_bandwidth = Some(session_context.bandwidth.clone());
+    c.publisher_info = Some(publisher_info);
+
+    start_idle_players(&mut c, session_context).await;
+
+    drop(c);
+
+    session_context.read_status.channel_status = Some(channel_mu);
+
+    server_context
+        .status
+        .events
+        .record(
+            channel,
+            "publish-resume",
+            session_context.id,
+            &format!("IP: {}", session_context.ip),
+        )
+        .await;
+
+    true
+}
+
+/// Ends the stream waiting for its publisher to reconnect, if any,
+/// unpublishing it from the channel
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+pub async fn end_publisher_resume(server_context: &RtmpServerContext, channel: &str) {
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return;
+        }
+    };
+
+    drop(status);
+
+    let mut channel_status = channel_mu.lock().await;
+
+    if channel_status.publishing {
+        return;
+    }
+
+    let end_sender = match channel_status.resume_end_sender.take() {
+        Some(s) => s,
+        None => {
+            return;
+        }
+    };
+
+    drop(channel_status);
+
+    request_publisher_resume_end(end_sender, None).await;
+}
+
+/// Requests the task waiting for the publisher to resume the stream to end it,
+/// waiting for the stream to be ended
+///
+/// # Arguments
+///
+/// * `end_sender` - Sender of the requests to end the stream, taken from the channel status
+/// * `reason` - Reason, if the stream was killed
+pub async fn request_publisher_resume_end(
+    end_sender: oneshot::Sender<PublisherResumeEnd>,
+    reason: Option<String>,
+) {
+    let (done_sender, done_receiver) = oneshot::channel();
+
+    if end_sender
+        .send(PublisherResumeEnd {
+            reason,
+            done_sender,
+        })
+        .is_ok()
+    {
+        // If the grace period already expired, the request is dropped
+        // once the task ends the stream
+        _ = done_receiver.await;
+    }
+}
+
+/// Spawns a task to end the stream if the publisher
+/// does not resume it before the grace period expires
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `publisher_id` - ID of the publisher that dropped
+/// * `key` - Key provided by the publisher, to send it in the stop callback
+/// * `resume_seconds` - Duration of the grace period (seconds)
+/// * `end_receiver` - Receiver of the requests to end the stream before the grace period expires
+pub fn spawn_task_expire_publisher_resume(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    channel: String,
+    publisher_id: u64,
+    key: String,
+    resume_seconds: u32,
+    mut end_receiver: oneshot::Receiver<PublisherResumeEnd>,
+) {
+    tokio::spawn(async move {
+        let end = tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(resume_seconds as u64)) => None,
+            r = &mut end_receiver => match r {
+                Ok(end) => Some(end),
+                Err(_) => {
+                    // The stream was resumed or removed
+                    return;
+                }
+            },
+        };
+
+        // If the stream was resumed, the publisher is a different session, so this does nothing
+
+        remove_publisher(
+            &logger,
+            &server_context,
+            &channel,
+            publisher_id,
+            &key,
+            false,
+            end.as_ref().and_then(|e| e.reason.as_deref()),
+        )
+        .await;
+
+        match end {
+            Some(end) => {
+                _ = end.done_sender.send(());
+            }
+            None => {
+                try_clear_channel(&server_context, &channel).await;
+            }
+        }
+    });
+}
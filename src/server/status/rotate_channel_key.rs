@@ -0,0 +1,133 @@
+// Logic to rotate the key the players must provide to play a channel
+
+use std::time::Duration;
+
+use crate::{
+    log::Logger,
+    log_debug, log_info,
+    server::{kick_players_matching, RtmpServerContext},
+};
+
+/// Rotates the key the players must provide to play a channel being published,
+/// without disconnecting the publisher (eg: if the key was leaked).
+/// The players that provided the previous key are kicked
+/// after the grace period, unless they reconnect with the new key.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `new_key` - The new key for the players
+/// * `grace_seconds` - Time the previous key is still accepted (seconds)
+///
+/// # Return value
+///
+/// Returns an error message if the key could not be rotated
+pub async fn rotate_channel_key(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    new_key: &str,
+    grace_seconds: u32,
+) -> Result<(), String> {
+    if new_key.is_empty() {
+        return Err("The new key cannot be empty".to_string());
+    }
+
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return Err("The channel is not publishing".to_string());
+        }
+    };
+
+    drop(status);
+
+    let mut channel_status = channel_mu.lock().await;
+
+    let publish_id = match (&channel_status.publish_id, channel_status.has_stream()) {
+        (Some(id), true) => id.clone(),
+        _ => {
+            return Err("The channel is not publishing".to_string());
+        }
+    };
+
+    channel_status.rotate_player_key(new_key, Duration::from_secs(grace_seconds as u64));
+
+    drop(channel_status);
+
+    log_info!(
+        logger,
+        format!(
+            "Rotated the player key of channel {} (grace period: {} seconds)",
+            channel, grace_seconds
+        )
+    );
+
+    server_context
+        .status
+        .events
+        .record(
+            channel,
+            "key-rotated",
+            0,
+            &format!("Grace period: {} seconds", grace_seconds),
+        )
+        .await;
+
+    spawn_task_expire_previous_key(
+        logger.make_child_logger(""),
+        server_context.clone(),
+        channel.to_string(),
+        publish_id,
+        grace_seconds,
+    );
+
+    Ok(())
+}
+
+/// Spawns a task to kick the players using the previous key
+/// of a channel once the grace period expires
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `publish_id` - Unique ID of the publication the key was rotated for
+/// * `grace_seconds` - Duration of the grace period (seconds)
+fn spawn_task_expire_previous_key(
+    logger: Logger,
+    server_context: RtmpServerContext,
+    channel: String,
+    publish_id: String,
+    grace_seconds: u32,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(grace_seconds as u64)).await;
+
+        // If the channel was published again, the rotation no longer applies
+
+        let kicked = kick_players_matching(
+            &logger,
+            &server_context,
+            &channel,
+            |channel_status, _, player| {
+                channel_status.publish_id.as_deref() == Some(publish_id.as_str())
+                    && !channel_status.is_player_key_valid(&player.provided_key)
+            },
+        )
+        .await;
+
+        log_debug!(
+            logger,
+            format!(
+                "Previous player key of channel {} expired: {} players kicked",
+                channel, kicked
+            )
+        );
+    });
+}
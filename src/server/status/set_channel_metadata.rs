@@ -1,28 +1,39 @@
 use std::sync::Arc;
 
-use crate::{server::RtmpServerContext, session::RtmpSessionMessage};
+use crate::{
+    rtmp::{
+        RtmpPacket, StreamMetadata, RTMP_CHANNEL_METADATA_UPDATE, RTMP_CHUNK_TYPE_0, RTMP_TYPE_DATA,
+    },
+    server::RtmpServerContext,
+    session::RtmpSessionMessage,
+};
 
 /// Sets channel metadata
 ///
+/// Each time the metadata is set, its version is increased,
+/// and the players and the recording of the channel receive the update
+///
 /// # Arguments
 ///
 /// * `server_context` - The server context
 /// * `channel` - The channel ID
 /// * `publisher_id` - ID of the publisher setting the metadata
 /// * `metadata` - The metadata
+/// * `stream_metadata` - The metadata, parsed
 pub async fn set_channel_metadata(
     server_context: &RtmpServerContext,
     channel: &str,
     publisher_id: u64,
     metadata: Arc<Vec<u8>>,
+    stream_metadata: StreamMetadata,
 ) {
-    let mut status = server_context.status.lock().await;
+    let mut status = server_context.status.lock_shard(channel).await;
 
     if let Some(c) = status.channels.get_mut(channel) {
         let channel_mu = c.clone();
         drop(status);
 
-        let channel_status = channel_mu.lock().await;
+        let mut channel_status = channel_mu.lock().await;
 
         if let Some(pid) = channel_status.publisher_id {
             if pid != publisher_id {
@@ -31,7 +42,7 @@ pub async fn set_channel_metadata(
         }
 
         let publish_status_mu = match &channel_status.publish_status {
-            Some(s) => s,
+            Some(s) => s.clone(),
             None => {
                 return;
             }
@@ -39,12 +50,22 @@ pub async fn set_channel_metadata(
 
         // Set metadata in the status
 
+        channel_status.metadata_version += 1;
+        let metadata_version = channel_status.metadata_version;
+
         let mut publish_status = publish_status_mu.lock().await;
 
         publish_status.metadata = metadata.clone();
+        publish_status.metadata_version = metadata_version;
+
+        let clock = publish_status
+            .clock
+            .wrapping_add(publish_status.timestamp_offset);
 
         drop(publish_status);
 
+        channel_status.stream_metadata = Some(stream_metadata);
+
         // Send metadata to players
 
         for player in channel_status.players.values() {
@@ -52,8 +73,24 @@ pub async fn set_channel_metadata(
                 .message_sender
                 .send(RtmpSessionMessage::PlayMetadata {
                     metadata: metadata.clone(),
+                    metadata_version,
                 })
                 .await;
         }
+
+        // Send metadata to the recording, placed at the current time of the stream
+
+        if channel_status.recording.is_some() && !metadata.is_empty() {
+            let mut packet = RtmpPacket::new_blank();
+
+            packet.header.format = RTMP_CHUNK_TYPE_0;
+            packet.header.channel_id = RTMP_CHANNEL_METADATA_UPDATE;
+            packet.header.packet_type = RTMP_TYPE_DATA;
+            packet.payload = metadata.to_vec();
+            packet.header.length = packet.payload.len();
+            packet.header.timestamp = clock;
+
+            _ = channel_status.packet_sender.send(Arc::new(packet));
+        }
     }
 }
@@ -1,13 +1,30 @@
 use std::sync::Arc;
 
+use chrono::Utc;
 use tokio::sync::Mutex;
 
 use crate::{
-    server::{RtmpChannelStatus, RtmpServerContext},
+    server::{
+        PublishStatistics, PublishStreamFlags, RtmpChannelStatus, RtmpPublisherInfo,
+        RtmpServerContext,
+    },
     session::{RtmpSessionMessage, SessionReadThreadContext},
-    utils::string_compare_time_safe,
+    utils::StreamKeyHash,
 };
 
+/// Authorization to publish, given by the key validation
+#[derive(Clone)]
+pub struct PublishAuthorization {
+    /// Stream ID
+    pub stream_id: String,
+
+    /// Max number of concurrent players for the channel (None to use the server configuration)
+    pub max_players: Option<u32>,
+
+    /// Flags of the stream, not parsed yet (None if not provided)
+    pub flags: Option<String>,
+}
+
 /// Sets a publisher for a channel
 ///
 /// # Arguments
@@ -16,7 +33,9 @@ use crate::{
 /// * `session_context` - The session context
 /// * `channel` - Channel ID
 /// * `key` - Channel key
-/// * `stream_id` - Stream ID
+/// * `publish_id` - Unique ID of the publication
+/// * `authorization` - The authorization given by the key validation
+/// * `flags` - The flags of the stream
 ///
 /// # Return value
 ///
@@ -26,11 +45,21 @@ pub async fn set_publisher(
     session_context: &mut SessionReadThreadContext,
     channel: &str,
     key: &str,
-    stream_id: &str,
+    publish_id: &str,
+    authorization: &PublishAuthorization,
+    flags: &PublishStreamFlags,
 ) -> bool {
     let channel_status_ref: Arc<Mutex<RtmpChannelStatus>>;
 
-    let mut status = server_context.status.lock().await;
+    let publisher_info = session_context.publisher_info().await;
+
+    // Media accepted for the stream
+
+    let mut publish_status = session_context.publish_status.lock().await;
+    publish_status.media_mode = flags.media;
+    drop(publish_status);
+
+    let mut status = server_context.status.lock_shard(channel).await;
 
     match status.channels.get(channel) {
         Some(channel_mu) => {
@@ -40,62 +69,36 @@ pub async fn set_publisher(
 
             let mut c = channel_mu_clone.lock().await;
 
-            if c.publishing {
+            if c.has_stream() {
                 return false;
             }
 
             // Update
-            c.key = Some(key.to_string());
-            c.stream_id = Some(stream_id.to_string());
-            c.publishing = true;
-            c.publisher_id = Some(session_context.id);
-            c.publish_status = Some(session_context.publish_status.clone());
-            c.publisher_message_sender = Some(session_context.session_msg_sender.clone());
-
-            // Get idle players
-
-            let mut players_to_remove: Vec<u64> = Vec::new();
-
-            for (player_id, player) in &mut c.players {
-                if player.idle {
-                    if string_compare_time_safe(&player.provided_key, key) {
-                        // Correct key, start player
-
-                        let mut publish_status = session_context.publish_status.lock().await;
-
-                        let play_start_message = publish_status.get_play_start_message();
-
-                        if player.gop_clear {
-                            publish_status.clear_gop();
-                        }
-
-                        drop(publish_status);
-
-                        _ = player.message_sender.send(play_start_message);
-                    } else {
-                        // Invalid key
-                        players_to_remove.push(*player_id);
-                        _ = player.message_sender.send(RtmpSessionMessage::InvalidKey);
-                    }
-
-                    player.idle = false;
-                }
-            }
-
-            for player_to_remove in players_to_remove {
-                c.players.remove(&player_to_remove);
-            }
+            set_channel_publisher(
+                &mut c,
+                session_context,
+                key,
+                publish_id,
+                authorization,
+                flags,
+                publisher_info,
+            );
+
+            start_idle_players(&mut c, session_context).await;
         }
         None => {
-            let mut new_channel_status = RtmpChannelStatus::new();
-
-            new_channel_status.key = Some(key.to_string());
-            new_channel_status.stream_id = Some(stream_id.to_string());
-            new_channel_status.publishing = true;
-            new_channel_status.publisher_id = Some(session_context.id);
-            new_channel_status.publish_status = Some(session_context.publish_status.clone());
-            new_channel_status.publisher_message_sender =
-                Some(session_context.session_msg_sender.clone());
+            let mut new_channel_status =
+                RtmpChannelStatus::new(server_context.config.player_packet_buffer_size);
+
+            set_channel_publisher(
+                &mut new_channel_status,
+                session_context,
+                key,
+                publish_id,
+                authorization,
+                flags,
+                publisher_info,
+            );
 
             let channel_mu = Arc::new(Mutex::new(new_channel_status));
 
@@ -109,5 +112,113 @@ pub async fn set_publisher(
 
     session_context.read_status.channel_status = Some(channel_status_ref);
 
+    server_context
+        .status
+        .events
+        .record(
+            channel,
+            "publish-start",
+            session_context.id,
+            &format!(
+                "Stream ID: {} | IP: {}",
+                &authorization.stream_id, session_context.ip
+            ),
+        )
+        .await;
+
     true
 }
+
+/// Sets the publisher session in the status of a channel
+///
+/// # Arguments
+///
+/// * `channel_status` - The channel status
+/// * `session_context` - The context of the publisher session
+/// * `key` - Channel key
+/// * `publish_id` - Unique ID of the publication
+/// * `authorization` - The authorization given by the key validation
+/// * `flags` - The flags of the stream
+/// * `publisher_info` - Information of the publisher
+pub fn set_channel_publisher(
+    channel_status: &mut RtmpChannelStatus,
+    session_context: &SessionReadThreadContext,
+    key: &str,
+    publish_id: &str,
+    authorization: &PublishAuthorization,
+    flags: &PublishStreamFlags,
+    publisher_info: RtmpPublisherInfo,
+) {
+    channel_status.key = Some(StreamKeyHash::new(key));
+    channel_status.key_rotation = None;
+    channel_status.stream_id = Some(authorization.stream_id.clone());
+    channel_status.max_players = authorization.max_players;
+    channel_status.flags = flags.clone();
+    channel_status.publish_stats = Some(PublishStatistics::new(Utc::now().timestamp_millis()));
+    channel_status.publishing = true;
+    channel_status.publisher_id = Some(session_context.id);
+    channel_status.publish_id = Some(publish_id.to_string());
+    channel_status.publish_status = Some(session_context.publish_status.clone());
+    channel_status.publisher_message_sender = Some(session_context.session_msg_sender.clone());
+    channel_status.publisher_bandwidth = Some(session_context.bandwidth.clone());
+    channel_status.publisher_info = Some(publisher_info);
+}
+
+/// Starts the idle players of a channel, after its publisher is set.
+/// Players that provided an invalid key are removed from the channel.
+///
+/// # Arguments
+///
+/// * `channel_status` - The channel status
+/// * `session_context` - The context of the publisher session
+pub async fn start_idle_players(
+    channel_status: &mut RtmpChannelStatus,
+    session_context: &SessionReadThreadContext,
+) {
+    let packet_sender = channel_status.packet_sender.clone();
+    let mut players_to_remove: Vec<u64> = Vec::new();
+
+    let valid_key_players: Vec<u64> = channel_status
+        .players
+        .iter()
+        .filter(|(_, player)| channel_status.is_player_key_valid(&player.provided_key))
+        .map(|(player_id, _)| *player_id)
+        .collect();
+
+    for (player_id, player) in &mut channel_status.players {
+        if player.idle {
+            if valid_key_players.contains(player_id) {
+                // Correct key, start player
+
+                let mut publish_status = session_context.publish_status.lock().await;
+
+                let play_start_message = publish_status.get_play_start_message(
+                    packet_sender.subscribe(),
+                    player.low_latency,
+                    Some(session_context.bandwidth.clone()),
+                );
+
+                if player.gop_clear {
+                    publish_status.clear_gop();
+                }
+
+                drop(publish_status);
+
+                _ = player.message_sender.send(play_start_message).await;
+            } else {
+                // Invalid key
+                players_to_remove.push(*player_id);
+                _ = player
+                    .message_sender
+                    .send(RtmpSessionMessage::InvalidKey)
+                    .await;
+            }
+
+            player.idle = false;
+        }
+    }
+
+    for player_to_remove in players_to_remove {
+        channel_status.players.remove(&player_to_remove);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    auth::StoredStreamKey, log::Logger, log_info, log_warning, server::RtmpServerContext,
+    utils::validate_id_string,
+};
+
+/// Generates a random key to publish on a channel,
+/// storing it in the built-in key store
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `expiration_seconds` - Time until the key expires (seconds, 0 to never expire)
+///
+/// # Return value
+///
+/// Returns the stored key and the key itself, or an error message
+pub async fn generate_stored_channel_key(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    expiration_seconds: u32,
+) -> Result<(StoredStreamKey, String), String> {
+    if !server_context.status.key_store.is_enabled() {
+        return Err("The key store is disabled".to_string());
+    }
+
+    if let Err(e) = validate_id_string(channel, &server_context.config.id_validation) {
+        return Err(format!("Invalid channel: {}", e));
+    }
+
+    let (stored_key, key, save_res) = server_context
+        .status
+        .key_store
+        .generate(channel, expiration_seconds)
+        .await;
+
+    if let Err(e) = save_res {
+        log_warning!(logger, e);
+    }
+
+    log_info!(
+        logger,
+        format!("Generated key {} for channel {}", &stored_key.id, channel)
+    );
+
+    Ok((stored_key, key))
+}
+
+/// Revokes a key of the built-in key store
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `key_id` - ID of the key
+///
+/// # Return value
+///
+/// Returns an error message if the key could not be revoked
+pub async fn revoke_stored_channel_key(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    channel: &str,
+    key_id: &str,
+) -> Result<(), String> {
+    let (revoked, save_res) = server_context
+        .status
+        .key_store
+        .revoke(channel, key_id)
+        .await;
+
+    if !revoked {
+        return Err("Key not found".to_string());
+    }
+
+    if let Err(e) = save_res {
+        log_warning!(logger, e);
+    }
+
+    log_info!(
+        logger,
+        format!("Revoked key {} for channel {}", key_id, channel)
+    );
+
+    Ok(())
+}
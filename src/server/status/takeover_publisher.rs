@@ -0,0 +1,199 @@
+use chrono::Utc;
+
+use crate::{
+    callback::{make_stop_callback, CallbackCorrelation},
+    control::ControlKeyValidationRequest,
+    log::Logger,
+    server::{
+        set_channel_publisher, start_idle_players, PublishAuthorization, PublishStreamFlags,
+        RtmpServerContext,
+    },
+    session::{RtmpSessionMessage, SessionReadThreadContext},
+};
+
+/// Replaces the publisher of a channel with a new publisher session.
+/// Only allowed if the new publisher provided the same key.
+/// The new publisher is set along with the kill of the existing one,
+/// so the channel is never left without a publisher.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `server_context` - The server context
+/// * `session_context` - The context of the new publisher session
+/// * `channel` - Channel ID
+/// * `key` - Channel key
+/// * `publish_id` - Unique ID of the publication
+/// * `authorization` - The authorization given by the key validation
+/// * `flags` - The flags of the stream
+/// * `reason` - Reason of the kill, recorded in the event history of the channel
+///
+/// # Return value
+///
+/// Returns true if the publisher was replaced,
+/// false if the channel is not being published with the same key
+#[allow(clippy::too_many_arguments)]
+pub async fn takeover_publisher(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &mut SessionReadThreadContext,
+    channel: &str,
+    key: &str,
+    publish_id: &str,
+    authorization: &PublishAuthorization,
+    flags: &PublishStreamFlags,
+    reason: &str,
+) -> bool {
+    let publisher_info = session_context.publisher_info().await;
+
+    // Media accepted for the stream
+
+    let mut publish_status = session_context.publish_status.lock().await;
+    publish_status.media_mode = flags.media;
+    drop(publish_status);
+
+    let status = server_context.status.lock_shard(channel).await;
+
+    let channel_mu = match status.channels.get(channel) {
+        Some(c) => c.clone(),
+        None => {
+            return false;
+        }
+    };
+
+    drop(status);
+
+    let mut channel_status = channel_mu.lock().await;
+
+    // Streams pulled from other nodes are not replaced
+
+    if !channel_status.publishing || channel_status.relay_origin.is_some() {
+        return false;
+    }
+
+    match &channel_status.key {
+        Some(k) => {
+            if !k.matches(key) {
+                return false;
+            }
+        }
+        None => {
+            return false;
+        }
+    }
+
+    // Kill the existing publisher.
+    // Its session cleanup does not remove the new publisher, since the publisher ID changes.
+
+    let replaced_publisher_id = channel_status.publisher_id.unwrap_or(0);
+
+    let correlation = CallbackCorrelation {
+        session_id: replaced_publisher_id,
+        publish_id: channel_status.publish_id.clone().unwrap_or_default(),
+    };
+
+    let replaced_stream_id = channel_status.stream_id.clone().unwrap_or_default();
+
+    let replaced_stats = channel_status
+        .publish_stats
+        .take()
+        .map(|s| s.summary(Utc::now().timestamp_millis()));
+
+    let replaced_publisher_info = channel_status.publisher_info.take();
+
+    if let Some(pub_sender) = channel_status.publisher_message_sender.take() {
+        _ = pub_sender
+            .send(RtmpSessionMessage::Kill {
+                reason: reason.to_string(),
+                stop: None,
+            })
+            .await;
+    }
+
+    channel_status.stream_metadata = None;
+    channel_status.recording = None;
+
+    // Stop the players, so they start again with the new publisher
+
+    for player in channel_status.players.values_mut() {
+        player.idle = true;
+        _ = player
+            .message_sender
+            .send(RtmpSessionMessage::PlayStop)
+            .await;
+    }
+
+    // Set the new publisher
+
+    set_channel_publisher(
+        &mut channel_status,
+        session_context,
+        key,
+        publish_id,
+        authorization,
+        flags,
+        publisher_info,
+    );
+
+    start_idle_players(&mut channel_status, session_context).await;
+
+    drop(channel_status);
+
+    session_context.read_status.channel_status = Some(channel_mu);
+
+    server_context
+        .status
+        .events
+        .record(
+            channel,
+            "publish-killed",
+            replaced_publisher_id,
+            &format!("Stream ID: {} | Reason: {}", &replaced_stream_id, reason),
+        )
+        .await;
+
+    server_context
+        .status
+        .events
+        .record(
+            channel,
+            "publish-start",
+            session_context.id,
+            &format!(
+                "Stream ID: {} | IP: {}",
+                &authorization.stream_id, session_context.ip
+            ),
+        )
+        .await;
+
+    // Notify the end of the replaced stream.
+    // The new publisher provided the same key, so it is sent in the stop callback.
+
+    match &server_context.control_key_validator_sender {
+        Some(sender) => {
+            _ = sender
+                .send(ControlKeyValidationRequest::PublishEnd {
+                    channel: channel.to_string(),
+                    stream_id: replaced_stream_id,
+                    stats: replaced_stats,
+                    publisher: replaced_publisher_info,
+                })
+                .await;
+        }
+        None => {
+            make_stop_callback(
+                logger,
+                server_context.config.get_callback_config(channel),
+                channel,
+                key,
+                &correlation,
+                &replaced_stream_id,
+                replaced_stats.as_ref(),
+                Some(reason),
+            )
+            .await;
+        }
+    }
+
+    true
+}
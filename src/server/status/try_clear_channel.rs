@@ -8,12 +8,12 @@ use crate::server::RtmpServerContext;
 /// * `server_context` - The server context
 /// * `channel` - The channel ID
 pub async fn try_clear_channel(server_context: &RtmpServerContext, channel: &str) {
-    let mut status = server_context.status.lock().await;
+    let mut status = server_context.status.lock_shard(channel).await;
 
     let should_delete = match status.channels.get(channel) {
         Some(c) => {
             let channel_status = c.lock().await;
-            !channel_status.publishing && channel_status.players.is_empty()
+            !channel_status.has_stream() && channel_status.players.is_empty()
         }
         None => false,
     };
@@ -4,53 +4,84 @@ use std::{net::IpAddr, sync::Arc};
 
 use tokio::{
     io::AsyncWriteExt,
-    net::{TcpListener, TcpStream},
-    sync::{mpsc::Sender, Mutex},
+    net::TcpStream,
+    sync::{mpsc::Sender, watch, Mutex},
 };
 
-use crate::{log::Logger, log_error, log_info};
+use crate::{
+    log::Logger,
+    log_info,
+    session::{BandwidthCountingWriter, SessionBandwidth, WriteTimeoutWriter},
+    trace::{SessionTraceTap, TraceCapturingWriter},
+    utils::normalize_ip_address,
+};
 
-use super::{handle_connection, RtmpServerContextExtended};
+use super::{
+    apply_tcp_socket_options, handle_connection, wait_for_stop, AcceptBackoff, BoundListener,
+    IpConnectionGuard, OpenSocketGuard, RtmpServerContextExtended,
+};
 
 /// Run the TCP server
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `server_context` - The server context
+/// * `listener` - The listener, already bound
+/// * `stop_receiver` - Receiver to stop accepting connections
+/// * `end_notifier` - Sender to notify when the server ends
 pub fn tcp_server(
     logger: Arc<Logger>,
     server_context: RtmpServerContextExtended,
+    listener: BoundListener,
+    mut stop_receiver: watch::Receiver<bool>,
     end_notifier: Sender<()>,
 ) {
     tokio::spawn(async move {
-        let listen_addr = server_context.config.get_tcp_listen_addr();
-
-        // Create listener
-        let listener = match TcpListener::bind(&listen_addr).await {
-            Ok(l) => l,
-            Err(e) => {
-                log_error!(logger, format!("Could not create TCP listener: {}", e));
-                end_notifier
-                    .send(())
-                    .await
-                    .expect("failed to notify to main thread");
-                return;
-            }
-        };
+        log_info!(logger, format!("Listening on {}", listener.addr));
 
-        log_info!(logger, format!("Listening on {}", listen_addr));
+        let mut backoff = AcceptBackoff::new();
 
         loop {
-            let accept_res = listener.accept().await;
+            let accept_res = tokio::select! {
+                r = listener.listener.accept() => r,
+                _ = wait_for_stop(&mut stop_receiver) => {
+                    log_info!(logger, format!("Stopped listening on {}", listener.addr));
+                    end_notifier
+                        .send(())
+                        .await
+                        .expect("failed to notify to main thread");
+                    return;
+                }
+            };
 
             match accept_res {
                 Ok((connection, addr)) => {
+                    backoff.reset();
+
+                    // Reject the connection if close to the open files limit
+                    let open_socket = match server_context.open_sockets.try_open(&logger) {
+                        Some(g) => g,
+                        None => {
+                            drop(connection);
+                            continue;
+                        }
+                    };
+
                     // Handle connection
                     handle_connection_tcp(
                         logger.clone(),
                         server_context.clone(),
                         connection,
-                        addr.ip(),
+                        normalize_ip_address(addr.ip()),
+                        open_socket,
                     );
                 }
                 Err(e) => {
-                    log_error!(logger, format!("Could not accept connection: {}", e));
+                    if backoff.handle_error(&logger, &e).await {
+                        continue;
+                    }
+
                     end_notifier
                         .send(())
                         .await
@@ -68,25 +99,46 @@ fn handle_connection_tcp(
     server_context: RtmpServerContextExtended,
     mut connection: TcpStream,
     ip: IpAddr,
+    open_socket: OpenSocketGuard,
 ) {
     tokio::spawn(async move {
+        let _open_socket = open_socket;
+
         let is_exempted = server_context
             .config
             .as_ref()
             .max_concurrent_connections_whitelist
             .contains_ip(&ip);
         let mut should_accept = true;
+        let mut ip_connection: Option<IpConnectionGuard> = None;
+
+        if !is_exempted && !server_context.ip_rate_limiter.check_ip(&logger, &ip).await {
+            let _ = connection.shutdown().await;
+            return;
+        }
 
         if !is_exempted {
-            let mut ip_counter_v = server_context.ip_counter.as_ref().lock().await;
-            should_accept = (*ip_counter_v).add(&ip);
-            drop(ip_counter_v);
+            ip_connection = IpConnectionGuard::try_add(&server_context.ip_counter, &ip).await;
+            should_accept = ip_connection.is_some();
         }
 
         if should_accept {
+            apply_tcp_socket_options(&logger, &connection, &server_context.config.tcp_socket);
+
             // Handle connection
             let (mut read_stream, write_stream) = connection.into_split();
-            let write_stream_mu = Arc::new(Mutex::new(write_stream));
+            let bandwidth = Arc::new(SessionBandwidth::new());
+            let trace = Arc::new(SessionTraceTap::new());
+            let write_stream_mu = Arc::new(Mutex::new(TraceCapturingWriter::new(
+                server_context.chaos.wrap(BandwidthCountingWriter::new(
+                    WriteTimeoutWriter::new(
+                        write_stream,
+                        server_context.config.write_timeout_seconds,
+                    ),
+                    bandwidth.clone(),
+                )),
+                trace.clone(),
+            )));
 
             handle_connection(
                 logger,
@@ -94,6 +146,9 @@ fn handle_connection_tcp(
                 &mut read_stream,
                 write_stream_mu.clone(),
                 ip,
+                bandwidth,
+                trace,
+                None,
             )
             .await;
 
@@ -103,11 +158,7 @@ fn handle_connection_tcp(
             drop(write_stream_mu_v);
 
             // After connection is closed, remove from ip counter
-            if !is_exempted {
-                let mut ip_counter_v = server_context.ip_counter.as_ref().lock().await;
-                (*ip_counter_v).remove(&ip);
-                drop(ip_counter_v);
-            }
+            drop(ip_connection);
         } else {
             log_info!(
                 logger,
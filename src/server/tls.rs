@@ -1,137 +1,117 @@
-// TCP server
+// TLS server
 
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use filetime::FileTime;
-use rustls::server::ResolvesServerCert;
-use rustls::sign::{CertifiedKey, SigningKey};
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::{ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::RootCertStore;
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::Mutex;
-use tokio::{net::TcpListener, sync::mpsc::Sender};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::{watch, Mutex};
 
 use rustls::pki_types::pem::PemObject;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio_rustls::{rustls, TlsAcceptor};
 
+use crate::acme::{acme_certificate_needs_renewal, obtain_acme_certificate};
 use crate::log::Logger;
+use crate::session::{BandwidthCountingWriter, SessionBandwidth, WriteTimeoutWriter};
+use crate::trace::{SessionTraceTap, TraceCapturingWriter};
+use crate::utils::{normalize_ip_address, parse_certificate_identity};
 use crate::{log_debug, log_error, log_info};
 
-use super::{handle_connection, RtmpServerConfiguration, RtmpServerContextExtended};
-
-/// Run the TCP server
+use super::{
+    apply_tcp_socket_options, handle_connection, wait_for_stop, AcceptBackoff, BoundListener,
+    IpConnectionGuard, OpenSocketGuard, RtmpServerConfiguration, RtmpServerContextExtended,
+    TlsCertificateConfiguration, TlsServerConfiguration,
+};
+
+/// Interval to check if the ACME certificate must be renewed (seconds)
+const ACME_RENEWAL_CHECK_SECONDS: u64 = 12 * 60 * 60;
+
+/// Run the TLS server
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `server_context` - The server context
+/// * `listeners` - The listeners, already bound
+/// * `stop_receiver` - Receiver to stop accepting connections
+/// * `end_notifier` - Sender to notify when the server ends
 pub fn tls_server(
     logger: Arc<Logger>,
     server_context: RtmpServerContextExtended,
+    listeners: Vec<BoundListener>,
+    stop_receiver: watch::Receiver<bool>,
     end_notifier: Sender<()>,
 ) {
     tokio::spawn(async move {
-        let cert_file_metadata =
-            match tokio::fs::metadata(&server_context.config.tls.certificate).await {
-                Ok(m) => m,
-                Err(e) => {
-                    log_error!(logger, format!("Could not load certificate: {}", e));
-                    end_notifier
-                        .send(())
-                        .await
-                        .expect("failed to notify to main thread");
-                    return;
-                }
-            };
+        // Obtain the certificate through ACME, if needed
 
-        let cert_file_mod_time =
-            FileTime::from_last_modification_time(&cert_file_metadata).unix_seconds();
+        let acme_config = &server_context.config.tls.acme;
 
-        let certs_res = CertificateDer::pem_file_iter(&server_context.config.tls.certificate);
-        let mut certificate: Vec<CertificateDer<'_>> = Vec::new();
+        if acme_config.is_enabled() && acme_certificate_needs_renewal(acme_config).await {
+            let acme_logger = Arc::new(logger.make_child_logger("[ACME] "));
 
-        match certs_res {
-            Ok(certs_iter) => {
-                for c in certs_iter.flatten() {
-                    certificate.push(c);
-                }
-            }
-            Err(e) => {
-                log_error!(logger, format!("Could not load certificate: {}", e));
-                end_notifier
-                    .send(())
-                    .await
-                    .expect("failed to notify to main thread");
-                return;
+            if let Err(e) = obtain_acme_certificate(acme_logger, acme_config).await {
+                // If a previous certificate exists, it is still used
+                log_error!(
+                    logger,
+                    format!("Could not obtain certificate through ACME: {}", e)
+                );
             }
         }
 
-        let key_file_metadata = match tokio::fs::metadata(&server_context.config.tls.key).await {
-            Ok(m) => m,
-            Err(e) => {
-                log_error!(logger, format!("Could not load private key: {}", e));
-                end_notifier
-                    .send(())
-                    .await
-                    .expect("failed to notify to main thread");
-                return;
-            }
-        };
-
-        let key_file_mod_time =
-            FileTime::from_last_modification_time(&key_file_metadata).unix_seconds();
-
-        let key = match PrivateKeyDer::from_pem_file(&server_context.config.tls.key) {
-            Ok(k) => k,
-            Err(e) => {
-                log_error!(logger, format!("Could not load private key: {}", e));
-                end_notifier
-                    .send(())
-                    .await
-                    .expect("failed to notify to main thread");
-                return;
-            }
-        };
+        // Load certificates
 
-        let listen_addr = server_context.config.tls.get_tcp_listen_addr();
+        let mut certified_keys: Vec<Arc<CertifiedKey>> = Vec::new();
+        let mut mod_times: Vec<(i64, i64)> = Vec::new();
 
-        let tls_config_builder = rustls::ServerConfig::builder();
+        for cert_config in &server_context.config.tls.certificates {
+            let loaded = match load_certificate(&logger, cert_config).await {
+                Ok(l) => l,
+                Err(()) => {
+                    end_notifier
+                        .send(())
+                        .await
+                        .expect("failed to notify to main thread");
+                    return;
+                }
+            };
 
-        let key_provider = tls_config_builder.crypto_provider().key_provider;
+            certified_keys.push(Arc::new(loaded.certified_key));
+            mod_times.push((loaded.cert_mod_time, loaded.key_mod_time));
+        }
 
-        let signing_key = match key_provider.load_private_key(key) {
-            Ok(k) => k,
-            Err(e) => {
-                log_error!(logger, format!("Could not load private key: {}", e));
-                end_notifier
-                    .send(())
-                    .await
-                    .expect("failed to notify to main thread");
-                return;
-            }
-        };
+        let client_cert_verifier =
+            match load_client_cert_verifier(&logger, &server_context.config.tls) {
+                Ok(v) => v,
+                Err(()) => {
+                    end_notifier
+                        .send(())
+                        .await
+                        .expect("failed to notify to main thread");
+                    return;
+                }
+            };
 
-        let cert_resolver = Arc::new(CustomCertResolver::new(certificate, signing_key));
+        let cert_resolver = Arc::new(CustomCertResolver::new(
+            &server_context.config.tls.certificates,
+            certified_keys,
+        ));
 
-        let tls_config = tls_config_builder
-            .with_no_client_auth()
+        let tls_config = rustls::ServerConfig::builder()
+            .with_client_cert_verifier(client_cert_verifier)
             .with_cert_resolver(cert_resolver.clone());
 
         let acceptor = TlsAcceptor::from(Arc::new(tls_config));
 
-        // Create listener
-        let listener = match TcpListener::bind(&listen_addr).await {
-            Ok(l) => l,
-            Err(e) => {
-                log_error!(logger, format!("Could not create TCP listener: {}", e));
-                end_notifier
-                    .send(())
-                    .await
-                    .expect("failed to notify to main thread");
-                return;
-            }
-        };
-
-        log_info!(logger, format!("Listening on {}", listen_addr));
-
         // Spawn task to reload certificates periodically
 
         let cancel_tls_reloader_sender = if server_context.config.tls.check_reload_seconds > 0 {
@@ -140,10 +120,9 @@ pub fn tls_server(
             spawn_task_periodically_reload_tls_config(
                 logger.clone(),
                 server_context.config.clone(),
-                cert_resolver,
+                cert_resolver.clone(),
                 cancel_receiver,
-                cert_file_mod_time,
-                key_file_mod_time,
+                mod_times,
             );
 
             Some(cancel_sender)
@@ -151,37 +130,214 @@ pub fn tls_server(
             None
         };
 
-        // Main loop
+        // Spawn task to renew the ACME certificate
 
-        loop {
-            let accept_res = listener.accept().await;
-
-            match accept_res {
-                Ok((connection, addr)) => {
-                    // Handle connection
-                    handle_connection_tls(
-                        logger.clone(),
-                        server_context.clone(),
-                        acceptor.clone(),
-                        connection,
-                        addr.ip(),
+        let cancel_acme_renewal_sender = if acme_config.is_enabled() {
+            let (cancel_sender, cancel_receiver) = tokio::sync::mpsc::channel::<()>(1);
+
+            spawn_task_renew_acme_certificate(
+                Arc::new(logger.make_child_logger("[ACME] ")),
+                server_context.config.clone(),
+                cert_resolver.clone(),
+                cancel_receiver,
+            );
+
+            Some(cancel_sender)
+        } else {
+            None
+        };
+
+        // One accept loop per listener
+
+        let mut accept_loops = Vec::new();
+
+        for listener in listeners {
+            log_info!(logger, format!("Listening on {}", listener.addr));
+
+            accept_loops.push(tokio::spawn(run_tls_accept_loop(
+                logger.clone(),
+                server_context.clone(),
+                acceptor.clone(),
+                listener,
+                stop_receiver.clone(),
+            )));
+        }
+
+        for accept_loop in accept_loops {
+            _ = accept_loop.await;
+        }
+
+        if let Some(cancel_sender) = cancel_tls_reloader_sender {
+            _ = cancel_sender.send(()).await;
+        }
+
+        if let Some(cancel_sender) = cancel_acme_renewal_sender {
+            _ = cancel_sender.send(()).await;
+        }
+
+        end_notifier
+            .send(())
+            .await
+            .expect("failed to notify to main thread");
+    });
+}
+
+/// Checks the certificates and the client CA of the TLS configuration
+/// can be loaded, without starting the server
+///
+/// # Arguments
+///
+/// * `logger` - The logger, to log the errors
+/// * `config` - The TLS configuration
+pub async fn check_tls_certificates(
+    logger: &Logger,
+    config: &TlsServerConfiguration,
+) -> Result<(), ()> {
+    let mut result = Ok(());
+
+    for cert_config in &config.certificates {
+        if config.acme.is_enabled() && cert_config.certificate == config.acme.get_certificate_path()
+        {
+            continue; // Obtained through ACME when the server starts
+        }
+
+        if load_certificate(logger, cert_config).await.is_err() {
+            result = Err(());
+        }
+    }
+
+    if load_client_cert_verifier(logger, config).is_err() {
+        result = Err(());
+    }
+
+    result
+}
+
+/// Loads the verifier for client certificates
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `config` - The TLS configuration
+///
+/// # Return value
+///
+/// Returns the verifier. If client certificate authentication
+/// is disabled, the verifier does not request any certificate.
+fn load_client_cert_verifier(
+    logger: &Logger,
+    config: &TlsServerConfiguration,
+) -> Result<Arc<dyn ClientCertVerifier>, ()> {
+    if !config.is_client_auth_enabled() {
+        return Ok(WebPkiClientVerifier::no_client_auth());
+    }
+
+    let mut roots = RootCertStore::empty();
+
+    match CertificateDer::pem_file_iter(&config.client_ca) {
+        Ok(certs_iter) => {
+            for c in certs_iter.flatten() {
+                if let Err(e) = roots.add(c) {
+                    log_error!(
+                        logger,
+                        format!("Could not load client CA certificate: {}", e)
                     );
-                }
-                Err(e) => {
-                    log_error!(logger, format!("Could not accept connection: {}", e));
-                    end_notifier
-                        .send(())
-                        .await
-                        .expect("failed to notify to main thread");
-                    break;
+                    return Err(());
                 }
             }
         }
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not load client CA certificate: {}", e)
+            );
+            return Err(());
+        }
+    }
 
-        if let Some(cancel_sender) = cancel_tls_reloader_sender {
-            _ = cancel_sender.send(());
+    if roots.is_empty() {
+        log_error!(
+            logger,
+            "Could not load client CA certificate: No certificates found"
+        );
+        return Err(());
+    }
+
+    let mut verifier_builder = WebPkiClientVerifier::builder(Arc::new(roots));
+
+    if !config.client_cert_required {
+        verifier_builder = verifier_builder.allow_unauthenticated();
+    }
+
+    match verifier_builder.build() {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not create client certificate verifier: {}", e)
+            );
+            Err(())
         }
-    });
+    }
+}
+
+/// Accepts TLS connections until the listener fails with a fatal error or the server stops
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `server_context` - The server context
+/// * `acceptor` - The TLS acceptor
+/// * `listener` - The TCP listener
+/// * `stop_receiver` - Receiver to stop accepting connections
+async fn run_tls_accept_loop(
+    logger: Arc<Logger>,
+    server_context: RtmpServerContextExtended,
+    acceptor: TlsAcceptor,
+    listener: BoundListener,
+    mut stop_receiver: watch::Receiver<bool>,
+) {
+    let mut backoff = AcceptBackoff::new();
+
+    loop {
+        let accept_res = tokio::select! {
+            r = listener.listener.accept() => r,
+            _ = wait_for_stop(&mut stop_receiver) => {
+                log_info!(logger, format!("Stopped listening on {}", listener.addr));
+                return;
+            }
+        };
+
+        match accept_res {
+            Ok((connection, addr)) => {
+                backoff.reset();
+
+                // Reject the connection if close to the open files limit
+                let open_socket = match server_context.open_sockets.try_open(&logger) {
+                    Some(g) => g,
+                    None => {
+                        drop(connection);
+                        continue;
+                    }
+                };
+
+                // Handle connection
+                handle_connection_tls(
+                    logger.clone(),
+                    server_context.clone(),
+                    acceptor.clone(),
+                    connection,
+                    normalize_ip_address(addr.ip()),
+                    open_socket,
+                );
+            }
+            Err(e) => {
+                if !backoff.handle_error(&logger, &e).await {
+                    return;
+                }
+            }
+        }
+    }
 }
 
 /// Handles a TLS connection
@@ -191,34 +347,65 @@ fn handle_connection_tls(
     tls_acceptor: TlsAcceptor,
     mut connection: TcpStream,
     ip: IpAddr,
+    open_socket: OpenSocketGuard,
 ) {
     tokio::spawn(async move {
+        let _open_socket = open_socket;
+
         let is_exempted = server_context
             .config
             .as_ref()
             .max_concurrent_connections_whitelist
             .contains_ip(&ip);
         let mut should_accept = true;
+        let mut ip_connection: Option<IpConnectionGuard> = None;
+
+        if !is_exempted && !server_context.ip_rate_limiter.check_ip(&logger, &ip).await {
+            let _ = connection.shutdown().await;
+            return;
+        }
 
         if !is_exempted {
-            let mut ip_counter_v = server_context.ip_counter.as_ref().lock().await;
-            should_accept = (*ip_counter_v).add(&ip);
-            drop(ip_counter_v);
+            ip_connection = IpConnectionGuard::try_add(&server_context.ip_counter, &ip).await;
+            should_accept = ip_connection.is_some();
         }
 
         if should_accept {
+            apply_tcp_socket_options(&logger, &connection, &server_context.config.tcp_socket);
+
             let stream = match tls_acceptor.accept(connection).await {
                 Ok(s) => s,
                 Err(e) => {
                     log_debug!(logger, format!("Could not accept connection: {}", e));
+
+                    // Handshake failed, the guard removes it from ip counter
                     return;
                 }
             };
 
+            // Identity of the client certificate (if provided)
+            let client_cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| parse_certificate_identity(cert.as_ref()));
+
             // Handle connection
             let (mut read_stream, write_stream) = tokio::io::split(stream);
 
-            let write_stream_mu = Arc::new(Mutex::new(write_stream));
+            let bandwidth = Arc::new(SessionBandwidth::new());
+            let trace = Arc::new(SessionTraceTap::new());
+            let write_stream_mu = Arc::new(Mutex::new(TraceCapturingWriter::new(
+                server_context.chaos.wrap(BandwidthCountingWriter::new(
+                    WriteTimeoutWriter::new(
+                        write_stream,
+                        server_context.config.write_timeout_seconds,
+                    ),
+                    bandwidth.clone(),
+                )),
+                trace.clone(),
+            )));
 
             handle_connection(
                 logger.clone(),
@@ -226,6 +413,9 @@ fn handle_connection_tls(
                 &mut read_stream,
                 write_stream_mu.clone(),
                 ip,
+                bandwidth,
+                trace,
+                client_cert,
             )
             .await;
 
@@ -236,11 +426,7 @@ fn handle_connection_tls(
             drop(write_stream_mu_v);
 
             // After connection is closed, remove from ip counter
-            if !is_exempted {
-                let mut ip_counter_v = server_context.ip_counter.as_ref().lock().await;
-                (*ip_counter_v).remove(&ip);
-                drop(ip_counter_v);
-            }
+            drop(ip_connection);
         } else {
             log_info!(
                 logger,
@@ -251,35 +437,202 @@ fn handle_connection_tls(
     });
 }
 
+/// Certificate loaded from the files
+struct LoadedCertificate {
+    /// Key + certs
+    certified_key: CertifiedKey,
+
+    /// Modification time of the certificate file
+    cert_mod_time: i64,
+
+    /// Modification time of the key file
+    key_mod_time: i64,
+}
+
+/// Gets the modification times of the certificate and key files
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `config` - The certificate configuration
+///
+/// # Return value
+///
+/// Returns a tuple with the modification times of the certificate and the key
+async fn get_certificate_mod_times(
+    logger: &Logger,
+    config: &TlsCertificateConfiguration,
+) -> Result<(i64, i64), ()> {
+    let cert_file_metadata = match tokio::fs::metadata(&config.certificate).await {
+        Ok(m) => m,
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not load certificate {}: {}", &config.certificate, e)
+            );
+            return Err(());
+        }
+    };
+
+    let key_file_metadata = match tokio::fs::metadata(&config.key).await {
+        Ok(m) => m,
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not load private key {}: {}", &config.key, e)
+            );
+            return Err(());
+        }
+    };
+
+    Ok((
+        FileTime::from_last_modification_time(&cert_file_metadata).unix_seconds(),
+        FileTime::from_last_modification_time(&key_file_metadata).unix_seconds(),
+    ))
+}
+
+/// Loads a certificate and its private key
+///
+/// # Arguments
+///
+/// * `logger` - The server logger
+/// * `config` - The certificate configuration
+async fn load_certificate(
+    logger: &Logger,
+    config: &TlsCertificateConfiguration,
+) -> Result<LoadedCertificate, ()> {
+    let (cert_mod_time, key_mod_time) = get_certificate_mod_times(logger, config).await?;
+
+    let mut certificate: Vec<CertificateDer<'static>> = Vec::new();
+
+    match CertificateDer::pem_file_iter(&config.certificate) {
+        Ok(certs_iter) => {
+            for c in certs_iter.flatten() {
+                certificate.push(c);
+            }
+        }
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not load certificate {}: {}", &config.certificate, e)
+            );
+            return Err(());
+        }
+    }
+
+    let key = match PrivateKeyDer::from_pem_file(&config.key) {
+        Ok(k) => k,
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not load private key {}: {}", &config.key, e)
+            );
+            return Err(());
+        }
+    };
+
+    let key_provider = rustls::ServerConfig::builder()
+        .crypto_provider()
+        .key_provider;
+
+    let signing_key = match key_provider.load_private_key(key) {
+        Ok(k) => k,
+        Err(e) => {
+            log_error!(
+                logger,
+                format!("Could not load private key {}: {}", &config.key, e)
+            );
+            return Err(());
+        }
+    };
+
+    Ok(LoadedCertificate {
+        certified_key: CertifiedKey::new(certificate, signing_key),
+        cert_mod_time,
+        key_mod_time,
+    })
+}
+
 /// Custom certificate resolver
+/// Selects the certificate by the server name (SNI) requested by the client
 #[derive(Debug)]
 struct CustomCertResolver {
-    /// Key + certs
-    pub certified_key: std::sync::Mutex<Arc<CertifiedKey>>,
+    /// Server names of the certificates (lower case, empty for the default one)
+    server_names: Vec<String>,
+
+    /// Key + certs, in the same order as the server names
+    pub certified_keys: std::sync::Mutex<Vec<Arc<CertifiedKey>>>,
 }
 
 impl CustomCertResolver {
     /// Creates new CustomCertResolver
-    pub fn new(cert: Vec<CertificateDer<'static>>, key: Arc<dyn SigningKey>) -> CustomCertResolver {
+    ///
+    /// # Arguments
+    ///
+    /// * `configs` - The certificate configurations
+    /// * `certified_keys` - The loaded certificates, one per configuration
+    pub fn new(
+        configs: &[TlsCertificateConfiguration],
+        certified_keys: Vec<Arc<CertifiedKey>>,
+    ) -> CustomCertResolver {
         CustomCertResolver {
-            certified_key: std::sync::Mutex::new(Arc::new(CertifiedKey::new(cert, key))),
+            server_names: configs
+                .iter()
+                .map(|c| c.server_name.to_lowercase())
+                .collect(),
+            certified_keys: std::sync::Mutex::new(certified_keys),
         }
     }
 
     /// Sets TLS configuration
-    pub fn set_config(&self, cert: Vec<CertificateDer<'static>>, key: Arc<dyn SigningKey>) {
-        let mut certified_key_v = self.certified_key.lock().unwrap();
-        *certified_key_v = Arc::new(CertifiedKey::new(cert, key));
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the certificate in the configuration
+    /// * `certified_key` - The new certificate
+    pub fn set_config(&self, index: usize, certified_key: CertifiedKey) {
+        let mut certified_keys_v = self.certified_keys.lock().unwrap();
+
+        if let Some(k) = certified_keys_v.get_mut(index) {
+            *k = Arc::new(certified_key);
+        }
+    }
+
+    /// Finds the index of the certificate to use for a server name
+    /// Exact matches are preferred over wildcards (*.example.com).
+    /// If no certificate matches, the default one is used.
+    fn find_certificate_index(&self, server_name: Option<&str>) -> usize {
+        if let Some(name) = server_name {
+            let name = name.to_lowercase();
+
+            if let Some(i) = self.server_names.iter().position(|n| *n == name) {
+                return i;
+            }
+
+            if let Some((_, parent)) = name.split_once('.') {
+                let wildcard = format!("*.{}", parent);
+
+                if let Some(i) = self.server_names.iter().position(|n| *n == wildcard) {
+                    return i;
+                }
+            }
+        }
+
+        self.server_names
+            .iter()
+            .position(|n| n.is_empty())
+            .unwrap_or(0)
     }
 }
 
 impl ResolvesServerCert for CustomCertResolver {
     fn resolve(
         &self,
-        _client_hello: rustls::server::ClientHello<'_>,
+        client_hello: rustls::server::ClientHello<'_>,
     ) -> Option<Arc<rustls::sign::CertifiedKey>> {
-        let certified_key_v = self.certified_key.lock().unwrap();
-        Some(certified_key_v.clone())
+        let index = self.find_certificate_index(client_hello.server_name());
+        let certified_keys_v = self.certified_keys.lock().unwrap();
+        certified_keys_v.get(index).cloned()
     }
 }
 
@@ -288,12 +641,10 @@ fn spawn_task_periodically_reload_tls_config(
     config: Arc<RtmpServerConfiguration>,
     cert_resolver: Arc<CustomCertResolver>,
     mut cancel_receiver: Receiver<()>,
-    initial_cert_time: i64,
-    initial_key_time: i64,
+    initial_mod_times: Vec<(i64, i64)>,
 ) {
     tokio::spawn(async move {
-        let mut cert_time = initial_cert_time;
-        let mut key_time = initial_key_time;
+        let mut mod_times = initial_mod_times;
 
         let mut finished = false;
 
@@ -309,82 +660,97 @@ fn spawn_task_periodically_reload_tls_config(
 
             log_debug!(logger, "Checking for changes in TLS configuration...");
 
-            // Check
+            for (i, cert_config) in config.tls.certificates.iter().enumerate() {
+                // Check
 
-            let cert_file_metadata = match tokio::fs::metadata(&config.tls.certificate).await {
-                Ok(m) => m,
-                Err(e) => {
-                    log_error!(logger, format!("Could not load certificate: {}", e));
+                let current_mod_times = match get_certificate_mod_times(&logger, cert_config).await
+                {
+                    Ok(t) => t,
+                    Err(()) => {
+                        continue;
+                    }
+                };
+
+                if current_mod_times == mod_times[i] {
                     continue;
                 }
-            };
 
-            let cert_file_mod_time =
-                FileTime::from_last_modification_time(&cert_file_metadata).unix_seconds();
+                // Changes detected, reload certificate
 
-            let key_file_metadata = match tokio::fs::metadata(&config.tls.key).await {
-                Ok(m) => m,
-                Err(e) => {
-                    log_error!(logger, format!("Could not load private key: {}", e));
-                    continue;
-                }
-            };
+                let loaded = match load_certificate(&logger, cert_config).await {
+                    Ok(l) => l,
+                    Err(()) => {
+                        continue;
+                    }
+                };
 
-            let key_file_mod_time =
-                FileTime::from_last_modification_time(&key_file_metadata).unix_seconds();
+                // Update mod times
+                mod_times[i] = (loaded.cert_mod_time, loaded.key_mod_time);
 
-            if cert_file_mod_time == cert_time && key_file_mod_time == key_time {
-                log_debug!(logger, "No changes detected in TLS configuration");
+                // Update config
+                cert_resolver.set_config(i, loaded.certified_key);
 
-                continue;
+                // Log
+                log_info!(
+                    logger,
+                    format!("TLS certificate reloaded: {}", &cert_config.certificate)
+                );
             }
+        }
+    });
+}
 
-            // Changes detected, reload configuration
-
-            let certs_res = CertificateDer::pem_file_iter(&config.tls.certificate);
-            let mut certificate: Vec<CertificateDer<'_>> = Vec::new();
+/// Spawns a task to periodically renew the certificate obtained through ACME.
+/// Renewed certificates are applied to the certificate resolver.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The server configuration
+/// * `cert_resolver` - The certificate resolver
+/// * `cancel_receiver` - Receiver to stop the task
+fn spawn_task_renew_acme_certificate(
+    logger: Arc<Logger>,
+    config: Arc<RtmpServerConfiguration>,
+    cert_resolver: Arc<CustomCertResolver>,
+    mut cancel_receiver: Receiver<()>,
+) {
+    tokio::spawn(async move {
+        let acme_config = &config.tls.acme;
 
-            match certs_res {
-                Ok(certs_iter) => {
-                    for c in certs_iter.flatten() {
-                        certificate.push(c);
-                    }
-                }
-                Err(e) => {
-                    log_error!(logger, format!("Could not load certificate: {}", e));
-                    continue;
+        loop {
+            // Wait
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(ACME_RENEWAL_CHECK_SECONDS)) => {}
+                _ = cancel_receiver.recv() => {
+                    return;
                 }
             }
 
-            let key = match PrivateKeyDer::from_pem_file(&config.tls.key) {
-                Ok(k) => k,
-                Err(e) => {
-                    log_error!(logger, format!("Could not load private key: {}", e));
-                    continue;
-                }
-            };
+            if !acme_certificate_needs_renewal(acme_config).await {
+                continue;
+            }
+
+            if let Err(e) = obtain_acme_certificate(logger.clone(), acme_config).await {
+                log_error!(logger, format!("Could not renew certificate: {}", e));
+                continue;
+            }
 
-            let tls_config_builder = rustls::ServerConfig::builder();
+            // Apply the renewed certificate
 
-            let key_provider = tls_config_builder.crypto_provider().key_provider;
+            let certificate_path = acme_config.get_certificate_path();
 
-            let signing_key = match key_provider.load_private_key(key) {
-                Ok(k) => k,
-                Err(e) => {
-                    log_error!(logger, format!("Could not load private key: {}", e));
+            for (i, cert_config) in config.tls.certificates.iter().enumerate() {
+                if cert_config.certificate != certificate_path {
                     continue;
                 }
-            };
 
-            // Update mod times
-            cert_time = cert_file_mod_time;
-            key_time = key_file_mod_time;
-
-            // Update config
-            cert_resolver.set_config(certificate, signing_key);
+                if let Ok(loaded) = load_certificate(&logger, cert_config).await {
+                    cert_resolver.set_config(i, loaded.certified_key);
+                }
+            }
 
-            // Log
-            log_info!(logger, "TLS configuration reloaded");
+            log_info!(logger, "Certificate renewed");
         }
     });
 }
@@ -0,0 +1,232 @@
+// Zero-downtime binary upgrade
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+use crate::{log::Logger, log_error, log_info, log_warning};
+
+use super::ServerListeners;
+
+/// Time to wait for the new process to start before handing off the listeners (milliseconds)
+#[cfg(unix)]
+const UPGRADE_STARTUP_WAIT_MS: u64 = 2000;
+
+/// Counter of the active connections, in order to drain them
+pub struct ActiveConnections {
+    /// Number of active connections
+    count: AtomicUsize,
+
+    /// Notified when the last connection ends
+    drained: Notify,
+}
+
+impl ActiveConnections {
+    /// Creates new ActiveConnections
+    pub fn new() -> ActiveConnections {
+        ActiveConnections {
+            count: AtomicUsize::new(0),
+            drained: Notify::new(),
+        }
+    }
+
+    /// Registers a new connection
+    pub fn add(&self) {
+        self.count.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Unregisters a connection
+    pub fn remove(&self) {
+        if self.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.drained.notify_waiters();
+        }
+    }
+
+    /// Gets the number of active connections
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Waits for all the active connections to end
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout_seconds` - Max time to wait (seconds). 0 to wait indefinitely
+    ///
+    /// # Return value
+    ///
+    /// Returns true if all the connections ended, false if the timeout was reached
+    pub async fn wait_drained(&self, timeout_seconds: u32) -> bool {
+        let wait = async {
+            loop {
+                let drained = self.drained.notified();
+
+                if self.count() == 0 {
+                    return;
+                }
+
+                drained.await;
+            }
+        };
+
+        if timeout_seconds == 0 {
+            wait.await;
+            return true;
+        }
+
+        tokio::time::timeout(Duration::from_secs(timeout_seconds as u64), wait)
+            .await
+            .is_ok()
+    }
+}
+
+/// Listeners to pass to the new process on a binary upgrade
+pub struct UpgradeHandoff {
+    /// Duplicated file descriptors of the listeners
+    #[cfg(unix)]
+    fds: Vec<std::os::fd::OwnedFd>,
+}
+
+impl UpgradeHandoff {
+    /// Creates the handoff, duplicating the file descriptors of the listeners,
+    /// so they can be passed after the listeners are moved to the accept loops
+    ///
+    /// # Arguments
+    ///
+    /// * `listeners` - The listeners of the server
+    #[cfg(unix)]
+    pub fn new(listeners: &ServerListeners) -> std::io::Result<UpgradeHandoff> {
+        use std::os::fd::AsFd;
+
+        let fds = listeners
+            .tcp
+            .iter()
+            .chain(listeners.tls.iter())
+            .map(|l| l.listener.as_fd().try_clone_to_owned())
+            .collect::<std::io::Result<Vec<std::os::fd::OwnedFd>>>()?;
+
+        Ok(UpgradeHandoff { fds })
+    }
+
+    /// Creates the handoff (not supported on this platform)
+    #[cfg(not(unix))]
+    pub fn new(_listeners: &ServerListeners) -> std::io::Result<UpgradeHandoff> {
+        Ok(UpgradeHandoff {})
+    }
+}
+
+/// Waits for the signal to upgrade the binary (SIGUSR2),
+/// and starts the new process, passing the listeners to it.
+/// If the new process fails to start, it keeps waiting for the signal.
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `handoff` - The listeners to pass to the new process
+#[cfg(unix)]
+pub async fn wait_for_binary_upgrade(logger: &Logger, handoff: &UpgradeHandoff) {
+    let mut sigusr2 =
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined2()) {
+            Ok(s) => s,
+            Err(e) => {
+                log_warning!(
+                    logger,
+                    format!("Could not listen for the upgrade signal: {}", e)
+                );
+                std::future::pending::<()>().await;
+                return;
+            }
+        };
+
+    loop {
+        sigusr2.recv().await;
+
+        log_info!(logger, "Received upgrade signal. Starting new process...");
+
+        match spawn_upgraded_process(handoff).await {
+            Ok(pid) => {
+                log_info!(
+                    logger,
+                    format!(
+                        "New process started (PID: {}). Draining connections...",
+                        pid
+                    )
+                );
+                return;
+            }
+            Err(e) => {
+                log_error!(logger, format!("Could not upgrade the binary: {}", e));
+            }
+        }
+    }
+}
+
+/// Waits for the signal to upgrade the binary
+/// (not supported on this platform)
+#[cfg(not(unix))]
+pub async fn wait_for_binary_upgrade(_logger: &Logger, _handoff: &UpgradeHandoff) {
+    std::future::pending::<()>().await;
+}
+
+/// Starts a new process of the current binary, passing the listeners to it
+///
+/// # Arguments
+///
+/// * `handoff` - The listeners to pass to the new process
+///
+/// # Return value
+///
+/// Returns the PID of the new process, once it is running
+#[cfg(unix)]
+async fn spawn_upgraded_process(handoff: &UpgradeHandoff) -> Result<u32, String> {
+    use std::os::fd::AsRawFd;
+
+    // The file descriptors must be inherited by the new process
+    set_handoff_cloexec(handoff, false)?;
+
+    let fds_env = handoff
+        .fds
+        .iter()
+        .map(|fd| fd.as_raw_fd().to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+
+    let spawn_res = std::env::current_exe().and_then(|exe| {
+        tokio::process::Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .env(super::UPGRADE_LISTEN_FDS_ENV, fds_env)
+            .env_remove("LISTEN_PID")
+            .env_remove("LISTEN_FDS")
+            .env_remove("LISTEN_FDNAMES")
+            .spawn()
+    });
+
+    set_handoff_cloexec(handoff, true)?;
+
+    let mut child = spawn_res.map_err(|e| e.to_string())?;
+
+    let pid = child.id().unwrap_or(0);
+
+    // If the new process exits on startup (eg: invalid configuration), keep serving
+
+    match tokio::time::timeout(Duration::from_millis(UPGRADE_STARTUP_WAIT_MS), child.wait()).await {
+        Ok(Ok(status)) => Err(format!("New process exited on startup: {}", status)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Ok(pid),
+    }
+}
+
+/// Sets or clears FD_CLOEXEC on the file descriptors of the handoff
+#[cfg(unix)]
+fn set_handoff_cloexec(handoff: &UpgradeHandoff, close_on_exec: bool) -> Result<(), String> {
+    for fd in &handoff.fds {
+        socket2::SockRef::from(fd)
+            .set_cloexec(close_on_exec)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
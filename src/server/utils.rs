@@ -17,7 +17,7 @@ pub async fn check_channel_publishing_status(
     server_context: &RtmpServerContext,
     channel: &str,
 ) -> bool {
-    let status = server_context.status.lock().await;
+    let status = server_context.status.lock_shard(channel).await;
 
     match status.channels.get(channel) {
         Some(c) => {
@@ -31,3 +31,37 @@ pub async fn check_channel_publishing_status(
         None => false,
     }
 }
+
+/// Checks if a channel is being published with a key
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel ID
+/// * `key` - The stream key
+///
+/// # Return value
+///
+/// Returns true if the channel is being published with the same key
+pub async fn check_channel_publisher_key(
+    server_context: &RtmpServerContext,
+    channel: &str,
+    key: &str,
+) -> bool {
+    let status = server_context.status.lock_shard(channel).await;
+
+    match status.channels.get(channel) {
+        Some(c) => {
+            let channel_mu = c.clone();
+            drop(status);
+
+            let channel_status = channel_mu.lock().await;
+
+            match (&channel_status.key, channel_status.publishing) {
+                (Some(k), true) => k.matches(key),
+                _ => false,
+            }
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,209 @@
+// Outbound acknowledgement window
+
+use std::{
+    io::{Error, ErrorKind},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+use super::SessionBandwidth;
+
+/// Acknowledgements sent by the client for the bytes it received
+pub struct SessionOutboundAck {
+    /// Sequence number of the last acknowledgement (bytes received by the client)
+    last_ack: AtomicU32,
+
+    /// True if the client sent any acknowledgement
+    ack_received: AtomicBool,
+
+    /// Notified when an acknowledgement is received
+    notify: Notify,
+}
+
+impl SessionOutboundAck {
+    /// Creates new SessionOutboundAck
+    pub fn new() -> SessionOutboundAck {
+        SessionOutboundAck {
+            last_ack: AtomicU32::new(0),
+            ack_received: AtomicBool::new(false),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Registers an acknowledgement sent by the client
+    ///
+    /// # Arguments
+    ///
+    /// * `sequence_number` - Number of bytes received by the client
+    pub fn set_ack(&self, sequence_number: u32) {
+        self.last_ack.store(sequence_number, Ordering::Relaxed);
+        self.ack_received.store(true, Ordering::Release);
+        self.notify.notify_waiters();
+    }
+
+    /// Gets the number of bytes sent to the client and not acknowledged yet
+    ///
+    /// # Arguments
+    ///
+    /// * `sent_bytes` - Total number of bytes sent to the client
+    ///
+    /// # Return value
+    ///
+    /// Returns None if the client never sent an acknowledgement,
+    /// since some clients do not implement them
+    pub fn unacknowledged_bytes(&self, sent_bytes: u64) -> Option<u64> {
+        if !self.ack_received.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // The sequence number wraps around at 2^32
+        Some((sent_bytes as u32).wrapping_sub(self.last_ack.load(Ordering::Relaxed)) as u64)
+    }
+}
+
+/// Limit of the bytes sent to the client without acknowledgement
+pub struct OutboundAckLimit {
+    /// Acknowledgements of the client
+    ack: Arc<SessionOutboundAck>,
+
+    /// Bandwidth usage of the session, to count the sent bytes
+    bandwidth: Arc<SessionBandwidth>,
+
+    /// Max number of unacknowledged bytes. 0 for unlimited
+    max_unacknowledged: u64,
+
+    /// Max time to wait for an acknowledgement
+    timeout: Duration,
+}
+
+impl OutboundAckLimit {
+    /// Creates new OutboundAckLimit
+    ///
+    /// # Arguments
+    ///
+    /// * `ack` - Acknowledgements of the client
+    /// * `bandwidth` - Bandwidth usage of the session
+    /// * `max_unacknowledged` - Max number of unacknowledged bytes. 0 for unlimited
+    /// * `timeout_seconds` - Max time to wait for an acknowledgement (seconds)
+    pub fn new(
+        ack: Arc<SessionOutboundAck>,
+        bandwidth: Arc<SessionBandwidth>,
+        max_unacknowledged: u64,
+        timeout_seconds: u32,
+    ) -> OutboundAckLimit {
+        OutboundAckLimit {
+            ack,
+            bandwidth,
+            max_unacknowledged,
+            timeout: Duration::from_secs(timeout_seconds as u64),
+        }
+    }
+
+    /// Checks if the number of sent bytes is within the limit
+    fn is_within_limit(&self) -> bool {
+        if self.max_unacknowledged == 0 {
+            return true;
+        }
+
+        match self
+            .ack
+            .unacknowledged_bytes(self.bandwidth.output.total_bytes())
+        {
+            Some(unacknowledged) => unacknowledged <= self.max_unacknowledged,
+            None => true,
+        }
+    }
+
+    /// Pauses sending until the client acknowledges enough bytes
+    ///
+    /// # Return value
+    ///
+    /// Returns an error if the client did not acknowledge the bytes before the timeout
+    pub async fn wait(&self) -> Result<(), Error> {
+        if self.is_within_limit() {
+            return Ok(());
+        }
+
+        let wait = async {
+            loop {
+                let notified = self.ack.notify.notified();
+
+                if self.is_within_limit() {
+                    return;
+                }
+
+                notified.await;
+            }
+        };
+
+        tokio::time::timeout(self.timeout, wait).await.map_err(|_| {
+            Error::new(
+                ErrorKind::TimedOut,
+                "The client did not acknowledge the sent bytes in time",
+            )
+        })
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbound_ack_unacknowledged_bytes() {
+        let ack = SessionOutboundAck::new();
+
+        assert_eq!(ack.unacknowledged_bytes(1000), None);
+
+        ack.set_ack(400);
+
+        assert_eq!(ack.unacknowledged_bytes(1000), Some(600));
+
+        // Sequence number wrap around
+        ack.set_ack(u32::MAX - 99);
+
+        assert_eq!(ack.unacknowledged_bytes((u32::MAX as u64) + 101), Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_outbound_ack_limit() {
+        let ack = Arc::new(SessionOutboundAck::new());
+        let bandwidth = Arc::new(SessionBandwidth::new());
+
+        let limit = OutboundAckLimit::new(ack.clone(), bandwidth.clone(), 1000, 0);
+
+        // Clients not sending acknowledgements are not limited
+        bandwidth.output.add(5000);
+        assert!(limit.wait().await.is_ok());
+
+        ack.set_ack(4500);
+        assert!(limit.wait().await.is_ok());
+
+        bandwidth.output.add(1000);
+        assert_eq!(
+            limit.wait().await.map_err(|e| e.kind()),
+            Err(ErrorKind::TimedOut)
+        );
+
+        let limit = OutboundAckLimit::new(ack.clone(), bandwidth.clone(), 1000, 10);
+
+        let ack_task = {
+            let ack = ack.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                ack.set_ack(5500);
+            })
+        };
+
+        assert!(limit.wait().await.is_ok());
+
+        ack_task.await.unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+// Bandwidth accounting
+
+use std::{
+    io::IoSlice,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use chrono::Utc;
+use tokio::io::AsyncWrite;
+
+/// Size of the window to compute the bit rate (milliseconds)
+pub const BANDWIDTH_RATE_WINDOW_MS: i64 = 1000;
+
+/// Counter of transferred bytes
+pub struct BandwidthCounter {
+    /// Total number of bytes
+    total_bytes: AtomicU64,
+
+    /// Start of the current window (Unix milliseconds)
+    window_start: AtomicI64,
+
+    /// Bytes transferred in the current window
+    window_bytes: AtomicU64,
+
+    /// Bit rate computed in the last completed window (bits per second)
+    bit_rate: AtomicU64,
+
+    /// True if a window was completed since the bit rate was last taken
+    bit_rate_updated: AtomicBool,
+}
+
+impl BandwidthCounter {
+    /// Creates new BandwidthCounter
+    pub fn new() -> BandwidthCounter {
+        BandwidthCounter {
+            total_bytes: AtomicU64::new(0),
+            window_start: AtomicI64::new(Utc::now().timestamp_millis()),
+            window_bytes: AtomicU64::new(0),
+            bit_rate: AtomicU64::new(0),
+            bit_rate_updated: AtomicBool::new(false),
+        }
+    }
+
+    /// Adds transferred bytes to the counter
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Number of bytes transferred
+    ///
+    /// # Return value
+    ///
+    /// Returns the new bit rate if the rate window was completed
+    pub fn add(&self, bytes: usize) -> Option<u64> {
+        self.total_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.window_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+
+        let now = Utc::now().timestamp_millis();
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        let time_diff = now - window_start;
+
+        if time_diff < BANDWIDTH_RATE_WINDOW_MS {
+            return None;
+        }
+
+        if self
+            .window_start
+            .compare_exchange(window_start, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return None; // Window completed by other task
+        }
+
+        let window_bytes = self.window_bytes.swap(0, Ordering::Relaxed);
+        let bit_rate = window_bytes.saturating_mul(8000) / (time_diff as u64);
+
+        self.bit_rate.store(bit_rate, Ordering::Relaxed);
+        self.bit_rate_updated.store(true, Ordering::Relaxed);
+
+        Some(bit_rate)
+    }
+
+    /// Takes the bit rate if a rate window was completed since the last call.
+    /// Useful when the bytes are added by a different task (eg: the write stream wrapper)
+    ///
+    /// # Return value
+    ///
+    /// Returns the new bit rate, or None if not updated
+    pub fn take_bit_rate_update(&self) -> Option<u64> {
+        if self.bit_rate_updated.swap(false, Ordering::Relaxed) {
+            Some(self.bit_rate.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the total number of transferred bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Gets the bit rate (bits per second)
+    /// If nothing was transferred for longer than the window, returns 0
+    pub fn bit_rate(&self) -> u64 {
+        let now = Utc::now().timestamp_millis();
+        let window_start = self.window_start.load(Ordering::Relaxed);
+
+        if now - window_start >= 2 * BANDWIDTH_RATE_WINDOW_MS {
+            return 0;
+        }
+
+        self.bit_rate.load(Ordering::Relaxed)
+    }
+}
+
+/// Bandwidth usage of a session
+pub struct SessionBandwidth {
+    /// Bytes received from the client
+    pub input: BandwidthCounter,
+
+    /// Bytes sent to the client
+    pub output: BandwidthCounter,
+
+    /// Last measured round trip time (milliseconds), -1 if not measured yet
+    rtt: AtomicI64,
+}
+
+impl SessionBandwidth {
+    /// Creates new SessionBandwidth
+    pub fn new() -> SessionBandwidth {
+        SessionBandwidth {
+            input: BandwidthCounter::new(),
+            output: BandwidthCounter::new(),
+            rtt: AtomicI64::new(-1),
+        }
+    }
+
+    /// Sets the round trip time, measured with a ping
+    ///
+    /// # Arguments
+    ///
+    /// * `rtt` - The round trip time (milliseconds)
+    pub fn set_rtt(&self, rtt: u32) {
+        self.rtt.store(rtt as i64, Ordering::Relaxed);
+    }
+
+    /// Gets the last measured round trip time (milliseconds)
+    pub fn rtt(&self) -> Option<u32> {
+        let rtt = self.rtt.load(Ordering::Relaxed);
+
+        if rtt < 0 {
+            None
+        } else {
+            Some(rtt as u32)
+        }
+    }
+}
+
+/// Write stream wrapper counting the bytes sent to the client
+pub struct BandwidthCountingWriter<TW: AsyncWrite + Unpin> {
+    /// The inner write stream
+    inner: TW,
+
+    /// The session bandwidth
+    bandwidth: Arc<SessionBandwidth>,
+}
+
+impl<TW: AsyncWrite + Unpin> BandwidthCountingWriter<TW> {
+    /// Creates new BandwidthCountingWriter
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The write stream to wrap
+    /// * `bandwidth` - The session bandwidth to count the bytes
+    pub fn new(inner: TW, bandwidth: Arc<SessionBandwidth>) -> BandwidthCountingWriter<TW> {
+        BandwidthCountingWriter { inner, bandwidth }
+    }
+}
+
+impl<TW: AsyncWrite + Unpin> AsyncWrite for BandwidthCountingWriter<TW> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(n)) = &res {
+            this.bandwidth.output.add(*n);
+        }
+
+        res
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+
+        if let Poll::Ready(Ok(n)) = &res {
+            this.bandwidth.output.add(*n);
+        }
+
+        res
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
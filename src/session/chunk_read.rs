@@ -1,8 +1,8 @@
 // Chunk read logic
 
-use std::{cmp, time::Duration};
+use std::{fmt, io, time::Duration};
 
-use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use byteorder::{BigEndian, ByteOrder};
 use chrono::Utc;
 use tokio::{
     io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
@@ -11,21 +11,46 @@ use tokio::{
 
 use crate::{
     log::Logger,
-    log_debug, log_error,
+    log_debug, log_error, log_info,
     rtmp::{
-        get_rtmp_header_size, rtmp_make_ack, RTMP_CHUNK_TYPE_0, RTMP_CHUNK_TYPE_1,
-        RTMP_CHUNK_TYPE_2, RTMP_PING_TIMEOUT, RTMP_TYPE_METADATA,
+        rtmp_decode_chunk, rtmp_make_ack, RtmpChunkDecodeError, RtmpChunkDecodeStatus,
+        RtmpDecodedChunk, RtmpInputPacketBuffer, RTMP_PING_TIMEOUT, RTMP_TYPE_ABORT,
+        RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO,
     },
     server::RtmpServerContext,
 };
 
 use super::{
-    handle_rtmp_packet, session_write_bytes, RtmpPacketWrapper, SessionReadThreadContext,
-    IN_PACKETS_BUFFER_SIZE,
+    handle_rtmp_packet, session_write_bytes, PublishCongestion, RtmpSessionMessage,
+    SessionReadThreadContext,
 };
 
-/// Interval to compute bit rate (milliseconds)
-const BIT_RATE_COMPUTE_INTERVAL_MS: i64 = 1000;
+/// Error reading a RTMP chunk
+#[derive(Debug)]
+pub enum RtmpChunkReadError {
+    /// Could not read a field of the chunk from the stream
+    Read { field: String, error: io::Error },
+
+    /// Timed out reading a field of the chunk
+    TimedOut { field: String },
+
+    /// The chunk could not be decoded
+    Decode(RtmpChunkDecodeError),
+}
+
+impl fmt::Display for RtmpChunkReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtmpChunkReadError::Read { field, error } => {
+                write!(f, "Could not read {}: {}", field, error)
+            }
+            RtmpChunkReadError::TimedOut { field } => {
+                write!(f, "Could not read {}: Timed out", field)
+            }
+            RtmpChunkReadError::Decode(e) => e.fmt(f),
+        }
+    }
+}
 
 /// Reads a RTMP chunk
 /// Handles the packet when the last chunk of the packet is read
@@ -37,7 +62,7 @@ const BIT_RATE_COMPUTE_INTERVAL_MS: i64 = 1000;
 /// * `session_context` - The session context
 /// * `read_stream` - The stream to read from the client
 /// * `write_stream` - The stream to write to the client
-/// * `in_packets` - Array of input packets
+/// * `in_packets` - Buffer of input packets
 pub async fn read_rtmp_chunk<
     TR: AsyncRead + AsyncReadExt + Send + Sync + Unpin,
     TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
@@ -47,7 +72,7 @@ pub async fn read_rtmp_chunk<
     session_context: &mut SessionReadThreadContext,
     read_stream: &mut TR,
     write_stream: &Mutex<TW>,
-    in_packets: &mut [RtmpPacketWrapper; IN_PACKETS_BUFFER_SIZE],
+    in_packets: &mut RtmpInputPacketBuffer,
 ) -> bool {
     // Check if the session was killed before reading any chunk
 
@@ -57,326 +82,75 @@ pub async fn read_rtmp_chunk<
         return false;
     }
 
-    let mut bytes_read_count: usize = 0; // Counter of read bytes
-
-    // Read start byte
-
-    let start_byte = match tokio::time::timeout(
-        Duration::from_secs(RTMP_PING_TIMEOUT),
-        read_stream.read_u8(),
+    let chunk = match read_rtmp_chunk_to_buffer(
+        logger,
+        read_stream,
+        in_packets,
+        session_context.read_status.in_chunk_size,
     )
     .await
     {
-        Ok(br) => match br {
-            Ok(b) => b,
-            Err(e) => {
-                log_debug!(
-                    logger,
-                    format!("Chunk read error. Could not read start byte: {}", e)
-                );
-
-                return false;
+        Ok(c) => c,
+        Err(e) => {
+            match e {
+                RtmpChunkReadError::Decode(RtmpChunkDecodeError::InvalidHeader(_)) => {
+                    log_error!(logger, format!("Chunk read error: {}", e));
+                }
+                _ => {
+                    log_debug!(logger, format!("Chunk read error: {}", e));
+                }
             }
-        },
-        Err(_) => {
-            log_debug!(
-                logger,
-                "Chunk read error. Could not read start byte: Timed out"
-            );
 
             return false;
         }
     };
 
-    bytes_read_count += 1;
-
-    // Read header
-
-    let basic_bytes: usize = if start_byte & 0x3f == 0 {
-        2
-    } else if start_byte & 0x3f == 1 {
-        3
-    } else {
-        1
-    };
-
-    let header_res_bytes_size = get_rtmp_header_size(start_byte >> 6);
-
-    let mut header: Vec<u8> = vec![0; basic_bytes + header_res_bytes_size];
-
-    header[0] = start_byte;
-
-    if basic_bytes > 1 {
-        for (i, header_byte) in header.iter_mut().enumerate().take(basic_bytes).skip(1) {
-            let basic_byte = match tokio::time::timeout(
-                Duration::from_secs(RTMP_PING_TIMEOUT),
-                read_stream.read_u8(),
-            )
-            .await
-            {
-                Ok(br) => match br {
-                    Ok(b) => b,
-                    Err(e) => {
-                        log_debug!(
-                            logger,
-                            format!("Chunk read error. Could not read basic byte [{}]: {}", i, e,)
-                        );
-
-                        return false;
-                    }
-                },
-                Err(_) => {
-                    log_debug!(
-                        logger,
-                        format!(
-                            "Chunk read error. Could not read basic byte [{}]: Timed out",
-                            i
-                        )
-                    );
-
-                    return false;
-                }
-            };
+    let bytes_read_count = chunk.size;
 
-            *header_byte = basic_byte;
-
-            bytes_read_count += 1;
-        }
+    if chunk.message_start {
+        session_context.set_clock(chunk.clock).await;
     }
 
-    if header_res_bytes_size > 0 {
-        // Read the rest of the header
-        match tokio::time::timeout(
-            Duration::from_secs(RTMP_PING_TIMEOUT),
-            read_stream.read_exact(&mut header[basic_bytes..]),
-        )
-        .await
-        {
-            Ok(r) => {
-                if let Err(e) = r {
-                    log_debug!(
-                        logger,
-                        format!("Chunk read error. Could not read header: {}", e)
-                    );
+    // If packet is ready, handle
+    if let Some(packet) = &chunk.packet {
+        if packet.header.packet_type == RTMP_TYPE_ABORT {
+            // Abort message, handled here since it affects the input packets buffer
 
-                    return false;
-                }
-            }
-            Err(_) => {
-                log_debug!(logger, "Chunk read error. Could not read header: Timed out");
+            if packet.payload.len() < 4 {
+                log_debug!(logger, "Packet error: Payload too short");
 
                 return false;
             }
-        };
-
-        bytes_read_count += header_res_bytes_size;
-    }
 
-    // Parse packet metadata
+            let aborted_channel_id = BigEndian::read_u32(&packet.payload[0..4]);
 
-    let format = (header[0] >> 6) as u32;
-
-    let channel_id = match basic_bytes {
-        2 => 64 + (header[1] as u32),
-        3 => (64 + (header[1] as u32) + (header[2] as u32)) << 8,
-        _ => (header[0] & 0x3f) as u32,
-    };
-
-    // Find the packet in the buffer
-
-    let (packet_buf_index, packet_buf_dropped) =
-        get_input_packet_from_buffer(in_packets, channel_id);
-
-    let packet_wrapper = in_packets.get_mut(packet_buf_index).unwrap();
-
-    if packet_buf_dropped {
-        log_debug!(
-            logger,
-            format!(
-                "Reusing a packet slot from the buffer: {}",
-                packet_buf_index
-            )
-        );
-    }
-
-    packet_wrapper.packet.header.channel_id = channel_id;
-    packet_wrapper.packet.header.format = format;
-
-    let mut offset: usize = basic_bytes;
-
-    // Timestamp / delta
-    if packet_wrapper.packet.header.format <= RTMP_CHUNK_TYPE_2 {
-        if header.len() < offset + 3 {
-            log_error!(
-                logger,
-                "Header parsing error: Could not parse timestamp/delta"
-            );
-
-            return false;
-        }
-
-        let ts_bytes = &header[offset..offset + 3];
-
-        packet_wrapper.packet.header.timestamp = ((ts_bytes[2] as u32)
-            | ((ts_bytes[1] as u32) << 8)
-            | ((ts_bytes[0] as u32) << 16)) as i64;
-
-        offset += 3;
-    }
-
-    // Message length + type
-    if packet_wrapper.packet.header.format <= RTMP_CHUNK_TYPE_1 {
-        if header.len() < offset + 4 {
-            log_error!(
-                logger,
-                "Header parsing error: Could not parse message length + type"
-            );
-
-            return false;
-        }
-
-        let ts_bytes = &header[offset..offset + 3];
-
-        packet_wrapper.packet.header.length = ((ts_bytes[2] as u32)
-            | ((ts_bytes[1] as u32) << 8)
-            | ((ts_bytes[0] as u32) << 16)) as usize;
-        packet_wrapper.packet.header.packet_type = header[offset + 3] as u32;
-
-        offset += 4;
-    }
-
-    // Stream id
-    if packet_wrapper.packet.header.format == RTMP_CHUNK_TYPE_0 {
-        if header.len() < offset + 4 {
-            log_error!(logger, "Header parsing error: Could not parse stream id");
-
-            return false;
-        }
-
-        packet_wrapper.packet.header.stream_id =
-            LittleEndian::read_u32(&header[offset..offset + 4]);
-    }
-
-    // Stop packet
-    if packet_wrapper.packet.header.packet_type > RTMP_TYPE_METADATA {
-        log_debug!(
-            logger,
-            format!(
-                "Received stop packet: {}",
-                packet_wrapper.packet.header.packet_type
-            )
-        );
-
-        return false;
-    }
-
-    // Extended timestamp
-    let extended_timestamp: i64 = if packet_wrapper.packet.header.timestamp == 0xffffff {
-        let mut ts_bytes: Vec<u8> = vec![0; 4];
-
-        // Read extended timestamp
-        match tokio::time::timeout(
-            Duration::from_secs(RTMP_PING_TIMEOUT),
-            read_stream.read_exact(&mut ts_bytes),
-        )
-        .await
-        {
-            Ok(r) => {
-                if let Err(e) = r {
+            match in_packets.abort_packet(aborted_channel_id) {
+                Some(discarded) => {
                     log_debug!(
                         logger,
-                        format!("Chunk read error. Could not read extended timestamp: {}", e)
+                        format!(
+                            "Aborted packet of chunk stream {}: {} bytes discarded",
+                            aborted_channel_id, discarded
+                        )
                     );
-
-                    return false;
                 }
-            }
-            Err(_) => {
-                log_debug!(
-                    logger,
-                    "Chunk read error. Could not read extended timestamp: Timed out"
-                );
-
-                return false;
-            }
-        };
-
-        bytes_read_count += 4;
-
-        BigEndian::read_u32(&ts_bytes) as i64
-    } else {
-        packet_wrapper.packet.header.timestamp
-    };
-
-    if packet_wrapper.bytes == 0 {
-        if packet_wrapper.packet.header.format == RTMP_CHUNK_TYPE_0 {
-            packet_wrapper.clock = extended_timestamp;
-        } else {
-            packet_wrapper.clock = packet_wrapper.clock.wrapping_add(extended_timestamp);
-        }
-
-        session_context.set_clock(packet_wrapper.clock).await;
-    }
-
-    // Packet payload
-
-    let size_to_read: usize = cmp::min(
-        session_context.read_status.in_chunk_size
-            - (packet_wrapper.bytes % session_context.read_status.in_chunk_size),
-        packet_wrapper.packet.header.length - packet_wrapper.bytes,
-    );
-
-    if size_to_read > 0 {
-        let new_payload_size = packet_wrapper.bytes + size_to_read;
-        packet_wrapper
-            .packet
-            .payload
-            .resize(packet_wrapper.bytes + size_to_read, 0);
-
-        // Read payload bytes
-        match tokio::time::timeout(
-            Duration::from_secs(RTMP_PING_TIMEOUT),
-            read_stream.read_exact(
-                &mut packet_wrapper.packet.payload[packet_wrapper.bytes..new_payload_size],
-            ),
-        )
-        .await
-        {
-            Ok(r) => {
-                if let Err(e) = r {
+                None => {
                     log_debug!(
                         logger,
-                        format!("Chunk read error. Could not read payload bytes: {}", e)
+                        format!(
+                            "Received abort message for inactive chunk stream {}",
+                            aborted_channel_id
+                        )
                     );
-
-                    return false;
                 }
             }
-            Err(_) => {
-                log_debug!(
-                    logger,
-                    "Chunk read error. Could not read payload bytes: Timed out"
-                );
-
-                return false;
-            }
-        };
-
-        bytes_read_count += size_to_read;
-        packet_wrapper.bytes = new_payload_size;
-    }
-
-    // If packet is ready, handle
-    if packet_wrapper.bytes >= packet_wrapper.packet.header.length {
-        packet_wrapper.handled = true;
-
-        if packet_wrapper.clock <= 0xffffffff
+        } else if chunk.clock <= 0xffffffff
             && !handle_rtmp_packet(
                 logger,
                 server_context,
                 session_context,
                 write_stream,
-                &packet_wrapper.packet,
+                packet,
             )
             .await
         {
@@ -419,84 +193,202 @@ pub async fn read_rtmp_chunk<
         );
     }
 
-    // Bitrate
-
-    if logger.config.debug_enabled {
-        let now = Utc::now().timestamp_millis();
-        session_context.read_status.bit_rate_bytes = session_context
-            .read_status
-            .bit_rate_bytes
-            .wrapping_add(bytes_read_count);
+    // Bandwidth
 
-        let time_diff = now - session_context.read_status.bit_rate_last_update;
+    if let Some(bit_rate) = session_context.bandwidth.input.add(bytes_read_count) {
+        log_debug!(logger, format!("Input bit rate is now: {} bps", bit_rate));
 
-        if time_diff >= BIT_RATE_COMPUTE_INTERVAL_MS {
-            let bit_rate = f64::round(
-                (session_context.read_status.bit_rate_bytes as f64) * 8.0
-                    / ((time_diff as f64) / 1000.0),
-            );
+        if session_context.publish_congestion.is_enabled()
+            && session_context.read_status.channel_status.is_some()
+        {
+            let congestion = session_context
+                .publish_congestion
+                .check_bit_rate(bit_rate, Utc::now().timestamp_millis());
+            notify_publish_congestion(logger, session_context, congestion);
+        }
+    }
 
-            session_context.read_status.bit_rate_bytes = 0;
-            session_context.read_status.bit_rate_last_update = now;
+    // Congestion (delay of the received media)
 
-            log_debug!(logger, format!("Input bit rate is now: {} bps", bit_rate));
+    if let Some(packet) = &chunk.packet {
+        if session_context.publish_congestion.is_enabled()
+            && session_context.read_status.channel_status.is_some()
+            && (packet.header.packet_type == RTMP_TYPE_AUDIO
+                || packet.header.packet_type == RTMP_TYPE_VIDEO)
+        {
+            let congestion = session_context
+                .publish_congestion
+                .check_clock(chunk.clock, Utc::now().timestamp_millis());
+            notify_publish_congestion(logger, session_context, congestion);
         }
     }
 
     true
 }
 
-/// Gets an input packet from the buffer
+/// Notifies the publisher of the congestion, if detected
 ///
 /// # Arguments
 ///
-/// * `in_packets` - Input packets buffer
-/// * `channel_id` - Channel ID
+/// * `logger` - The session logger
+/// * `session_context` - The session context
+/// * `congestion` - The detected congestion
+fn notify_publish_congestion(
+    logger: &Logger,
+    session_context: &SessionReadThreadContext,
+    congestion: Option<PublishCongestion>,
+) {
+    let congestion = match congestion {
+        Some(c) => c,
+        None => return,
+    };
+
+    let description = congestion.description();
+
+    log_info!(logger, format!("Publisher congestion: {}", description));
+
+    // The notification is skipped if the message buffer is full
+
+    _ = session_context
+        .session_msg_sender
+        .try_send(RtmpSessionMessage::PublishCongestion { description });
+}
+
+/// Reads a RTMP chunk into the buffer of input packets
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `read_stream` - The stream to read from the client
+/// * `in_packets` - Buffer of input packets
+/// * `in_chunk_size` - Size of the input chunks
 ///
 /// # Return value
 ///
-/// Returns the index of the slot to use, and true if there were no slots, so the first one was chosen
-pub fn get_input_packet_from_buffer(
-    in_packets: &mut [RtmpPacketWrapper; IN_PACKETS_BUFFER_SIZE],
-    channel_id: u32,
-) -> (usize, bool) {
-    for (i, item) in in_packets
-        .iter_mut()
-        .enumerate()
-        .take(IN_PACKETS_BUFFER_SIZE)
-    {
-        if item.packet.header.channel_id == channel_id {
-            if item.handled {
-                item.reset();
+/// Returns the decoded chunk, or an error if the session must be closed
+pub async fn read_rtmp_chunk_to_buffer<TR: AsyncRead + AsyncReadExt + Send + Sync + Unpin>(
+    logger: &Logger,
+    read_stream: &mut TR,
+    in_packets: &mut RtmpInputPacketBuffer,
+    in_chunk_size: usize,
+) -> Result<RtmpDecodedChunk, RtmpChunkReadError> {
+    let mut data: Vec<u8> = Vec::new();
+
+    loop {
+        let (needed, field) = match rtmp_decode_chunk(logger, &data, in_packets, in_chunk_size) {
+            Ok(RtmpChunkDecodeStatus::Complete(chunk)) => {
+                return Ok(chunk);
             }
-            return (i, false);
-        }
+            Ok(RtmpChunkDecodeStatus::Incomplete { needed, field }) => (needed, field),
+            Err(e) => {
+                return Err(RtmpChunkReadError::Decode(e));
+            }
+        };
+
+        // Read only the missing bytes, so no bytes of the next chunk are consumed
+
+        let offset = data.len();
+
+        data.resize(needed, 0);
+
+        match tokio::time::timeout(
+            Duration::from_secs(RTMP_PING_TIMEOUT),
+            read_stream.read_exact(&mut data[offset..]),
+        )
+        .await
+        {
+            Ok(r) => {
+                if let Err(e) = r {
+                    return Err(RtmpChunkReadError::Read {
+                        field: field.to_string(),
+                        error: e,
+                    });
+                }
+            }
+            Err(_) => {
+                return Err(RtmpChunkReadError::TimedOut {
+                    field: field.to_string(),
+                });
+            }
+        };
     }
+}
 
-    for (i, item) in in_packets
-        .iter_mut()
-        .enumerate()
-        .take(IN_PACKETS_BUFFER_SIZE)
-    {
-        if !item.used {
-            item.used = true;
-            return (i, false);
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use crate::rtmp::{RtmpPacket, RTMP_CHUNK_TYPE_0, RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_chunks() {
+        let logger = Logger::new_disabled();
+
+        let mut stream: Vec<u8> = Vec::new();
+        let mut expected: Vec<(u32, Vec<u8>)> = Vec::new();
+
+        for (channel_id, packet_type) in [(4, RTMP_TYPE_AUDIO), (6, RTMP_TYPE_VIDEO)] {
+            let mut packet = RtmpPacket::new_blank();
+
+            packet.header.format = RTMP_CHUNK_TYPE_0;
+            packet.header.channel_id = channel_id;
+            packet.header.packet_type = packet_type;
+            packet.payload = (0..300).map(|i| (i % 256) as u8).collect();
+            packet.header.length = packet.payload.len();
+
+            stream.extend(packet.create_chunks(128));
+            expected.push((channel_id, packet.payload));
         }
-    }
 
-    for (i, item) in in_packets
-        .iter_mut()
-        .enumerate()
-        .take(IN_PACKETS_BUFFER_SIZE)
-    {
-        if item.handled {
-            item.reset_full();
-            item.used = true;
-            return (i, true);
+        let mut in_packets = RtmpInputPacketBuffer::new(4);
+        let mut received: Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut bytes_read: usize = 0;
+        let mut reader: &[u8] = &stream;
+
+        while !reader.is_empty() {
+            let chunk = read_rtmp_chunk_to_buffer(&logger, &mut reader, &mut in_packets, 128)
+                .await
+                .expect("Could not read chunk");
+
+            bytes_read += chunk.size;
+
+            if let Some(packet) = chunk.packet {
+                received.push((packet.header.channel_id, packet.payload));
+            }
         }
-    }
 
-    in_packets[0].reset_full();
+        assert_eq!(bytes_read, stream.len());
+        assert_eq!(received, expected);
+    }
 
-    (0, true)
+    #[tokio::test]
+    async fn test_read_chunk_errors() {
+        let logger = Logger::new_disabled();
+
+        let mut in_packets = RtmpInputPacketBuffer::new(4);
+
+        let mut reader: &[u8] = &[];
+        assert!(matches!(
+            read_rtmp_chunk_to_buffer(&logger, &mut reader, &mut in_packets, 128).await,
+            Err(RtmpChunkReadError::Read { field, .. }) if field == "start byte"
+        ));
+
+        // Incomplete header
+        let mut reader: &[u8] = &[0x03, 0, 0];
+        assert!(matches!(
+            read_rtmp_chunk_to_buffer(&logger, &mut reader, &mut in_packets, 128).await,
+            Err(RtmpChunkReadError::Read { field, .. }) if field == "header"
+        ));
+
+        // Type 3 chunk with no previous message
+        let mut reader: &[u8] = &[0xC3, 0x00];
+        assert!(matches!(
+            read_rtmp_chunk_to_buffer(&logger, &mut reader, &mut in_packets, 128).await,
+            Err(RtmpChunkReadError::Decode(
+                RtmpChunkDecodeError::MissingPreviousHeader { channel_id: 3 }
+            ))
+        ));
+    }
 }
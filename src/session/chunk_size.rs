@@ -0,0 +1,110 @@
+// Outbound chunk size negotiation
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::rtmp::{RTMP_MAX_CHUNK_SIZE, RTMP_MIN_CHUNK_SIZE};
+
+/// Outbound chunk size of a session
+pub struct SessionOutChunkSize {
+    /// Chunk size (bytes)
+    size: AtomicUsize,
+}
+
+impl SessionOutChunkSize {
+    /// Creates new SessionOutChunkSize.
+    /// Until negotiated, the default RTMP chunk size is used.
+    pub fn new() -> SessionOutChunkSize {
+        SessionOutChunkSize {
+            size: AtomicUsize::new(RTMP_MIN_CHUNK_SIZE),
+        }
+    }
+
+    /// Gets the chunk size
+    pub fn get(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Sets the chunk size
+    pub fn set(&self, size: usize) {
+        self.size.store(size, Ordering::Relaxed);
+    }
+}
+
+/// Negotiates the outbound chunk size for a session
+///
+/// # Arguments
+///
+/// * `chunk_size` - The configured chunk size
+/// * `legacy_clients` - Prefixes of flashVer for clients only supporting the default chunk size
+/// * `match_client` - True to use the chunk size announced by the client, if bigger
+/// * `flash_ver` - The flashVer sent by the client on connect
+/// * `in_chunk_size` - The chunk size announced by the client
+///
+/// # Return value
+///
+/// Returns the chunk size to use to send packets to the client
+pub fn negotiate_out_chunk_size(
+    chunk_size: usize,
+    legacy_clients: &[String],
+    match_client: bool,
+    flash_ver: Option<&str>,
+    in_chunk_size: usize,
+) -> usize {
+    if let Some(flash_ver) = flash_ver {
+        let flash_ver = flash_ver.to_lowercase();
+
+        if legacy_clients
+            .iter()
+            .any(|prefix| flash_ver.starts_with(&prefix.to_lowercase()))
+        {
+            return RTMP_MIN_CHUNK_SIZE;
+        }
+    }
+
+    if match_client && in_chunk_size > chunk_size {
+        return in_chunk_size.min(RTMP_MAX_CHUNK_SIZE);
+    }
+
+    chunk_size
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_out_chunk_size() {
+        let legacy_clients = vec!["LNX 9,0".to_string()];
+
+        assert_eq!(
+            negotiate_out_chunk_size(4096, &legacy_clients, false, Some("FMLE/3.0"), 128),
+            4096
+        );
+        assert_eq!(
+            negotiate_out_chunk_size(4096, &legacy_clients, false, None, 60000),
+            4096
+        );
+
+        // Legacy clients keep the default chunk size
+        assert_eq!(
+            negotiate_out_chunk_size(4096, &legacy_clients, true, Some("lnx 9,0,124,2"), 8192),
+            RTMP_MIN_CHUNK_SIZE
+        );
+
+        // Modern encoders announcing bigger chunks
+        assert_eq!(
+            negotiate_out_chunk_size(4096, &legacy_clients, true, None, 60000),
+            60000
+        );
+        assert_eq!(
+            negotiate_out_chunk_size(4096, &legacy_clients, true, None, 1024),
+            4096
+        );
+        assert_eq!(
+            negotiate_out_chunk_size(4096, &legacy_clients, true, None, usize::MAX),
+            RTMP_MAX_CHUNK_SIZE
+        );
+    }
+}
@@ -1,5 +1,7 @@
 // Session cleanup logic
 
+use std::sync::Arc;
+
 use crate::{
     log::Logger,
     server::{remove_player, remove_publisher, try_clear_channel, RtmpServerContext},
@@ -28,8 +30,10 @@ pub async fn do_session_cleanup(
         }
     };
 
-    let must_clear_player = session_status_v.play_status.is_player;
-    let must_clear_publisher = session_status_v.is_publisher;
+    let key = session_status_v.key.clone().unwrap_or_default();
+
+    let must_clear_player = session_status_v.is_player();
+    let must_clear_publisher = session_status_v.is_publisher();
 
     drop(session_status_v);
 
@@ -38,10 +42,99 @@ pub async fn do_session_cleanup(
     }
 
     if must_clear_publisher {
-        remove_publisher(logger, server_context, &channel, session_context.id).await
+        remove_publisher(
+            logger,
+            server_context,
+            &channel,
+            session_context.id,
+            &key,
+            true,
+            None,
+        )
+        .await
     }
 
     if must_clear_player || must_clear_publisher {
         try_clear_channel(server_context, &channel).await;
     }
 }
+
+/// Guard to perform the session cleanup.
+/// If dropped before the cleanup is performed
+/// (the task panicked or was cancelled), the cleanup is performed in a new task,
+/// so the channel status is not left with the session in it.
+pub struct SessionCleanupGuard {
+    /// The session logger
+    logger: Arc<Logger>,
+
+    /// The server context
+    server_context: RtmpServerContext,
+
+    /// The session context
+    session_context: SessionContext,
+
+    /// True if the cleanup was performed
+    done: bool,
+}
+
+impl SessionCleanupGuard {
+    /// Creates new SessionCleanupGuard
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The session logger
+    /// * `server_context` - The server context
+    /// * `session_context` - The session context
+    pub fn new(
+        logger: Arc<Logger>,
+        server_context: RtmpServerContext,
+        session_context: SessionContext,
+    ) -> SessionCleanupGuard {
+        SessionCleanupGuard {
+            logger,
+            server_context,
+            session_context,
+            done: false,
+        }
+    }
+
+    /// Performs the session cleanup
+    pub async fn cleanup(mut self) {
+        do_session_cleanup(
+            &self.logger,
+            &mut self.server_context,
+            &self.session_context,
+        )
+        .await;
+
+        self.done = true;
+    }
+
+    /// Disarms the guard, when the cleanup is performed by another task
+    pub fn disarm(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for SessionCleanupGuard {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(r) => r,
+            Err(_) => {
+                return; // The runtime is gone, nothing to clean
+            }
+        };
+
+        let logger = self.logger.clone();
+        let mut server_context = self.server_context.clone();
+        let session_context = self.session_context.clone();
+
+        runtime.spawn(async move {
+            do_session_cleanup(&logger, &mut server_context, &session_context).await;
+        });
+    }
+}
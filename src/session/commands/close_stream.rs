@@ -1,4 +1,4 @@
-// Delete stream command
+// Close stream command
 
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
@@ -39,6 +39,8 @@ pub async fn handle_rtmp_command_close_stream<
 
     if stream_id == 0 {
         log_debug!(logger, "Command error: streamId cannot be 0");
+
+        return true;
     }
 
     rtmp_delete_stream(
@@ -47,6 +49,7 @@ pub async fn handle_rtmp_command_close_stream<
         session_context,
         write_stream,
         stream_id,
+        false,
     )
     .await
 }
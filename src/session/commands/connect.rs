@@ -12,10 +12,10 @@ use crate::{
     rtmp::{
         rtmp_make_chunk_size_set_message, rtmp_make_connect_response,
         rtmp_make_peer_bandwidth_set_message, rtmp_make_window_ack, RtmpCommand,
-        RTMP_PEER_BANDWIDTH, RTMP_WINDOW_ACK,
+        RTMP_MIN_CHUNK_SIZE, RTMP_OBJECT_ENCODING_AMF3, RTMP_PEER_BANDWIDTH, RTMP_WINDOW_ACK,
     },
     server::RtmpServerContext,
-    session::SessionReadThreadContext,
+    session::{negotiate_out_chunk_size, RtmpConnectInfo, SessionReadThreadContext},
     utils::validate_id_string,
 };
 
@@ -45,35 +45,58 @@ pub async fn handle_rtmp_command_connect<
 ) -> bool {
     // Load and validate parameters
 
-    let channel = match cmd.get_argument("cmdObj") {
-        Some(cmd_obj) => match cmd_obj.get_object_property("app") {
-            Some(app) => {
-                let app_str = app.get_string();
+    let cmd_obj = match cmd.get_argument("cmdObj") {
+        Some(c) => c,
+        None => {
+            log_debug!(logger, "Command error: cmdObj argument not provided");
 
-                if !validate_id_string(app_str, &server_context.config.id_validation) {
-                    log_debug!(
-                        logger,
-                        format!("Command error: Invalid app value: {}", app_str)
-                    );
+            return false;
+        }
+    };
 
-                    return false;
-                }
+    let app = cmd_obj.get_object_property("app").map(|a| a.get_string());
 
-                app_str
-            }
-            None => {
-                log_debug!(logger, "Command error: app property not provided");
+    let channel = match app {
+        Some(app_str) if !app_str.is_empty() => app_str.to_string(),
+        _ => {
+            // Some clients only send the app in the tcUrl
 
-                return false;
-            }
-        },
-        None => {
-            log_debug!(logger, "Command error: cmdObj argument not provided");
+            let tc_url_app = cmd_obj
+                .get_object_property("tcUrl")
+                .and_then(|t| get_app_from_tc_url(t.get_string()));
 
-            return false;
+            match (tc_url_app, app) {
+                (Some(tc_url_app), _) => {
+                    log_debug!(logger, format!("App taken from the tcUrl: {}", tc_url_app));
+
+                    tc_url_app
+                }
+                (None, Some(app_str)) => app_str.to_string(),
+                (None, None) => {
+                    log_debug!(logger, "Command error: app property not provided");
+
+                    return false;
+                }
+            }
         }
     };
 
+    if channel.is_empty() && server_context.config.empty_app_compat {
+        log_debug!(
+            logger,
+            "Empty app: The channel will be taken from the stream name"
+        );
+    } else if let Err(reason) = validate_app(server_context, &channel) {
+        log_debug!(
+            logger,
+            format!("Command error: Invalid app value: {} ({})", channel, reason)
+        );
+
+        return false;
+    }
+
+    let channel: &str = &channel;
+
     let object_encoding: Option<u32> = match cmd.get_argument("cmdObj") {
         Some(cmd_obj) => match cmd_obj.get_object_property("objectEncoding") {
             Some(oe) => {
@@ -88,6 +111,24 @@ pub async fn handle_rtmp_command_connect<
         None => None,
     };
 
+    let connect_info = match cmd.get_argument("cmdObj") {
+        Some(cmd_obj) => {
+            let get_field = |name: &str| {
+                cmd_obj
+                    .get_object_property(name)
+                    .filter(|v| !v.is_undefined())
+                    .and_then(|v| RtmpConnectInfo::sanitize_field(v.get_string()))
+            };
+
+            RtmpConnectInfo {
+                flash_ver: get_field("flashVer"),
+                swf_url: get_field("swfUrl"),
+                tc_url: get_field("tcUrl"),
+            }
+        }
+        None => RtmpConnectInfo::default(),
+    };
+
     let trans_id = match cmd.get_argument("transId") {
         Some(t) => t.get_integer(),
         None => 0,
@@ -111,11 +152,29 @@ pub async fn handle_rtmp_command_connect<
         return false;
     }
 
+    session_status_v.app = Some(channel.to_string());
     session_status_v.channel = Some(channel.to_string());
     session_status_v.connect_time = now;
+    let flash_ver = connect_info.flash_ver.clone();
+    session_status_v.connect_info = connect_info;
 
     drop(session_status_v);
 
+    if !channel.is_empty() || !server_context.config.empty_app_compat {
+        logger.set_session_channel(channel);
+
+        server_context
+            .status
+            .traces
+            .set_session_channel(
+                logger,
+                &server_context.config.trace,
+                session_context.id,
+                channel,
+            )
+            .await;
+    }
+
     // Send window ACK
 
     let window_ack_bytes = rtmp_make_window_ack(RTMP_WINDOW_ACK);
@@ -140,20 +199,34 @@ pub async fn handle_rtmp_command_connect<
 
     // Set chunk size
 
-    let chunk_size_bytes =
-        rtmp_make_chunk_size_set_message(server_context.config.chunk_size as u32);
-    if let Err(e) = session_write_bytes(write_stream, &chunk_size_bytes).await {
-        log_debug!(
-            logger,
-            format!("Send error: Could not set chunk size: {}", e)
-        );
-        return false;
+    let out_chunk_size = negotiate_out_chunk_size(
+        server_context.config.chunk_size,
+        &server_context.config.chunk_size_legacy_clients,
+        server_context.config.chunk_size_match_client,
+        flash_ver.as_deref(),
+        session_context.read_status.in_chunk_size,
+    );
+
+    if out_chunk_size != RTMP_MIN_CHUNK_SIZE {
+        let chunk_size_bytes = rtmp_make_chunk_size_set_message(out_chunk_size as u32);
+        if let Err(e) = session_write_bytes(write_stream, &chunk_size_bytes).await {
+            log_debug!(
+                logger,
+                format!("Send error: Could not set chunk size: {}", e)
+            );
+            return false;
+        }
     }
 
+    session_context.out_chunk_size.set(out_chunk_size);
+    session_context.trace.set_out_chunk_size(out_chunk_size);
+
+    log_debug!(logger, format!("Outbound chunk size: {}", out_chunk_size));
+
     // Respond
 
     let connect_response_bytes =
-        rtmp_make_connect_response(trans_id, object_encoding, server_context.config.chunk_size);
+        rtmp_make_connect_response(trans_id, object_encoding, out_chunk_size);
     if let Err(e) = session_write_bytes(write_stream, &connect_response_bytes).await {
         log_debug!(
             logger,
@@ -163,7 +236,155 @@ pub async fn handle_rtmp_command_connect<
         return false;
     }
 
+    // Use the requested object encoding for the next commands
+
+    if object_encoding == Some(RTMP_OBJECT_ENCODING_AMF3) {
+        session_context
+            .object_encoding
+            .set(RTMP_OBJECT_ENCODING_AMF3);
+
+        log_debug!(logger, "Object encoding: AMF3");
+    }
+
     // Done
 
     true
 }
+
+/// Validates the app requested by the client.
+/// If tenant namespaces are enabled, the app must have the format `tenant/app`,
+/// with a registered tenant.
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `app` - The requested app
+///
+/// # Return value
+///
+/// Returns an error with the reason if the app is not valid
+fn validate_app(server_context: &RtmpServerContext, app: &str) -> Result<(), String> {
+    let id_validation = &server_context.config.id_validation;
+    let tenants = &server_context.config.tenants;
+
+    if !tenants.is_enabled() {
+        return validate_id_string(app, id_validation);
+    }
+
+    match app.split_once('/') {
+        Some((tenant, tenant_app)) => {
+            if !tenants.is_allowed(tenant) {
+                return Err("unknown tenant".to_string());
+            }
+
+            validate_id_string(tenant, id_validation).map_err(|e| format!("tenant {}", e))?;
+            validate_id_string(tenant_app, id_validation)?;
+
+            if tenant_app.contains('/') {
+                return Err("too many path segments".to_string());
+            }
+
+            Ok(())
+        }
+        None => Err("missing the tenant".to_string()),
+    }
+}
+
+/// Takes the app from the stream name, for clients connecting with an empty app
+/// (empty app compatibility mode). Some encoders put the whole path in the stream name.
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `stream_name` - The stream name, without the query string, with the format `app/key`
+///
+/// # Return value
+///
+/// Returns the app and the key, or an error with the reason
+pub fn resolve_deferred_app<'a>(
+    server_context: &RtmpServerContext,
+    stream_name: &'a str,
+) -> Result<(String, &'a str), String> {
+    let (app, key) = match stream_name.rsplit_once('/') {
+        Some(p) => p,
+        None => return Err("the stream name must include the channel".to_string()),
+    };
+
+    validate_app(server_context, app).map_err(|e| format!("channel {}", e))?;
+
+    Ok((app.to_string(), key))
+}
+
+/// Gets the app from the tcUrl sent by the client on connect
+/// (eg: `rtmp://host:1935/app?query` -> `app`)
+///
+/// # Arguments
+///
+/// * `tc_url` - The tcUrl
+///
+/// # Return value
+///
+/// Returns the app, or None if the tcUrl does not include it
+fn get_app_from_tc_url(tc_url: &str) -> Option<String> {
+    let without_scheme = match tc_url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => tc_url,
+    };
+
+    let without_query = match without_scheme.split_once('?') {
+        Some((url, _)) => url,
+        None => without_scheme,
+    };
+
+    let (_, path) = without_query.split_once('/')?;
+
+    let app = path.trim_matches('/');
+
+    if app.is_empty() {
+        None
+    } else {
+        Some(app.to_string())
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_app_from_tc_url() {
+        assert_eq!(
+            get_app_from_tc_url("rtmp://localhost/live"),
+            Some("live".to_string())
+        );
+        assert_eq!(
+            get_app_from_tc_url("rtmp://127.0.0.1:1935/live/"),
+            Some("live".to_string())
+        );
+        assert_eq!(
+            get_app_from_tc_url("rtmps://live.example.com:443/app?token=abc/def"),
+            Some("app".to_string())
+        );
+        assert_eq!(
+            get_app_from_tc_url("rtmp://[::1]:1935/live"),
+            Some("live".to_string())
+        );
+        assert_eq!(
+            get_app_from_tc_url("RTMP://Example.com/tenant/live"),
+            Some("tenant/live".to_string())
+        );
+        assert_eq!(
+            get_app_from_tc_url("example.com/live"),
+            Some("live".to_string())
+        );
+
+        // Without app
+
+        assert_eq!(get_app_from_tc_url("rtmp://localhost"), None);
+        assert_eq!(get_app_from_tc_url("rtmp://localhost:1935/"), None);
+        assert_eq!(get_app_from_tc_url("rtmp://localhost?a=b/c"), None);
+        assert_eq!(get_app_from_tc_url(""), None);
+    }
+}
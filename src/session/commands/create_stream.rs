@@ -9,8 +9,7 @@ use crate::{
     log::Logger,
     log_debug,
     rtmp::{rtmp_make_create_stream_response, RtmpCommand},
-    server::RtmpServerContext,
-    session::SessionReadThreadContext,
+    session::{SessionReadThreadContext, RTMP_SESSION_MAX_STREAMS},
 };
 
 use super::super::session_write_bytes;
@@ -20,7 +19,6 @@ use super::super::session_write_bytes;
 /// # Arguments
 ///
 /// * `logger` - The session logger
-/// * `server_context` - The server context
 /// * `session_context` - The session context
 /// * `write_stream` - The stream to write to the client
 /// * `cmd` - The command
@@ -32,7 +30,6 @@ pub async fn handle_rtmp_command_create_stream<
     TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
 >(
     logger: &Logger,
-    server_context: &mut RtmpServerContext,
     session_context: &mut SessionReadThreadContext,
     write_stream: &Mutex<TW>,
     cmd: &RtmpCommand,
@@ -47,14 +44,32 @@ pub async fn handle_rtmp_command_create_stream<
     // Create stream
 
     let mut session_status_v = session_context.status.lock().await;
-    session_status_v.streams = session_status_v.streams.wrapping_add(1);
-    let stream_index = session_status_v.streams as u32;
+    let stream_index = session_status_v.create_stream();
     drop(session_status_v);
 
+    let stream_index = match stream_index {
+        Some(i) => i,
+        None => {
+            log_debug!(
+                logger,
+                format!(
+                    "Protocol error: Reached the max number of streams ({})",
+                    RTMP_SESSION_MAX_STREAMS
+                )
+            );
+
+            return false;
+        }
+    };
+
     // Respond
 
-    let response_bytes =
-        rtmp_make_create_stream_response(trans_id, stream_index, server_context.config.chunk_size);
+    let response_bytes = rtmp_make_create_stream_response(
+        trans_id,
+        stream_index,
+        session_context.object_encoding(),
+        session_context.out_chunk_size(),
+    );
     if let Err(e) = session_write_bytes(write_stream, &response_bytes).await {
         log_debug!(
             logger,
@@ -46,6 +46,8 @@ pub async fn handle_rtmp_command_delete_stream<
 
     if stream_id == 0 {
         log_debug!(logger, "Command error: streamId cannot be 0");
+
+        return true;
     }
 
     rtmp_delete_stream(
@@ -54,6 +56,7 @@ pub async fn handle_rtmp_command_delete_stream<
         session_context,
         write_stream,
         stream_id,
+        true,
     )
     .await
 }
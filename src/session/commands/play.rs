@@ -6,12 +6,24 @@ use tokio::{
 };
 
 use crate::{
+    amf::AMF0Value,
+    auth::{validate_stream_token, StreamTokenRole},
+    callback::{make_play_callback, PlayAuthorization},
+    cluster::cluster_pull_channel,
+    control::control_validate_play,
     log::Logger,
     log_debug, log_info,
-    rtmp::{RtmpCommand, RtmpPacket},
-    server::{add_player, AddPlayerOptions, RtmpServerContext},
-    session::{send_status_message, SessionReadThreadContext},
-    utils::{parse_query_string_simple, validate_id_string},
+    rtmp::{RtmpCommand, RtmpPacket, RTMP_PLAY_START_ANY, RTMP_PLAY_START_LIVE},
+    server::{
+        add_player, is_channel_published, AddPlayerOptions, AddPlayerResult, RtmpServerContext,
+    },
+    session::{
+        resolve_deferred_app, send_redirect_status_message, send_status_message,
+        SessionReadThreadContext,
+    },
+    utils::{
+        parse_query_string_simple, split_stream_name, validate_id_string, REDACTED_STREAM_KEY,
+    },
 };
 
 /// Handles RTMP command: PLAY
@@ -42,7 +54,7 @@ pub async fn handle_rtmp_command_play<
 
     let play_stream_id = packet.header.stream_id;
 
-    let channel = match session_context.channel().await {
+    let app = match session_context.app().await {
         Some(c) => c,
         None => {
             log_debug!(logger, "Protocol error: Received play before connect");
@@ -53,7 +65,8 @@ pub async fn handle_rtmp_command_play<
                 "error",
                 "NetStream.Play.BadConnection",
                 Some("No channel is selected"),
-                server_context.config.chunk_size,
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
             )
             .await
             {
@@ -67,52 +80,99 @@ pub async fn handle_rtmp_command_play<
         }
     };
 
-    let (key, gop_receive, gop_clear) = match cmd.get_argument("streamName") {
-        Some(k) => {
-            let k_parts: Vec<&str> = k.get_string().split("?").collect();
+    let (key, query_params, gop_receive, gop_clear, low_latency) =
+        match cmd.get_argument("streamName") {
+            Some(k) => {
+                let (key, query_string) = split_stream_name(k.get_string());
+                let query_params = parse_query_string_simple(query_string);
 
-            if k_parts.len() > 1 {
-                let q_str = parse_query_string_simple(k_parts[1]);
-
-                match q_str.get("cache") {
+                let (gop_receive, gop_clear) = match query_params.get("cache") {
                     Some(cache_opt) => match cache_opt.as_str() {
-                        "clear" => (k_parts[0], true, false),
-                        "no" => (k_parts[0], false, false),
-                        _ => (k_parts[0], true, false),
+                        "clear" => (true, false),
+                        "no" => (false, false),
+                        _ => (true, false),
                     },
-                    None => (k_parts[0], true, false),
+                    None => (true, false),
+                };
+
+                // Low latency mode: skip the GOP cache and start at the next key frame
+
+                let low_latency = query_params.get("latency").is_some_and(|l| l == "low");
+
+                (
+                    key,
+                    query_params,
+                    gop_receive && !low_latency,
+                    gop_clear,
+                    low_latency,
+                )
+            }
+            None => {
+                log_debug!(logger, "Command error: streamName property not provided");
+
+                if let Err(e) = send_status_message(
+                    write_stream,
+                    play_stream_id,
+                    "error",
+                    "NetStream.Play.BadName",
+                    Some("No stream key provided"),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                )
+                .await
+                {
+                    log_debug!(
+                        logger,
+                        format!("Send error: Could not send status message: {}", e)
+                    );
                 }
-            } else {
-                (k.get_string(), true, false)
+
+                return false;
             }
-        }
-        None => {
-            log_debug!(logger, "Command error: streamName property not provided");
+        };
 
-            if let Err(e) = send_status_message(
-                write_stream,
-                play_stream_id,
-                "error",
-                "NetStream.Play.BadName",
-                Some("No stream key provided"),
-                server_context.config.chunk_size,
-            )
-            .await
-            {
+    // Take the channel from the stream name if the app was empty on connect
+
+    let (app, key) = if app.is_empty() && server_context.config.empty_app_compat {
+        match resolve_deferred_app(server_context, key) {
+            Ok(r) => r,
+            Err(reason) => {
                 log_debug!(
                     logger,
-                    format!("Send error: Could not send status message: {}", e)
+                    format!("Command error: Invalid streamName value: {}", reason)
                 );
-            }
 
-            return false;
+                if let Err(e) = send_status_message(
+                    write_stream,
+                    play_stream_id,
+                    "error",
+                    "NetStream.Play.BadName",
+                    Some(&format!("Invalid stream name provided: {}", reason)),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                )
+                .await
+                {
+                    log_debug!(
+                        logger,
+                        format!("Send error: Could not send status message: {}", e)
+                    );
+                }
+
+                return false;
+            }
         }
+    } else {
+        (app, key)
     };
 
-    if !validate_id_string(key, &server_context.config.id_validation) {
+    if let Err(reason) = validate_id_string(key, &server_context.config.id_validation) {
         log_debug!(
             logger,
-            format!("Command error: Invalid streamName value: {}", key)
+            format!(
+                "Command error: Invalid streamName value: {} ({})",
+                REDACTED_STREAM_KEY, reason
+            )
         );
 
         if let Err(e) = send_status_message(
@@ -120,8 +180,90 @@ pub async fn handle_rtmp_command_play<
             play_stream_id,
             "error",
             "NetStream.Play.BadName",
-            Some("Invalid stream key provided"),
-            server_context.config.chunk_size,
+            Some(&format!("Invalid stream key provided: {}", reason)),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
+        )
+        .await
+        {
+            log_debug!(
+                logger,
+                format!("Send error: Could not send status message: {}", e)
+            );
+        }
+
+        return false;
+    }
+
+    // Load the playback options
+    // There are no recorded streams, so any start position plays the live stream
+
+    let start = match cmd.get_argument("start") {
+        Some(AMF0Value::Number { value }) => *value as i64,
+        _ => RTMP_PLAY_START_ANY,
+    };
+
+    let live_only = start == RTMP_PLAY_START_LIVE;
+
+    if start >= 0 {
+        log_debug!(
+            logger,
+            format!(
+                "Requested recorded stream (start = {}), playing the live stream",
+                start
+            )
+        );
+    }
+
+    let duration = match cmd.get_argument("duration") {
+        Some(AMF0Value::Number { value }) if *value >= 0.0 => Some((*value * 1000.0) as i64),
+        _ => None,
+    };
+
+    let reset = match cmd.get_argument("reset") {
+        Some(AMF0Value::Bool { value }) => *value,
+        Some(AMF0Value::Number { value }) => (*value as i64) & 1 == 1,
+        _ => true,
+    };
+
+    // Apply the channel aliases
+
+    let (channel, key) = match server_context.config.channel_aliases.resolve(&app, key) {
+        Some((alias_channel, alias_key)) => {
+            log_debug!(
+                logger,
+                format!(
+                    "Channel alias: {}/{} -> {}/{}",
+                    &app, REDACTED_STREAM_KEY, &alias_channel, REDACTED_STREAM_KEY
+                )
+            );
+
+            (alias_channel, alias_key)
+        }
+        None => (app, key.to_string()),
+    };
+
+    let key: &str = &key;
+
+    // Ensure the stream is not already in use
+
+    if session_context.is_stream_in_use(play_stream_id).await {
+        log_debug!(
+            logger,
+            format!(
+                "Protocol error: Received play command, but the stream {} is already in use",
+                play_stream_id
+            )
+        );
+
+        if let Err(e) = send_status_message(
+            write_stream,
+            play_stream_id,
+            "error",
+            "NetStream.Play.BadConnection",
+            Some("Stream already in use"),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -148,7 +290,36 @@ pub async fn handle_rtmp_command_play<
             "error",
             "NetStream.Play.BadConnection",
             Some("Connection already playing"),
-            server_context.config.chunk_size,
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
+        )
+        .await
+        {
+            log_debug!(
+                logger,
+                format!("Send error: Could not send status message: {}", e)
+            );
+        }
+
+        return false;
+    }
+
+    // Set the channel of the session
+
+    if !session_context.set_channel(&channel).await {
+        log_debug!(
+            logger,
+            "Protocol error: The session is already using a different channel"
+        );
+
+        if let Err(e) = send_status_message(
+            write_stream,
+            play_stream_id,
+            "error",
+            "NetStream.Play.BadConnection",
+            Some("Connection already using a different channel"),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -161,6 +332,19 @@ pub async fn handle_rtmp_command_play<
         return false;
     }
 
+    logger.set_session_channel(&channel);
+
+    server_context
+        .status
+        .traces
+        .set_session_channel(
+            logger,
+            &server_context.config.trace,
+            session_context.id,
+            &channel,
+        )
+        .await;
+
     // Ensure the client IP is whitelisted
 
     if !server_context
@@ -176,7 +360,8 @@ pub async fn handle_rtmp_command_play<
             "error",
             "NetStream.Play.BadName",
             Some("Your net address is not whitelisted for playing"),
-            server_context.config.chunk_size,
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -189,19 +374,149 @@ pub async fn handle_rtmp_command_play<
         return false;
     }
 
+    // Validate the token, if required
+
+    if server_context
+        .config
+        .get_auth_token_config(&channel)
+        .is_play_token_required()
+    {
+        if let Err(e) = validate_stream_token(
+            server_context.config.get_auth_token_config(&channel),
+            &query_params,
+            &channel,
+            key,
+            StreamTokenRole::Play,
+        ) {
+            log_debug!(logger, format!("Invalid play token: {}", e));
+
+            if let Err(e) = send_status_message(
+                write_stream,
+                play_stream_id,
+                "error",
+                "NetStream.Play.BadName",
+                Some("Invalid token provided"),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            )
+            .await
+            {
+                log_debug!(
+                    logger,
+                    format!("Send error: Could not send status message: {}", e)
+                );
+            }
+
+            return false;
+        }
+    }
+
+    // Validate the player with the control server, if it validates the keys,
+    // or with the callback, if enabled
+
+    let control_play_authorization = match &server_context.control_key_validator_sender {
+        Some(control_key_validator_sender_v) => {
+            control_validate_play(
+                control_key_validator_sender_v,
+                &channel,
+                key,
+                session_context.id,
+                &session_context.ip,
+                &query_params,
+            )
+            .await
+        }
+        None => None,
+    };
+
+    let play_authorization = match control_play_authorization {
+        Some(a) => a,
+        None => {
+            make_play_callback(
+                logger,
+                server_context.config.get_callback_config(&channel),
+                &channel,
+                key,
+                session_context.id,
+                &session_context.ip,
+                session_context.client_geo.as_deref(),
+                &query_params,
+            )
+            .await
+        }
+    };
+
+    match play_authorization {
+        PlayAuthorization::Accepted => {}
+        PlayAuthorization::Rejected => {
+            log_debug!(logger, "Player rejected by the key validation");
+
+            if let Err(e) = send_status_message(
+                write_stream,
+                play_stream_id,
+                "error",
+                "NetStream.Play.BadName",
+                Some("Invalid stream key provided"),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            )
+            .await
+            {
+                log_debug!(
+                    logger,
+                    format!("Send error: Could not send status message: {}", e)
+                );
+            }
+
+            return false;
+        }
+        PlayAuthorization::Redirect(redirect_url) => {
+            log_info!(
+                logger,
+                format!(
+                    "PLAY REDIRECT ({}): {} -> {}",
+                    play_stream_id, &channel, &redirect_url
+                )
+            );
+
+            if let Err(e) = send_redirect_status_message(
+                write_stream,
+                play_stream_id,
+                &redirect_url,
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            )
+            .await
+            {
+                log_debug!(
+                    logger,
+                    format!("Send error: Could not send status message: {}", e)
+                );
+            }
+
+            return false;
+        }
+    }
+
     // Log
 
     log_info!(logger, format!("PLAY ({}): {}", play_stream_id, &channel));
 
+    // If the channel is published on another node of the cluster, pull it
+
+    if server_context.cluster.is_some() && !is_channel_published(server_context, &channel).await {
+        cluster_pull_channel(logger, server_context, &channel, key).await;
+    }
+
     // Update session status
 
     let (receive_audio, receive_video) = session_context
-        .set_player(gop_receive, play_stream_id)
+        .set_player(gop_receive, play_stream_id, duration, reset)
         .await;
 
     // Update server status
 
-    if !add_player(
+    let add_player_result = add_player(
         server_context,
         session_context,
         &channel,
@@ -210,32 +525,63 @@ pub async fn handle_rtmp_command_play<
             gop_clear,
             receive_audio,
             receive_video,
+            live_only,
+            low_latency,
         },
     )
-    .await
-    {
-        log_debug!(logger, "Invalid streaming key provided");
+    .await;
+
+    let (status_code, description) = match add_player_result {
+        AddPlayerResult::Added => {
+            server_context
+                .status
+                .events
+                .record(
+                    &channel,
+                    "player-join",
+                    session_context.id,
+                    &format!("IP: {}", session_context.ip),
+                )
+                .await;
+
+            return true;
+        }
+        AddPlayerResult::InvalidKey => {
+            log_debug!(logger, "Invalid streaming key provided");
 
-        if let Err(e) = send_status_message(
-            write_stream,
-            play_stream_id,
-            "error",
-            "NetStream.Play.BadName",
-            Some("Invalid stream key provided"),
-            server_context.config.chunk_size,
-        )
-        .await
-        {
+            ("NetStream.Play.BadName", "Invalid stream key provided")
+        }
+        AddPlayerResult::PlayerLimitReached => {
+            log_debug!(logger, "Cannot play: The channel reached the viewer limit");
+
+            ("NetStream.Play.Failed", "Viewer limit reached")
+        }
+        AddPlayerResult::NotPublishing => {
             log_debug!(
                 logger,
-                format!("Send error: Could not send status message: {}", e)
+                "Cannot play: Live stream requested, but not publishing"
             );
+
+            ("NetStream.Play.StreamNotFound", "Live stream not found")
         }
+    };
 
-        return false;
+    if let Err(e) = send_status_message(
+        write_stream,
+        play_stream_id,
+        "error",
+        status_code,
+        Some(description),
+        session_context.object_encoding(),
+        session_context.out_chunk_size(),
+    )
+    .await
+    {
+        log_debug!(
+            logger,
+            format!("Send error: Could not send status message: {}", e)
+        );
     }
 
-    // Done
-
-    true
+    false
 }
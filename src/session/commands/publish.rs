@@ -6,14 +6,23 @@ use tokio::{
 };
 
 use crate::{
-    callback::make_start_callback,
-    control::control_validate_key,
+    auth::{validate_stream_token, StreamTokenRole},
+    callback::{make_start_callback, CallbackCorrelation},
+    control::{control_validate_key, ControlKeyValidationResult},
     log::Logger,
-    log_debug, log_info,
-    rtmp::{RtmpCommand, RtmpPacket},
-    server::{check_channel_publishing_status, set_publisher, RtmpServerContext},
-    session::SessionReadThreadContext,
-    utils::validate_id_string,
+    log_debug, log_info, log_warning,
+    record::{start_recording, PublishTypeRecordingPolicy},
+    rtmp::{RtmpCommand, RtmpPacket, RtmpPublishType},
+    server::{
+        check_channel_publisher_key, check_channel_publishing_status, end_publisher_resume,
+        resume_publisher, set_publisher, takeover_publisher, PublishAuthorization,
+        PublishStreamFlags, PublishTakeoverPolicy, RtmpServerContext,
+    },
+    session::{resolve_deferred_app, spawn_task_send_publish_statistics, SessionReadThreadContext},
+    utils::{
+        generate_uuid_v4, parse_query_string_simple, split_stream_name, validate_id_string,
+        REDACTED_STREAM_KEY,
+    },
 };
 
 use super::super::send_status_message;
@@ -46,7 +55,7 @@ pub async fn handle_rtmp_command_publish<
 
     let publish_stream_id = packet.header.stream_id;
 
-    let channel = match session_context.channel().await {
+    let app = match session_context.app().await {
         Some(c) => c,
         None => {
             log_debug!(logger, "Protocol error: Received publish before connect");
@@ -57,7 +66,8 @@ pub async fn handle_rtmp_command_publish<
                 "error",
                 "NetStream.Publish.BadConnection",
                 Some("No channel is selected"),
-                server_context.config.chunk_size,
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
             )
             .await
             {
@@ -71,15 +81,11 @@ pub async fn handle_rtmp_command_publish<
         }
     };
 
-    let key = match cmd.get_argument("streamName") {
+    let (key, query_params) = match cmd.get_argument("streamName") {
         Some(k) => {
-            let k_parts: Vec<&str> = k.get_string().split("?").collect();
+            let (key, query_string) = split_stream_name(k.get_string());
 
-            if !k_parts.is_empty() {
-                k_parts[0]
-            } else {
-                k.get_string()
-            }
+            (key, parse_query_string_simple(query_string))
         }
         None => {
             log_debug!(logger, "Command error: streamName property not provided");
@@ -90,7 +96,8 @@ pub async fn handle_rtmp_command_publish<
                 "error",
                 "NetStream.Publish.BadName",
                 Some("No stream key provided"),
-                server_context.config.chunk_size,
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
             )
             .await
             {
@@ -104,10 +111,48 @@ pub async fn handle_rtmp_command_publish<
         }
     };
 
-    if !validate_id_string(key, &server_context.config.id_validation) {
+    // Take the channel from the stream name if the app was empty on connect
+
+    let (app, key) = if app.is_empty() && server_context.config.empty_app_compat {
+        match resolve_deferred_app(server_context, key) {
+            Ok(r) => r,
+            Err(reason) => {
+                log_debug!(
+                    logger,
+                    format!("Command error: Invalid streamName value: {}", reason)
+                );
+
+                if let Err(e) = send_status_message(
+                    write_stream,
+                    publish_stream_id,
+                    "error",
+                    "NetStream.Publish.BadName",
+                    Some(&format!("Invalid stream name provided: {}", reason)),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                )
+                .await
+                {
+                    log_debug!(
+                        logger,
+                        format!("Send error: Could not send status message: {}", e)
+                    );
+                }
+
+                return false;
+            }
+        }
+    } else {
+        (app, key)
+    };
+
+    if let Err(reason) = validate_id_string(key, &server_context.config.id_validation) {
         log_debug!(
             logger,
-            format!("Command error: Invalid streamName value: {}", key)
+            format!(
+                "Command error: Invalid streamName value: {} ({})",
+                REDACTED_STREAM_KEY, reason
+            )
         );
 
         if let Err(e) = send_status_message(
@@ -115,8 +160,97 @@ pub async fn handle_rtmp_command_publish<
             publish_stream_id,
             "error",
             "NetStream.Publish.BadName",
-            Some("Invalid stream key provided"),
-            server_context.config.chunk_size,
+            Some(&format!("Invalid stream key provided: {}", reason)),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
+        )
+        .await
+        {
+            log_debug!(
+                logger,
+                format!("Send error: Could not send status message: {}", e)
+            );
+        }
+
+        return false;
+    }
+
+    // Publish type
+
+    let publish_type = match cmd.get_argument("type") {
+        Some(t) => RtmpPublishType::parse(t.get_string()),
+        None => RtmpPublishType::Live,
+    };
+
+    if publish_type.is_recording()
+        && server_context.config.recording.publish_type_policy == PublishTypeRecordingPolicy::Reject
+    {
+        log_debug!(
+            logger,
+            format!(
+                "Command error: Publish type not allowed: {}",
+                publish_type.as_str()
+            )
+        );
+
+        if let Err(e) = send_status_message(
+            write_stream,
+            publish_stream_id,
+            "error",
+            "NetStream.Record.NoAccess",
+            Some("Recording is not allowed. Publish with the live type."),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
+        )
+        .await
+        {
+            log_debug!(
+                logger,
+                format!("Send error: Could not send status message: {}", e)
+            );
+        }
+
+        return false;
+    }
+
+    // Apply the channel aliases
+
+    let (channel, key) = match server_context.config.channel_aliases.resolve(&app, key) {
+        Some((alias_channel, alias_key)) => {
+            log_debug!(
+                logger,
+                format!(
+                    "Channel alias: {}/{} -> {}/{}",
+                    &app, REDACTED_STREAM_KEY, &alias_channel, REDACTED_STREAM_KEY
+                )
+            );
+
+            (alias_channel, alias_key)
+        }
+        None => (app, key.to_string()),
+    };
+
+    let key: &str = &key;
+
+    // Ensure the stream is not already in use
+
+    if session_context.is_stream_in_use(publish_stream_id).await {
+        log_debug!(
+            logger,
+            format!(
+                "Protocol error: Received publish command, but the stream {} is already in use",
+                publish_stream_id
+            )
+        );
+
+        if let Err(e) = send_status_message(
+            write_stream,
+            publish_stream_id,
+            "error",
+            "NetStream.Publish.BadConnection",
+            Some("Stream already in use"),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -143,7 +277,36 @@ pub async fn handle_rtmp_command_publish<
             "error",
             "NetStream.Publish.BadConnection",
             Some("Connection already publishing"),
-            server_context.config.chunk_size,
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
+        )
+        .await
+        {
+            log_debug!(
+                logger,
+                format!("Send error: Could not send status message: {}", e)
+            );
+        }
+
+        return false;
+    }
+
+    // Set the channel of the session
+
+    if !session_context.set_channel(&channel).await {
+        log_debug!(
+            logger,
+            "Protocol error: The session is already using a different channel"
+        );
+
+        if let Err(e) = send_status_message(
+            write_stream,
+            publish_stream_id,
+            "error",
+            "NetStream.Publish.BadConnection",
+            Some("Connection already using a different channel"),
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -156,9 +319,45 @@ pub async fn handle_rtmp_command_publish<
         return false;
     }
 
-    // Ensure the channel is free to publish
+    logger.set_session_channel(&channel);
+
+    server_context
+        .status
+        .traces
+        .set_session_channel(
+            logger,
+            &server_context.config.trace,
+            session_context.id,
+            &channel,
+        )
+        .await;
+
+    // Resume the stream if the publisher is reconnecting during the grace period
+
+    if resume_publisher(server_context, session_context, &channel, key).await {
+        log_info!(
+            logger,
+            format!("PUBLISH ({}) (RESUMED): {}", publish_stream_id, &channel)
+        );
+
+        return start_publishing(
+            logger,
+            server_context,
+            session_context,
+            write_stream,
+            publish_stream_id,
+            &channel,
+            key,
+        )
+        .await;
+    }
+
+    // Ensure the channel is free to publish,
+    // or it can be taken over once the new publisher is authorized
 
-    if check_channel_publishing_status(server_context, &channel).await {
+    let takeover = check_channel_publishing_status(server_context, &channel).await;
+
+    if takeover && !is_takeover_allowed(server_context, &channel, key).await {
         log_debug!(
             logger,
             "Cannot publish: Another session is already publishing on the channel"
@@ -170,7 +369,8 @@ pub async fn handle_rtmp_command_publish<
             "error",
             "NetStream.Publish.BadName",
             Some("Stream already publishing"),
-            server_context.config.chunk_size,
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -190,31 +390,131 @@ pub async fn handle_rtmp_command_publish<
         format!("PUBLISH ({}): {}", publish_stream_id, &channel)
     );
 
-    // Check validity of the key (callback or coordinator)
+    // Generate an unique ID for the publication, to correlate its events
 
-    let stream_id_res = match &server_context.control_key_validator_sender {
-        Some(control_key_validator_sender_v) => {
-            control_validate_key(
-                control_key_validator_sender_v,
-                &channel,
-                key,
-                &session_context.ip,
-            )
-            .await
+    let publish_id = generate_uuid_v4();
+
+    log_debug!(logger, format!("Publish ID: {}", &publish_id));
+
+    // Check validity of the key (token, key store, callback or coordinator)
+
+    let authorization_res = if server_context
+        .config
+        .get_auth_token_config(&channel)
+        .is_enabled()
+    {
+        match validate_stream_token(
+            server_context.config.get_auth_token_config(&channel),
+            &query_params,
+            &channel,
+            key,
+            StreamTokenRole::Publish,
+        ) {
+            Ok(grant) => Some(PublishAuthorization {
+                stream_id: grant.stream_id.unwrap_or_else(|| key.to_string()),
+                max_players: grant.max_players,
+                flags: None,
+            }),
+            Err(e) => {
+                log_debug!(logger, format!("Invalid publish token: {}", e));
+                None
+            }
         }
-        None => {
-            make_start_callback(
-                logger,
-                &server_context.config.callback,
-                &channel,
-                key,
-                &session_context.ip,
-            )
+    } else if server_context.status.key_store.is_enabled() {
+        match server_context
+            .status
+            .key_store
+            .validate(&channel, key)
             .await
+        {
+            Some(key_id) => Some(PublishAuthorization {
+                stream_id: key_id,
+                max_players: None,
+                flags: None,
+            }),
+            None => {
+                log_debug!(logger, "Key not found in the key store");
+                None
+            }
+        }
+    } else {
+        let control_res = match &server_context.control_key_validator_sender {
+            Some(control_key_validator_sender_v) => Some(
+                control_validate_key(
+                    control_key_validator_sender_v,
+                    &channel,
+                    key,
+                    session_context.id,
+                    &publish_id,
+                    &session_context.publisher_info().await,
+                    session_context.client_cert.as_deref(),
+                    &query_params,
+                )
+                .await,
+            ),
+            None => None,
+        };
+
+        match control_res {
+            Some(ControlKeyValidationResult::Accepted(authorization)) => Some(authorization),
+            Some(ControlKeyValidationResult::Rejected) => None,
+            Some(ControlKeyValidationResult::Fallback) | None => {
+                let callback_config = server_context.config.get_callback_config(&channel);
+
+                let cached_res = if callback_config.cache_seconds > 0 {
+                    server_context
+                        .status
+                        .callback_cache
+                        .get(&channel, key, &session_context.ip)
+                        .await
+                } else {
+                    None
+                };
+
+                match cached_res {
+                    Some(res) => {
+                        log_debug!(logger, "Using cached start callback response");
+
+                        res
+                    }
+                    None => {
+                        let res = make_start_callback(
+                            logger,
+                            callback_config,
+                            &channel,
+                            key,
+                            &CallbackCorrelation {
+                                session_id: session_context.id,
+                                publish_id: publish_id.clone(),
+                            },
+                            &session_context.ip,
+                            session_context.client_cert.as_deref(),
+                            session_context.client_geo.as_deref(),
+                            &query_params,
+                            publish_type,
+                        )
+                        .await;
+
+                        server_context
+                            .status
+                            .callback_cache
+                            .set(
+                                &channel,
+                                key,
+                                &session_context.ip,
+                                res.clone(),
+                                callback_config.cache_seconds,
+                            )
+                            .await;
+
+                        res
+                    }
+                }
+            }
         }
     };
 
-    let stream_id = match stream_id_res {
+    let authorization = match authorization_res {
         Some(s) => s,
         None => {
             if let Err(e) = send_status_message(
@@ -223,7 +523,8 @@ pub async fn handle_rtmp_command_publish<
                 "error",
                 "NetStream.Publish.BadName",
                 Some("Invalid stream key provided"),
-                server_context.config.chunk_size,
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
             )
             .await
             {
@@ -237,9 +538,64 @@ pub async fn handle_rtmp_command_publish<
         }
     };
 
-    // Set publisher into the server status
+    // Parse the flags of the stream
 
-    if !set_publisher(server_context, session_context, &channel, key, &stream_id).await {
+    let flags = match &authorization.flags {
+        Some(f) => match PublishStreamFlags::parse(f) {
+            Ok(flags) => flags,
+            Err(e) => {
+                log_warning!(logger, format!("Ignored invalid stream flags: {}", e));
+
+                PublishStreamFlags::default()
+            }
+        },
+        None => PublishStreamFlags::default(),
+    };
+
+    // End the stream waiting for its publisher to resume it, if any
+
+    end_publisher_resume(server_context, &channel).await;
+
+    // Set publisher into the server status.
+    // If taking over the channel, the existing publisher is replaced at the same time.
+
+    let published = if takeover
+        && takeover_publisher(
+            logger,
+            server_context,
+            session_context,
+            &channel,
+            key,
+            &publish_id,
+            &authorization,
+            &flags,
+            "Publisher takeover",
+        )
+        .await
+    {
+        log_info!(
+            logger,
+            format!(
+                "Publisher takeover: Replaced the existing publisher of {}",
+                &channel
+            )
+        );
+
+        true
+    } else {
+        set_publisher(
+            server_context,
+            session_context,
+            &channel,
+            key,
+            &publish_id,
+            &authorization,
+            &flags,
+        )
+        .await
+    };
+
+    if !published {
         log_debug!(
             logger,
             "Cannot publish: Another session is already publishing on the channel"
@@ -251,7 +607,8 @@ pub async fn handle_rtmp_command_publish<
             "error",
             "NetStream.Publish.BadName",
             Some("Stream already publishing"),
-            server_context.config.chunk_size,
+            session_context.object_encoding(),
+            session_context.out_chunk_size(),
         )
         .await
         {
@@ -264,19 +621,116 @@ pub async fn handle_rtmp_command_publish<
         return false;
     }
 
+    // Announce the channel to the other nodes of the cluster
+
+    if let Some(cluster) = &server_context.cluster {
+        cluster.register_channel(logger, &channel).await;
+    }
+
+    if !start_publishing(
+        logger,
+        server_context,
+        session_context,
+        write_stream,
+        publish_stream_id,
+        &channel,
+        key,
+    )
+    .await
+    {
+        return false;
+    }
+
+    // Start recording, if enabled by the stream flags or requested by the publisher
+
+    let record_requested = publish_type.is_recording()
+        && server_context.config.recording.publish_type_policy
+            == PublishTypeRecordingPolicy::Record;
+
+    if flags.is_record_enabled() || record_requested {
+        if let Err(e) = start_recording(logger, server_context, &channel).await {
+            log_warning!(
+                logger,
+                format!("Channel {}: Could not start recording: {}", &channel, e)
+            );
+        }
+    }
+
+    true
+}
+
+/// Checks if the takeover policy allows a new publisher
+/// to replace the existing publisher of a channel.
+/// The replacement only happens after the new publisher is authorized.
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `channel` - The channel
+/// * `key` - The stream key provided by the new publisher
+///
+/// # Return value
+///
+/// Returns true if the existing publisher can be replaced
+async fn is_takeover_allowed(server_context: &RtmpServerContext, channel: &str, key: &str) -> bool {
+    if server_context.config.publish_takeover != PublishTakeoverPolicy::KickExisting {
+        return false;
+    }
+
+    // Only allowed for the same key, so the new publisher is the same encoder reconnecting
+
+    check_channel_publisher_key(server_context, channel, key).await
+}
+
+/// Sets the session as publisher and notifies the client, once the publisher is set
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `write_stream` - The stream to write to the client
+/// * `publish_stream_id` - ID of the RTMP stream used to publish
+/// * `channel` - The channel
+/// * `key` - The stream key
+///
+/// # Return value
+///
+/// Returns true to continue receiving chunks. Returns false to end the session main loop.
+async fn start_publishing<TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static>(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &mut SessionReadThreadContext,
+    write_stream: &Mutex<TW>,
+    publish_stream_id: u32,
+    channel: &str,
+    key: &str,
+) -> bool {
     // Set publishing status to the session status
 
     session_context.set_publisher(publish_stream_id).await;
 
-    // Respond with status message
+    // Respond with status message, advising the max interval between key frames if enabled
+
+    let mut description = format!("/{}/{} is now published.", channel, key);
+
+    if server_context.config.keyframe_interval_advice
+        && server_context.config.keyframe_interval_warning_ms > 0
+    {
+        description.push_str(&format!(
+            " Recommended max keyframe interval: {} ms.",
+            server_context.config.keyframe_interval_warning_ms
+        ));
+    }
 
     if let Err(e) = send_status_message(
         write_stream,
         publish_stream_id,
         "status",
         "NetStream.Publish.Start",
-        Some(&format!("/{}/{} is now published.", channel, key)),
-        server_context.config.chunk_size,
+        Some(&description),
+        session_context.object_encoding(),
+        session_context.out_chunk_size(),
     )
     .await
     {
@@ -286,6 +740,10 @@ pub async fn handle_rtmp_command_publish<
         );
     }
 
+    // Send the statistics of the channel to the publisher, if enabled
+
+    spawn_task_send_publish_statistics(server_context, session_context);
+
     // Done
 
     true
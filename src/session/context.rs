@@ -4,9 +4,15 @@ use std::{net::IpAddr, sync::Arc};
 
 use tokio::sync::{mpsc::Sender, Mutex};
 
+use crate::{
+    geoip::GeoIpInfo, server::RtmpPublisherInfo, trace::SessionTraceTap, utils::CertificateIdentity,
+};
+
 use super::{
-    RtmpSessionMessage, RtmpSessionPlayStatus, RtmpSessionPublishStreamStatus,
-    RtmpSessionReadStatus, RtmpSessionStatus,
+    PublishCongestionDetector, RtmpSessionMessage, RtmpSessionPlayStatus,
+    RtmpSessionPublishStreamStatus, RtmpSessionReadStatus, RtmpSessionStatus,
+    RtmpSessionStreamRole, SessionBandwidth, SessionObjectEncoding, SessionOutChunkSize,
+    SessionOutboundAck, SessionPanicTracker, SessionPlayerLag,
 };
 
 /// Session context
@@ -23,9 +29,46 @@ pub struct SessionContext {
 
     /// Publishing status
     pub publish_status: Arc<Mutex<RtmpSessionPublishStreamStatus>>,
+
+    /// Bandwidth usage
+    pub bandwidth: Arc<SessionBandwidth>,
+
+    /// Diagnostics of the delivery of the stream, when playing
+    pub player_lag: Arc<SessionPlayerLag>,
+
+    /// Trace tap
+    pub trace: Arc<SessionTraceTap>,
+
+    /// Outbound chunk size
+    pub out_chunk_size: Arc<SessionOutChunkSize>,
+
+    /// Acknowledgements of the bytes sent to the client
+    pub outbound_ack: Arc<SessionOutboundAck>,
+
+    /// Object encoding negotiated with the client
+    pub object_encoding: Arc<SessionObjectEncoding>,
+
+    /// Identity of the client certificate (if provided)
+    pub client_cert: Option<Arc<CertificateIdentity>>,
+
+    /// Geolocation of the client IP address (if available)
+    pub client_geo: Option<Arc<GeoIpInfo>>,
+
+    /// Activity of the session, tracked to report its panics
+    pub panic_tracker: Arc<SessionPanicTracker>,
 }
 
 impl SessionContext {
+    /// Gets the chunk size to send packets to the client
+    pub fn out_chunk_size(&self) -> usize {
+        self.out_chunk_size.get()
+    }
+
+    /// Gets the object encoding to send commands to the client
+    pub fn object_encoding(&self) -> u32 {
+        self.object_encoding.get()
+    }
+
     /// Sets the session as killed
     pub async fn set_killed(&self) {
         let mut status = self.status.lock().await;
@@ -37,10 +80,12 @@ impl SessionContext {
     ///
     /// # Return value
     ///
-    /// Returns the current player status of the session
-    pub async fn play_status(&self) -> RtmpSessionPlayStatus {
+    /// Returns None if the session is not a player.
+    /// Otherwise, returns the ID of the internal RTMP stream used to play and the current player options.
+    pub async fn play_status(&self) -> Option<(u32, RtmpSessionPlayStatus)> {
         let status = self.status.lock().await;
-        status.play_status.clone()
+        let play_stream_id = status.play_stream_id()?;
+        Some((play_stream_id, status.play_status.clone()))
     }
 
     /// Checks the play status of a session
@@ -52,16 +97,26 @@ impl SessionContext {
     ///  2. If the session is a player, the ID of the internal RTMP stream used to play
     pub async fn play_stream_id(&self) -> (bool, u32) {
         let status = self.status.lock().await;
-        (
-            status.play_status.is_player,
-            status.play_status.play_stream_id,
-        )
+        match status.play_stream_id() {
+            Some(id) => (true, id),
+            None => (false, 0),
+        }
+    }
+
+    /// Gets the ID of the internal RTMP stream used to publish
+    /// Returns None if the session is not a publisher
+    pub async fn publish_stream_id(&self) -> Option<u32> {
+        let status = self.status.lock().await;
+        status.publish_stream_id()
     }
 
     /// Sets the playing status to false
+    /// The stream used for playing remains created, so it can be used again
     pub async fn stop_playing(&self) {
         let mut status_v = self.status.lock().await;
-        status_v.play_status.is_player = false;
+        if let Some(id) = status_v.play_stream_id() {
+            status_v.set_stream_role(id, RtmpSessionStreamRole::Idle);
+        }
     }
 }
 
@@ -79,32 +134,129 @@ pub struct SessionReadThreadContext {
     /// Publishing status
     pub publish_status: Arc<Mutex<RtmpSessionPublishStreamStatus>>,
 
+    /// Bandwidth usage
+    pub bandwidth: Arc<SessionBandwidth>,
+
+    /// Diagnostics of the delivery of the stream, when playing
+    pub player_lag: Arc<SessionPlayerLag>,
+
+    /// Trace tap
+    pub trace: Arc<SessionTraceTap>,
+
+    /// Outbound chunk size
+    pub out_chunk_size: Arc<SessionOutChunkSize>,
+
+    /// Acknowledgements of the bytes sent to the client
+    pub outbound_ack: Arc<SessionOutboundAck>,
+
+    /// Object encoding negotiated with the client
+    pub object_encoding: Arc<SessionObjectEncoding>,
+
+    /// Identity of the client certificate (if provided)
+    pub client_cert: Option<Arc<CertificateIdentity>>,
+
+    /// Geolocation of the client IP address (if available)
+    pub client_geo: Option<Arc<GeoIpInfo>>,
+
+    /// Activity of the session, tracked to report its panics
+    pub panic_tracker: Arc<SessionPanicTracker>,
+
     /// Sender for session messages
     pub session_msg_sender: Sender<RtmpSessionMessage>,
 
     /// Read status
     pub read_status: RtmpSessionReadStatus,
+
+    /// Detector of the congestion, when publishing
+    pub publish_congestion: PublishCongestionDetector,
 }
 
 impl SessionReadThreadContext {
+    /// Gets the chunk size to send packets to the client
+    pub fn out_chunk_size(&self) -> usize {
+        self.out_chunk_size.get()
+    }
+
+    /// Gets the object encoding to send commands to the client
+    pub fn object_encoding(&self) -> u32 {
+        self.object_encoding.get()
+    }
+
     /// Gets the current channel of the session
     pub async fn channel(&self) -> Option<String> {
         let status = self.status.lock().await;
         status.channel.clone()
     }
 
+    /// Gets the channel requested by the session on connect (RTMP app)
+    pub async fn app(&self) -> Option<String> {
+        let status = self.status.lock().await;
+        status.app.clone()
+    }
+
+    /// Gets the information of the session as a publisher
+    /// (IP address and client information sent on connect)
+    pub async fn publisher_info(&self) -> RtmpPublisherInfo {
+        let status = self.status.lock().await;
+
+        RtmpPublisherInfo {
+            ip: self.ip,
+            connect_info: status.connect_info.clone(),
+        }
+    }
+
+    /// Sets the current channel of the session,
+    /// after applying the channel aliases
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel
+    ///
+    /// # Return value
+    ///
+    /// Returns false if the session is already publishing or playing on a different channel
+    pub async fn set_channel(&self, channel: &str) -> bool {
+        let mut status = self.status.lock().await;
+
+        if status.channel.as_deref() == Some(channel) {
+            return true;
+        }
+
+        if status.is_publisher() || status.is_player() {
+            return false;
+        }
+
+        status.channel = Some(channel.to_string());
+
+        true
+    }
+
     /// Checks if the session is a publisher
     pub async fn is_publisher(&self) -> bool {
         let status = self.status.lock().await;
 
-        status.is_publisher
+        status.is_publisher()
     }
 
     /// Checks if the session is a player
     pub async fn is_player(&self) -> bool {
         let status = self.status.lock().await;
 
-        status.play_status.is_player
+        status.is_player()
+    }
+
+    /// Checks if a RTMP stream of the session is being used to play or publish
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    pub async fn is_stream_in_use(&self, stream_id: u32) -> bool {
+        let status = self.status.lock().await;
+
+        matches!(
+            status.stream_role(stream_id),
+            Some(RtmpSessionStreamRole::Playing) | Some(RtmpSessionStreamRole::Publishing)
+        )
     }
 
     /// Checks if the session is killed
@@ -122,8 +274,7 @@ impl SessionReadThreadContext {
     pub async fn set_publisher(&self, publish_stream_id: u32) {
         let mut status = self.status.lock().await;
 
-        status.is_publisher = true;
-        status.publish_stream_id = publish_stream_id;
+        status.set_stream_role(publish_stream_id, RtmpSessionStreamRole::Publishing);
     }
 
     /// Updates session status for playing
@@ -132,18 +283,27 @@ impl SessionReadThreadContext {
     ///
     /// * `receive_gop` - True for the player to receive packets from the GOP cache, false to receive only live packets
     /// * `play_stream_id` - ID of the internal RTMP stream used for playing
+    /// * `duration` - Max duration of the playback (milliseconds), None for unlimited
+    /// * `reset` - True to send the NetStream.Play.Reset status on play start
     ///
     /// # Return value
     ///
     /// Returns a tuple with 2 values:
     ///  1. The receive_audio setting (True to receive audio packets, false to ignore them)
     ///  2. The receive_video setting (True to receive video packets, false to ignore them)
-    pub async fn set_player(&self, receive_gop: bool, play_stream_id: u32) -> (bool, bool) {
+    pub async fn set_player(
+        &self,
+        receive_gop: bool,
+        play_stream_id: u32,
+        duration: Option<i64>,
+        reset: bool,
+    ) -> (bool, bool) {
         let mut status = self.status.lock().await;
 
-        status.play_status.is_player = true;
+        status.set_stream_role(play_stream_id, RtmpSessionStreamRole::Playing);
         status.play_status.receive_gop = receive_gop;
-        status.publish_stream_id = play_stream_id;
+        status.play_status.duration = duration;
+        status.play_status.reset = reset;
 
         (
             status.play_status.receive_audio,
@@ -151,6 +311,15 @@ impl SessionReadThreadContext {
         )
     }
 
+    /// Clears the publishing status of the read thread,
+    /// once the stream used for publishing is closed,
+    /// so the session can publish again
+    pub fn end_publishing(&mut self) {
+        self.read_status.channel_status = None;
+        self.publish_congestion.reset();
+        self.publish_status = Arc::new(Mutex::new(RtmpSessionPublishStreamStatus::new()));
+    }
+
     /// Sets the clock value for the publish status
     ///
     /// # Arguments
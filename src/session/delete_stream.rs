@@ -11,9 +11,14 @@ use crate::{
     server::{remove_player, remove_publisher, try_clear_channel, RtmpServerContext},
 };
 
-use super::{send_status_message, SessionReadThreadContext};
+use super::{
+    send_status_message, RtmpSessionMessage, RtmpSessionStreamRole, SessionReadThreadContext,
+};
 
-/// Deletes RTMP stream
+/// Closes or deletes RTMP stream
+///
+/// Only the indicated stream is affected: the session keeps
+/// playing or publishing with any other stream.
 ///
 /// # Arguments
 ///
@@ -21,7 +26,8 @@ use super::{send_status_message, SessionReadThreadContext};
 /// * `server_context` - The server context
 /// * `session_context` - The session context
 /// * `write_stream` - The stream to write to the client
-/// * `stream_id` - ID of the RTMP stream to delete
+/// * `stream_id` - ID of the RTMP stream to close
+/// * `delete` - True to delete the stream (deleteStream), false to keep it, so it can be used again (closeStream)
 ///
 /// # Return value
 ///
@@ -32,6 +38,7 @@ pub async fn rtmp_delete_stream<TW: AsyncWrite + AsyncWriteExt + Send + Sync + U
     session_context: &mut SessionReadThreadContext,
     write_stream: &Mutex<TW>,
     stream_id: u32,
+    delete: bool,
 ) -> bool {
     let mut session_status_v = session_context.status.lock().await;
 
@@ -52,71 +59,75 @@ pub async fn rtmp_delete_stream<TW: AsyncWrite + AsyncWriteExt + Send + Sync + U
         None => "".to_string(),
     };
 
-    let can_clear_player = session_status_v.play_status.is_player;
-    let can_clear_publisher = session_status_v.is_publisher;
-
-    let is_play_stream = stream_id == session_status_v.play_status.play_stream_id;
+    let role = if delete {
+        session_status_v.remove_stream(stream_id)
+    } else {
+        let role = session_status_v.stream_role(stream_id);
 
-    if is_play_stream {
-        session_status_v.play_status.play_stream_id = 0;
-    }
-
-    let is_publish_stream = stream_id == session_status_v.publish_stream_id;
+        if role.is_some() {
+            session_status_v.set_stream_role(stream_id, RtmpSessionStreamRole::Idle);
+        }
 
-    if is_publish_stream {
-        session_status_v.publish_stream_id = 0;
-    }
+        role
+    };
 
     drop(session_status_v);
 
-    if is_play_stream {
-        log_info!(logger, "PLAY STOP");
-
-        if let Err(e) = send_status_message(
-            write_stream,
-            stream_id,
-            "status",
-            "NetStream.Play.Stop",
-            Some("Stopped playing stream."),
-            server_context.config.chunk_size,
-        )
-        .await
-        {
-            log_debug!(
-                logger,
-                format!("Send error: Could not send status message: {}", e)
-            );
-        }
+    match role {
+        Some(RtmpSessionStreamRole::Playing) => {
+            log_info!(logger, format!("PLAY STOP ({})", stream_id));
 
-        if can_clear_player {
             remove_player(server_context, &channel, session_context.id).await;
             try_clear_channel(server_context, &channel).await;
+
+            // The status is sent by the messages task, after the last packet sent to the player
+
+            _ = session_context
+                .session_msg_sender
+                .send(RtmpSessionMessage::PlayEnd { stream_id })
+                .await;
         }
-    }
+        Some(RtmpSessionStreamRole::Publishing) => {
+            log_info!(logger, format!("PUBLISH END ({})", stream_id));
+
+            remove_publisher(
+                logger,
+                server_context,
+                &channel,
+                session_context.id,
+                &key,
+                false,
+                None,
+            )
+            .await;
+            try_clear_channel(server_context, &channel).await;
 
-    if is_publish_stream {
-        log_info!(logger, "PUBLISH END");
-
-        if let Err(e) = send_status_message(
-            write_stream,
-            stream_id,
-            "status",
-            "NetStream.Unpublish.Success",
-            Some(&format!("/{}/{} is now unpublished.", channel, key)),
-            server_context.config.chunk_size,
-        )
-        .await
-        {
+            session_context.end_publishing();
+
+            if let Err(e) = send_status_message(
+                write_stream,
+                stream_id,
+                "status",
+                "NetStream.Unpublish.Success",
+                Some(&format!("/{}/{} is now unpublished.", channel, key)),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            )
+            .await
+            {
+                log_debug!(
+                    logger,
+                    format!("Send error: Could not send status message: {}", e)
+                );
+            }
+        }
+        Some(RtmpSessionStreamRole::Idle) => {}
+        None => {
             log_debug!(
                 logger,
-                format!("Send error: Could not send status message: {}", e)
+                format!("Command error: Stream {} was not created", stream_id)
             );
         }
-
-        if can_clear_publisher {
-            remove_publisher(logger, server_context, &channel, session_context.id).await;
-            try_clear_channel(server_context, &channel).await;
-        }
     }
 
     true
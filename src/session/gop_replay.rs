@@ -0,0 +1,305 @@
+// Replay of the GOP cache to the players joining a stream
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+
+use crate::rtmp::{RtmpPacket, RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO};
+
+/// Characteristics of the players that can share a serialized GOP cache
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GopReplayKey {
+    /// The stream ID of the player
+    pub stream_id: u32,
+
+    /// The output chunk size of the player
+    pub chunk_size: usize,
+
+    /// True if the player receives the audio packets
+    pub receive_audio: bool,
+
+    /// True if the player receives the video packets
+    pub receive_video: bool,
+}
+
+impl GopReplayKey {
+    /// Checks if the player receives a packet
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet
+    fn receives(&self, packet: &RtmpPacket) -> bool {
+        match packet.header.packet_type {
+            RTMP_TYPE_AUDIO => self.receive_audio,
+            RTMP_TYPE_VIDEO => self.receive_video,
+            _ => true,
+        }
+    }
+}
+
+/// GOP cache serialized for the players with the same characteristics
+struct GopReplayBlob {
+    /// Generation of the GOP cache when serialized
+    generation: u64,
+
+    /// First packet of the GOP cache when serialized
+    first_packet: Arc<RtmpPacket>,
+
+    /// Last packet of the GOP cache when serialized
+    last_packet: Arc<RtmpPacket>,
+
+    /// Number of serialized packets
+    packet_count: usize,
+
+    /// The serialized packets
+    bytes: Arc<Vec<u8>>,
+}
+
+/// Cache of the GOP cache of a stream, serialized,
+/// so it is shared by the players joining the stream
+/// instead of serializing it for each one of them
+pub struct GopReplayBlobCache {
+    /// Generation of the GOP cache, increased each time
+    /// packets are removed from it
+    generation: AtomicU64,
+
+    /// Serialized GOP cache
+    blobs: Mutex<HashMap<GopReplayKey, GopReplayBlob>>,
+}
+
+impl GopReplayBlobCache {
+    /// Creates new GopReplayBlobCache
+    pub fn new() -> GopReplayBlobCache {
+        GopReplayBlobCache {
+            generation: AtomicU64::new(0),
+            blobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Invalidates the serialized GOP cache.
+    /// Call it when packets are removed from the GOP cache (cleared or rotated).
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Gets the serialized GOP cache, serializing it if not cached.
+    /// If the cached one is a prefix of the GOP cache
+    /// (packets were added since), only the new packets are serialized.
+    ///
+    /// # Arguments
+    ///
+    /// * `gop_cache` - The packets of the GOP cache
+    /// * `key` - Characteristics of the player
+    pub async fn get_blob(&self, gop_cache: &[Arc<RtmpPacket>], key: GopReplayKey) -> Arc<Vec<u8>> {
+        let (first_packet, last_packet) = match (gop_cache.first(), gop_cache.last()) {
+            (Some(f), Some(l)) => (f, l),
+            _ => {
+                return Arc::new(Vec::new());
+            }
+        };
+
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        let mut blobs = self.blobs.lock().await;
+
+        // Release the blobs of previous generations
+
+        blobs.retain(|_, b| b.generation == generation);
+
+        let (mut bytes, serialized_count) = match blobs.get(&key) {
+            Some(blob)
+                if Arc::ptr_eq(&blob.first_packet, first_packet)
+                    && blob.packet_count <= gop_cache.len()
+                    && Arc::ptr_eq(&blob.last_packet, &gop_cache[blob.packet_count - 1]) =>
+            {
+                if blob.packet_count == gop_cache.len() {
+                    return blob.bytes.clone();
+                }
+
+                (blob.bytes.as_ref().clone(), blob.packet_count)
+            }
+            _ => (Vec::new(), 0),
+        };
+
+        for packet in &gop_cache[serialized_count..] {
+            if key.receives(packet) {
+                bytes.extend(packet.create_chunks_for_stream(key.stream_id, key.chunk_size));
+            }
+        }
+
+        let bytes = Arc::new(bytes);
+
+        blobs.insert(
+            key,
+            GopReplayBlob {
+                generation,
+                first_packet: first_packet.clone(),
+                last_packet: last_packet.clone(),
+                packet_count: gop_cache.len(),
+                bytes: bytes.clone(),
+            },
+        );
+
+        bytes
+    }
+}
+
+/// Limiter of the concurrent GOP cache replays,
+/// to absorb the players joining a stream at the same time
+pub struct GopReplayLimiter {
+    /// Permits for the replays (None for unlimited)
+    semaphore: Option<Semaphore>,
+
+    /// Max time to wait for a permit
+    queue_timeout: Duration,
+}
+
+impl GopReplayLimiter {
+    /// Creates new GopReplayLimiter
+    ///
+    /// # Arguments
+    ///
+    /// * `max_concurrent` - Max number of concurrent replays (0 for unlimited)
+    /// * `queue_timeout_ms` - Max time to wait for a replay to start (milliseconds)
+    pub fn new(max_concurrent: usize, queue_timeout_ms: u64) -> GopReplayLimiter {
+        GopReplayLimiter {
+            semaphore: if max_concurrent > 0 {
+                Some(Semaphore::new(max_concurrent))
+            } else {
+                None
+            },
+            queue_timeout: Duration::from_millis(queue_timeout_ms),
+        }
+    }
+
+    /// Waits for a replay to be allowed
+    ///
+    /// # Return value
+    ///
+    /// Returns the permit (None if unlimited), to keep while replaying,
+    /// or an error if the wait timed out
+    pub async fn acquire(&self) -> Result<Option<SemaphorePermit<'_>>, ()> {
+        let semaphore = match &self.semaphore {
+            Some(s) => s,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        match tokio::time::timeout(self.queue_timeout, semaphore.acquire()).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            _ => Err(()),
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_packet(packet_type: u32, size: usize) -> Arc<RtmpPacket> {
+        let mut packet = RtmpPacket::new_blank();
+
+        packet.header.packet_type = packet_type;
+        packet.header.length = size;
+        packet.payload = vec![1; size];
+
+        Arc::new(packet)
+    }
+
+    fn serialize(gop_cache: &[Arc<RtmpPacket>], key: GopReplayKey) -> Vec<u8> {
+        gop_cache
+            .iter()
+            .filter(|p| key.receives(p))
+            .flat_map(|p| p.create_chunks_for_stream(key.stream_id, key.chunk_size))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_gop_replay_blob_cache() {
+        let cache = GopReplayBlobCache::new();
+
+        let key = GopReplayKey {
+            stream_id: 1,
+            chunk_size: 128,
+            receive_audio: true,
+            receive_video: true,
+        };
+
+        let mut gop_cache = vec![
+            make_packet(RTMP_TYPE_VIDEO, 10),
+            make_packet(RTMP_TYPE_AUDIO, 200),
+        ];
+
+        let blob = cache.get_blob(&gop_cache, key).await;
+
+        assert_eq!(*blob, serialize(&gop_cache, key));
+        assert!(Arc::ptr_eq(&blob, &cache.get_blob(&gop_cache, key).await));
+
+        // Extended with the new packets
+
+        gop_cache.push(make_packet(RTMP_TYPE_VIDEO, 50));
+
+        let blob = cache.get_blob(&gop_cache, key).await;
+
+        assert_eq!(*blob, serialize(&gop_cache, key));
+
+        // Players not receiving audio
+
+        let video_key = GopReplayKey {
+            receive_audio: false,
+            ..key
+        };
+
+        let video_blob = cache.get_blob(&gop_cache, video_key).await;
+
+        assert_eq!(*video_blob, serialize(&gop_cache, video_key));
+        assert_ne!(*video_blob, *blob);
+
+        // Rotated
+
+        gop_cache.remove(0);
+        cache.invalidate();
+
+        let blob = cache.get_blob(&gop_cache, key).await;
+
+        assert_eq!(*blob, serialize(&gop_cache, key));
+        assert_eq!(cache.blobs.lock().await.len(), 1);
+
+        // New GOP
+
+        let gop_cache = vec![make_packet(RTMP_TYPE_VIDEO, 20)];
+
+        let blob = cache.get_blob(&gop_cache, key).await;
+
+        assert_eq!(*blob, serialize(&gop_cache, key));
+    }
+
+    #[tokio::test]
+    async fn test_gop_replay_limiter() {
+        let limiter = GopReplayLimiter::new(1, 10);
+
+        let permit = limiter.acquire().await.unwrap();
+
+        assert!(permit.is_some());
+        assert!(limiter.acquire().await.is_err());
+
+        drop(permit);
+
+        assert!(limiter.acquire().await.is_ok());
+        assert!(GopReplayLimiter::new(0, 10)
+            .acquire()
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -10,20 +10,20 @@ use tokio::{
 use crate::{
     log::Logger,
     log_debug, log_error,
-    rtmp::{generate_s0_s1_s2, RTMP_HANDSHAKE_SIZE, RTMP_PING_TIMEOUT, RTMP_VERSION},
+    rtmp::{
+        generate_s0_s1_s2, RtmpInputPacketBuffer, RTMP_HANDSHAKE_SIZE, RTMP_PING_TIMEOUT,
+        RTMP_VERSION,
+    },
     server::RtmpServerContext,
     session::read_rtmp_chunk,
 };
 
 use super::{
-    session_write_bytes, spawn_task_to_read_session_messages, spawn_task_to_send_pings,
-    RtmpPacketWrapper, RtmpSessionMessage, RtmpSessionReadStatus, SessionContext,
-    SessionReadThreadContext,
+    catch_session_panic, session_write_bytes, spawn_task_to_read_session_messages,
+    spawn_task_to_send_pings, PublishCongestionDetector, RtmpSessionMessage, RtmpSessionReadStatus,
+    SessionCleanupGuard, SessionContext, SessionReadThreadContext,
 };
 
-/// Size if the buffer to store input packets
-pub const IN_PACKETS_BUFFER_SIZE: usize = 4;
-
 /// Handles RTMP session
 ///
 /// # Arguments
@@ -119,8 +119,14 @@ pub async fn handle_rtmp_session<
 
     let handshake_response = match generate_s0_s1_s2(&client_signature, &logger) {
         Ok(r) => r,
-        Err(()) => {
-            log_error!(logger, "BAD HANDSHAKE: Could not generate handshake response [Note: This is probably a server bug]");
+        Err(e) => {
+            log_debug!(
+                logger,
+                format!(
+                    "BAD HANDSHAKE: Could not generate handshake response: {}",
+                    e
+                )
+            );
             return;
         }
     };
@@ -162,6 +168,20 @@ pub async fn handle_rtmp_session<
 
     log_debug!(logger, "Handshake successful. Entering main loop...");
 
+    // Register the session, so it can be traced
+
+    server_context
+        .status
+        .traces
+        .register_session(
+            &logger,
+            &server_context.config.trace,
+            session_context.id,
+            session_context.ip,
+            session_context.trace.clone(),
+        )
+        .await;
+
     ////////////////////
     //    Main loop   //
     ////////////////////
@@ -181,22 +201,28 @@ pub async fn handle_rtmp_session<
         msg_receiver,
     );
 
+    // Make sure the session is cleaned up if the messages task cannot do it
+
+    let cleanup_guard = SessionCleanupGuard::new(
+        logger.clone(),
+        server_context.clone(),
+        session_context.clone(),
+    );
+
     // Create task to send ping requests
 
     let (cancel_pings_sender, cancel_pings_receiver) = tokio::sync::mpsc::channel::<()>(1);
 
     spawn_task_to_send_pings(
         logger.clone(),
-        server_context.clone(),
         session_context.clone(),
         write_stream.clone(),
         cancel_pings_receiver,
     );
 
-    // Create array of input packets
+    // Create buffer of input packets
 
-    let mut in_packets: [RtmpPacketWrapper; IN_PACKETS_BUFFER_SIZE] =
-        std::array::from_fn(|_| RtmpPacketWrapper::new());
+    let mut in_packets = RtmpInputPacketBuffer::new(server_context.config.max_chunk_streams);
 
     // Prepare read thread context
 
@@ -205,31 +231,85 @@ pub async fn handle_rtmp_session<
         ip: session_context.ip,
         status: session_context.status,
         publish_status: session_context.publish_status,
+        bandwidth: session_context.bandwidth,
+        player_lag: session_context.player_lag,
+        trace: session_context.trace,
+        out_chunk_size: session_context.out_chunk_size,
+        outbound_ack: session_context.outbound_ack,
+        object_encoding: session_context.object_encoding,
+        client_cert: session_context.client_cert,
+        client_geo: session_context.client_geo,
+        panic_tracker: session_context.panic_tracker,
         session_msg_sender: msg_sender,
         read_status: RtmpSessionReadStatus::new(),
+        publish_congestion: PublishCongestionDetector::new(
+            server_context.config.publish_bitrate_cap_kbps,
+            server_context.config.publish_max_lag_ms,
+            server_context.config.publish_congestion_seconds,
+        ),
     };
 
-    // Read chunks
-
-    let mut continue_loop = true;
-
-    while continue_loop {
-        continue_loop = read_rtmp_chunk(
-            &logger,
-            &mut server_context,
-            &mut read_thread_context,
-            &mut read_stream,
-            &write_stream,
-            &mut in_packets,
+    // Read chunks.
+    // A panic ends the loop, so the session is cleaned up.
+
+    let session_status = read_thread_context.status.clone();
+    let panic_tracker = read_thread_context.panic_tracker.clone();
+
+    _ = catch_session_panic(
+        &logger,
+        "read",
+        read_thread_context.id,
+        &session_status,
+        &panic_tracker,
+        async {
+            let mut continue_loop = true;
+
+            while continue_loop {
+                continue_loop = read_rtmp_chunk(
+                    &logger,
+                    &mut server_context,
+                    &mut read_thread_context,
+                    &mut read_stream,
+                    &write_stream,
+                    &mut in_packets,
+                )
+                .await;
+            }
+        },
+    )
+    .await;
+
+    log_debug!(
+        logger,
+        format!(
+            "Session ended. Bytes received: {} | Bytes sent: {}",
+            read_thread_context.bandwidth.input.total_bytes(),
+            read_thread_context.bandwidth.output.total_bytes()
         )
+    );
+
+    server_context
+        .status
+        .traces
+        .unregister_session(&logger, read_thread_context.id)
         .await;
-    }
 
     // End of loop, make sure all the tasks end
 
     _ = cancel_pings_sender.send(()).await;
-    _ = read_thread_context
+
+    // The messages task performs the session cleanup.
+    // If it already ended, perform it here, since the session
+    // may have joined a channel after that.
+
+    if read_thread_context
         .session_msg_sender
         .send(RtmpSessionMessage::End)
-        .await;
+        .await
+        .is_ok()
+    {
+        cleanup_guard.disarm();
+    } else {
+        cleanup_guard.cleanup().await;
+    }
 }
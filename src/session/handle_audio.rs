@@ -4,12 +4,15 @@ use std::sync::Arc;
 
 use crate::{
     log::Logger,
-    log_debug, log_trace,
-    rtmp::{RtmpPacket, RTMP_CHANNEL_AUDIO, RTMP_CHUNK_TYPE_0, RTMP_TYPE_AUDIO},
+    log_debug, log_info, log_trace, log_trace_sampled,
+    rtmp::{
+        rtmp_get_audio_codec_name, rtmp_is_audio_sequence_header, RtmpPacket, RTMP_CHANNEL_AUDIO,
+        RTMP_CHUNK_TYPE_0, RTMP_TYPE_AUDIO,
+    },
     server::RtmpServerContext,
 };
 
-use super::SessionReadThreadContext;
+use super::{report_publish_codecs, SessionReadThreadContext};
 
 /// Handles AUDIO RTMP packet
 ///
@@ -48,25 +51,81 @@ pub async fn handle_rtmp_packet_audio(
 
     let mut publish_status_v = session_context.publish_status.lock().await;
 
+    if !publish_status_v.media_mode.accepts_audio() {
+        log_trace!(
+            logger,
+            "Audio packet ignored since the stream is video-only"
+        );
+
+        return true;
+    }
+
     let sound_format = (packet.payload[0] >> 4) & 0x0f;
 
     if publish_status_v.audio_codec == 0 {
         publish_status_v.audio_codec = sound_format as u32;
     }
 
-    let is_header = (sound_format == 10 || sound_format == 13) && packet.payload[1] == 0;
+    let is_header = rtmp_is_audio_sequence_header(&packet.payload);
 
     if is_header {
         publish_status_v.aac_sequence_header = Arc::new(packet.payload.clone());
     }
 
-    let clock = publish_status_v.clock;
+    // Identify the codec
+
+    let mut codecs_identified = false;
+
+    if publish_status_v.audio_codec_name.is_none() {
+        let codec_name = rtmp_get_audio_codec_name(&packet.payload);
+
+        match codec_name {
+            Some(name) => {
+                if !server_context.config.is_audio_codec_allowed(name) {
+                    log_info!(
+                        logger,
+                        format!("Publish rejected: audio codec not allowed: {}", name)
+                    );
+
+                    return false;
+                }
+
+                publish_status_v.audio_codec_name = Some(name);
+                codecs_identified = true;
+            }
+            None => {
+                if !server_context.config.allowed_audio_codecs.is_empty() {
+                    log_info!(logger, "Publish rejected: Unknown audio codec");
+
+                    return false;
+                }
+            }
+        }
+    }
+
+    let audio_codec_name = publish_status_v.audio_codec_name;
+    let video_codec_name = publish_status_v.video_codec_name;
+
+    let clock = publish_status_v
+        .clock
+        .wrapping_add(publish_status_v.timestamp_offset);
 
     drop(publish_status_v);
 
+    if codecs_identified {
+        report_publish_codecs(
+            logger,
+            server_context,
+            session_context,
+            audio_codec_name,
+            video_codec_name,
+        )
+        .await;
+    }
+
     // Log
 
-    log_trace!(
+    log_trace_sampled!(
         logger,
         format!("AUDIO PACKET: {} bytes", packet.payload.len())
     );
@@ -84,14 +143,17 @@ pub async fn handle_rtmp_packet_audio(
 
     // Send packet to the channel
 
-    let channel_status = channel_status_mu.lock().await;
+    let mut channel_status = channel_status_mu.lock().await;
 
     channel_status
         .send_packet(
             session_context.id,
             Arc::new(copied_packet),
             is_header,
-            server_context.config.gop_cache_size,
+            server_context
+                .status
+                .config_overrides
+                .get_gop_cache_size(&server_context.config),
         )
         .await;
 
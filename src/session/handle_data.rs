@@ -5,11 +5,14 @@ use std::sync::Arc;
 use crate::{
     log::Logger,
     log_debug, log_error, log_trace,
-    rtmp::{rtmp_build_metadata, RtmpData, RtmpPacket, RTMP_TYPE_FLEX_STREAM},
+    rtmp::{
+        rtmp_build_metadata, RtmpData, RtmpPacket, StreamMetadata, RTMP_CHANNEL_DATA,
+        RTMP_CHUNK_TYPE_0, RTMP_TYPE_DATA, RTMP_TYPE_FLEX_STREAM,
+    },
     server::{set_channel_metadata, RtmpServerContext},
 };
 
-use super::SessionReadThreadContext;
+use super::{report_publish_metadata, SessionReadThreadContext};
 
 /// Handles DATA RTMP packet
 ///
@@ -52,8 +55,11 @@ pub async fn handle_rtmp_packet_data(
 
     let data = match RtmpData::decode(&packet.payload[offset..packet.header.length]) {
         Ok(c) => c,
-        Err(_) => {
-            log_debug!(logger, "Packet error: Could not decode RTMP data");
+        Err(e) => {
+            log_debug!(
+                logger,
+                format!("Packet error: Could not decode RTMP data: {}", e)
+            );
 
             return false;
         }
@@ -65,23 +71,48 @@ pub async fn handle_rtmp_packet_data(
         "@setDataFrame" => {
             let metadata = Arc::new(rtmp_build_metadata(&data));
             let metadata_size = metadata.len();
+            let stream_metadata = StreamMetadata::from_data(&data);
 
             let channel_opt = session_context.channel().await;
 
             if let Some(channel) = channel_opt {
-                set_channel_metadata(server_context, &channel, session_context.id, metadata).await;
+                set_channel_metadata(
+                    server_context,
+                    &channel,
+                    session_context.id,
+                    metadata,
+                    stream_metadata.clone(),
+                )
+                .await;
 
                 log_debug!(
                     logger,
                     format!(
-                        "Set channel metadata: {} -> {} bytes",
-                        channel, metadata_size
+                        "Set channel metadata: {} -> {} bytes | {}",
+                        channel,
+                        metadata_size,
+                        stream_metadata.to_debug_string()
                     )
                 );
+
+                report_publish_metadata(logger, server_context, session_context, stream_metadata)
+                    .await;
             }
 
             true
         }
+        tag if server_context.config.is_data_passthrough_allowed(tag) => {
+            forward_data_packet(
+                server_context,
+                session_context,
+                &packet.payload[offset..packet.header.length],
+            )
+            .await;
+
+            log_trace!(logger, format!("Forwarded data to the players: {}", tag));
+
+            true
+        }
         _ => {
             log_debug!(logger, format!("Unrecognized data: {}", data.tag));
 
@@ -89,3 +120,56 @@ pub async fn handle_rtmp_packet_data(
         }
     }
 }
+
+/// Forwards a data message (eg: onTextData or onCuePoint) to the players, unchanged
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `payload` - The AMF0 payload of the data message
+async fn forward_data_packet(
+    server_context: &RtmpServerContext,
+    session_context: &SessionReadThreadContext,
+    payload: &[u8],
+) {
+    let channel_status_mu = match &session_context.read_status.channel_status {
+        Some(s) => s,
+        None => {
+            return;
+        }
+    };
+
+    let publish_status_v = session_context.publish_status.lock().await;
+
+    let clock = publish_status_v
+        .clock
+        .wrapping_add(publish_status_v.timestamp_offset);
+
+    drop(publish_status_v);
+
+    let mut copied_packet = RtmpPacket::new_blank();
+
+    copied_packet.header.format = RTMP_CHUNK_TYPE_0;
+    copied_packet.header.channel_id = RTMP_CHANNEL_DATA;
+    copied_packet.header.packet_type = RTMP_TYPE_DATA;
+    copied_packet.payload = payload.to_vec();
+    copied_packet.header.length = copied_packet.payload.len();
+    copied_packet.header.timestamp = clock;
+
+    let mut channel_status = channel_status_mu.lock().await;
+
+    channel_status
+        .send_packet(
+            session_context.id,
+            Arc::new(copied_packet),
+            true,
+            server_context
+                .status
+                .config_overrides
+                .get_gop_cache_size(&server_context.config),
+        )
+        .await;
+
+    drop(channel_status);
+}
@@ -0,0 +1,167 @@
+// Logic to handle user control events
+
+use byteorder::{BigEndian, ByteOrder};
+use chrono::Utc;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::Mutex,
+};
+
+use crate::{
+    log::Logger,
+    log_debug, log_trace,
+    rtmp::{
+        rtmp_make_ping_response, RtmpPacket, RTMP_EVENT_PING_REQUEST, RTMP_EVENT_PING_RESPONSE,
+        RTMP_EVENT_SET_BUFFER_LENGTH, RTMP_EVENT_STREAM_BEGIN, RTMP_EVENT_STREAM_DRY,
+        RTMP_EVENT_STREAM_EOF, RTMP_EVENT_STREAM_IS_RECORDED, RTMP_PING_TIMEOUT,
+    },
+};
+
+use super::{session_write_bytes, SessionReadThreadContext};
+
+/// Handles EVENT (user control) RTMP packet
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `session_context` - The session context
+/// * `write_stream` - The stream to write to the client
+/// * `packet` - The packet
+///
+/// # Return value
+///
+/// Returns true to continue receiving chunks. Returns false to end the session main loop.
+pub async fn handle_rtmp_packet_event<
+    TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
+>(
+    logger: &Logger,
+    session_context: &mut SessionReadThreadContext,
+    write_stream: &Mutex<TW>,
+    packet: &RtmpPacket,
+) -> bool {
+    if packet.payload.len() < 2 {
+        log_debug!(logger, "Packet error: Payload too short");
+
+        return false;
+    }
+
+    let event_type = BigEndian::read_u16(&packet.payload[0..2]);
+
+    match event_type {
+        RTMP_EVENT_PING_RESPONSE => {
+            if packet.payload.len() < 6 {
+                log_debug!(logger, "Packet error: Payload too short");
+
+                return false;
+            }
+
+            let timestamp = BigEndian::read_u32(&packet.payload[2..6]);
+
+            let connect_time = session_context.status.lock().await.connect_time;
+
+            // The timestamp of the ping request is relative to the connect time
+            let now = Utc::now().timestamp_millis().wrapping_sub(connect_time) as u32;
+
+            let rtt = now.wrapping_sub(timestamp);
+
+            if rtt as u64 > RTMP_PING_TIMEOUT * 1000 {
+                log_debug!(
+                    logger,
+                    format!(
+                        "Ignored ping response with invalid timestamp: {}",
+                        timestamp
+                    )
+                );
+
+                return true;
+            }
+
+            session_context.bandwidth.set_rtt(rtt);
+
+            log_debug!(logger, format!("Ping response received. RTT: {} ms", rtt));
+
+            true
+        }
+        RTMP_EVENT_PING_REQUEST => {
+            if packet.payload.len() < 6 {
+                log_debug!(logger, "Packet error: Payload too short");
+
+                return false;
+            }
+
+            let timestamp = BigEndian::read_u32(&packet.payload[2..6]);
+
+            log_trace!(logger, "Ping request received");
+
+            let response = rtmp_make_ping_response(timestamp, session_context.out_chunk_size());
+
+            if let Err(e) = session_write_bytes(write_stream, &response).await {
+                log_debug!(logger, format!("Could not send ping response: {}", e));
+
+                return false;
+            }
+
+            true
+        }
+        RTMP_EVENT_SET_BUFFER_LENGTH => {
+            if packet.payload.len() < 10 {
+                log_debug!(logger, "Packet error: Payload too short");
+
+                return false;
+            }
+
+            let stream_id = BigEndian::read_u32(&packet.payload[2..6]);
+            let buffer_length = BigEndian::read_u32(&packet.payload[6..10]);
+
+            // Players use it as a hint of the amount of media buffered before playback starts
+
+            session_context.status.lock().await.buffer_length = buffer_length;
+
+            log_debug!(
+                logger,
+                format!(
+                    "Buffer length set: {} ms (stream {})",
+                    buffer_length, stream_id
+                )
+            );
+
+            true
+        }
+        RTMP_EVENT_STREAM_IS_RECORDED => {
+            let stream_id = packet
+                .payload
+                .get(2..6)
+                .map(BigEndian::read_u32)
+                .unwrap_or(0);
+
+            log_debug!(
+                logger,
+                format!("Client indicated stream {} is recorded", stream_id)
+            );
+
+            true
+        }
+        RTMP_EVENT_STREAM_BEGIN | RTMP_EVENT_STREAM_EOF | RTMP_EVENT_STREAM_DRY => {
+            // Stream status events are only meaningful when sent by the server
+
+            log_trace!(
+                logger,
+                format!("Ignored stream status event from client: {}", event_type)
+            );
+
+            true
+        }
+        _ => {
+            log_debug!(
+                logger,
+                format!(
+                    "Received unknown user control event: {} ({} bytes)",
+                    event_type,
+                    packet.payload.len()
+                )
+            );
+
+            true
+        }
+    }
+}
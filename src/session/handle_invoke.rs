@@ -64,8 +64,11 @@ pub async fn handle_rtmp_packet_invoke<
 
     let cmd = match RtmpCommand::decode(&packet.payload[offset..packet.header.length]) {
         Ok(c) => c,
-        Err(_) => {
-            log_debug!(logger, "Packet error: Could not decode RTMP command");
+        Err(e) => {
+            log_debug!(
+                logger,
+                format!("Packet error: Could not decode RTMP command: {}", e)
+            );
 
             return false;
         }
@@ -79,14 +82,7 @@ pub async fn handle_rtmp_packet_invoke<
                 .await
         }
         "createStream" => {
-            handle_rtmp_command_create_stream(
-                logger,
-                server_context,
-                session_context,
-                write_stream,
-                &cmd,
-            )
-            .await
+            handle_rtmp_command_create_stream(logger, session_context, write_stream, &cmd).await
         }
         "publish" => {
             handle_rtmp_command_publish(
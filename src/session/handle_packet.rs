@@ -8,18 +8,19 @@ use tokio::{
 
 use crate::{
     log::Logger,
-    log_debug, log_trace,
+    log_debug, log_trace, log_trace_sampled,
     rtmp::{
-        RtmpPacket, RTMP_MAX_CHUNK_SIZE, RTMP_MIN_CHUNK_SIZE, RTMP_TYPE_AUDIO, RTMP_TYPE_DATA,
-        RTMP_TYPE_FLEX_MESSAGE, RTMP_TYPE_FLEX_STREAM, RTMP_TYPE_INVOKE, RTMP_TYPE_SET_CHUNK_SIZE,
-        RTMP_TYPE_VIDEO, RTMP_TYPE_WINDOW_ACKNOWLEDGEMENT_SIZE,
+        RtmpPacket, RTMP_MAX_CHUNK_SIZE, RTMP_MIN_CHUNK_SIZE, RTMP_TYPE_ACKNOWLEDGEMENT,
+        RTMP_TYPE_AUDIO, RTMP_TYPE_DATA, RTMP_TYPE_EVENT, RTMP_TYPE_FLEX_MESSAGE,
+        RTMP_TYPE_FLEX_STREAM, RTMP_TYPE_INVOKE, RTMP_TYPE_SET_CHUNK_SIZE, RTMP_TYPE_VIDEO,
+        RTMP_TYPE_WINDOW_ACKNOWLEDGEMENT_SIZE,
     },
     server::RtmpServerContext,
 };
 
 use super::{
-    handle_rtmp_packet_audio, handle_rtmp_packet_data, handle_rtmp_packet_invoke,
-    handle_rtmp_packet_video, SessionReadThreadContext,
+    handle_rtmp_packet_audio, handle_rtmp_packet_data, handle_rtmp_packet_event,
+    handle_rtmp_packet_invoke, handle_rtmp_packet_video, SessionReadThreadContext,
 };
 
 /// Handles parsed RTMP packet
@@ -42,6 +43,15 @@ pub async fn handle_rtmp_packet<TW: AsyncWrite + AsyncWriteExt + Send + Sync + U
     write_stream: &Mutex<TW>,
     packet: &RtmpPacket,
 ) -> bool {
+    if session_context.trace.is_active() {
+        let clock = session_context.publish_status.lock().await.clock;
+
+        session_context.trace.trace_inbound(packet, clock);
+    }
+    session_context
+        .panic_tracker
+        .set_last_packet_type(packet.header.packet_type);
+
     match packet.header.packet_type {
         RTMP_TYPE_SET_CHUNK_SIZE => {
             // Packet to set chunk size
@@ -115,17 +125,43 @@ pub async fn handle_rtmp_packet<TW: AsyncWrite + AsyncWriteExt + Send + Sync + U
 
             true
         }
+        RTMP_TYPE_ACKNOWLEDGEMENT => {
+            // Acknowledgement of the bytes received by the client
+
+            log_trace!(logger, "Received packet: RTMP_TYPE_ACKNOWLEDGEMENT");
+
+            if packet.payload.len() < 4 {
+                log_debug!(logger, "Packet error: Payload too short");
+
+                return false;
+            }
+
+            let sequence_number = BigEndian::read_u32(&packet.payload[0..4]);
+
+            session_context.outbound_ack.set_ack(sequence_number);
+
+            log_trace!(logger, format!("Received ACK: {}", sequence_number));
+
+            true
+        }
+        RTMP_TYPE_EVENT => {
+            // User control event
+
+            log_trace!(logger, "Received packet: RTMP_TYPE_EVENT");
+
+            handle_rtmp_packet_event(logger, session_context, write_stream, packet).await
+        }
         RTMP_TYPE_AUDIO => {
             // Audio packet
 
-            log_trace!(logger, "Received packet: RTMP_TYPE_AUDIO");
+            log_trace_sampled!(logger, "Received packet: RTMP_TYPE_AUDIO");
 
             handle_rtmp_packet_audio(logger, server_context, session_context, packet).await
         }
         RTMP_TYPE_VIDEO => {
             // Video packet
 
-            log_trace!(logger, "Received packet: RTMP_TYPE_VIDEO");
+            log_trace_sampled!(logger, "Received packet: RTMP_TYPE_VIDEO");
 
             handle_rtmp_packet_video(logger, server_context, session_context, packet).await
         }
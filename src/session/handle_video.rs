@@ -1,15 +1,25 @@
 // Logic to handle video packets
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     log::Logger,
-    log_debug, log_trace,
-    rtmp::{RtmpPacket, RTMP_CHANNEL_VIDEO, RTMP_CHUNK_TYPE_0, RTMP_TYPE_VIDEO},
+    log_debug, log_info, log_trace, log_trace_sampled, log_warning,
+    record::flv_make_snapshot,
+    rtmp::{
+        rtmp_get_video_codec_name, rtmp_get_video_composition_time, RtmpPacket, RTMP_CHANNEL_VIDEO,
+        RTMP_CHUNK_TYPE_0, RTMP_TYPE_VIDEO,
+    },
     server::RtmpServerContext,
 };
 
-use super::SessionReadThreadContext;
+use super::{
+    report_publish_codecs, report_publish_keyframe_warning, report_publish_snapshot,
+    SessionReadThreadContext,
+};
 
 /// Handles VIDEO RTMP packet
 ///
@@ -48,6 +58,15 @@ pub async fn handle_rtmp_packet_video(
 
     let mut publish_status_v = session_context.publish_status.lock().await;
 
+    if !publish_status_v.media_mode.accepts_video() {
+        log_trace!(
+            logger,
+            "Video packet ignored since the stream is audio-only"
+        );
+
+        return true;
+    }
+
     let frame_type = (packet.payload[0] >> 4) & 0x0f;
     let codec_id = packet.payload[0] & 0x0f;
 
@@ -58,19 +77,131 @@ pub async fn handle_rtmp_packet_video(
         publish_status_v.avc_sequence_header = Arc::new(packet.payload.clone());
         publish_status_v.gop_cache.clear();
         publish_status_v.gop_cache_size = 0;
+        publish_status_v.gop_blob_cache.invalidate();
     }
 
     if publish_status_v.video_codec == 0 {
         publish_status_v.video_codec = codec_id as u32;
     }
 
-    let clock = publish_status_v.clock;
+    // Identify the codec
+
+    let mut codecs_identified = false;
+
+    if publish_status_v.video_codec_name.is_none() {
+        let codec_name = rtmp_get_video_codec_name(&packet.payload);
+
+        match codec_name {
+            Some(name) => {
+                if !server_context.config.is_video_codec_allowed(name) {
+                    log_info!(
+                        logger,
+                        format!("Publish rejected: video codec not allowed: {}", name)
+                    );
+
+                    return false;
+                }
+
+                publish_status_v.video_codec_name = Some(name);
+                codecs_identified = true;
+            }
+            None => {
+                if !server_context.config.allowed_video_codecs.is_empty() {
+                    log_info!(logger, "Publish rejected: Unknown video codec");
+
+                    return false;
+                }
+            }
+        }
+    }
+
+    // Check the composition time offset, not zero if the stream uses B-frames.
+    // The offset is relative to the packet timestamp, so it is kept
+    // when the timestamps are shifted (eg: resumed streams).
+
+    if !publish_status_v.has_composition_time
+        && rtmp_get_video_composition_time(&packet.payload).is_some_and(|cts| cts != 0)
+    {
+        publish_status_v.has_composition_time = true;
+
+        log_debug!(
+            logger,
+            "Video packets have composition time offsets (B-frames)"
+        );
+    }
+
+    // Take a snapshot of the keyframe, if the interval has passed
+
+    let snapshots_config = &server_context.config.snapshots;
+
+    let snapshot = if frame_type == 1
+        && !is_header
+        && snapshots_config.is_enabled()
+        && !publish_status_v.avc_sequence_header.is_empty()
+        && publish_status_v.last_snapshot.is_none_or(|t| {
+            t.elapsed() >= Duration::from_secs(snapshots_config.interval_seconds as u64)
+        }) {
+        publish_status_v.last_snapshot = Some(Instant::now());
+
+        Some(flv_make_snapshot(
+            &publish_status_v.avc_sequence_header,
+            &packet.payload,
+        ))
+    } else {
+        None
+    };
+
+    let audio_codec_name = publish_status_v.audio_codec_name;
+    let video_codec_name = publish_status_v.video_codec_name;
+
+    let clock = publish_status_v
+        .clock
+        .wrapping_add(publish_status_v.timestamp_offset);
+
+    // Measure the interval between key frames
+
+    let keyframe_interval_warning = if frame_type == 1 && !is_header {
+        publish_status_v.record_keyframe(
+            clock,
+            server_context.config.keyframe_interval_warning_ms as i64,
+        )
+    } else {
+        None
+    };
 
     drop(publish_status_v);
 
+    if codecs_identified {
+        report_publish_codecs(
+            logger,
+            server_context,
+            session_context,
+            audio_codec_name,
+            video_codec_name,
+        )
+        .await;
+    }
+
+    if let Some(snapshot) = snapshot {
+        report_publish_snapshot(logger, server_context, session_context, snapshot).await;
+    }
+
+    if let Some(keyframe_interval) = keyframe_interval_warning {
+        log_warning!(
+            logger,
+            format!(
+                "Keyframe interval too long: {} ms (max: {} ms)",
+                keyframe_interval, server_context.config.keyframe_interval_warning_ms
+            )
+        );
+
+        report_publish_keyframe_warning(logger, server_context, session_context, keyframe_interval)
+            .await;
+    }
+
     // Log
 
-    log_trace!(
+    log_trace_sampled!(
         logger,
         format!("VIDEO PACKET: {} bytes", packet.payload.len())
     );
@@ -88,14 +219,23 @@ pub async fn handle_rtmp_packet_video(
 
     // Send packet to the channel
 
-    let channel_status = channel_status_mu.lock().await;
+    let mut channel_status = channel_status_mu.lock().await;
+
+    if keyframe_interval_warning.is_some() {
+        if let Some(stats) = &mut channel_status.publish_stats {
+            stats.record_keyframe_interval_warning();
+        }
+    }
 
     channel_status
         .send_packet(
             session_context.id,
             Arc::new(copied_packet),
             is_header,
-            server_context.config.gop_cache_size,
+            server_context
+                .status
+                .config_overrides
+                .get_gop_cache_size(&server_context.config),
         )
         .await;
 
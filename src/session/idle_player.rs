@@ -0,0 +1,155 @@
+// Timer of the players waiting for a stream to be published
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Event of the idle player timer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdlePlayerTimerEvent {
+    /// The player must be notified it is still waiting
+    Notify,
+
+    /// The player waited for too long
+    Expired,
+}
+
+/// Timer of a player waiting for the stream to be published,
+/// to periodically notify it, and to stop waiting after a max time
+pub struct IdlePlayerTimer {
+    /// Interval to notify the player (None to disable)
+    notify_interval: Option<Duration>,
+
+    /// Max time to wait (None for unlimited)
+    max_wait: Option<Duration>,
+
+    /// Next time to notify the player (None if not waiting)
+    next_notify: Option<Instant>,
+
+    /// Time to stop waiting (None if not waiting or unlimited)
+    expiration: Option<Instant>,
+}
+
+impl IdlePlayerTimer {
+    /// Creates new IdlePlayerTimer
+    ///
+    /// # Arguments
+    ///
+    /// * `notify_seconds` - Interval to notify the player (seconds, 0 to disable)
+    /// * `max_wait_seconds` - Max time to wait (seconds, 0 for unlimited)
+    pub fn new(notify_seconds: u32, max_wait_seconds: u32) -> IdlePlayerTimer {
+        IdlePlayerTimer {
+            notify_interval: if notify_seconds > 0 {
+                Some(Duration::from_secs(notify_seconds as u64))
+            } else {
+                None
+            },
+            max_wait: if max_wait_seconds > 0 {
+                Some(Duration::from_secs(max_wait_seconds as u64))
+            } else {
+                None
+            },
+            next_notify: None,
+            expiration: None,
+        }
+    }
+
+    /// Starts the timer, when the player starts waiting
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    pub fn start(&mut self, now: Instant) {
+        self.next_notify = self.notify_interval.map(|i| now + i);
+        self.expiration = self.max_wait.map(|m| now + m);
+    }
+
+    /// Stops the timer, when the player stops waiting
+    pub fn stop(&mut self) {
+        self.next_notify = None;
+        self.expiration = None;
+    }
+
+    /// Gets the next time the timer must be checked (None if stopped)
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.next_notify, self.expiration) {
+            (Some(n), Some(e)) => Some(n.min(e)),
+            (n, e) => n.or(e),
+        }
+    }
+
+    /// Checks the timer
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    ///
+    /// # Return value
+    ///
+    /// Returns the event, if any. Once expired, the timer is stopped.
+    pub fn check(&mut self, now: Instant) -> Option<IdlePlayerTimerEvent> {
+        if self.expiration.is_some_and(|e| now >= e) {
+            self.stop();
+            return Some(IdlePlayerTimerEvent::Expired);
+        }
+
+        match (self.next_notify, self.notify_interval) {
+            (Some(n), Some(i)) if now >= n => {
+                self.next_notify = Some(now + i);
+                Some(IdlePlayerTimerEvent::Notify)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_player_timer() {
+        let mut timer = IdlePlayerTimer::new(5, 12);
+        let now = Instant::now();
+
+        assert_eq!(timer.deadline(), None);
+        assert_eq!(timer.check(now + Duration::from_secs(20)), None);
+
+        timer.start(now);
+
+        assert_eq!(timer.deadline(), Some(now + Duration::from_secs(5)));
+        assert_eq!(timer.check(now + Duration::from_secs(1)), None);
+        assert_eq!(
+            timer.check(now + Duration::from_secs(5)),
+            Some(IdlePlayerTimerEvent::Notify)
+        );
+        assert_eq!(timer.deadline(), Some(now + Duration::from_secs(10)));
+        assert_eq!(
+            timer.check(now + Duration::from_secs(10)),
+            Some(IdlePlayerTimerEvent::Notify)
+        );
+        assert_eq!(timer.deadline(), Some(now + Duration::from_secs(12)));
+        assert_eq!(
+            timer.check(now + Duration::from_secs(12)),
+            Some(IdlePlayerTimerEvent::Expired)
+        );
+        assert_eq!(timer.deadline(), None);
+
+        // Stopped before expiring
+
+        timer.start(now);
+        timer.stop();
+
+        assert_eq!(timer.check(now + Duration::from_secs(20)), None);
+
+        // Disabled
+
+        let mut timer = IdlePlayerTimer::new(0, 0);
+
+        timer.start(now);
+
+        assert_eq!(timer.deadline(), None);
+    }
+}
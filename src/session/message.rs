@@ -2,26 +2,38 @@
 
 use std::sync::Arc;
 
-use crate::rtmp::RtmpPacket;
+use tokio::sync::broadcast;
+
+use crate::{rtmp::RtmpPacket, server::KilledPublishStop};
+
+use super::{GopReplayBlobCache, SessionBandwidth};
+
+/// Receiver for the packets of the stream being played
+pub type RtmpPacketReceiver = broadcast::Receiver<Arc<RtmpPacket>>;
 
 /// RTMP session message
-#[derive(Clone)]
 pub enum RtmpSessionMessage {
     /// Message to start playing a stream
     PlayStart {
         metadata: Arc<Vec<u8>>,
+        metadata_version: u64,
         audio_codec: u32,
         aac_sequence_header: Arc<Vec<u8>>,
         video_codec: u32,
         avc_sequence_header: Arc<Vec<u8>>,
         gop_cache: Vec<Arc<RtmpPacket>>,
+        gop_blob_cache: Option<Arc<GopReplayBlobCache>>,
+        packet_receiver: RtmpPacketReceiver,
+        low_latency: bool,
+        stream_bandwidth: Option<Arc<SessionBandwidth>>,
     },
 
-    /// Message to send the metadata of the stream to play
-    PlayMetadata { metadata: Arc<Vec<u8>> },
-
-    /// Message to send a packet of the stream to play
-    PlayPacket { packet: Arc<RtmpPacket> },
+    /// Message to send the metadata of the stream to play,
+    /// when the publisher updates it
+    PlayMetadata {
+        metadata: Arc<Vec<u8>>,
+        metadata_version: u64,
+    },
 
     /// Message to pause the stream being played
     Pause,
@@ -32,19 +44,40 @@ pub enum RtmpSessionMessage {
         aac_sequence_header: Arc<Vec<u8>>,
         video_codec: u32,
         avc_sequence_header: Arc<Vec<u8>>,
+        packet_receiver: RtmpPacketReceiver,
     },
 
     /// Message to resume playing, but as Idle status
     ResumeIdle,
 
+    /// Message to indicate the player is waiting for the stream to be published
+    PlayIdle,
+
     /// Message to stop playing the stream
     PlayStop,
 
+    /// Message to indicate the stream used for playing was closed or deleted by the client
+    PlayEnd { stream_id: u32 },
+
     /// Message to indicate an invalid key was given to play the stream
     InvalidKey,
 
-    /// Message to kill the session
-    Kill,
+    /// Message to kick the player from the channel, killing the session
+    #[cfg(any(feature = "control", feature = "redis"))]
+    KickPlayer,
+
+    /// Message to send the statistics of the channel to the publisher
+    PublishStatistics { viewers: usize, bit_rate: u64 },
+
+    /// Message to notify the publisher of the congestion, so it can reduce the bit rate
+    PublishCongestion { description: String },
+
+    /// Message to kill the session, with the reason sent to the client.
+    /// If the stop is set, the session sends the stop callback of the killed publication.
+    Kill {
+        reason: String,
+        stop: Option<Box<KilledPublishStop>>,
+    },
 
     /// Message sent at the end of the read thread
     End,
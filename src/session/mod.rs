@@ -1,36 +1,64 @@
 // RTMP session
 
+mod ack_window;
+mod bandwidth;
 mod chunk_read;
+mod chunk_size;
 mod cleanup;
 mod commands;
 mod context;
 mod delete_stream;
+mod gop_replay;
 mod handle;
 mod handle_audio;
 mod handle_data;
+mod handle_event;
 mod handle_invoke;
 mod handle_packet;
 mod handle_video;
+mod idle_player;
 mod message;
 mod msg_handle;
-mod packet_wrapper;
+mod object_encoding;
+mod panic_report;
 mod ping;
+mod play_lanes;
+mod player_lag;
+mod publish_congestion;
+mod publish_events;
+mod publish_statistics;
 mod status;
+mod throttle;
 mod write;
+mod write_timeout;
 
+pub use ack_window::*;
+pub use bandwidth::*;
 pub use chunk_read::*;
+pub use chunk_size::*;
 pub use cleanup::*;
 pub use commands::*;
 pub use context::*;
+pub use gop_replay::*;
 pub use handle::*;
 pub use handle_audio::*;
 pub use handle_data::*;
+pub use handle_event::*;
 pub use handle_invoke::*;
 pub use handle_packet::*;
 pub use handle_video::*;
+pub use idle_player::*;
 pub use message::*;
 pub use msg_handle::*;
-pub use packet_wrapper::*;
+pub use object_encoding::*;
+pub use panic_report::*;
 pub use ping::*;
+pub use play_lanes::*;
+pub use player_lag::*;
+pub use publish_congestion::*;
+pub use publish_events::*;
+pub use publish_statistics::*;
 pub use status::*;
+pub use throttle::*;
 pub use write::*;
+pub use write_timeout::*;
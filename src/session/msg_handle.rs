@@ -1,29 +1,578 @@
 // Message read logic
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
-    sync::{mpsc::Receiver, Mutex},
+    sync::{
+        broadcast::error::{RecvError, TryRecvError as BroadcastTryRecvError},
+        mpsc::{error::TryRecvError, Receiver},
+        Mutex,
+    },
 };
 
 use crate::{
+    callback::{make_player_lagging_callback, make_stop_callback},
     log::Logger,
-    log_debug, log_trace,
+    log_debug, log_info, log_trace, log_trace_sampled,
     rtmp::{
+        rtmp_audio_codec_has_sequence_header, rtmp_get_video_composition_time,
         rtmp_make_audio_codec_header_message, rtmp_make_metadata_message,
-        rtmp_make_sample_access_message, rtmp_make_stream_status_message,
-        rtmp_make_video_codec_header_message, RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO, STREAM_BEGIN,
-        STREAM_EOF,
+        rtmp_make_play_status_message, rtmp_make_sample_access_message,
+        rtmp_make_statistics_message, rtmp_make_status_message, rtmp_make_stream_status_message,
+        rtmp_make_video_codec_header_message, RtmpPacket, RTMP_CHANNEL_METADATA_UPDATE,
+        RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO, RTMP_WINDOW_ACK, STREAM_BEGIN, STREAM_EOF,
     },
-    server::RtmpServerContext,
+    server::{remove_player, try_clear_channel, RtmpServerContext},
 };
 
 use super::{
-    do_session_cleanup, send_status_message, session_write_bytes, RtmpSessionMessage,
-    SessionContext,
+    catch_session_panic, EgressThrottle, GopReplayKey, IdlePlayerTimer, IdlePlayerTimerEvent,
+    OutboundAckLimit, PlayPacketLanes, PlayerLagChange, PlayerLagDetector, RtmpPacketReceiver,
+    RtmpSessionMessage, RtmpSessionPlayStatus, SessionCleanupGuard, SessionContext,
+    SessionWriteBuffer,
 };
 
+/// Receiver of the packets of the stream being played
+pub struct PlayPacketReceiver {
+    /// The receiver (set only while playing)
+    receiver: Option<RtmpPacketReceiver>,
+
+    /// Lanes of the packets taken from the receiver when the player falls behind,
+    /// so the audio is sent ahead of the queued video
+    lanes: PlayPacketLanes,
+
+    /// True if packets were lost, so video packets are skipped until the next key frame
+    waiting_key_frame: bool,
+
+    /// Timestamp of the first media packet sent, to limit the duration of the playback
+    duration_start: Option<i64>,
+
+    /// Version of the last metadata sent to the player
+    metadata_version: u64,
+
+    /// True to measure the duration of the playback with the presentation timestamps
+    /// of the video packets (adding their composition time offset)
+    use_composition_time: bool,
+
+    /// Timer to notify the player while waiting for the stream to be published
+    idle_timer: IdlePlayerTimer,
+}
+
+impl PlayPacketReceiver {
+    /// Creates new PlayPacketReceiver
+    ///
+    /// # Arguments
+    ///
+    /// * `use_composition_time` - True to measure the duration with the presentation timestamps
+    /// * `audio_priority_packets` - Min number of queued packets to send the audio ahead of the video (0 to disable)
+    /// * `idle_timer` - Timer to notify the player while waiting for the stream to be published
+    pub fn new(
+        use_composition_time: bool,
+        audio_priority_packets: u32,
+        idle_timer: IdlePlayerTimer,
+    ) -> PlayPacketReceiver {
+        PlayPacketReceiver {
+            receiver: None,
+            lanes: PlayPacketLanes::new(audio_priority_packets as usize),
+            waiting_key_frame: false,
+            duration_start: None,
+            metadata_version: 0,
+            use_composition_time,
+            idle_timer,
+        }
+    }
+
+    /// Sets the receiver to start receiving packets
+    pub fn set_receiver(&mut self, receiver: RtmpPacketReceiver) {
+        self.receiver = Some(receiver);
+        self.lanes.clear();
+        self.waiting_key_frame = false;
+        self.idle_timer.stop();
+    }
+
+    /// Stops receiving packets
+    pub fn clear(&mut self) {
+        self.receiver = None;
+        self.lanes.clear();
+    }
+
+    /// Gets the number of packets waiting to be sent to the player
+    pub fn queue_depth(&self) -> usize {
+        self.receiver.as_ref().map_or(0, |r| r.len()) + self.lanes.len()
+    }
+
+    /// Takes the next packet from the lanes, moving the available packets
+    /// of the receiver to them if the player is falling behind
+    ///
+    /// # Return value
+    ///
+    /// Returns the event, or None if the audio is not being prioritized
+    /// or there are no packets in the lanes
+    fn try_next_prioritized(&mut self) -> Option<SessionTaskEvent> {
+        let receiver = self.receiver.as_mut()?;
+
+        if !self.lanes.should_prioritize(receiver.len()) {
+            return None;
+        }
+
+        loop {
+            match receiver.try_recv() {
+                Ok(packet) => self.lanes.push(packet),
+                Err(BroadcastTryRecvError::Lagged(lost)) => {
+                    return Some(SessionTaskEvent::PacketsLost(lost));
+                }
+                Err(BroadcastTryRecvError::Closed) | Err(BroadcastTryRecvError::Empty) => break,
+            }
+        }
+
+        if self.lanes.limit_video() > 0 {
+            // Video packets dropped, skip until the next key frame
+            self.waiting_key_frame = true;
+        }
+
+        self.lanes.pop().map(SessionTaskEvent::Packet)
+    }
+
+    /// Checks if the max duration of the playback was reached, after sending a packet
+    ///
+    /// # Arguments
+    ///
+    /// * `play_status` - The play status of the session
+    /// * `packet` - The packet sent to the player
+    ///
+    /// # Return value
+    ///
+    /// Returns true if the playback must be completed
+    fn is_duration_reached(
+        &mut self,
+        play_status: &RtmpSessionPlayStatus,
+        packet: &RtmpPacket,
+    ) -> bool {
+        let duration = match play_status.duration {
+            Some(d) => d,
+            None => {
+                return false;
+            }
+        };
+
+        if packet.header.packet_type != RTMP_TYPE_AUDIO
+            && packet.header.packet_type != RTMP_TYPE_VIDEO
+        {
+            return false;
+        }
+
+        if duration == 0 {
+            // Play a single frame
+            return packet.header.packet_type == RTMP_TYPE_VIDEO || !play_status.receive_video;
+        }
+
+        let timestamp =
+            if self.use_composition_time && packet.header.packet_type == RTMP_TYPE_VIDEO {
+                packet.header.timestamp.wrapping_add(
+                    rtmp_get_video_composition_time(&packet.payload).unwrap_or(0) as i64,
+                )
+            } else {
+                packet.header.timestamp
+            };
+
+        let start = *self.duration_start.get_or_insert(timestamp);
+
+        timestamp - start >= duration
+    }
+}
+
+/// Event to be handled by the session messages task
+enum SessionTaskEvent {
+    /// Session message (None if the channel was closed)
+    Message(Option<RtmpSessionMessage>),
+
+    /// Packet of the stream being played
+    Packet(Arc<RtmpPacket>),
+
+    /// The player lagged behind, so packets were lost
+    PacketsLost(u64),
+
+    /// The packets channel was closed
+    PacketsClosed,
+
+    /// The timer of the player waiting for the stream to be published
+    IdleTimer,
+}
+
+impl SessionTaskEvent {
+    /// Converts the result of receiving a packet into an event
+    fn from_packet_result(res: Result<Arc<RtmpPacket>, RecvError>) -> SessionTaskEvent {
+        match res {
+            Ok(packet) => SessionTaskEvent::Packet(packet),
+            Err(RecvError::Lagged(lost)) => SessionTaskEvent::PacketsLost(lost),
+            Err(RecvError::Closed) => SessionTaskEvent::PacketsClosed,
+        }
+    }
+}
+
+/// Gets the next event for the session messages task, if available without waiting
+fn try_next_session_task_event(
+    session_msg_receiver: &mut Receiver<RtmpSessionMessage>,
+    play_packets: &mut PlayPacketReceiver,
+) -> Option<SessionTaskEvent> {
+    match session_msg_receiver.try_recv() {
+        Ok(msg) => {
+            return Some(SessionTaskEvent::Message(Some(msg)));
+        }
+        Err(TryRecvError::Disconnected) => {
+            return Some(SessionTaskEvent::Message(None));
+        }
+        Err(TryRecvError::Empty) => {}
+    }
+
+    if let Some(event) = play_packets.try_next_prioritized() {
+        return Some(event);
+    }
+
+    match play_packets.receiver.as_mut()?.try_recv() {
+        Ok(packet) => Some(SessionTaskEvent::Packet(packet)),
+        Err(BroadcastTryRecvError::Lagged(lost)) => Some(SessionTaskEvent::PacketsLost(lost)),
+        Err(BroadcastTryRecvError::Closed) => Some(SessionTaskEvent::PacketsClosed),
+        Err(BroadcastTryRecvError::Empty) => None,
+    }
+}
+
+/// Waits for the next event for the session messages task
+/// Session messages have priority over packets
+async fn next_session_task_event(
+    session_msg_receiver: &mut Receiver<RtmpSessionMessage>,
+    play_packets: &mut PlayPacketReceiver,
+) -> SessionTaskEvent {
+    match play_packets.receiver.as_mut() {
+        Some(packet_receiver) => {
+            tokio::select! {
+                biased;
+                msg = session_msg_receiver.recv() => SessionTaskEvent::Message(msg),
+                res = packet_receiver.recv() => SessionTaskEvent::from_packet_result(res),
+            }
+        }
+        None => match play_packets.idle_timer.deadline() {
+            Some(deadline) => {
+                tokio::select! {
+                    biased;
+                    msg = session_msg_receiver.recv() => SessionTaskEvent::Message(msg),
+                    _ = tokio::time::sleep_until(deadline) => SessionTaskEvent::IdleTimer,
+                }
+            }
+            None => SessionTaskEvent::Message(session_msg_receiver.recv().await),
+        },
+    }
+}
+
+/// Checks the timer of the player waiting for the stream to be published,
+/// notifying the player, or stopping the playback if it waited for too long
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `write_buffer` - The buffer to write to the client
+/// * `play_packets` - The receiver of the packets of the stream being played
+async fn check_idle_player_timer(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionContext,
+    write_buffer: &mut SessionWriteBuffer,
+    play_packets: &mut PlayPacketReceiver,
+) {
+    let event = match play_packets.idle_timer.check(tokio::time::Instant::now()) {
+        Some(e) => e,
+        None => {
+            return;
+        }
+    };
+
+    // Get play status
+    let (is_player, play_stream_id) = session_context.play_stream_id().await;
+
+    if !is_player || play_packets.receiver.is_some() {
+        play_packets.idle_timer.stop();
+        return;
+    }
+
+    match event {
+        IdlePlayerTimerEvent::Notify => {
+            log_trace!(logger, "Notifying idle player");
+
+            write_buffer.push(rtmp_make_status_message(
+                play_stream_id,
+                "status",
+                "NetStream.Play.PublishNotify",
+                Some("Waiting for the stream to be published."),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
+        }
+        IdlePlayerTimerEvent::Expired => {
+            log_info!(logger, "PLAY STOP (stream not published in time)");
+
+            // Set playing status to false
+
+            session_context.stop_playing().await;
+
+            let channel = session_context.status.lock().await.channel.clone();
+
+            // Send status messages
+
+            write_buffer.push(rtmp_make_status_message(
+                play_stream_id,
+                "error",
+                "NetStream.Play.StreamNotFound",
+                Some("The stream was not published in time."),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
+
+            write_buffer.push(rtmp_make_status_message(
+                play_stream_id,
+                "status",
+                "NetStream.Play.Stop",
+                Some("Stopped playing stream."),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
+
+            // Send stream status
+
+            let stream_status_bytes = rtmp_make_stream_status_message(STREAM_EOF, play_stream_id);
+
+            write_buffer.push(stream_status_bytes);
+
+            // Remove the player from the channel
+
+            if let Some(channel) = channel {
+                remove_player(server_context, &channel, session_context.id).await;
+                try_clear_channel(server_context, &channel).await;
+            }
+        }
+    }
+}
+
+/// Completes the playback, once its max duration is reached
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `write_buffer` - The buffer to write to the client
+/// * `play_packets` - The receiver of the packets of the stream being played
+/// * `play_stream_id` - ID of the RTMP stream used for playing
+async fn complete_play(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionContext,
+    write_buffer: &mut SessionWriteBuffer,
+    play_packets: &mut PlayPacketReceiver,
+    play_stream_id: u32,
+) {
+    log_info!(logger, "PLAY STOP (duration reached)");
+
+    // Stop receiving packets
+
+    play_packets.clear();
+
+    // Set playing status to false
+
+    session_context.stop_playing().await;
+
+    let channel = session_context.status.lock().await.channel.clone();
+
+    // Send status messages
+
+    write_buffer.push(rtmp_make_play_status_message(
+        play_stream_id,
+        "NetStream.Play.Complete",
+        Some("Playback completed."),
+        session_context.out_chunk_size(),
+    ));
+
+    write_buffer.push(rtmp_make_status_message(
+        play_stream_id,
+        "status",
+        "NetStream.Play.Stop",
+        Some("Stopped playing stream."),
+        session_context.object_encoding(),
+        session_context.out_chunk_size(),
+    ));
+
+    // Send stream status
+
+    let stream_status_bytes = rtmp_make_stream_status_message(STREAM_EOF, play_stream_id);
+
+    write_buffer.push(stream_status_bytes);
+
+    // Remove the player from the channel
+
+    if let Some(channel) = channel {
+        remove_player(server_context, &channel, session_context.id).await;
+        try_clear_channel(server_context, &channel).await;
+    }
+}
+
+/// Handles a packet of the stream being played
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `write_buffer` - The buffer to write to the client
+/// * `play_packets` - The receiver of the packets of the stream being played
+/// * `packet` - The packet
+async fn handle_play_packet(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionContext,
+    write_buffer: &mut SessionWriteBuffer,
+    play_packets: &mut PlayPacketReceiver,
+    packet: Arc<RtmpPacket>,
+) {
+    log_trace_sampled!(logger, "Received packet to play");
+
+    // Get play status
+    let (play_stream_id, play_status) = match session_context.play_status().await {
+        Some(s) => s,
+        None => {
+            return;
+        }
+    };
+
+    if packet.header.channel_id == RTMP_CHANNEL_METADATA_UPDATE {
+        return; // Metadata updates are received as session messages
+    }
+
+    if packet.header.packet_type == RTMP_TYPE_AUDIO && !play_status.receive_audio {
+        return;
+    }
+
+    if packet.header.packet_type == RTMP_TYPE_VIDEO {
+        if !play_status.receive_video {
+            return;
+        }
+
+        if play_packets.waiting_key_frame {
+            let is_key_frame = match packet.payload.first() {
+                Some(b) => (b >> 4) & 0x07 == 1,
+                None => false,
+            };
+
+            if !is_key_frame {
+                return;
+            }
+
+            play_packets.waiting_key_frame = false;
+        }
+    }
+
+    let packet_bytes =
+        packet.create_chunks_for_stream(play_stream_id, session_context.out_chunk_size());
+
+    write_buffer.push(packet_bytes);
+
+    if play_packets.is_duration_reached(&play_status, &packet) {
+        complete_play(
+            logger,
+            server_context,
+            session_context,
+            write_buffer,
+            play_packets,
+            play_stream_id,
+        )
+        .await;
+    }
+}
+
+/// Updates the diagnostics of the delivery of the stream to the player,
+/// reporting when the player stays behind the stream or recovers
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `write_buffer` - The buffer to write to the client
+/// * `play_packets` - The receiver of the packets of the stream being played
+/// * `lag_detector` - The detector of the lagging status
+async fn check_player_lag(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionContext,
+    write_buffer: &SessionWriteBuffer,
+    play_packets: &PlayPacketReceiver,
+    lag_detector: &mut PlayerLagDetector,
+) {
+    let queue_depth = play_packets.queue_depth();
+
+    let player_lag = &session_context.player_lag;
+
+    player_lag.set_pending(queue_depth, write_buffer.pending_bytes());
+    player_lag.set_send_latency(write_buffer.last_write_latency());
+
+    let change = if play_packets.receiver.is_some() {
+        lag_detector.check(queue_depth, Instant::now())
+    } else {
+        lag_detector.reset()
+    };
+
+    match change {
+        Some(PlayerLagChange::Lagging(lagging_time)) => {
+            player_lag.set_lagging(true);
+
+            let lag = player_lag.snapshot();
+
+            log_debug!(
+                logger,
+                format!(
+                    "Player is lagging behind the stream for {} ms. Queue depth: {} packets. Pending bytes: {}. Last send latency: {} ms",
+                    lagging_time.as_millis(),
+                    lag.queue_depth,
+                    lag.pending_bytes,
+                    lag.last_send_latency_ms
+                )
+            );
+
+            let (channel, key) = {
+                let status = session_context.status.lock().await;
+                (status.channel.clone(), status.key.clone())
+            };
+
+            if let Some(channel) = channel {
+                let logger = logger.make_child_logger("");
+                let server_context = server_context.clone();
+                let session_id = session_context.id;
+                let ip = session_context.ip;
+
+                tokio::spawn(async move {
+                    make_player_lagging_callback(
+                        &logger,
+                        server_context.config.get_callback_config(&channel),
+                        &channel,
+                        key.as_deref().unwrap_or_default(),
+                        session_id,
+                        &ip,
+                        lag,
+                    )
+                    .await;
+                });
+            }
+        }
+        Some(PlayerLagChange::Recovered) => {
+            player_lag.set_lagging(false);
+
+            log_debug!(logger, "Player is no longer lagging behind the stream");
+        }
+        None => {}
+    }
+}
+
 /// Handles session message
 ///
 /// # Arguments
@@ -32,6 +581,8 @@ use super::{
 /// * `server_context` - The server context
 /// * `session_context` - The session context
 /// * `write_stream` - The stream to write to the client
+/// * `write_buffer` - The buffer to write to the client (flushed by the caller)
+/// * `play_packets` - The receiver of the packets of the stream being played
 /// * `msg` - The message
 pub async fn handle_session_message<
     TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
@@ -40,76 +591,78 @@ pub async fn handle_session_message<
     server_context: &RtmpServerContext,
     session_context: &SessionContext,
     write_stream: &Mutex<TW>,
+    write_buffer: &mut SessionWriteBuffer,
+    play_packets: &mut PlayPacketReceiver,
     msg: RtmpSessionMessage,
 ) -> bool {
-    let server_config = &server_context.config;
     match msg {
         RtmpSessionMessage::PlayStart {
             metadata,
+            metadata_version,
             audio_codec,
             aac_sequence_header,
             video_codec,
             avc_sequence_header,
             gop_cache,
+            gop_blob_cache,
+            packet_receiver,
+            low_latency,
+            stream_bandwidth,
         } => {
             log_debug!(logger, "RtmpSessionMessage::PlayStart");
 
             // Get play status
-            let play_status = session_context.play_status().await;
+            let (play_stream_id, play_status) = match session_context.play_status().await {
+                Some(s) => s,
+                None => {
+                    return true;
+                }
+            };
 
-            if !play_status.is_player {
-                return true;
-            }
+            // Receive the packets sent after the GOP cache
 
-            // Send stream status
+            play_packets.set_receiver(packet_receiver);
+            play_packets.duration_start = None;
+            play_packets.metadata_version = metadata_version;
 
-            let stream_status_bytes =
-                rtmp_make_stream_status_message(STREAM_BEGIN, play_status.play_stream_id);
+            // In low latency mode, start at the next key frame and flush smaller amounts of bytes
 
-            if let Err(e) = session_write_bytes(write_stream, &stream_status_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send stream status: {}", e)
-                );
+            play_packets.waiting_key_frame = low_latency;
+            write_buffer.set_low_latency(low_latency);
 
-                return true;
-            }
+            // Limit the bit rate relative to the input of the publisher
+
+            write_buffer.set_stream_bandwidth(stream_bandwidth);
+
+            // Send stream status
+
+            let stream_status_bytes = rtmp_make_stream_status_message(STREAM_BEGIN, play_stream_id);
+
+            write_buffer.push(stream_status_bytes);
 
             log_debug!(logger, "RtmpSessionMessage::PlayStart - Sent stream status");
 
             // Send status messages indicating play
 
-            if let Err(e) = send_status_message(
-                write_stream,
-                play_status.play_stream_id,
-                "status",
-                "NetStream.Play.Reset",
-                Some("Playing and resetting stream."),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
+            if play_status.reset {
+                write_buffer.push(rtmp_make_status_message(
+                    play_stream_id,
+                    "status",
+                    "NetStream.Play.Reset",
+                    Some("Playing and resetting stream."),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                ));
             }
 
-            if let Err(e) = send_status_message(
-                write_stream,
-                play_status.play_stream_id,
+            write_buffer.push(rtmp_make_status_message(
+                play_stream_id,
                 "status",
                 "NetStream.Play.Start",
                 Some("Started playing stream."),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
-            }
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
 
             log_debug!(
                 logger,
@@ -118,35 +671,22 @@ pub async fn handle_session_message<
 
             // Send sample access message
 
-            let sample_access_bytes = rtmp_make_sample_access_message(0, server_config.chunk_size);
+            let sample_access_bytes =
+                rtmp_make_sample_access_message(0, session_context.out_chunk_size());
 
-            if let Err(e) = session_write_bytes(write_stream, &sample_access_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send sample access: {}", e)
-                );
-
-                return true;
-            }
+            write_buffer.push(sample_access_bytes);
 
             // Send metadata
 
             if !metadata.is_empty() {
                 let metadata_bytes = rtmp_make_metadata_message(
-                    play_status.play_stream_id,
+                    play_stream_id,
                     &metadata,
                     0,
-                    server_config.chunk_size,
+                    session_context.out_chunk_size(),
                 );
 
-                if let Err(e) = session_write_bytes(write_stream, &metadata_bytes).await {
-                    log_debug!(
-                        logger,
-                        format!("Send error: Could not send metadata bytes: {}", e)
-                    );
-
-                    return true;
-                }
+                write_buffer.push(metadata_bytes);
 
                 log_debug!(
                     logger,
@@ -156,22 +696,16 @@ pub async fn handle_session_message<
 
             // Send audio codec header
 
-            if audio_codec == 10 || audio_codec == 13 {
+            if rtmp_audio_codec_has_sequence_header(audio_codec) && !aac_sequence_header.is_empty()
+            {
                 let audio_codec_header = rtmp_make_audio_codec_header_message(
-                    play_status.play_stream_id,
+                    play_stream_id,
                     &aac_sequence_header,
                     0,
-                    server_config.chunk_size,
+                    session_context.out_chunk_size(),
                 );
 
-                if let Err(e) = session_write_bytes(write_stream, &audio_codec_header).await {
-                    log_debug!(
-                        logger,
-                        format!("Send error: Could not send audio codec header: {}", e)
-                    );
-
-                    return true;
-                }
+                write_buffer.push(audio_codec_header);
 
                 log_debug!(logger, "Sent audio codec header");
             }
@@ -180,63 +714,166 @@ pub async fn handle_session_message<
 
             if video_codec == 7 || video_codec == 12 {
                 let video_codec_header = rtmp_make_video_codec_header_message(
-                    play_status.play_stream_id,
+                    play_stream_id,
                     &avc_sequence_header,
                     0,
-                    server_config.chunk_size,
+                    session_context.out_chunk_size(),
                 );
 
-                if let Err(e) = session_write_bytes(write_stream, &video_codec_header).await {
-                    log_debug!(
-                        logger,
-                        format!("Send error: Could not send video codec header: {}", e)
-                    );
-
-                    return true;
-                }
+                write_buffer.push(video_codec_header);
 
                 log_debug!(logger, "Sent video codec header");
             }
 
             // Send GOP cache
 
-            if play_status.receive_gop {
-                for packet in gop_cache {
-                    if packet.header.packet_type == RTMP_TYPE_AUDIO && !play_status.receive_audio {
-                        continue;
-                    }
+            if play_status.receive_gop && !gop_cache.is_empty() {
+                // Wait for the replay to be allowed, so many players joining
+                // at the same time do not send their GOP cache all at once.
+                // The permit is kept until the GOP cache is written.
 
-                    if packet.header.packet_type == RTMP_TYPE_VIDEO && !play_status.receive_video {
-                        continue;
-                    }
+                let replay_permit = server_context.status.gop_replay_limiter.acquire().await;
+
+                if replay_permit.is_err() {
+                    log_debug!(
+                        logger,
+                        "RtmpSessionMessage::PlayStart - GOP cache skipped: Too many concurrent replays"
+                    );
+                } else if let Some(blob_cache) =
+                    gop_blob_cache.filter(|_| play_status.duration.is_none())
+                {
+                    // The serialized GOP cache is shared with the players
+                    // with the same characteristics
+
+                    let gop_bytes = blob_cache
+                        .get_blob(
+                            &gop_cache,
+                            GopReplayKey {
+                                stream_id: play_stream_id,
+                                chunk_size: session_context.out_chunk_size(),
+                                receive_audio: play_status.receive_audio,
+                                receive_video: play_status.receive_video,
+                            },
+                        )
+                        .await;
+
+                    write_buffer.push(gop_bytes.clone());
 
-                    let packet_bytes = packet.create_chunks_for_stream(
-                        play_status.play_stream_id,
-                        server_config.chunk_size,
+                    log_debug!(
+                        logger,
+                        format!(
+                            "RtmpSessionMessage::PlayStart - Sent GOP cache: {} bytes",
+                            gop_bytes.len()
+                        )
                     );
+                } else {
+                    for packet in gop_cache {
+                        if packet.header.packet_type == RTMP_TYPE_AUDIO
+                            && !play_status.receive_audio
+                        {
+                            continue;
+                        }
+
+                        if packet.header.packet_type == RTMP_TYPE_VIDEO
+                            && !play_status.receive_video
+                        {
+                            continue;
+                        }
+
+                        let packet_bytes = packet.create_chunks_for_stream(
+                            play_stream_id,
+                            session_context.out_chunk_size(),
+                        );
+
+                        write_buffer.push(packet_bytes);
 
-                    if let Err(e) = session_write_bytes(write_stream, &packet_bytes).await {
                         log_debug!(
                             logger,
-                            format!("Send error: Could not send GOP cached packet: {}", e)
+                            format!(
+                                "RtmpSessionMessage::PlayStart - Sent GOP packet: {} bytes",
+                                packet.payload.len()
+                            )
                         );
 
-                        return true;
+                        if play_packets.is_duration_reached(&play_status, &packet) {
+                            complete_play(
+                                logger,
+                                server_context,
+                                session_context,
+                                write_buffer,
+                                play_packets,
+                                play_stream_id,
+                            )
+                            .await;
+
+                            return true;
+                        }
+
+                        if write_buffer.should_flush() {
+                            if let Err(e) = write_buffer.flush(write_stream).await {
+                                log_debug!(
+                                    logger,
+                                    format!("Send error: Could not send GOP cached packet: {}", e)
+                                );
+
+                                return true;
+                            }
+                        }
                     }
+                }
 
+                if let Err(e) = write_buffer.flush(write_stream).await {
                     log_debug!(
                         logger,
-                        format!(
-                            "RtmpSessionMessage::PlayStart - Sent GOP packet: {} bytes",
-                            packet.payload.len()
-                        )
+                        format!("Send error: Could not send GOP cached packets: {}", e)
                     );
+
+                    return true;
                 }
+
+                drop(replay_permit);
             }
 
             // Log
 
-            log_debug!(logger, "Changed play status: PLAYING");
+            let buffer_length = session_context.status.lock().await.buffer_length;
+
+            log_debug!(
+                logger,
+                format!(
+                    "Changed play status: PLAYING (client buffer: {} ms)",
+                    buffer_length
+                )
+            );
+        }
+        RtmpSessionMessage::PlayEnd { stream_id } => {
+            log_debug!(logger, "RtmpSessionMessage::PlayEnd");
+
+            // Stop receiving packets
+
+            play_packets.clear();
+            play_packets.idle_timer.stop();
+
+            // Send status message
+
+            write_buffer.push(rtmp_make_status_message(
+                stream_id,
+                "status",
+                "NetStream.Play.Stop",
+                Some("Stopped playing stream."),
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
+
+            // Send stream status
+
+            let stream_status_bytes = rtmp_make_stream_status_message(STREAM_EOF, stream_id);
+
+            write_buffer.push(stream_status_bytes);
+
+            // Log
+
+            log_debug!(logger, "Changed play status: STOPPED");
         }
         RtmpSessionMessage::InvalidKey => {
             log_debug!(logger, "RtmpSessionMessage::InvalidKey");
@@ -248,6 +885,10 @@ pub async fn handle_session_message<
                 return true;
             }
 
+            // Stop receiving packets
+
+            play_packets.clear();
+
             // Set playing status to false
             session_context.stop_playing().await;
 
@@ -255,24 +896,26 @@ pub async fn handle_session_message<
 
             log_debug!(logger, "Invalid play stream key provided");
 
-            if let Err(e) = send_status_message(
-                write_stream,
+            write_buffer.push(rtmp_make_status_message(
                 play_stream_id,
                 "error",
                 "NetStream.Publish.BadName",
                 Some("Invalid stream key provided"),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
-            }
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
         }
-        RtmpSessionMessage::PlayMetadata { metadata } => {
-            log_debug!(logger, "RtmpSessionMessage::PlayMetadata");
+        RtmpSessionMessage::PlayMetadata {
+            metadata,
+            metadata_version,
+        } => {
+            log_debug!(
+                logger,
+                format!(
+                    "RtmpSessionMessage::PlayMetadata: Version {}",
+                    metadata_version
+                )
+            );
 
             // Get play status
             let (is_player, play_stream_id) = session_context.play_stream_id().await;
@@ -281,43 +924,30 @@ pub async fn handle_session_message<
                 return true;
             }
 
-            if metadata.is_empty() {
-                return true;
-            }
-
-            // Make metadata message
-
-            let metadata_bytes =
-                rtmp_make_metadata_message(play_stream_id, &metadata, 0, server_config.chunk_size);
-
-            // Send metadata
+            // Skip outdated metadata (already sent when the player started)
 
-            if let Err(e) = session_write_bytes(write_stream, &metadata_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not channel metadata: {}", e)
-                );
+            if metadata_version <= play_packets.metadata_version {
                 return true;
             }
-        }
-        RtmpSessionMessage::PlayPacket { packet } => {
-            log_trace!(logger, "RtmpSessionMessage::PlayPacket");
 
-            // Get play status
-            let (is_player, play_stream_id) = session_context.play_stream_id().await;
+            play_packets.metadata_version = metadata_version;
 
-            if !is_player {
+            if metadata.is_empty() {
                 return true;
             }
 
-            let packet_bytes =
-                packet.create_chunks_for_stream(play_stream_id, server_config.chunk_size);
+            // Make metadata message
+
+            let metadata_bytes = rtmp_make_metadata_message(
+                play_stream_id,
+                &metadata,
+                0,
+                session_context.out_chunk_size(),
+            );
 
-            if let Err(e) = session_write_bytes(write_stream, &packet_bytes).await {
-                log_debug!(logger, format!("Send error: Could not send packet: {}", e));
+            // Send metadata
 
-                return true;
-            }
+            write_buffer.push(metadata_bytes);
         }
         RtmpSessionMessage::PlayStop => {
             log_debug!(logger, "RtmpSessionMessage::PlayStop");
@@ -329,36 +959,27 @@ pub async fn handle_session_message<
                 return true;
             }
 
+            // Stop receiving packets, and wait for the stream to be published again
+
+            play_packets.clear();
+            play_packets.idle_timer.start(tokio::time::Instant::now());
+
             // Send status message
 
-            if let Err(e) = send_status_message(
-                write_stream,
+            write_buffer.push(rtmp_make_status_message(
                 play_stream_id,
                 "status",
                 "NetStream.Play.UnpublishNotify",
                 Some("stream is now unpublished."),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
-            }
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
 
             // Send stream status
 
             let stream_status_bytes = rtmp_make_stream_status_message(STREAM_EOF, play_stream_id);
 
-            if let Err(e) = session_write_bytes(write_stream, &stream_status_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send stream status: {}", e)
-                );
-
-                return true;
-            }
+            write_buffer.push(stream_status_bytes);
 
             // Log
 
@@ -374,35 +995,27 @@ pub async fn handle_session_message<
                 return true;
             }
 
+            // Stop receiving packets
+
+            play_packets.clear();
+            play_packets.idle_timer.stop();
+
             // Send stream status
 
             let stream_status_bytes = rtmp_make_stream_status_message(STREAM_EOF, play_stream_id);
 
-            if let Err(e) = session_write_bytes(write_stream, &stream_status_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send stream status: {}", e)
-                );
-                return true;
-            }
+            write_buffer.push(stream_status_bytes);
 
             // Send status message
 
-            if let Err(e) = send_status_message(
-                write_stream,
+            write_buffer.push(rtmp_make_status_message(
                 play_stream_id,
                 "status",
                 "NetStream.Pause.Notify",
                 Some("Paused live"),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
-            }
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
 
             // Log
 
@@ -413,6 +1026,7 @@ pub async fn handle_session_message<
             aac_sequence_header,
             video_codec,
             avc_sequence_header,
+            packet_receiver,
         } => {
             log_debug!(logger, "RtmpSessionMessage::Resume");
 
@@ -423,36 +1037,28 @@ pub async fn handle_session_message<
                 return true;
             }
 
+            // Receive packets again
+
+            play_packets.set_receiver(packet_receiver);
+
             // Send stream status
 
             let stream_status_bytes = rtmp_make_stream_status_message(STREAM_BEGIN, play_stream_id);
 
-            if let Err(e) = session_write_bytes(write_stream, &stream_status_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send stream status: {}", e)
-                );
-                return true;
-            }
+            write_buffer.push(stream_status_bytes);
 
             // Send audio codec header
 
-            if audio_codec == 10 || audio_codec == 13 {
+            if rtmp_audio_codec_has_sequence_header(audio_codec) && !aac_sequence_header.is_empty()
+            {
                 let audio_codec_header = rtmp_make_audio_codec_header_message(
                     play_stream_id,
                     &aac_sequence_header,
                     0,
-                    server_config.chunk_size,
+                    session_context.out_chunk_size(),
                 );
 
-                if let Err(e) = session_write_bytes(write_stream, &audio_codec_header).await {
-                    log_debug!(
-                        logger,
-                        format!("Send error: Could not send audio codec header: {}", e)
-                    );
-
-                    return true;
-                }
+                write_buffer.push(audio_codec_header);
 
                 log_debug!(logger, "Sent audio codec header");
             }
@@ -464,38 +1070,24 @@ pub async fn handle_session_message<
                     play_stream_id,
                     &avc_sequence_header,
                     0,
-                    server_config.chunk_size,
+                    session_context.out_chunk_size(),
                 );
 
-                if let Err(e) = session_write_bytes(write_stream, &video_codec_header).await {
-                    log_debug!(
-                        logger,
-                        format!("Send error: Could not send video codec header: {}", e)
-                    );
-
-                    return true;
-                }
+                write_buffer.push(video_codec_header);
 
                 log_debug!(logger, "Sent video codec header");
             }
 
             // Send status message
 
-            if let Err(e) = send_status_message(
-                write_stream,
+            write_buffer.push(rtmp_make_status_message(
                 play_stream_id,
                 "status",
                 "NetStream.Unpause.Notify",
                 Some("Unpaused live"),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
-            }
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
 
             // Log
 
@@ -515,41 +1107,144 @@ pub async fn handle_session_message<
 
             let stream_status_bytes = rtmp_make_stream_status_message(STREAM_BEGIN, play_stream_id);
 
-            if let Err(e) = session_write_bytes(write_stream, &stream_status_bytes).await {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send stream status: {}", e)
-                );
-                return true;
-            }
+            write_buffer.push(stream_status_bytes);
 
             // Send status message
 
-            if let Err(e) = send_status_message(
-                write_stream,
+            write_buffer.push(rtmp_make_status_message(
                 play_stream_id,
                 "status",
                 "NetStream.Unpause.Notify",
                 Some("Unpaused live"),
-                server_config.chunk_size,
-            )
-            .await
-            {
-                log_debug!(
-                    logger,
-                    format!("Send error: Could not send status message: {}", e)
-                );
-            }
+                session_context.object_encoding(),
+                session_context.out_chunk_size(),
+            ));
+
+            // Wait for the stream to be published
+
+            play_packets.idle_timer.start(tokio::time::Instant::now());
 
             // Log
 
             log_debug!(logger, "Changed play status: IDLE");
         }
-        RtmpSessionMessage::Kill => {
-            log_debug!(logger, "RtmpSessionMessage::Kill");
+        RtmpSessionMessage::PlayIdle => {
+            log_debug!(logger, "RtmpSessionMessage::PlayIdle");
+
+            // Wait for the stream to be published
+
+            play_packets.idle_timer.start(tokio::time::Instant::now());
+        }
+        #[cfg(any(feature = "control", feature = "redis"))]
+        RtmpSessionMessage::KickPlayer => {
+            log_debug!(logger, "RtmpSessionMessage::KickPlayer");
+
+            // Get play status
+            let (is_player, play_stream_id) = session_context.play_stream_id().await;
+
+            if is_player {
+                // Stop receiving packets
+
+                play_packets.clear();
+
+                // Set playing status to false
+                session_context.stop_playing().await;
+
+                // Send status message
+
+                write_buffer.push(rtmp_make_status_message(
+                    play_stream_id,
+                    "status",
+                    "NetStream.Play.Stop",
+                    Some("Kicked from the channel"),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                ));
+            }
 
             session_context.set_killed().await;
         }
+        RtmpSessionMessage::PublishStatistics { viewers, bit_rate } => {
+            log_trace!(
+                logger,
+                format!(
+                    "RtmpSessionMessage::PublishStatistics: viewers={}, bitrate={}",
+                    viewers, bit_rate
+                )
+            );
+
+            if let Some(publish_stream_id) = session_context.publish_stream_id().await {
+                write_buffer.push(rtmp_make_statistics_message(
+                    publish_stream_id,
+                    viewers,
+                    bit_rate,
+                    session_context.out_chunk_size(),
+                ));
+            }
+        }
+        RtmpSessionMessage::PublishCongestion { description } => {
+            log_trace!(
+                logger,
+                format!("RtmpSessionMessage::PublishCongestion: {}", description)
+            );
+
+            if let Some(publish_stream_id) = session_context.publish_stream_id().await {
+                write_buffer.push(rtmp_make_status_message(
+                    publish_stream_id,
+                    "warning",
+                    "NetStream.Publish.InsufficientBW",
+                    Some(&description),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                ));
+            }
+        }
+        RtmpSessionMessage::Kill { reason, stop } => {
+            log_debug!(logger, format!("RtmpSessionMessage::Kill: {}", reason));
+
+            // Tell the publisher why it was disconnected
+
+            if let Some(publish_stream_id) = session_context.publish_stream_id().await {
+                write_buffer.push(rtmp_make_status_message(
+                    publish_stream_id,
+                    "error",
+                    "NetStream.Publish.BadName",
+                    Some(&reason),
+                    session_context.object_encoding(),
+                    session_context.out_chunk_size(),
+                ));
+            }
+
+            // Send the pending messages and close the connection
+
+            if let Err(e) = write_buffer.flush(write_stream).await {
+                log_debug!(logger, format!("Send error: {}", e));
+            }
+
+            if let Err(e) = write_stream.lock().await.shutdown().await {
+                log_debug!(logger, format!("Could not close the connection: {}", e));
+            }
+
+            session_context.set_killed().await;
+
+            // Only the session keeps the key, so it sends the stop callback
+
+            if let Some(stop) = stop {
+                let key = session_context.status.lock().await.key.clone();
+
+                make_stop_callback(
+                    logger,
+                    server_context.config.get_callback_config(&stop.channel),
+                    &stop.channel,
+                    key.as_deref().unwrap_or_default(),
+                    &stop.correlation,
+                    &stop.stream_id,
+                    stop.stats.as_ref(),
+                    Some(&stop.reason),
+                )
+                .await;
+            }
+        }
         RtmpSessionMessage::End => {
             log_debug!(logger, "RtmpSessionMessage::End");
 
@@ -573,39 +1268,176 @@ pub fn spawn_task_to_read_session_messages<
     TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
 >(
     logger: Arc<Logger>,
-    mut server_context: RtmpServerContext,
+    server_context: RtmpServerContext,
     session_context: SessionContext,
     write_stream: Arc<Mutex<TW>>,
     mut session_msg_receiver: Receiver<RtmpSessionMessage>,
 ) {
     tokio::spawn(async move {
-        let mut continue_loop = true;
+        // Make sure the session is cleaned up, even if the task does not complete
+        let cleanup_guard = SessionCleanupGuard::new(
+            logger.clone(),
+            server_context.clone(),
+            session_context.clone(),
+        );
+
+        let mut write_buffer = SessionWriteBuffer::new(
+            EgressThrottle::new(
+                server_context.config.player_max_kbps,
+                server_context.config.player_max_stream_bit_rate_percent,
+            ),
+            OutboundAckLimit::new(
+                session_context.outbound_ack.clone(),
+                session_context.bandwidth.clone(),
+                (server_context.config.ack_window_limit as u64) * (RTMP_WINDOW_ACK as u64),
+                server_context.config.ack_timeout_seconds,
+            ),
+        );
+        let mut play_packets = PlayPacketReceiver::new(
+            server_context.config.play_duration_composition_time,
+            server_context.config.player_audio_priority_packets,
+            IdlePlayerTimer::new(
+                server_context.config.player_idle_notify_seconds,
+                server_context.config.player_idle_max_seconds,
+            ),
+        );
+        let mut lag_detector = PlayerLagDetector::new(
+            server_context.config.player_lag_packets,
+            server_context.config.player_lag_seconds,
+        );
+
+        // Handle events.
+        // A panic ends the loop, so the session is cleaned up.
+
+        let handle_events = async {
+            let mut continue_loop = true;
+
+            while continue_loop {
+                let event =
+                    match try_next_session_task_event(&mut session_msg_receiver, &mut play_packets)
+                    {
+                        Some(e) => e,
+                        None => {
+                            // Nothing pending, flush the buffered bytes before waiting
+
+                            if let Err(e) = write_buffer.flush(&write_stream).await {
+                                log_debug!(logger, format!("Send error: {}", e));
+                            }
+
+                            next_session_task_event(&mut session_msg_receiver, &mut play_packets)
+                                .await
+                        }
+                    };
+
+                match event {
+                    SessionTaskEvent::Message(Some(msg)) => {
+                        continue_loop = handle_session_message(
+                            &logger,
+                            &server_context,
+                            &session_context,
+                            &write_stream,
+                            &mut write_buffer,
+                            &mut play_packets,
+                            msg,
+                        )
+                        .await;
+                    }
+                    SessionTaskEvent::Message(None) => {
+                        continue_loop = false;
+                    }
+                    SessionTaskEvent::Packet(packet) => {
+                        handle_play_packet(
+                            &logger,
+                            &server_context,
+                            &session_context,
+                            &mut write_buffer,
+                            &mut play_packets,
+                            packet,
+                        )
+                        .await;
+                    }
+                    SessionTaskEvent::PacketsLost(lost) => {
+                        log_debug!(
+                            logger,
+                            format!(
+                                "Player is too slow: {} packets lost. Waiting for the next key frame.",
+                                lost
+                            )
+                        );
 
-        while continue_loop {
-            let msg_opt = session_msg_receiver.recv().await;
+                        play_packets.waiting_key_frame = true;
+                    }
+                    SessionTaskEvent::PacketsClosed => {
+                        play_packets.clear();
+                    }
+                    SessionTaskEvent::IdleTimer => {
+                        check_idle_player_timer(
+                            &logger,
+                            &server_context,
+                            &session_context,
+                            &mut write_buffer,
+                            &mut play_packets,
+                        )
+                        .await;
+                    }
+                }
 
-            match msg_opt {
-                Some(msg) => {
-                    continue_loop = handle_session_message(
-                        &logger,
-                        &server_context,
-                        &session_context,
-                        &write_stream,
-                        msg,
-                    )
-                    .await;
+                if write_buffer.should_flush() {
+                    if let Err(e) = write_buffer.flush(&write_stream).await {
+                        log_debug!(logger, format!("Send error: {}", e));
+                    }
                 }
-                None => {
-                    continue_loop = false;
+
+                // Bandwidth
+
+                if let Some(bit_rate) = session_context.bandwidth.output.take_bit_rate_update() {
+                    match write_buffer.bit_rate_limit() {
+                        Some(limit) => {
+                            log_debug!(
+                                logger,
+                                format!(
+                                    "Output bit rate is now: {} bps (limit: {} bps)",
+                                    bit_rate, limit
+                                )
+                            );
+                        }
+                        None => {
+                            log_debug!(logger, format!("Output bit rate is now: {} bps", bit_rate));
+                        }
+                    }
                 }
+
+                check_player_lag(
+                    &logger,
+                    &server_context,
+                    &session_context,
+                    &write_buffer,
+                    &play_packets,
+                    &mut lag_detector,
+                )
+                .await;
             }
+        };
+
+        _ = catch_session_panic(
+            &logger,
+            "messages",
+            session_context.id,
+            &session_context.status,
+            &session_context.panic_tracker,
+            handle_events,
+        )
+        .await;
+
+        if let Err(e) = write_buffer.flush(&write_stream).await {
+            log_debug!(logger, format!("Send error: {}", e));
         }
 
         // Cleanup
 
         log_debug!(logger, "Performing session cleanup...");
 
-        do_session_cleanup(&logger, &mut server_context, &session_context).await;
+        cleanup_guard.cleanup().await;
 
         log_debug!(logger, "Draining message channel...");
 
@@ -0,0 +1,32 @@
+// Object encoding negotiation
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::rtmp::RTMP_OBJECT_ENCODING_AMF0;
+
+/// Object encoding negotiated with the client,
+/// used to encode the commands sent to it
+pub struct SessionObjectEncoding {
+    /// Object encoding (0 for AMF0, 3 for AMF3)
+    encoding: AtomicU32,
+}
+
+impl SessionObjectEncoding {
+    /// Creates new SessionObjectEncoding.
+    /// Until negotiated, AMF0 is used.
+    pub fn new() -> SessionObjectEncoding {
+        SessionObjectEncoding {
+            encoding: AtomicU32::new(RTMP_OBJECT_ENCODING_AMF0),
+        }
+    }
+
+    /// Gets the object encoding
+    pub fn get(&self) -> u32 {
+        self.encoding.load(Ordering::Relaxed)
+    }
+
+    /// Sets the object encoding
+    pub fn set(&self, encoding: u32) {
+        self.encoding.store(encoding, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,141 @@
+// Isolation of the panics of the session tasks
+
+use std::{
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use futures_util::FutureExt;
+use tokio::sync::Mutex;
+
+use crate::{
+    log::{get_panic_message, Logger},
+    log_error,
+};
+
+use super::RtmpSessionStatus;
+
+/// Value of the last packet type, when no packets were received yet
+const NO_PACKET_TYPE: u32 = u32::MAX;
+
+/// Activity of a session, tracked to report its panics
+pub struct SessionPanicTracker {
+    /// Type of the last packet received from the client
+    last_packet_type: AtomicU32,
+}
+
+impl SessionPanicTracker {
+    /// Creates new SessionPanicTracker
+    pub fn new() -> SessionPanicTracker {
+        SessionPanicTracker {
+            last_packet_type: AtomicU32::new(NO_PACKET_TYPE),
+        }
+    }
+
+    /// Sets the type of the last packet received from the client
+    ///
+    /// # Arguments
+    ///
+    /// * `packet_type` - The packet type
+    pub fn set_last_packet_type(&self, packet_type: u32) {
+        self.last_packet_type.store(packet_type, Ordering::Relaxed);
+    }
+
+    /// Gets the type of the last packet received from the client
+    /// (None if no packets were received)
+    pub fn last_packet_type(&self) -> Option<u32> {
+        match self.last_packet_type.load(Ordering::Relaxed) {
+            NO_PACKET_TYPE => None,
+            t => Some(t),
+        }
+    }
+}
+
+/// Runs a task of a session, catching its panics,
+/// so they do not stop the session cleanup.
+/// When the task panics, a report is logged with the session details.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `task` - Name of the task, for the report
+/// * `session_id` - The session ID
+/// * `session_status` - The session status
+/// * `tracker` - The activity of the session
+/// * `future` - The task
+///
+/// # Return value
+///
+/// Returns the output of the task, or an error if it panicked
+pub async fn catch_session_panic<F: Future>(
+    logger: &Logger,
+    task: &str,
+    session_id: u64,
+    session_status: &Mutex<RtmpSessionStatus>,
+    tracker: &SessionPanicTracker,
+    future: F,
+) -> Result<F::Output, ()> {
+    let payload = match AssertUnwindSafe(future).catch_unwind().await {
+        Ok(r) => {
+            return Ok(r);
+        }
+        Err(p) => p,
+    };
+
+    let channel = session_status.lock().await.channel.clone();
+
+    log_error!(
+        logger,
+        format!(
+            "Session task panicked: {} | Task: {} | Session: #{} | Channel: {} | Last packet type: {}",
+            get_panic_message(payload.as_ref()),
+            task,
+            session_id,
+            channel.as_deref().unwrap_or("-"),
+            tracker
+                .last_packet_type()
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        )
+    );
+
+    Err(())
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_catch_session_panic() {
+        let logger = Logger::new_disabled();
+        let status = Mutex::new(RtmpSessionStatus::new());
+        let tracker = SessionPanicTracker::new();
+
+        assert_eq!(tracker.last_packet_type(), None);
+
+        tracker.set_last_packet_type(20);
+
+        assert_eq!(tracker.last_packet_type(), Some(20));
+
+        let res = catch_session_panic(&logger, "test", 1, &status, &tracker, async { 5 }).await;
+
+        assert_eq!(res, Ok(5));
+
+        let res = catch_session_panic(&logger, "test", 1, &status, &tracker, async {
+            if tracker.last_packet_type().is_some() {
+                panic!("Test panic");
+            }
+        })
+        .await;
+
+        assert_eq!(res, Err(()));
+
+        // The status is usable after the panic
+
+        assert!(status.lock().await.channel.is_none());
+    }
+}
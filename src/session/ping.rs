@@ -11,7 +11,6 @@ use crate::{
     log::Logger,
     log_debug,
     rtmp::{rtmp_make_ping_request, RTMP_PING_TIME},
-    server::RtmpServerContext,
     session::session_write_bytes,
 };
 
@@ -22,13 +21,11 @@ use super::SessionContext;
 /// # Arguments
 ///
 /// * `logger` - The session logger
-/// * `server_context` - The server context
 /// * `session_context` - The session context
 /// * `write_stream` - The stream to write to the client
 /// * `cancel_pings_receiver` - A receiver to listen for a cancel signal
 pub fn spawn_task_to_send_pings<TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static>(
     logger: Arc<Logger>,
-    server_context: RtmpServerContext,
     session_context: SessionContext,
     write_stream: Arc<Mutex<TW>>,
     mut cancel_pings_receiver: Receiver<()>,
@@ -59,7 +56,7 @@ pub fn spawn_task_to_send_pings<TW: AsyncWrite + AsyncWriteExt + Send + Sync + U
 
             // Create ping
 
-            let ping_bytes = rtmp_make_ping_request(connect_time, server_context.config.chunk_size);
+            let ping_bytes = rtmp_make_ping_request(connect_time, session_context.out_chunk_size());
 
             log_debug!(logger, "Sending ping request to client");
 
@@ -0,0 +1,146 @@
+// Priority lanes of the packets to send to a player
+
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::rtmp::{RtmpPacket, RTMP_TYPE_AUDIO, RTMP_TYPE_VIDEO};
+
+/// Packets of the stream being played, taken from the shared buffer
+/// when the player falls behind, split into lanes,
+/// so the audio is sent ahead of the queued video
+pub struct PlayPacketLanes {
+    /// Min number of queued packets to prioritize the audio (0 to disable)
+    threshold: usize,
+
+    /// Audio packets
+    audio: VecDeque<Arc<RtmpPacket>>,
+
+    /// Video and data packets
+    video: VecDeque<Arc<RtmpPacket>>,
+}
+
+impl PlayPacketLanes {
+    /// Creates new PlayPacketLanes
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Min number of queued packets to prioritize the audio (0 to disable)
+    pub fn new(threshold: usize) -> PlayPacketLanes {
+        PlayPacketLanes {
+            threshold,
+            audio: VecDeque::new(),
+            video: VecDeque::new(),
+        }
+    }
+
+    /// Checks if the audio must be prioritized
+    ///
+    /// # Arguments
+    ///
+    /// * `queued` - Number of packets queued in the shared buffer for the player
+    pub fn should_prioritize(&self, queued: usize) -> bool {
+        self.threshold > 0 && (!self.is_empty() || queued >= self.threshold)
+    }
+
+    /// Checks if the lanes are empty
+    pub fn is_empty(&self) -> bool {
+        self.audio.is_empty() && self.video.is_empty()
+    }
+
+    /// Gets the number of packets in the lanes
+    pub fn len(&self) -> usize {
+        self.audio.len() + self.video.len()
+    }
+
+    /// Removes all the packets
+    pub fn clear(&mut self) {
+        self.audio.clear();
+        self.video.clear();
+    }
+
+    /// Adds a packet to its lane
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet
+    pub fn push(&mut self, packet: Arc<RtmpPacket>) {
+        if packet.header.packet_type == RTMP_TYPE_AUDIO {
+            self.audio.push_back(packet);
+        } else {
+            self.video.push_back(packet);
+        }
+    }
+
+    /// Drops the queued video packets if too many,
+    /// so the player skips to the next key frame instead of falling further behind
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of dropped packets
+    pub fn limit_video(&mut self) -> usize {
+        if self.video.len() <= self.threshold {
+            return 0;
+        }
+
+        let len = self.video.len();
+
+        self.video
+            .retain(|p| p.header.packet_type != RTMP_TYPE_VIDEO);
+
+        len - self.video.len()
+    }
+
+    /// Takes the next packet to send, audio first
+    pub fn pop(&mut self) -> Option<Arc<RtmpPacket>> {
+        self.audio.pop_front().or_else(|| self.video.pop_front())
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use crate::rtmp::RTMP_TYPE_DATA;
+
+    use super::*;
+
+    fn make_packet(packet_type: u32) -> Arc<RtmpPacket> {
+        let mut packet = RtmpPacket::new_blank();
+        packet.header.packet_type = packet_type;
+        Arc::new(packet)
+    }
+
+    #[test]
+    fn test_play_packet_lanes() {
+        let mut lanes = PlayPacketLanes::new(3);
+
+        assert!(!lanes.should_prioritize(2));
+        assert!(lanes.should_prioritize(3));
+        assert!(!PlayPacketLanes::new(0).should_prioritize(100));
+
+        lanes.push(make_packet(RTMP_TYPE_VIDEO));
+        lanes.push(make_packet(RTMP_TYPE_DATA));
+        lanes.push(make_packet(RTMP_TYPE_AUDIO));
+        lanes.push(make_packet(RTMP_TYPE_VIDEO));
+
+        assert!(lanes.should_prioritize(0));
+        assert_eq!(lanes.len(), 4);
+        assert_eq!(lanes.limit_video(), 0);
+
+        // Audio first
+
+        assert_eq!(lanes.pop().unwrap().header.packet_type, RTMP_TYPE_AUDIO);
+        assert_eq!(lanes.pop().unwrap().header.packet_type, RTMP_TYPE_VIDEO);
+
+        // Too many video packets: the data packets are kept
+
+        lanes.push(make_packet(RTMP_TYPE_VIDEO));
+        lanes.push(make_packet(RTMP_TYPE_VIDEO));
+        lanes.push(make_packet(RTMP_TYPE_AUDIO));
+
+        assert_eq!(lanes.limit_video(), 3);
+        assert_eq!(lanes.pop().unwrap().header.packet_type, RTMP_TYPE_AUDIO);
+        assert_eq!(lanes.pop().unwrap().header.packet_type, RTMP_TYPE_DATA);
+        assert!(lanes.pop().is_none());
+        assert!(lanes.is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+// Diagnostics of the players receiving the stream slower than it is published
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Diagnostics of the delivery of the stream to a player,
+/// to distinguish server issues from network issues of the viewers
+pub struct SessionPlayerLag {
+    /// Number of packets of the stream waiting to be sent to the player
+    queue_depth: AtomicU64,
+
+    /// Number of bytes buffered, waiting to be written to the connection
+    pending_bytes: AtomicU64,
+
+    /// Time it took to write the last buffered bytes to the connection (microseconds)
+    last_send_latency_us: AtomicU64,
+
+    /// True if the player is lagging behind the stream
+    lagging: AtomicBool,
+}
+
+/// Snapshot of the diagnostics of a player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerLagSnapshot {
+    /// Number of packets of the stream waiting to be sent to the player
+    pub queue_depth: u64,
+
+    /// Number of bytes buffered, waiting to be written to the connection
+    pub pending_bytes: u64,
+
+    /// Time it took to write the last buffered bytes to the connection (milliseconds)
+    pub last_send_latency_ms: f64,
+
+    /// True if the player is lagging behind the stream
+    pub lagging: bool,
+}
+
+impl SessionPlayerLag {
+    /// Creates new SessionPlayerLag
+    pub fn new() -> SessionPlayerLag {
+        SessionPlayerLag {
+            queue_depth: AtomicU64::new(0),
+            pending_bytes: AtomicU64::new(0),
+            last_send_latency_us: AtomicU64::new(0),
+            lagging: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the number of packets and bytes waiting to be sent
+    ///
+    /// # Arguments
+    ///
+    /// * `queue_depth` - Number of packets of the stream waiting to be sent
+    /// * `pending_bytes` - Number of bytes buffered, waiting to be written
+    pub fn set_pending(&self, queue_depth: usize, pending_bytes: usize) {
+        self.queue_depth
+            .store(queue_depth as u64, Ordering::Relaxed);
+        self.pending_bytes
+            .store(pending_bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the time it took to write the last buffered bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `latency` - The time
+    pub fn set_send_latency(&self, latency: Duration) {
+        self.last_send_latency_us
+            .store(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the lagging status of the player
+    ///
+    /// # Arguments
+    ///
+    /// * `lagging` - True if the player is lagging behind the stream
+    pub fn set_lagging(&self, lagging: bool) {
+        self.lagging.store(lagging, Ordering::Relaxed);
+    }
+
+    /// Gets a snapshot of the diagnostics
+    pub fn snapshot(&self) -> PlayerLagSnapshot {
+        PlayerLagSnapshot {
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            pending_bytes: self.pending_bytes.load(Ordering::Relaxed),
+            last_send_latency_ms: self.last_send_latency_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            lagging: self.lagging.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Change of the lagging status of a player
+#[derive(Debug, PartialEq)]
+pub enum PlayerLagChange {
+    /// The player started lagging, and stayed lagging for the given time
+    Lagging(Duration),
+
+    /// The player is no longer lagging
+    Recovered,
+}
+
+/// Detector of the players staying behind the stream
+pub struct PlayerLagDetector {
+    /// Min number of packets waiting to be sent to consider the player lagging (0 to disable)
+    threshold_packets: u64,
+
+    /// Time the player must stay lagging before reporting it
+    threshold_duration: Duration,
+
+    /// Time the player started lagging
+    lagging_since: Option<Instant>,
+
+    /// True if the current lag was reported
+    reported: bool,
+}
+
+impl PlayerLagDetector {
+    /// Creates new PlayerLagDetector
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_packets` - Min number of packets waiting to be sent to consider the player lagging (0 to disable)
+    /// * `threshold_seconds` - Time the player must stay lagging before reporting it (seconds)
+    pub fn new(threshold_packets: u32, threshold_seconds: u32) -> PlayerLagDetector {
+        PlayerLagDetector {
+            threshold_packets: threshold_packets as u64,
+            threshold_duration: Duration::from_secs(threshold_seconds as u64),
+            lagging_since: None,
+            reported: false,
+        }
+    }
+
+    /// Checks the number of packets waiting to be sent to the player
+    ///
+    /// # Arguments
+    ///
+    /// * `queue_depth` - Number of packets waiting to be sent
+    /// * `now` - The current time
+    ///
+    /// # Return value
+    ///
+    /// Returns the change of the lagging status to report, if any
+    pub fn check(&mut self, queue_depth: usize, now: Instant) -> Option<PlayerLagChange> {
+        if self.threshold_packets == 0 {
+            return None;
+        }
+
+        if (queue_depth as u64) < self.threshold_packets {
+            self.lagging_since = None;
+
+            if self.reported {
+                self.reported = false;
+                return Some(PlayerLagChange::Recovered);
+            }
+
+            return None;
+        }
+
+        let since = *self.lagging_since.get_or_insert(now);
+        let lagging_time = now.duration_since(since);
+
+        if !self.reported && lagging_time >= self.threshold_duration {
+            self.reported = true;
+            return Some(PlayerLagChange::Lagging(lagging_time));
+        }
+
+        None
+    }
+
+    /// Resets the detector, when the player stops receiving the stream
+    ///
+    /// # Return value
+    ///
+    /// Returns the change of the lagging status to report, if any
+    pub fn reset(&mut self) -> Option<PlayerLagChange> {
+        self.lagging_since = None;
+
+        if self.reported {
+            self.reported = false;
+            return Some(PlayerLagChange::Recovered);
+        }
+
+        None
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_lag_detector() {
+        let mut detector = PlayerLagDetector::new(10, 2);
+        let start = Instant::now();
+
+        assert_eq!(detector.check(5, start), None);
+        assert_eq!(detector.check(10, start), None);
+        assert_eq!(detector.check(20, start + Duration::from_secs(1)), None);
+        assert_eq!(
+            detector.check(20, start + Duration::from_secs(2)),
+            Some(PlayerLagChange::Lagging(Duration::from_secs(2)))
+        );
+
+        // Reported once
+
+        assert_eq!(detector.check(20, start + Duration::from_secs(3)), None);
+        assert_eq!(
+            detector.check(0, start + Duration::from_secs(4)),
+            Some(PlayerLagChange::Recovered)
+        );
+
+        // Short lags are not reported
+
+        assert_eq!(detector.check(20, start + Duration::from_secs(5)), None);
+        assert_eq!(detector.check(0, start + Duration::from_secs(6)), None);
+        assert_eq!(detector.check(20, start + Duration::from_secs(7)), None);
+        assert_eq!(detector.reset(), None);
+
+        // Disabled
+
+        let mut detector = PlayerLagDetector::new(0, 0);
+
+        assert_eq!(detector.check(1000, start), None);
+    }
+}
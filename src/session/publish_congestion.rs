@@ -0,0 +1,240 @@
+// Detection of the congestion of the publishers
+
+/// Congestion detected for a publisher
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PublishCongestion {
+    /// The input bit rate exceeded the cap
+    BitRate {
+        /// Bit rate (bits per second)
+        bit_rate: u64,
+    },
+
+    /// The received media is delayed, compared to the elapsed time
+    Lag {
+        /// Delay (milliseconds)
+        lag_ms: i64,
+    },
+}
+
+impl PublishCongestion {
+    /// Gets the description to send to the publisher
+    pub fn description(&self) -> String {
+        match self {
+            PublishCongestion::BitRate { bit_rate } => format!(
+                "Bit rate too high: {} kbps. Reduce the bit rate of the stream.",
+                bit_rate / 1000
+            ),
+            PublishCongestion::Lag { lag_ms } => format!(
+                "Insufficient bandwidth: The stream is delayed by {} ms. Reduce the bit rate of the stream.",
+                lag_ms
+            ),
+        }
+    }
+}
+
+/// Detector of the congestion of a publisher,
+/// in order to notify encoders with adaptive bit rate
+pub struct PublishCongestionDetector {
+    /// Bit rate cap (bits per second, 0 to disable)
+    max_bit_rate: u64,
+
+    /// Max delay of the received media (milliseconds, 0 to disable)
+    max_lag_ms: i64,
+
+    /// Time the bit rate must exceed the cap,
+    /// and min time between notifications (milliseconds)
+    sustained_ms: i64,
+
+    /// Time the bit rate started exceeding the cap (Unix milliseconds)
+    over_cap_since: Option<i64>,
+
+    /// Reference to compute the delay: time (Unix milliseconds) and clock of the most advanced message
+    clock_base: Option<(i64, i64)>,
+
+    /// Time of the last notification (Unix milliseconds)
+    last_notification: Option<i64>,
+}
+
+impl PublishCongestionDetector {
+    /// Creates new PublishCongestionDetector
+    ///
+    /// # Arguments
+    ///
+    /// * `max_kbps` - Bit rate cap (kilobits per second, 0 to disable)
+    /// * `max_lag_ms` - Max delay of the received media (milliseconds, 0 to disable)
+    /// * `sustained_seconds` - Time the bit rate must exceed the cap, and min time between notifications (seconds)
+    pub fn new(
+        max_kbps: u32,
+        max_lag_ms: u32,
+        sustained_seconds: u32,
+    ) -> PublishCongestionDetector {
+        PublishCongestionDetector {
+            max_bit_rate: (max_kbps as u64) * 1000,
+            max_lag_ms: max_lag_ms as i64,
+            sustained_ms: (sustained_seconds as i64) * 1000,
+            over_cap_since: None,
+            clock_base: None,
+            last_notification: None,
+        }
+    }
+
+    /// Checks if the detector is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.max_bit_rate > 0 || self.max_lag_ms > 0
+    }
+
+    /// Resets the detector, when the publisher stops publishing
+    pub fn reset(&mut self) {
+        self.over_cap_since = None;
+        self.clock_base = None;
+        self.last_notification = None;
+    }
+
+    /// Checks the input bit rate, when measured
+    ///
+    /// # Arguments
+    ///
+    /// * `bit_rate` - The input bit rate (bits per second)
+    /// * `now` - The current time (Unix milliseconds)
+    ///
+    /// # Return value
+    ///
+    /// Returns the congestion, if the publisher must be notified
+    pub fn check_bit_rate(&mut self, bit_rate: u64, now: i64) -> Option<PublishCongestion> {
+        if self.max_bit_rate == 0 || bit_rate <= self.max_bit_rate {
+            self.over_cap_since = None;
+            return None;
+        }
+
+        let since = *self.over_cap_since.get_or_insert(now);
+
+        if now - since < self.sustained_ms {
+            return None;
+        }
+
+        self.notify(PublishCongestion::BitRate { bit_rate }, now)
+    }
+
+    /// Checks the clock of a received media message
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - Clock value of the message (milliseconds)
+    /// * `now` - The current time (Unix milliseconds)
+    ///
+    /// # Return value
+    ///
+    /// Returns the congestion, if the publisher must be notified
+    pub fn check_clock(&mut self, clock: i64, now: i64) -> Option<PublishCongestion> {
+        if self.max_lag_ms == 0 {
+            return None;
+        }
+
+        let (base_time, base_clock) = *self.clock_base.get_or_insert((now, clock));
+
+        let lag_ms = (now - base_time) - (clock - base_clock);
+
+        if lag_ms <= 0 {
+            // Received ahead of the reference (or the clock jumped)
+            self.clock_base = Some((now, clock));
+            return None;
+        }
+
+        if lag_ms < self.max_lag_ms {
+            return None;
+        }
+
+        self.notify(PublishCongestion::Lag { lag_ms }, now)
+    }
+
+    /// Limits the notifications to one per interval
+    ///
+    /// # Arguments
+    ///
+    /// * `congestion` - The detected congestion
+    /// * `now` - The current time (Unix milliseconds)
+    fn notify(&mut self, congestion: PublishCongestion, now: i64) -> Option<PublishCongestion> {
+        if self
+            .last_notification
+            .is_some_and(|t| now - t < self.sustained_ms)
+        {
+            return None;
+        }
+
+        self.last_notification = Some(now);
+
+        Some(congestion)
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_congestion_bit_rate() {
+        let mut detector = PublishCongestionDetector::new(1000, 0, 5);
+
+        assert!(detector.is_enabled());
+
+        assert_eq!(detector.check_bit_rate(900_000, 0), None);
+        assert_eq!(detector.check_bit_rate(3_000_000, 1000), None);
+        assert_eq!(detector.check_bit_rate(3_000_000, 5000), None);
+        assert_eq!(
+            detector.check_bit_rate(3_000_000, 6000),
+            Some(PublishCongestion::BitRate {
+                bit_rate: 3_000_000
+            })
+        );
+
+        // Not notified again until the interval passes
+
+        assert_eq!(detector.check_bit_rate(3_000_000, 7000), None);
+        assert!(detector.check_bit_rate(3_000_000, 11000).is_some());
+
+        // Must be sustained again after going under the cap
+
+        assert_eq!(detector.check_bit_rate(500_000, 12000), None);
+        assert_eq!(detector.check_bit_rate(3_000_000, 20000), None);
+
+        // Disabled
+
+        let mut detector = PublishCongestionDetector::new(0, 0, 5);
+
+        assert!(!detector.is_enabled());
+        assert_eq!(detector.check_bit_rate(u64::MAX, 0), None);
+        assert_eq!(detector.check_bit_rate(u64::MAX, 10000), None);
+    }
+
+    #[test]
+    fn test_publish_congestion_lag() {
+        let mut detector = PublishCongestionDetector::new(0, 2000, 5);
+
+        // Received in real time
+
+        assert_eq!(detector.check_clock(0, 1000), None);
+        assert_eq!(detector.check_clock(1000, 2000), None);
+
+        // Burst ahead of time moves the reference
+
+        assert_eq!(detector.check_clock(3000, 2500), None);
+
+        // Falling behind
+
+        assert_eq!(detector.check_clock(4000, 4000), None);
+        assert_eq!(
+            detector.check_clock(3500, 5000),
+            Some(PublishCongestion::Lag { lag_ms: 2000 })
+        );
+        assert_eq!(detector.check_clock(5000, 6000), None);
+
+        // Reset when the publisher stops
+
+        detector.reset();
+
+        assert_eq!(detector.check_clock(0, 20000), None);
+        assert_eq!(detector.check_clock(500, 20500), None);
+    }
+}
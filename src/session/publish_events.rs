@@ -0,0 +1,322 @@
+// Logic to report events of a published stream
+
+use crate::{
+    callback::{
+        make_codecs_callback, make_keyframe_warning_callback, make_metadata_callback,
+        make_snapshot_callback, CallbackCorrelation,
+    },
+    control::ControlKeyValidationRequest,
+    log::Logger,
+    log_debug, log_warning,
+    record::save_snapshot,
+    rtmp::StreamMetadata,
+    server::{RtmpPublisherInfo, RtmpServerContext},
+};
+
+use super::SessionReadThreadContext;
+
+/// Information of the stream being published
+struct PublishedStreamInfo {
+    /// The channel
+    channel: String,
+
+    /// The stream key
+    key: String,
+
+    /// The stream ID
+    stream_id: String,
+
+    /// Identifiers of the session and the publication
+    correlation: CallbackCorrelation,
+
+    /// Media accepted for the stream
+    media_mode: &'static str,
+
+    /// The publisher (IP address and client information)
+    publisher: Option<RtmpPublisherInfo>,
+}
+
+/// Gets the information of the stream being published by the session
+///
+/// # Arguments
+///
+/// * `session_context` - The session context
+///
+/// # Return value
+///
+/// Returns the stream information, or None if the session is not publishing
+/// or the stream is pulled from another node of the cluster
+async fn get_published_stream_info(
+    session_context: &SessionReadThreadContext,
+) -> Option<PublishedStreamInfo> {
+    let channel_status_mu = session_context.read_status.channel_status.as_ref()?;
+
+    let session_status = session_context.status.lock().await;
+
+    let channel = session_status.channel.clone()?;
+
+    // Only the hash of the key is kept in the channel status
+
+    let key = session_status.key.clone().unwrap_or_default();
+
+    drop(session_status);
+
+    let channel_status = channel_status_mu.lock().await;
+
+    if channel_status.relay_origin.is_some() {
+        return None; // Reported by the node publishing the stream
+    }
+
+    let stream_id = match &channel_status.stream_id {
+        Some(i) => i.clone(),
+        None => "".to_string(),
+    };
+
+    let correlation = CallbackCorrelation {
+        session_id: session_context.id,
+        publish_id: channel_status.publish_id.clone().unwrap_or_default(),
+    };
+
+    Some(PublishedStreamInfo {
+        channel,
+        key,
+        stream_id,
+        correlation,
+        media_mode: channel_status.flags.media.as_str(),
+        publisher: channel_status.publisher_info.clone(),
+    })
+}
+
+/// Reports the identified codecs of the stream being published
+/// to the control server or the callback URL.
+/// The callback is made in a separate task, so the session is not blocked.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `audio_codec` - The audio codec, if identified
+/// * `video_codec` - The video codec, if identified
+pub async fn report_publish_codecs(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionReadThreadContext,
+    audio_codec: Option<&'static str>,
+    video_codec: Option<&'static str>,
+) {
+    let info = match get_published_stream_info(session_context).await {
+        Some(i) => i,
+        None => {
+            return;
+        }
+    };
+
+    match &server_context.control_key_validator_sender {
+        Some(sender) => {
+            // Notify control server
+            _ = sender
+                .send(ControlKeyValidationRequest::StreamCodecs {
+                    channel: info.channel,
+                    stream_id: info.stream_id,
+                    audio_codec: audio_codec.map(|c| c.to_string()),
+                    video_codec: video_codec.map(|c| c.to_string()),
+                    media_mode: info.media_mode.to_string(),
+                    publisher: info.publisher,
+                })
+                .await;
+        }
+        None => {
+            // Callback
+            let logger = logger.make_child_logger("");
+            let server_context = server_context.clone();
+
+            tokio::spawn(async move {
+                make_codecs_callback(
+                    &logger,
+                    server_context.config.get_callback_config(&info.channel),
+                    &info.channel,
+                    &info.key,
+                    &info.correlation,
+                    &info.stream_id,
+                    audio_codec,
+                    video_codec,
+                    info.media_mode,
+                )
+                .await;
+            });
+        }
+    }
+}
+
+/// Reports the metadata of the stream being published
+/// to the control server or the callback URL.
+/// The callback is made in a separate task, so the session is not blocked.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `metadata` - The stream metadata
+pub async fn report_publish_metadata(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionReadThreadContext,
+    metadata: StreamMetadata,
+) {
+    let info = match get_published_stream_info(session_context).await {
+        Some(i) => i,
+        None => {
+            return;
+        }
+    };
+
+    match &server_context.control_key_validator_sender {
+        Some(sender) => {
+            // Notify control server
+            _ = sender
+                .send(ControlKeyValidationRequest::StreamMetadataChange {
+                    channel: info.channel,
+                    stream_id: info.stream_id,
+                    metadata,
+                    publisher: info.publisher,
+                })
+                .await;
+        }
+        None => {
+            // Callback
+            let logger = logger.make_child_logger("");
+            let server_context = server_context.clone();
+
+            tokio::spawn(async move {
+                make_metadata_callback(
+                    &logger,
+                    server_context.config.get_callback_config(&info.channel),
+                    &info.channel,
+                    &info.key,
+                    &info.correlation,
+                    &info.stream_id,
+                    metadata,
+                )
+                .await;
+            });
+        }
+    }
+}
+
+/// Reports that the interval between key frames of the stream being published
+/// exceeded the warning threshold, to the control server or the callback URL.
+/// The callback is made in a separate task, so the session is not blocked.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `keyframe_interval` - The measured interval between key frames (milliseconds)
+pub async fn report_publish_keyframe_warning(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionReadThreadContext,
+    keyframe_interval: i64,
+) {
+    let info = match get_published_stream_info(session_context).await {
+        Some(i) => i,
+        None => {
+            return;
+        }
+    };
+
+    let threshold = server_context.config.keyframe_interval_warning_ms as i64;
+
+    match &server_context.control_key_validator_sender {
+        Some(sender) => {
+            // Notify control server
+            _ = sender
+                .send(ControlKeyValidationRequest::KeyframeIntervalWarning {
+                    channel: info.channel,
+                    stream_id: info.stream_id,
+                    keyframe_interval,
+                    threshold,
+                    publisher: info.publisher,
+                })
+                .await;
+        }
+        None => {
+            // Callback
+            let logger = logger.make_child_logger("");
+            let server_context = server_context.clone();
+
+            tokio::spawn(async move {
+                make_keyframe_warning_callback(
+                    &logger,
+                    server_context.config.get_callback_config(&info.channel),
+                    &info.channel,
+                    &info.key,
+                    &info.correlation,
+                    &info.stream_id,
+                    keyframe_interval,
+                    threshold,
+                )
+                .await;
+            });
+        }
+    }
+}
+
+/// Reports a snapshot of the stream being published,
+/// storing it and / or sending it to the snapshot callback URL.
+/// The snapshot is processed in a separate task, so the session is not blocked.
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `session_context` - The session context
+/// * `snapshot` - The snapshot (FLV)
+pub async fn report_publish_snapshot(
+    logger: &Logger,
+    server_context: &RtmpServerContext,
+    session_context: &SessionReadThreadContext,
+    snapshot: Vec<u8>,
+) {
+    let info = match get_published_stream_info(session_context).await {
+        Some(i) => i,
+        None => {
+            return;
+        }
+    };
+
+    let logger = logger.make_child_logger("");
+    let server_context = server_context.clone();
+
+    tokio::spawn(async move {
+        let config = &server_context.config.snapshots;
+
+        if !config.path.is_empty() {
+            match save_snapshot(config, &info.channel, &snapshot).await {
+                Ok(path) => {
+                    log_debug!(logger, format!("Snapshot stored: {}", path));
+                }
+                Err(e) => {
+                    log_warning!(logger, format!("Could not store snapshot: {}", e));
+                }
+            }
+        }
+
+        if !config.callback_url.is_empty() {
+            make_snapshot_callback(
+                &logger,
+                server_context.config.get_callback_config(&info.channel),
+                &config.callback_url,
+                &info.channel,
+                &info.key,
+                &info.correlation,
+                &info.stream_id,
+                snapshot,
+            )
+            .await;
+        }
+    });
+}
@@ -0,0 +1,69 @@
+// Periodic statistics sent to the publisher
+
+use std::time::Duration;
+
+use crate::server::RtmpServerContext;
+
+use super::{RtmpSessionMessage, SessionReadThreadContext};
+
+/// Creates a task to periodically send the statistics of the channel to the publisher,
+/// so the encoder can display them (eg: in an overlay).
+/// The task ends when the session stops publishing.
+///
+/// # Arguments
+///
+/// * `server_context` - The server context
+/// * `session_context` - The session context, after the publisher is set
+pub fn spawn_task_send_publish_statistics(
+    server_context: &RtmpServerContext,
+    session_context: &SessionReadThreadContext,
+) {
+    let interval_seconds = server_context.config.publish_statistics_interval_seconds;
+
+    if interval_seconds == 0 {
+        return;
+    }
+
+    let channel_status = match &session_context.read_status.channel_status {
+        Some(c) => c.clone(),
+        None => {
+            return;
+        }
+    };
+
+    let session_id = session_context.id;
+    let session_msg_sender = session_context.session_msg_sender.clone();
+
+    tokio::spawn(async move {
+        let publish_id = channel_status.lock().await.publish_id.clone();
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval_seconds as u64)).await;
+
+            let channel_status_v = channel_status.lock().await;
+
+            if channel_status_v.publisher_id != Some(session_id)
+                || channel_status_v.publish_id != publish_id
+            {
+                return; // Stopped publishing
+            }
+
+            let viewers = channel_status_v.players.len();
+            let bit_rate = channel_status_v
+                .publish_stats
+                .as_ref()
+                .map(|s| s.bit_rate())
+                .unwrap_or(0);
+
+            drop(channel_status_v);
+
+            if session_msg_sender
+                .send(RtmpSessionMessage::PublishStatistics { viewers, bit_rate })
+                .await
+                .is_err()
+            {
+                return; // Session ended
+            }
+        }
+    });
+}
@@ -1,26 +1,82 @@
 // RTMP session status model
 
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Instant,
+};
 
-use chrono::Utc;
 use tokio::sync::Mutex;
 
 use crate::{
     rtmp::{RtmpPacket, RTMP_MIN_CHUNK_SIZE},
-    server::RtmpChannelStatus,
+    server::{PublishMediaMode, RtmpChannelStatus},
 };
 
-use super::RtmpSessionMessage;
+use super::{GopReplayBlobCache, RtmpPacketReceiver, RtmpSessionMessage, SessionBandwidth};
 
-/// Status of the session playing a stream
-#[derive(Clone)]
-pub struct RtmpSessionPlayStatus {
-    /// True if the session is player for the channel
-    pub is_player: bool,
+/// Max number of RTMP streams a session can create
+pub const RTMP_SESSION_MAX_STREAMS: usize = 16;
 
-    /// ID of the RTMP stream used for playing
-    pub play_stream_id: u32,
+/// Role of a RTMP stream created by the session
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RtmpSessionStreamRole {
+    /// Created, but not used yet
+    Idle,
+
+    /// Used to play the channel
+    Playing,
+
+    /// Used to publish to the channel
+    Publishing,
+}
+
+/// Max length of the client information fields retained from the connect command
+const RTMP_CONNECT_INFO_MAX_LENGTH: usize = 256;
+
+/// Information sent by the client in the connect command
+#[cfg_attr(not(feature = "control"), allow(dead_code))]
+#[derive(Clone, Default)]
+pub struct RtmpConnectInfo {
+    /// Version of the client software (flashVer)
+    pub flash_ver: Option<String>,
+
+    /// URL of the SWF file of the client (swfUrl)
+    pub swf_url: Option<String>,
+
+    /// URL of the server requested by the client (tcUrl)
+    pub tc_url: Option<String>,
+}
 
+impl RtmpConnectInfo {
+    /// Sanitizes a field of the connect command, in order to retain it,
+    /// removing control characters and limiting its length
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value of the field
+    ///
+    /// # Return value
+    ///
+    /// Returns the sanitized value, or None if empty
+    pub fn sanitize_field(value: &str) -> Option<String> {
+        let sanitized: String = value
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(RTMP_CONNECT_INFO_MAX_LENGTH)
+            .collect();
+
+        if sanitized.is_empty() {
+            None
+        } else {
+            Some(sanitized)
+        }
+    }
+}
+
+/// Options of the session playing a stream
+#[derive(Clone)]
+pub struct RtmpSessionPlayStatus {
     /// True to receive audio
     pub receive_audio: bool,
 
@@ -29,17 +85,24 @@ pub struct RtmpSessionPlayStatus {
 
     /// Receive GOP cache?
     pub receive_gop: bool,
+
+    /// Max duration of the playback, as media time (milliseconds)
+    /// None for unlimited playback
+    pub duration: Option<i64>,
+
+    /// True to send the NetStream.Play.Reset status on play start
+    pub reset: bool,
 }
 
 impl RtmpSessionPlayStatus {
     /// Creates new instance of RtmpSessionPlayStatus
     pub fn new() -> RtmpSessionPlayStatus {
         RtmpSessionPlayStatus {
-            is_player: false,
-            play_stream_id: 0,
             receive_audio: true,
             receive_video: true,
             receive_gop: true,
+            duration: None,
+            reset: true,
         }
     }
 }
@@ -52,23 +115,29 @@ pub struct RtmpSessionStatus {
     /// True if the session was killed
     pub killed: bool,
 
+    /// Channel requested on connect (RTMP app)
+    pub app: Option<String>,
+
+    /// Client information sent on connect
+    pub connect_info: RtmpConnectInfo,
+
     /// Channel
     pub channel: Option<String>,
 
     /// Key
     pub key: Option<String>,
 
-    /// The player status
+    /// The player options
     pub play_status: RtmpSessionPlayStatus,
 
-    /// True if the session is a publisher for a channel
-    pub is_publisher: bool,
+    /// RTMP streams created by the session (stream ID -> role)
+    pub streams: HashMap<u32, RtmpSessionStreamRole>,
 
-    /// ID of the RTMP stream used for publishing
-    pub publish_stream_id: u32,
+    /// Last stream ID given to the client
+    pub last_stream_id: u32,
 
-    /// Current number of streams
-    pub streams: usize,
+    /// Buffer length set by the client (milliseconds)
+    pub buffer_length: u32,
 }
 
 impl RtmpSessionStatus {
@@ -76,15 +145,114 @@ impl RtmpSessionStatus {
     pub fn new() -> RtmpSessionStatus {
         RtmpSessionStatus {
             killed: false,
+            app: None,
+            connect_info: RtmpConnectInfo::default(),
             channel: None,
             connect_time: 0,
             key: None,
             play_status: RtmpSessionPlayStatus::new(),
-            is_publisher: false,
-            publish_stream_id: 0,
-            streams: 0,
+            streams: HashMap::new(),
+            last_stream_id: 0,
+            buffer_length: 0,
         }
     }
+
+    /// Creates a new RTMP stream
+    ///
+    /// # Return value
+    ///
+    /// Returns the ID of the new stream, or None if the session reached the max number of streams
+    pub fn create_stream(&mut self) -> Option<u32> {
+        if self.streams.len() >= RTMP_SESSION_MAX_STREAMS {
+            return None;
+        }
+
+        loop {
+            self.last_stream_id = self.last_stream_id.wrapping_add(1);
+
+            if self.last_stream_id != 0 && !self.streams.contains_key(&self.last_stream_id) {
+                break;
+            }
+        }
+
+        self.streams
+            .insert(self.last_stream_id, RtmpSessionStreamRole::Idle);
+
+        Some(self.last_stream_id)
+    }
+
+    /// Gets the role of a RTMP stream
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    ///
+    /// # Return value
+    ///
+    /// Returns the role, or None if the stream was not created
+    pub fn stream_role(&self, stream_id: u32) -> Option<RtmpSessionStreamRole> {
+        self.streams.get(&stream_id).copied()
+    }
+
+    /// Sets the role of a RTMP stream,
+    /// adding it if it was not created
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    /// * `role` - The role
+    pub fn set_stream_role(&mut self, stream_id: u32, role: RtmpSessionStreamRole) {
+        self.streams.insert(stream_id, role);
+    }
+
+    /// Removes a RTMP stream
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - ID of the stream
+    ///
+    /// # Return value
+    ///
+    /// Returns the role the stream had, or None if the stream was not created
+    pub fn remove_stream(&mut self, stream_id: u32) -> Option<RtmpSessionStreamRole> {
+        self.streams.remove(&stream_id)
+    }
+
+    /// Finds the stream with a role
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - The role
+    ///
+    /// # Return value
+    ///
+    /// Returns the ID of the stream, or None if no stream has the role
+    fn find_stream(&self, role: RtmpSessionStreamRole) -> Option<u32> {
+        self.streams
+            .iter()
+            .find(|(_, r)| **r == role)
+            .map(|(id, _)| *id)
+    }
+
+    /// Gets the ID of the stream used for playing, if playing
+    pub fn play_stream_id(&self) -> Option<u32> {
+        self.find_stream(RtmpSessionStreamRole::Playing)
+    }
+
+    /// Gets the ID of the stream used for publishing, if publishing
+    pub fn publish_stream_id(&self) -> Option<u32> {
+        self.find_stream(RtmpSessionStreamRole::Publishing)
+    }
+
+    /// Checks if the session is playing
+    pub fn is_player(&self) -> bool {
+        self.play_stream_id().is_some()
+    }
+
+    /// Checks if the session is publishing
+    pub fn is_publisher(&self) -> bool {
+        self.publish_stream_id().is_some()
+    }
 }
 
 /// Status to maintain only for the read task
@@ -101,12 +269,6 @@ pub struct RtmpSessionReadStatus {
     /// ACK size
     pub ack_size: usize,
 
-    /// Bit rate bytes counter
-    pub bit_rate_bytes: usize,
-
-    /// Bitrate last updated (Unix milliseconds)
-    pub bit_rate_last_update: i64,
-
     /// Channel status (set only when publishing)
     pub channel_status: Option<Arc<Mutex<RtmpChannelStatus>>>,
 }
@@ -119,8 +281,6 @@ impl RtmpSessionReadStatus {
             in_ack_size: 0,
             in_last_ack: 0,
             ack_size: 0,
-            bit_rate_bytes: 0,
-            bit_rate_last_update: Utc::now().timestamp_millis(),
             channel_status: None,
         }
     }
@@ -131,21 +291,34 @@ pub struct RtmpSessionPublishStreamStatus {
     /// Clock value
     pub clock: i64,
 
+    /// Offset added to the timestamps of the packets sent to the players
+    /// (set when the stream is resumed, so the timestamps do not go back)
+    pub timestamp_offset: i64,
+
     /// Video codec
     pub video_codec: u32,
 
+    /// Name of the video codec (once identified)
+    pub video_codec_name: Option<&'static str>,
+
     /// AVC sequence header
     pub avc_sequence_header: Arc<Vec<u8>>,
 
     /// Audio codec
     pub audio_codec: u32,
 
+    /// Name of the audio codec (once identified)
+    pub audio_codec_name: Option<&'static str>,
+
     /// AAC sequence header
     pub aac_sequence_header: Arc<Vec<u8>>,
 
     /// Metadata
     pub metadata: Arc<Vec<u8>>,
 
+    /// Version of the metadata (see RtmpChannelStatus::metadata_version)
+    pub metadata_version: u64,
+
     /// GOP cache
     pub gop_cache: VecDeque<Arc<RtmpPacket>>,
 
@@ -154,6 +327,24 @@ pub struct RtmpSessionPublishStreamStatus {
 
     /// Size of the GOP cache
     pub gop_cache_size: usize,
+
+    /// GOP cache serialized for the players joining the stream
+    pub gop_blob_cache: Arc<GopReplayBlobCache>,
+
+    /// Time of the last snapshot taken from the stream
+    pub last_snapshot: Option<Instant>,
+
+    /// Clock value of the last key frame
+    pub last_keyframe_clock: Option<i64>,
+
+    /// True if the last interval between key frames exceeded the warning threshold
+    pub keyframe_interval_exceeded: bool,
+
+    /// True if video packets with composition time offsets (B-frames) were received
+    pub has_composition_time: bool,
+
+    /// Media accepted for the stream (set by the stream flags)
+    pub media_mode: PublishMediaMode,
 }
 
 impl RtmpSessionPublishStreamStatus {
@@ -161,28 +352,54 @@ impl RtmpSessionPublishStreamStatus {
     pub fn new() -> RtmpSessionPublishStreamStatus {
         RtmpSessionPublishStreamStatus {
             clock: 0,
+            timestamp_offset: 0,
             audio_codec: 0,
+            audio_codec_name: None,
             aac_sequence_header: Arc::new(Vec::new()),
             video_codec: 0,
+            video_codec_name: None,
             avc_sequence_header: Arc::new(Vec::new()),
             metadata: Arc::new(Vec::new()),
+            metadata_version: 0,
             gop_cache: VecDeque::new(),
             gop_cache_cleared: false,
             gop_cache_size: 0,
+            gop_blob_cache: Arc::new(GopReplayBlobCache::new()),
+            last_snapshot: None,
+            last_keyframe_clock: None,
+            keyframe_interval_exceeded: false,
+            has_composition_time: false,
+            media_mode: PublishMediaMode::All,
         }
     }
 
     /// Gets message to wake players
-    pub fn get_play_start_message(&self) -> RtmpSessionMessage {
+    ///
+    /// # Arguments
+    ///
+    /// * `packet_receiver` - Receiver for the packets sent after the GOP cache
+    /// * `low_latency` - True if the player requested the low latency mode
+    /// * `publisher_bandwidth` - Bandwidth of the publisher, to limit the bit rate sent to the player
+    pub fn get_play_start_message(
+        &self,
+        packet_receiver: RtmpPacketReceiver,
+        low_latency: bool,
+        publisher_bandwidth: Option<Arc<SessionBandwidth>>,
+    ) -> RtmpSessionMessage {
         let copy_of_gop_cache: Vec<Arc<RtmpPacket>> = self.gop_cache.iter().cloned().collect();
 
         RtmpSessionMessage::PlayStart {
             metadata: self.metadata.clone(),
+            metadata_version: self.metadata_version,
             audio_codec: self.audio_codec,
             aac_sequence_header: self.aac_sequence_header.clone(),
             video_codec: self.video_codec,
             avc_sequence_header: self.avc_sequence_header.clone(),
             gop_cache: copy_of_gop_cache,
+            gop_blob_cache: Some(self.gop_blob_cache.clone()),
+            packet_receiver,
+            low_latency,
+            stream_bandwidth: publisher_bandwidth,
         }
     }
 
@@ -192,16 +409,105 @@ impl RtmpSessionPublishStreamStatus {
             self.gop_cache.clear();
             self.gop_cache_cleared = true;
             self.gop_cache_size = 0;
+            self.gop_blob_cache.invalidate();
+        }
+    }
+
+    /// Clears the GOP cache, even if it was already cleared for this stream
+    /// (eg: to remove corrupted packets breaking the new players)
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of removed packets
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub fn force_clear_gop(&mut self) -> usize {
+        let removed = self.gop_cache.len();
+
+        self.gop_cache.clear();
+        self.gop_cache_size = 0;
+        self.gop_blob_cache.invalidate();
+
+        removed
+    }
+
+    /// Records a key frame, checking the interval since the previous one
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - Clock value of the key frame
+    /// * `threshold` - Max interval between key frames (milliseconds, 0 to disable)
+    ///
+    /// # Return value
+    ///
+    /// Returns the interval if it exceeds the threshold and the previous one did not,
+    /// so the warning is only sent once until the interval goes back under the threshold
+    pub fn record_keyframe(&mut self, clock: i64, threshold: i64) -> Option<i64> {
+        let last_clock = self.last_keyframe_clock.replace(clock)?;
+
+        if threshold <= 0 || clock < last_clock {
+            return None;
+        }
+
+        let interval = clock - last_clock;
+
+        if interval <= threshold {
+            self.keyframe_interval_exceeded = false;
+            return None;
+        }
+
+        if self.keyframe_interval_exceeded {
+            return None;
         }
+
+        self.keyframe_interval_exceeded = true;
+
+        Some(interval)
     }
 
     /// Gets message to resume players
-    pub fn get_player_resume_message(&self) -> RtmpSessionMessage {
+    ///
+    /// # Arguments
+    ///
+    /// * `packet_receiver` - Receiver for the packets to send to the player
+    pub fn get_player_resume_message(
+        &self,
+        packet_receiver: RtmpPacketReceiver,
+    ) -> RtmpSessionMessage {
         RtmpSessionMessage::Resume {
             audio_codec: self.audio_codec,
             aac_sequence_header: self.aac_sequence_header.clone(),
             video_codec: self.video_codec,
             avc_sequence_header: self.avc_sequence_header.clone(),
+            packet_receiver,
         }
     }
 }
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_keyframe() {
+        let mut status = RtmpSessionPublishStreamStatus::new();
+
+        assert_eq!(status.record_keyframe(0, 2000), None);
+        assert_eq!(status.record_keyframe(2000, 2000), None);
+        assert_eq!(status.record_keyframe(6000, 2000), Some(4000));
+
+        // Only warned once while exceeding the threshold
+
+        assert_eq!(status.record_keyframe(10000, 2000), None);
+
+        // Warned again after going back under the threshold
+
+        assert_eq!(status.record_keyframe(11000, 2000), None);
+        assert_eq!(status.record_keyframe(15000, 2000), Some(4000));
+
+        // Disabled
+
+        assert_eq!(status.record_keyframe(30000, 0), None);
+    }
+}
@@ -0,0 +1,260 @@
+// Egress rate limiting for players
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::SessionBandwidth;
+
+/// Max burst allowed by the rate limiter (seconds of the rate)
+const EGRESS_THROTTLE_BURST_SECONDS: f64 = 1.0;
+
+/// Rate limiter of the bytes sent to a player (token bucket)
+pub struct EgressThrottle {
+    /// Max bit rate (bits per second). 0 for unlimited
+    max_bit_rate: u64,
+
+    /// Max bit rate, relative to the bit rate of the stream (percent). 0 for unlimited
+    max_stream_bit_rate_percent: u32,
+
+    /// Bandwidth of the publisher of the stream being played,
+    /// to measure the input bit rate of the stream (None if not playing a published stream)
+    stream_bandwidth: Option<Arc<SessionBandwidth>>,
+
+    /// Available tokens (bytes). Negative if the sent bytes exceeded the rate
+    tokens: f64,
+
+    /// Time of the last refill
+    last_refill: Option<Instant>,
+}
+
+impl EgressThrottle {
+    /// Creates new EgressThrottle
+    ///
+    /// # Arguments
+    ///
+    /// * `max_kbps` - Max bit rate (kilobits per second). 0 for unlimited
+    /// * `max_stream_bit_rate_percent` - Max bit rate, relative to the bit rate of the stream (percent). 0 for unlimited
+    pub fn new(max_kbps: u32, max_stream_bit_rate_percent: u32) -> EgressThrottle {
+        EgressThrottle {
+            max_bit_rate: (max_kbps as u64) * 1000,
+            max_stream_bit_rate_percent,
+            stream_bandwidth: None,
+            tokens: 0.0,
+            last_refill: None,
+        }
+    }
+
+    /// Checks if the rate limiter is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.max_bit_rate > 0 || self.max_stream_bit_rate_percent > 0
+    }
+
+    /// Sets the bandwidth of the publisher of the stream being played.
+    /// The relative limit is based on the input bit rate of the stream,
+    /// since the bytes sent to the player are already limited.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_bandwidth` - The bandwidth of the publisher (None if not playing a published stream)
+    pub fn set_stream_bandwidth(&mut self, stream_bandwidth: Option<Arc<SessionBandwidth>>) {
+        self.stream_bandwidth = stream_bandwidth;
+    }
+
+    /// Gets the input bit rate of the stream being played (bits per second). 0 if not measured
+    fn stream_bit_rate(&self) -> u64 {
+        match &self.stream_bandwidth {
+            Some(b) => b.input.bit_rate(),
+            None => 0,
+        }
+    }
+
+    /// Gets the current rate limit (bytes per second)
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_bit_rate` - Measured bit rate of the stream (bits per second). 0 if not measured yet
+    ///
+    /// # Return value
+    ///
+    /// Returns the rate limit, or None if unlimited
+    fn rate_limit(&self, stream_bit_rate: u64) -> Option<f64> {
+        let relative_bit_rate = if self.max_stream_bit_rate_percent > 0 && stream_bit_rate > 0 {
+            stream_bit_rate * (self.max_stream_bit_rate_percent as u64) / 100
+        } else {
+            0
+        };
+
+        let bit_rate = match (self.max_bit_rate, relative_bit_rate) {
+            (0, 0) => {
+                return None;
+            }
+            (0, r) | (r, 0) => r,
+            (a, r) => a.min(r),
+        };
+
+        Some(bit_rate.max(8) as f64 / 8.0)
+    }
+
+    /// Gets the current rate limit (bits per second),
+    /// using the measured bit rate of the stream
+    ///
+    /// # Return value
+    ///
+    /// Returns the rate limit, or None if unlimited
+    pub fn bit_rate_limit(&self) -> Option<u64> {
+        self.rate_limit(self.stream_bit_rate())
+            .map(|r| (r * 8.0) as u64)
+    }
+
+    /// Consumes tokens for bytes about to be sent
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Number of bytes to send
+    /// * `stream_bit_rate` - Measured bit rate of the stream (bits per second)
+    /// * `now` - The current time
+    ///
+    /// # Return value
+    ///
+    /// Returns the time to wait before sending the bytes
+    fn consume(&mut self, bytes: usize, stream_bit_rate: u64, now: Instant) -> Duration {
+        let rate = match self.rate_limit(stream_bit_rate) {
+            Some(r) => r,
+            None => {
+                self.last_refill = None;
+                return Duration::ZERO;
+            }
+        };
+
+        let capacity = rate * EGRESS_THROTTLE_BURST_SECONDS;
+
+        self.tokens = match self.last_refill {
+            Some(last_refill) => {
+                let elapsed = now.saturating_duration_since(last_refill).as_secs_f64();
+                (self.tokens + elapsed * rate).min(capacity)
+            }
+            None => capacity,
+        };
+        self.last_refill = Some(now);
+
+        self.tokens -= bytes as f64;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+
+    /// Waits until the bytes can be sent, according to the rate limit
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Number of bytes to send
+    pub async fn wait(&mut self, bytes: usize) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let stream_bit_rate = self.stream_bit_rate();
+        let delay = self.consume(bytes, stream_bit_rate, Instant::now());
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_egress_throttle_absolute() {
+        // 80 kbps = 10000 bytes per second
+        let mut throttle = EgressThrottle::new(80, 0);
+        let start = Instant::now();
+
+        // Burst up to 1 second of the rate
+        assert_eq!(throttle.consume(10000, 0, start), Duration::ZERO);
+
+        // Exceeding the rate
+        assert_eq!(throttle.consume(5000, 0, start), Duration::from_millis(500));
+
+        assert_eq!(throttle.bit_rate_limit(), Some(80000));
+
+        // Tokens are refilled with the time
+        assert_eq!(
+            throttle.consume(5000, 0, start + Duration::from_millis(1000)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_egress_throttle_relative() {
+        // 200% of the stream bit rate, up to 800 kbps
+        let mut throttle = EgressThrottle::new(800, 200);
+
+        assert_eq!(throttle.rate_limit(0), Some(100000.0));
+        assert_eq!(throttle.rate_limit(40000), Some(10000.0));
+        assert_eq!(throttle.rate_limit(4000000), Some(100000.0));
+
+        // Relative only
+        throttle = EgressThrottle::new(0, 150);
+
+        assert_eq!(throttle.rate_limit(0), None);
+        assert_eq!(throttle.rate_limit(80000), Some(15000.0));
+
+        // Not measured without a publisher
+        assert_eq!(throttle.bit_rate_limit(), None);
+
+        throttle.set_stream_bandwidth(Some(Arc::new(SessionBandwidth::new())));
+
+        assert_eq!(throttle.bit_rate_limit(), None);
+
+        // Disabled
+        throttle = EgressThrottle::new(0, 0);
+
+        assert!(!throttle.is_enabled());
+        assert_eq!(throttle.bit_rate_limit(), None);
+        assert_eq!(throttle.consume(1000000, 0, Instant::now()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_egress_throttle_relative_steady_state() {
+        // 100% of a stream of 80 kbps = 10000 bytes per second
+        let mut throttle = EgressThrottle::new(0, 100);
+        let start = Instant::now();
+
+        // The player receives the stream as fast as the limit allows,
+        // for 30 seconds, in packets of 1000 bytes.
+        // Since the base is the input bit rate, the limit does not shrink
+        // with the throttled output.
+
+        let mut time = start;
+        let mut sent_per_second: Vec<usize> = vec![0; 30];
+
+        loop {
+            time += throttle.consume(1000, 80000, time);
+
+            let second = time.duration_since(start).as_secs() as usize;
+
+            if second >= sent_per_second.len() {
+                break;
+            }
+
+            sent_per_second[second] += 1000;
+        }
+
+        assert_eq!(throttle.rate_limit(80000), Some(10000.0));
+
+        // After the initial burst, the throughput stays at the limit
+        for sent in &sent_per_second[1..] {
+            assert!((9000..=11000).contains(sent), "Sent: {}", sent);
+        }
+    }
+}
@@ -1,11 +1,18 @@
-use std::io::Error;
+use std::{
+    io::{Error, ErrorKind, IoSlice},
+    ops::Deref,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
     sync::Mutex,
 };
 
-use crate::rtmp::rtmp_make_status_message;
+use crate::rtmp::{rtmp_make_redirect_status_message, rtmp_make_status_message};
+
+use super::{EgressThrottle, OutboundAckLimit, SessionBandwidth};
 
 /// Writes bytes to the session write stream
 ///
@@ -31,6 +38,7 @@ pub async fn session_write_bytes<TW: AsyncWrite + AsyncWriteExt + Send + Sync +
 /// * `level` - Status message level
 /// * `code` - Status code
 /// * `description` - Status description
+/// * `object_encoding` - Object encoding negotiated with the client
 /// * `out_chunk_size` - Chunk size, in order to generate the RTMP packet chunks
 pub async fn send_status_message<TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static>(
     write_stream: &Mutex<TW>,
@@ -38,8 +46,242 @@ pub async fn send_status_message<TW: AsyncWrite + AsyncWriteExt + Send + Sync +
     level: &str,
     code: &str,
     description: Option<&str>,
+    object_encoding: u32,
+    out_chunk_size: usize,
+) -> Result<(), Error> {
+    let msg_bytes = rtmp_make_status_message(
+        stream_id,
+        level,
+        code,
+        description,
+        object_encoding,
+        out_chunk_size,
+    );
+    session_write_bytes(write_stream, &msg_bytes).await
+}
+
+/// Sends RTMP status message to the client, redirecting it to another URL
+///
+/// # Arguments
+///
+/// * `write_stream` - The stream to write to the client
+/// * `stream_id` - Stream ID subject of the status message
+/// * `redirect_url` - The URL to redirect the client to
+/// * `object_encoding` - Object encoding negotiated with the client
+/// * `out_chunk_size` - Chunk size, in order to generate the RTMP packet chunks
+pub async fn send_redirect_status_message<
+    TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static,
+>(
+    write_stream: &Mutex<TW>,
+    stream_id: u32,
+    redirect_url: &str,
+    object_encoding: u32,
     out_chunk_size: usize,
 ) -> Result<(), Error> {
-    let msg_bytes = rtmp_make_status_message(stream_id, level, code, description, out_chunk_size);
+    let msg_bytes =
+        rtmp_make_redirect_status_message(stream_id, redirect_url, object_encoding, out_chunk_size);
     session_write_bytes(write_stream, &msg_bytes).await
 }
+
+/// Size of the write buffer (bytes) that forces a flush
+pub const SESSION_WRITE_BUFFER_FLUSH_SIZE: usize = 64 * 1024;
+
+/// Size of the write buffer (bytes) that forces a flush, for players in low latency mode
+pub const SESSION_WRITE_BUFFER_FLUSH_SIZE_LOW_LATENCY: usize = 4 * 1024;
+
+/// Part of the bytes buffered to be written
+pub enum SessionWritePart {
+    /// Bytes owned by the buffer
+    Owned(Vec<u8>),
+
+    /// Bytes shared with other sessions (eg: the serialized GOP cache)
+    Shared(Arc<Vec<u8>>),
+}
+
+impl Deref for SessionWritePart {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SessionWritePart::Owned(b) => b,
+            SessionWritePart::Shared(b) => b,
+        }
+    }
+}
+
+impl From<Vec<u8>> for SessionWritePart {
+    fn from(bytes: Vec<u8>) -> Self {
+        SessionWritePart::Owned(bytes)
+    }
+}
+
+impl From<Arc<Vec<u8>>> for SessionWritePart {
+    fn from(bytes: Arc<Vec<u8>>) -> Self {
+        SessionWritePart::Shared(bytes)
+    }
+}
+
+/// Buffer to coalesce multiple writes to the session write stream
+/// into a single vectored write
+pub struct SessionWriteBuffer {
+    /// Buffered parts
+    parts: Vec<SessionWritePart>,
+
+    /// Total size of the buffered parts (bytes)
+    size: usize,
+
+    /// Rate limiter of the written bytes
+    throttle: EgressThrottle,
+
+    /// Limit of the bytes sent without acknowledgement
+    ack_limit: OutboundAckLimit,
+
+    /// Size of the buffer that forces a flush
+    flush_size: usize,
+
+    /// Time it took to write the last flushed bytes to the connection
+    last_write_latency: Duration,
+}
+
+impl SessionWriteBuffer {
+    /// Creates new SessionWriteBuffer
+    ///
+    /// # Arguments
+    ///
+    /// * `throttle` - The rate limiter of the written bytes
+    /// * `ack_limit` - The limit of the bytes sent without acknowledgement
+    pub fn new(throttle: EgressThrottle, ack_limit: OutboundAckLimit) -> SessionWriteBuffer {
+        SessionWriteBuffer {
+            parts: Vec::new(),
+            size: 0,
+            throttle,
+            ack_limit,
+            flush_size: SESSION_WRITE_BUFFER_FLUSH_SIZE,
+            last_write_latency: Duration::ZERO,
+        }
+    }
+
+    /// Sets the low latency mode, flushing smaller amounts of bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `low_latency` - True to enable the low latency mode
+    pub fn set_low_latency(&mut self, low_latency: bool) {
+        self.flush_size = if low_latency {
+            SESSION_WRITE_BUFFER_FLUSH_SIZE_LOW_LATENCY
+        } else {
+            SESSION_WRITE_BUFFER_FLUSH_SIZE
+        };
+    }
+
+    /// Adds bytes to the buffer.
+    /// Shared bytes are buffered without copying them.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes to write
+    pub fn push<B: Into<SessionWritePart>>(&mut self, bytes: B) {
+        let bytes: SessionWritePart = bytes.into();
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        self.size += bytes.len();
+        self.parts.push(bytes);
+    }
+
+    /// Sets the bandwidth of the publisher of the stream being played,
+    /// so the rate limit can be relative to the input bit rate of the stream
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_bandwidth` - The bandwidth of the publisher (None if not playing a published stream)
+    pub fn set_stream_bandwidth(&mut self, stream_bandwidth: Option<Arc<SessionBandwidth>>) {
+        self.throttle.set_stream_bandwidth(stream_bandwidth);
+    }
+
+    /// Gets the number of bytes buffered, waiting to be written
+    pub fn pending_bytes(&self) -> usize {
+        self.size
+    }
+
+    /// Gets the time it took to write the last flushed bytes to the connection,
+    /// not including the time waiting for the rate limit or the acknowledgements
+    pub fn last_write_latency(&self) -> Duration {
+        self.last_write_latency
+    }
+
+    /// Gets the current rate limit of the written bytes (bits per second)
+    ///
+    /// # Return value
+    ///
+    /// Returns the rate limit, or None if unlimited
+    pub fn bit_rate_limit(&self) -> Option<u64> {
+        self.throttle.bit_rate_limit()
+    }
+
+    /// Checks if the buffer reached the size to be flushed
+    pub fn should_flush(&self) -> bool {
+        self.size >= self.flush_size
+    }
+
+    /// Writes the buffered bytes to the session write stream, clearing the buffer.
+    /// If the client does not acknowledge the sent bytes in time,
+    /// or the write times out, the write stream is closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `write_stream` - The stream to write to the client
+    pub async fn flush<TW: AsyncWrite + AsyncWriteExt + Send + Sync + Unpin + 'static>(
+        &mut self,
+        write_stream: &Mutex<TW>,
+    ) -> Result<(), Error> {
+        if self.parts.is_empty() {
+            return Ok(());
+        }
+
+        let parts = std::mem::take(&mut self.parts);
+        let size = std::mem::take(&mut self.size);
+
+        self.throttle.wait(size).await;
+
+        if let Err(e) = self.ack_limit.wait().await {
+            let mut write_stream_v = write_stream.lock().await;
+            _ = (*write_stream_v).shutdown().await;
+
+            return Err(e);
+        }
+
+        let mut slices: Vec<IoSlice> = parts.iter().map(|p| IoSlice::new(p)).collect();
+        let mut remaining = &mut slices[..];
+
+        let write_start = Instant::now();
+
+        let mut write_stream_v = write_stream.lock().await;
+
+        while !remaining.is_empty() {
+            let written = match (*write_stream_v).write_vectored(remaining).await {
+                Ok(w) => w,
+                Err(e) => {
+                    if e.kind() == ErrorKind::TimedOut {
+                        // The client stopped reading, close the connection
+                        _ = (*write_stream_v).shutdown().await;
+                    }
+
+                    return Err(e);
+                }
+            };
+
+            if written == 0 {
+                return Err(Error::from(ErrorKind::WriteZero));
+            }
+
+            IoSlice::advance_slices(&mut remaining, written);
+        }
+
+        self.last_write_latency = write_start.elapsed();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+// Timeout of the writes to the client
+
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, IoSlice},
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use tokio::{io::AsyncWrite, time::Sleep};
+
+/// Write stream wrapper failing the writes that make no progress for too long
+/// (eg: the client stopped reading and the socket buffers are full).
+/// Once a write timed out, every following write fails, so the session ends.
+/// The shutdown is also limited by the timeout, but allowed after a write timed out.
+pub struct WriteTimeoutWriter<TW: AsyncWrite + Unpin> {
+    /// The inner write stream
+    inner: TW,
+
+    /// Max time a write can wait for the client (None to wait indefinitely)
+    timeout: Option<Duration>,
+
+    /// Deadline of the pending write
+    deadline: Option<Pin<Box<Sleep>>>,
+
+    /// True if a write timed out
+    timed_out: bool,
+}
+
+impl<TW: AsyncWrite + Unpin> WriteTimeoutWriter<TW> {
+    /// Creates new WriteTimeoutWriter
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The write stream to wrap
+    /// * `timeout_seconds` - Max time a write can wait for the client (seconds). 0 to wait indefinitely
+    pub fn new(inner: TW, timeout_seconds: u32) -> WriteTimeoutWriter<TW> {
+        WriteTimeoutWriter {
+            inner,
+            timeout: if timeout_seconds > 0 {
+                Some(Duration::from_secs(timeout_seconds as u64))
+            } else {
+                None
+            },
+            deadline: None,
+            timed_out: false,
+        }
+    }
+
+    /// Polls an operation of the inner stream, applying the timeout
+    ///
+    /// # Arguments
+    ///
+    /// * `cx` - The task context
+    /// * `op` - The operation
+    fn poll_with_timeout<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        op: impl FnOnce(Pin<&mut TW>, &mut Context<'_>) -> Poll<Result<T, Error>>,
+    ) -> Poll<Result<T, Error>> {
+        if let Poll::Ready(res) = op(Pin::new(&mut self.inner), cx) {
+            self.deadline = None;
+            return Poll::Ready(res);
+        }
+
+        let timeout = match self.timeout {
+            Some(t) => t,
+            None => return Poll::Pending,
+        };
+
+        let deadline = self
+            .deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+
+        ready!(deadline.as_mut().poll(cx));
+
+        self.deadline = None;
+        self.timed_out = true;
+
+        Poll::Ready(Err(Error::from(ErrorKind::TimedOut)))
+    }
+}
+
+impl<TW: AsyncWrite + Unpin> AsyncWrite for WriteTimeoutWriter<TW> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        if self.timed_out {
+            return Poll::Ready(Err(Error::from(ErrorKind::TimedOut)));
+        }
+
+        self.get_mut()
+            .poll_with_timeout(cx, |inner, cx| inner.poll_write(cx, buf))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, Error>> {
+        if self.timed_out {
+            return Poll::Ready(Err(Error::from(ErrorKind::TimedOut)));
+        }
+
+        self.get_mut()
+            .poll_with_timeout(cx, |inner, cx| inner.poll_write_vectored(cx, bufs))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if self.timed_out {
+            return Poll::Ready(Err(Error::from(ErrorKind::TimedOut)));
+        }
+
+        self.get_mut()
+            .poll_with_timeout(cx, |inner, cx| inner.poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut()
+            .poll_with_timeout(cx, |inner, cx| inner.poll_shutdown(cx))
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_timeout_writer() {
+        let (client, server) = tokio::io::duplex(16);
+        let (mut client_read, _client_write) = tokio::io::split(client);
+        let (_server_read, server_write) = tokio::io::split(server);
+
+        let mut writer = WriteTimeoutWriter::new(server_write, 0);
+        writer.timeout = Some(Duration::from_millis(50));
+
+        // The client reads
+
+        writer.write_all(&[1; 16]).await.unwrap();
+
+        let mut buf = [0; 16];
+        client_read.read_exact(&mut buf).await.unwrap();
+
+        // The client stops reading
+
+        writer.write_all(&[1; 16]).await.unwrap();
+
+        let err = writer.write_all(&[1; 16]).await.unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+        // Following writes fail, even if the client reads again
+
+        client_read.read_exact(&mut buf).await.unwrap();
+
+        assert!(writer.write_all(&[1]).await.is_err());
+    }
+}
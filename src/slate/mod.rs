@@ -0,0 +1,7 @@
+// Slate (stream unavailable) playback for idle channels
+
+mod player;
+mod source;
+
+pub use player::*;
+pub use source::*;
@@ -0,0 +1,81 @@
+// Slate playback task
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    record::{FLV_TAG_AUDIO, FLV_TAG_VIDEO},
+    rtmp::{
+        RtmpPacket, RTMP_CHANNEL_AUDIO, RTMP_CHANNEL_VIDEO, RTMP_CHUNK_TYPE_0, RTMP_TYPE_AUDIO,
+        RTMP_TYPE_VIDEO,
+    },
+};
+
+use super::SlateSource;
+
+/// Spawns a task to play the slate in loop,
+/// broadcasting its packets (in real time) to the players receiving it
+///
+/// # Arguments
+///
+/// * `slate` - The slate
+pub fn spawn_task_play_slate(slate: Arc<SlateSource>) {
+    tokio::spawn(async move {
+        let start = Instant::now();
+
+        // Offset of the timestamps, increased on each loop
+        let mut timestamp_offset: i64 = 0;
+
+        loop {
+            for (i, tag) in slate.tags.iter().enumerate() {
+                let timestamp = timestamp_offset.wrapping_add(tag.timestamp);
+
+                tokio::time::sleep_until(start + Duration::from_millis(timestamp.max(0) as u64))
+                    .await;
+
+                let mut packet = RtmpPacket::new_blank();
+
+                packet.header.format = RTMP_CHUNK_TYPE_0;
+
+                let is_key_frame = match tag.tag_type {
+                    FLV_TAG_AUDIO => {
+                        packet.header.channel_id = RTMP_CHANNEL_AUDIO;
+                        packet.header.packet_type = RTMP_TYPE_AUDIO;
+                        false
+                    }
+                    FLV_TAG_VIDEO => {
+                        packet.header.channel_id = RTMP_CHANNEL_VIDEO;
+                        packet.header.packet_type = RTMP_TYPE_VIDEO;
+                        (tag.payload[0] >> 4) & 0x07 == 1
+                    }
+                    _ => {
+                        continue;
+                    }
+                };
+
+                packet.payload = tag.payload.clone();
+                packet.header.length = packet.payload.len();
+                packet.header.timestamp = timestamp;
+
+                let packet = Arc::new(packet);
+
+                // Hold the GOP cache while sending, so new players do not miss the packet
+
+                let mut gop_cache = slate.gop_cache.lock().unwrap();
+
+                if is_key_frame || i == 0 {
+                    gop_cache.clear();
+                }
+
+                gop_cache.push_back(packet.clone());
+
+                _ = slate.packet_sender.send(packet);
+
+                drop(gop_cache);
+            }
+
+            timestamp_offset = timestamp_offset.wrapping_add(slate.duration);
+        }
+    });
+}
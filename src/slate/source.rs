@@ -0,0 +1,178 @@
+// Slate source, loaded from a FLV file
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::broadcast;
+
+use crate::{
+    record::{flv_read_tags, FlvTag, FLV_TAG_AUDIO, FLV_TAG_SCRIPT_DATA, FLV_TAG_VIDEO},
+    rtmp::{rtmp_is_audio_sequence_header, RtmpPacket},
+    session::{RtmpPacketReceiver, RtmpSessionMessage},
+};
+
+/// Size of the buffer of slate packets shared by the players
+const SLATE_PACKET_BUFFER_SIZE: usize = 256;
+
+/// Default duration of the last frame of the slate (milliseconds)
+const SLATE_DEFAULT_FRAME_DURATION: i64 = 40;
+
+/// Slate to play in the channels with no publisher
+pub struct SlateSource {
+    /// Metadata
+    pub metadata: Arc<Vec<u8>>,
+
+    /// Audio codec
+    pub audio_codec: u32,
+
+    /// AAC sequence header
+    pub aac_sequence_header: Arc<Vec<u8>>,
+
+    /// Video codec
+    pub video_codec: u32,
+
+    /// AVC sequence header
+    pub avc_sequence_header: Arc<Vec<u8>>,
+
+    /// Audio and video tags to play in loop
+    pub tags: Vec<FlvTag>,
+
+    /// Duration of the loop (milliseconds)
+    pub duration: i64,
+
+    /// Packets since the last key frame, sent to the new players
+    pub gop_cache: Mutex<VecDeque<Arc<RtmpPacket>>>,
+
+    /// Sender to broadcast the packets to the players
+    pub packet_sender: broadcast::Sender<Arc<RtmpPacket>>,
+}
+
+impl SlateSource {
+    /// Loads the slate from a FLV file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the FLV file
+    ///
+    /// # Return value
+    ///
+    /// Returns the slate, or an error message
+    pub async fn load_from_file(path: &str) -> Result<SlateSource, String> {
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+        let mut metadata: Vec<u8> = Vec::new();
+        let mut audio_codec: u32 = 0;
+        let mut aac_sequence_header: Vec<u8> = Vec::new();
+        let mut video_codec: u32 = 0;
+        let mut avc_sequence_header: Vec<u8> = Vec::new();
+        let mut tags: Vec<FlvTag> = Vec::new();
+
+        for tag in flv_read_tags(&data).map_err(|e| format!("{}: {}", path, e))? {
+            if tag.payload.len() < 2 {
+                continue;
+            }
+
+            match tag.tag_type {
+                FLV_TAG_SCRIPT_DATA if metadata.is_empty() => {
+                    metadata = tag.payload;
+                }
+                FLV_TAG_AUDIO => {
+                    let codec_id = ((tag.payload[0] >> 4) & 0x0f) as u32;
+
+                    if audio_codec == 0 {
+                        audio_codec = codec_id;
+                    }
+
+                    if rtmp_is_audio_sequence_header(&tag.payload) {
+                        aac_sequence_header = tag.payload;
+                    } else {
+                        tags.push(tag);
+                    }
+                }
+                FLV_TAG_VIDEO => {
+                    let frame_type = (tag.payload[0] >> 4) & 0x0f;
+                    let codec_id = (tag.payload[0] & 0x0f) as u32;
+
+                    if video_codec == 0 {
+                        video_codec = codec_id;
+                    }
+
+                    if (codec_id == 7 || codec_id == 12) && frame_type == 1 && tag.payload[1] == 0 {
+                        avc_sequence_header = tag.payload;
+                    } else {
+                        tags.push(tag);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if tags.is_empty() {
+            return Err(format!("{}: No audio or video frames found", path));
+        }
+
+        // Start the loop at the first frame
+
+        let first_timestamp = tags[0].timestamp;
+
+        for tag in tags.iter_mut() {
+            tag.timestamp = tag.timestamp.wrapping_sub(first_timestamp).max(0);
+        }
+
+        let duration = tags[tags.len() - 1].timestamp + SLATE_DEFAULT_FRAME_DURATION;
+
+        let (packet_sender, _) = broadcast::channel(SLATE_PACKET_BUFFER_SIZE);
+
+        Ok(SlateSource {
+            metadata: Arc::new(metadata),
+            audio_codec,
+            aac_sequence_header: Arc::new(aac_sequence_header),
+            video_codec,
+            avc_sequence_header: Arc::new(avc_sequence_header),
+            tags,
+            duration,
+            gop_cache: Mutex::new(VecDeque::new()),
+            packet_sender,
+        })
+    }
+
+    /// Subscribes to the packets of the slate
+    pub fn subscribe_packets(&self) -> RtmpPacketReceiver {
+        self.packet_sender.subscribe()
+    }
+
+    /// Gets message to start playing the slate
+    pub fn get_play_start_message(&self) -> RtmpSessionMessage {
+        // Subscribe while holding the GOP cache, so no packets are lost or repeated
+        let gop_cache = self.gop_cache.lock().unwrap();
+
+        RtmpSessionMessage::PlayStart {
+            metadata: self.metadata.clone(),
+            metadata_version: 0,
+            audio_codec: self.audio_codec,
+            aac_sequence_header: self.aac_sequence_header.clone(),
+            video_codec: self.video_codec,
+            avc_sequence_header: self.avc_sequence_header.clone(),
+            gop_cache: gop_cache.iter().cloned().collect(),
+            gop_blob_cache: None,
+            packet_receiver: self.subscribe_packets(),
+            low_latency: false,
+            stream_bandwidth: None,
+        }
+    }
+
+    /// Gets message to resume playing the slate
+    pub fn get_player_resume_message(&self) -> RtmpSessionMessage {
+        RtmpSessionMessage::Resume {
+            audio_codec: self.audio_codec,
+            aac_sequence_header: self.aac_sequence_header.clone(),
+            video_codec: self.video_codec,
+            avc_sequence_header: self.avc_sequence_header.clone(),
+            packet_receiver: self.subscribe_packets(),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// Trace configuration
+
+use crate::utils::get_env_string;
+
+/// Trace configuration
+#[derive(Clone)]
+pub struct TraceConfiguration {
+    /// Folder to store the trace files
+    pub path: String,
+}
+
+impl TraceConfiguration {
+    /// Loads trace configuration
+    /// from environment variables
+    pub fn load_from_env() -> TraceConfiguration {
+        let path = get_env_string("TRACE_PATH", "traces");
+
+        TraceConfiguration { path }
+    }
+
+    /// Gets the path of a new trace file
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The session ID
+    /// * `timestamp` - Start timestamp of the trace (Unix milliseconds)
+    pub fn get_trace_path(&self, session_id: u64, timestamp: i64) -> String {
+        format!(
+            "{}/{}-{}.jsonl",
+            self.path.trim_end_matches('/'),
+            session_id,
+            timestamp
+        )
+    }
+}
@@ -0,0 +1,314 @@
+// Decoder of the chunks sent to the client, to trace them as messages
+
+use std::collections::HashMap;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::rtmp::{RTMP_MAX_CHUNK_SIZE, RTMP_MIN_CHUNK_SIZE, RTMP_TYPE_SET_CHUNK_SIZE};
+
+/// Message decoded from the chunks
+pub struct TraceMessage {
+    /// Chunk stream ID
+    pub chunk_stream_id: u32,
+
+    /// Packet type
+    pub packet_type: u32,
+
+    /// Message stream ID
+    pub stream_id: u32,
+
+    /// Timestamp
+    pub timestamp: u32,
+
+    /// Payload
+    pub payload: Vec<u8>,
+}
+
+/// Status of a chunk stream
+#[derive(Clone, Default)]
+struct TraceChunkStream {
+    /// Timestamp of the message
+    timestamp: u32,
+
+    /// Last timestamp delta
+    delta: u32,
+
+    /// True if the last message header used an extended timestamp
+    extended: bool,
+
+    /// Message length
+    length: usize,
+
+    /// Packet type
+    packet_type: u32,
+
+    /// Message stream ID
+    stream_id: u32,
+
+    /// Received payload of the message
+    payload: Vec<u8>,
+}
+
+/// Incremental decoder of a chunk stream
+pub struct TraceChunkDecoder {
+    /// Size of the chunks
+    chunk_size: usize,
+
+    /// Received bytes, not decoded yet
+    buffer: Vec<u8>,
+
+    /// Status of the chunk streams
+    streams: HashMap<u32, TraceChunkStream>,
+}
+
+impl TraceChunkDecoder {
+    /// Creates new TraceChunkDecoder
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - Current size of the chunks
+    pub fn new(chunk_size: usize) -> TraceChunkDecoder {
+        TraceChunkDecoder {
+            chunk_size,
+            buffer: Vec::new(),
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Decodes bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes, following the previously decoded ones
+    ///
+    /// # Return value
+    ///
+    /// Returns the completed messages, or an error if the bytes are not valid chunks
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<TraceMessage>, ()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+
+        buffer.extend_from_slice(bytes);
+
+        let mut messages: Vec<TraceMessage> = Vec::new();
+        let mut offset: usize = 0;
+
+        while let Some((consumed, msg)) = self.decode_chunk(&buffer[offset..])? {
+            offset += consumed;
+
+            if let Some(msg) = msg {
+                if msg.packet_type == RTMP_TYPE_SET_CHUNK_SIZE && msg.payload.len() >= 4 {
+                    self.chunk_size = (BigEndian::read_u32(&msg.payload[0..4]) as usize)
+                        .clamp(RTMP_MIN_CHUNK_SIZE, RTMP_MAX_CHUNK_SIZE);
+                }
+
+                messages.push(msg);
+            }
+        }
+
+        buffer.drain(..offset);
+
+        self.buffer = buffer;
+
+        Ok(messages)
+    }
+
+    /// Decodes a chunk
+    ///
+    /// # Arguments
+    ///
+    /// * `b` - The bytes, starting at the chunk
+    ///
+    /// # Return value
+    ///
+    /// Returns None if the chunk is not complete.
+    /// Otherwise, returns the number of consumed bytes and the message, if the chunk completed it.
+    fn decode_chunk(&mut self, b: &[u8]) -> Result<Option<(usize, Option<TraceMessage>)>, ()> {
+        if b.is_empty() {
+            return Ok(None);
+        }
+
+        let format = b[0] >> 6;
+
+        let (chunk_stream_id, mut offset) = match b[0] & 0x3f {
+            0 => {
+                if b.len() < 2 {
+                    return Ok(None);
+                }
+
+                (64 + b[1] as u32, 2)
+            }
+            1 => {
+                if b.len() < 3 {
+                    return Ok(None);
+                }
+
+                (64 + b[1] as u32 + ((b[2] as u32) << 8), 3)
+            }
+            id => (id as u32, 1),
+        };
+
+        let header_size = match format {
+            0 => 11,
+            1 => 7,
+            2 => 3,
+            _ => 0,
+        };
+
+        if b.len() < offset + header_size {
+            return Ok(None);
+        }
+
+        let header = &b[offset..offset + header_size];
+
+        offset += header_size;
+
+        let mut status = match self.streams.get(&chunk_stream_id) {
+            Some(s) => s.clone(),
+            None => {
+                if format != 0 {
+                    return Err(());
+                }
+
+                TraceChunkStream::default()
+            }
+        };
+
+        let message_start = format < 3 || status.payload.is_empty();
+
+        let mut timestamp_field: u32 = 0;
+
+        if format < 3 {
+            timestamp_field = BigEndian::read_u24(&header[0..3]);
+            status.extended = timestamp_field == 0xffffff;
+        }
+
+        if format < 2 {
+            status.length = BigEndian::read_u24(&header[3..6]) as usize;
+            status.packet_type = header[6] as u32;
+        }
+
+        if format == 0 {
+            status.stream_id = LittleEndian::read_u32(&header[7..11]);
+        }
+
+        if status.extended {
+            if b.len() < offset + 4 {
+                return Ok(None);
+            }
+
+            timestamp_field = BigEndian::read_u32(&b[offset..offset + 4]);
+
+            offset += 4;
+        }
+
+        if message_start {
+            match format {
+                0 => {
+                    status.timestamp = timestamp_field;
+                    status.delta = 0;
+                }
+                1 | 2 => {
+                    status.delta = timestamp_field;
+                    status.timestamp = status.timestamp.wrapping_add(status.delta);
+                }
+                _ => {
+                    status.timestamp = status.timestamp.wrapping_add(status.delta);
+                }
+            }
+
+            status.payload.clear();
+        }
+
+        let size = self
+            .chunk_size
+            .min(status.length.saturating_sub(status.payload.len()));
+
+        if b.len() < offset + size {
+            return Ok(None);
+        }
+
+        status.payload.extend_from_slice(&b[offset..offset + size]);
+
+        offset += size;
+
+        let msg = if status.payload.len() >= status.length {
+            Some(TraceMessage {
+                chunk_stream_id,
+                packet_type: status.packet_type,
+                stream_id: status.stream_id,
+                timestamp: status.timestamp,
+                payload: std::mem::take(&mut status.payload),
+            })
+        } else {
+            None
+        };
+
+        self.streams.insert(chunk_stream_id, status);
+
+        Ok(Some((offset, msg)))
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rtmp::{
+        rtmp_make_chunk_size_set_message, RtmpPacket, RTMP_CHUNK_TYPE_0, RTMP_TYPE_VIDEO,
+    };
+
+    fn make_video_chunks(timestamp: i64, size: usize, chunk_size: usize) -> Vec<u8> {
+        let mut packet = RtmpPacket::new_blank();
+
+        packet.header.format = RTMP_CHUNK_TYPE_0;
+        packet.header.channel_id = 330;
+        packet.header.packet_type = RTMP_TYPE_VIDEO;
+        packet.header.stream_id = 1;
+        packet.header.timestamp = timestamp;
+        packet.payload = (0..size).map(|i| i as u8).collect();
+        packet.header.length = size;
+
+        packet.create_chunks(chunk_size)
+    }
+
+    #[test]
+    fn test_trace_chunk_decoder() {
+        let mut bytes = make_video_chunks(1000, 300, 128);
+
+        bytes.extend(rtmp_make_chunk_size_set_message(4096));
+        bytes.extend(make_video_chunks(0x1000000, 10000, 4096));
+
+        let mut decoder = TraceChunkDecoder::new(128);
+        let mut messages: Vec<TraceMessage> = Vec::new();
+
+        // Feed the bytes in small parts, as partial writes
+
+        for part in bytes.chunks(7) {
+            messages.extend(decoder.push(part).unwrap());
+        }
+
+        assert_eq!(messages.len(), 3);
+
+        assert_eq!(messages[0].chunk_stream_id, 330);
+        assert_eq!(messages[0].packet_type, RTMP_TYPE_VIDEO);
+        assert_eq!(messages[0].stream_id, 1);
+        assert_eq!(messages[0].timestamp, 1000);
+        assert_eq!(messages[0].payload.len(), 300);
+
+        assert_eq!(messages[1].packet_type, RTMP_TYPE_SET_CHUNK_SIZE);
+
+        assert_eq!(messages[2].timestamp, 0x1000000);
+        assert_eq!(
+            messages[2].payload,
+            (0..10000).map(|i| i as u8).collect::<Vec<u8>>()
+        );
+
+        // Continuation chunk without a previous header
+
+        let mut decoder = TraceChunkDecoder::new(128);
+
+        assert!(decoder.push(&[0xc3, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+// Trace files (JSON lines)
+
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    sync::mpsc::Receiver,
+};
+
+use crate::{log::Logger, log_error, log_info};
+
+use super::{
+    SessionTraceRecord, SessionTraceTap, TraceChunkDecoder, TraceConfiguration,
+    TRACE_RECORDS_BUFFER_SIZE,
+};
+
+/// Starts tracing a session to a new trace file
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `config` - The trace configuration
+/// * `session_id` - The session ID
+/// * `tap` - The session trace tap
+///
+/// # Return value
+///
+/// Returns the path of the trace file, or an error message
+pub async fn start_session_trace(
+    logger: &Logger,
+    config: &TraceConfiguration,
+    session_id: u64,
+    tap: &SessionTraceTap,
+) -> Result<String, String> {
+    let path = config.get_trace_path(session_id, Utc::now().timestamp_millis());
+
+    if let Some(parent) = Path::new(&path).parent() {
+        if let Err(e) = tokio::fs::create_dir_all(parent).await {
+            return Err(format!("Could not create trace folder: {}", e));
+        }
+    }
+
+    let file = match File::create(&path).await {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(format!("Could not create trace file: {}", e));
+        }
+    };
+
+    let (sender, receiver) =
+        tokio::sync::mpsc::channel::<SessionTraceRecord>(TRACE_RECORDS_BUFFER_SIZE);
+
+    let decoder = TraceChunkDecoder::new(tap.out_chunk_size());
+
+    tap.start(sender);
+
+    log_info!(
+        logger,
+        format!("Session #{}: Trace started: {}", session_id, path)
+    );
+
+    spawn_task_write_trace(
+        logger.make_child_logger(&format!("[TRACE:#{}] ", session_id)),
+        file,
+        path.clone(),
+        decoder,
+        receiver,
+    );
+
+    Ok(path)
+}
+
+/// Spawns a task to write the trace records to the trace file
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `file` - The trace file
+/// * `path` - Path of the trace file
+/// * `decoder` - Decoder for the bytes sent to the client
+/// * `receiver` - Receiver for the trace records (closed to stop)
+fn spawn_task_write_trace(
+    logger: Logger,
+    file: File,
+    path: String,
+    mut decoder: TraceChunkDecoder,
+    mut receiver: Receiver<SessionTraceRecord>,
+) {
+    tokio::spawn(async move {
+        let mut writer = BufWriter::new(file);
+        let mut decoder_failed = false;
+        let mut res: std::io::Result<()> = Ok(());
+
+        while let Some(record) = receiver.recv().await {
+            let lines = make_trace_lines(record, &mut decoder, &mut decoder_failed);
+
+            for line in lines {
+                res = writer.write_all(line.to_string().as_bytes()).await;
+
+                if res.is_ok() {
+                    res = writer.write_all(b"\n").await;
+                }
+
+                if res.is_err() {
+                    break;
+                }
+            }
+
+            if res.is_err() {
+                break;
+            }
+        }
+
+        if res.is_ok() {
+            res = writer.flush().await;
+        }
+
+        match res {
+            Ok(_) => {
+                log_info!(logger, format!("Trace finished: {}", path));
+            }
+            Err(e) => {
+                log_error!(logger, format!("Could not write trace {}: {}", path, e));
+            }
+        }
+    });
+}
+
+/// Makes the lines of the trace file for a record
+///
+/// # Arguments
+///
+/// * `record` - The trace record
+/// * `decoder` - Decoder for the bytes sent to the client
+/// * `decoder_failed` - Set to true if the sent bytes could not be decoded.
+///   After that, they are written raw.
+fn make_trace_lines(
+    record: SessionTraceRecord,
+    decoder: &mut TraceChunkDecoder,
+    decoder_failed: &mut bool,
+) -> Vec<Value> {
+    match record {
+        SessionTraceRecord::Inbound {
+            time,
+            clock,
+            packet,
+        } => vec![json!({
+            "time": time,
+            "direction": "in",
+            "chunk_stream_id": packet.header.channel_id,
+            "type": packet.header.packet_type,
+            "stream_id": packet.header.stream_id,
+            "timestamp": packet.header.timestamp,
+            "clock": clock,
+            "length": packet.payload.len(),
+            "payload": STANDARD.encode(&packet.payload),
+        })],
+        SessionTraceRecord::Outbound { time, bytes } => {
+            if !*decoder_failed {
+                match decoder.push(&bytes) {
+                    Ok(messages) => {
+                        return messages
+                            .into_iter()
+                            .map(|msg| {
+                                json!({
+                                    "time": time,
+                                    "direction": "out",
+                                    "chunk_stream_id": msg.chunk_stream_id,
+                                    "type": msg.packet_type,
+                                    "stream_id": msg.stream_id,
+                                    "timestamp": msg.timestamp,
+                                    "length": msg.payload.len(),
+                                    "payload": STANDARD.encode(&msg.payload),
+                                })
+                            })
+                            .collect();
+                    }
+                    Err(_) => {
+                        *decoder_failed = true;
+                    }
+                }
+            }
+
+            vec![json!({
+                "time": time,
+                "direction": "out",
+                "raw": STANDARD.encode(&bytes),
+            })]
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// Filters to select the sessions to trace
+
+use std::net::IpAddr;
+
+/// Filter to select the sessions to trace
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SessionTraceFilter {
+    /// Sessions from a client IP address
+    Ip(IpAddr),
+
+    /// Sessions connected to a channel
+    Channel(String),
+}
+
+impl SessionTraceFilter {
+    /// Parses a trace filter
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Kind of filter (`ip` or `channel`)
+    /// * `value` - Value of the filter
+    pub fn parse(kind: &str, value: &str) -> Result<SessionTraceFilter, String> {
+        let value = value.trim();
+
+        match kind.to_lowercase().as_str() {
+            "ip" => match value.parse::<IpAddr>() {
+                Ok(ip) => Ok(SessionTraceFilter::Ip(ip)),
+                Err(_) => Err(format!("Invalid IP address: {}", value)),
+            },
+            "channel" => {
+                if value.is_empty() {
+                    Err("Empty channel".to_string())
+                } else {
+                    Ok(SessionTraceFilter::Channel(value.to_string()))
+                }
+            }
+            _ => Err(format!("Unknown trace filter: {}", kind)),
+        }
+    }
+
+    /// Gets the kind of the filter (`ip` or `channel`)
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SessionTraceFilter::Ip(_) => "ip",
+            SessionTraceFilter::Channel(_) => "channel",
+        }
+    }
+
+    /// Gets the value of the filter as a string
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub fn value(&self) -> String {
+        match self {
+            SessionTraceFilter::Ip(ip) => ip.to_string(),
+            SessionTraceFilter::Channel(channel) => channel.clone(),
+        }
+    }
+
+    /// Checks if a session matches the filter
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The client IP address of the session
+    /// * `channel` - The channel of the session, if connected
+    pub fn matches(&self, ip: &IpAddr, channel: Option<&str>) -> bool {
+        match self {
+            SessionTraceFilter::Ip(filter_ip) => filter_ip == ip,
+            SessionTraceFilter::Channel(filter_channel) => channel == Some(filter_channel.as_str()),
+        }
+    }
+}
+
+// Tests
+
+#[cfg(all(test, any(feature = "control", feature = "redis")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_trace_filter() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let filter = SessionTraceFilter::parse("IP", " 10.0.0.1 ").unwrap();
+
+        assert!(filter.matches(&ip, None));
+        assert!(!filter.matches(&other_ip, Some("live")));
+        assert_eq!(filter.value(), "10.0.0.1");
+
+        let filter = SessionTraceFilter::parse("channel", "live").unwrap();
+
+        assert!(filter.matches(&other_ip, Some("live")));
+        assert!(!filter.matches(&ip, None));
+        assert!(!filter.matches(&ip, Some("live2")));
+
+        assert!(SessionTraceFilter::parse("ip", "not-an-ip").is_err());
+        assert!(SessionTraceFilter::parse("channel", "").is_err());
+        assert!(SessionTraceFilter::parse("session", "1").is_err());
+    }
+}
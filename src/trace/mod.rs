@@ -0,0 +1,19 @@
+// Traces of the RTMP messages of a session, for debugging
+
+mod config;
+mod decoder;
+mod file;
+mod filter;
+mod registry;
+mod replay;
+mod tap;
+mod writer;
+
+pub use config::*;
+pub use decoder::*;
+pub use file::*;
+pub use filter::*;
+pub use registry::*;
+pub use replay::*;
+pub use tap::*;
+pub use writer::*;
@@ -0,0 +1,288 @@
+// Registry of the sessions that can be traced
+
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+
+use tokio::sync::Mutex;
+
+use crate::{log::Logger, log_info, log_warning};
+
+use super::{start_session_trace, SessionTraceFilter, SessionTraceTap, TraceConfiguration};
+
+/// Session that can be traced
+struct SessionTraceEntry {
+    /// Client IP address
+    ip: IpAddr,
+
+    /// Channel, if connected
+    channel: Option<String>,
+
+    /// Trace tap
+    tap: Arc<SessionTraceTap>,
+}
+
+/// Status of the registry
+struct SessionTraceRegistryStatus {
+    /// Active trace filters
+    filters: Vec<SessionTraceFilter>,
+
+    /// Sessions
+    sessions: HashMap<u64, SessionTraceEntry>,
+}
+
+/// Registry of the sessions that can be traced,
+/// and the active trace filters
+pub struct SessionTraceRegistry {
+    /// Registry status
+    status: Mutex<SessionTraceRegistryStatus>,
+}
+
+impl SessionTraceRegistry {
+    /// Creates new SessionTraceRegistry
+    pub fn new() -> SessionTraceRegistry {
+        SessionTraceRegistry {
+            status: Mutex::new(SessionTraceRegistryStatus {
+                filters: Vec::new(),
+                sessions: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Starts or stops tracing a session,
+    /// depending on the active filters
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `config` - The trace configuration
+    /// * `filters` - The active filters
+    /// * `session_id` - The session ID
+    /// * `entry` - The session
+    async fn update_session(
+        logger: &Logger,
+        config: &TraceConfiguration,
+        filters: &[SessionTraceFilter],
+        session_id: u64,
+        entry: &SessionTraceEntry,
+    ) {
+        let should_trace = filters
+            .iter()
+            .any(|f| f.matches(&entry.ip, entry.channel.as_deref()));
+
+        if should_trace == entry.tap.is_active() {
+            return;
+        }
+
+        if should_trace {
+            if let Err(e) = start_session_trace(logger, config, session_id, &entry.tap).await {
+                log_warning!(
+                    logger,
+                    format!("Session #{}: Could not start trace: {}", session_id, e)
+                );
+            }
+        } else {
+            stop_session_trace(logger, session_id, &entry.tap);
+        }
+    }
+
+    /// Registers a session, tracing it if matches any active filter
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `config` - The trace configuration
+    /// * `session_id` - The session ID
+    /// * `ip` - The client IP address
+    /// * `tap` - The session trace tap
+    pub async fn register_session(
+        &self,
+        logger: &Logger,
+        config: &TraceConfiguration,
+        session_id: u64,
+        ip: IpAddr,
+        tap: Arc<SessionTraceTap>,
+    ) {
+        let entry = SessionTraceEntry {
+            ip,
+            channel: None,
+            tap,
+        };
+
+        let mut status = self.status.lock().await;
+
+        Self::update_session(logger, config, &status.filters, session_id, &entry).await;
+
+        status.sessions.insert(session_id, entry);
+    }
+
+    /// Sets the channel of a session, tracing it if matches any active filter
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `config` - The trace configuration
+    /// * `session_id` - The session ID
+    /// * `channel` - The channel
+    pub async fn set_session_channel(
+        &self,
+        logger: &Logger,
+        config: &TraceConfiguration,
+        session_id: u64,
+        channel: &str,
+    ) {
+        let mut status = self.status.lock().await;
+        let status = &mut *status;
+
+        if let Some(entry) = status.sessions.get_mut(&session_id) {
+            entry.channel = Some(channel.to_string());
+
+            Self::update_session(logger, config, &status.filters, session_id, entry).await;
+        }
+    }
+
+    /// Removes a session, stopping its trace
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `session_id` - The session ID
+    pub async fn unregister_session(&self, logger: &Logger, session_id: u64) {
+        let mut status = self.status.lock().await;
+
+        if let Some(entry) = status.sessions.remove(&session_id) {
+            if entry.tap.is_active() {
+                stop_session_trace(logger, session_id, &entry.tap);
+            }
+        }
+    }
+
+    /// Checks if a session ID is registered
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The session ID
+    pub async fn is_session_registered(&self, session_id: u64) -> bool {
+        self.status.lock().await.sessions.contains_key(&session_id)
+    }
+
+    /// Adds a trace filter, tracing the matching sessions
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `config` - The trace configuration
+    /// * `filter` - The filter
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of traced sessions matching the filter, or an error message
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub async fn start_trace(
+        &self,
+        logger: &Logger,
+        config: &TraceConfiguration,
+        filter: SessionTraceFilter,
+    ) -> Result<usize, String> {
+        let mut status = self.status.lock().await;
+        let status = &mut *status;
+
+        if status.filters.contains(&filter) {
+            return Err(format!(
+                "Already tracing sessions with {} {}",
+                filter.kind(),
+                filter.value()
+            ));
+        }
+
+        status.filters.push(filter.clone());
+
+        let mut count: usize = 0;
+
+        for (session_id, entry) in status.sessions.iter() {
+            if !filter.matches(&entry.ip, entry.channel.as_deref()) {
+                continue;
+            }
+
+            Self::update_session(logger, config, &status.filters, *session_id, entry).await;
+
+            if entry.tap.is_active() {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Removes a trace filter, stopping the traces
+    /// of the sessions not matching any other filter
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    /// * `config` - The trace configuration
+    /// * `filter` - The filter
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of stopped traces, or an error message
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub async fn stop_trace(
+        &self,
+        logger: &Logger,
+        config: &TraceConfiguration,
+        filter: &SessionTraceFilter,
+    ) -> Result<usize, String> {
+        let mut status = self.status.lock().await;
+        let status = &mut *status;
+
+        let filter_count = status.filters.len();
+
+        status.filters.retain(|f| f != filter);
+
+        if status.filters.len() == filter_count {
+            return Err(format!(
+                "Not tracing sessions with {} {}",
+                filter.kind(),
+                filter.value()
+            ));
+        }
+
+        let mut count: usize = 0;
+
+        for (session_id, entry) in status.sessions.iter() {
+            if !entry.tap.is_active() {
+                continue;
+            }
+
+            Self::update_session(logger, config, &status.filters, *session_id, entry).await;
+
+            if !entry.tap.is_active() {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// Stops tracing a session
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `session_id` - The session ID
+/// * `tap` - The session trace tap
+fn stop_session_trace(logger: &Logger, session_id: u64, tap: &SessionTraceTap) {
+    let dropped = tap.stop();
+
+    if dropped > 0 {
+        log_warning!(
+            logger,
+            format!(
+                "Session #{}: Trace stopped. Dropped records: {}",
+                session_id, dropped
+            )
+        );
+    } else {
+        log_info!(logger, format!("Session #{}: Trace stopped", session_id));
+    }
+}
@@ -0,0 +1,411 @@
+// Replay of the traces, feeding the received messages to a session without network
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{
+    auth::StreamKeyStore,
+    log::Logger,
+    log_error, log_info,
+    rtmp::{
+        RtmpPacket, RTMP_CHUNK_TYPE_0, RTMP_HANDSHAKE_SIZE, RTMP_MIN_CHUNK_SIZE,
+        RTMP_TYPE_SET_CHUNK_SIZE, RTMP_VERSION,
+    },
+    server::{RtmpServerConfiguration, RtmpServerContext, RtmpServerStatus},
+    session::{
+        handle_rtmp_session, GopReplayLimiter, RtmpSessionPublishStreamStatus, RtmpSessionStatus,
+        SessionBandwidth, SessionContext, SessionObjectEncoding, SessionOutChunkSize,
+        SessionOutboundAck, SessionPanicTracker, SessionPlayerLag,
+    },
+};
+
+use super::SessionTraceTap;
+
+/// ID of the replayed session
+const REPLAY_SESSION_ID: u64 = 1;
+
+/// Max time to wait for the tasks of the replayed session to end
+const REPLAY_TASKS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Line of a trace file
+#[derive(Deserialize)]
+struct TraceLine {
+    /// Direction (in or out)
+    direction: String,
+
+    /// Chunk stream ID
+    chunk_stream_id: Option<u32>,
+
+    /// Packet type
+    #[serde(rename = "type")]
+    packet_type: Option<u32>,
+
+    /// Message stream ID
+    stream_id: Option<u32>,
+
+    /// Timestamp of the message header
+    timestamp: Option<i64>,
+
+    /// Clock value of the message (not present in older traces)
+    clock: Option<i64>,
+
+    /// Payload (Base64)
+    payload: Option<String>,
+}
+
+/// Parses the messages received by a session from a trace
+///
+/// # Arguments
+///
+/// * `trace` - Content of the trace file
+///
+/// # Return value
+///
+/// Returns the received messages, or an error message
+pub fn parse_trace_inbound_packets(trace: &str) -> Result<Vec<RtmpPacket>, String> {
+    let mut packets: Vec<RtmpPacket> = Vec::new();
+
+    for (i, line) in trace.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: TraceLine =
+            serde_json::from_str(line).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+
+        if record.direction != "in" {
+            continue;
+        }
+
+        let (Some(channel_id), Some(packet_type), Some(payload)) =
+            (record.chunk_stream_id, record.packet_type, record.payload)
+        else {
+            return Err(format!("Line {}: Incomplete message", i + 1));
+        };
+
+        let mut packet = RtmpPacket::new_blank();
+
+        packet.header.format = RTMP_CHUNK_TYPE_0;
+        packet.header.channel_id = channel_id;
+        packet.header.packet_type = packet_type;
+        packet.header.stream_id = record.stream_id.unwrap_or(0);
+        packet.header.timestamp = record.clock.or(record.timestamp).unwrap_or(0);
+        packet.payload = STANDARD
+            .decode(payload)
+            .map_err(|e| format!("Line {}: Invalid payload: {}", i + 1, e))?;
+        packet.header.length = packet.payload.len();
+
+        packets.push(packet);
+    }
+
+    Ok(packets)
+}
+
+/// Makes the byte stream a client would send to the server
+/// in order to deliver the messages of a trace
+///
+/// # Arguments
+///
+/// * `packets` - The messages received by the traced session
+pub fn make_replay_stream(packets: &[RtmpPacket]) -> Vec<u8> {
+    // Handshake: C0, C1 and the copy of S1.
+    // A blank signature selects the basic handshake, so the server response is deterministic.
+
+    let mut stream: Vec<u8> = vec![0; 1 + 2 * RTMP_HANDSHAKE_SIZE];
+
+    stream[0] = RTMP_VERSION;
+
+    // Each message is sent as a type 0 chunk, with the clock as the timestamp
+
+    let mut chunk_size = RTMP_MIN_CHUNK_SIZE;
+
+    for packet in packets {
+        stream.extend(packet.create_chunks(chunk_size));
+
+        if packet.header.packet_type == RTMP_TYPE_SET_CHUNK_SIZE && packet.payload.len() >= 4 {
+            chunk_size = (BigEndian::read_u32(&packet.payload[0..4]) as usize).max(1);
+        }
+    }
+
+    stream
+}
+
+/// Makes a server context to replay sessions,
+/// with no control server, slate or cluster
+///
+/// # Arguments
+///
+/// * `config` - The server configuration
+pub fn make_replay_server_context(config: Arc<RtmpServerConfiguration>) -> RtmpServerContext {
+    let status = Arc::new(RtmpServerStatus::new(
+        config.channel_event_history_size,
+        GopReplayLimiter::new(
+            config.gop_replay_max_concurrent as usize,
+            config.gop_replay_queue_timeout_ms as u64,
+        ),
+        StreamKeyStore::new(config.key_store.clone()),
+    ));
+
+    RtmpServerContext {
+        config,
+        status,
+        control_key_validator_sender: None,
+        slate: None,
+        cluster: None,
+    }
+}
+
+/// Replays a session, feeding a byte stream to the session logic
+/// as if it was received from a client
+///
+/// # Arguments
+///
+/// * `logger` - The session logger
+/// * `server_context` - The server context
+/// * `stream` - The bytes sent by the client
+///
+/// # Return value
+///
+/// Returns the bytes sent by the server to the client
+pub async fn replay_session(
+    logger: Arc<Logger>,
+    server_context: RtmpServerContext,
+    stream: &[u8],
+) -> Vec<u8> {
+    let session_context = SessionContext {
+        id: REPLAY_SESSION_ID,
+        ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        status: Arc::new(Mutex::new(RtmpSessionStatus::new())),
+        publish_status: Arc::new(Mutex::new(RtmpSessionPublishStreamStatus::new())),
+        bandwidth: Arc::new(SessionBandwidth::new()),
+        player_lag: Arc::new(SessionPlayerLag::new()),
+        trace: Arc::new(SessionTraceTap::new()),
+        out_chunk_size: Arc::new(SessionOutChunkSize::new()),
+        outbound_ack: Arc::new(SessionOutboundAck::new()),
+        object_encoding: Arc::new(SessionObjectEncoding::new()),
+        client_cert: None,
+        client_geo: None,
+        panic_tracker: Arc::new(SessionPanicTracker::new()),
+    };
+
+    let write_stream = Arc::new(Mutex::new(Vec::<u8>::new()));
+
+    // The session ends when the stream is fully read
+
+    handle_rtmp_session(
+        logger,
+        server_context,
+        session_context,
+        stream,
+        write_stream.clone(),
+    )
+    .await;
+
+    // Wait for the tasks of the session, so their output is included
+
+    let deadline = tokio::time::Instant::now() + REPLAY_TASKS_TIMEOUT;
+
+    while Arc::strong_count(&write_stream) > 1 && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut sent = write_stream.lock().await;
+
+    std::mem::take(&mut *sent)
+}
+
+/// Replays a trace file, with the server configuration
+///
+/// # Arguments
+///
+/// * `logger` - The logger
+/// * `path` - Path of the trace file
+pub async fn run_replay_trace(logger: &Logger, path: &str) -> Result<(), ()> {
+    let config = RtmpServerConfiguration::load_from_env(logger)?;
+
+    let trace = match tokio::fs::read_to_string(path).await {
+        Ok(t) => t,
+        Err(e) => {
+            log_error!(logger, format!("Could not read trace {}: {}", path, e));
+            return Err(());
+        }
+    };
+
+    let packets = match parse_trace_inbound_packets(&trace) {
+        Ok(p) => p,
+        Err(e) => {
+            log_error!(logger, format!("Invalid trace {}: {}", path, e));
+            return Err(());
+        }
+    };
+
+    log_info!(
+        logger,
+        format!(
+            "Replaying {} received messages from {}",
+            packets.len(),
+            path
+        )
+    );
+
+    let sent = replay_session(
+        Arc::new(logger.make_child_logger("[REPLAY] ")),
+        make_replay_server_context(Arc::new(config)),
+        &make_replay_stream(&packets),
+    )
+    .await;
+
+    log_info!(
+        logger,
+        format!("Replay finished. The server sent {} bytes", sent.len())
+    );
+
+    Ok(())
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        amf::AMF0Value,
+        rtmp::{
+            rtmp_decode_chunk, RtmpChunkDecodeStatus, RtmpCommand, RtmpInputPacketBuffer,
+            RTMP_TYPE_INVOKE,
+        },
+        trace::TraceChunkDecoder,
+    };
+
+    use super::*;
+
+    /// Makes the trace of a client connecting to an app
+    fn make_connect_trace() -> String {
+        let mut connect = RtmpCommand::new("connect".to_string());
+
+        connect.set_argument("transId".to_string(), AMF0Value::Number { value: 1.0 });
+        connect.set_argument(
+            "cmdObj".to_string(),
+            AMF0Value::Object {
+                properties: HashMap::from([(
+                    "app".to_string(),
+                    AMF0Value::String {
+                        value: "live".to_string(),
+                    },
+                )]),
+            },
+        );
+
+        let set_chunk_size = 4096u32.to_be_bytes();
+
+        [
+            serde_json::json!({
+                "time": 0,
+                "direction": "in",
+                "chunk_stream_id": 2,
+                "type": RTMP_TYPE_SET_CHUNK_SIZE,
+                "stream_id": 0,
+                "timestamp": 0,
+                "clock": 0,
+                "length": 4,
+                "payload": STANDARD.encode(set_chunk_size),
+            }),
+            serde_json::json!({
+                "time": 0,
+                "direction": "out",
+                "raw": "",
+            }),
+            serde_json::json!({
+                "time": 0,
+                "direction": "in",
+                "chunk_stream_id": 3,
+                "type": RTMP_TYPE_INVOKE,
+                "stream_id": 0,
+                "timestamp": 0,
+                "length": 0,
+                "payload": STANDARD.encode(connect.encode()),
+            }),
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+    }
+
+    #[test]
+    fn test_make_replay_stream() {
+        let packets = parse_trace_inbound_packets(&make_connect_trace()).unwrap();
+
+        assert_eq!(packets.len(), 2);
+        assert!(parse_trace_inbound_packets("{\"direction\": \"in\"}").is_err());
+
+        let stream = make_replay_stream(&packets);
+
+        assert_eq!(stream[0], RTMP_VERSION);
+
+        // The messages are decoded as sent, following the chunk size change
+
+        let logger = Logger::new_disabled();
+        let mut in_packets = RtmpInputPacketBuffer::new(4);
+        let mut offset = 1 + 2 * RTMP_HANDSHAKE_SIZE;
+        let mut chunk_size = RTMP_MIN_CHUNK_SIZE;
+
+        for expected in &packets {
+            match rtmp_decode_chunk(&logger, &stream[offset..], &mut in_packets, chunk_size) {
+                Ok(RtmpChunkDecodeStatus::Complete(chunk)) => {
+                    let packet = chunk.packet.expect("Message not completed");
+
+                    assert_eq!(packet.header.packet_type, expected.header.packet_type);
+                    assert_eq!(packet.payload, expected.payload);
+
+                    offset += chunk.size;
+                }
+                _ => panic!("Could not decode the replay stream"),
+            }
+
+            chunk_size = 4096;
+        }
+
+        assert_eq!(offset, stream.len());
+    }
+
+    #[tokio::test]
+    async fn test_replay_session() {
+        let logger = Logger::new_disabled();
+
+        let config = RtmpServerConfiguration::load_from_env(&logger).unwrap();
+        let packets = parse_trace_inbound_packets(&make_connect_trace()).unwrap();
+
+        let sent = replay_session(
+            Arc::new(logger),
+            make_replay_server_context(Arc::new(config)),
+            &make_replay_stream(&packets),
+        )
+        .await;
+
+        // Basic handshake response
+
+        let handshake_size = 1 + 2 * RTMP_HANDSHAKE_SIZE;
+
+        assert!(sent.len() > handshake_size);
+        assert_eq!(sent[0], RTMP_VERSION);
+
+        // The connection was accepted
+
+        let mut decoder = TraceChunkDecoder::new(RTMP_MIN_CHUNK_SIZE);
+
+        let messages = decoder.push(&sent[handshake_size..]).unwrap();
+
+        assert!(messages.iter().any(|m| m.packet_type == RTMP_TYPE_INVOKE
+            && RtmpCommand::decode(&m.payload).is_ok_and(|c| c.cmd == "_result")));
+    }
+}
@@ -0,0 +1,163 @@
+// Tap to capture the messages of a session
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Mutex,
+};
+
+use chrono::Utc;
+use tokio::sync::mpsc::Sender;
+
+use crate::rtmp::{RtmpPacket, RTMP_MIN_CHUNK_SIZE};
+
+/// Size of the buffer of trace records
+/// If the trace file can't be written fast enough, the extra records are dropped
+pub const TRACE_RECORDS_BUFFER_SIZE: usize = 1024;
+
+/// Record of a trace
+pub enum SessionTraceRecord {
+    /// Packet received from the client
+    Inbound {
+        /// Time of the record (Unix milliseconds)
+        time: i64,
+
+        /// Clock value of the message, as seen by the session
+        clock: i64,
+
+        /// The packet
+        packet: RtmpPacket,
+    },
+
+    /// Bytes sent to the client
+    Outbound {
+        /// Time of the record (Unix milliseconds)
+        time: i64,
+
+        /// The bytes
+        bytes: Vec<u8>,
+    },
+}
+
+/// Tap to capture the messages of a session.
+/// It does nothing until a trace is started for the session.
+pub struct SessionTraceTap {
+    /// True if the session is being traced
+    active: AtomicBool,
+
+    /// Sender for the trace records
+    sender: Mutex<Option<Sender<SessionTraceRecord>>>,
+
+    /// Size of the chunks sent to the client
+    out_chunk_size: AtomicUsize,
+
+    /// Number of records dropped because the buffer was full
+    dropped: AtomicU64,
+}
+
+impl SessionTraceTap {
+    /// Creates new SessionTraceTap
+    pub fn new() -> SessionTraceTap {
+        SessionTraceTap {
+            active: AtomicBool::new(false),
+            sender: Mutex::new(None),
+            out_chunk_size: AtomicUsize::new(RTMP_MIN_CHUNK_SIZE),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Checks if the session is being traced
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Sets the size of the chunks sent to the client,
+    /// in order to decode them if the trace starts later
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The chunk size
+    pub fn set_out_chunk_size(&self, size: usize) {
+        self.out_chunk_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Gets the size of the chunks sent to the client
+    pub fn out_chunk_size(&self) -> usize {
+        self.out_chunk_size.load(Ordering::Relaxed)
+    }
+
+    /// Starts capturing
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - Sender for the trace records
+    pub fn start(&self, sender: Sender<SessionTraceRecord>) {
+        if let Ok(mut s) = self.sender.lock() {
+            *s = Some(sender);
+            self.dropped.store(0, Ordering::Relaxed);
+            self.active.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Stops capturing.
+    /// Dropping the sender closes the trace file.
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of dropped records
+    pub fn stop(&self) -> u64 {
+        if let Ok(mut s) = self.sender.lock() {
+            self.active.store(false, Ordering::Relaxed);
+            *s = None;
+        }
+
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Sends a record to the trace file
+    fn send(&self, record: SessionTraceRecord) {
+        let sender = match self.sender.lock() {
+            Ok(s) => s,
+            Err(_) => return,
+        };
+
+        if let Some(s) = sender.as_ref() {
+            if s.try_send(record).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Captures a packet received from the client
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet
+    /// * `clock` - Clock value of the message
+    pub fn trace_inbound(&self, packet: &RtmpPacket, clock: i64) {
+        if !self.is_active() {
+            return;
+        }
+
+        self.send(SessionTraceRecord::Inbound {
+            time: Utc::now().timestamp_millis(),
+            clock,
+            packet: packet.clone(),
+        });
+    }
+
+    /// Captures bytes sent to the client
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes
+    pub fn trace_outbound(&self, bytes: &[u8]) {
+        if !self.is_active() || bytes.is_empty() {
+            return;
+        }
+
+        self.send(SessionTraceRecord::Outbound {
+            time: Utc::now().timestamp_millis(),
+            bytes: bytes.to_vec(),
+        });
+    }
+}
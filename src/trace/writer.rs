@@ -0,0 +1,95 @@
+// Write stream wrapper to capture the bytes sent to the client
+
+use std::{
+    io::IoSlice,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tokio::io::AsyncWrite;
+
+use super::SessionTraceTap;
+
+/// Write stream wrapper capturing the bytes sent to the client
+/// when the session is being traced
+pub struct TraceCapturingWriter<TW: AsyncWrite + Unpin> {
+    /// The inner write stream
+    inner: TW,
+
+    /// The session trace tap
+    trace: Arc<SessionTraceTap>,
+}
+
+impl<TW: AsyncWrite + Unpin> TraceCapturingWriter<TW> {
+    /// Creates new TraceCapturingWriter
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The write stream to wrap
+    /// * `trace` - The session trace tap
+    pub fn new(inner: TW, trace: Arc<SessionTraceTap>) -> TraceCapturingWriter<TW> {
+        TraceCapturingWriter { inner, trace }
+    }
+}
+
+impl<TW: AsyncWrite + Unpin> AsyncWrite for TraceCapturingWriter<TW> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(n)) = &res {
+            this.trace.trace_outbound(&buf[..*n]);
+        }
+
+        res
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+
+        if let Poll::Ready(Ok(n)) = &res {
+            if this.trace.is_active() {
+                let mut written: Vec<u8> = Vec::with_capacity(*n);
+
+                for buf in bufs {
+                    let remaining = *n - written.len();
+
+                    if remaining == 0 {
+                        break;
+                    }
+
+                    written.extend_from_slice(&buf[..buf.len().min(remaining)]);
+                }
+
+                this.trace.trace_outbound(&written);
+            }
+        }
+
+        res
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
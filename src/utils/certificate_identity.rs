@@ -0,0 +1,23 @@
+// Identity of the client certificates
+
+/// Identity of the subject of a certificate
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CertificateIdentity {
+    /// Common name (CN) of the subject
+    pub common_name: Option<String>,
+
+    /// Subject alternative names (DNS names, emails, URIs and IP addresses)
+    pub subject_alt_names: Vec<String>,
+}
+
+impl CertificateIdentity {
+    /// Gets the subject alternative names split by commas,
+    /// or None if there are no alternative names
+    pub fn get_subject_alt_names_string(&self) -> Option<String> {
+        if self.subject_alt_names.is_empty() {
+            None
+        } else {
+            Some(self.subject_alt_names.join(","))
+        }
+    }
+}
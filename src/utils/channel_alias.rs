@@ -0,0 +1,219 @@
+// Channel aliasing (rewrite rules)
+
+use crate::{log::Logger, log_error};
+
+use super::{get_env_string, get_env_string_list};
+
+/// Wildcard to match any value (or keep the original value in the target)
+const ALIAS_WILDCARD: &str = "*";
+
+/// Rule to rewrite a channel and key
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelAliasRule {
+    /// Channel to match
+    pub from_channel: String,
+
+    /// Key to match
+    pub from_key: String,
+
+    /// Channel to rewrite to
+    pub to_channel: String,
+
+    /// Key to rewrite to
+    pub to_key: String,
+}
+
+impl ChannelAliasRule {
+    /// Parses a rule, with the format `CHANNEL/KEY -> CHANNEL/KEY`
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - The rule to parse
+    ///
+    /// # Return value
+    ///
+    /// Returns the rule, or None if the rule is not valid
+    pub fn parse(rule: &str) -> Option<ChannelAliasRule> {
+        let (from, to) = rule.split_once("->")?;
+
+        // The channel can contain slashes (tenant namespaces), the key cannot
+        let (from_channel, from_key) = from.trim().rsplit_once('/')?;
+        let (to_channel, to_key) = to.trim().rsplit_once('/')?;
+
+        if [from_channel, from_key, to_channel, to_key]
+            .iter()
+            .any(|p| p.is_empty())
+        {
+            return None;
+        }
+
+        Some(ChannelAliasRule {
+            from_channel: from_channel.to_string(),
+            from_key: from_key.to_string(),
+            to_channel: to_channel.to_string(),
+            to_key: to_key.to_string(),
+        })
+    }
+
+    /// Applies the rule
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The requested channel (RTMP app)
+    /// * `key` - The requested key (RTMP stream name)
+    ///
+    /// # Return value
+    ///
+    /// Returns the rewritten channel and key, or None if the rule does not match
+    pub fn apply(&self, channel: &str, key: &str) -> Option<(String, String)> {
+        if self.from_channel != ALIAS_WILDCARD && self.from_channel != channel {
+            return None;
+        }
+
+        if self.from_key != ALIAS_WILDCARD && self.from_key != key {
+            return None;
+        }
+
+        let to_channel = if self.to_channel == ALIAS_WILDCARD {
+            channel
+        } else {
+            &self.to_channel
+        };
+
+        let to_key = if self.to_key == ALIAS_WILDCARD {
+            key
+        } else {
+            &self.to_key
+        };
+
+        Some((to_channel.to_string(), to_key.to_string()))
+    }
+}
+
+/// Channel aliasing configuration
+#[derive(Clone)]
+pub struct ChannelAliasConfig {
+    /// Rules, in order of priority
+    rules: Vec<ChannelAliasRule>,
+}
+
+impl ChannelAliasConfig {
+    /// Creates configuration from a list of rules
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The rules to parse. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Return value
+    ///
+    /// Returns the configuration, or the invalid rule as the error
+    pub fn new_from_rules(rules: &[String]) -> Result<ChannelAliasConfig, String> {
+        let mut parsed_rules: Vec<ChannelAliasRule> = Vec::new();
+
+        for rule in rules {
+            let rule = rule.trim();
+
+            if rule.is_empty() || rule.starts_with('#') {
+                continue;
+            }
+
+            match ChannelAliasRule::parse(rule) {
+                Some(r) => parsed_rules.push(r),
+                None => {
+                    return Err(rule.to_string());
+                }
+            }
+        }
+
+        Ok(ChannelAliasConfig {
+            rules: parsed_rules,
+        })
+    }
+
+    /// Loads configuration for environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<ChannelAliasConfig, ()> {
+        let mut rules = get_env_string_list("CHANNEL_ALIASES", "");
+
+        let rules_file = get_env_string("CHANNEL_ALIASES_FILE", "");
+
+        if !rules_file.is_empty() {
+            match std::fs::read_to_string(&rules_file) {
+                Ok(content) => {
+                    rules.extend(content.lines().map(|l| l.to_string()));
+                }
+                Err(e) => {
+                    log_error!(
+                        logger,
+                        format!("Could not read CHANNEL_ALIASES_FILE {}: {}", rules_file, e)
+                    );
+                    return Err(());
+                }
+            }
+        }
+
+        match ChannelAliasConfig::new_from_rules(&rules) {
+            Ok(c) => Ok(c),
+            Err(rule) => {
+                log_error!(logger, format!("Invalid channel alias rule: {}", rule));
+                Err(())
+            }
+        }
+    }
+
+    /// Resolves the channel and key to use, applying the first matching rule
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The requested channel (RTMP app)
+    /// * `key` - The requested key (RTMP stream name)
+    ///
+    /// # Return value
+    ///
+    /// Returns the rewritten channel and key, or None if no rule matches
+    pub fn resolve(&self, channel: &str, key: &str) -> Option<(String, String)> {
+        self.rules.iter().find_map(|r| r.apply(channel, key))
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_alias_rules() {
+        assert_eq!(ChannelAliasRule::parse("live/a"), None);
+        assert_eq!(ChannelAliasRule::parse("live -> events/a"), None);
+        assert_eq!(ChannelAliasRule::parse("live/a -> events/"), None);
+
+        let config = ChannelAliasConfig::new_from_rules(&[
+            "# Comment".to_string(),
+            "live/alias123 -> events/conference1".to_string(),
+            "".to_string(),
+            "old-app/* -> new-app/*".to_string(),
+            "*/legacy -> */main".to_string(),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.resolve("live", "alias123"),
+            Some(("events".to_string(), "conference1".to_string()))
+        );
+        assert_eq!(config.resolve("live", "other"), None);
+        assert_eq!(
+            config.resolve("old-app", "key1"),
+            Some(("new-app".to_string(), "key1".to_string()))
+        );
+        assert_eq!(
+            config.resolve("tv", "legacy"),
+            Some(("tv".to_string(), "main".to_string()))
+        );
+
+        assert!(ChannelAliasConfig::new_from_rules(&["invalid".to_string()]).is_err());
+    }
+}
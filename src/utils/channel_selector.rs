@@ -0,0 +1,228 @@
+// Selection of groups of channels (wildcard patterns and lists)
+
+use std::fmt;
+
+/// Wildcard to match any sequence of characters
+const SELECTOR_WILDCARD: char = '*';
+
+/// Separator of the channels of a list
+const SELECTOR_LIST_SEPARATOR: char = ',';
+
+/// Selector of channels, for the commands targeting a group of channels.
+/// It can be a channel ID, a wildcard pattern (`event-*`)
+/// or a list of channel IDs and patterns, split by commas (`a,b,event-*`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelSelector {
+    /// Channel IDs or patterns
+    patterns: Vec<String>,
+}
+
+impl ChannelSelector {
+    /// Parses a channel selector
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - The selector to parse
+    ///
+    /// # Return value
+    ///
+    /// Returns the selector, or None if it is empty or the list contains empty entries
+    pub fn parse(selector: &str) -> Option<ChannelSelector> {
+        let patterns: Vec<String> = selector
+            .split(SELECTOR_LIST_SEPARATOR)
+            .map(|p| p.trim().to_string())
+            .collect();
+
+        if patterns.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+
+        Some(ChannelSelector { patterns })
+    }
+
+    /// Parses the selector of a kill command.
+    /// Selecting a group of channels requires confirmation,
+    /// so a mistyped pattern does not kill unexpected streams.
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - The selector to parse
+    /// * `confirmed` - True if the command confirmed the kill of a group of channels
+    ///
+    /// # Return value
+    ///
+    /// Returns the selector, or an error message
+    pub fn parse_kill_target(selector: &str, confirmed: bool) -> Result<ChannelSelector, String> {
+        let channels = match ChannelSelector::parse(selector) {
+            Some(s) => s,
+            None => {
+                return Err("Invalid channel selector".to_string());
+            }
+        };
+
+        if channels.is_group() && !confirmed {
+            return Err("Killing a group of channels requires confirmation".to_string());
+        }
+
+        Ok(channels)
+    }
+
+    /// Gets the channel ID, if the selector is a single channel
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub fn single_channel(&self) -> Option<&str> {
+        if self.is_group() {
+            return None;
+        }
+
+        self.patterns.first().map(|p| p.as_str())
+    }
+
+    /// Checks if the selector can match more than one channel
+    pub fn is_group(&self) -> bool {
+        self.patterns.len() > 1 || self.patterns.iter().any(|p| p.contains(SELECTOR_WILDCARD))
+    }
+
+    /// Checks if a channel is selected
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel ID
+    #[cfg(any(feature = "control", feature = "redis"))]
+    pub fn matches(&self, channel: &str) -> bool {
+        self.patterns.iter().any(|p| wildcard_match(p, channel))
+    }
+}
+
+impl fmt::Display for ChannelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.patterns.join(","))
+    }
+}
+
+/// Matches a value with a pattern,
+/// where the wildcard matches any sequence of characters
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern
+/// * `value` - The value to match
+#[cfg(any(feature = "control", feature = "redis"))]
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split(SELECTOR_WILDCARD);
+
+    // The first part must be a prefix
+
+    let first = parts.next().unwrap_or("");
+
+    let mut rest = match value.strip_prefix(first) {
+        Some(r) => r,
+        None => {
+            return false;
+        }
+    };
+
+    let parts: Vec<&str> = parts.collect();
+
+    let (last, middle) = match parts.split_last() {
+        Some(p) => p,
+        None => {
+            // No wildcard
+            return rest.is_empty();
+        }
+    };
+
+    // The middle parts are found in order, the last one must be a suffix
+
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => {
+                rest = &rest[i + part.len()..];
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+// Tests
+
+#[cfg(all(test, any(feature = "control", feature = "redis")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_selector_parse() {
+        let single = ChannelSelector::parse("live").unwrap();
+
+        assert!(!single.is_group());
+        assert_eq!(single.single_channel(), Some("live"));
+
+        let pattern = ChannelSelector::parse("event-*").unwrap();
+
+        assert!(pattern.is_group());
+        assert_eq!(pattern.single_channel(), None);
+
+        let list = ChannelSelector::parse("a, b,event-*").unwrap();
+
+        assert!(list.is_group());
+        assert!(list.matches("a"));
+        assert!(list.matches("b"));
+        assert!(list.matches("event-1"));
+        assert!(!list.matches("c"));
+
+        let padded = ChannelSelector::parse(" live ").unwrap();
+
+        assert_eq!(padded.single_channel(), Some("live"));
+        assert!(padded.matches("live"));
+
+        let padded_list = ChannelSelector::parse("live ,x").unwrap();
+
+        assert!(padded_list.matches("live"));
+        assert!(padded_list.matches("x"));
+
+        assert_eq!(ChannelSelector::parse(""), None);
+        assert_eq!(ChannelSelector::parse("  "), None);
+        assert_eq!(ChannelSelector::parse("a,,b"), None);
+        assert_eq!(ChannelSelector::parse("a,"), None);
+    }
+
+    #[test]
+    fn test_channel_selector_kill_target() {
+        assert!(ChannelSelector::parse_kill_target("live", false).is_ok());
+        assert!(ChannelSelector::parse_kill_target("live", true).is_ok());
+        assert!(ChannelSelector::parse_kill_target("event-*", false).is_err());
+        assert!(ChannelSelector::parse_kill_target("event-*", true).is_ok());
+        assert!(ChannelSelector::parse_kill_target("a,b", false).is_err());
+        assert!(ChannelSelector::parse_kill_target("a,b", true).is_ok());
+        assert!(ChannelSelector::parse_kill_target("", true).is_err());
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("live", "live"));
+        assert!(!wildcard_match("live", "live2"));
+        assert!(!wildcard_match("live", "liv"));
+
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("*", "anything"));
+
+        assert!(wildcard_match("event-*", "event-"));
+        assert!(wildcard_match("event-*", "event-123"));
+        assert!(!wildcard_match("event-*", "other-123"));
+
+        assert!(wildcard_match("*-hd", "event-hd"));
+        assert!(!wildcard_match("*-hd", "event-sd"));
+
+        assert!(wildcard_match("event-*-hd", "event-1-hd"));
+        assert!(wildcard_match("event-*-hd", "event--hd"));
+        assert!(!wildcard_match("event-*-hd", "event-hd"));
+
+        assert!(wildcard_match("a*b*c", "aXbYc"));
+        assert!(wildcard_match("a*b*c", "abbc"));
+        assert!(!wildcard_match("a*b*c", "acb"));
+        assert!(wildcard_match("tenant/*", "tenant/live"));
+    }
+}
@@ -51,6 +51,45 @@ pub fn get_env_string(var_name: &str, default_val: &str) -> String {
     }
 }
 
+/// Gets a secret string env var.
+/// The value can also be read from a file, whose path is set
+/// in the variable with the `_FILE` suffix (eg: docker or kubernetes secrets).
+///
+/// # Arguments
+///
+/// * `var_name` - The name of the environment variable
+/// * `default_val` - The default value
+///
+/// # Return value
+///
+/// Returns the value of the variable, the contents of the file
+/// (without the trailing line break) or, if none of them are present,
+/// the value of default_val.
+/// Returns an error message if the file could not be read, or if both variables are set.
+pub fn get_env_secret(var_name: &str, default_val: &str) -> Result<String, String> {
+    let file_var_name = format!("{}_FILE", var_name);
+    let file_path = get_env_string(&file_var_name, "");
+
+    if file_path.is_empty() {
+        return Ok(get_env_string(var_name, default_val));
+    }
+
+    if env::var(var_name).is_ok() {
+        return Err(format!(
+            "{} and {} cannot be set at the same time",
+            var_name, file_var_name
+        ));
+    }
+
+    match std::fs::read_to_string(&file_path) {
+        Ok(contents) => Ok(contents.trim_end_matches(['\r', '\n']).to_string()),
+        Err(e) => Err(format!(
+            "Could not read {} ({}): {}",
+            file_var_name, file_path, e
+        )),
+    }
+}
+
 /// Gets u32 env var
 ///
 /// # Arguments
@@ -77,3 +116,72 @@ pub fn get_env_u32(var_name: &str, default_val: u32) -> u32 {
         Err(_) => default_val,
     }
 }
+
+/// Gets a comma-separated list env var
+///
+/// # Arguments
+///
+/// * `var_name` - The name of the environment variable
+/// * `default_val` - The default value
+///
+/// # Return value
+///
+/// Returns the trimmed non-empty items of the variable or,
+/// if not present or empty, the items of default_val
+pub fn get_env_string_list(var_name: &str, default_val: &str) -> Vec<String> {
+    let items: Vec<String> = get_env_string(var_name, default_val)
+        .split(',')
+        .map(|i| i.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .collect();
+
+    if items.is_empty() {
+        default_val
+            .split(',')
+            .map(|i| i.trim().to_string())
+            .filter(|i| !i.is_empty())
+            .collect()
+    } else {
+        items
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_env_secret() {
+        let path = env::temp_dir().join(format!("rtmp-server-secret-test-{}", std::process::id()));
+        std::fs::write(&path, "file-secret\n").unwrap();
+
+        assert_eq!(
+            get_env_secret("TEST_ENV_SECRET_NOT_SET", "default").unwrap(),
+            "default"
+        );
+
+        env::set_var("TEST_ENV_SECRET_FILE", path.to_str().unwrap());
+
+        assert_eq!(
+            get_env_secret("TEST_ENV_SECRET", "").unwrap(),
+            "file-secret"
+        );
+
+        env::set_var("TEST_ENV_SECRET", "env-secret");
+
+        assert!(get_env_secret("TEST_ENV_SECRET", "").is_err());
+
+        env::set_var(
+            "TEST_ENV_SECRET_FILE",
+            path.with_extension("missing").to_str().unwrap(),
+        );
+        env::remove_var("TEST_ENV_SECRET");
+
+        assert!(get_env_secret("TEST_ENV_SECRET", "").is_err());
+
+        env::remove_var("TEST_ENV_SECRET_FILE");
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,10 +1,19 @@
 // ID validation
 
-use crate::utils::{get_env_bool, get_env_u32};
+use regex::Regex;
+
+use crate::{
+    log::Logger,
+    log_error,
+    utils::{get_env_bool, get_env_string, get_env_u32},
+};
 
 /// Default ID length limit
 pub const DEFAULT_MAX_ID_LENGTH: usize = 128;
 
+/// Characters never allowed in IDs, since they are used as separators
+const ID_FORBIDDEN_CHARACTERS: &[char] = &['>', '|', '\n'];
+
 /// ID validation configuration
 #[derive(Clone)]
 pub struct IdValidationConfig {
@@ -16,24 +25,71 @@ pub struct IdValidationConfig {
 
     /// TRue to allow special characters in IDs
     allow_special_characters: bool,
+
+    /// Custom set of allowed characters (matches the full ID)
+    allowed_chars: Option<Regex>,
 }
 
 impl IdValidationConfig {
     /// Loads configuration for environment variables
-    pub fn load_from_env() -> IdValidationConfig {
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - The logger
+    pub fn load_from_env(logger: &Logger) -> Result<IdValidationConfig, ()> {
         let max_len = get_env_u32("ID_MAX_LENGTH", DEFAULT_MAX_ID_LENGTH as u32) as usize;
 
         let allow_empty_string = get_env_bool("ID_ALLOW_EMPTY", false);
         let allow_special_characters = get_env_bool("ID_ALLOW_SPECIAL_CHARACTERS", false);
 
-        IdValidationConfig {
+        let allowed_chars_regex = get_env_string("ID_ALLOWED_CHARS_REGEX", "");
+
+        let allowed_chars = if allowed_chars_regex.is_empty() {
+            None
+        } else {
+            match make_allowed_chars_regex(&allowed_chars_regex) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    log_error!(
+                        logger,
+                        format!(
+                            "ID_ALLOWED_CHARS_REGEX is not a valid regular expression: {}",
+                            e
+                        )
+                    );
+                    return Err(());
+                }
+            }
+        };
+
+        Ok(IdValidationConfig {
             max_len,
             allow_empty_string,
             allow_special_characters,
-        }
+            allowed_chars,
+        })
     }
 }
 
+/// Makes the regular expression to match a full ID
+/// from the expression matching a single allowed character
+///
+/// # Arguments
+///
+/// * `allowed_chars` - Regular expression matching an allowed character (eg: `[A-Za-z0-9_=+-]`)
+fn make_allowed_chars_regex(allowed_chars: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})*$", allowed_chars))
+}
+
+/// Checks if a character is allowed by the default policy
+///
+/// # Arguments
+///
+/// * `c` - The character
+fn is_default_allowed_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
 /// Validates ID as string
 ///
 /// # Arguments
@@ -43,42 +99,42 @@ impl IdValidationConfig {
 ///
 /// # Return value
 ///
-/// Returns true if the ID is valid, false otherwise
-pub fn validate_id_string(id: &str, config: &IdValidationConfig) -> bool {
+/// Returns an error with the reason if the ID is not valid
+pub fn validate_id_string(id: &str, config: &IdValidationConfig) -> Result<(), String> {
     if id.is_empty() && !config.allow_empty_string {
-        return false;
+        return Err("must not be empty".to_string());
     }
 
     if id.len() > config.max_len {
-        return false;
+        return Err(format!("longer than {} characters", config.max_len));
     }
 
-    if config.allow_special_characters {
-        for char in id.chars() {
-            match char {
-                '>' | '|' | '\n' => {
-                    return false;
-                }
-                _ => {}
-            }
-        }
+    // Positions are reported as character indexes (not byte offsets),
+    // since the allowed characters may include non-ASCII ones
 
-        return true;
+    if let Some(position) = id
+        .chars()
+        .position(|c| ID_FORBIDDEN_CHARACTERS.contains(&c))
+    {
+        return Err(format!("forbidden character at position {}", position));
     }
 
-    for char in id.chars() {
-        match char {
-            '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' => {}
-            'A' | 'B' | 'C' | 'D' | 'E' | 'F' | 'G' | 'H' | 'I' | 'J' | 'K' | 'L' | 'M' | 'N'
-            | 'O' | 'P' | 'Q' | 'R' | 'S' | 'T' | 'U' | 'V' | 'W' | 'X' | 'Y' | 'Z' => {}
-            'a' | 'b' | 'c' | 'd' | 'e' | 'f' | 'g' | 'h' | 'i' | 'j' | 'k' | 'l' | 'm' | 'n'
-            | 'o' | 'p' | 'q' | 'r' | 's' | 't' | 'u' | 'v' | 'w' | 'x' | 'y' | 'z' => {}
-            '-' | '_' => {}
-            _ => return false,
+    if let Some(allowed_chars) = &config.allowed_chars {
+        if !allowed_chars.is_match(id) {
+            return Err("contains characters not allowed".to_string());
         }
+
+        return Ok(());
+    }
+
+    if config.allow_special_characters {
+        return Ok(());
     }
 
-    true
+    match id.chars().position(|c| !is_default_allowed_char(c)) {
+        Some(position) => Err(format!("forbidden character at position {}", position)),
+        None => Ok(()),
+    }
 }
 
 // Tests
@@ -95,58 +151,76 @@ mod tests {
             max_len: 32,
             allow_empty_string: false,
             allow_special_characters: false,
+            allowed_chars: None,
         };
 
-        assert!(!validate_id_string("", &config));
-        assert!(!validate_id_string(
-            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-            &config
-        ));
-        assert!(!validate_id_string("a%", &config));
+        assert!(validate_id_string("", &config).is_err());
+        assert!(validate_id_string("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &config).is_err());
+        assert_eq!(
+            validate_id_string("a%", &config),
+            Err("forbidden character at position 1".to_string())
+        );
 
-        assert!(validate_id_string("a", &config));
-        assert!(validate_id_string(
-            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-            &config
-        ));
-        assert!(validate_id_string("abc-DEF-1234567890_", &config));
+        assert!(validate_id_string("a", &config).is_ok());
+        assert!(validate_id_string("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &config).is_ok());
+        assert!(validate_id_string("abc-DEF-1234567890_", &config).is_ok());
 
         // Allow empty
 
         config.allow_empty_string = true;
 
-        assert!(validate_id_string("", &config));
-        assert!(!validate_id_string("a%", &config));
+        assert!(validate_id_string("", &config).is_ok());
+        assert!(validate_id_string("a%", &config).is_err());
 
-        assert!(validate_id_string("a", &config));
-        assert!(validate_id_string(
-            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-            &config
-        ));
-        assert!(validate_id_string("abc-DEF-1234567890_", &config));
+        assert!(validate_id_string("a", &config).is_ok());
+        assert!(validate_id_string("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &config).is_ok());
+        assert!(validate_id_string("abc-DEF-1234567890_", &config).is_ok());
 
-        assert!(!validate_id_string(
-            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-            &config
-        ));
+        assert_eq!(
+            validate_id_string("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &config),
+            Err("longer than 32 characters".to_string())
+        );
 
         // Allow special characters
 
         config.allow_special_characters = true;
 
-        assert!(validate_id_string("", &config));
-        assert!(validate_id_string("a%", &config));
+        assert!(validate_id_string("", &config).is_ok());
+        assert!(validate_id_string("a%", &config).is_ok());
+        assert!(validate_id_string("a|b", &config).is_err());
+        assert_eq!(
+            validate_id_string("éñ|b", &config),
+            Err("forbidden character at position 2".to_string())
+        );
+
+        assert!(validate_id_string("a", &config).is_ok());
+        assert!(validate_id_string("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &config).is_ok());
+        assert!(validate_id_string("abc-DEF-1234567890_", &config).is_ok());
+
+        assert!(validate_id_string("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &config).is_err());
+
+        // Custom allowed characters
+
+        config.allow_special_characters = false;
+        config.allowed_chars = Some(make_allowed_chars_regex("[A-Za-z0-9_=+-]").unwrap());
+
+        assert!(validate_id_string("abc+DEF=", &config).is_ok());
+        assert!(validate_id_string("", &config).is_ok());
+        assert_eq!(
+            validate_id_string("a%", &config),
+            Err("contains characters not allowed".to_string())
+        );
+        assert!(validate_id_string("a|b", &config).is_err());
+
+        // Non-ASCII allowed characters
 
-        assert!(validate_id_string("a", &config));
-        assert!(validate_id_string(
-            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-            &config
-        ));
-        assert!(validate_id_string("abc-DEF-1234567890_", &config));
+        config.allowed_chars = Some(make_allowed_chars_regex(r"\p{L}").unwrap());
 
-        assert!(!validate_id_string(
-            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-            &config
-        ));
+        assert!(validate_id_string("canal-ñandú", &config).is_err());
+        assert!(validate_id_string("ñandú", &config).is_ok());
+        assert_eq!(
+            validate_id_string("ñandú>x", &config),
+            Err("forbidden character at position 5".to_string())
+        );
     }
 }
@@ -0,0 +1,49 @@
+// IP address utilities
+
+use std::net::IpAddr;
+
+/// Normalizes an IP address, so the same client is always
+/// represented the same way, regardless of the listener it connected to.
+/// IPv4-mapped IPv6 addresses (::ffff:a.b.c.d) are converted to IPv4.
+///
+/// # Arguments
+///
+/// * `ip` - The IP address
+///
+/// # Return value
+///
+/// Returns the normalized IP address
+pub fn normalize_ip_address(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ipv6_addr) => match ipv6_addr.to_ipv4_mapped() {
+            Some(ipv4_addr) => IpAddr::V4(ipv4_addr),
+            None => ip,
+        },
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_ip_address() {
+        assert_eq!(
+            normalize_ip_address(IpAddr::from_str("::ffff:10.0.0.1").unwrap()),
+            IpAddr::from_str("10.0.0.1").unwrap()
+        );
+        assert_eq!(
+            normalize_ip_address(IpAddr::from_str("10.0.0.1").unwrap()),
+            IpAddr::from_str("10.0.0.1").unwrap()
+        );
+        assert_eq!(
+            normalize_ip_address(IpAddr::from_str("::1").unwrap()),
+            IpAddr::from_str("::1").unwrap()
+        );
+    }
+}
@@ -7,6 +7,8 @@ use std::{
 
 use ipnet::{Ipv4Net, Ipv6Net};
 
+use super::normalize_ip_address;
+
 // IP range configuration
 // Represents a list of IP ranges
 #[derive(Clone)]
@@ -85,9 +87,14 @@ impl IpRangeConfig {
                                     let res_ip_v6 = Ipv6Addr::from_str(range_str);
 
                                     match res_ip_v6 {
-                                        Ok(ip_v6) => {
-                                            ips_v6.push(ip_v6);
-                                        }
+                                        Ok(ip_v6) => match ip_v6.to_ipv4_mapped() {
+                                            Some(ip_v4) => {
+                                                ips_v4.push(ip_v4);
+                                            }
+                                            None => {
+                                                ips_v6.push(ip_v6);
+                                            }
+                                        },
                                         Err(_) => {
                                             return Err(range_str.to_string());
                                         }
@@ -163,14 +170,6 @@ impl IpRangeConfig {
             }
         }
 
-        let ipv4_addr_opt = ipv6_addr.to_ipv4();
-
-        if let Some(ipv4_addr) = ipv4_addr_opt {
-            if self.check_ip_v4(&ipv4_addr) {
-                return true;
-            }
-        }
-
         false
     }
 
@@ -188,14 +187,14 @@ impl IpRangeConfig {
             return true;
         }
 
-        match ip {
+        match normalize_ip_address(*ip) {
             IpAddr::V4(ipv4_addr) => {
-                if self.check_ip_v4(ipv4_addr) {
+                if self.check_ip_v4(&ipv4_addr) {
                     return true;
                 }
             }
             IpAddr::V6(ipv6_addr) => {
-                if self.check_ip_v6(ipv6_addr) {
+                if self.check_ip_v6(&ipv6_addr) {
                     return true;
                 }
             }
@@ -256,5 +255,15 @@ mod tests {
         assert!(range_5.contains_ip(&ip_v4_2));
         assert!(!range_5.contains_ip(&ip_v6_1));
         assert!(range_5.contains_ip(&ip_v6_2));
+
+        let ip_v4_mapped = IpAddr::V6(Ipv6Addr::from_str("::ffff:10.0.0.1").unwrap());
+
+        assert!(range_5.contains_ip(&ip_v4_mapped));
+        assert!(!range_4.contains_ip(&IpAddr::V6(Ipv6Addr::from_str("::a00:1").unwrap())));
+
+        let range_6 = IpRangeConfig::new_from_string("::ffff:10.0.0.1").unwrap();
+
+        assert!(range_6.contains_ip(&ip_v4_2));
+        assert!(range_6.contains_ip(&ip_v4_mapped));
     }
 }
@@ -1,13 +1,29 @@
 // Utils module
 
+mod certificate_identity;
+mod channel_alias;
+mod channel_selector;
 mod env;
 mod id_validation;
+mod ip_address;
 mod ip_range_check;
 mod query_string;
+mod stream_key_hash;
 mod string_compare_secure;
+mod uuid;
+#[cfg(feature = "tls")]
+mod x509;
 
+pub use certificate_identity::*;
+pub use channel_alias::*;
+pub use channel_selector::*;
 pub use env::*;
 pub use id_validation::*;
+pub use ip_address::*;
 pub use ip_range_check::*;
 pub use query_string::*;
+pub use stream_key_hash::*;
 pub use string_compare_secure::*;
+pub use uuid::*;
+#[cfg(feature = "tls")]
+pub use x509::*;
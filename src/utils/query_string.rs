@@ -2,8 +2,55 @@
 
 use std::collections::HashMap;
 
-/// Parses query string (does not parse parameters)
-/// Used in order to parse RTMP play parameters
+/// Splits a stream name into the stream key and the query string
+///
+/// # Arguments
+///
+/// * `stream_name` - The stream name (eg: `key?token=abc`)
+///
+/// # Return value
+///
+/// A tuple with the key and the query string (empty if not present)
+pub fn split_stream_name(stream_name: &str) -> (&str, &str) {
+    match stream_name.split_once("?") {
+        Some((key, query_string)) => (key, query_string),
+        None => (stream_name, ""),
+    }
+}
+
+/// Decodes the percent-encoded characters of a query string component.
+/// The `+` character is kept, since tokens may contain it.
+///
+/// # Arguments
+///
+/// * `component` - The key or the value
+pub fn decode_query_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(b) = bytes
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Parses query string
+/// Used in order to parse RTMP play and publish parameters
 ///
 /// # Arguments
 ///
@@ -19,10 +66,10 @@ pub fn parse_query_string_simple(query_string: &str) -> HashMap<String, String>
         let parts = query_string.split("&");
 
         for part in parts {
-            let key_val: Vec<&str> = part.split("=").collect();
-
-            if key_val.len() == 2 {
-                result.insert(key_val[0].to_string(), key_val[1].to_string());
+            if let Some((key, val)) = part.split_once("=") {
+                if !key.is_empty() {
+                    result.insert(decode_query_component(key), decode_query_component(val));
+                }
             }
         }
     }
@@ -52,5 +99,20 @@ mod tests {
         assert!(!params_3.is_empty());
         assert_eq!(params_3.get("cache").unwrap(), "clear");
         assert_eq!(params_3.get("opt").unwrap(), "1");
+
+        let params_4 = parse_query_string_simple("token=a+b/c==&user=J%C3%BCrgen%20K&flag");
+
+        assert_eq!(params_4.len(), 2);
+        assert_eq!(params_4.get("token").unwrap(), "a+b/c==");
+        assert_eq!(params_4.get("user").unwrap(), "Jürgen K");
+    }
+
+    #[test]
+    fn test_split_stream_name() {
+        assert_eq!(split_stream_name("key"), ("key", ""));
+        assert_eq!(split_stream_name("key?token=1"), ("key", "token=1"));
+        assert_eq!(split_stream_name("key?a=1?b"), ("key", "a=1?b"));
+        assert_eq!(decode_query_component("100%"), "100%");
+        assert_eq!(decode_query_component("%zz%41"), "%zzA");
     }
 }
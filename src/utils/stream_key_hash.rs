@@ -0,0 +1,97 @@
+// Salted hashes of the stream keys
+
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use sha2::Sha256;
+
+/// Text logged instead of the stream keys
+pub const REDACTED_STREAM_KEY: &str = "<redacted>";
+
+/// Salt of the stream key hashes, generated randomly for each server instance
+static STREAM_KEY_SALT: OnceLock<[u8; 32]> = OnceLock::new();
+
+/// Gets the salt of the stream key hashes, generating it if needed
+fn get_stream_key_salt() -> &'static [u8; 32] {
+    STREAM_KEY_SALT.get_or_init(|| {
+        let mut salt = [0u8; 32];
+        StdRng::from_os_rng().fill_bytes(&mut salt);
+        salt
+    })
+}
+
+/// Salted hash of a stream key.
+/// Kept in memory instead of the key, so the key
+/// is not exposed if the memory of the server leaks.
+#[derive(Clone)]
+pub struct StreamKeyHash {
+    /// HMAC-SHA256 of the key, with the salt of the server instance
+    hash: [u8; 32],
+}
+
+impl StreamKeyHash {
+    /// Computes the hash of a stream key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The stream key
+    pub fn new(key: &str) -> StreamKeyHash {
+        let mut mac = Hmac::<Sha256>::new_from_slice(get_stream_key_salt())
+            .expect("HMAC accepts keys of any size");
+        mac.update(key.as_bytes());
+
+        StreamKeyHash {
+            hash: mac.finalize().into_bytes().into(),
+        }
+    }
+
+    /// Checks if a stream key matches the hash.
+    /// Ensures timing attacks are not viable.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The stream key
+    pub fn matches(&self, key: &str) -> bool {
+        self.matches_hash(&StreamKeyHash::new(key))
+    }
+
+    /// Checks if two hashes are equal, in constant time
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other hash
+    pub fn matches_hash(&self, other: &StreamKeyHash) -> bool {
+        self.hash
+            .iter()
+            .zip(other.hash.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_key_hash() {
+        let hash = StreamKeyHash::new("aaa123");
+
+        assert!(hash.matches("aaa123"));
+        assert!(!hash.matches("aaa1234"));
+        assert!(!hash.matches(""));
+
+        assert!(hash.matches_hash(&StreamKeyHash::new("aaa123")));
+        assert!(!hash.matches_hash(&StreamKeyHash::new("baa123")));
+
+        // Salted, so it is not the plain SHA-256 of the key
+
+        assert_ne!(
+            hash.hash[..],
+            <Sha256 as sha2::Digest>::digest("aaa123")[..]
+        );
+    }
+}
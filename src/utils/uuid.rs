@@ -0,0 +1,46 @@
+// UUID generation
+
+/// Generates a random UUID (version 4)
+///
+/// # Return value
+///
+/// Returns the UUID in its hyphenated lowercase form
+pub fn generate_uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // Version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // RFC 4122 variant
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_uuid_v4() {
+        let uuid = generate_uuid_v4();
+
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.chars().nth(14), Some('4'));
+        assert!(matches!(uuid.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+        assert_eq!(
+            uuid.split('-').map(|p| p.len()).collect::<Vec<usize>>(),
+            vec![8, 4, 4, 4, 12]
+        );
+
+        assert_ne!(uuid, generate_uuid_v4());
+    }
+}
@@ -0,0 +1,338 @@
+// X.509 certificate utilities
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::CertificateIdentity;
+
+/// DER tag: SEQUENCE
+const DER_TAG_SEQUENCE: u8 = 0x30;
+
+/// DER tag: SET
+const DER_TAG_SET: u8 = 0x31;
+
+/// DER tag: OBJECT IDENTIFIER
+const DER_TAG_OID: u8 = 0x06;
+
+/// DER tag: OCTET STRING
+const DER_TAG_OCTET_STRING: u8 = 0x04;
+
+/// DER tag: [0] (version of the certificate)
+const DER_TAG_VERSION: u8 = 0xa0;
+
+/// DER tag: [3] (extensions of the certificate)
+const DER_TAG_EXTENSIONS: u8 = 0xa3;
+
+/// DER tags of string types
+const DER_TAG_UTF8_STRING: u8 = 0x0c;
+const DER_TAG_PRINTABLE_STRING: u8 = 0x13;
+const DER_TAG_TELETEX_STRING: u8 = 0x14;
+const DER_TAG_IA5_STRING: u8 = 0x16;
+const DER_TAG_BMP_STRING: u8 = 0x1e;
+
+/// DER tags of the general names (subject alternative names)
+const DER_TAG_SAN_EMAIL: u8 = 0x81;
+const DER_TAG_SAN_DNS: u8 = 0x82;
+const DER_TAG_SAN_URI: u8 = 0x86;
+const DER_TAG_SAN_IP: u8 = 0x87;
+
+/// OID 2.5.4.3 (Common name)
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// OID 2.5.29.17 (Subject alternative name)
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// Reads a DER element (tag, length, value)
+///
+/// # Arguments
+///
+/// * `data` - The DER data
+///
+/// # Return value
+///
+/// Returns a tuple with 3 values:
+///  1. The tag
+///  2. The content of the element
+///  3. The remaining data after the element
+///
+/// Returns None if the data is not valid DER
+fn read_der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+
+    if tag & 0x1f == 0x1f {
+        return None; // High tag numbers are not used by certificates
+    }
+
+    let length_byte = *data.get(1)? as usize;
+
+    let (length, header_size) = if length_byte < 0x80 {
+        (length_byte, 2)
+    } else {
+        let length_size = length_byte & 0x7f;
+
+        if length_size == 0 || length_size > 4 {
+            return None;
+        }
+
+        let mut length: usize = 0;
+
+        for b in data.get(2..2 + length_size)? {
+            length = (length << 8) | (*b as usize);
+        }
+
+        (length, 2 + length_size)
+    };
+
+    let content = data.get(header_size..header_size.checked_add(length)?)?;
+    let rest = &data[header_size + length..];
+
+    Some((tag, content, rest))
+}
+
+/// Reads all the DER elements of a constructed element content
+fn read_der_elements(mut data: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut elements = Vec::new();
+
+    while !data.is_empty() {
+        let (tag, content, rest) = read_der_element(data)?;
+        elements.push((tag, content));
+        data = rest;
+    }
+
+    Some(elements)
+}
+
+/// Decodes a DER string, removing control characters
+fn decode_der_string(tag: u8, content: &[u8]) -> Option<String> {
+    let s = match tag {
+        DER_TAG_UTF8_STRING
+        | DER_TAG_PRINTABLE_STRING
+        | DER_TAG_TELETEX_STRING
+        | DER_TAG_IA5_STRING
+        | DER_TAG_SAN_EMAIL
+        | DER_TAG_SAN_DNS
+        | DER_TAG_SAN_URI => String::from_utf8_lossy(content).to_string(),
+        DER_TAG_BMP_STRING => {
+            let units: Vec<u16> = content
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => {
+            return None;
+        }
+    };
+
+    let s: String = s.chars().filter(|c| !c.is_control()).collect();
+
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+/// Finds the common name in a distinguished name
+fn find_common_name(name: &[u8]) -> Option<String> {
+    for (set_tag, set) in read_der_elements(name)? {
+        if set_tag != DER_TAG_SET {
+            continue;
+        }
+
+        for (attr_tag, attr) in read_der_elements(set)? {
+            if attr_tag != DER_TAG_SEQUENCE {
+                continue;
+            }
+
+            let attr_elements = read_der_elements(attr)?;
+
+            if attr_elements.len() == 2 && attr_elements[0] == (DER_TAG_OID, OID_COMMON_NAME) {
+                return decode_der_string(attr_elements[1].0, attr_elements[1].1);
+            }
+        }
+    }
+
+    None
+}
+
+/// Finds the subject alternative names in the extensions of a certificate
+fn find_subject_alt_names(extensions: &[u8]) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+
+    let (_, extensions_seq, _) = read_der_element(extensions)?;
+
+    for (ext_tag, ext) in read_der_elements(extensions_seq)? {
+        if ext_tag != DER_TAG_SEQUENCE {
+            continue;
+        }
+
+        let ext_elements = read_der_elements(ext)?;
+
+        if ext_elements.first() != Some(&(DER_TAG_OID, OID_SUBJECT_ALT_NAME)) {
+            continue;
+        }
+
+        // The value is the last element (after the optional critical flag)
+        let (value_tag, value) = *ext_elements.last()?;
+
+        if value_tag != DER_TAG_OCTET_STRING {
+            continue;
+        }
+
+        let (_, general_names, _) = read_der_element(value)?;
+
+        for (name_tag, name) in read_der_elements(general_names)? {
+            if name_tag == DER_TAG_SAN_IP {
+                let ip = match name.len() {
+                    4 => IpAddr::V4(Ipv4Addr::new(name[0], name[1], name[2], name[3])),
+                    16 => {
+                        let mut bytes = [0u8; 16];
+                        bytes.copy_from_slice(name);
+                        IpAddr::V6(Ipv6Addr::from(bytes))
+                    }
+                    _ => {
+                        continue;
+                    }
+                };
+
+                names.push(ip.to_string());
+            } else if let Some(s) = decode_der_string(name_tag, name) {
+                names.push(s);
+            }
+        }
+    }
+
+    Some(names)
+}
+
+/// Parses the identity of the subject of a certificate
+///
+/// # Arguments
+///
+/// * `der` - The certificate (DER encoded)
+///
+/// # Return value
+///
+/// Returns the identity, or None if the certificate could not be parsed
+pub fn parse_certificate_identity(der: &[u8]) -> Option<CertificateIdentity> {
+    let (cert_tag, cert, _) = read_der_element(der)?;
+
+    if cert_tag != DER_TAG_SEQUENCE {
+        return None;
+    }
+
+    let (tbs_tag, tbs, _) = read_der_element(cert)?;
+
+    if tbs_tag != DER_TAG_SEQUENCE {
+        return None;
+    }
+
+    let mut tbs_elements = read_der_elements(tbs)?;
+
+    if tbs_elements.first()?.0 == DER_TAG_VERSION {
+        tbs_elements.remove(0);
+    }
+
+    // serialNumber, signature, issuer, validity, subject
+    let (subject_tag, subject) = *tbs_elements.get(4)?;
+
+    if subject_tag != DER_TAG_SEQUENCE {
+        return None;
+    }
+
+    let subject_alt_names = match tbs_elements.iter().find(|e| e.0 == DER_TAG_EXTENSIONS) {
+        Some((_, extensions)) => find_subject_alt_names(extensions)?,
+        None => Vec::new(),
+    };
+
+    Some(CertificateIdentity {
+        common_name: find_common_name(subject),
+        subject_alt_names,
+    })
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut res = vec![tag];
+
+        if content.len() < 0x80 {
+            res.push(content.len() as u8);
+        } else {
+            res.push(0x82);
+            res.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        }
+
+        res.extend_from_slice(content);
+        res
+    }
+
+    #[test]
+    fn test_parse_certificate_identity() {
+        let cn_attr = der(
+            DER_TAG_SEQUENCE,
+            &[
+                der(DER_TAG_OID, OID_COMMON_NAME),
+                der(DER_TAG_UTF8_STRING, b"encoder-01"),
+            ]
+            .concat(),
+        );
+        let subject = der(DER_TAG_SEQUENCE, &der(DER_TAG_SET, &cn_attr));
+
+        let general_names = der(
+            DER_TAG_SEQUENCE,
+            &[
+                der(DER_TAG_SAN_DNS, b"encoder-01.example.com"),
+                der(DER_TAG_SAN_IP, &[10, 0, 0, 1]),
+            ]
+            .concat(),
+        );
+        let san_ext = der(
+            DER_TAG_SEQUENCE,
+            &[
+                der(DER_TAG_OID, OID_SUBJECT_ALT_NAME),
+                der(DER_TAG_OCTET_STRING, &general_names),
+            ]
+            .concat(),
+        );
+        let extensions = der(DER_TAG_EXTENSIONS, &der(DER_TAG_SEQUENCE, &san_ext));
+
+        let placeholder = der(DER_TAG_SEQUENCE, &[]);
+
+        let tbs = der(
+            DER_TAG_SEQUENCE,
+            &[
+                der(DER_TAG_VERSION, &der(0x02, &[2])),
+                der(0x02, &[1]),
+                placeholder.clone(),
+                placeholder.clone(),
+                placeholder.clone(),
+                subject,
+                placeholder.clone(),
+                extensions,
+            ]
+            .concat(),
+        );
+
+        let cert = der(DER_TAG_SEQUENCE, &[tbs, placeholder].concat());
+
+        let identity = parse_certificate_identity(&cert).unwrap();
+
+        assert_eq!(identity.common_name, Some("encoder-01".to_string()));
+        assert_eq!(
+            identity.subject_alt_names,
+            vec!["encoder-01.example.com".to_string(), "10.0.0.1".to_string()]
+        );
+        assert_eq!(
+            identity.get_subject_alt_names_string(),
+            Some("encoder-01.example.com,10.0.0.1".to_string())
+        );
+
+        assert_eq!(parse_certificate_identity(&[]), None);
+        assert_eq!(parse_certificate_identity(&cert[..cert.len() - 1]), None);
+    }
+}
@@ -0,0 +1,89 @@
+// Conformance tests
+// Runs the test client against a server instance
+
+use std::{
+    net::{TcpListener, TcpStream},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time to wait for the server to start listening
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Server process, killed on drop
+struct ServerProcess {
+    child: Child,
+}
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        _ = self.child.kill();
+        _ = self.child.wait();
+    }
+}
+
+/// Finds a free TCP port
+fn find_free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+        .expect("Could not find a free port")
+}
+
+/// Starts a server instance, waiting for it to listen
+///
+/// # Arguments
+///
+/// * `port` - Port to listen
+fn start_server(port: u16) -> ServerProcess {
+    let child = Command::new(env!("CARGO_BIN_EXE_rtmp-server"))
+        .current_dir(std::env::temp_dir())
+        .env("RTMP_PORT", port.to_string())
+        .env("BIND_ADDRESS", "127.0.0.1")
+        .env("SSL_CERT", "")
+        .env("SSL_CERTS", "")
+        .env("CALLBACK_URL", "")
+        .env("JWT_SECRET", "")
+        .env("CONTROL_USE", "NO")
+        .env("REDIS_USE", "NO")
+        .env("TENANTS", "")
+        .env("RTMP_PLAY_WHITELIST", "*")
+        .env("CONCURRENT_LIMIT_WHITELIST", "*")
+        .env("LOG_INFO", "NO")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Could not start the server");
+
+    let server = ServerProcess { child };
+
+    let start = Instant::now();
+
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        if start.elapsed() > SERVER_START_TIMEOUT {
+            panic!("The server did not start listening on port {}", port);
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    server
+}
+
+#[test]
+fn test_conformance_suite() {
+    let port = find_free_port();
+
+    let _server = start_server(port);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rtmp-test-client"))
+        .args(["--port", &port.to_string()])
+        .output()
+        .expect("Could not run the test client");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    print!("{}", stdout);
+
+    assert!(output.status.success(), "Conformance suite failed");
+}